@@ -0,0 +1,50 @@
+#![no_main]
+
+use alkahest::*;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&selector, data)) = data.split_first() else {
+        return;
+    };
+
+    match selector % 32 {
+        0 => fuzz_deserialize::<(), ()>(data),
+        1 => fuzz_deserialize::<bool, bool>(data),
+        2 => fuzz_deserialize::<u8, u8>(data),
+        3 => fuzz_deserialize::<u16, u16>(data),
+        4 => fuzz_deserialize::<u32, u32>(data),
+        5 => fuzz_deserialize::<u64, u64>(data),
+        6 => fuzz_deserialize::<u128, u128>(data),
+        7 => fuzz_deserialize::<i64, i64>(data),
+        8 => fuzz_deserialize::<f64, f64>(data),
+        9 => fuzz_deserialize::<i8, i8>(data),
+        10 => fuzz_deserialize::<usize, usize>(data),
+        11 => fuzz_deserialize::<Vlq, u64>(data),
+        12 => fuzz_deserialize::<Bytes, &[u8]>(data),
+        13 => fuzz_deserialize::<str, &str>(data),
+        14 => fuzz_deserialize::<[u8], Vec<u8>>(data),
+        15 => fuzz_deserialize::<[u32], Vec<u32>>(data),
+        16 => fuzz_deserialize::<[()], Vec<()>>(data),
+        17 => fuzz_deserialize::<[Bytes], Vec<&[u8]>>(data),
+        18 => fuzz_deserialize::<[Vlq], Vec<u32>>(data),
+        19 => fuzz_deserialize::<Vec<u16>, Vec<u16>>(data),
+        20 => fuzz_deserialize::<String, String>(data),
+        21 => fuzz_deserialize::<Vec<String>, Vec<String>>(data),
+        22 => fuzz_deserialize::<Vec<Vec<u8>>, Vec<Vec<u8>>>(data),
+        23 => fuzz_deserialize::<Ref<str>, &str>(data),
+        24 => fuzz_deserialize::<InlineRef<str>, &str>(data),
+        25 => fuzz_deserialize::<Option<u32>, Option<u32>>(data),
+        26 => fuzz_deserialize::<Option<Ref<str>>, Option<&str>>(data),
+        27 => fuzz_deserialize::<[u16; 4], [u16; 4]>(data),
+        28 => fuzz_deserialize::<(u8, Bytes, Vec<u32>), (u8, &[u8], Vec<u32>)>(data),
+        29 => fuzz_deserialize::<Indexed<Bytes>, Vec<&[u8]>>(data),
+        30 => fuzz_deserialize::<[As<str>], Vec<&str>>(data),
+        _ => {
+            if let Ok(lazy) = deserialize::<[String], Lazy<[String]>>(data) {
+                let _ = lazy.len();
+                lazy.iter::<&str>().for_each(drop);
+            }
+        }
+    }
+});
@@ -0,0 +1,48 @@
+#![no_main]
+
+use alkahest::*;
+use arbitrary::{Arbitrary, Unstructured};
+use libfuzzer_sys::fuzz_target;
+
+#[derive(Clone, Debug, PartialEq, Arbitrary)]
+#[alkahest(Formula, Serialize, Deserialize)]
+struct Flat {
+    a: u8,
+    b: u32,
+    c: bool,
+    d: [u16; 3],
+}
+
+#[derive(Clone, Debug, PartialEq, Arbitrary)]
+#[alkahest(Formula, Serialize, Deserialize)]
+struct Nested {
+    name: String,
+    flat: Flat,
+    values: Vec<u64>,
+    words: Vec<String>,
+    maybe: Option<Flat>,
+}
+
+#[derive(Clone, Debug, PartialEq, Arbitrary)]
+#[alkahest(Formula, Serialize, Deserialize)]
+enum Message {
+    Empty,
+    Tuple(u32, String),
+    Struct { nested: Nested, tail: Vec<u8> },
+}
+
+fuzz_target!(|data: &[u8]| {
+    let mut u = Unstructured::new(data);
+    let Ok(selector) = u.arbitrary::<u8>() else {
+        return;
+    };
+
+    let _ = match selector % 6 {
+        0 => fuzz_roundtrip::<Flat, Flat>(&mut u),
+        1 => fuzz_roundtrip::<Nested, Nested>(&mut u),
+        2 => fuzz_roundtrip::<Message, Message>(&mut u),
+        3 => fuzz_roundtrip::<Vec<Message>, Vec<Message>>(&mut u),
+        4 => fuzz_roundtrip::<(u8, String, Vec<Vec<u8>>), (u8, String, Vec<Vec<u8>>)>(&mut u),
+        _ => fuzz_roundtrip::<[Vlq], Vec<u64>>(&mut u),
+    };
+});
@@ -29,13 +29,27 @@ proc_easy::easy_parse! {
 proc_easy::easy_parse! {
     struct DeserializeParams {
         lt_token: syn::Token![<],
-        lifetime: syn::Lifetime,
-        comma_token: syn::Token![,],
+        lifetime: DeserializeLifetime,
         formula: syn::Path,
         gt_token: syn::Token![>],
     }
 }
 
+/// Optional deserializer lifetime followed by comma.
+/// When omitted, lifetime is generated and outlives all lifetimes of the type.
+struct DeserializeLifetime(Option<syn::Lifetime>);
+
+impl syn::parse::Parse for DeserializeLifetime {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        if !input.peek(syn::Lifetime) {
+            return Ok(DeserializeLifetime(None));
+        }
+        let lifetime = input.parse()?;
+        input.parse::<syn::Token![,]>()?;
+        Ok(DeserializeLifetime(Some(lifetime)))
+    }
+}
+
 proc_easy::easy_parse! {
     enum ImplTrait {
         Formula(Formula),
@@ -186,7 +200,7 @@ impl Args {
                     let (formula, lifetime) = match params {
                         proc_easy::EasyMaybe::Just(params) => (
                             Some(path_make_expr_style(params.formula)),
-                            Some(params.lifetime),
+                            params.lifetime.0,
                         ),
                         proc_easy::EasyMaybe::Nothing => (None, None),
                     };
@@ -66,6 +66,7 @@ pub fn derive(args: FormulaArgs, input: &syn::DeriveInput) -> syn::Result<TokenS
     let ident = &input.ident;
 
     let config = Config::from_args(args, &input.generics, &input.data);
+    let registration = registration(input);
 
     match &input.data {
         syn::Data::Union(data) => Err(syn::Error::new_spanned(
@@ -92,9 +93,13 @@ pub fn derive(args: FormulaArgs, input: &syn::DeriveInput) -> syn::Result<TokenS
 
             let field_ids: Vec<_> = (0..data.fields.len()).collect();
 
+            let field_names = field_names(&data.fields);
+
             let (formula_impl_generics, formula_type_generics, formula_where_clause) =
                 config.formula_generics.split_for_impl();
 
+            let projections = projections(input, &config.formula_generics, &data.fields);
+
             let touch_fields = match &data.fields {
                 syn::Fields::Unit => quote::quote! {},
                 syn::Fields::Unnamed(fields) => {
@@ -144,9 +149,25 @@ pub fn derive(args: FormulaArgs, input: &syn::DeriveInput) -> syn::Result<TokenS
                     const EXACT_SIZE: ::alkahest::private::bool = {true #(; <#last_field_type as ::alkahest::private::Formula>::EXACT_SIZE)*};
 
                     const HEAPLESS: ::alkahest::private::bool = true #(&& <#all_field_types as ::alkahest::private::Formula>::HEAPLESS)*;
+
+                    #[inline(always)]
+                    fn describe() -> ::alkahest::private::Descriptor {
+                        ::alkahest::private::Descriptor::new::<Self>(::alkahest::private::DescriptorKind::Struct(const {
+                            &[#(
+                                ::alkahest::private::FieldDescriptor {
+                                    name: #field_names,
+                                    formula: ::alkahest::private::LazyDescriptor::of::<#all_field_types>(),
+                                },
+                            )*]
+                        }))
+                    }
                 }
 
                 impl #formula_impl_generics ::alkahest::private::BareFormula for #ident #formula_type_generics #formula_where_clause {}
+
+                #projections
+
+                #registration
             };
 
             Ok(tokens)
@@ -197,6 +218,15 @@ pub fn derive(args: FormulaArgs, input: &syn::DeriveInput) -> syn::Result<TokenS
             #[allow(clippy::cast_possible_truncation)]
             let variant_ids: Vec<_> = (0..data.variants.len() as u32).collect();
 
+            let variant_names: Vec<String> =
+                data.variants.iter().map(|v| v.ident.to_string()).collect();
+
+            let variant_field_names: Vec<Vec<String>> = data
+                .variants
+                .iter()
+                .map(|v| field_names(&v.fields))
+                .collect();
+
             let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
 
             let (formula_impl_generics, formula_type_generics, formula_where_clause) =
@@ -339,10 +369,206 @@ pub fn derive(args: FormulaArgs, input: &syn::DeriveInput) -> syn::Result<TokenS
                     };
 
                     const HEAPLESS: ::alkahest::private::bool = true #(#(&& <#all_field_types as ::alkahest::private::Formula>::HEAPLESS)*)*;
+
+                    #[inline(always)]
+                    fn describe() -> ::alkahest::private::Descriptor {
+                        ::alkahest::private::Descriptor::new::<Self>(::alkahest::private::DescriptorKind::Enum(const {
+                            &[#(
+                                ::alkahest::private::VariantDescriptor {
+                                    name: #variant_names,
+                                    tag: #variant_ids,
+                                    fields: &[#(
+                                        ::alkahest::private::FieldDescriptor {
+                                            name: #variant_field_names,
+                                            formula: ::alkahest::private::LazyDescriptor::of::<#all_field_types>(),
+                                        },
+                                    )*],
+                                },
+                            )*]
+                        }))
+                    }
                 }
 
                 impl #formula_impl_generics ::alkahest::private::BareFormula for #ident #formula_type_generics #formula_where_clause {}
+
+                #registration
             })
         }
     }
 }
+
+/// Registers non-generic formula for lock file checks.
+/// Generic formulas are registered manually for each instantiation.
+fn registration(input: &syn::DeriveInput) -> TokenStream {
+    if !input.generics.params.is_empty() {
+        return TokenStream::new();
+    }
+    let ident = &input.ident;
+    quote::quote! {
+        ::alkahest::register_formula!(#ident);
+    }
+}
+
+/// Returns names of the fields.
+/// Unnamed fields are named by index.
+fn field_names(fields: &syn::Fields) -> Vec<String> {
+    fields
+        .iter()
+        .enumerate()
+        .map(|(idx, field)| match &field.ident {
+            Some(ident) => ident.to_string(),
+            None => idx.to_string(),
+        })
+        .collect()
+}
+
+/// Generates traits with lazy projections of the struct fields
+/// implemented for `Lazy` and `LazyMut` of the struct,
+/// and `Fixed` implementation for the struct.
+fn projections(
+    input: &syn::DeriveInput,
+    generics: &syn::Generics,
+    fields: &syn::Fields,
+) -> TokenStream {
+    let de: syn::Lifetime = syn::parse_quote!('de);
+    if fields.is_empty() || generics.lifetimes().any(|param| param.lifetime == de) {
+        return TokenStream::new();
+    }
+
+    let vis = &input.vis;
+    let ident = &input.ident;
+    let trait_ident = quote::format_ident!("{}Projection", ident);
+    let trait_doc = format!("Lazy projections of [`{ident}`] fields.");
+
+    let (_, formula_type_generics, _) = generics.split_for_impl();
+
+    let mut trait_generics = generics.clone();
+    trait_generics.params.insert(
+        0,
+        syn::GenericParam::Lifetime(syn::LifetimeParam::new(de.clone())),
+    );
+    let (trait_impl_generics, trait_type_generics, trait_where_clause) =
+        trait_generics.split_for_impl();
+
+    let field_types: Vec<_> = fields.iter().map(|field| &field.ty).collect();
+    let last_idx = field_types.len() - 1;
+
+    let methods: Vec<_> = field_names(fields)
+        .iter()
+        .map(|name| {
+            let method = quote::format_ident!("project_{}", name);
+            let doc = format!("Projects field `{name}` without deserializing other fields.");
+            (method, doc)
+        })
+        .collect();
+
+    // Higher-ranked bounds are not checked until method is called,
+    // so fields with formulas that cannot be projected don't break the derive.
+    let declarations = methods.iter().zip(&field_types).map(|((method, doc), ty)| {
+        quote::quote! {
+            #[doc = #doc]
+            fn #method(&self) -> ::alkahest::private::Result<
+                ::alkahest::private::Lazy<#de, <#ty as ::alkahest::private::Project>::Target>,
+                ::alkahest::private::DeserializeError,
+            >
+            where
+                for<'__a> #ty: ::alkahest::private::Project;
+        }
+    });
+
+    let definitions = methods.iter().zip(&field_types).enumerate().map(
+        |(idx, ((method, _), ty))| {
+            let skipped = &field_types[..idx];
+            let last = idx == last_idx;
+            quote::quote! {
+                #[inline(always)]
+                fn #method(&self) -> ::alkahest::private::Result<
+                    ::alkahest::private::Lazy<#de, <#ty as ::alkahest::private::Project>::Target>,
+                    ::alkahest::private::DeserializeError,
+                >
+                where
+                    for<'__a> #ty: ::alkahest::private::Project,
+                {
+                    #[allow(unused_mut)]
+                    let mut de = ::alkahest::private::Lazy::deserializer(self);
+                    #(
+                        ::alkahest::private::skip_field::<#skipped>(&mut de)?;
+                    )*
+                    ::alkahest::private::project_field::<#ty>(&mut de, #last)
+                }
+            }
+        },
+    );
+
+    let (formula_impl_generics, _, formula_where_clause) = generics.split_for_impl();
+
+    let mut fixed_generics = generics.clone();
+    fixed_generics
+        .make_where_clause()
+        .predicates
+        .extend(field_types.iter().map(|ty| -> syn::WherePredicate {
+            syn::parse_quote! { for<'__a> #ty: ::alkahest::private::Fixed }
+        }));
+    let fixed_where_clause = &fixed_generics.where_clause;
+
+    let trait_mut_ident = quote::format_ident!("{}ProjectionMut", ident);
+    let trait_mut_doc = format!("Mutable views over [`{ident}`] fields.");
+
+    let declarations_mut = methods.iter().zip(&field_types).map(|((method, _), ty)| {
+        let doc = format!(
+            "Returns mutable view over field `{}`.",
+            &method.to_string()["project_".len()..]
+        );
+        quote::quote! {
+            #[doc = #doc]
+            fn #method(&mut self) -> ::alkahest::private::LazyMut<'_, #ty>
+            where
+                for<'__a> #ty: ::alkahest::private::Fixed;
+        }
+    });
+
+    let definitions_mut =
+        methods
+            .iter()
+            .zip(&field_types)
+            .enumerate()
+            .map(|(idx, ((method, _), ty))| {
+                let skipped = &field_types[..idx];
+                quote::quote! {
+                    #[inline(always)]
+                    fn #method(&mut self) -> ::alkahest::private::LazyMut<'_, #ty>
+                    where
+                        for<'__a> #ty: ::alkahest::private::Fixed,
+                    {
+                        let offset = ::alkahest::private::fixed_offset(&[
+                            #(<#skipped as ::alkahest::private::Formula>::MAX_STACK_SIZE,)*
+                        ]);
+                        ::alkahest::private::project_fixed::<_, #ty>(self, offset)
+                    }
+                }
+            });
+
+    quote::quote! {
+        #[doc = #trait_doc]
+        #[allow(dead_code)]
+        #vis trait #trait_ident #trait_impl_generics #trait_where_clause {
+            #(#declarations)*
+        }
+
+        impl #trait_impl_generics #trait_ident #trait_type_generics for ::alkahest::private::Lazy<#de, #ident #formula_type_generics> #trait_where_clause {
+            #(#definitions)*
+        }
+
+        impl #formula_impl_generics ::alkahest::private::Fixed for #ident #formula_type_generics #fixed_where_clause {}
+
+        #[doc = #trait_mut_doc]
+        #[allow(dead_code)]
+        #vis trait #trait_mut_ident #formula_impl_generics #formula_where_clause {
+            #(#declarations_mut)*
+        }
+
+        impl #trait_impl_generics #trait_mut_ident #formula_type_generics for ::alkahest::private::LazyMut<#de, #ident #formula_type_generics> #trait_where_clause {
+            #(#definitions_mut)*
+        }
+    }
+}
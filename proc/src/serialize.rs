@@ -282,6 +282,15 @@ pub fn derive(
                                 return Some(sizes);
                             }
                             let #ident #bind_ref_names = *self;
+                            #(
+                                let with_formula = ::alkahest::private::with_formula(|s: &#formula_path| match *s {
+                                    #formula_path #with_variant #bind_ref_names => #bound_names,
+                                    _ => unreachable!(),
+                                });
+                                if with_formula.size_hint_unknown(#field_count == 1 + #field_ids) {
+                                    return ::alkahest::private::Option::None;
+                                }
+                            )*
                             let mut __total = ::alkahest::private::Sizes::with_stack(#start_stack_size);
                             #(
                                 let with_formula = ::alkahest::private::with_formula(|s: &#formula_path| match *s {
@@ -325,6 +334,15 @@ pub fn derive(
                                 return Some(sizes);
                             }
                             let #ident #bind_ref_names = *self;
+                            #(
+                                let with_formula = ::alkahest::private::with_formula(|s: &#formula_path| match *s {
+                                    #formula_path #with_variant #bind_ref_names => #bound_names,
+                                    _ => unreachable!(),
+                                });
+                                if with_formula.size_hint_unknown(#field_count == 1 + #field_ids) {
+                                    return ::alkahest::private::Option::None;
+                                }
+                            )*
                             let mut __total = ::alkahest::private::Sizes::with_stack(#start_stack_size);
                             #(
                                 let with_formula = ::alkahest::private::with_formula(|s: &#formula_path| match *s {
@@ -500,6 +518,15 @@ pub fn derive(
                             match *self {
                                 #(
                                     #ident::#variant_names #bind_ref_names => {
+                                        #(
+                                            let with_formula = ::alkahest::private::with_formula(|s: &#formula_path| match *s {
+                                                #formula_path::#variant_names #bind_ref_names => #bound_names,
+                                                _ => unreachable!(),
+                                            });
+                                            if with_formula.size_hint_unknown(#field_counts == 1 + #field_ids) {
+                                                return ::alkahest::private::Option::None;
+                                            }
+                                        )*
                                         let mut __total = ::alkahest::private::Sizes::with_stack(::alkahest::private::VARIANT_SIZE);
                                         #(
                                             let with_formula = ::alkahest::private::with_formula(|s: &#formula_path| match *s {
@@ -552,6 +579,15 @@ pub fn derive(
                             match *self {
                                 #(
                                     #ident::#variant_names #bind_ref_names => {
+                                        #(
+                                            let with_formula = ::alkahest::private::with_formula(|s: &#formula_path| match *s {
+                                                #formula_path::#variant_names #bind_ref_names => #bound_names,
+                                                _ => unreachable!(),
+                                            });
+                                            if with_formula.size_hint_unknown(#field_counts == 1 + #field_ids) {
+                                                return ::alkahest::private::Option::None;
+                                            }
+                                        )*
                                         let mut __total = ::alkahest::private::Sizes::with_stack(::alkahest::private::VARIANT_SIZE);
                                         #(
                                             let with_formula = ::alkahest::private::with_formula(|s: &#formula_path| match *s {
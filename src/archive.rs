@@ -0,0 +1,162 @@
+//!
+//! This module provides archive of records over owned or memory-mapped bytes.
+//!
+
+use core::{marker::PhantomData, ops::Deref};
+
+use crate::{
+    deserialize::DeserializeError,
+    formula::{BareFormula, Formula},
+    lazy::Lazy,
+    records::Records,
+};
+
+/// Archive of records written by [`RecordWriter`](crate::RecordWriter)
+/// over bytes owned by the storage `M`.
+///
+/// Storage is any type that dereferences to bytes,
+/// e.g. `Vec<u8>`, `Box<[u8]>` or memory map like `memmap2::Mmap`,
+/// so multi-gigabyte archives can be read without loading them into RAM.
+/// With "memmap2" feature `Archive::open_file` maps the file directly.
+/// Records are exposed as [`Lazy`] views which lifetime is tied to the archive
+/// and only pages they touch are read.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// let mut writer = RecordWriter::<[u16]>::new();
+/// writer.push([1u16, 2]);
+/// writer.push([3u16, 4, 5]);
+///
+/// // `memmap2::Mmap` works the same way.
+/// let archive = Archive::<[u16], _>::open(writer.finish()).unwrap();
+/// assert_eq!(archive.len(), 2);
+///
+/// let lazy = archive.get(1).unwrap().unwrap();
+/// assert_eq!(lazy.len(), 3);
+/// assert_eq!(lazy.get::<Vec<u16>>().unwrap(), [3, 4, 5]);
+/// ```
+pub struct Archive<F: ?Sized, M> {
+    storage: M,
+    index_offset: usize,
+    marker: PhantomData<fn(&F)>,
+}
+
+impl<F, M> Archive<F, M>
+where
+    F: Formula + ?Sized,
+    M: Deref<Target = [u8]>,
+{
+    /// Opens archive in the storage.
+    /// Checks that footer and index are consistent,
+    /// records themselves are checked when read.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError` if storage does not contain valid record container,
+    /// see [`Records::open`].
+    pub fn open(storage: M) -> Result<Self, DeserializeError> {
+        let index_offset = Records::<F>::open(&storage)?.index_offset();
+        Ok(Archive {
+            storage,
+            index_offset,
+            marker: PhantomData,
+        })
+    }
+
+    /// Opens archive in the storage, verifies checksum of every record
+    /// and validates it with [`validate_packet`](crate::validate_packet).
+    /// Records of validated archive are safe to read from untrusted sources.
+    ///
+    /// Touches all bytes of the storage.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError` if storage does not contain valid record container
+    /// or any record fails validation.
+    #[cfg(feature = "alloc")]
+    pub fn open_validated(storage: M) -> Result<Self, DeserializeError> {
+        let archive = Archive::open(storage)?;
+        let records = archive.records();
+        for n in 0..records.len() {
+            if let Some(record) = records.checked_record(n) {
+                crate::validate::validate_packet::<F>(record?)?;
+            }
+        }
+        Ok(archive)
+    }
+
+    /// Returns view of the records.
+    #[must_use]
+    #[inline(always)]
+    pub fn records(&self) -> Records<'_, F> {
+        Records::reopen(&self.storage, self.index_offset)
+    }
+
+    /// Returns number of records.
+    #[must_use]
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.records().len()
+    }
+
+    /// Returns `true` if archive has no records.
+    #[must_use]
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.records().is_empty()
+    }
+
+    /// Returns lazy view of record `n`.
+    /// Returns `None` if `n` is out of bounds.
+    #[inline]
+    pub fn get(&self, n: usize) -> Option<Result<Lazy<'_, F>, DeserializeError>>
+    where
+        F: BareFormula,
+    {
+        self.records().get(n)
+    }
+
+    /// Returns reference to the storage.
+    #[inline(always)]
+    pub fn storage(&self) -> &M {
+        &self.storage
+    }
+
+    /// Unwraps the storage.
+    #[inline(always)]
+    pub fn into_storage(self) -> M {
+        self.storage
+    }
+}
+
+#[cfg(feature = "memmap2")]
+impl<F> Archive<F, memmap2::Mmap>
+where
+    F: Formula + ?Sized,
+{
+    /// Maps the file into memory and opens archive in it.
+    /// See [`Archive::open`].
+    ///
+    /// # Errors
+    ///
+    /// Returns `io::Error` if the file cannot be opened or mapped.
+    /// Returns `io::Error` of `InvalidData` kind with `DeserializeError`
+    /// if the file does not contain valid record container.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated while the archive is alive,
+    /// same as for [`memmap2::Mmap::map`].
+    #[allow(unsafe_code)]
+    pub unsafe fn open_file<P>(path: P) -> std::io::Result<Self>
+    where
+        P: AsRef<std::path::Path>,
+    {
+        let file = std::fs::File::open(path)?;
+        // Safety: the caller guarantees that the file is not modified.
+        let map = unsafe { memmap2::Mmap::map(&file)? };
+        Archive::open(map).map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+    }
+}
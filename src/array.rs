@@ -1,5 +1,6 @@
 use crate::{
     buffer::Buffer,
+    descriptor::{Descriptor, DescriptorKind, LazyDescriptor},
     deserialize::{Deserialize, DeserializeError, Deserializer},
     formula::{repeat_size, BareFormula, Formula},
     iter::{owned_iter_fast_sizes, ref_iter_fast_sizes},
@@ -13,6 +14,11 @@ where
     const MAX_STACK_SIZE: Option<usize> = repeat_size(F::MAX_STACK_SIZE, N);
     const EXACT_SIZE: bool = F::EXACT_SIZE;
     const HEAPLESS: bool = F::HEAPLESS;
+
+    #[inline(always)]
+    fn describe() -> Descriptor {
+        Descriptor::new::<Self>(DescriptorKind::Array(LazyDescriptor::of::<F>(), N))
+    }
 }
 
 impl<F, const N: usize> BareFormula for [F; N] where F: Formula {}
@@ -103,7 +109,7 @@ where
         let mut opts = [(); N].map(|_| None);
         opts.iter_mut().try_for_each(|slot| {
             *slot = Some(de.read_value::<F, T>(false)?);
-            Ok(())
+            Ok::<_, DeserializeError>(())
         })?;
         let value = opts.map(Option::unwrap);
         Ok(value)
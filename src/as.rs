@@ -2,6 +2,7 @@ use core::marker::PhantomData;
 
 use crate::{
     buffer::Buffer,
+    descriptor::Descriptor,
     deserialize::{Deserialize, DeserializeError, Deserializer},
     formula::{BareFormula, Formula},
     serialize::{Serialize, Sizes},
@@ -51,6 +52,12 @@ where
     const MAX_STACK_SIZE: Option<usize> = F::MAX_STACK_SIZE;
     const EXACT_SIZE: bool = F::EXACT_SIZE;
     const HEAPLESS: bool = F::HEAPLESS;
+
+    /// `As` does not affect layout and is described as `F`.
+    #[inline(always)]
+    fn describe() -> Descriptor {
+        F::describe()
+    }
 }
 
 impl<F, T> Serialize<As<F>> for T
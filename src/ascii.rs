@@ -0,0 +1,160 @@
+use crate::{
+    buffer::Buffer,
+    deserialize::{Deserialize, DeserializeError, Deserializer},
+    formula::{BareFormula, Formula},
+    serialize::{write_bytes, SerializeRef, Sizes},
+};
+
+/// A formula for strings that contain only 7-bit ASCII characters.
+///
+/// Layout is the same as `str`.
+/// Deserialization checks that all bytes are ASCII,
+/// which is cheaper than full UTF-8 validation,
+/// and fails with `DeserializeError::NonAscii` otherwise.
+/// Suitable for protocol tokens and identifiers
+/// where non-ASCII characters are an error anyway.
+///
+/// Serializable from `str` and `String`.
+/// Deserializable into `&str` and `String`.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// let mut buffer = [0u8; 16];
+/// let (size, root) = serialize::<AsciiStr, _>("GET", &mut buffer).unwrap();
+///
+/// let value = deserialize_with_size::<AsciiStr, &str>(&buffer[..size], root).unwrap();
+/// assert_eq!(value, "GET");
+///
+/// let (size, root) = serialize::<str, _>("GÉT", &mut buffer).unwrap();
+/// let err = deserialize_with_size::<AsciiStr, &str>(&buffer[..size], root).unwrap_err();
+/// assert!(matches!(err, DeserializeError::NonAscii));
+/// ```
+pub struct AsciiStr;
+
+impl Formula for AsciiStr {
+    const MAX_STACK_SIZE: Option<usize> = None;
+    const EXACT_SIZE: bool = false;
+    const HEAPLESS: bool = true;
+}
+
+impl BareFormula for AsciiStr {}
+
+impl SerializeRef<AsciiStr> for str {
+    /// # Panics
+    ///
+    /// Panics if string contains non-ASCII characters.
+    #[inline]
+    fn serialize<B>(&self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        assert!(
+            self.is_ascii(),
+            "String with non-ASCII characters cannot be serialized with `AsciiStr`"
+        );
+        write_bytes(self.as_bytes(), sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        Some(Sizes::with_stack(self.len()))
+    }
+}
+
+impl<'de, 'fe: 'de> Deserialize<'fe, AsciiStr> for &'de str {
+    #[inline]
+    fn deserialize(de: Deserializer<'fe>) -> Result<Self, DeserializeError> {
+        read_ascii(de)
+    }
+
+    #[inline]
+    fn deserialize_in_place(&mut self, de: Deserializer<'fe>) -> Result<(), DeserializeError> {
+        *self = read_ascii(de)?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "alloc")]
+mod alloc_impls {
+    use alloc::{borrow::ToOwned, string::String};
+
+    use crate::{
+        buffer::Buffer,
+        deserialize::{Deserialize, DeserializeError, Deserializer},
+        serialize::{Serialize, SerializeRef, Sizes},
+    };
+
+    use super::{read_ascii, AsciiStr};
+
+    impl SerializeRef<AsciiStr> for String {
+        /// # Panics
+        ///
+        /// Panics if string contains non-ASCII characters.
+        #[inline(always)]
+        fn serialize<B>(&self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+        where
+            B: Buffer,
+        {
+            <str as SerializeRef<AsciiStr>>::serialize(self, sizes, buffer)
+        }
+
+        #[inline(always)]
+        fn size_hint(&self) -> Option<Sizes> {
+            <str as SerializeRef<AsciiStr>>::size_hint(self)
+        }
+    }
+
+    impl Serialize<AsciiStr> for String {
+        /// # Panics
+        ///
+        /// Panics if string contains non-ASCII characters.
+        #[inline(always)]
+        fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+        where
+            B: Buffer,
+        {
+            <str as SerializeRef<AsciiStr>>::serialize(&self, sizes, buffer)
+        }
+
+        #[inline(always)]
+        fn size_hint(&self) -> Option<Sizes> {
+            <str as SerializeRef<AsciiStr>>::size_hint(self)
+        }
+    }
+
+    impl<'de> Deserialize<'de, AsciiStr> for String {
+        #[inline]
+        fn deserialize(de: Deserializer<'de>) -> Result<Self, DeserializeError> {
+            Ok(read_ascii(de)?.to_owned())
+        }
+
+        #[inline]
+        fn deserialize_in_place(&mut self, de: Deserializer<'de>) -> Result<(), DeserializeError> {
+            let string = read_ascii(de)?;
+            self.clear();
+            self.push_str(string);
+            Ok(())
+        }
+    }
+}
+
+#[inline]
+fn read_ascii<'de>(de: Deserializer<'de>) -> Result<&'de str, DeserializeError> {
+    let bytes = de.read_all_bytes();
+    if !bytes.is_ascii() {
+        return Err(DeserializeError::NonAscii);
+    }
+
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "unchecked-utf8")] {
+            // SAFETY: ASCII bytes are valid UTF-8.
+            #[allow(unsafe_code)]
+            Ok(unsafe { core::str::from_utf8_unchecked(bytes) })
+        } else {
+            // Fast path of UTF-8 validation covers ASCII bytes.
+            core::str::from_utf8(bytes).map_err(DeserializeError::NonUtf8)
+        }
+    }
+}
@@ -1,40 +1,164 @@
 use std::{io::Cursor, marker::PhantomData, mem::size_of};
 
+use bincode::config::{
+    AllowTrailing, Bounded, DefaultOptions, FixintEncoding, WithOtherEndian, WithOtherIntEncoding,
+    WithOtherLimit, WithOtherTrailing,
+};
+
 use crate::{
     buffer::Buffer,
     bytes::Bytes,
+    descriptor::{Descriptor, DescriptorKind, LazyDescriptor},
     deserialize::{Deserialize, DeserializeError, Deserializer},
     formula::{reference_size, Formula},
     serialize::{write_reference, Serialize, Sizes},
     size::FixedUsizeType,
 };
 
+/// Type-level configuration of [`Bincode`] and [`Bincoded`] formulas.
+///
+/// Options are composed from [`BincodeDefault`] or [`BincodeLegacy`]
+/// with wrappers like [`BincodeFixint`], [`BincodeBigEndian`], [`BincodeLimit`]
+/// and [`BincodeAllowTrailing`], or implemented manually
+/// to match existing data.
+pub trait BincodeOptions {
+    /// Options type of [`bincode`] crate.
+    type Options: bincode::Options;
+
+    /// Returns the options.
+    fn options() -> Self::Options;
+}
+
+/// Options of `bincode::DefaultOptions`.
+/// Variable-length integers, little-endian byte order,
+/// no size limit and trailing bytes rejected.
+pub struct BincodeDefault;
+
+impl BincodeOptions for BincodeDefault {
+    type Options = DefaultOptions;
+
+    #[inline(always)]
+    fn options() -> DefaultOptions {
+        DefaultOptions::new()
+    }
+}
+
+/// Options used by `bincode::serialize` and `bincode::deserialize` functions.
+/// Fixed-size integers, little-endian byte order,
+/// no size limit and trailing bytes allowed.
+pub struct BincodeLegacy;
+
+impl BincodeOptions for BincodeLegacy {
+    type Options =
+        WithOtherTrailing<WithOtherIntEncoding<DefaultOptions, FixintEncoding>, AllowTrailing>;
+
+    #[inline(always)]
+    fn options() -> Self::Options {
+        bincode::Options::allow_trailing_bytes(bincode::Options::with_fixint_encoding(
+            DefaultOptions::new(),
+        ))
+    }
+}
+
+/// Options `O` with fixed-size integers encoding.
+pub struct BincodeFixint<O = BincodeDefault>(PhantomData<fn() -> O>);
+
+impl<O> BincodeOptions for BincodeFixint<O>
+where
+    O: BincodeOptions,
+{
+    type Options = WithOtherIntEncoding<O::Options, FixintEncoding>;
+
+    #[inline(always)]
+    fn options() -> Self::Options {
+        bincode::Options::with_fixint_encoding(O::options())
+    }
+}
+
+/// Options `O` with big-endian byte order.
+pub struct BincodeBigEndian<O = BincodeDefault>(PhantomData<fn() -> O>);
+
+impl<O> BincodeOptions for BincodeBigEndian<O>
+where
+    O: BincodeOptions,
+{
+    type Options = WithOtherEndian<O::Options, bincode::config::BigEndian>;
+
+    #[inline(always)]
+    fn options() -> Self::Options {
+        bincode::Options::with_big_endian(O::options())
+    }
+}
+
+/// Options `O` with size limit of `LIMIT` bytes.
+///
+/// Serializing value that exceeds the limit will cause a panic.
+/// Deserializing value that exceeds the limit will cause deserialization error.
+pub struct BincodeLimit<const LIMIT: u64, O = BincodeDefault>(PhantomData<fn() -> O>);
+
+impl<const LIMIT: u64, O> BincodeOptions for BincodeLimit<LIMIT, O>
+where
+    O: BincodeOptions,
+{
+    type Options = WithOtherLimit<O::Options, Bounded>;
+
+    #[inline(always)]
+    fn options() -> Self::Options {
+        bincode::Options::with_limit(O::options(), LIMIT)
+    }
+}
+
+/// Options `O` with trailing bytes allowed.
+pub struct BincodeAllowTrailing<O = BincodeDefault>(PhantomData<fn() -> O>);
+
+impl<O> BincodeOptions for BincodeAllowTrailing<O>
+where
+    O: BincodeOptions,
+{
+    type Options = WithOtherTrailing<O::Options, AllowTrailing>;
+
+    #[inline(always)]
+    fn options() -> Self::Options {
+        bincode::Options::allow_trailing_bytes(O::options())
+    }
+}
+
 /// A formula that can be used to serialize and deserialize data
 /// using [`bincode`] crate.
 ///
 /// Any type serializable with `serde` can be used with this formula.
 /// If type is not serializable with `bincode` crate it will cause a panic.
 /// Deserializing non-compatible type will cause deserialization error.
-pub struct Bincode;
+///
+/// Bincode configuration is selected with `O` parameter,
+/// see [`BincodeOptions`].
+pub struct Bincode<O = BincodeDefault>(PhantomData<fn() -> O>);
 
-impl Formula for Bincode {
+impl<O> Formula for Bincode<O>
+where
+    O: BincodeOptions,
+{
     const MAX_STACK_SIZE: Option<usize> = Some(reference_size::<Bytes>());
     const EXACT_SIZE: bool = true;
     const HEAPLESS: bool = false;
+
+    #[inline(always)]
+    fn describe() -> Descriptor {
+        Descriptor::new::<Self>(DescriptorKind::Ref(LazyDescriptor::of::<Bytes>()))
+    }
 }
 
-impl<T> Serialize<Bincode> for T
+impl<T, O> Serialize<Bincode<O>> for T
 where
     T: serde::Serialize,
+    O: BincodeOptions,
 {
     #[inline]
     fn serialize<B>(self, sizes: &mut Sizes, mut buffer: B) -> Result<(), B::Error>
     where
         B: Buffer,
     {
-        let options = bincode::config::DefaultOptions::new();
-
-        let size = match bincode::Options::serialized_size(options, &self) {
+        let size = match bincode::Options::serialized_size(O::options(), &self) {
             Ok(size) => size,
             Err(err) => panic!("Bincode serialization error: {}", err),
         };
@@ -52,7 +176,8 @@ where
             Ok([]) => {} // Nothing to do.
             Ok(bytes) => {
                 let mut cursor = Cursor::new(&mut bytes[sizes.heap..]);
-                if let Err(err) = bincode::Options::serialize_into(options, &mut cursor, &self) {
+                if let Err(err) = bincode::Options::serialize_into(O::options(), &mut cursor, &self)
+                {
                     panic!("Bincode serialization error: {}", err);
                 };
                 assert_eq!(cursor.position(), size as u64);
@@ -71,9 +196,10 @@ where
     }
 }
 
-impl<'de, T> Deserialize<'de, Bincode> for T
+impl<'de, T, O> Deserialize<'de, Bincode<O>> for T
 where
     T: serde::Deserialize<'de>,
+    O: BincodeOptions,
 {
     #[inline]
     fn deserialize(de: Deserializer<'de>) -> Result<Self, DeserializeError>
@@ -82,8 +208,7 @@ where
     {
         let de = de.deref::<Bytes>()?;
 
-        let options = bincode::config::DefaultOptions::new();
-        let mut de = bincode::de::Deserializer::from_slice(de.read_all_bytes(), options);
+        let mut de = bincode::de::Deserializer::from_slice(de.read_all_bytes(), O::options());
 
         match <T as serde::Deserialize<'de>>::deserialize(&mut de) {
             Ok(value) => Ok(value),
@@ -95,8 +220,7 @@ where
     fn deserialize_in_place(&mut self, de: Deserializer<'de>) -> Result<(), DeserializeError> {
         let de = de.deref::<Bytes>()?;
 
-        let options = bincode::config::DefaultOptions::new();
-        let mut de = bincode::de::Deserializer::from_slice(de.read_all_bytes(), options);
+        let mut de = bincode::de::Deserializer::from_slice(de.read_all_bytes(), O::options());
 
         match <T as serde::Deserialize<'de>>::deserialize_in_place(&mut de, self) {
             Ok(()) => Ok(()),
@@ -113,65 +237,79 @@ where
 ///
 /// If type is not serializable with `bincode` crate it will cause a panic.
 /// Deserializing non-compatible type will cause deserialization error.
-pub struct Bincoded<T>(PhantomData<fn(&T) -> &T>);
+///
+/// Bincode configuration is selected with `O` parameter,
+/// see [`BincodeOptions`].
+pub struct Bincoded<T, O = BincodeDefault>(PhantomData<fn(&T, O) -> &T>);
 
-impl<T> Formula for Bincoded<T> {
+impl<T, O> Formula for Bincoded<T, O>
+where
+    O: BincodeOptions,
+{
     const MAX_STACK_SIZE: Option<usize> = Some(size_of::<[FixedUsizeType; 2]>());
     const EXACT_SIZE: bool = true;
     const HEAPLESS: bool = false;
+
+    #[inline(always)]
+    fn describe() -> Descriptor {
+        Descriptor::new::<Self>(DescriptorKind::Ref(LazyDescriptor::of::<Bytes>()))
+    }
 }
 
-impl<T> Serialize<Bincoded<T>> for T
+impl<T, O> Serialize<Bincoded<T, O>> for T
 where
     T: serde::Serialize,
+    O: BincodeOptions,
 {
     #[inline(always)]
     fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
     where
         B: Buffer,
     {
-        <T as Serialize<Bincode>>::serialize(self, sizes, buffer)
+        <T as Serialize<Bincode<O>>>::serialize(self, sizes, buffer)
     }
 
     #[inline(always)]
     fn size_hint(&self) -> Option<Sizes> {
-        <T as Serialize<Bincode>>::size_hint(self)
+        <T as Serialize<Bincode<O>>>::size_hint(self)
     }
 }
 
-impl<T> Serialize<Bincoded<T>> for &T
+impl<T, O> Serialize<Bincoded<T, O>> for &T
 where
     T: serde::Serialize,
+    O: BincodeOptions,
 {
     #[inline(always)]
     fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
     where
         B: Buffer,
     {
-        <&T as Serialize<Bincode>>::serialize(self, sizes, buffer)
+        <&T as Serialize<Bincode<O>>>::serialize(self, sizes, buffer)
     }
 
     #[inline(always)]
     fn size_hint(&self) -> Option<Sizes> {
-        <&T as Serialize<Bincode>>::size_hint(self)
+        <&T as Serialize<Bincode<O>>>::size_hint(self)
     }
 }
 
-impl<'de, T> Deserialize<'de, Bincoded<T>> for T
+impl<'de, T, O> Deserialize<'de, Bincoded<T, O>> for T
 where
     T: serde::Deserialize<'de>,
+    O: BincodeOptions,
 {
     #[inline(always)]
     fn deserialize(de: Deserializer<'de>) -> Result<Self, DeserializeError>
     where
         Self: Sized,
     {
-        <T as Deserialize<'de, Bincode>>::deserialize(de)
+        <T as Deserialize<'de, Bincode<O>>>::deserialize(de)
     }
 
     #[inline(always)]
     fn deserialize_in_place(&mut self, de: Deserializer<'de>) -> Result<(), DeserializeError> {
-        <T as Deserialize<'de, Bincode>>::deserialize_in_place(self, de)
+        <T as Deserialize<'de, Bincode<O>>>::deserialize_in_place(self, de)
     }
 }
 
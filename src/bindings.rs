@@ -0,0 +1,600 @@
+//!
+//! This module provides bindings generation for other languages.
+//!
+
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::fmt::Write;
+
+use crate::{
+    codegen::{ident, is_tuple},
+    private::VARIANT_SIZE,
+    schema::{Schema, SchemaField, SchemaKind},
+};
+
+impl Schema {
+    /// Generates C header with definitions of fixed-size formulas of the schema.
+    ///
+    /// For each structure a packed C structure is emitted along with
+    /// `NAME_SIZE` and `NAME_FIELD_OFFSET` macros.
+    /// Enums are emitted as byte arrays with `NAME_TAG_OFFSET`,
+    /// `NAME_VARIANT_TAG` and `NAME_VARIANT_FIELD_OFFSET` macros.
+    ///
+    /// Alkahest stores fields in reverse order, so members of C structures
+    /// are declared last to first and array elements are stored last to first.
+    /// Numbers are little-endian.
+    ///
+    /// Formulas that are not fixed-size, i.e. with references,
+    /// variable size or opaque, are skipped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if schema refers to missing nodes.
+    #[must_use]
+    pub fn to_c_header(&self) -> String {
+        let bindings = Bindings::new(self);
+        let mut out = String::new();
+
+        let guard = format!("ALKAHEST_{}_H", bindings.names[0].to_uppercase());
+        let _ = write!(
+            out,
+            "/* Generated from alkahest schema. Do not edit. */\n\
+             #ifndef {guard}\n#define {guard}\n\n#include <stdint.h>\n\n#pragma pack(push, 1)\n"
+        );
+
+        let mut emitted = vec![false; self.nodes.len()];
+        for idx in 0..self.nodes.len() {
+            bindings.c_declare(&mut out, &mut emitted, idx);
+        }
+
+        let _ = write!(out, "\n#pragma pack(pop)\n\n#endif /* {guard} */\n");
+        out
+    }
+
+    /// Generates TypeScript types with decoders and encoders
+    /// for fixed-size formulas of the schema.
+    ///
+    /// For each structure and enum `decodeName(view, offset)` and
+    /// `encodeName(view, offset, value)` functions are emitted,
+    /// that read and write value at `offset` of a `DataView`.
+    /// `NAME_SIZE` constant is the number of bytes the value occupies.
+    /// Enums are represented as unions tagged by `kind` property
+    /// with the name of the variant.
+    /// 64 and 128 bit integers are represented as `bigint`.
+    ///
+    /// Formulas that are not fixed-size, i.e. with references,
+    /// variable size or opaque, are skipped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if schema refers to missing nodes.
+    #[must_use]
+    pub fn to_typescript(&self) -> String {
+        let bindings = Bindings::new(self);
+        let mut out = String::from("// Generated from alkahest schema. Do not edit.\n");
+
+        for (idx, node) in self.nodes.iter().enumerate() {
+            if !matches!(node.kind, SchemaKind::Struct(_) | SchemaKind::Enum(_)) {
+                continue;
+            }
+            let name = &bindings.names[idx];
+            match bindings.fixed_size(idx, 0) {
+                None => {
+                    let _ = write!(out, "\n// {name} is not fixed-size and is skipped.\n");
+                }
+                Some(size) => {
+                    let _ = write!(
+                        out,
+                        "\nexport const {}_SIZE = {size};\n",
+                        name.to_uppercase()
+                    );
+                    bindings.ts_declare(&mut out, idx, size);
+                }
+            }
+        }
+        out
+    }
+}
+
+struct Bindings<'a> {
+    schema: &'a Schema,
+    names: Vec<String>,
+}
+
+impl<'a> Bindings<'a> {
+    fn new(schema: &'a Schema) -> Self {
+        let names = schema.idl_names().iter().map(|name| ident(name)).collect();
+        Bindings { schema, names }
+    }
+
+    /// Returns size of the formula if it is fixed-size.
+    fn fixed_size(&self, idx: usize, depth: usize) -> Option<usize> {
+        // Fixed-size formulas can't be recursive.
+        if depth > self.schema.nodes.len() {
+            return None;
+        }
+
+        let node = &self.schema.nodes[idx];
+        if !node.exact_size || !node.heapless {
+            return None;
+        }
+
+        let fixed = match &node.kind {
+            SchemaKind::Bool | SchemaKind::Float(_) => true,
+            SchemaKind::Unsigned(size) | SchemaKind::Signed(size) => {
+                matches!(size, 1 | 2 | 4 | 8 | 16)
+            }
+            SchemaKind::Array(elem, _) => self.fixed_size(*elem, depth + 1).is_some(),
+            SchemaKind::Tuple(elems) => self.all_fixed(elems.iter().copied(), depth),
+            SchemaKind::Struct(fields) => self.all_fixed(fields.iter().map(|f| f.formula), depth),
+            SchemaKind::Enum(variants) => self.all_fixed(
+                variants
+                    .iter()
+                    .flat_map(|v| v.fields.iter().map(|f| f.formula)),
+                depth,
+            ),
+            _ => false,
+        };
+
+        if fixed {
+            node.max_stack_size
+        } else {
+            None
+        }
+    }
+
+    fn all_fixed(&self, mut elems: impl Iterator<Item = usize>, depth: usize) -> bool {
+        elems.all(|elem| self.fixed_size(elem, depth + 1).is_some())
+    }
+
+    fn size(&self, idx: usize) -> usize {
+        self.schema.nodes[idx].max_stack_size.unwrap_or(0)
+    }
+
+    /// Returns offsets of fields from the start of the value
+    /// with `total` size, when `tail` bytes at the end are occupied.
+    fn offsets(
+        &self,
+        fields: impl Iterator<Item = usize>,
+        total: usize,
+        tail: usize,
+    ) -> Vec<usize> {
+        let mut end = total - tail;
+        fields
+            .map(|field| {
+                end -= self.size(field);
+                end
+            })
+            .collect()
+    }
+
+    fn c_declare(&self, out: &mut String, emitted: &mut [bool], idx: usize) {
+        if emitted[idx] {
+            return;
+        }
+        emitted[idx] = true;
+
+        let node = &self.schema.nodes[idx];
+        let name = &self.names[idx];
+        let upper = name.to_uppercase();
+
+        let Some(size) = self.fixed_size(idx, 0) else {
+            if matches!(node.kind, SchemaKind::Struct(_) | SchemaKind::Enum(_)) {
+                let _ = write!(out, "\n/* {name} is not fixed-size and is skipped. */\n");
+            }
+            return;
+        };
+
+        match &node.kind {
+            SchemaKind::Struct(fields) => {
+                // Nested structures must be declared first.
+                for field in fields {
+                    self.c_declare_nested(out, emitted, field.formula);
+                }
+
+                let _ = write!(out, "\n/* struct {name}, {size} bytes */\n");
+                if size > 0 {
+                    out.push_str("typedef struct {\n");
+                    for field in fields.iter().rev() {
+                        self.c_member(out, field.formula, &member_name(&field.name), 1);
+                    }
+                    let _ = writeln!(out, "}} {name};");
+                }
+                let _ = writeln!(out, "#define {upper}_SIZE {size}");
+
+                let offsets = self.offsets(fields.iter().map(|f| f.formula), size, 0);
+                for (field, offset) in fields.iter().zip(offsets) {
+                    let _ = writeln!(
+                        out,
+                        "#define {upper}_{}_OFFSET {offset}",
+                        macro_name(&field.name)
+                    );
+                }
+            }
+            SchemaKind::Enum(variants) => {
+                for variant in variants {
+                    for field in &variant.fields {
+                        self.c_declare_nested(out, emitted, field.formula);
+                    }
+                }
+
+                let _ = write!(
+                    out,
+                    "\n/* enum {name}, {size} bytes */\n\
+                     typedef struct {{\n    uint8_t bytes[{size}];\n}} {name};\n\
+                     #define {upper}_SIZE {size}\n\
+                     #define {upper}_TAG_OFFSET {}\n",
+                    size - VARIANT_SIZE
+                );
+
+                for variant in variants {
+                    let variant_upper = macro_name(&variant.name);
+                    let _ = writeln!(out, "#define {upper}_{variant_upper}_TAG {}", variant.tag);
+
+                    let offsets =
+                        self.offsets(variant.fields.iter().map(|f| f.formula), size, VARIANT_SIZE);
+                    for (field, offset) in variant.fields.iter().zip(offsets) {
+                        let _ = writeln!(
+                            out,
+                            "#define {upper}_{variant_upper}_{}_OFFSET {offset}",
+                            macro_name(&field.name)
+                        );
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Declares structures and enums used by the formula.
+    fn c_declare_nested(&self, out: &mut String, emitted: &mut [bool], idx: usize) {
+        match &self.schema.nodes[idx].kind {
+            SchemaKind::Struct(_) | SchemaKind::Enum(_) => self.c_declare(out, emitted, idx),
+            SchemaKind::Array(elem, _) => self.c_declare_nested(out, emitted, *elem),
+            SchemaKind::Tuple(elems) => {
+                for elem in elems {
+                    self.c_declare_nested(out, emitted, *elem);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Writes member declaration of C structure.
+    /// Zero-sized members are omitted.
+    fn c_member(&self, out: &mut String, idx: usize, declarator: &str, indent: usize) {
+        if self.size(idx) == 0 {
+            return;
+        }
+
+        let pad = "    ".repeat(indent);
+        let base = match &self.schema.nodes[idx].kind {
+            SchemaKind::Bool => "uint8_t".to_string(),
+            SchemaKind::Unsigned(16) | SchemaKind::Signed(16) => {
+                let _ = writeln!(out, "{pad}uint8_t {declarator}[16];");
+                return;
+            }
+            SchemaKind::Unsigned(size) => format!("uint{}_t", size * 8),
+            SchemaKind::Signed(size) => format!("int{}_t", size * 8),
+            SchemaKind::Float(4) => "float".to_string(),
+            SchemaKind::Float(_) => "double".to_string(),
+            SchemaKind::Array(elem, len) => {
+                self.c_member(out, *elem, &format!("{declarator}[{len}]"), indent);
+                return;
+            }
+            SchemaKind::Tuple(elems) => {
+                let _ = writeln!(out, "{pad}struct {{");
+                for (elem_idx, elem) in elems.iter().enumerate().rev() {
+                    self.c_member(out, *elem, &format!("_{elem_idx}"), indent + 1);
+                }
+                let _ = writeln!(out, "{pad}}} {declarator};");
+                return;
+            }
+            _ => self.names[idx].clone(),
+        };
+        let _ = writeln!(out, "{pad}{base} {declarator};");
+    }
+
+    fn ts_declare(&self, out: &mut String, idx: usize, size: usize) {
+        let name = &self.names[idx];
+
+        match &self.schema.nodes[idx].kind {
+            SchemaKind::Struct(fields) => {
+                if is_tuple(fields) {
+                    let elems = fields
+                        .iter()
+                        .map(|field| self.ts_type(field.formula))
+                        .collect::<Vec<_>>();
+                    let _ = writeln!(out, "export type {name} = [{}];", elems.join(", "));
+                } else {
+                    let _ = writeln!(out, "export interface {name} {{");
+                    for field in fields {
+                        let _ = writeln!(out, "  {}: {};", field.name, self.ts_type(field.formula));
+                    }
+                    out.push_str("}\n");
+                }
+
+                let offsets = self.offsets(fields.iter().map(|f| f.formula), size, 0);
+
+                let _ = writeln!(
+                    out,
+                    "\nexport function decode{name}(view: DataView, offset: number): {name} {{"
+                );
+                let values = fields
+                    .iter()
+                    .zip(&offsets)
+                    .map(|(field, offset)| self.ts_decode(field.formula, &at("offset", *offset), 0))
+                    .collect::<Vec<_>>();
+                if is_tuple(fields) {
+                    let _ = writeln!(out, "  return [{}];", values.join(", "));
+                } else {
+                    out.push_str("  return {\n");
+                    for (field, value) in fields.iter().zip(values) {
+                        let _ = writeln!(out, "    {}: {value},", field.name);
+                    }
+                    out.push_str("  };\n");
+                }
+                out.push_str("}\n");
+
+                let _ = writeln!(
+                    out,
+                    "\nexport function encode{name}(view: DataView, offset: number, value: {name}): void {{"
+                );
+                for (field, offset) in fields.iter().zip(&offsets) {
+                    let value = field_access("value", field, fields);
+                    self.ts_encode(out, field.formula, &at("offset", *offset), &value, 1);
+                }
+                out.push_str("}\n");
+            }
+            SchemaKind::Enum(variants) => {
+                let _ = writeln!(out, "export type {name} =");
+                for (variant_idx, variant) in variants.iter().enumerate() {
+                    let _ = write!(out, "  | {{ kind: \"{}\"", variant.name);
+                    for field in &variant.fields {
+                        let _ = write!(out, "; {}: {}", field.name, self.ts_type(field.formula));
+                    }
+                    out.push_str(" }");
+                    if variant_idx + 1 == variants.len() {
+                        out.push(';');
+                    }
+                    out.push('\n');
+                }
+
+                let tag_offset = at("offset", size - VARIANT_SIZE);
+
+                let _ = write!(
+                    out,
+                    "\nexport function decode{name}(view: DataView, offset: number): {name} {{\n  \
+                     switch (view.getUint32({tag_offset}, true)) {{\n"
+                );
+                for variant in variants {
+                    let offsets =
+                        self.offsets(variant.fields.iter().map(|f| f.formula), size, VARIANT_SIZE);
+                    let _ = write!(
+                        out,
+                        "    case {}:\n      return {{ kind: \"{}\"",
+                        variant.tag, variant.name
+                    );
+                    for (field, offset) in variant.fields.iter().zip(offsets) {
+                        let value = self.ts_decode(field.formula, &at("offset", offset), 0);
+                        let _ = write!(out, ", {}: {value}", field.name);
+                    }
+                    out.push_str(" };\n");
+                }
+                let _ = write!(
+                    out,
+                    "    default:\n      throw new Error(\"Wrong variant of {name}\");\n  }}\n}}\n"
+                );
+
+                let _ = write!(
+                    out,
+                    "\nexport function encode{name}(view: DataView, offset: number, value: {name}): void {{\n  \
+                     switch (value.kind) {{\n"
+                );
+                for variant in variants {
+                    let offsets =
+                        self.offsets(variant.fields.iter().map(|f| f.formula), size, VARIANT_SIZE);
+                    let _ = write!(
+                        out,
+                        "    case \"{}\":\n      view.setUint32({tag_offset}, {}, true);\n",
+                        variant.name, variant.tag
+                    );
+                    for (field, offset) in variant.fields.iter().zip(offsets) {
+                        let value = format!("value.{}", field.name);
+                        let value = if field.name.starts_with(|c: char| c.is_ascii_digit()) {
+                            format!("value[{}]", field.name)
+                        } else {
+                            value
+                        };
+                        self.ts_encode(out, field.formula, &at("offset", offset), &value, 3);
+                    }
+                    out.push_str("      break;\n");
+                }
+                out.push_str("  }\n}\n");
+            }
+            _ => {}
+        }
+    }
+
+    fn ts_type(&self, idx: usize) -> String {
+        match &self.schema.nodes[idx].kind {
+            SchemaKind::Bool => "boolean".to_string(),
+            SchemaKind::Unsigned(size) | SchemaKind::Signed(size) if *size > 4 => {
+                "bigint".to_string()
+            }
+            SchemaKind::Unsigned(_) | SchemaKind::Signed(_) | SchemaKind::Float(_) => {
+                "number".to_string()
+            }
+            SchemaKind::Array(elem, _) => format!("{}[]", self.ts_type(*elem)),
+            SchemaKind::Tuple(elems) => {
+                let elems = elems
+                    .iter()
+                    .map(|elem| self.ts_type(*elem))
+                    .collect::<Vec<_>>();
+                format!("[{}]", elems.join(", "))
+            }
+            _ => self.names[idx].clone(),
+        }
+    }
+
+    /// Returns expression that decodes the formula at `offset` expression.
+    fn ts_decode(&self, idx: usize, offset: &str, depth: usize) -> String {
+        match &self.schema.nodes[idx].kind {
+            SchemaKind::Bool => format!("view.getUint8({offset}) !== 0"),
+            SchemaKind::Unsigned(1) => format!("view.getUint8({offset})"),
+            SchemaKind::Signed(1) => format!("view.getInt8({offset})"),
+            SchemaKind::Unsigned(16) => format!(
+                "(view.getBigUint64({offset} + 8, true) << 64n) | view.getBigUint64({offset}, true)"
+            ),
+            SchemaKind::Signed(16) => format!(
+                "(view.getBigInt64({offset} + 8, true) << 64n) | view.getBigUint64({offset}, true)"
+            ),
+            SchemaKind::Unsigned(8) => format!("view.getBigUint64({offset}, true)"),
+            SchemaKind::Signed(8) => format!("view.getBigInt64({offset}, true)"),
+            SchemaKind::Unsigned(size) => format!("view.getUint{}({offset}, true)", size * 8),
+            SchemaKind::Signed(size) => format!("view.getInt{}({offset}, true)", size * 8),
+            SchemaKind::Float(size) => format!("view.getFloat{}({offset}, true)", size * 8),
+            SchemaKind::Array(elem, len) => {
+                let elem_offset =
+                    format!("{offset} + ({} - i{depth}) * {}", len - 1, self.size(*elem));
+                format!(
+                    "Array.from({{ length: {len} }}, (_, i{depth}) => {})",
+                    self.ts_decode(*elem, &elem_offset, depth + 1)
+                )
+            }
+            SchemaKind::Tuple(elems) => {
+                let offsets = self.offsets(elems.iter().copied(), self.size(idx), 0);
+                let elems = elems
+                    .iter()
+                    .zip(offsets)
+                    .map(|(elem, elem_offset)| {
+                        self.ts_decode(*elem, &at(offset, elem_offset), depth)
+                    })
+                    .collect::<Vec<_>>();
+                format!("[{}]", elems.join(", "))
+            }
+            _ => format!("decode{}(view, {offset})", self.names[idx]),
+        }
+    }
+
+    /// Writes statements that encode `value` expression with the formula at `offset` expression.
+    fn ts_encode(&self, out: &mut String, idx: usize, offset: &str, value: &str, indent: usize) {
+        let pad = "  ".repeat(indent);
+        match &self.schema.nodes[idx].kind {
+            SchemaKind::Bool => {
+                let _ = writeln!(out, "{pad}view.setUint8({offset}, {value} ? 1 : 0);");
+            }
+            SchemaKind::Unsigned(1) => {
+                let _ = writeln!(out, "{pad}view.setUint8({offset}, {value});");
+            }
+            SchemaKind::Signed(1) => {
+                let _ = writeln!(out, "{pad}view.setInt8({offset}, {value});");
+            }
+            SchemaKind::Unsigned(16) | SchemaKind::Signed(16) => {
+                let (kind, bits) = match self.schema.nodes[idx].kind {
+                    SchemaKind::Signed(_) => ("Int", "asIntN"),
+                    _ => ("Uint", "asUintN"),
+                };
+                let _ = write!(
+                    out,
+                    "{pad}view.setBigUint64({offset}, BigInt.asUintN(64, {value}), true);\n\
+                     {pad}view.setBig{kind}64({offset} + 8, BigInt.{bits}(64, {value} >> 64n), true);\n"
+                );
+            }
+            SchemaKind::Unsigned(8) => {
+                let _ = writeln!(out, "{pad}view.setBigUint64({offset}, {value}, true);");
+            }
+            SchemaKind::Signed(8) => {
+                let _ = writeln!(out, "{pad}view.setBigInt64({offset}, {value}, true);");
+            }
+            SchemaKind::Unsigned(size) => {
+                let _ = writeln!(
+                    out,
+                    "{pad}view.setUint{}({offset}, {value}, true);",
+                    size * 8
+                );
+            }
+            SchemaKind::Signed(size) => {
+                let _ = writeln!(
+                    out,
+                    "{pad}view.setInt{}({offset}, {value}, true);",
+                    size * 8
+                );
+            }
+            SchemaKind::Float(size) => {
+                let _ = writeln!(
+                    out,
+                    "{pad}view.setFloat{}({offset}, {value}, true);",
+                    size * 8
+                );
+            }
+            SchemaKind::Array(elem, len) => {
+                let i = format!("i{indent}");
+                let _ = writeln!(out, "{pad}for (let {i} = 0; {i} < {len}; {i}++) {{");
+                let elem_offset = format!("{offset} + ({} - {i}) * {}", len - 1, self.size(*elem));
+                self.ts_encode(
+                    out,
+                    *elem,
+                    &elem_offset,
+                    &format!("{value}[{i}]"),
+                    indent + 1,
+                );
+                let _ = writeln!(out, "{pad}}}");
+            }
+            SchemaKind::Tuple(elems) => {
+                let offsets = self.offsets(elems.iter().copied(), self.size(idx), 0);
+                for (elem_idx, (elem, elem_offset)) in elems.iter().zip(offsets).enumerate() {
+                    let elem_value = format!("{value}[{elem_idx}]");
+                    self.ts_encode(out, *elem, &at(offset, elem_offset), &elem_value, indent);
+                }
+            }
+            _ => {
+                let _ = writeln!(
+                    out,
+                    "{pad}encode{}(view, {offset}, {value});",
+                    self.names[idx]
+                );
+            }
+        }
+    }
+}
+
+/// Returns offset expression `base + offset`.
+fn at(base: &str, offset: usize) -> String {
+    if offset == 0 {
+        base.to_string()
+    } else {
+        format!("{base} + {offset}")
+    }
+}
+
+/// Returns name of C structure member for the field.
+fn member_name(name: &str) -> String {
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{name}")
+    } else {
+        ident(name)
+    }
+}
+
+/// Returns name of the field or variant in C macros.
+fn macro_name(name: &str) -> String {
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        name.to_string()
+    } else {
+        ident(name).to_uppercase()
+    }
+}
+
+/// Returns expression that accesses the field of TypeScript value.
+fn field_access(value: &str, field: &SchemaField, fields: &[SchemaField]) -> String {
+    if is_tuple(fields) {
+        format!("{value}[{}]", field.name)
+    } else {
+        format!("{value}.{}", field.name)
+    }
+}
@@ -0,0 +1,134 @@
+//!
+//! This module provides support for bit-packed structs defined with [`define_bits!`](crate::define_bits).
+//!
+
+use crate::{
+    buffer::Buffer,
+    deserialize::{DeserializeError, Deserializer},
+    serialize::{write_bytes, Sizes},
+};
+
+/// Maximum total number of bits in bit-packed struct.
+pub const MAX_BITS: u32 = u128::BITS;
+
+/// Type of a field that can be stored in bit-packed struct.
+pub trait BitField: Copy {
+    /// Maximum width of the field in bits.
+    const MAX_WIDTH: u32;
+
+    /// Returns bits of the value.
+    fn into_bits(self) -> u128;
+
+    /// Constructs value from bits.
+    /// Bits never exceed `MAX_WIDTH`.
+    fn from_bits(bits: u128) -> Self;
+}
+
+impl BitField for bool {
+    const MAX_WIDTH: u32 = 1;
+
+    #[inline(always)]
+    fn into_bits(self) -> u128 {
+        u128::from(self)
+    }
+
+    #[inline(always)]
+    fn from_bits(bits: u128) -> Self {
+        bits != 0
+    }
+}
+
+macro_rules! impl_bit_field {
+    ($($ty:ident)*) => {$(
+        impl BitField for $ty {
+            const MAX_WIDTH: u32 = $ty::BITS;
+
+            #[inline(always)]
+            fn into_bits(self) -> u128 {
+                u128::from(self)
+            }
+
+            #[inline(always)]
+            #[allow(clippy::cast_possible_truncation)]
+            fn from_bits(bits: u128) -> Self {
+                bits as $ty
+            }
+        }
+    )*};
+}
+
+impl_bit_field!(u8 u16 u32 u64);
+
+/// Returns number of bytes that hold `bits`.
+#[must_use]
+#[inline(always)]
+pub const fn packed_size(bits: u32) -> usize {
+    bits.div_ceil(8) as usize
+}
+
+/// Packs value into `width` bits of the accumulator at `shift`
+/// and advances `shift`.
+///
+/// # Panics
+///
+/// Panics if value does not fit into `width` bits.
+#[inline(always)]
+pub fn pack<T>(acc: &mut u128, shift: &mut u32, value: T, width: u32, field: &str)
+where
+    T: BitField,
+{
+    let bits = value.into_bits();
+    assert!(
+        width == u128::BITS || bits >> width == 0,
+        "Value of field `{field}` does not fit into {width} bits"
+    );
+    *acc |= bits << *shift;
+    *shift += width;
+}
+
+/// Unpacks value from `width` bits of the accumulator at `shift`
+/// and advances `shift`.
+#[inline(always)]
+pub fn unpack<T>(acc: u128, shift: &mut u32, width: u32) -> T
+where
+    T: BitField,
+{
+    let mask = if width == u128::BITS {
+        u128::MAX
+    } else {
+        (1 << width) - 1
+    };
+    let bits = (acc >> *shift) & mask;
+    *shift += width;
+    T::from_bits(bits)
+}
+
+/// Writes lowest `packed_size(bits)` bytes of the accumulator.
+///
+/// # Errors
+///
+/// Returns error if buffer write fails.
+#[inline(always)]
+pub fn write_packed<B>(acc: u128, bits: u32, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+where
+    B: Buffer,
+{
+    write_bytes(&acc.to_le_bytes()[..packed_size(bits)], sizes, buffer)
+}
+
+/// Reads `packed_size(bits)` bytes into accumulator.
+///
+/// # Errors
+///
+/// Returns `DeserializeError::NonCanonical` if unused bits of the last byte are set.
+#[inline(always)]
+pub fn read_packed(mut de: Deserializer<'_>, bits: u32) -> Result<u128, DeserializeError> {
+    let size = packed_size(bits);
+    let mut bytes = [0; 16];
+    bytes[..size].copy_from_slice(de.read_bytes(size)?);
+    let acc = u128::from_le_bytes(bytes);
+    if bits < u128::BITS && acc >> bits != 0 {
+        return Err(DeserializeError::NonCanonical);
+    }
+    Ok(acc)
+}
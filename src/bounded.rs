@@ -0,0 +1,213 @@
+//!
+//! This module provides formula wrapper that limits length of slices and strings.
+//!
+
+use core::marker::PhantomData;
+
+use crate::{
+    buffer::Buffer,
+    deserialize::{Deserialize, DeserializeError, Deserializer, ErrorDetails},
+    formula::Formula,
+    serialize::{write_bytes, write_field, write_slice, Serialize, Sizes},
+    size::{usize_truncate_unchecked, SIZE_STACK},
+};
+
+/// Formula wrapper that limits length of `[F]` to `N` elements
+/// and length of `str` to `N` bytes.
+///
+/// Length is serialized ahead of the elements,
+/// so `MAX_STACK_SIZE` is bounded when elements are sized
+/// and buffers can be sized with [`max_packet_size`](crate::max_packet_size).
+/// Slices are serialized from any iterator in a single pass,
+/// length is patched in after the elements are written.
+/// Deserialization fails with `DeserializeError::WrongLength`
+/// if peer sends longer value, before elements are deserialized.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// type Message = (u8, Bounded<[u16], 4>, Bounded<str, 8>);
+///
+/// let mut buffer = [0u8; max_packet_size::<Message>()];
+/// let size = write_packet::<Message, _>((1u8, [1u16, 2, 3], "name"), &mut buffer).unwrap();
+///
+/// let (value, _) = read_packet::<Message, (u8, Vec<u16>, &str)>(&buffer[..size]).unwrap();
+/// assert_eq!(value, (1, vec![1, 2, 3], "name"));
+///
+/// let size = write_packet::<Bounded<str, 16>, _>("too long name", &mut buffer).unwrap();
+/// let err = read_packet::<Bounded<str, 8>, &str>(&buffer[..size]).unwrap_err();
+/// assert!(matches!(err, DeserializeError::WrongLength(_)));
+/// ```
+pub struct Bounded<F: ?Sized, const N: usize> {
+    marker: PhantomData<fn(&F) -> &F>,
+}
+
+impl<F, const N: usize> Formula for Bounded<[F], N>
+where
+    F: Formula,
+{
+    const MAX_STACK_SIZE: Option<usize> = match F::MAX_STACK_SIZE {
+        Some(0) => Some(SIZE_STACK),
+        Some(max_stack) => match max_stack.checked_mul(N) {
+            Some(elements) => elements.checked_add(SIZE_STACK),
+            None => None,
+        },
+        None => None,
+    };
+    const EXACT_SIZE: bool = false;
+    const HEAPLESS: bool = F::HEAPLESS;
+}
+
+impl<F, I, const N: usize> Serialize<Bounded<[F], N>> for I
+where
+    F: Formula,
+    I: IntoIterator,
+    I::Item: Serialize<F>,
+{
+    /// # Panics
+    ///
+    /// Panics if iterator yields more than `N` elements.
+    #[inline]
+    fn serialize<B>(self, sizes: &mut Sizes, mut buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        let mut len = 0;
+        let iter = self.into_iter().inspect(|_| {
+            len += 1;
+            assert!(len <= N, "Slice length exceeds bound of {N} elements");
+        });
+
+        // Slice of zero-sized elements consists of the length only.
+        if F::MAX_STACK_SIZE == Some(0) {
+            return write_slice::<F, _, _>(iter, sizes, buffer);
+        }
+
+        // Length is known only when iterator is exhausted.
+        // Reserve space for it and patch it after the elements are written.
+        let at = sizes.stack;
+        write_bytes(&[0; SIZE_STACK], sizes, buffer.reborrow())?;
+        write_slice::<F, _, _>(iter, sizes, buffer.reborrow())?;
+
+        let len = usize_truncate_unchecked(len).to_le_bytes();
+        if buffer.write_stack(sizes.heap, at, &len).is_err() {
+            unreachable!("Successfully written before");
+        }
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        None
+    }
+}
+
+impl<'de, F, T, const N: usize> Deserialize<'de, Bounded<[F], N>> for T
+where
+    F: Formula,
+    T: Deserialize<'de, [F]>,
+{
+    #[inline]
+    fn deserialize(de: Deserializer<'de>) -> Result<Self, DeserializeError> {
+        let elements = bounded_elements::<F, N>(de)?;
+        <T as Deserialize<'de, [F]>>::deserialize(elements)
+    }
+
+    #[inline]
+    fn deserialize_in_place(&mut self, de: Deserializer<'de>) -> Result<(), DeserializeError> {
+        let elements = bounded_elements::<F, N>(de)?;
+        <T as Deserialize<'de, [F]>>::deserialize_in_place(self, elements)
+    }
+}
+
+impl<const N: usize> Formula for Bounded<str, N> {
+    const MAX_STACK_SIZE: Option<usize> = N.checked_add(SIZE_STACK);
+    const EXACT_SIZE: bool = false;
+    const HEAPLESS: bool = true;
+}
+
+impl<T, const N: usize> Serialize<Bounded<str, N>> for T
+where
+    T: AsRef<str>,
+{
+    /// # Panics
+    ///
+    /// Panics if string is longer than `N` bytes.
+    #[inline]
+    fn serialize<B>(self, sizes: &mut Sizes, mut buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        let bytes = self.as_ref().as_bytes();
+        let len = bytes.len();
+        assert!(len <= N, "String length {len} exceeds bound of {N} bytes");
+
+        write_field::<usize, _, _>(len, sizes, buffer.reborrow(), false)?;
+        write_bytes(bytes, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        Some(Sizes::with_stack(SIZE_STACK + self.as_ref().len()))
+    }
+}
+
+impl<'de, T, const N: usize> Deserialize<'de, Bounded<str, N>> for T
+where
+    T: Deserialize<'de, str>,
+{
+    #[inline]
+    fn deserialize(mut de: Deserializer<'de>) -> Result<Self, DeserializeError> {
+        let len = read_len::<N>(&mut de)?;
+        <T as Deserialize<'de, str>>::deserialize(de.sub(len)?)
+    }
+
+    #[inline]
+    fn deserialize_in_place(&mut self, mut de: Deserializer<'de>) -> Result<(), DeserializeError> {
+        let len = read_len::<N>(&mut de)?;
+        <T as Deserialize<'de, str>>::deserialize_in_place(self, de.sub(len)?)
+    }
+}
+
+/// Reads length and checks that it does not exceed `N`.
+#[inline(always)]
+fn read_len<const N: usize>(de: &mut Deserializer<'_>) -> Result<usize, DeserializeError> {
+    let len = de.read_usize()?;
+    if len > N {
+        return Err(DeserializeError::WrongLength(ErrorDetails::sizes(N, len)));
+    }
+    Ok(len)
+}
+
+/// Reads length of bounded slice and returns deserializer for `[F]`
+/// that spans exactly the elements.
+#[inline]
+fn bounded_elements<F, const N: usize>(
+    mut de: Deserializer<'_>,
+) -> Result<Deserializer<'_>, DeserializeError>
+where
+    F: Formula,
+{
+    match F::MAX_STACK_SIZE {
+        Some(0) => {
+            read_len::<N>(&mut de.clone())?;
+            Ok(de)
+        }
+        Some(max_stack) => {
+            let len = read_len::<N>(&mut de)?;
+            de.sub(len * max_stack)
+        }
+        None => {
+            let len = read_len::<N>(&mut de)?;
+            let mut elements = de.clone();
+            let mut stack = 0;
+            for _ in 0..len {
+                let size = elements.read_usize()?;
+                elements.read_bytes(size)?;
+                stack += SIZE_STACK + size;
+            }
+            de.sub(stack)
+        }
+    }
+}
@@ -0,0 +1,90 @@
+//!
+//! This module provides packet reading and writing with `bytes` buffers.
+//!
+
+use alloc::vec::Vec;
+
+use ::bytes::{Buf, BufMut};
+
+use crate::{
+    deserialize::{Deserialize, DeserializeError, ErrorDetails},
+    formula::{reference_size, Formula},
+    packet::{packet_address, read_packet, write_packet_to_vec},
+    serialize::Serialize,
+};
+
+/// Writes packet with the value into `BufMut`.
+/// Returns the number of bytes written.
+///
+/// # Panics
+///
+/// Panics if `buf` does not have enough remaining capacity,
+/// same as [`BufMut::put_slice`].
+#[inline]
+pub fn write_packet_to_buf<F, T, B>(value: T, buf: &mut B) -> usize
+where
+    F: Formula + ?Sized,
+    T: Serialize<F>,
+    B: BufMut + ?Sized,
+{
+    let mut packet = Vec::new();
+    let size = write_packet_to_vec::<F, T>(value, &mut packet);
+    buf.put_slice(&packet[..size]);
+    size
+}
+
+/// Reads packet with value from the `Buf` and advances it past the packet.
+///
+/// Packet that lies in the first chunk is deserialized in place.
+/// Packet that spans multiple chunks, e.g. of `Chain`,
+/// is copied into contiguous memory first.
+///
+/// # Errors
+///
+/// Returns `DeserializeError` if deserialization fails.
+/// Returns `DeserializeError::OutOfBounds` if `buf` does not contain the whole packet,
+/// in which case `buf` may be partially consumed.
+#[inline]
+pub fn read_packet_from_buf<F, T, B>(buf: &mut B) -> Result<T, DeserializeError>
+where
+    F: Formula + ?Sized,
+    T: for<'de> Deserialize<'de, F>,
+    B: Buf + ?Sized,
+{
+    let reference_size = reference_size::<F>();
+
+    let chunk = buf.chunk();
+    if let Some(size) = packet_address::<F>(chunk) {
+        let size = size.max(reference_size);
+        if chunk.len() >= size {
+            let (value, _) = read_packet::<F, T>(&chunk[..size])?;
+            buf.advance(size);
+            return Ok(value);
+        }
+    }
+
+    if buf.remaining() < reference_size {
+        return Err(DeserializeError::OutOfBounds(ErrorDetails::sizes(
+            reference_size,
+            buf.remaining(),
+        )));
+    }
+
+    let mut packet = alloc::vec![0; reference_size];
+    buf.copy_to_slice(&mut packet);
+
+    let size = packet_address::<F>(&packet)
+        .unwrap_or(0)
+        .max(reference_size);
+    if buf.remaining() < size - reference_size {
+        return Err(DeserializeError::OutOfBounds(ErrorDetails::sizes(
+            size - reference_size,
+            buf.remaining(),
+        )));
+    }
+    packet.resize(size, 0);
+    buf.copy_to_slice(&mut packet[reference_size..]);
+
+    let (value, _) = read_packet::<F, T>(&packet)?;
+    Ok(value)
+}
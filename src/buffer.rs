@@ -3,6 +3,11 @@ use core::{convert::Infallible, fmt};
 #[cfg(feature = "alloc")]
 use alloc::vec::Vec;
 
+use crate::size::PACKET_SIZE_LIMIT;
+
+#[cfg(feature = "metrics")]
+use crate::metrics::Metrics;
+
 /// Buffer API that is used by serializer.
 /// Buffers can be extensible or fixed size.
 /// Extensible buffers grow automatically when needed.
@@ -26,6 +31,7 @@ pub trait Buffer {
     fn write_stack(&mut self, heap: usize, stack: usize, bytes: &[u8]) -> Result<(), Self::Error>;
 
     /// Add padding bytes to the stack.
+    /// Padding bytes must be zeroed, so that encoding is canonical.
     ///
     /// # Errors
     ///
@@ -54,6 +60,14 @@ pub trait Buffer {
         stack: usize,
         len: usize,
     ) -> Result<&mut [u8], Self::Error>;
+
+    /// Returns statistics collected by this buffer, if any.
+    /// Serializer updates them while writing.
+    #[cfg(feature = "metrics")]
+    #[inline(always)]
+    fn metrics(&mut self) -> Option<&mut Metrics> {
+        None
+    }
 }
 
 /// No-op buffer that does not write anything.
@@ -105,8 +119,21 @@ impl Buffer for DryBuffer {
 /// This type does not contain the size of the buffer required to fit serialized data.
 /// To get the size use `serialize_or_size` function that returns `Result<usize, BufferSizeRequired>`.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct BufferExhausted;
 
+impl BufferExhausted {
+    /// Returns stable numeric code of the error.
+    ///
+    /// Same as code of [`BufferSizeRequired`](crate::BufferSizeRequired),
+    /// see [`DeserializeError::code`](crate::DeserializeError::code) for other codes.
+    #[must_use]
+    #[inline(always)]
+    pub const fn code(&self) -> u16 {
+        256
+    }
+}
+
 impl fmt::Display for BufferExhausted {
     #[inline(always)]
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -114,6 +141,9 @@ impl fmt::Display for BufferExhausted {
     }
 }
 
+#[cfg(feature = "std")]
+impl std::error::Error for BufferExhausted {}
+
 /// Fixed buffer without bound checks.
 /// If buffer is too small to fit serialized data, it will panic.
 #[repr(transparent)]
@@ -123,9 +153,14 @@ pub struct CheckedFixedBuffer<'a> {
 
 impl<'a> CheckedFixedBuffer<'a> {
     /// Creates a new buffer.
+    /// Uses at most [`PACKET_SIZE_LIMIT`](crate::PACKET_SIZE_LIMIT) bytes of `buf`.
     #[inline(always)]
+    #[allow(clippy::unnecessary_min_or_max)] // `PACKET_SIZE_LIMIT` may be `usize::MAX`.
     pub fn new(buf: &'a mut [u8]) -> Self {
-        CheckedFixedBuffer { buf }
+        let len = buf.len().min(PACKET_SIZE_LIMIT);
+        CheckedFixedBuffer {
+            buf: &mut buf[..len],
+        }
     }
 }
 
@@ -161,11 +196,8 @@ impl<'a> Buffer for CheckedFixedBuffer<'a> {
             return Err(BufferExhausted);
         }
 
-        #[cfg(test)]
-        {
-            let at = self.buf.len() - stack - len;
-            self.buf[at..][..len].fill(0);
-        }
+        let at = self.buf.len() - stack - len;
+        self.buf[at..][..len].fill(0);
         Ok(())
     }
 
@@ -216,11 +248,8 @@ impl<'a> Buffer for &'a mut [u8] {
         debug_assert!(heap + stack <= self.len());
         assert!(self.len() - heap - stack >= len);
 
-        #[cfg(test)]
-        {
-            let at = self.len() - stack - len;
-            self[at..][..len].fill(0);
-        }
+        let at = self.len() - stack - len;
+        self[at..][..len].fill(0);
         Ok(())
     }
 
@@ -257,8 +286,14 @@ pub struct MaybeFixedBuffer<'a> {
 
 impl<'a> MaybeFixedBuffer<'a> {
     /// Creates a new buffer with exhausted flag.
+    /// Uses at most [`PACKET_SIZE_LIMIT`](crate::PACKET_SIZE_LIMIT) bytes of `buf`.
+    #[allow(clippy::unnecessary_min_or_max)] // `PACKET_SIZE_LIMIT` may be `usize::MAX`.
     pub fn new(buf: &'a mut [u8], exhausted: &'a mut bool) -> Self {
-        MaybeFixedBuffer { buf, exhausted }
+        let len = buf.len().min(PACKET_SIZE_LIMIT);
+        MaybeFixedBuffer {
+            buf: &mut buf[..len],
+            exhausted,
+        }
     }
 }
 
@@ -297,6 +332,9 @@ impl<'a> Buffer for MaybeFixedBuffer<'a> {
             debug_assert!(heap + stack <= self.buf.len());
             if self.buf.len() - heap - stack < len {
                 *self.exhausted = true;
+            } else {
+                let at = self.buf.len() - stack - len;
+                self.buf[at..][..len].fill(0);
             }
         }
         Ok(())
@@ -339,33 +377,194 @@ impl<'a> Buffer for MaybeFixedBuffer<'a> {
 /// Extensible buffer that writes to a vector.
 /// If buffer is too small to fit serialized data it extends the vector.
 /// Never returns an error, cannot fail to serialize data except for OOM error.
+///
+/// Buffer either borrows the vector or owns it.
+/// Keep one buffer alive and [`clear`](VecBuffer::clear) it between messages
+/// to reuse allocated memory.
+///
+/// # Panics
+///
+/// Panics if serialized data exceeds [`PACKET_SIZE_LIMIT`](crate::PACKET_SIZE_LIMIT).
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::{*, advanced::*};
+/// let mut buffer = VecBuffer::with_capacity(64);
+///
+/// for id in 0..3u32 {
+///     buffer.clear();
+///     let size = write_packet_into::<(u32, As<str>), _, _>((id, "ping"), buffer.reborrow()).unwrap();
+///     buffer.truncate(size);
+///
+///     let (value, _) = read_packet::<(u32, As<str>), (u32, &str)>(buffer.as_slice()).unwrap();
+///     assert_eq!(value, (id, "ping"));
+/// }
+///
+/// let vec = buffer.into_inner();
+/// assert!(vec.capacity() >= 64);
+/// ```
 #[cfg(feature = "alloc")]
 pub struct VecBuffer<'a> {
-    buf: &'a mut Vec<u8>,
+    buf: VecStorage<'a>,
+    #[cfg(feature = "metrics")]
+    metrics: Option<&'a mut Metrics>,
+}
+
+#[cfg(feature = "alloc")]
+enum VecStorage<'a> {
+    Borrowed(&'a mut Vec<u8>),
+    Owned(Vec<u8>),
 }
 
 #[cfg(feature = "alloc")]
 impl<'a> VecBuffer<'a> {
     /// Creates a new buffer that writes to the given vector.
     pub fn new(buf: &'a mut Vec<u8>) -> Self {
-        VecBuffer { buf }
+        VecBuffer {
+            buf: VecStorage::Borrowed(buf),
+            #[cfg(feature = "metrics")]
+            metrics: None,
+        }
+    }
+
+    /// Creates a new buffer that writes to the given vector
+    /// and collects serialization statistics.
+    #[cfg(feature = "metrics")]
+    pub fn with_metrics(buf: &'a mut Vec<u8>, metrics: &'a mut Metrics) -> Self {
+        VecBuffer {
+            buf: VecStorage::Borrowed(buf),
+            metrics: Some(metrics),
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl VecBuffer<'static> {
+    /// Creates a new buffer that owns the given vector.
+    #[must_use]
+    pub fn from_vec(buf: Vec<u8>) -> Self {
+        VecBuffer {
+            buf: VecStorage::Owned(buf),
+            #[cfg(feature = "metrics")]
+            metrics: None,
+        }
+    }
+
+    /// Creates a new buffer that owns empty vector
+    /// with at least the specified capacity.
+    #[must_use]
+    pub fn with_capacity(capacity: usize) -> Self {
+        VecBuffer::from_vec(Vec::with_capacity(capacity))
+    }
+
+    /// Returns the vector.
+    /// Borrowed vector is taken, leaving it empty.
+    #[must_use]
+    pub fn into_inner(self) -> Vec<u8> {
+        match self.buf {
+            VecStorage::Borrowed(buf) => core::mem::take(buf),
+            VecStorage::Owned(buf) => buf,
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl From<Vec<u8>> for VecBuffer<'static> {
+    #[inline(always)]
+    fn from(buf: Vec<u8>) -> Self {
+        VecBuffer::from_vec(buf)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a> From<&'a mut Vec<u8>> for VecBuffer<'a> {
+    #[inline(always)]
+    fn from(buf: &'a mut Vec<u8>) -> Self {
+        VecBuffer::new(buf)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl VecBuffer<'_> {
+    /// Returns length of the vector.
+    ///
+    /// Serialization into empty buffer leaves it
+    /// as long as the written data.
+    /// Otherwise [`truncate`](VecBuffer::truncate) it
+    /// to the size returned by serialization.
+    #[must_use]
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.as_slice().len()
+    }
+
+    /// Returns `true` if the vector is empty.
+    #[must_use]
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns bytes of the vector.
+    #[must_use]
+    #[inline(always)]
+    pub fn as_slice(&self) -> &[u8] {
+        match &self.buf {
+            VecStorage::Borrowed(buf) => buf,
+            VecStorage::Owned(buf) => buf,
+        }
+    }
+
+    /// Shortens the vector to `len` bytes, keeping its capacity.
+    #[inline(always)]
+    pub fn truncate(&mut self, len: usize) {
+        self.vec().truncate(len);
+    }
+
+    /// Clears the vector, keeping its capacity,
+    /// so the buffer can be reused for the next message.
+    #[inline(always)]
+    pub fn clear(&mut self) {
+        self.vec().clear();
+    }
+
+    #[inline(always)]
+    fn vec(&mut self) -> &mut Vec<u8> {
+        match &mut self.buf {
+            VecStorage::Borrowed(buf) => buf,
+            VecStorage::Owned(buf) => buf,
+        }
     }
 }
 
 #[cfg(feature = "alloc")]
 impl VecBuffer<'_> {
     #[cold]
+    #[allow(clippy::absurd_extreme_comparisons)] // `PACKET_SIZE_LIMIT` may be `usize::MAX`.
     fn do_reserve(&mut self, heap: usize, stack: usize, additional: usize) {
-        let old_len = self.buf.len();
-        self.buf.resize(heap + stack + additional, 0);
-        let new_len = self.buf.len();
-        self.buf
-            .copy_within(old_len - stack..old_len, new_len - stack);
+        let buf = self.vec();
+        let old_len = buf.len();
+        let new_len = heap + stack + additional;
+        assert!(
+            new_len <= PACKET_SIZE_LIMIT,
+            "Serialized data exceeds `PACKET_SIZE_LIMIT`"
+        );
+        #[cfg(feature = "metrics")]
+        let old_capacity = buf.capacity();
+        buf.resize(new_len, 0);
+        buf.copy_within(old_len - stack..old_len, new_len - stack);
+        #[cfg(feature = "metrics")]
+        if buf.capacity() != old_capacity {
+            if let Some(metrics) = &mut self.metrics {
+                metrics.reallocations += 1;
+            }
+        }
     }
     /// Ensures that at least `additional` bytes
     /// can be written between first `heap` and last `stack` bytes.
     fn reserve(&mut self, heap: usize, stack: usize, additional: usize) {
-        let free = self.buf.len() - heap - stack;
+        let free = self.vec().len() - heap - stack;
         if free < additional {
             self.do_reserve(heap, stack, additional);
         }
@@ -379,37 +578,45 @@ impl<'a> Buffer for VecBuffer<'a> {
 
     #[inline(always)]
     fn reborrow(&mut self) -> Self::Reborrow<'_> {
-        VecBuffer { buf: self.buf }
+        let buf = match &mut self.buf {
+            VecStorage::Borrowed(buf) => &mut **buf,
+            VecStorage::Owned(buf) => buf,
+        };
+        VecBuffer {
+            buf: VecStorage::Borrowed(buf),
+            #[cfg(feature = "metrics")]
+            metrics: self.metrics.as_deref_mut(),
+        }
     }
 
     #[inline(always)]
     fn write_stack(&mut self, heap: usize, stack: usize, bytes: &[u8]) -> Result<(), Infallible> {
-        debug_assert!(heap + stack <= self.buf.len());
+        debug_assert!(heap + stack <= self.vec().len());
         self.reserve(heap, stack, bytes.len());
-        let at = self.buf.len() - stack - bytes.len();
-        self.buf[at..][..bytes.len()].copy_from_slice(bytes);
+        let buf = self.vec();
+        let at = buf.len() - stack - bytes.len();
+        buf[at..][..bytes.len()].copy_from_slice(bytes);
         Ok(())
     }
 
     #[inline(always)]
     fn pad_stack(&mut self, heap: usize, stack: usize, len: usize) -> Result<(), Infallible> {
-        debug_assert!(heap + stack <= self.buf.len());
+        debug_assert!(heap + stack <= self.vec().len());
         self.reserve(heap, stack, len);
 
-        #[cfg(test)]
-        {
-            let at = self.buf.len() - stack - len;
-            self.buf[at..][..len].fill(0);
-        }
+        let buf = self.vec();
+        let at = buf.len() - stack - len;
+        buf[at..][..len].fill(0);
         Ok(())
     }
 
     #[inline(always)]
     fn move_to_heap(&mut self, heap: usize, stack: usize, len: usize) {
-        debug_assert!(heap + stack <= self.buf.len());
+        debug_assert!(heap + stack <= self.vec().len());
         debug_assert!(stack >= len);
-        let at = self.buf.len() - stack;
-        self.buf.copy_within(at..at + len, heap);
+        let buf = self.vec();
+        let at = buf.len() - stack;
+        buf.copy_within(at..at + len, heap);
     }
 
     #[inline(always)]
@@ -419,8 +626,160 @@ impl<'a> Buffer for VecBuffer<'a> {
         stack: usize,
         len: usize,
     ) -> Result<&mut [u8], Infallible> {
-        debug_assert!(heap + stack <= self.buf.len());
+        debug_assert!(heap + stack <= self.vec().len());
         self.reserve(heap, stack, len);
-        Ok(&mut self.buf[..heap + len])
+        Ok(&mut self.vec()[..heap + len])
+    }
+
+    #[cfg(feature = "metrics")]
+    #[inline(always)]
+    fn metrics(&mut self) -> Option<&mut Metrics> {
+        self.metrics.as_deref_mut()
+    }
+}
+
+/// Object-safe counterpart of [`Buffer`].
+///
+/// Implemented for all buffers.
+/// `&mut dyn DynBuffer<Error = E>` implements `Buffer`,
+/// so serialization target may be chosen at runtime,
+/// e.g. by plugins that receive buffer from the host,
+/// without monomorphizing serialization code for each buffer type.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::{*, advanced::*};
+/// # use core::convert::Infallible;
+/// fn write_greeting(buffer: &mut dyn DynBuffer<Error = Infallible>) -> usize {
+///     write_packet_into::<As<str>, _, _>("hello", buffer).unwrap()
+/// }
+///
+/// let mut output = Vec::new();
+/// let size = write_greeting(&mut VecBuffer::new(&mut output));
+/// assert_eq!(read_packet::<As<str>, &str>(&output[..size]).unwrap().0, "hello");
+/// ```
+pub trait DynBuffer {
+    /// Write error.
+    type Error;
+
+    /// Writes bytes to the stack.
+    /// See [`Buffer::write_stack`].
+    ///
+    /// # Errors
+    ///
+    /// If buffer cannot write bytes, it should return `Err`.
+    fn dyn_write_stack(
+        &mut self,
+        heap: usize,
+        stack: usize,
+        bytes: &[u8],
+    ) -> Result<(), Self::Error>;
+
+    /// Add padding bytes to the stack.
+    /// See [`Buffer::pad_stack`].
+    ///
+    /// # Errors
+    ///
+    /// If buffer cannot add padding bytes, it should return `Err`.
+    fn dyn_pad_stack(&mut self, heap: usize, stack: usize, len: usize) -> Result<(), Self::Error>;
+
+    /// Moves bytes from stack to heap.
+    /// See [`Buffer::move_to_heap`].
+    fn dyn_move_to_heap(&mut self, heap: usize, stack: usize, len: usize);
+
+    /// Reserves heap space and returns a buffer over it.
+    /// See [`Buffer::reserve_heap`].
+    ///
+    /// # Errors
+    ///
+    /// If buffer cannot reserve heap space, it should return `Err`.
+    fn dyn_reserve_heap(
+        &mut self,
+        heap: usize,
+        stack: usize,
+        len: usize,
+    ) -> Result<&mut [u8], Self::Error>;
+
+    /// Returns statistics collected by this buffer, if any.
+    /// See [`Buffer::metrics`].
+    #[cfg(feature = "metrics")]
+    fn dyn_metrics(&mut self) -> Option<&mut Metrics>;
+}
+
+impl<B> DynBuffer for B
+where
+    B: Buffer,
+{
+    type Error = B::Error;
+
+    #[inline(always)]
+    fn dyn_write_stack(&mut self, heap: usize, stack: usize, bytes: &[u8]) -> Result<(), B::Error> {
+        self.write_stack(heap, stack, bytes)
+    }
+
+    #[inline(always)]
+    fn dyn_pad_stack(&mut self, heap: usize, stack: usize, len: usize) -> Result<(), B::Error> {
+        self.pad_stack(heap, stack, len)
+    }
+
+    #[inline(always)]
+    fn dyn_move_to_heap(&mut self, heap: usize, stack: usize, len: usize) {
+        self.move_to_heap(heap, stack, len);
+    }
+
+    #[inline(always)]
+    fn dyn_reserve_heap(
+        &mut self,
+        heap: usize,
+        stack: usize,
+        len: usize,
+    ) -> Result<&mut [u8], B::Error> {
+        self.reserve_heap(heap, stack, len)
+    }
+
+    #[cfg(feature = "metrics")]
+    #[inline(always)]
+    fn dyn_metrics(&mut self) -> Option<&mut Metrics> {
+        self.metrics()
+    }
+}
+
+impl<'a, 'b, E> Buffer for &'b mut (dyn DynBuffer<Error = E> + 'a) {
+    type Error = E;
+    type Reborrow<'c>
+        = &'c mut (dyn DynBuffer<Error = E> + 'a)
+    where
+        'b: 'c;
+
+    #[inline(always)]
+    fn reborrow(&mut self) -> Self::Reborrow<'_> {
+        &mut **self
+    }
+
+    #[inline(always)]
+    fn write_stack(&mut self, heap: usize, stack: usize, bytes: &[u8]) -> Result<(), E> {
+        (**self).dyn_write_stack(heap, stack, bytes)
+    }
+
+    #[inline(always)]
+    fn pad_stack(&mut self, heap: usize, stack: usize, len: usize) -> Result<(), E> {
+        (**self).dyn_pad_stack(heap, stack, len)
+    }
+
+    #[inline(always)]
+    fn move_to_heap(&mut self, heap: usize, stack: usize, len: usize) {
+        (**self).dyn_move_to_heap(heap, stack, len);
+    }
+
+    #[inline(always)]
+    fn reserve_heap(&mut self, heap: usize, stack: usize, len: usize) -> Result<&mut [u8], E> {
+        (**self).dyn_reserve_heap(heap, stack, len)
+    }
+
+    #[cfg(feature = "metrics")]
+    #[inline(always)]
+    fn metrics(&mut self) -> Option<&mut Metrics> {
+        (**self).dyn_metrics()
     }
 }
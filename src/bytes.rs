@@ -1,8 +1,9 @@
 use crate::{
     buffer::Buffer,
-    deserialize::{Deserialize, DeserializeError, Deserializer},
+    descriptor::{Descriptor, DescriptorKind},
+    deserialize::{Deserialize, DeserializeError, Deserializer, ErrorDetails},
     formula::{BareFormula, Formula},
-    serialize::{write_bytes, SerializeRef, Sizes},
+    serialize::{write_bytes, Serialize, SerializeRef, Sizes},
 };
 
 /// A formula for a raw byte slices.
@@ -13,6 +14,11 @@ impl Formula for Bytes {
     const MAX_STACK_SIZE: Option<usize> = None;
     const EXACT_SIZE: bool = false;
     const HEAPLESS: bool = true;
+
+    #[inline(always)]
+    fn describe() -> Descriptor {
+        Descriptor::new::<Bytes>(DescriptorKind::Bytes)
+    }
 }
 
 impl BareFormula for Bytes {}
@@ -44,3 +50,75 @@ impl<'de, 'fe: 'de> Deserialize<'fe, Bytes> for &'de [u8] {
         Ok(())
     }
 }
+
+impl<const N: usize> Serialize<Bytes> for [u8; N] {
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_bytes(&self, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        Some(Sizes::with_stack(N))
+    }
+}
+
+impl<const N: usize> Serialize<Bytes> for &[u8; N] {
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_bytes(self, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        Some(Sizes::with_stack(N))
+    }
+}
+
+/// Returns all bytes as array reference.
+/// Fails with `DeserializeError::WrongLength` if length is not `N`.
+#[inline(always)]
+fn read_exact_array<'de, const N: usize>(
+    de: Deserializer<'de>,
+) -> Result<&'de [u8; N], DeserializeError> {
+    let bytes = de.read_all_bytes();
+    match bytes.try_into() {
+        Ok(array) => Ok(array),
+        Err(_) => Err(DeserializeError::WrongLength(ErrorDetails::sizes(
+            N,
+            bytes.len(),
+        ))),
+    }
+}
+
+impl<'de, const N: usize> Deserialize<'de, Bytes> for [u8; N] {
+    #[inline(always)]
+    fn deserialize(de: Deserializer<'de>) -> Result<Self, DeserializeError> {
+        read_exact_array(de).copied()
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(&mut self, de: Deserializer<'de>) -> Result<(), DeserializeError> {
+        *self = *read_exact_array(de)?;
+        Ok(())
+    }
+}
+
+impl<'de, 'fe: 'de, const N: usize> Deserialize<'fe, Bytes> for &'de [u8; N] {
+    #[inline(always)]
+    fn deserialize(de: Deserializer<'fe>) -> Result<Self, DeserializeError> {
+        read_exact_array(de)
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(&mut self, de: Deserializer<'fe>) -> Result<(), DeserializeError> {
+        *self = read_exact_array(de)?;
+        Ok(())
+    }
+}
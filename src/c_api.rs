@@ -0,0 +1,293 @@
+//!
+//! This module provides C functions to exchange packets with registered messages.
+//!
+
+// Every function here dereferences pointers passed from C.
+#![allow(unsafe_code)]
+
+use alloc::collections::BTreeMap;
+use core::{
+    ffi::{c_int, c_void},
+    slice,
+};
+use std::sync::{PoisonError, RwLock};
+
+use crate::{
+    deserialize::{Deserialize, DeserializeError},
+    envelope::{Envelope, Enveloped, Opened, Tagged},
+    packet::{packet_size, read_packet, write_packet},
+    serialize::Serialize,
+};
+
+/// Operation succeeded.
+pub const ALKAHEST_OK: c_int = 0;
+
+/// Required pointer argument is null.
+pub const ALKAHEST_NULL_POINTER: c_int = 1;
+
+/// No message is registered with the tag.
+pub const ALKAHEST_UNKNOWN_TAG: c_int = 2;
+
+/// Output buffer is too small for the packet.
+/// Required size is written instead of the packet size.
+pub const ALKAHEST_BUFFER_TOO_SMALL: c_int = 3;
+
+/// Packet holds message with different tag.
+pub const ALKAHEST_WRONG_TAG: c_int = 4;
+
+/// Packet is malformed or incomplete.
+pub const ALKAHEST_DESERIALIZE_FAILED: c_int = 5;
+
+/// C declarations of the functions and error codes of [`ffi`](crate::ffi) module.
+pub const FFI_HEADER: &str = "\
+/* alkahest C API. */
+#ifndef ALKAHEST_FFI_H
+#define ALKAHEST_FFI_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#define ALKAHEST_OK 0
+#define ALKAHEST_NULL_POINTER 1
+#define ALKAHEST_UNKNOWN_TAG 2
+#define ALKAHEST_BUFFER_TOO_SMALL 3
+#define ALKAHEST_WRONG_TAG 4
+#define ALKAHEST_DESERIALIZE_FAILED 5
+
+int alkahest_value_size(uint32_t tag, size_t *size);
+int alkahest_write_packet(uint32_t tag, const void *value, uint8_t *output, size_t output_len, size_t *written);
+int alkahest_packet_tag(const uint8_t *input, size_t input_len, uint32_t *tag);
+int alkahest_read_packet(uint32_t tag, const uint8_t *input, size_t input_len, void *value, size_t *consumed);
+
+#endif /* ALKAHEST_FFI_H */
+";
+
+struct Entry {
+    size: usize,
+    write: unsafe fn(*const c_void, &mut [u8]) -> Result<usize, usize>,
+    read: unsafe fn(Opened<'_>, *mut c_void) -> Result<(), DeserializeError>,
+}
+
+static ENTRIES: RwLock<BTreeMap<u32, Entry>> = RwLock::new(BTreeMap::new());
+
+/// Registers message formula `F` for C functions of [`ffi`](crate::ffi) module.
+///
+/// C side passes pointers to values of `T`,
+/// so `T` should be `#[repr(C)]` and match declaration of the C structure.
+/// Messages are written in packets with [`Envelope`] formula,
+/// so Rust side can decode them with [`Registry`](crate::Registry).
+///
+/// # Panics
+///
+/// Panics if formula with the same tag is already registered.
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(feature = "derive")] {
+/// # use alkahest::{*, ffi::*};
+/// #[alkahest(Formula, Serialize, Deserialize)]
+/// #[derive(Clone, Copy)]
+/// #[repr(C)]
+/// struct Reading {
+///     sensor: u32,
+///     value: f32,
+/// }
+///
+/// impl Tagged for Reading {
+///     const TAG: u32 = 7;
+/// }
+///
+/// register_ffi::<Reading, Reading>();
+///
+/// let mut size = 0;
+/// assert_eq!(unsafe { alkahest_value_size(7, &mut size) }, ALKAHEST_OK);
+/// assert_eq!(size, core::mem::size_of::<Reading>());
+/// # }
+/// ```
+pub fn register_ffi<F, T>()
+where
+    F: Tagged + ?Sized,
+    T: Copy + Serialize<F> + for<'de> Deserialize<'de, F> + 'static,
+{
+    let entry = Entry {
+        size: core::mem::size_of::<T>(),
+        write: write_value::<F, T>,
+        read: read_value::<F, T>,
+    };
+
+    let mut entries = ENTRIES.write().unwrap_or_else(PoisonError::into_inner);
+    let old = entries.insert(F::TAG, entry);
+    assert!(
+        old.is_none(),
+        "Message tag {} is already registered",
+        F::TAG
+    );
+}
+
+unsafe fn write_value<F, T>(value: *const c_void, output: &mut [u8]) -> Result<usize, usize>
+where
+    F: Tagged + ?Sized,
+    T: Copy + Serialize<F>,
+{
+    let value = value.cast::<T>().read_unaligned();
+    write_packet::<Envelope, _>(Enveloped::<F, T>::new(value), output)
+        .map_err(|_| packet_size::<Envelope, _>(Enveloped::<F, T>::new(value)))
+}
+
+unsafe fn read_value<F, T>(opened: Opened<'_>, value: *mut c_void) -> Result<(), DeserializeError>
+where
+    F: Tagged + ?Sized,
+    T: for<'de> Deserialize<'de, F>,
+{
+    let decoded = opened.decode::<F, T>()?;
+    value.cast::<T>().write_unaligned(decoded);
+    Ok(())
+}
+
+/// Returns slice for pointer and length passed from C.
+unsafe fn bytes<'a>(ptr: *const u8, len: usize) -> Option<&'a [u8]> {
+    match (ptr.is_null(), len) {
+        (_, 0) => Some(&[]),
+        (true, _) => None,
+        (false, _) => Some(slice::from_raw_parts(ptr, len)),
+    }
+}
+
+/// Writes size of the value type registered with the tag into `size`.
+///
+/// # Safety
+///
+/// `size` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn alkahest_value_size(tag: u32, size: *mut usize) -> c_int {
+    if size.is_null() {
+        return ALKAHEST_NULL_POINTER;
+    }
+
+    let entries = ENTRIES.read().unwrap_or_else(PoisonError::into_inner);
+    match entries.get(&tag) {
+        None => ALKAHEST_UNKNOWN_TAG,
+        Some(entry) => {
+            size.write(entry.size);
+            ALKAHEST_OK
+        }
+    }
+}
+
+/// Writes packet with the message registered with the tag into `output`.
+/// Writes the packet size into `written`,
+/// or required size if `output` is too small.
+///
+/// # Safety
+///
+/// `value` must be null or point to the value of the type registered with the tag.
+/// `output` must be valid for writes of `output_len` bytes.
+/// `written` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn alkahest_write_packet(
+    tag: u32,
+    value: *const c_void,
+    output: *mut u8,
+    output_len: usize,
+    written: *mut usize,
+) -> c_int {
+    if value.is_null() || written.is_null() || (output.is_null() && output_len > 0) {
+        return ALKAHEST_NULL_POINTER;
+    }
+    let output = match output_len {
+        0 => &mut [],
+        _ => slice::from_raw_parts_mut(output, output_len),
+    };
+
+    let entries = ENTRIES.read().unwrap_or_else(PoisonError::into_inner);
+    let Some(entry) = entries.get(&tag) else {
+        return ALKAHEST_UNKNOWN_TAG;
+    };
+
+    match (entry.write)(value, output) {
+        Ok(size) => {
+            written.write(size);
+            ALKAHEST_OK
+        }
+        Err(required) => {
+            written.write(required);
+            ALKAHEST_BUFFER_TOO_SMALL
+        }
+    }
+}
+
+/// Writes tag of the message in the packet into `tag`.
+///
+/// # Safety
+///
+/// `input` must be valid for reads of `input_len` bytes.
+/// `tag` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn alkahest_packet_tag(
+    input: *const u8,
+    input_len: usize,
+    tag: *mut u32,
+) -> c_int {
+    let Some(input) = bytes(input, input_len) else {
+        return ALKAHEST_NULL_POINTER;
+    };
+    if tag.is_null() {
+        return ALKAHEST_NULL_POINTER;
+    }
+
+    match read_packet::<Envelope, Opened>(input) {
+        Ok((opened, _)) => {
+            tag.write(opened.tag());
+            ALKAHEST_OK
+        }
+        Err(_) => ALKAHEST_DESERIALIZE_FAILED,
+    }
+}
+
+/// Reads packet with the message registered with the tag from `input`
+/// into `value` and writes number of bytes consumed into `consumed`.
+///
+/// Use [`alkahest_packet_tag`] to find out which message the packet holds.
+///
+/// # Safety
+///
+/// `input` must be valid for reads of `input_len` bytes.
+/// `value` must be null or valid for writes of the type registered with the tag.
+/// `consumed` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn alkahest_read_packet(
+    tag: u32,
+    input: *const u8,
+    input_len: usize,
+    value: *mut c_void,
+    consumed: *mut usize,
+) -> c_int {
+    let Some(input) = bytes(input, input_len) else {
+        return ALKAHEST_NULL_POINTER;
+    };
+    if value.is_null() || consumed.is_null() {
+        return ALKAHEST_NULL_POINTER;
+    }
+
+    let entries = ENTRIES.read().unwrap_or_else(PoisonError::into_inner);
+    let Some(entry) = entries.get(&tag) else {
+        return ALKAHEST_UNKNOWN_TAG;
+    };
+
+    let (opened, size) = match read_packet::<Envelope, Opened>(input) {
+        Ok(packet) => packet,
+        Err(_) => return ALKAHEST_DESERIALIZE_FAILED,
+    };
+    if opened.tag() != tag {
+        return ALKAHEST_WRONG_TAG;
+    }
+
+    match (entry.read)(opened, value) {
+        Ok(()) => {
+            consumed.write(size);
+            ALKAHEST_OK
+        }
+        Err(_) => ALKAHEST_DESERIALIZE_FAILED,
+    }
+}
@@ -0,0 +1,112 @@
+use core::cell::Cell;
+
+use crate::{
+    buffer::Buffer,
+    formula::BareFormula,
+    serialize::{Serialize, SerializeRef, Sizes},
+};
+
+/// Wrapper that computes sizes of the wrapped value at most once.
+///
+/// Sizes of a value are requested by each enclosing `Ref`, `Vec`,
+/// or unbounded field before the value is serialized.
+/// For values that walk their data in `size_hint`, like collections
+/// of variable-size elements or large derived structures,
+/// this means the data is traversed several times.
+/// `SizeCached` remembers the first result and returns it for later calls.
+///
+/// The cache is not invalidated, so the value must not change
+/// between `size_hint` and `serialize`.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// let value = SizeCached::new(vec![vec![1u32, 2], vec![3]]);
+///
+/// let mut buffer = Vec::new();
+/// let size = write_packet_to_vec::<Vec<Vec<u32>>, _>(value, &mut buffer);
+/// let (value, _) = read_packet::<Vec<Vec<u32>>, Vec<Vec<u32>>>(&buffer[..size]).unwrap();
+/// assert_eq!(value, [vec![1, 2], vec![3]]);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct SizeCached<T> {
+    value: T,
+    sizes: Cell<Option<Option<Sizes>>>,
+}
+
+impl<T> SizeCached<T> {
+    /// Wraps the value.
+    #[must_use]
+    #[inline(always)]
+    pub const fn new(value: T) -> Self {
+        SizeCached {
+            value,
+            sizes: Cell::new(None),
+        }
+    }
+
+    /// Returns reference to the wrapped value.
+    #[must_use]
+    #[inline(always)]
+    pub const fn get(&self) -> &T {
+        &self.value
+    }
+
+    /// Unwraps the value.
+    #[must_use]
+    #[inline(always)]
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+
+    #[inline(always)]
+    fn cached(&self, size_hint: impl FnOnce(&T) -> Option<Sizes>) -> Option<Sizes> {
+        match self.sizes.get() {
+            Some(sizes) => sizes,
+            None => {
+                let sizes = size_hint(&self.value);
+                self.sizes.set(Some(sizes));
+                sizes
+            }
+        }
+    }
+}
+
+impl<F, T> Serialize<F> for SizeCached<T>
+where
+    F: BareFormula + ?Sized,
+    T: Serialize<F>,
+{
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        <T as Serialize<F>>::serialize(self.value, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        self.cached(<T as Serialize<F>>::size_hint)
+    }
+}
+
+impl<F, T> SerializeRef<F> for SizeCached<T>
+where
+    F: BareFormula + ?Sized,
+    T: SerializeRef<F>,
+{
+    #[inline(always)]
+    fn serialize<B>(&self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        <T as SerializeRef<F>>::serialize(&self.value, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        self.cached(<T as SerializeRef<F>>::size_hint)
+    }
+}
@@ -0,0 +1,159 @@
+//!
+//! This module provides `tokio-util` codec for streams of packets.
+//!
+
+use core::{fmt, marker::PhantomData};
+use std::io;
+
+use ::bytes::{Bytes, BytesMut};
+use tokio_util::codec::{Decoder, Encoder, LengthDelimitedCodec};
+
+use crate::{
+    deserialize::{Deserialize, DeserializeError, ErrorDetails},
+    formula::Formula,
+    packet::{read_packet, write_packet_to_vec},
+    serialize::Serialize,
+};
+
+/// Error of [`AlkahestCodec`].
+#[derive(Debug)]
+pub enum CodecError {
+    /// I/O error or invalid frame,
+    /// e.g. frame that exceeds maximum frame size.
+    Io(io::Error),
+
+    /// Frame failed to deserialize.
+    Deserialize(DeserializeError),
+}
+
+impl From<io::Error> for CodecError {
+    #[inline(always)]
+    fn from(err: io::Error) -> Self {
+        CodecError::Io(err)
+    }
+}
+
+impl From<DeserializeError> for CodecError {
+    #[inline(always)]
+    fn from(err: DeserializeError) -> Self {
+        CodecError::Deserialize(err)
+    }
+}
+
+impl fmt::Display for CodecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CodecError::Io(err) => write!(f, "codec I/O error: {err}"),
+            CodecError::Deserialize(err) => write!(f, "frame deserialization error: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for CodecError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CodecError::Io(err) => Some(err),
+            CodecError::Deserialize(err) => Some(err),
+        }
+    }
+}
+
+/// Codec for streams of values of formula `F`.
+///
+/// Each value is written as a packet prefixed with its length
+/// as big-endian `u32`, the same framing as [`LengthDelimitedCodec`] uses by default.
+/// Encodes any `Serialize<F>` value and decodes into `T`.
+///
+/// Use with `tokio_util::codec::Framed` to turn byte stream
+/// into stream and sink of values.
+pub struct AlkahestCodec<F: ?Sized, T> {
+    framing: LengthDelimitedCodec,
+    marker: PhantomData<fn(&F) -> T>,
+}
+
+impl<F, T> Default for AlkahestCodec<F, T>
+where
+    F: Formula + ?Sized,
+{
+    #[inline(always)]
+    fn default() -> Self {
+        AlkahestCodec::new()
+    }
+}
+
+impl<F, T> AlkahestCodec<F, T>
+where
+    F: Formula + ?Sized,
+{
+    /// Returns new codec with default maximum frame size of 8 MiB.
+    #[must_use]
+    #[inline(always)]
+    pub fn new() -> Self {
+        AlkahestCodec {
+            framing: LengthDelimitedCodec::new(),
+            marker: PhantomData,
+        }
+    }
+
+    /// Returns new codec with specified maximum frame size.
+    #[must_use]
+    #[inline(always)]
+    pub fn with_max_frame_size(max_frame_size: usize) -> Self {
+        let mut codec = AlkahestCodec::new();
+        codec.set_max_frame_size(max_frame_size);
+        codec
+    }
+
+    /// Returns maximum frame size.
+    #[must_use]
+    #[inline(always)]
+    pub fn max_frame_size(&self) -> usize {
+        self.framing.max_frame_length()
+    }
+
+    /// Sets maximum frame size.
+    /// Larger frames are rejected both when encoding and decoding.
+    #[inline(always)]
+    pub fn set_max_frame_size(&mut self, max_frame_size: usize) {
+        self.framing.set_max_frame_length(max_frame_size);
+    }
+}
+
+impl<F, T, U> Encoder<U> for AlkahestCodec<F, T>
+where
+    F: Formula + ?Sized,
+    U: Serialize<F>,
+{
+    type Error = CodecError;
+
+    fn encode(&mut self, item: U, dst: &mut BytesMut) -> Result<(), CodecError> {
+        let mut packet = Vec::new();
+        let size = write_packet_to_vec::<F, U>(item, &mut packet);
+        packet.truncate(size);
+        self.framing.encode(Bytes::from(packet), dst)?;
+        Ok(())
+    }
+}
+
+impl<F, T> Decoder for AlkahestCodec<F, T>
+where
+    F: Formula + ?Sized,
+    T: for<'de> Deserialize<'de, F>,
+{
+    type Item = T;
+    type Error = CodecError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<T>, CodecError> {
+        match self.framing.decode(src)? {
+            None => Ok(None),
+            Some(frame) => {
+                let (value, size) = read_packet::<F, T>(&frame)?;
+                if size != frame.len() {
+                    let err = DeserializeError::WrongLength(ErrorDetails::sizes(size, frame.len()));
+                    return Err(CodecError::Deserialize(err.with_formula::<F>()));
+                }
+                Ok(Some(value))
+            }
+        }
+    }
+}
@@ -0,0 +1,598 @@
+//!
+//! This module provides code generation from schema files.
+//!
+
+use alloc::{
+    borrow::ToOwned,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt::{self, Write};
+
+use crate::schema::{
+    node_children_valid, Schema, SchemaField, SchemaKind, SchemaNode, SchemaVariant,
+};
+
+/// Error that can occur when schema file is parsed
+/// or Rust code is generated from it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SchemaParseError {
+    /// Byte offset in the input where error is detected.
+    pub offset: usize,
+
+    /// Description of the error.
+    pub message: &'static str,
+}
+
+impl fmt::Display for SchemaParseError {
+    #[inline(always)]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at offset {}", self.message, self.offset)
+    }
+}
+
+impl Schema {
+    /// Parses schema from JSON document produced by [`Schema::to_json`].
+    ///
+    /// # Errors
+    ///
+    /// Returns `SchemaParseError` if document is not valid JSON
+    /// or does not describe a schema.
+    pub fn from_json(json: &str) -> Result<Self, SchemaParseError> {
+        let mut parser = JsonParser {
+            input: json,
+            offset: 0,
+        };
+        let document = parser.value()?;
+        parser.skip_ws();
+        if parser.offset != json.len() {
+            return Err(parser.error("trailing characters"));
+        }
+
+        let nodes = document
+            .field("nodes")?
+            .array()?
+            .iter()
+            .map(json_node)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let len = nodes.len();
+        if len == 0 || nodes.iter().any(|node| !node_children_valid(node, len)) {
+            return Err(SchemaParseError {
+                offset: 0,
+                message: "node index out of bounds",
+            });
+        }
+
+        Ok(Schema { nodes })
+    }
+
+    /// Generates Rust code that defines formula types of the schema.
+    ///
+    /// Structures and enums are emitted with `#[alkahest(Formula)]` attribute,
+    /// so the code requires "derive" feature.
+    /// Other formulas are mapped to formula types provided by the crate.
+    /// `Vec` and `String` are emitted as [`Ref`](crate::Ref) with the same layout.
+    /// Opaque formulas are referred by name and must be in scope.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SchemaParseError` if schema can't be expressed with derived formulas.
+    /// For example if enum tags are not sequential.
+    pub fn to_rust(&self) -> Result<String, SchemaParseError> {
+        let names = self
+            .idl_names()
+            .iter()
+            .map(|name| ident(name))
+            .collect::<Vec<_>>();
+
+        let mut out = String::new();
+        RustWriter {
+            schema: self,
+            names: &names,
+            out: &mut out,
+        }
+        .write()?;
+        Ok(out)
+    }
+}
+
+/// Generates Rust formula types from JSON schema produced by [`Schema::to_json`].
+///
+/// Intended to be called from build scripts to keep formulas
+/// of multiple services in a single schema file.
+///
+/// ```ignore
+/// // build.rs
+/// let json = std::fs::read_to_string("schema.json").unwrap();
+/// let code = alkahest::generate_rust(&json).unwrap();
+/// let out = std::path::Path::new(&std::env::var("OUT_DIR").unwrap()).join("schema.rs");
+/// std::fs::write(out, code).unwrap();
+///
+/// // lib.rs
+/// include!(concat!(env!("OUT_DIR"), "/schema.rs"));
+/// ```
+///
+/// # Errors
+///
+/// Returns `SchemaParseError` if schema can't be parsed
+/// or can't be expressed with derived formulas.
+pub fn generate_rust(json: &str) -> Result<String, SchemaParseError> {
+    Schema::from_json(json)?.to_rust()
+}
+
+/// Turns type name into identifier.
+pub(crate) fn ident(name: &str) -> String {
+    let mut ident = String::with_capacity(name.len());
+    for c in name.chars() {
+        if c.is_alphanumeric() || c == '_' {
+            ident.push(c);
+        } else if !ident.ends_with('_') {
+            ident.push('_');
+        }
+    }
+    let ident = ident.trim_end_matches('_');
+    if ident.is_empty() || ident.starts_with(|c: char| c.is_ascii_digit()) {
+        return "_".to_owned() + ident;
+    }
+    ident.to_owned()
+}
+
+struct RustWriter<'a> {
+    schema: &'a Schema,
+    names: &'a [String],
+    out: &'a mut String,
+}
+
+impl RustWriter<'_> {
+    fn error(message: &'static str) -> SchemaParseError {
+        SchemaParseError { offset: 0, message }
+    }
+
+    fn write(&mut self) -> Result<(), SchemaParseError> {
+        self.out
+            .push_str("// Generated from alkahest schema. Do not edit.\n");
+
+        for (idx, node) in self.schema.nodes.iter().enumerate() {
+            match &node.kind {
+                SchemaKind::Struct(fields) => {
+                    let _ = write!(
+                        self.out,
+                        "\n#[::alkahest::alkahest(Formula)]\npub struct {}",
+                        self.names[idx]
+                    );
+                    self.fields(fields, "pub ")?;
+                    if fields.is_empty() || is_tuple(fields) {
+                        self.out.push(';');
+                    }
+                    self.out.push('\n');
+                }
+                SchemaKind::Enum(variants) => {
+                    let _ = writeln!(
+                        self.out,
+                        "\n#[::alkahest::alkahest(Formula)]\npub enum {} {{",
+                        self.names[idx]
+                    );
+                    self.variants(variants)?;
+                    self.out.push_str("}\n");
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn variants(&mut self, variants: &[SchemaVariant]) -> Result<(), SchemaParseError> {
+        // Derived enums assign tags sequentially.
+        for (tag, variant) in variants.iter().enumerate() {
+            if u32::try_from(tag) != Ok(variant.tag) {
+                return Err(Self::error("enum tags are not sequential"));
+            }
+            let _ = write!(self.out, "    {}", ident(&variant.name));
+            self.fields(&variant.fields, "")?;
+            self.out.push_str(",\n");
+        }
+        Ok(())
+    }
+
+    fn fields(&mut self, fields: &[SchemaField], vis: &str) -> Result<(), SchemaParseError> {
+        if fields.is_empty() {
+            return Ok(());
+        }
+
+        let tuple = is_tuple(fields);
+        self.out.push_str(if tuple { "(" } else { " {\n" });
+        for (idx, field) in fields.iter().enumerate() {
+            if tuple {
+                if idx > 0 {
+                    self.out.push_str(", ");
+                }
+                self.out.push_str(vis);
+            } else {
+                let indent = if vis.is_empty() { "        " } else { "    " };
+                let _ = write!(self.out, "{indent}{vis}{}: ", ident(&field.name));
+            }
+            self.expr(field.formula, true)?;
+            if !tuple {
+                self.out.push_str(",\n");
+            }
+        }
+        if tuple {
+            self.out.push(')');
+        } else {
+            self.out
+                .push_str(if vis.is_empty() { "    }" } else { "}" });
+        }
+        Ok(())
+    }
+
+    /// Writes type expression for the node.
+    /// Unsized formulas are wrapped into `As` where sized type is required.
+    fn expr(&mut self, idx: usize, sized: bool) -> Result<(), SchemaParseError> {
+        let node = &self.schema.nodes[idx];
+        let unsized_kind = matches!(
+            node.kind,
+            SchemaKind::Bytes | SchemaKind::Str | SchemaKind::Slice(_)
+        );
+        if sized && unsized_kind {
+            self.out.push_str("::alkahest::As<");
+            self.expr(idx, false)?;
+            self.out.push('>');
+            return Ok(());
+        }
+
+        match &node.kind {
+            SchemaKind::Opaque | SchemaKind::Struct(_) | SchemaKind::Enum(_) => {
+                self.out.push_str(&self.names[idx]);
+            }
+            SchemaKind::Bool => self.out.push_str("bool"),
+            SchemaKind::Unsigned(size) => self.primitive('u', *size, &[1, 2, 4, 8, 16])?,
+            SchemaKind::Signed(size) => self.primitive('i', *size, &[1, 2, 4, 8, 16])?,
+            SchemaKind::Float(size) => self.primitive('f', *size, &[4, 8])?,
+            SchemaKind::Vlq => self.out.push_str("::alkahest::Vlq"),
+            SchemaKind::Bytes => self.out.push_str("::alkahest::Bytes"),
+            SchemaKind::Str => self.out.push_str("str"),
+            SchemaKind::Array(elem, len) => {
+                self.out.push('[');
+                self.expr(*elem, true)?;
+                let _ = write!(self.out, "; {len}]");
+            }
+            SchemaKind::Slice(elem) => {
+                self.out.push('[');
+                self.expr(*elem, true)?;
+                self.out.push(']');
+            }
+            SchemaKind::Ref(formula) => {
+                self.out.push_str("::alkahest::Ref<");
+                self.expr(*formula, false)?;
+                self.out.push('>');
+            }
+            SchemaKind::Option(formula) => {
+                self.out.push_str("Option<");
+                self.expr(*formula, true)?;
+                self.out.push('>');
+            }
+            SchemaKind::Tuple(elems) => {
+                self.out.push('(');
+                for (idx, elem) in elems.iter().enumerate() {
+                    if idx > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.expr(*elem, sized || idx + 1 < elems.len())?;
+                }
+                if elems.len() == 1 {
+                    self.out.push(',');
+                }
+                self.out.push(')');
+            }
+        }
+        Ok(())
+    }
+
+    fn primitive(
+        &mut self,
+        prefix: char,
+        size: usize,
+        sizes: &[usize],
+    ) -> Result<(), SchemaParseError> {
+        if !sizes.contains(&size) {
+            return Err(Self::error("unsupported primitive size"));
+        }
+        let _ = write!(self.out, "{prefix}{}", size * 8);
+        Ok(())
+    }
+}
+
+/// Fields of tuple structures and variants are named by index.
+pub(crate) fn is_tuple(fields: &[SchemaField]) -> bool {
+    fields
+        .iter()
+        .enumerate()
+        .all(|(idx, field)| field.name == idx.to_string())
+}
+
+enum Json {
+    Null,
+    Bool(bool),
+    Number(usize, usize),
+    String(String, usize),
+    Array(Vec<Json>, usize),
+    Object(Vec<(String, Json)>, usize),
+}
+
+impl Json {
+    fn offset(&self) -> usize {
+        match *self {
+            Json::Null | Json::Bool(_) => 0,
+            Json::Number(_, offset)
+            | Json::String(_, offset)
+            | Json::Array(_, offset)
+            | Json::Object(_, offset) => offset,
+        }
+    }
+
+    fn error(&self, message: &'static str) -> SchemaParseError {
+        SchemaParseError {
+            offset: self.offset(),
+            message,
+        }
+    }
+
+    fn field(&self, name: &str) -> Result<&Json, SchemaParseError> {
+        match self {
+            Json::Object(fields, _) => fields
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value)
+                .ok_or_else(|| self.error("missing property")),
+            _ => Err(self.error("object expected")),
+        }
+    }
+
+    fn array(&self) -> Result<&[Json], SchemaParseError> {
+        match self {
+            Json::Array(elems, _) => Ok(elems),
+            _ => Err(self.error("array expected")),
+        }
+    }
+
+    fn number(&self) -> Result<usize, SchemaParseError> {
+        match *self {
+            Json::Number(value, _) => Ok(value),
+            _ => Err(self.error("number expected")),
+        }
+    }
+
+    fn bool(&self) -> Result<bool, SchemaParseError> {
+        match *self {
+            Json::Bool(value) => Ok(value),
+            _ => Err(self.error("boolean expected")),
+        }
+    }
+
+    fn string(&self) -> Result<&str, SchemaParseError> {
+        match self {
+            Json::String(value, _) => Ok(value),
+            _ => Err(self.error("string expected")),
+        }
+    }
+}
+
+fn json_fields(json: &Json) -> Result<Vec<SchemaField>, SchemaParseError> {
+    json.array()?
+        .iter()
+        .map(|field| {
+            Ok(SchemaField {
+                name: field.field("name")?.string()?.to_owned(),
+                formula: field.field("formula")?.number()?,
+            })
+        })
+        .collect()
+}
+
+fn json_node(json: &Json) -> Result<SchemaNode, SchemaParseError> {
+    let max_stack_size = match json.field("max_stack_size")? {
+        Json::Null => None,
+        size => Some(size.number()?),
+    };
+
+    let kind_json = json.field("kind")?;
+    let kind = match kind_json.string()? {
+        "opaque" => SchemaKind::Opaque,
+        "bool" => SchemaKind::Bool,
+        "unsigned" => SchemaKind::Unsigned(json.field("size")?.number()?),
+        "signed" => SchemaKind::Signed(json.field("size")?.number()?),
+        "float" => SchemaKind::Float(json.field("size")?.number()?),
+        "vlq" => SchemaKind::Vlq,
+        "bytes" => SchemaKind::Bytes,
+        "str" => SchemaKind::Str,
+        "array" => SchemaKind::Array(json.field("elem")?.number()?, json.field("len")?.number()?),
+        "slice" => SchemaKind::Slice(json.field("elem")?.number()?),
+        "ref" => SchemaKind::Ref(json.field("formula")?.number()?),
+        "option" => SchemaKind::Option(json.field("formula")?.number()?),
+        "tuple" => SchemaKind::Tuple(
+            json.field("elems")?
+                .array()?
+                .iter()
+                .map(Json::number)
+                .collect::<Result<_, _>>()?,
+        ),
+        "struct" => SchemaKind::Struct(json_fields(json.field("fields")?)?),
+        "enum" => SchemaKind::Enum(
+            json.field("variants")?
+                .array()?
+                .iter()
+                .map(|variant| {
+                    let tag = variant.field("tag")?;
+                    Ok(SchemaVariant {
+                        name: variant.field("name")?.string()?.to_owned(),
+                        tag: u32::try_from(tag.number()?)
+                            .map_err(|_| tag.error("tag is out of range"))?,
+                        fields: json_fields(variant.field("fields")?)?,
+                    })
+                })
+                .collect::<Result<_, _>>()?,
+        ),
+        _ => return Err(kind_json.error("unknown kind")),
+    };
+
+    Ok(SchemaNode {
+        name: json.field("name")?.string()?.to_owned(),
+        max_stack_size,
+        exact_size: json.field("exact_size")?.bool()?,
+        heapless: json.field("heapless")?.bool()?,
+        kind,
+    })
+}
+
+struct JsonParser<'a> {
+    input: &'a str,
+    offset: usize,
+}
+
+impl<'a> JsonParser<'a> {
+    fn error(&self, message: &'static str) -> SchemaParseError {
+        SchemaParseError {
+            offset: self.offset,
+            message,
+        }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.offset..]
+    }
+
+    fn skip_ws(&mut self) {
+        let rest = self.rest();
+        self.offset += rest.len() - rest.trim_start().len();
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_ws();
+        if self.rest().starts_with(token) {
+            self.offset += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &str, message: &'static str) -> Result<(), SchemaParseError> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(self.error(message))
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, SchemaParseError> {
+        self.skip_ws();
+        let offset = self.offset;
+
+        if self.eat("null") {
+            return Ok(Json::Null);
+        }
+        if self.eat("true") {
+            return Ok(Json::Bool(true));
+        }
+        if self.eat("false") {
+            return Ok(Json::Bool(false));
+        }
+        if self.rest().starts_with('"') {
+            return Ok(Json::String(self.string()?, offset));
+        }
+
+        if self.eat("[") {
+            let mut elems = Vec::new();
+            if !self.eat("]") {
+                loop {
+                    elems.push(self.value()?);
+                    if self.eat("]") {
+                        break;
+                    }
+                    self.expect(",", "expected ',' or ']'")?;
+                }
+            }
+            return Ok(Json::Array(elems, offset));
+        }
+
+        if self.eat("{") {
+            let mut fields = Vec::new();
+            if !self.eat("}") {
+                loop {
+                    self.skip_ws();
+                    let key = self.string()?;
+                    self.expect(":", "expected ':'")?;
+                    fields.push((key, self.value()?));
+                    if self.eat("}") {
+                        break;
+                    }
+                    self.expect(",", "expected ',' or '}'")?;
+                }
+            }
+            return Ok(Json::Object(fields, offset));
+        }
+
+        let digits = self.rest().len()
+            - self
+                .rest()
+                .trim_start_matches(|c: char| c.is_ascii_digit())
+                .len();
+        if digits == 0 {
+            return Err(self.error("unexpected character"));
+        }
+        let number = self.rest()[..digits]
+            .parse()
+            .map_err(|_| self.error("number is out of range"))?;
+        self.offset += digits;
+        Ok(Json::Number(number, offset))
+    }
+
+    fn string(&mut self) -> Result<String, SchemaParseError> {
+        if !self.rest().starts_with('"') {
+            return Err(self.error("string expected"));
+        }
+        self.offset += 1;
+
+        let mut out = String::new();
+        loop {
+            let mut chars = self.rest().chars();
+            let c = chars
+                .next()
+                .ok_or_else(|| self.error("unterminated string"))?;
+            self.offset += c.len_utf8();
+
+            match c {
+                '"' => return Ok(out),
+                '\\' => {
+                    let escape = chars
+                        .next()
+                        .ok_or_else(|| self.error("unterminated string"))?;
+                    self.offset += escape.len_utf8();
+                    match escape {
+                        '"' => out.push('"'),
+                        '\\' => out.push('\\'),
+                        '/' => out.push('/'),
+                        'n' => out.push('\n'),
+                        'r' => out.push('\r'),
+                        't' => out.push('\t'),
+                        'b' => out.push('\u{8}'),
+                        'f' => out.push('\u{c}'),
+                        'u' => {
+                            let code = self
+                                .rest()
+                                .get(..4)
+                                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                                .and_then(char::from_u32)
+                                .ok_or_else(|| self.error("invalid unicode escape"))?;
+                            self.offset += 4;
+                            out.push(code);
+                        }
+                        _ => return Err(self.error("invalid escape")),
+                    }
+                }
+                c => out.push(c),
+            }
+        }
+    }
+}
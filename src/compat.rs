@@ -0,0 +1,476 @@
+//!
+//! This module provides compatibility checks between schemas.
+//!
+
+use alloc::{collections::BTreeSet, format, string::String, vec::Vec};
+
+use crate::schema::{Schema, SchemaField, SchemaKind, SchemaNode, SchemaVariant};
+
+/// Compatibility level of data written with one schema
+/// and read with another.
+///
+/// Levels are ordered from the best to the worst.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Compatibility {
+    /// Any value written by the writer can be read by the reader.
+    Compatible,
+
+    /// Reader ignores part of the data or fails to read some values.
+    /// For example when writer has trailing fields unknown to reader
+    /// or variants that reader does not have.
+    Lossy,
+
+    /// Reader can't read data produced by the writer.
+    Incompatible,
+}
+
+/// Kind of difference between writer and reader schemas.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SchemaChangeKind {
+    /// Formula kind differs.
+    KindChanged {
+        /// Kind name in writer schema.
+        writer: &'static str,
+
+        /// Kind name in reader schema.
+        reader: &'static str,
+    },
+
+    /// Size of a primitive or length of an array differs.
+    SizeChanged {
+        /// Size in writer schema.
+        writer: usize,
+
+        /// Size in reader schema.
+        reader: usize,
+    },
+
+    /// Stack size or exactness of the formula differs
+    /// in a position where it affects the layout.
+    LayoutChanged,
+
+    /// Opaque formulas differ by name.
+    OpaqueChanged,
+
+    /// Reader has a field that writer does not.
+    FieldAdded {
+        /// Name of the field.
+        name: String,
+    },
+
+    /// Writer has a field that reader does not.
+    FieldRemoved {
+        /// Name of the field.
+        name: String,
+    },
+
+    /// Field at the same position is named differently.
+    FieldRenamed {
+        /// Name in writer schema.
+        writer: String,
+
+        /// Name in reader schema.
+        reader: String,
+    },
+
+    /// Reader has a variant that writer does not.
+    VariantAdded {
+        /// Name of the variant.
+        name: String,
+
+        /// Tag of the variant.
+        tag: u32,
+    },
+
+    /// Writer has a variant that reader does not.
+    VariantRemoved {
+        /// Name of the variant.
+        name: String,
+
+        /// Tag of the variant.
+        tag: u32,
+    },
+
+    /// Variant with the same tag is named differently.
+    VariantRenamed {
+        /// Name in writer schema.
+        writer: String,
+
+        /// Name in reader schema.
+        reader: String,
+
+        /// Tag of the variant.
+        tag: u32,
+    },
+}
+
+/// Single difference between writer and reader schemas.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SchemaChange {
+    /// Path to the changed formula from the root.
+    ///
+    /// Consists of `.field` for fields and tuple elements,
+    /// `::Variant` for enum variants,
+    /// `[]` for array and slice elements and `?` for optional values.
+    /// Empty for the root formula.
+    pub path: String,
+
+    /// What has changed.
+    pub kind: SchemaChangeKind,
+
+    /// How the change affects reading.
+    pub compatibility: Compatibility,
+}
+
+/// Result of [`check_compatibility`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CompatibilityReport {
+    /// Overall compatibility. The worst of all changes.
+    pub compatibility: Compatibility,
+
+    /// All differences found.
+    pub changes: Vec<SchemaChange>,
+}
+
+impl CompatibilityReport {
+    /// Returns true if any value written by the writer can be read by the reader.
+    #[must_use]
+    #[inline(always)]
+    pub fn is_compatible(&self) -> bool {
+        self.compatibility == Compatibility::Compatible
+    }
+}
+
+/// Checks whether data written with `writer` schema
+/// can be read with `reader` schema.
+///
+/// Schemas are compared by layout.
+/// Fields are matched by position and variants are matched by tag,
+/// names are only reported when they differ.
+/// Root value is assumed to be written as a packet or a reference.
+///
+/// # Panics
+///
+/// Panics if either schema refers to missing nodes.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// let writer = Schema::of::<(u32, u8, u8)>();
+/// let reader = Schema::of::<(u32, u8)>();
+///
+/// let report = check_compatibility(&writer, &reader);
+/// assert_eq!(report.compatibility, Compatibility::Lossy);
+///
+/// let report = check_compatibility(&reader, &writer);
+/// assert_eq!(report.compatibility, Compatibility::Incompatible);
+/// ```
+#[must_use]
+pub fn check_compatibility(writer: &Schema, reader: &Schema) -> CompatibilityReport {
+    let mut checker = Checker {
+        writer,
+        reader,
+        visited: BTreeSet::new(),
+        changes: Vec::new(),
+    };
+    checker.node(0, 0, Position::Deref, &mut String::new());
+
+    let compatibility = checker
+        .changes
+        .iter()
+        .map(|change| change.compatibility)
+        .max()
+        .unwrap_or(Compatibility::Compatible);
+
+    CompatibilityReport {
+        compatibility,
+        changes: checker.changes,
+    }
+}
+
+/// How the formula is read by the reader and was written by the writer.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Position {
+    /// Not the last field for both.
+    Inner,
+
+    /// Last field for both.
+    Last,
+
+    /// Not the last field for the writer, but the last one for the reader.
+    Truncated,
+
+    /// Pointee of a reference.
+    Deref,
+}
+
+struct Checker<'a> {
+    writer: &'a Schema,
+    reader: &'a Schema,
+    visited: BTreeSet<(usize, usize, Position)>,
+    changes: Vec<SchemaChange>,
+}
+
+impl Checker<'_> {
+    fn report(&mut self, path: &str, kind: SchemaChangeKind, compatibility: Compatibility) {
+        self.changes.push(SchemaChange {
+            path: path.into(),
+            kind,
+            compatibility,
+        });
+    }
+
+    fn node(&mut self, w: usize, r: usize, position: Position, path: &mut String) {
+        // Recursive formulas are assumed compatible on revisit.
+        if !self.visited.insert((w, r, position)) {
+            return;
+        }
+
+        let (writer, reader) = (self.writer, self.reader);
+        let writer = &writer.nodes[w];
+        let reader = &reader.nodes[r];
+        let changes = self.changes.len();
+
+        match (&writer.kind, &reader.kind) {
+            (SchemaKind::Opaque, SchemaKind::Opaque) => {
+                if writer.name != reader.name {
+                    self.report(
+                        path,
+                        SchemaChangeKind::OpaqueChanged,
+                        Compatibility::Incompatible,
+                    );
+                }
+            }
+            (SchemaKind::Bool, SchemaKind::Bool)
+            | (SchemaKind::Vlq, SchemaKind::Vlq)
+            | (SchemaKind::Bytes, SchemaKind::Bytes)
+            | (SchemaKind::Str, SchemaKind::Str) => {}
+            (SchemaKind::Str, SchemaKind::Bytes) => {
+                self.kind_changed(path, &writer.kind, &reader.kind, Compatibility::Compatible)
+            }
+            (SchemaKind::Bytes, SchemaKind::Str) => {
+                // Bytes may be not UTF-8.
+                self.kind_changed(path, &writer.kind, &reader.kind, Compatibility::Lossy);
+            }
+            (SchemaKind::Unsigned(w_size), SchemaKind::Unsigned(r_size))
+            | (SchemaKind::Signed(w_size), SchemaKind::Signed(r_size))
+            | (SchemaKind::Float(w_size), SchemaKind::Float(r_size)) => {
+                self.size(path, *w_size, *r_size);
+            }
+            (SchemaKind::Array(w_elem, w_len), SchemaKind::Array(r_elem, r_len)) => {
+                self.size(path, *w_len, *r_len);
+                self.nested(*w_elem, *r_elem, Position::Inner, path, "[]");
+            }
+            (SchemaKind::Slice(w_elem), SchemaKind::Slice(r_elem)) => {
+                self.nested(*w_elem, *r_elem, Position::Inner, path, "[]");
+            }
+            (SchemaKind::Ref(w_formula), SchemaKind::Ref(r_formula)) => {
+                self.nested(*w_formula, *r_formula, Position::Deref, path, "");
+            }
+            (SchemaKind::Option(w_formula), SchemaKind::Option(r_formula)) => {
+                self.nested(*w_formula, *r_formula, Position::Last, path, "?");
+            }
+            (SchemaKind::Tuple(w_elems), SchemaKind::Tuple(r_elems)) => {
+                let names = |elems: &[usize]| -> Vec<SchemaField> {
+                    elems
+                        .iter()
+                        .enumerate()
+                        .map(|(idx, elem)| SchemaField {
+                            name: format!("{idx}"),
+                            formula: *elem,
+                        })
+                        .collect()
+                };
+                self.fields(&names(w_elems), &names(r_elems), path);
+            }
+            (SchemaKind::Struct(w_fields), SchemaKind::Struct(r_fields)) => {
+                self.fields(w_fields, r_fields, path);
+            }
+            (SchemaKind::Enum(w_variants), SchemaKind::Enum(r_variants)) => {
+                self.variants(w_variants, r_variants, path);
+            }
+            _ => self.kind_changed(
+                path,
+                &writer.kind,
+                &reader.kind,
+                Compatibility::Incompatible,
+            ),
+        }
+
+        // Layout is checked only if nested formulas are not already incompatible,
+        // as their changes usually change the layout as well.
+        let nested_incompatible = self.changes[changes..]
+            .iter()
+            .any(|change| change.compatibility == Compatibility::Incompatible);
+
+        if !nested_incompatible && !layout_matches(writer, reader, position) {
+            self.report(
+                path,
+                SchemaChangeKind::LayoutChanged,
+                Compatibility::Incompatible,
+            );
+        }
+    }
+
+    fn nested(&mut self, w: usize, r: usize, position: Position, path: &mut String, segment: &str) {
+        let len = path.len();
+        path.push_str(segment);
+        self.node(w, r, position, path);
+        path.truncate(len);
+    }
+
+    fn kind_changed(
+        &mut self,
+        path: &str,
+        writer: &SchemaKind,
+        reader: &SchemaKind,
+        compatibility: Compatibility,
+    ) {
+        self.report(
+            path,
+            SchemaChangeKind::KindChanged {
+                writer: writer.name(),
+                reader: reader.name(),
+            },
+            compatibility,
+        );
+    }
+
+    fn size(&mut self, path: &str, writer: usize, reader: usize) {
+        if writer != reader {
+            self.report(
+                path,
+                SchemaChangeKind::SizeChanged { writer, reader },
+                Compatibility::Incompatible,
+            );
+        }
+    }
+
+    fn fields(&mut self, writer: &[SchemaField], reader: &[SchemaField], path: &mut String) {
+        for (idx, (w, r)) in writer.iter().zip(reader).enumerate() {
+            let position = match (idx + 1 == writer.len(), idx + 1 == reader.len()) {
+                (true, true) => Position::Last,
+                (false, true) => Position::Truncated,
+                (_, false) => Position::Inner,
+            };
+
+            let len = path.len();
+            path.push('.');
+            path.push_str(&r.name);
+
+            if w.name != r.name {
+                self.report(
+                    path,
+                    SchemaChangeKind::FieldRenamed {
+                        writer: w.name.clone(),
+                        reader: r.name.clone(),
+                    },
+                    Compatibility::Compatible,
+                );
+            }
+            self.node(w.formula, r.formula, position, path);
+            path.truncate(len);
+        }
+
+        for w in writer.iter().skip(reader.len()) {
+            self.report(
+                path,
+                SchemaChangeKind::FieldRemoved {
+                    name: w.name.clone(),
+                },
+                Compatibility::Lossy,
+            );
+        }
+
+        for r in reader.iter().skip(writer.len()) {
+            self.report(
+                path,
+                SchemaChangeKind::FieldAdded {
+                    name: r.name.clone(),
+                },
+                Compatibility::Incompatible,
+            );
+        }
+    }
+
+    fn variants(&mut self, writer: &[SchemaVariant], reader: &[SchemaVariant], path: &mut String) {
+        for w in writer {
+            match reader.iter().find(|r| r.tag == w.tag) {
+                None => self.report(
+                    path,
+                    SchemaChangeKind::VariantRemoved {
+                        name: w.name.clone(),
+                        tag: w.tag,
+                    },
+                    Compatibility::Lossy,
+                ),
+                Some(r) => {
+                    let len = path.len();
+                    path.push_str("::");
+                    path.push_str(&r.name);
+
+                    if w.name != r.name {
+                        self.report(
+                            path,
+                            SchemaChangeKind::VariantRenamed {
+                                writer: w.name.clone(),
+                                reader: r.name.clone(),
+                                tag: w.tag,
+                            },
+                            Compatibility::Compatible,
+                        );
+                    }
+                    self.fields(&w.fields, &r.fields, path);
+                    path.truncate(len);
+                }
+            }
+        }
+
+        for r in reader {
+            if writer.iter().all(|w| w.tag != r.tag) {
+                self.report(
+                    path,
+                    SchemaChangeKind::VariantAdded {
+                        name: r.name.clone(),
+                        tag: r.tag,
+                    },
+                    Compatibility::Compatible,
+                );
+            }
+        }
+    }
+}
+
+/// Checks that reader finds the value where writer put it.
+///
+/// Reader may take only a prefix of the writer's value stack,
+/// differences within the value are checked separately.
+fn layout_matches(writer: &SchemaNode, reader: &SchemaNode, position: Position) -> bool {
+    let covers = |writer: Option<usize>, reader: usize| writer.is_none_or(|size| size >= reader);
+
+    match position {
+        // Value is padded to the stack size or prefixed with its size.
+        Position::Inner => writer.max_stack_size == reader.max_stack_size,
+        Position::Last => match reader.max_stack_size {
+            Some(max_stack) if reader.exact_size => covers(writer.max_stack_size, max_stack),
+            _ => true,
+        },
+        // Reader takes the rest of the stack, it must not consume
+        // size prefix of the writer's value.
+        Position::Truncated => match (writer.max_stack_size, reader.max_stack_size) {
+            (Some(size), Some(max_stack)) => size >= max_stack,
+            _ => false,
+        },
+        // Reference layout depends on exactness of the pointee.
+        Position::Deref => {
+            writer.exact_size == reader.exact_size
+                && match reader.max_stack_size {
+                    Some(max_stack) if reader.exact_size => {
+                        covers(writer.max_stack_size, max_stack)
+                    }
+                    _ => true,
+                }
+        }
+    }
+}
@@ -0,0 +1,178 @@
+//!
+//! This module provides formulas for `Duration` and `SystemTime`
+//! encoded as integers: `DurationNanos`, `DurationMillis`,
+//! `UnixSeconds`, `UnixMillis` and `UnixNanos`.
+//! It also allows serializing `Ipv4Addr` and `Ipv6Addr`
+//! with `u32` and `u128` formulas respectively.
+//!
+
+use core::{
+    mem::size_of,
+    net::{Ipv4Addr, Ipv6Addr},
+    time::Duration,
+};
+
+use crate::{
+    buffer::Buffer,
+    deserialize::DeserializeError,
+    serialize::{Serialize, Sizes},
+};
+
+const NANOS_PER_SEC: u128 = 1_000_000_000;
+
+crate::define_as! {
+    /// Formula for `Duration` encoded as number of nanoseconds in 128-bit unsigned integer.
+    ///
+    /// Deserialization fails with `DeserializeError::IntegerOverflow`
+    /// if number of seconds does not fit into `u64`.
+    pub struct DurationNanos(Duration as u128) {
+        into = Duration::as_nanos;
+        from = |nanos| {
+            let Ok(secs) = u64::try_from(nanos / NANOS_PER_SEC) else {
+                return Err(DeserializeError::IntegerOverflow);
+            };
+            #[allow(clippy::cast_possible_truncation)]
+            let subsec = (nanos % NANOS_PER_SEC) as u32;
+            Ok(Duration::new(secs, subsec))
+        };
+    }
+}
+
+crate::define_as! {
+    /// Formula for `Duration` encoded as number of whole milliseconds in 64-bit unsigned integer.
+    ///
+    /// Sub-millisecond part is truncated on serialization.
+    /// Durations longer than `u64::MAX` milliseconds are saturated.
+    pub struct DurationMillis(Duration as u64) {
+        into = |duration| u64::try_from(duration.as_millis()).unwrap_or(u64::MAX);
+        from = |millis| Ok(Duration::from_millis(millis));
+    }
+}
+
+#[cfg(feature = "std")]
+crate::define_as! {
+    /// Formula for `SystemTime` encoded as number of whole seconds
+    /// since Unix epoch in 64-bit signed integer.
+    ///
+    /// Times before the epoch are negative and rounded towards negative infinity.
+    /// Times out of range of `i64` are saturated.
+    /// Deserialization fails with `DeserializeError::IntegerOverflow`
+    /// if time is not representable by `SystemTime`.
+    pub struct UnixSeconds(std::time::SystemTime as i64) {
+        into = |time| saturate_i64(unix_nanos(time).div_euclid(NANOS_PER_SEC as i128));
+        from = |secs| from_unix_nanos(i128::from(secs) * NANOS_PER_SEC as i128);
+    }
+}
+
+#[cfg(feature = "std")]
+crate::define_as! {
+    /// Formula for `SystemTime` encoded as number of whole milliseconds
+    /// since Unix epoch in 64-bit signed integer.
+    ///
+    /// Times before the epoch are negative and rounded towards negative infinity.
+    /// Times out of range of `i64` are saturated.
+    /// Deserialization fails with `DeserializeError::IntegerOverflow`
+    /// if time is not representable by `SystemTime`.
+    pub struct UnixMillis(std::time::SystemTime as i64) {
+        into = |time| saturate_i64(unix_nanos(time).div_euclid(NANOS_PER_MILLI));
+        from = |millis| from_unix_nanos(i128::from(millis) * NANOS_PER_MILLI);
+    }
+}
+
+#[cfg(feature = "std")]
+crate::define_as! {
+    /// Formula for `SystemTime` encoded as number of nanoseconds
+    /// since Unix epoch in 128-bit signed integer.
+    ///
+    /// Encoding is exact for any time representable by `SystemTime`.
+    /// Times before the epoch are negative.
+    /// Deserialization fails with `DeserializeError::IntegerOverflow`
+    /// if time is not representable by `SystemTime`.
+    pub struct UnixNanos(std::time::SystemTime as i128) {
+        into = unix_nanos;
+        from = from_unix_nanos;
+    }
+}
+
+#[cfg(feature = "std")]
+const NANOS_PER_MILLI: i128 = 1_000_000;
+
+/// Returns signed number of nanoseconds since Unix epoch.
+#[cfg(feature = "std")]
+#[allow(clippy::cast_possible_wrap)]
+fn unix_nanos(time: &std::time::SystemTime) -> i128 {
+    // Number of nanoseconds in `Duration` fits into 94 bits.
+    match time.duration_since(std::time::UNIX_EPOCH) {
+        Ok(after) => after.as_nanos() as i128,
+        Err(before) => -(before.duration().as_nanos() as i128),
+    }
+}
+
+#[cfg(feature = "std")]
+fn from_unix_nanos(nanos: i128) -> Result<std::time::SystemTime, DeserializeError> {
+    let abs = nanos.unsigned_abs();
+    let Ok(secs) = u64::try_from(abs / NANOS_PER_SEC) else {
+        return Err(DeserializeError::IntegerOverflow);
+    };
+    #[allow(clippy::cast_possible_truncation)]
+    let duration = Duration::new(secs, (abs % NANOS_PER_SEC) as u32);
+
+    let time = if nanos < 0 {
+        std::time::UNIX_EPOCH.checked_sub(duration)
+    } else {
+        std::time::UNIX_EPOCH.checked_add(duration)
+    };
+    time.ok_or(DeserializeError::IntegerOverflow)
+}
+
+#[cfg(feature = "std")]
+fn saturate_i64(value: i128) -> i64 {
+    i64::try_from(value).unwrap_or(if value < 0 { i64::MIN } else { i64::MAX })
+}
+
+macro_rules! impl_as_int {
+    ($($ty:ty as $repr:ty),* $(,)?) => {$(
+        /// Encoded as integer of the same width produced by `From` conversion,
+        /// so first octet of the address is the most significant byte.
+        impl Serialize<$repr> for $ty {
+            #[inline(always)]
+            fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+            where
+                B: Buffer,
+            {
+                <$repr as Serialize<$repr>>::serialize(<$repr>::from(self), sizes, buffer)
+            }
+
+            #[inline(always)]
+            fn size_hint(&self) -> Option<Sizes> {
+                Some(Sizes {
+                    heap: 0,
+                    stack: size_of::<$repr>(),
+                })
+            }
+        }
+
+        impl Serialize<$repr> for &$ty {
+            #[inline(always)]
+            fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+            where
+                B: Buffer,
+            {
+                <$ty as Serialize<$repr>>::serialize(*self, sizes, buffer)
+            }
+
+            #[inline(always)]
+            fn size_hint(&self) -> Option<Sizes> {
+                Some(Sizes {
+                    heap: 0,
+                    stack: size_of::<$repr>(),
+                })
+            }
+        }
+    )*};
+}
+
+impl_as_int! {
+    Ipv4Addr as u32,
+    Ipv6Addr as u128,
+}
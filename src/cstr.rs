@@ -0,0 +1,232 @@
+use core::ffi::CStr;
+
+use crate::{
+    buffer::Buffer,
+    deserialize::{Deserialize, DeserializeError, Deserializer},
+    formula::{BareFormula, Formula},
+    serialize::{write_bytes, SerializeRef, Sizes},
+};
+
+/// A formula for strings terminated with NUL byte,
+/// matching layout of C strings byte-for-byte.
+///
+/// Serialized bytes are string bytes followed by a single NUL.
+/// Note that like any unsized formula it gets size prefix
+/// when used as a non-last field.
+///
+/// Serializable from `str`, `String` and `CStr`.
+/// Deserializable into `&str`, `&CStr`, `String` and `CString`.
+/// Deserialization fails with `DeserializeError::Incompatible`
+/// if bytes do not end with NUL or contain interior NUL.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// let mut buffer = [0u8; 16];
+/// let (size, root) = serialize::<CStrFormula, _>("hello", &mut buffer).unwrap();
+/// assert_eq!(size, 6);
+///
+/// let value = deserialize_with_size::<CStrFormula, &str>(&buffer[..size], root).unwrap();
+/// assert_eq!(value, "hello");
+///
+/// let value = deserialize_with_size::<CStrFormula, &core::ffi::CStr>(&buffer[..size], root).unwrap();
+/// assert_eq!(value.to_bytes(), b"hello");
+/// ```
+pub struct CStrFormula;
+
+impl Formula for CStrFormula {
+    const MAX_STACK_SIZE: Option<usize> = None;
+    const EXACT_SIZE: bool = false;
+    const HEAPLESS: bool = true;
+}
+
+impl BareFormula for CStrFormula {}
+
+impl SerializeRef<CStrFormula> for str {
+    /// # Panics
+    ///
+    /// Panics if string contains NUL byte.
+    #[inline]
+    fn serialize<B>(&self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_c_str(self.as_bytes(), sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        Some(Sizes::with_stack(self.len() + 1))
+    }
+}
+
+impl SerializeRef<CStrFormula> for CStr {
+    #[inline]
+    fn serialize<B>(&self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_bytes(self.to_bytes_with_nul(), sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        Some(Sizes::with_stack(self.to_bytes_with_nul().len()))
+    }
+}
+
+impl<'de, 'fe: 'de> Deserialize<'fe, CStrFormula> for &'de CStr {
+    #[inline]
+    fn deserialize(de: Deserializer<'fe>) -> Result<Self, DeserializeError> {
+        read_c_str(de)
+    }
+
+    #[inline]
+    fn deserialize_in_place(&mut self, de: Deserializer<'fe>) -> Result<(), DeserializeError> {
+        *self = read_c_str(de)?;
+        Ok(())
+    }
+}
+
+impl<'de, 'fe: 'de> Deserialize<'fe, CStrFormula> for &'de str {
+    #[inline]
+    fn deserialize(de: Deserializer<'fe>) -> Result<Self, DeserializeError> {
+        let c_str = read_c_str(de)?;
+        core::str::from_utf8(c_str.to_bytes()).map_err(DeserializeError::NonUtf8)
+    }
+
+    #[inline]
+    fn deserialize_in_place(&mut self, de: Deserializer<'fe>) -> Result<(), DeserializeError> {
+        *self = <&str as Deserialize<'fe, CStrFormula>>::deserialize(de)?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "alloc")]
+mod alloc_impls {
+    use alloc::{borrow::ToOwned, ffi::CString, string::String};
+
+    use crate::{
+        buffer::Buffer,
+        deserialize::{Deserialize, DeserializeError, Deserializer},
+        serialize::{Serialize, SerializeRef, Sizes},
+    };
+
+    use super::{read_c_str, CStrFormula};
+
+    impl SerializeRef<CStrFormula> for String {
+        /// # Panics
+        ///
+        /// Panics if string contains NUL byte.
+        #[inline(always)]
+        fn serialize<B>(&self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+        where
+            B: Buffer,
+        {
+            <str as SerializeRef<CStrFormula>>::serialize(self, sizes, buffer)
+        }
+
+        #[inline(always)]
+        fn size_hint(&self) -> Option<Sizes> {
+            <str as SerializeRef<CStrFormula>>::size_hint(self)
+        }
+    }
+
+    impl Serialize<CStrFormula> for String {
+        /// # Panics
+        ///
+        /// Panics if string contains NUL byte.
+        #[inline(always)]
+        fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+        where
+            B: Buffer,
+        {
+            <str as SerializeRef<CStrFormula>>::serialize(&self, sizes, buffer)
+        }
+
+        #[inline(always)]
+        fn size_hint(&self) -> Option<Sizes> {
+            <str as SerializeRef<CStrFormula>>::size_hint(self)
+        }
+    }
+
+    impl SerializeRef<CStrFormula> for CString {
+        #[inline(always)]
+        fn serialize<B>(&self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+        where
+            B: Buffer,
+        {
+            <core::ffi::CStr as SerializeRef<CStrFormula>>::serialize(self, sizes, buffer)
+        }
+
+        #[inline(always)]
+        fn size_hint(&self) -> Option<Sizes> {
+            <core::ffi::CStr as SerializeRef<CStrFormula>>::size_hint(self)
+        }
+    }
+
+    impl Serialize<CStrFormula> for CString {
+        #[inline(always)]
+        fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+        where
+            B: Buffer,
+        {
+            <core::ffi::CStr as SerializeRef<CStrFormula>>::serialize(&self, sizes, buffer)
+        }
+
+        #[inline(always)]
+        fn size_hint(&self) -> Option<Sizes> {
+            <core::ffi::CStr as SerializeRef<CStrFormula>>::size_hint(self)
+        }
+    }
+
+    impl<'de> Deserialize<'de, CStrFormula> for String {
+        #[inline]
+        fn deserialize(de: Deserializer<'de>) -> Result<Self, DeserializeError> {
+            let string = <&str as Deserialize<'de, CStrFormula>>::deserialize(de)?;
+            Ok(string.to_owned())
+        }
+
+        #[inline]
+        fn deserialize_in_place(&mut self, de: Deserializer<'de>) -> Result<(), DeserializeError> {
+            let string = <&str as Deserialize<'de, CStrFormula>>::deserialize(de)?;
+            self.clear();
+            self.push_str(string);
+            Ok(())
+        }
+    }
+
+    impl<'de> Deserialize<'de, CStrFormula> for CString {
+        #[inline]
+        fn deserialize(de: Deserializer<'de>) -> Result<Self, DeserializeError> {
+            Ok(read_c_str(de)?.to_owned())
+        }
+
+        #[inline]
+        fn deserialize_in_place(&mut self, de: Deserializer<'de>) -> Result<(), DeserializeError> {
+            *self = read_c_str(de)?.to_owned();
+            Ok(())
+        }
+    }
+}
+
+#[inline]
+fn write_c_str<B>(bytes: &[u8], sizes: &mut Sizes, mut buffer: B) -> Result<(), B::Error>
+where
+    B: Buffer,
+{
+    assert!(
+        !bytes.contains(&0),
+        "String with interior NUL cannot be serialized with `CStrFormula`"
+    );
+    // Stack grows towards the start of the buffer,
+    // so terminator is written first to follow the string bytes.
+    write_bytes(&[0], sizes, buffer.reborrow())?;
+    write_bytes(bytes, sizes, buffer)
+}
+
+#[inline]
+fn read_c_str<'de>(de: Deserializer<'de>) -> Result<&'de CStr, DeserializeError> {
+    CStr::from_bytes_with_nul(de.read_all_bytes()).map_err(|_| DeserializeError::Incompatible)
+}
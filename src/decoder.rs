@@ -0,0 +1,346 @@
+//!
+//! This module provides incremental decoder that accepts input in chunks.
+//!
+
+use alloc::vec::Vec;
+use core::{fmt, marker::PhantomData};
+
+use crate::{
+    deserialize::{Deserialize, DeserializeError},
+    formula::{reference_size, Formula},
+    packet::{packet_address, read_packet},
+};
+
+/// Default maximum packet size of [`Decoder`].
+const DEFAULT_MAX_PACKET_SIZE: usize = 8 * 1024 * 1024;
+
+/// Error of [`Decoder`].
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DecoderError {
+    /// Packet header declares size that exceeds maximum packet size.
+    /// Input cannot be decoded further until the decoder is cleared.
+    PacketTooLarge(usize),
+
+    /// Packet header declares size that does not fit `usize`.
+    /// Input cannot be decoded further until the decoder is cleared.
+    InvalidHeader,
+
+    /// Packet failed to deserialize.
+    /// The packet is skipped and decoding may continue.
+    Deserialize(DeserializeError),
+}
+
+impl From<DeserializeError> for DecoderError {
+    #[inline(always)]
+    fn from(err: DeserializeError) -> Self {
+        DecoderError::Deserialize(err)
+    }
+}
+
+impl fmt::Display for DecoderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecoderError::PacketTooLarge(size) => {
+                write!(f, "packet of {size} bytes exceeds maximum packet size")
+            }
+            DecoderError::InvalidHeader => f.write_str("packet header declares invalid size"),
+            DecoderError::Deserialize(err) => write!(f, "deserialization error: {err}"),
+        }
+    }
+}
+
+/// Push-based decoder of consecutive packets of formula `F` into `T`.
+///
+/// Accepts input in chunks of any size as they arrive
+/// and returns values once whole packets are available.
+/// Bytes are buffered only while packet is incomplete,
+/// packets that lie entirely in the pushed chunk are decoded in place.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// let mut stream = Vec::new();
+/// for value in [1u32, 2, 3] {
+///     let mut packet = Vec::new();
+///     let size = write_packet_to_vec::<u32, _>(value, &mut packet);
+///     stream.extend_from_slice(&packet[..size]);
+/// }
+///
+/// let mut decoder = Decoder::<u32, u32>::new();
+/// let mut values = Vec::new();
+/// for chunk in stream.chunks(3) {
+///     if let Some(value) = decoder.push(chunk).unwrap() {
+///         values.push(value);
+///     }
+///     while let Some(value) = decoder.poll().unwrap() {
+///         values.push(value);
+///     }
+/// }
+/// assert_eq!(values, [1, 2, 3]);
+/// assert_eq!(decoder.buffered(), 0);
+/// ```
+pub struct Decoder<F: ?Sized, T> {
+    buffer: Vec<u8>,
+    max_packet_size: usize,
+    marker: PhantomData<fn(&F) -> T>,
+}
+
+impl<F, T> Default for Decoder<F, T>
+where
+    F: Formula + ?Sized,
+    T: for<'de> Deserialize<'de, F>,
+{
+    #[inline(always)]
+    fn default() -> Self {
+        Decoder::new()
+    }
+}
+
+impl<F, T> Decoder<F, T>
+where
+    F: Formula + ?Sized,
+    T: for<'de> Deserialize<'de, F>,
+{
+    /// Returns new decoder with default maximum packet size of 8 MiB.
+    #[must_use]
+    #[inline(always)]
+    pub fn new() -> Self {
+        Decoder::with_max_packet_size(DEFAULT_MAX_PACKET_SIZE)
+    }
+
+    /// Returns new decoder that rejects packets larger than specified size.
+    #[must_use]
+    #[inline(always)]
+    pub fn with_max_packet_size(max_packet_size: usize) -> Self {
+        Decoder {
+            buffer: Vec::new(),
+            max_packet_size,
+            marker: PhantomData,
+        }
+    }
+
+    /// Accepts next chunk of input.
+    /// Returns value if the chunk completes a packet.
+    ///
+    /// Chunk may complete more than one packet,
+    /// call [`Decoder::poll`] until it returns `None`
+    /// to decode the rest of them.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DecoderError::PacketTooLarge` if packet exceeds maximum size.
+    /// Returns `DecoderError::InvalidHeader` if packet header is invalid.
+    /// Returns `DecoderError::Deserialize` if packet fails to deserialize.
+    pub fn push(&mut self, chunk: &[u8]) -> Result<Option<T>, DecoderError> {
+        if self.buffer.is_empty() {
+            if let Some(size) = self.complete_packet(chunk)? {
+                self.buffer.extend_from_slice(&chunk[size..]);
+                let (value, _) = read_packet::<F, T>(&chunk[..size])?;
+                return Ok(Some(value));
+            }
+        }
+
+        self.buffer.extend_from_slice(chunk);
+        self.poll()
+    }
+
+    /// Decodes next packet from buffered input without accepting new input.
+    /// Returns `None` if buffered input does not contain whole packet.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DecoderError::PacketTooLarge` if packet exceeds maximum size.
+    /// Returns `DecoderError::InvalidHeader` if packet header is invalid.
+    /// Returns `DecoderError::Deserialize` if packet fails to deserialize.
+    pub fn poll(&mut self) -> Result<Option<T>, DecoderError> {
+        let Some(size) = self.complete_packet(&self.buffer)? else {
+            return Ok(None);
+        };
+
+        let result = read_packet::<F, T>(&self.buffer[..size]);
+        self.buffer.drain(..size);
+        let (value, _) = result?;
+        Ok(Some(value))
+    }
+
+    /// Returns number of buffered bytes of incomplete packets.
+    #[must_use]
+    #[inline(always)]
+    pub fn buffered(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Discards buffered input.
+    #[inline(always)]
+    pub fn clear(&mut self) {
+        self.buffer.clear();
+    }
+
+    /// Returns size of the first packet in the input if it is complete.
+    fn complete_packet(&self, input: &[u8]) -> Result<Option<usize>, DecoderError> {
+        let header = reference_size::<F>();
+        if input.len() < header {
+            return Ok(None);
+        }
+
+        let Some(size) = packet_address::<F>(input) else {
+            return Err(DecoderError::InvalidHeader);
+        };
+        let size = size.max(header);
+        if size > self.max_packet_size {
+            return Err(DecoderError::PacketTooLarge(size));
+        }
+
+        if input.len() < size {
+            Ok(None)
+        } else {
+            Ok(Some(size))
+        }
+    }
+}
+
+/// Outcome of [`Resumable::read`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Progress<T> {
+    /// Packet was complete and deserialized.
+    Done(T),
+
+    /// Input ends before the next packet is complete.
+    /// Reading should be resumed when input is extended
+    /// to at least `required` bytes.
+    Suspended {
+        /// Number of input bytes required to make progress.
+        required: usize,
+    },
+}
+
+/// Resumable reader of consecutive packets of formula `F`
+/// from input that grows over time, e.g. file being downloaded.
+///
+/// Reading suspends at packet boundary when input runs out
+/// instead of failing with `DeserializeError::OutOfBounds`
+/// and resumes from the same point when called with extended input.
+/// Unlike [`Decoder`] it does not own the input,
+/// so values may borrow from it.
+///
+/// Large collections should be written as a sequence of packets
+/// to suspend between their parts.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// let mut input = Vec::new();
+/// for part in [&["a", "b"][..], &["c"]] {
+///     let mut packet = Vec::new();
+///     let size = write_packet_to_vec::<[As<str>], _>(part, &mut packet);
+///     input.extend_from_slice(&packet[..size]);
+/// }
+///
+/// let mut reader = Resumable::<[As<str>]>::new();
+/// let mut available = 3;
+/// let mut parts = Vec::new();
+/// while reader.position() < input.len() {
+///     match reader.read::<Vec<&str>>(&input[..available]).unwrap() {
+///         Progress::Done(part) => parts.push(part),
+///         Progress::Suspended { required } => available = required,
+///     }
+/// }
+/// assert_eq!(parts, [vec!["a", "b"], vec!["c"]]);
+/// ```
+pub struct Resumable<F: ?Sized> {
+    position: usize,
+    marker: PhantomData<fn(&F)>,
+}
+
+impl<F> Default for Resumable<F>
+where
+    F: Formula + ?Sized,
+{
+    #[inline(always)]
+    fn default() -> Self {
+        Resumable::new()
+    }
+}
+
+impl<F> Resumable<F>
+where
+    F: Formula + ?Sized,
+{
+    /// Returns new reader positioned at the start of input.
+    #[must_use]
+    #[inline(always)]
+    pub fn new() -> Self {
+        Resumable::at(0)
+    }
+
+    /// Returns new reader positioned at specified offset of input.
+    /// Offset must be at packet boundary.
+    #[must_use]
+    #[inline(always)]
+    pub fn at(position: usize) -> Self {
+        Resumable {
+            position,
+            marker: PhantomData,
+        }
+    }
+
+    /// Returns offset of the next packet in the input.
+    /// Bytes before it are consumed and may be discarded,
+    /// see [`Resumable::rebase`].
+    #[must_use]
+    #[inline(always)]
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Notifies the reader that `consumed` bytes
+    /// were removed from the start of input.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `consumed` exceeds current position.
+    #[inline(always)]
+    pub fn rebase(&mut self, consumed: usize) {
+        assert!(
+            consumed <= self.position,
+            "Consumed bytes exceed reader position"
+        );
+        self.position -= consumed;
+    }
+
+    /// Reads next packet from the input if it is complete.
+    /// Otherwise returns `Progress::Suspended` with number of bytes required
+    /// and keeps the position, so reading may be resumed with extended input.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError` if complete packet fails to deserialize.
+    /// The packet is skipped in this case.
+    pub fn read<'de, T>(&mut self, input: &'de [u8]) -> Result<Progress<T>, DeserializeError>
+    where
+        T: Deserialize<'de, F>,
+    {
+        let rest = input.get(self.position..).unwrap_or(&[]);
+
+        let header = reference_size::<F>();
+        if rest.len() < header {
+            return Ok(Progress::Suspended {
+                required: self.position.saturating_add(header),
+            });
+        }
+
+        let size = packet_address::<F>(rest).unwrap_or(usize::MAX).max(header);
+        if rest.len() < size {
+            return Ok(Progress::Suspended {
+                required: self.position.saturating_add(size),
+            });
+        }
+
+        self.position += size;
+        let (value, _) = read_packet::<F, T>(&rest[..size])?;
+        Ok(Progress::Done(value))
+    }
+}
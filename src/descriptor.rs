@@ -0,0 +1,204 @@
+//!
+//! This module provides runtime descriptors of formulas.
+//!
+
+use core::{any::type_name, fmt};
+
+use crate::formula::Formula;
+
+/// Lazily evaluated descriptor of a formula.
+///
+/// Descriptors refer to nested formulas through this type
+/// so that recursive formulas can be described without
+/// infinite recursion.
+#[derive(Clone, Copy)]
+pub struct LazyDescriptor {
+    describe: fn() -> Descriptor,
+}
+
+impl LazyDescriptor {
+    /// Returns lazy descriptor of the formula `F`.
+    #[must_use]
+    #[inline(always)]
+    pub const fn of<F>() -> Self
+    where
+        F: Formula + ?Sized,
+    {
+        LazyDescriptor {
+            describe: F::describe,
+        }
+    }
+
+    /// Evaluates the descriptor.
+    #[must_use]
+    #[inline(always)]
+    pub fn get(&self) -> Descriptor {
+        (self.describe)()
+    }
+}
+
+impl fmt::Debug for LazyDescriptor {
+    #[inline(always)]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.get().name)
+    }
+}
+
+/// Descriptor of a field of a structure or an enum variant.
+#[derive(Clone, Copy, Debug)]
+pub struct FieldDescriptor {
+    /// Name of the field.
+    /// Fields of tuple structures and variants are named by index.
+    pub name: &'static str,
+
+    /// Formula of the field.
+    pub formula: LazyDescriptor,
+}
+
+/// Descriptor of an enum variant.
+#[derive(Clone, Copy, Debug)]
+pub struct VariantDescriptor {
+    /// Name of the variant.
+    pub name: &'static str,
+
+    /// Tag that is written before variant's fields.
+    pub tag: u32,
+
+    /// Fields of the variant.
+    pub fields: &'static [FieldDescriptor],
+}
+
+/// Structure of a formula.
+#[derive(Clone, Copy, Debug)]
+pub enum DescriptorKind {
+    /// Formula that does not describe its structure.
+    Opaque,
+
+    /// Boolean value encoded in a single byte.
+    Bool,
+
+    /// Little-endian unsigned integer of specified size in bytes.
+    Unsigned(usize),
+
+    /// Little-endian signed integer of specified size in bytes.
+    Signed(usize),
+
+    /// Little-endian IEEE 754 floating point number of specified size in bytes.
+    Float(usize),
+
+    /// Variable-length quantity. See [`Vlq`](crate::Vlq).
+    Vlq,
+
+    /// Raw bytes that occupy the whole value. See [`Bytes`](crate::Bytes).
+    Bytes,
+
+    /// UTF-8 string that occupies the whole value.
+    Str,
+
+    /// Array of fixed number of elements.
+    Array(LazyDescriptor, usize),
+
+    /// Slice of elements.
+    Slice(LazyDescriptor),
+
+    /// Reference to a value placed in the heap.
+    Ref(LazyDescriptor),
+
+    /// Optional value prefixed with presence byte.
+    Option(LazyDescriptor),
+
+    /// Tuple of formulas.
+    Tuple(&'static [LazyDescriptor]),
+
+    /// Structure with fields.
+    Struct(&'static [FieldDescriptor]),
+
+    /// Enum with variants.
+    Enum(&'static [VariantDescriptor]),
+}
+
+// Tags of formula kinds used in fingerprints and serialized schemas.
+pub(crate) const KIND_OPAQUE: u8 = 0;
+pub(crate) const KIND_BOOL: u8 = 1;
+pub(crate) const KIND_UNSIGNED: u8 = 2;
+pub(crate) const KIND_SIGNED: u8 = 3;
+pub(crate) const KIND_FLOAT: u8 = 4;
+pub(crate) const KIND_VLQ: u8 = 5;
+pub(crate) const KIND_BYTES: u8 = 6;
+pub(crate) const KIND_STR: u8 = 7;
+pub(crate) const KIND_ARRAY: u8 = 8;
+pub(crate) const KIND_SLICE: u8 = 9;
+pub(crate) const KIND_REF: u8 = 10;
+pub(crate) const KIND_OPTION: u8 = 11;
+pub(crate) const KIND_TUPLE: u8 = 12;
+pub(crate) const KIND_STRUCT: u8 = 13;
+pub(crate) const KIND_ENUM: u8 = 14;
+
+/// Runtime descriptor of a formula.
+///
+/// Describes layout of the formula.
+/// Produced by [`Formula::describe`] and can be used to
+/// inspect serialized data and build tooling around formulas.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// let descriptor = <(u32, [u8])>::describe();
+///
+/// let DescriptorKind::Tuple(elems) = descriptor.kind else {
+///     panic!("tuple is expected");
+/// };
+///
+/// assert!(matches!(elems[0].get().kind, DescriptorKind::Unsigned(4)));
+/// assert!(matches!(elems[1].get().kind, DescriptorKind::Slice(_)));
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct Descriptor {
+    /// Name of the formula type.
+    /// Informative only and should not be relied upon,
+    /// except to tell recursive formulas apart from their ancestors.
+    pub name: &'static str,
+
+    /// Value of [`Formula::MAX_STACK_SIZE`].
+    pub max_stack_size: Option<usize>,
+
+    /// Value of [`Formula::EXACT_SIZE`].
+    pub exact_size: bool,
+
+    /// Value of [`Formula::HEAPLESS`].
+    pub heapless: bool,
+
+    /// Structure of the formula.
+    pub kind: DescriptorKind,
+}
+
+impl Descriptor {
+    /// Returns descriptor of the formula `F` with specified structure.
+    ///
+    /// Use in [`Formula::describe`] implementation.
+    #[must_use]
+    #[inline(always)]
+    pub fn new<F>(kind: DescriptorKind) -> Self
+    where
+        F: Formula + ?Sized,
+    {
+        Descriptor {
+            name: type_name::<F>(),
+            max_stack_size: F::MAX_STACK_SIZE,
+            exact_size: F::EXACT_SIZE,
+            heapless: F::HEAPLESS,
+            kind,
+        }
+    }
+
+    /// Returns descriptor of the formula `F` that does not describe its structure.
+    #[must_use]
+    #[inline(always)]
+    pub fn opaque<F>() -> Self
+    where
+        F: Formula + ?Sized,
+    {
+        Descriptor::new::<F>(DescriptorKind::Opaque)
+    }
+}
@@ -1,7 +1,9 @@
-use core::{any::type_name, iter::FusedIterator, marker::PhantomData, str::Utf8Error};
+use core::{any::type_name, fmt, iter::FusedIterator, marker::PhantomData, str::Utf8Error};
 
 use crate::{
-    formula::{reference_size, unwrap_size, Formula},
+    buffer::BufferExhausted,
+    formula::{reference_size_with, unwrap_size, Formula},
+    serialize::BufferSizeRequired,
     size::{deserialize_usize, FixedIsizeType, FixedUsizeType, SIZE_STACK},
 };
 
@@ -12,17 +14,21 @@ pub(crate) const fn cold_err<T>(e: DeserializeError) -> Result<T, DeserializeErr
 }
 
 /// Error that can occur during deserialization.
+///
+/// New variants may be added in minor releases,
+/// so matches on this enum must have a wildcard arm.
 #[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
 pub enum DeserializeError {
     /// Indicates that input buffer is smaller than
     /// expected value length.
-    OutOfBounds,
+    OutOfBounds(ErrorDetails),
 
     /// Relative address is invalid.
     WrongAddress,
 
     /// Incorrect expected value length.
-    WrongLength,
+    WrongLength(ErrorDetails),
 
     /// Size value exceeds the maximum `usize` for current platform.
     InvalidUsize(FixedUsizeType),
@@ -31,7 +37,7 @@ pub enum DeserializeError {
     InvalidIsize(FixedIsizeType),
 
     /// Enum variant is invalid.
-    WrongVariant(u32),
+    WrongVariant(u32, ErrorDetails),
 
     /// Bytes slice is not UTF8 where `str` is expected.
     NonUtf8(Utf8Error),
@@ -45,6 +51,361 @@ pub enum DeserializeError {
 
     /// Data is incompatible with the type to be deserialized.
     Incompatible,
+
+    /// Referenced data overlaps with data of another reference
+    /// or with the stack of the value that holds the reference.
+    OverlappingReference,
+
+    /// Input is not the canonical encoding of the value,
+    /// e.g. has oversized size prefixes or non-zero padding.
+    NonCanonical,
+
+    /// Byte of `bool` value or `Option` tag is neither 0 nor 1.
+    /// Reported only with "strict" feature.
+    InvalidBool(u8),
+
+    /// Value is not a valid `char`.
+    InvalidChar(u32),
+
+    /// Checksum of the data does not match the stored one,
+    /// the data is corrupted.
+    ChecksumMismatch,
+
+    /// Elements of sorted collection are out of order or duplicated.
+    Unsorted,
+
+    /// Bytes slice is not ASCII where ASCII string is expected.
+    NonAscii,
+
+    /// Encrypted payload failed authentication,
+    /// it was tampered with or encrypted with another key.
+    AuthenticationFailed,
+
+    /// Signature of signed payload is missing or does not match the payload.
+    InvalidSignature,
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for DeserializeError {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        match self {
+            DeserializeError::OutOfBounds(details) => {
+                defmt::write!(f, "OutOfBounds({})", details);
+            }
+            DeserializeError::WrongAddress => defmt::write!(f, "WrongAddress"),
+            DeserializeError::WrongLength(details) => {
+                defmt::write!(f, "WrongLength({})", details);
+            }
+            DeserializeError::InvalidUsize(value) => defmt::write!(f, "InvalidUsize({})", value),
+            DeserializeError::InvalidIsize(value) => defmt::write!(f, "InvalidIsize({})", value),
+            DeserializeError::WrongVariant(tag, details) => {
+                defmt::write!(f, "WrongVariant({}, {})", tag, details);
+            }
+            DeserializeError::NonUtf8(err) => {
+                defmt::write!(f, "NonUtf8(valid up to {})", err.valid_up_to());
+            }
+            DeserializeError::IntegerOverflow => defmt::write!(f, "IntegerOverflow"),
+            DeserializeError::Incompatible => defmt::write!(f, "Incompatible"),
+            DeserializeError::OverlappingReference => defmt::write!(f, "OverlappingReference"),
+            DeserializeError::NonCanonical => defmt::write!(f, "NonCanonical"),
+            DeserializeError::InvalidBool(byte) => defmt::write!(f, "InvalidBool({})", byte),
+            DeserializeError::InvalidChar(value) => defmt::write!(f, "InvalidChar({})", value),
+            DeserializeError::ChecksumMismatch => defmt::write!(f, "ChecksumMismatch"),
+            DeserializeError::Unsorted => defmt::write!(f, "Unsorted"),
+            DeserializeError::NonAscii => defmt::write!(f, "NonAscii"),
+            DeserializeError::AuthenticationFailed => defmt::write!(f, "AuthenticationFailed"),
+            DeserializeError::InvalidSignature => defmt::write!(f, "InvalidSignature"),
+        }
+    }
+}
+
+impl DeserializeError {
+    /// Returns stable numeric code of the error.
+    ///
+    /// Codes do not change between versions, new variants get new codes,
+    /// so they can be logged or passed across FFI boundary.
+    ///
+    /// | Code | Variant |
+    /// |---|---|
+    /// | 1 | `OutOfBounds` |
+    /// | 2 | `WrongAddress` |
+    /// | 3 | `WrongLength` |
+    /// | 4 | `InvalidUsize` |
+    /// | 5 | `InvalidIsize` |
+    /// | 6 | `WrongVariant` |
+    /// | 7 | `NonUtf8` |
+    /// | 8 | `IntegerOverflow` |
+    /// | 9 | `Incompatible` |
+    /// | 10 | `OverlappingReference` |
+    /// | 11 | `NonCanonical` |
+    /// | 12 | `InvalidBool` |
+    /// | 13 | `InvalidChar` |
+    /// | 14 | `ChecksumMismatch` |
+    /// | 15 | `Unsorted` |
+    /// | 16 | `NonAscii` |
+    /// | 17 | `AuthenticationFailed` |
+    /// | 18 | `InvalidSignature` |
+    ///
+    /// Serialization errors [`BufferExhausted`] and [`BufferSizeRequired`]
+    /// have code 256.
+    #[must_use]
+    #[inline(always)]
+    pub const fn code(&self) -> u16 {
+        match self {
+            DeserializeError::OutOfBounds(_) => 1,
+            DeserializeError::WrongAddress => 2,
+            DeserializeError::WrongLength(_) => 3,
+            DeserializeError::InvalidUsize(_) => 4,
+            DeserializeError::InvalidIsize(_) => 5,
+            DeserializeError::WrongVariant(..) => 6,
+            DeserializeError::NonUtf8(_) => 7,
+            DeserializeError::IntegerOverflow => 8,
+            DeserializeError::Incompatible => 9,
+            DeserializeError::OverlappingReference => 10,
+            DeserializeError::NonCanonical => 11,
+            DeserializeError::InvalidBool(_) => 12,
+            DeserializeError::InvalidChar(_) => 13,
+            DeserializeError::ChecksumMismatch => 14,
+            DeserializeError::Unsorted => 15,
+            DeserializeError::NonAscii => 16,
+            DeserializeError::AuthenticationFailed => 17,
+            DeserializeError::InvalidSignature => 18,
+        }
+    }
+
+    /// Returns details attached to the error, if the variant has them.
+    #[must_use]
+    #[inline(always)]
+    pub const fn details(&self) -> Option<&ErrorDetails> {
+        match self {
+            DeserializeError::OutOfBounds(details)
+            | DeserializeError::WrongLength(details)
+            | DeserializeError::WrongVariant(_, details) => Some(details),
+            _ => None,
+        }
+    }
+
+    /// Records name of the formula `F` in the error details,
+    /// unless name of more nested formula is already recorded.
+    /// Does nothing without "debug-errors" feature.
+    #[must_use]
+    #[inline(always)]
+    #[cfg_attr(not(feature = "debug-errors"), allow(unused_mut))]
+    pub fn with_formula<F>(mut self) -> Self
+    where
+        F: Formula + ?Sized,
+    {
+        #[cfg(feature = "tracing")]
+        tracing::trace!(formula = type_name::<F>(), error = ?self, "deserialization error in formula");
+
+        #[cfg(feature = "debug-errors")]
+        if let DeserializeError::OutOfBounds(details)
+        | DeserializeError::WrongLength(details)
+        | DeserializeError::WrongVariant(_, details) = &mut self
+        {
+            if details.formula.is_none() {
+                details.formula = Some(type_name::<F>());
+            }
+        }
+        self
+    }
+}
+
+/// Details attached to `OutOfBounds`, `WrongLength` and `WrongVariant` errors.
+///
+/// Collected only with "debug-errors" feature.
+/// Otherwise it is zero-sized and all accessors return `None`.
+#[derive(Clone, Copy, Default)]
+pub struct ErrorDetails {
+    #[cfg(feature = "debug-errors")]
+    formula: Option<&'static str>,
+    #[cfg(feature = "debug-errors")]
+    expected: Option<usize>,
+    #[cfg(feature = "debug-errors")]
+    actual: Option<usize>,
+}
+
+impl ErrorDetails {
+    /// Returns empty details.
+    #[must_use]
+    #[inline(always)]
+    pub const fn new() -> Self {
+        ErrorDetails {
+            #[cfg(feature = "debug-errors")]
+            formula: None,
+            #[cfg(feature = "debug-errors")]
+            expected: None,
+            #[cfg(feature = "debug-errors")]
+            actual: None,
+        }
+    }
+
+    /// Returns details with expected and actual number of bytes.
+    #[must_use]
+    #[inline(always)]
+    #[cfg_attr(not(feature = "debug-errors"), allow(unused_variables))]
+    pub const fn sizes(expected: usize, actual: usize) -> Self {
+        ErrorDetails {
+            #[cfg(feature = "debug-errors")]
+            formula: None,
+            #[cfg(feature = "debug-errors")]
+            expected: Some(expected),
+            #[cfg(feature = "debug-errors")]
+            actual: Some(actual),
+        }
+    }
+
+    /// Returns type name of the innermost formula that failed to deserialize.
+    #[must_use]
+    #[inline(always)]
+    pub const fn formula(&self) -> Option<&'static str> {
+        #[cfg(feature = "debug-errors")]
+        return self.formula;
+
+        #[cfg(not(feature = "debug-errors"))]
+        return None;
+    }
+
+    /// Returns number of bytes the formula expected.
+    #[must_use]
+    #[inline(always)]
+    pub const fn expected(&self) -> Option<usize> {
+        #[cfg(feature = "debug-errors")]
+        return self.expected;
+
+        #[cfg(not(feature = "debug-errors"))]
+        return None;
+    }
+
+    /// Returns number of bytes that were available.
+    #[must_use]
+    #[inline(always)]
+    pub const fn actual(&self) -> Option<usize> {
+        #[cfg(feature = "debug-errors")]
+        return self.actual;
+
+        #[cfg(not(feature = "debug-errors"))]
+        return None;
+    }
+}
+
+impl fmt::Debug for ErrorDetails {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut s = f.debug_struct("ErrorDetails");
+        if let Some(formula) = self.formula() {
+            s.field("formula", &formula);
+        }
+        if let Some(expected) = self.expected() {
+            s.field("expected", &expected);
+        }
+        if let Some(actual) = self.actual() {
+            s.field("actual", &actual);
+        }
+        s.finish()
+    }
+}
+
+impl fmt::Display for DeserializeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeserializeError::OutOfBounds(_) => f.write_str("input is shorter than expected")?,
+            DeserializeError::WrongAddress => f.write_str("invalid relative address")?,
+            DeserializeError::WrongLength(_) => f.write_str("wrong length of the value")?,
+            DeserializeError::InvalidUsize(value) => {
+                write!(f, "size {value} exceeds the maximum `usize`")?;
+            }
+            DeserializeError::InvalidIsize(value) => {
+                write!(f, "size {value} exceeds the range of `isize`")?;
+            }
+            DeserializeError::WrongVariant(tag, _) => write!(f, "invalid enum variant {tag}")?,
+            DeserializeError::NonUtf8(err) => write!(f, "string is not UTF-8: {err}")?,
+            DeserializeError::IntegerOverflow => {
+                f.write_str("integer does not fit into destination type")?;
+            }
+            DeserializeError::Incompatible => {
+                f.write_str("data is incompatible with the destination type")?;
+            }
+            DeserializeError::OverlappingReference => {
+                f.write_str("referenced data overlaps with other data")?;
+            }
+            DeserializeError::NonCanonical => f.write_str("input is not canonical encoding")?,
+            DeserializeError::InvalidBool(byte) => {
+                write!(f, "invalid `bool` or `Option` tag byte {byte}")?;
+            }
+            DeserializeError::InvalidChar(value) => write!(f, "invalid `char` value {value:#x}")?,
+            DeserializeError::ChecksumMismatch => {
+                f.write_str("checksum mismatch, data is corrupted")?;
+            }
+            DeserializeError::Unsorted => {
+                f.write_str("elements of sorted collection are out of order")?;
+            }
+            DeserializeError::NonAscii => f.write_str("string is not ASCII")?,
+            DeserializeError::AuthenticationFailed => {
+                f.write_str("encrypted payload failed authentication")?;
+            }
+            DeserializeError::InvalidSignature => f.write_str("invalid signature")?,
+        }
+
+        if let Some(details) = self.details() {
+            if let Some(formula) = details.formula() {
+                write!(f, " in `{formula}`")?;
+            }
+            match (details.expected(), details.actual()) {
+                (Some(expected), Some(actual)) => {
+                    write!(f, ", expected {expected} bytes, found {actual}")?;
+                }
+                (Some(expected), None) => write!(f, ", expected {expected} bytes")?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DeserializeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DeserializeError::NonUtf8(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<BufferExhausted> for DeserializeError {
+    /// Converts into `DeserializeError::OutOfBounds`.
+    #[inline(always)]
+    fn from(_: BufferExhausted) -> Self {
+        DeserializeError::OutOfBounds(ErrorDetails::new())
+    }
+}
+
+impl From<BufferSizeRequired> for DeserializeError {
+    /// Converts into `DeserializeError::OutOfBounds`
+    /// that expects required number of bytes.
+    #[inline(always)]
+    #[cfg_attr(not(feature = "debug-errors"), allow(unused_variables, unused_mut))]
+    fn from(err: BufferSizeRequired) -> Self {
+        let mut details = ErrorDetails::new();
+        #[cfg(feature = "debug-errors")]
+        {
+            details.expected = Some(err.required);
+        }
+        DeserializeError::OutOfBounds(details)
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for ErrorDetails {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::write!(
+            f,
+            "formula: {}, expected: {}, actual: {}",
+            self.formula(),
+            self.expected(),
+            self.actual()
+        );
+    }
 }
 
 /// Trait for types that can be deserialized
@@ -87,9 +448,28 @@ pub struct Deserializer<'de> {
     /// Input buffer sub-slice usable for deserialization.
     input: &'de [u8],
     stack: usize,
+
+    /// Signals that strings in the input are known to be valid UTF-8.
+    #[cfg(feature = "unchecked-utf8")]
+    trusted_utf8: bool,
 }
 
 impl<'de> Deserializer<'de> {
+    /// Returns error that occurred at current position in the input.
+    /// With "tracing" feature reports it along with the position.
+    #[inline(never)]
+    #[cold]
+    fn error<T>(&self, err: DeserializeError) -> Result<T, DeserializeError> {
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            offset = self.input.len(),
+            stack = self.stack,
+            error = ?err,
+            "deserialization error"
+        );
+        Err(err)
+    }
+
     /// Creates new deserializer from input buffer.
     ///
     /// # Errors
@@ -99,7 +479,10 @@ impl<'de> Deserializer<'de> {
     #[inline(always)]
     pub const fn new(stack: usize, input: &'de [u8]) -> Result<Self, DeserializeError> {
         if stack > input.len() {
-            return cold_err(DeserializeError::OutOfBounds);
+            return cold_err(DeserializeError::OutOfBounds(ErrorDetails::sizes(
+                stack,
+                input.len(),
+            )));
         }
         Ok(Self::new_unchecked(stack, input))
     }
@@ -108,16 +491,96 @@ impl<'de> Deserializer<'de> {
     #[inline(always)]
     pub const fn new_unchecked(stack: usize, input: &'de [u8]) -> Self {
         debug_assert!(stack <= input.len());
-        Deserializer { input, stack }
+        Deserializer {
+            input,
+            stack,
+            #[cfg(feature = "unchecked-utf8")]
+            trusted_utf8: false,
+        }
+    }
+
+    /// Marks input as trusted to contain only valid UTF-8 strings,
+    /// so that `str` is deserialized without validation.
+    ///
+    /// Deserializers for fields and references inherit the mark.
+    ///
+    /// # Safety
+    ///
+    /// Every byte sequence deserialized with `str` formula
+    /// from this deserializer must be valid UTF-8.
+    /// This holds if the input was produced by serialization
+    /// or passed [`validate_packet`](crate::validate_packet).
+    #[cfg(feature = "unchecked-utf8")]
+    #[allow(unsafe_code)]
+    #[inline(always)]
+    pub unsafe fn assume_utf8(mut self) -> Self {
+        self.trusted_utf8 = true;
+        self
+    }
+
+    /// Returns `true` if strings in the input are known to be valid UTF-8.
+    #[cfg(feature = "unchecked-utf8")]
+    #[inline(always)]
+    pub(crate) const fn trusted_utf8(&self) -> bool {
+        self.trusted_utf8
+    }
+
+    /// Creates deserializer for part of the input
+    /// that inherits properties of this one.
+    #[inline(always)]
+    fn part(&self, stack: usize, input: &'de [u8]) -> Self {
+        debug_assert!(stack <= input.len());
+        let mut part = self.clone();
+        part.input = input;
+        part.stack = stack;
+        part
+    }
+
+    /// Returns number of bytes remaining on stack.
+    #[inline(always)]
+    pub(crate) fn stack(&self) -> usize {
+        self.stack
+    }
+
+    /// Returns range of the input occupied by the stack.
+    /// Offsets are relative to the start of the original input.
+    #[cfg(feature = "std")]
+    #[inline(always)]
+    pub(crate) fn stack_range(&self) -> core::ops::Range<usize> {
+        self.input.len() - self.stack..self.input.len()
+    }
+
+    /// Returns input buffer that ends with the stack.
+    #[cfg(feature = "alloc")]
+    #[inline(always)]
+    pub(crate) fn input(&self) -> &'de [u8] {
+        self.input
+    }
+
+    /// Reads specified number of bytes from the front of the stack,
+    /// i.e. bytes that were written last.
+    #[cfg(feature = "alloc")]
+    #[inline(always)]
+    pub(crate) fn read_front_bytes(&mut self, len: usize) -> Result<&'de [u8], DeserializeError> {
+        if len > self.stack {
+            return self.error(DeserializeError::WrongLength(ErrorDetails::sizes(
+                len, self.stack,
+            )));
+        }
+        let at = self.input.len() - self.stack;
+        self.stack -= len;
+        Ok(&self.input[at..][..len])
     }
 
     #[inline(always)]
     pub(crate) fn sub(&mut self, stack: usize) -> Result<Self, DeserializeError> {
         if self.stack < stack {
-            return cold_err(DeserializeError::WrongLength);
+            return self.error(DeserializeError::WrongLength(ErrorDetails::sizes(
+                stack, self.stack,
+            )));
         }
 
-        let sub = Deserializer::new_unchecked(stack, self.input);
+        let sub = self.part(stack, self.input);
 
         self.stack -= stack;
         let end = self.input.len() - stack;
@@ -135,7 +598,9 @@ impl<'de> Deserializer<'de> {
     #[inline(always)]
     pub fn read_bytes(&mut self, len: usize) -> Result<&'de [u8], DeserializeError> {
         if len > self.stack {
-            return cold_err(DeserializeError::WrongLength);
+            return self.error(DeserializeError::WrongLength(ErrorDetails::sizes(
+                len, self.stack,
+            )));
         }
         let at = self.input.len() - len;
         let (head, tail) = self.input.split_at(at);
@@ -154,7 +619,7 @@ impl<'de> Deserializer<'de> {
     #[inline(always)]
     pub fn read_byte(&mut self) -> Result<u8, DeserializeError> {
         if self.stack == 0 {
-            return cold_err(DeserializeError::WrongLength);
+            return self.error(DeserializeError::WrongLength(ErrorDetails::sizes(1, 0)));
         }
 
         let [head @ .., last] = self.input else {
@@ -165,6 +630,25 @@ impl<'de> Deserializer<'de> {
         Ok(*last)
     }
 
+    /// Reads single byte flag, e.g. `bool` value or `Option` tag.
+    /// Any non-zero byte is `true` unless "strict" feature is enabled.
+    /// Advances the input buffer.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError` if stack is empty.
+    /// Returns `DeserializeError::InvalidBool` with "strict" feature
+    /// if byte is neither 0 nor 1.
+    #[inline(always)]
+    pub fn read_flag(&mut self) -> Result<bool, DeserializeError> {
+        match self.read_byte()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            byte if cfg!(feature = "strict") => self.error(DeserializeError::InvalidBool(byte)),
+            _ => Ok(true),
+        }
+    }
+
     /// Reads specified number of bytes from the input buffer.
     /// Returns slice of bytes.
     /// Advances the input buffer.
@@ -175,7 +659,9 @@ impl<'de> Deserializer<'de> {
     #[inline(always)]
     pub fn read_byte_array<const N: usize>(&mut self) -> Result<[u8; N], DeserializeError> {
         if N > self.stack {
-            return cold_err(DeserializeError::WrongLength);
+            return self.error(DeserializeError::WrongLength(ErrorDetails::sizes(
+                N, self.stack,
+            )));
         }
         let at = self.input.len() - N;
 
@@ -219,14 +705,30 @@ impl<'de> Deserializer<'de> {
         F: Formula + ?Sized,
         T: Deserialize<'de, F>,
     {
-        let stack = match (F::MAX_STACK_SIZE, F::EXACT_SIZE, last) {
+        let sub = self
+            .read_field_with(F::MAX_STACK_SIZE, F::EXACT_SIZE, last)
+            .map_err(DeserializeError::with_formula::<F>)?;
+        <T as Deserialize<'de, F>>::deserialize(sub).map_err(DeserializeError::with_formula::<F>)
+    }
+
+    /// Reads field of formula with specified properties from the input buffer.
+    /// Returns deserializer for the field.
+    #[cfg_attr(feature = "inline-more", inline(always))]
+    #[cfg_attr(not(feature = "inline-more"), inline(never))]
+    pub(crate) fn read_field_with(
+        &mut self,
+        max_stack_size: Option<usize>,
+        exact_size: bool,
+        last: bool,
+    ) -> Result<Self, DeserializeError> {
+        let stack = match (max_stack_size, exact_size, last) {
             (None, _, false) => self.read_usize()?,
             (None, _, true) => self.stack,
             (Some(max_stack), false, true) => max_stack.min(self.stack),
             (Some(max_stack), _, _) => max_stack,
         };
 
-        <T as Deserialize<'de, F>>::deserialize(self.sub(stack)?)
+        self.sub(stack)
     }
 
     /// Reads and deserializes field from the back of input buffer.
@@ -244,15 +746,16 @@ impl<'de> Deserializer<'de> {
         let stack = unwrap_size(F::MAX_STACK_SIZE);
 
         if self.stack < stack {
+            let details = ErrorDetails::sizes(stack, self.stack);
             self.stack = 0;
-            return cold_err(DeserializeError::WrongLength);
+            return self.error(DeserializeError::WrongLength(details).with_formula::<F>());
         }
 
         let input_back = &self.input[..self.input.len() - self.stack + stack];
         self.stack -= stack;
 
-        let sub = Deserializer::new_unchecked(stack, input_back);
-        <T as Deserialize<'de, F>>::deserialize(sub)
+        let sub = self.part(stack, input_back);
+        <T as Deserialize<'de, F>>::deserialize(sub).map_err(DeserializeError::with_formula::<F>)
     }
 
     /// Reads and deserializes field from the input buffer in-place.
@@ -272,7 +775,11 @@ impl<'de> Deserializer<'de> {
             (false, None) => self.read_usize()?,
         };
 
-        <T as Deserialize<'de, F>>::deserialize_in_place(place, self.sub(stack)?)
+        let sub = self
+            .sub(stack)
+            .map_err(DeserializeError::with_formula::<F>)?;
+        <T as Deserialize<'de, F>>::deserialize_in_place(place, sub)
+            .map_err(DeserializeError::with_formula::<F>)
     }
 
     /// Reads and deserializes reference from the input buffer.
@@ -286,21 +793,41 @@ impl<'de> Deserializer<'de> {
     where
         F: Formula + ?Sized,
     {
-        let reference_size = reference_size::<F>();
+        self.deref_with(F::MAX_STACK_SIZE, F::EXACT_SIZE)
+    }
+
+    /// Reads reference to formula with specified properties from the input buffer.
+    #[cfg_attr(feature = "inline-more", inline)]
+    #[cfg_attr(not(feature = "inline-more"), inline(never))]
+    pub(crate) fn deref_with(
+        self,
+        max_stack_size: Option<usize>,
+        exact_size: bool,
+    ) -> Result<Deserializer<'de>, DeserializeError> {
+        let reference_size = reference_size_with(exact_size);
         if self.stack < reference_size {
-            return Err(DeserializeError::OutOfBounds);
+            return self.error(DeserializeError::OutOfBounds(ErrorDetails::sizes(
+                reference_size,
+                self.stack,
+            )));
         }
 
         let (head, tail) = self.input.split_at(self.input.len() - reference_size);
-        let (address, size) = read_reference::<F>(tail, head.len());
+        let (address, size) = read_reference_with(tail, head.len(), max_stack_size, exact_size)?;
 
         if address > head.len() {
-            return Err(DeserializeError::WrongAddress);
+            return self.error(DeserializeError::WrongAddress);
         }
 
         let input = &head[..address];
+        if size > input.len() {
+            return self.error(DeserializeError::OutOfBounds(ErrorDetails::sizes(
+                size,
+                input.len(),
+            )));
+        }
 
-        Deserializer::new(size, input)
+        Ok(self.part(size, input))
     }
 
     /// Converts deserializer into iterator over deserialized values with
@@ -403,6 +930,67 @@ impl<'de> Deserializer<'de> {
     //     }
     // }
 
+    /// Splits off deserializer for at most `n` next values with specified formula.
+    /// Returned deserializer can be used independently from `self`,
+    /// e.g. on another thread, and `self` continues after split off values.
+    ///
+    /// This is O(1) for formulas with known `MAX_STACK_SIZE`.
+    /// Otherwise size prefixes of split off values are read.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError` if size prefix of a value is malformed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if formula is zero-sized, since values of such formulas
+    /// are not stored and only their count is.
+    #[inline]
+    pub fn split_values<F>(&mut self, n: usize) -> Result<Deserializer<'de>, DeserializeError>
+    where
+        F: Formula + ?Sized,
+    {
+        let stack = match F::MAX_STACK_SIZE {
+            None => {
+                let mut de = self.clone();
+                let mut count = 0;
+                while count < n && de.stack >= SIZE_STACK {
+                    de.skip_values::<F>(1)?;
+                    count += 1;
+                }
+                self.stack - de.stack
+            }
+            Some(0) => panic!("Values of zero-sized formula cannot be split"),
+            Some(max_stack) => max_stack * n.min(self.stack / max_stack),
+        };
+        self.sub(stack)
+    }
+
+    /// Returns number of values with specified formula remaining on stack
+    /// without deserializing them.
+    ///
+    /// This is O(1) for formulas with known `MAX_STACK_SIZE`.
+    /// Otherwise size prefixes of all values are read.
+    /// Counting stops at the first malformed value.
+    #[inline]
+    pub(crate) fn count_values<F>(&self) -> usize
+    where
+        F: Formula + ?Sized,
+    {
+        match F::MAX_STACK_SIZE {
+            None => {
+                let mut de = self.clone();
+                let mut count = 0;
+                while de.stack >= SIZE_STACK && de.skip_values::<F>(1).is_ok() {
+                    count += 1;
+                }
+                count
+            }
+            Some(0) => self.clone().read_usize().unwrap_or(0),
+            Some(max_stack) => self.stack / max_stack,
+        }
+    }
+
     /// Skips specified number of values with specified formula.
     #[inline]
     fn skip_values<F>(&mut self, n: usize) -> Result<(), DeserializeError>
@@ -421,7 +1009,7 @@ impl<'de> Deserializer<'de> {
                 }
             }
             Some(max_stack) => {
-                let skip_bytes = max_stack * n;
+                let skip_bytes = max_stack.saturating_mul(n);
                 self.read_bytes(skip_bytes)?;
             }
         }
@@ -539,39 +1127,31 @@ where
         Fun: FnMut(B, Result<T, DeserializeError>) -> B,
     {
         match F::MAX_STACK_SIZE {
-            None => loop {
-                if self.de.stack < SIZE_STACK {
-                    break;
-                }
-                let sub = Deserializer::new_unchecked(SIZE_STACK, self.de.input);
-                self.de.input = &self.de.input[..self.de.input.len() - SIZE_STACK];
-
-                let stack = match deserialize_usize(sub) {
-                    Ok(stack) => stack,
-                    Err(err) => {
-                        self.de.stack = 0;
-                        return f(init, cold_err(err));
-                    }
-                };
-                let sub = Deserializer::new_unchecked(stack, self.de.input);
-                self.de.input = &self.de.input[..self.de.input.len() - stack];
-                self.de.stack -= SIZE_STACK * stack;
+            None => {
+                while !self.is_empty() {
+                    let sub = match self.de.read_usize().and_then(|stack| self.de.sub(stack)) {
+                        Ok(sub) => sub,
+                        Err(err) => return f(init, cold_err(err)),
+                    };
+                    self.upper -= 1;
 
-                let result = <T as Deserialize<'de, F>>::deserialize(sub);
-                init = f(init, result);
-            },
+                    let result = <T as Deserialize<'de, F>>::deserialize(sub);
+                    init = f(init, result);
+                }
+            }
             Some(0) => {
-                let sub = Deserializer::new_unchecked(0, self.de.input);
+                let sub = self.de.part(0, self.de.input);
                 for _ in 0..self.upper {
                     let result = <T as Deserialize<'de, F>>::deserialize(sub.clone());
                     init = f(init, result);
                 }
             }
             Some(stack) => {
-                assert_eq!(self.de.stack / stack, self.upper);
                 for _ in 0..self.upper {
-                    let sub = Deserializer::new_unchecked(stack, self.de.input);
-                    self.de.input = &self.de.input[..self.de.input.len() - stack];
+                    let sub = match self.de.sub(stack) {
+                        Ok(sub) => sub,
+                        Err(err) => return f(init, cold_err(err)),
+                    };
 
                     let result = <T as Deserialize<'de, F>>::deserialize(sub);
                     init = f(init, result);
@@ -612,7 +1192,14 @@ where
                 self.upper = 0;
                 return None;
             }
-            self.de.stack -= n * Self::ELEMENT_SIZE;
+            let skip_bytes = Self::ELEMENT_SIZE.saturating_mul(n);
+            if skip_bytes > self.de.stack {
+                let details = ErrorDetails::sizes(skip_bytes, self.de.stack);
+                self.upper = 0;
+                self.de.stack = 0;
+                return Some(cold_err(DeserializeError::WrongLength(details)));
+            }
+            self.de.stack -= skip_bytes;
             self.upper -= n;
         }
         self.next_back()
@@ -625,18 +1212,25 @@ where
     {
         match Self::ELEMENT_SIZE {
             0 => {
-                let sub = Deserializer::new_unchecked(0, self.de.input);
+                let sub = self.de.part(0, self.de.input);
                 for _ in 0..self.upper {
                     let result = <T as Deserialize<'de, F>>::deserialize(sub.clone());
                     init = f(init, result);
                 }
             }
             stack => {
-                assert_eq!(self.de.stack / stack, self.upper);
-                let mut end = self.de.input.len() - stack * self.upper;
+                let Some(size) = stack
+                    .checked_mul(self.upper)
+                    .filter(|&size| size <= self.de.stack)
+                else {
+                    let details =
+                        ErrorDetails::sizes(stack.saturating_mul(self.upper), self.de.stack);
+                    return f(init, cold_err(DeserializeError::WrongLength(details)));
+                };
+                let mut end = self.de.input.len() - size;
                 for _ in 0..self.upper {
                     end += stack;
-                    let sub = Deserializer::new_unchecked(stack, &self.de.input[..end]);
+                    let sub = self.de.part(stack, &self.de.input[..end]);
 
                     let result = <T as Deserialize<'de, F>>::deserialize(sub);
                     init = f(init, result);
@@ -679,6 +1273,14 @@ where
     F: Formula + ?Sized,
     T: Deserialize<'de, F>,
 {
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!(
+        "deserialize",
+        formula = core::any::type_name::<F>(),
+        len = input.len(),
+    )
+    .entered();
+
     assert!(
         F::HEAPLESS || F::MAX_STACK_SIZE.is_some(),
         "The value must be either sized or heap-less.
@@ -702,7 +1304,8 @@ where
     };
 
     let de = Deserializer::new_unchecked(stack, input);
-    let value = <T as Deserialize<'de, F>>::deserialize(de)?;
+    let value =
+        <T as Deserialize<'de, F>>::deserialize(de).map_err(DeserializeError::with_formula::<F>)?;
 
     Ok(value)
 }
@@ -723,8 +1326,17 @@ where
     F: Formula + ?Sized,
     T: Deserialize<'de, F>,
 {
-    let de = Deserializer::new_unchecked(stack, input);
-    let value = <T as Deserialize<'de, F>>::deserialize(de)?;
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!(
+        "deserialize_with_size",
+        formula = core::any::type_name::<F>(),
+        len = input.len(),
+    )
+    .entered();
+
+    let de = Deserializer::new(stack, input).map_err(DeserializeError::with_formula::<F>)?;
+    let value =
+        <T as Deserialize<'de, F>>::deserialize(de).map_err(DeserializeError::with_formula::<F>)?;
 
     Ok(value)
 }
@@ -746,12 +1358,21 @@ where
     F: Formula + ?Sized,
     T: Deserialize<'de, F> + ?Sized,
 {
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!(
+        "deserialize_in_place",
+        formula = core::any::type_name::<F>(),
+        len = input.len(),
+    )
+    .entered();
+
     let stack = match F::MAX_STACK_SIZE {
         None => input.len(),
         Some(max_stack) => max_stack.min(input.len()),
     };
     let de = Deserializer::new_unchecked(stack, input);
-    <T as Deserialize<'de, F>>::deserialize_in_place(place, de)?;
+    <T as Deserialize<'de, F>>::deserialize_in_place(place, de)
+        .map_err(DeserializeError::with_formula::<F>)?;
 
     Ok(())
 }
@@ -773,31 +1394,74 @@ where
     F: Formula + ?Sized,
     T: Deserialize<'de, F> + ?Sized,
 {
-    let de = Deserializer::new_unchecked(stack, input);
-    <T as Deserialize<'de, F>>::deserialize_in_place(place, de)?;
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!(
+        "deserialize_in_place_with_size",
+        formula = core::any::type_name::<F>(),
+        len = input.len(),
+    )
+    .entered();
+
+    let de = Deserializer::new(stack, input).map_err(DeserializeError::with_formula::<F>)?;
+    <T as Deserialize<'de, F>>::deserialize_in_place(place, de)
+        .map_err(DeserializeError::with_formula::<F>)?;
 
     Ok(())
 }
 
+/// Reads address and size of the value of formula `F`
+/// from the reference at the start of `input`.
+///
+/// # Panics
+///
+/// Panics if reference is malformed.
+/// Use [`try_read_reference`] for untrusted input.
 #[inline(always)]
 pub fn read_reference<F>(input: &[u8], len: usize) -> (usize, usize)
 where
     F: Formula + ?Sized,
 {
-    let reference_size = reference_size::<F>();
-    debug_assert!(reference_size <= input.len());
+    match try_read_reference::<F>(input, len) {
+        Ok(reference) => reference,
+        Err(err) => panic!("malformed reference: {err}"),
+    }
+}
 
-    if F::EXACT_SIZE {
-        let mut de = Deserializer::new(reference_size, &input[..reference_size]).unwrap();
-        let Ok(address) = de.read_usize() else {
-            unreachable!();
-        };
-        (address, unwrap_size(F::MAX_STACK_SIZE).min(len))
+/// Reads address and size of the value of formula `F`
+/// from the reference at the start of `input`.
+///
+/// # Errors
+///
+/// Returns `DeserializeError` if reference is malformed.
+#[inline(always)]
+pub fn try_read_reference<F>(input: &[u8], len: usize) -> Result<(usize, usize), DeserializeError>
+where
+    F: Formula + ?Sized,
+{
+    read_reference_with(input, len, F::MAX_STACK_SIZE, F::EXACT_SIZE)
+}
+
+#[inline(always)]
+pub(crate) fn read_reference_with(
+    input: &[u8],
+    len: usize,
+    max_stack_size: Option<usize>,
+    exact_size: bool,
+) -> Result<(usize, usize), DeserializeError> {
+    let reference_size = reference_size_with(exact_size);
+    let Some(input) = input.get(..reference_size) else {
+        return cold_err(DeserializeError::OutOfBounds(ErrorDetails::sizes(
+            reference_size,
+            input.len(),
+        )));
+    };
+
+    let mut de = Deserializer::new_unchecked(reference_size, input);
+    if exact_size {
+        let address = de.read_usize()?;
+        Ok((address, unwrap_size(max_stack_size).min(len)))
     } else {
-        let mut de = Deserializer::new(reference_size, &input[..reference_size]).unwrap();
-        let Ok([size, address]) = de.read_value::<[usize; 2], [usize; 2]>(true) else {
-            unreachable!();
-        };
-        (address, size)
+        let [size, address] = de.read_value::<[usize; 2], [usize; 2]>(true)?;
+        Ok((address, size))
     }
 }
@@ -0,0 +1,413 @@
+//!
+//! This module provides structural diff of serialized values.
+//!
+
+use alloc::{format, string::String, vec::Vec};
+
+use crate::{
+    deserialize::{Deserialize, DeserializeError, Deserializer, ErrorDetails},
+    formula::Formula,
+    schema::{Schema, SchemaField, SchemaKind},
+    size::SIZE_STACK,
+    value::{decode, node, packet_deserializer, slice_elements, Value},
+};
+
+/// Difference between two serialized values at some path.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValueDiff {
+    /// Path to the differing value from the root.
+    /// Uses the same notation as [`SchemaChange::path`](crate::SchemaChange::path).
+    /// Empty for the root value.
+    pub path: String,
+
+    /// Value in the old buffer.
+    /// `None` if value is absent, e.g. slice element past the end.
+    pub old: Option<Value>,
+
+    /// Value in the new buffer.
+    /// `None` if value is absent, e.g. slice element past the end.
+    pub new: Option<Value>,
+}
+
+/// Compares two packets with values of formula `F`.
+/// Returns list of differences, empty if values are equal.
+///
+/// See [`Schema::diff`].
+///
+/// # Errors
+///
+/// Returns `DeserializeError` if either packet fails to deserialize.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// let mut old = Vec::new();
+/// write_packet_to_vec::<(u8, Vec<u16>), _>((1u8, [2u16, 3]), &mut old);
+///
+/// let mut new = Vec::new();
+/// write_packet_to_vec::<(u8, Vec<u16>), _>((1u8, [2u16, 4]), &mut new);
+///
+/// let diffs = diff::<(u8, Vec<u16>)>(&old, &new).unwrap();
+/// assert_eq!(diffs.len(), 1);
+/// assert_eq!(diffs[0].path, ".1[1]");
+/// assert_eq!(diffs[0].old, Some(Value::Unsigned(3)));
+/// assert_eq!(diffs[0].new, Some(Value::Unsigned(4)));
+/// ```
+pub fn diff<F>(old: &[u8], new: &[u8]) -> Result<Vec<ValueDiff>, DeserializeError>
+where
+    F: Formula + ?Sized,
+{
+    Schema::of::<F>().diff(old, new)
+}
+
+/// Compares two packets with values of formula `F` for semantic equality.
+///
+/// Values are walked in parallel without deserializing them,
+/// so encodings that differ only in non-canonical details,
+/// like placement of referenced data or slack in sizes, compare equal.
+///
+/// See [`Schema::values_eq`].
+///
+/// # Errors
+///
+/// Returns `DeserializeError` if either packet fails to deserialize.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// let mut a = Vec::new();
+/// write_packet_to_vec::<(Ref<str>, Ref<str>), _>(("x", "y"), &mut a);
+///
+/// let mut b = Vec::new();
+/// write_packet_to_vec::<(Ref<str>, Ref<str>), _>(("x", "y"), &mut b);
+///
+/// let mut c = Vec::new();
+/// write_packet_to_vec::<(Ref<str>, Ref<str>), _>(("x", "z"), &mut c);
+///
+/// assert!(eq::<(Ref<str>, Ref<str>)>(&a, &b).unwrap());
+/// assert!(!eq::<(Ref<str>, Ref<str>)>(&a, &c).unwrap());
+/// ```
+pub fn eq<F>(a: &[u8], b: &[u8]) -> Result<bool, DeserializeError>
+where
+    F: Formula + ?Sized,
+{
+    Schema::of::<F>().values_eq(a, b)
+}
+
+impl Schema {
+    /// Compares two packets with values which layout is described by the schema
+    /// for semantic equality.
+    ///
+    /// Strings and bytes are compared in place, numbers are compared after decoding
+    /// and floats are compared as with `==`.
+    /// Data under formulas that do not describe their structure is compared byte-wise.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError` if either packet fails to deserialize.
+    /// Returns `DeserializeError::Incompatible` if schema is malformed.
+    pub fn values_eq(&self, a: &[u8], b: &[u8]) -> Result<bool, DeserializeError> {
+        let (a, _) = packet_deserializer(self, a)?;
+        let (b, _) = packet_deserializer(self, b)?;
+        nodes_eq(self, 0, a, b)
+    }
+
+    /// Compares two packets with values which layout is described by the schema.
+    /// Returns list of differences, empty if values are equal.
+    ///
+    /// Values are walked in parallel and only differing parts are decoded.
+    /// Subtrees without references that have equal bytes are skipped without decoding.
+    /// When enum variants or presence of optional values differ,
+    /// the whole enum or option is reported.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError` if either packet fails to deserialize.
+    /// Returns `DeserializeError::Incompatible` if schema is malformed.
+    pub fn diff(&self, old: &[u8], new: &[u8]) -> Result<Vec<ValueDiff>, DeserializeError> {
+        let (old, _) = packet_deserializer(self, old)?;
+        let (new, _) = packet_deserializer(self, new)?;
+
+        let mut differ = Differ {
+            schema: self,
+            diffs: Vec::new(),
+        };
+        differ.node(0, old, new, &mut String::new())?;
+        Ok(differ.diffs)
+    }
+}
+
+struct Differ<'a> {
+    schema: &'a Schema,
+    diffs: Vec<ValueDiff>,
+}
+
+impl Differ<'_> {
+    fn report(
+        &mut self,
+        idx: usize,
+        old: Option<Deserializer>,
+        new: Option<Deserializer>,
+        path: &str,
+    ) -> Result<(), DeserializeError> {
+        let old = old.map(|de| decode(self.schema, idx, de)).transpose()?;
+        let new = new.map(|de| decode(self.schema, idx, de)).transpose()?;
+        if old != new {
+            self.diffs.push(ValueDiff {
+                path: path.into(),
+                old,
+                new,
+            });
+        }
+        Ok(())
+    }
+
+    fn node(
+        &mut self,
+        idx: usize,
+        mut old: Deserializer,
+        mut new: Deserializer,
+        path: &mut String,
+    ) -> Result<(), DeserializeError> {
+        let current = node(self.schema, idx)?;
+
+        // Without references equal bytes mean equal values.
+        if current.heapless && old.clone().read_all_bytes() == new.clone().read_all_bytes() {
+            return Ok(());
+        }
+
+        match &current.kind {
+            SchemaKind::Array(elem, len) => {
+                for elem_idx in 0..*len {
+                    let old = self.field(*elem, &mut old, false)?;
+                    let new = self.field(*elem, &mut new, false)?;
+                    self.nested(*elem, old, new, path, &format!("[{elem_idx}]"))?;
+                }
+                Ok(())
+            }
+            SchemaKind::Slice(elem) => {
+                let old = slice_elements(self.schema, *elem, old)?;
+                let new = slice_elements(self.schema, *elem, new)?;
+                let len = old.len().max(new.len());
+
+                let mut old = old.into_iter();
+                let mut new = new.into_iter();
+                for elem_idx in 0..len {
+                    let segment = format!("[{elem_idx}]");
+                    match (old.next(), new.next()) {
+                        (Some(old), Some(new)) => self.nested(*elem, old, new, path, &segment)?,
+                        (old, new) => {
+                            let len = path.len();
+                            path.push_str(&segment);
+                            self.report(*elem, old, new, path)?;
+                            path.truncate(len);
+                        }
+                    }
+                }
+                Ok(())
+            }
+            SchemaKind::Ref(formula) => {
+                let pointee = node(self.schema, *formula)?;
+                let old = old.deref_with(pointee.max_stack_size, pointee.exact_size)?;
+                let new = new.deref_with(pointee.max_stack_size, pointee.exact_size)?;
+                self.node(*formula, old, new, path)
+            }
+            SchemaKind::Option(formula) => {
+                let (old_some, new_some) = (old.clone().read_flag()?, new.clone().read_flag()?);
+                if old_some != new_some {
+                    return self.report(idx, Some(old), Some(new), path);
+                }
+                if !old_some {
+                    return Ok(());
+                }
+                old.read_byte()?;
+                new.read_byte()?;
+                let old = self.field(*formula, &mut old, true)?;
+                let new = self.field(*formula, &mut new, true)?;
+                self.nested(*formula, old, new, path, "?")
+            }
+            SchemaKind::Tuple(elems) => {
+                let len = elems.len();
+                for (elem_idx, elem) in elems.iter().enumerate() {
+                    let last = elem_idx + 1 == len;
+                    let old = self.field(*elem, &mut old, last)?;
+                    let new = self.field(*elem, &mut new, last)?;
+                    self.nested(*elem, old, new, path, &format!(".{elem_idx}"))?;
+                }
+                Ok(())
+            }
+            SchemaKind::Struct(fields) => self.fields(fields, old, new, path),
+            SchemaKind::Enum(variants) => {
+                let old_tag = old.clone().read_value::<u32, u32>(false)?;
+                let new_tag = new.clone().read_value::<u32, u32>(false)?;
+                if old_tag != new_tag {
+                    return self.report(idx, Some(old), Some(new), path);
+                }
+
+                let Some(variant) = variants.iter().find(|variant| variant.tag == old_tag) else {
+                    return Err(DeserializeError::WrongVariant(old_tag, ErrorDetails::new()));
+                };
+                old.read_value::<u32, u32>(false)?;
+                new.read_value::<u32, u32>(false)?;
+
+                let len = path.len();
+                path.push_str("::");
+                path.push_str(&variant.name);
+                self.fields(&variant.fields, old, new, path)?;
+                path.truncate(len);
+                Ok(())
+            }
+            _ => self.report(idx, Some(old), Some(new), path),
+        }
+    }
+
+    fn field<'de>(
+        &self,
+        idx: usize,
+        de: &mut Deserializer<'de>,
+        last: bool,
+    ) -> Result<Deserializer<'de>, DeserializeError> {
+        let field = node(self.schema, idx)?;
+        de.read_field_with(field.max_stack_size, field.exact_size, last)
+    }
+
+    fn nested(
+        &mut self,
+        idx: usize,
+        old: Deserializer,
+        new: Deserializer,
+        path: &mut String,
+        segment: &str,
+    ) -> Result<(), DeserializeError> {
+        let len = path.len();
+        path.push_str(segment);
+        self.node(idx, old, new, path)?;
+        path.truncate(len);
+        Ok(())
+    }
+
+    fn fields(
+        &mut self,
+        fields: &[SchemaField],
+        mut old: Deserializer,
+        mut new: Deserializer,
+        path: &mut String,
+    ) -> Result<(), DeserializeError> {
+        let len = fields.len();
+        for (field_idx, field) in fields.iter().enumerate() {
+            let last = field_idx + 1 == len;
+            let old_field = self.field(field.formula, &mut old, last)?;
+            let new_field = self.field(field.formula, &mut new, last)?;
+            self.nested(
+                field.formula,
+                old_field,
+                new_field,
+                path,
+                &format!(".{}", field.name),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn fields_eq(
+    schema: &Schema,
+    fields: impl ExactSizeIterator<Item = usize>,
+    mut a: Deserializer,
+    mut b: Deserializer,
+) -> Result<bool, DeserializeError> {
+    let len = fields.len();
+    for (field_idx, idx) in fields.enumerate() {
+        let last = field_idx + 1 == len;
+        let field = node(schema, idx)?;
+        let a = a.read_field_with(field.max_stack_size, field.exact_size, last)?;
+        let b = b.read_field_with(field.max_stack_size, field.exact_size, last)?;
+        if !nodes_eq(schema, idx, a, b)? {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+fn nodes_eq(
+    schema: &Schema,
+    idx: usize,
+    mut a: Deserializer,
+    mut b: Deserializer,
+) -> Result<bool, DeserializeError> {
+    let current = node(schema, idx)?;
+
+    // Without references equal bytes mean equal values.
+    if current.heapless && a.clone().read_all_bytes() == b.clone().read_all_bytes() {
+        return Ok(true);
+    }
+
+    match &current.kind {
+        SchemaKind::Opaque | SchemaKind::Bytes => Ok(a.read_all_bytes() == b.read_all_bytes()),
+        SchemaKind::Str => {
+            let a = <&str as Deserialize<str>>::deserialize(a)?;
+            let b = <&str as Deserialize<str>>::deserialize(b)?;
+            Ok(a == b)
+        }
+        SchemaKind::Bool
+        | SchemaKind::Unsigned(_)
+        | SchemaKind::Signed(_)
+        | SchemaKind::Float(_)
+        | SchemaKind::Vlq => Ok(decode(schema, idx, a)? == decode(schema, idx, b)?),
+        SchemaKind::Array(elem, len) => fields_eq(schema, (0..*len).map(|_| *elem), a, b),
+        SchemaKind::Slice(elem) => {
+            let elem_node = node(schema, *elem)?;
+            let min_stack = match elem_node.max_stack_size {
+                // Zero-sized elements are equal, only count matters.
+                Some(0) => return Ok(a.read_usize()? == b.read_usize()?),
+                Some(max_stack) => max_stack,
+                None => SIZE_STACK,
+            };
+            loop {
+                match (a.stack() >= min_stack, b.stack() >= min_stack) {
+                    (false, false) => return Ok(true),
+                    (true, true) => {}
+                    _ => return Ok(false),
+                }
+                let a = a.read_field_with(elem_node.max_stack_size, elem_node.exact_size, false)?;
+                let b = b.read_field_with(elem_node.max_stack_size, elem_node.exact_size, false)?;
+                if !nodes_eq(schema, *elem, a, b)? {
+                    return Ok(false);
+                }
+            }
+        }
+        SchemaKind::Ref(formula) => {
+            let pointee = node(schema, *formula)?;
+            let a = a.deref_with(pointee.max_stack_size, pointee.exact_size)?;
+            let b = b.deref_with(pointee.max_stack_size, pointee.exact_size)?;
+            nodes_eq(schema, *formula, a, b)
+        }
+        SchemaKind::Option(formula) => match (a.read_flag()?, b.read_flag()?) {
+            (false, false) => Ok(true),
+            (true, true) => fields_eq(schema, core::iter::once(*formula), a, b),
+            _ => Ok(false),
+        },
+        SchemaKind::Tuple(elems) => fields_eq(schema, elems.iter().copied(), a, b),
+        SchemaKind::Struct(fields) => {
+            fields_eq(schema, fields.iter().map(|field| field.formula), a, b)
+        }
+        SchemaKind::Enum(variants) => {
+            let a_tag = a.read_value::<u32, u32>(false)?;
+            let b_tag = b.read_value::<u32, u32>(false)?;
+            if a_tag != b_tag {
+                return Ok(false);
+            }
+            let Some(variant) = variants.iter().find(|variant| variant.tag == a_tag) else {
+                return Err(DeserializeError::WrongVariant(a_tag, ErrorDetails::new()));
+            };
+            fields_eq(
+                schema,
+                variant.fields.iter().map(|field| field.formula),
+                a,
+                b,
+            )
+        }
+    }
+}
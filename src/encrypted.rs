@@ -0,0 +1,292 @@
+//!
+//! This module provides formula wrapper that encrypts payload with pluggable AEAD cipher.
+//!
+
+use core::{fmt, marker::PhantomData};
+
+use alloc::vec::Vec;
+
+use crate::{
+    buffer::Buffer,
+    bytes::Bytes,
+    deserialize::{Deserialize, DeserializeError, Deserializer, ErrorDetails},
+    formula::{BareFormula, Formula},
+    packet::{read_packet, write_packet_to_vec},
+    reference::Ref,
+    serialize::{Serialize, Sizes},
+};
+
+/// Authenticated encryption with associated data.
+///
+/// Implement this trait for cipher of choice,
+/// e.g. AES-GCM or ChaCha20-Poly1305, to use it with [`Encrypted`] formula.
+pub trait Cipher {
+    /// Size of the nonce in bytes.
+    const NONCE_SIZE: usize;
+
+    /// Size of the authentication tag in bytes.
+    const TAG_SIZE: usize;
+
+    /// Encrypts data in place and writes authentication tag.
+    ///
+    /// `nonce` is `NONCE_SIZE` bytes long and `tag` is `TAG_SIZE` bytes long.
+    fn encrypt_in_place(&self, nonce: &[u8], data: &mut [u8], tag: &mut [u8]);
+
+    /// Verifies authentication tag and decrypts data in place.
+    ///
+    /// `nonce` is `NONCE_SIZE` bytes long and `tag` is `TAG_SIZE` bytes long.
+    ///
+    /// # Errors
+    ///
+    /// Returns `AuthenticationFailed` if data or tag is forged or corrupted.
+    fn decrypt_in_place(
+        &self,
+        nonce: &[u8],
+        data: &mut [u8],
+        tag: &[u8],
+    ) -> Result<(), AuthenticationFailed>;
+}
+
+/// Error returned by [`Cipher::decrypt_in_place`]
+/// when authentication tag does not match.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AuthenticationFailed;
+
+impl fmt::Display for AuthenticationFailed {
+    #[inline(always)]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "authentication failed")
+    }
+}
+
+/// Formula wrapper that stores payload of formula `F`
+/// encrypted with cipher `C`.
+///
+/// Layout is the same as `Ref<Bytes>`.
+/// Referenced bytes are nonce, then encrypted packet of formula `F`,
+/// then authentication tag, with no associated data.
+/// Peers with the same cipher can decrypt the payload without alkahest
+/// and read the packet with [`read_packet`].
+///
+/// Values are serialized through [`Encrypt`]
+/// and deserialized into [`Sealed`] that is opened with the cipher.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// // Toy cipher for demonstration. Never use it for real data.
+/// struct Xor(u8);
+///
+/// impl Cipher for Xor {
+///     const NONCE_SIZE: usize = 1;
+///     const TAG_SIZE: usize = 1;
+///
+///     fn encrypt_in_place(&self, nonce: &[u8], data: &mut [u8], tag: &mut [u8]) {
+///         tag[0] = data.iter().fold(self.0, |acc, b| acc.wrapping_add(*b));
+///         data.iter_mut().for_each(|b| *b ^= self.0 ^ nonce[0]);
+///     }
+///
+///     fn decrypt_in_place(&self, nonce: &[u8], data: &mut [u8], tag: &[u8]) -> Result<(), AuthenticationFailed> {
+///         data.iter_mut().for_each(|b| *b ^= self.0 ^ nonce[0]);
+///         match data.iter().fold(self.0, |acc, b| acc.wrapping_add(*b)) == tag[0] {
+///             true => Ok(()),
+///             false => Err(AuthenticationFailed),
+///         }
+///     }
+/// }
+///
+/// let cipher = Xor(0x5a);
+/// let mut buffer = Vec::new();
+/// let size = write_packet_to_vec::<(u32, Encrypted<str, Xor>), _>(
+///     (1u32, Encrypt::new(&cipher, &[7], "secret")),
+///     &mut buffer,
+/// );
+///
+/// let ((id, sealed), _) =
+///     read_packet::<(u32, Encrypted<str, Xor>), (u32, Sealed<str, Xor>)>(&buffer[..size]).unwrap();
+/// assert_eq!(id, 1);
+/// assert_eq!(sealed.nonce(), [7]);
+///
+/// let mut plain = Vec::new();
+/// let secret: &str = sealed.open(&cipher, &mut plain).unwrap();
+/// assert_eq!(secret, "secret");
+///
+/// assert!(matches!(
+///     sealed.open::<&str>(&Xor(0), &mut plain),
+///     Err(DeserializeError::AuthenticationFailed),
+/// ));
+/// ```
+pub struct Encrypted<F: ?Sized, C> {
+    marker: PhantomData<fn(&F, C) -> &F>,
+}
+
+impl<F, C> Formula for Encrypted<F, C>
+where
+    F: Formula + ?Sized,
+    C: Cipher,
+{
+    const MAX_STACK_SIZE: Option<usize> = <Ref<Bytes> as Formula>::MAX_STACK_SIZE;
+    const EXACT_SIZE: bool = <Ref<Bytes> as Formula>::EXACT_SIZE;
+    const HEAPLESS: bool = <Ref<Bytes> as Formula>::HEAPLESS;
+}
+
+impl<F, C> BareFormula for Encrypted<F, C>
+where
+    F: Formula + ?Sized,
+    C: Cipher,
+{
+}
+
+/// Value to be encrypted with cipher `C` and serialized with [`Encrypted`] formula.
+pub struct Encrypt<'a, C, T> {
+    cipher: &'a C,
+    nonce: &'a [u8],
+    value: T,
+}
+
+impl<'a, C, T> Encrypt<'a, C, T>
+where
+    C: Cipher,
+{
+    /// Wraps the value to be encrypted with the cipher and nonce.
+    ///
+    /// Nonce must never be reused with the same key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if nonce is not `C::NONCE_SIZE` bytes long.
+    #[inline(always)]
+    pub fn new(cipher: &'a C, nonce: &'a [u8], value: T) -> Self {
+        assert_eq!(nonce.len(), C::NONCE_SIZE, "Wrong nonce size");
+        Encrypt {
+            cipher,
+            nonce,
+            value,
+        }
+    }
+}
+
+impl<F, C, T> Serialize<Encrypted<F, C>> for Encrypt<'_, C, T>
+where
+    F: Formula + ?Sized,
+    C: Cipher,
+    T: Serialize<F>,
+{
+    #[inline]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        let mut packet = Vec::new();
+        let size = write_packet_to_vec::<F, T>(self.value, &mut packet);
+
+        let mut sealed = Vec::with_capacity(C::NONCE_SIZE + size + C::TAG_SIZE);
+        sealed.extend_from_slice(self.nonce);
+        sealed.extend_from_slice(&packet[..size]);
+        sealed.resize(C::NONCE_SIZE + size + C::TAG_SIZE, 0);
+
+        let (nonce, rest) = sealed.split_at_mut(C::NONCE_SIZE);
+        let (data, tag) = rest.split_at_mut(size);
+        self.cipher.encrypt_in_place(nonce, data, tag);
+
+        <&[u8] as Serialize<Ref<Bytes>>>::serialize(&sealed, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        None
+    }
+}
+
+/// Encrypted payload deserialized from [`Encrypted`] formula.
+pub struct Sealed<'de, F: ?Sized, C> {
+    bytes: &'de [u8],
+    marker: PhantomData<fn(&F, C) -> &F>,
+}
+
+impl<F, C> Clone for Sealed<'_, F, C>
+where
+    F: ?Sized,
+{
+    #[inline(always)]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<F, C> Copy for Sealed<'_, F, C> where F: ?Sized {}
+
+impl<'de, F, C> Sealed<'de, F, C>
+where
+    F: Formula + ?Sized,
+    C: Cipher,
+{
+    /// Returns nonce the payload was encrypted with.
+    #[must_use]
+    #[inline(always)]
+    pub fn nonce(&self) -> &'de [u8] {
+        &self.bytes[..C::NONCE_SIZE]
+    }
+
+    /// Returns encrypted bytes including nonce and authentication tag.
+    #[must_use]
+    #[inline(always)]
+    pub fn as_bytes(&self) -> &'de [u8] {
+        self.bytes
+    }
+
+    /// Authenticates and decrypts the payload into `buffer`
+    /// and deserializes value from it.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError::AuthenticationFailed`
+    /// if payload was not encrypted by this cipher or was tampered with.
+    /// Returns other `DeserializeError` if decrypted packet is invalid.
+    #[inline]
+    pub fn open<'a, T>(&self, cipher: &C, buffer: &'a mut Vec<u8>) -> Result<T, DeserializeError>
+    where
+        T: Deserialize<'a, F>,
+    {
+        let (nonce, rest) = self.bytes.split_at(C::NONCE_SIZE);
+        let (data, tag) = rest.split_at(rest.len() - C::TAG_SIZE);
+
+        buffer.clear();
+        buffer.extend_from_slice(data);
+        if cipher.decrypt_in_place(nonce, buffer, tag).is_err() {
+            return Err(DeserializeError::AuthenticationFailed);
+        }
+
+        let (value, _) = read_packet::<F, T>(buffer)?;
+        Ok(value)
+    }
+}
+
+impl<'de, 'fe: 'de, F, C> Deserialize<'fe, Encrypted<F, C>> for Sealed<'de, F, C>
+where
+    F: Formula + ?Sized,
+    C: Cipher,
+{
+    #[inline(always)]
+    fn deserialize(de: Deserializer<'fe>) -> Result<Self, DeserializeError> {
+        let bytes = <&[u8] as Deserialize<Ref<Bytes>>>::deserialize(de)?;
+        if bytes.len() < C::NONCE_SIZE + C::TAG_SIZE {
+            return Err(DeserializeError::WrongLength(ErrorDetails::sizes(
+                C::NONCE_SIZE + C::TAG_SIZE,
+                bytes.len(),
+            )));
+        }
+        Ok(Sealed {
+            bytes,
+            marker: PhantomData,
+        })
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(&mut self, de: Deserializer<'fe>) -> Result<(), DeserializeError> {
+        *self = <Self as Deserialize<'fe, Encrypted<F, C>>>::deserialize(de)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,242 @@
+//!
+//! This module provides tagged envelope for messages of different types.
+//!
+
+use core::marker::PhantomData;
+
+use crate::{
+    buffer::Buffer,
+    deserialize::{Deserialize, DeserializeError, Deserializer, ErrorDetails},
+    formula::{BareFormula, Formula},
+    private::VARIANT_SIZE,
+    serialize::{field_size_hint, write_field, Serialize, Sizes},
+};
+
+#[cfg(feature = "alloc")]
+use alloc::{boxed::Box, collections::BTreeMap};
+
+/// Formula of a message that can be sent in [`Envelope`].
+pub trait Tagged: Formula {
+    /// Tag that identifies the message formula.
+    /// Must be unique among messages of a protocol.
+    const TAG: u32;
+}
+
+/// Formula for a message of any [`Tagged`] formula.
+///
+/// Message is serialized after its tag,
+/// the same way as enum variant with a single field.
+/// Receiver reads the tag with [`Opened`] and decodes the message
+/// with matching formula, or dispatches it with [`Registry`].
+///
+/// Serialized from [`Enveloped`].
+pub struct Envelope;
+
+impl Formula for Envelope {
+    const MAX_STACK_SIZE: Option<usize> = None;
+    const EXACT_SIZE: bool = false;
+    const HEAPLESS: bool = false;
+}
+
+impl BareFormula for Envelope {}
+
+/// Message value of formula `F` to be serialized with [`Envelope`] formula.
+pub struct Enveloped<F: ?Sized, T> {
+    value: T,
+    marker: PhantomData<fn(&F) -> &F>,
+}
+
+impl<F, T> Enveloped<F, T>
+where
+    F: Tagged + ?Sized,
+    T: Serialize<F>,
+{
+    /// Wraps the message value.
+    #[inline(always)]
+    pub fn new(value: T) -> Self {
+        Enveloped {
+            value,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<F, T> Serialize<Envelope> for Enveloped<F, T>
+where
+    F: Tagged + ?Sized,
+    T: Serialize<F>,
+{
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, mut buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_field::<u32, u32, _>(F::TAG, sizes, buffer.reborrow(), false)?;
+        write_field::<F, T, _>(self.value, sizes, buffer, true)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        let mut sizes = field_size_hint::<F>(&self.value, true)?;
+        sizes.add_stack(VARIANT_SIZE);
+        Some(sizes)
+    }
+}
+
+/// Deserialized [`Envelope`] with message not decoded yet.
+#[derive(Clone)]
+pub struct Opened<'de> {
+    tag: u32,
+    de: Deserializer<'de>,
+}
+
+impl<'de> Opened<'de> {
+    /// Returns tag of the message.
+    #[must_use]
+    #[inline(always)]
+    pub fn tag(&self) -> u32 {
+        self.tag
+    }
+
+    /// Returns `true` if message has formula `F`.
+    #[must_use]
+    #[inline(always)]
+    pub fn is<F>(&self) -> bool
+    where
+        F: Tagged + ?Sized,
+    {
+        self.tag == F::TAG
+    }
+
+    /// Decodes message with formula `F`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError::WrongVariant` if message has different tag.
+    /// Returns `DeserializeError` if deserialization fails.
+    #[inline(always)]
+    pub fn decode<F, T>(mut self) -> Result<T, DeserializeError>
+    where
+        F: Tagged + ?Sized,
+        T: Deserialize<'de, F>,
+    {
+        if self.tag != F::TAG {
+            return Err(
+                DeserializeError::WrongVariant(self.tag, ErrorDetails::new()).with_formula::<F>(),
+            );
+        }
+        self.de.read_value::<F, T>(true)
+    }
+}
+
+impl<'de> Deserialize<'de, Envelope> for Opened<'de> {
+    #[inline(always)]
+    fn deserialize(mut de: Deserializer<'de>) -> Result<Self, DeserializeError> {
+        let tag = de.read_value::<u32, u32>(false)?;
+        Ok(Opened { tag, de })
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(&mut self, de: Deserializer<'de>) -> Result<(), DeserializeError> {
+        *self = <Self as Deserialize<Envelope>>::deserialize(de)?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "alloc")]
+type Decoder<M> = Box<dyn for<'de> Fn(Opened<'de>) -> Result<M, DeserializeError>>;
+
+/// Registry of message formulas that decodes [`Envelope`]
+/// with any registered message into common type `M`.
+///
+/// `M` is typically an enum of all messages or a boxed trait object.
+#[cfg(feature = "alloc")]
+pub struct Registry<M> {
+    decoders: BTreeMap<u32, Decoder<M>>,
+}
+
+#[cfg(feature = "alloc")]
+impl<M> Default for Registry<M> {
+    #[inline(always)]
+    fn default() -> Self {
+        Registry::new()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<M> Registry<M> {
+    /// Returns new empty registry.
+    #[must_use]
+    #[inline(always)]
+    pub fn new() -> Self {
+        Registry {
+            decoders: BTreeMap::new(),
+        }
+    }
+
+    /// Registers message formula `F` that is decoded into `T`
+    /// and converted with `map`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if formula with the same tag is already registered.
+    pub fn register<F, T>(&mut self, map: impl Fn(T) -> M + 'static) -> &mut Self
+    where
+        F: Tagged + ?Sized,
+        T: for<'de> Deserialize<'de, F>,
+    {
+        let decoder: Decoder<M> = Box::new(move |opened| opened.decode::<F, T>().map(&map));
+        let old = self.decoders.insert(F::TAG, decoder);
+        assert!(
+            old.is_none(),
+            "Message tag {} is already registered",
+            F::TAG
+        );
+        self
+    }
+
+    /// Returns `true` if formula with the tag is registered.
+    #[must_use]
+    #[inline(always)]
+    pub fn contains(&self, tag: u32) -> bool {
+        self.decoders.contains_key(&tag)
+    }
+
+    /// Decodes opened envelope with registered formula.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError::WrongVariant` if tag is not registered.
+    /// Returns `DeserializeError` if deserialization fails.
+    pub fn decode(&self, opened: Opened<'_>) -> Result<M, DeserializeError> {
+        match self.decoders.get(&opened.tag) {
+            None => Err(DeserializeError::WrongVariant(
+                opened.tag,
+                ErrorDetails::new(),
+            )),
+            Some(decoder) => decoder(opened),
+        }
+    }
+
+    /// Deserializes [`Envelope`] from the input and decodes the message.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError` if deserialization fails
+    /// or tag is not registered.
+    pub fn deserialize(&self, input: &[u8]) -> Result<M, DeserializeError> {
+        self.decode(crate::deserialize::deserialize::<Envelope, Opened>(input)?)
+    }
+
+    /// Reads packet with [`Envelope`] from the input and decodes the message.
+    /// Returns decoded message and number of bytes consumed.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError` if deserialization fails
+    /// or tag is not registered.
+    pub fn read_packet(&self, input: &[u8]) -> Result<(M, usize), DeserializeError> {
+        let (opened, size) = crate::packet::read_packet::<Envelope, Opened>(input)?;
+        Ok((self.decode(opened)?, size))
+    }
+}
@@ -0,0 +1,325 @@
+//!
+//! This module provides text export of schemas.
+//!
+
+use alloc::{string::String, vec::Vec};
+use core::fmt::{self, Write};
+
+use crate::schema::{Schema, SchemaField, SchemaKind};
+
+impl Schema {
+    /// Renders schema as JSON document.
+    ///
+    /// Document contains `nodes` array with one object per schema node.
+    /// Each node has `name`, `max_stack_size`, `exact_size`, `heapless` and `kind`
+    /// properties and kind-specific properties that refer to other nodes by index.
+    /// Root node is the first one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if schema refers to missing nodes.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use alkahest::*;
+    /// let json = Schema::of::<Option<u8>>().to_json();
+    /// assert!(json.contains(r#""kind":"option","formula":1"#));
+    /// ```
+    #[must_use]
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+        self.write_json(&mut out)
+            .expect("Writing to string can't fail");
+        out
+    }
+
+    /// Renders schema in a small interface definition language.
+    ///
+    /// Structures, enums and opaque formulas are declared by name,
+    /// other formulas are written inline, e.g. `Option<Ref<[u8]>>`.
+    /// The first line names the root formula.
+    ///
+    /// # Panics
+    ///
+    /// Panics if schema refers to missing nodes.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use alkahest::*;
+    /// let idl = Schema::of::<(u32, Vec<u8>)>().to_idl();
+    /// assert_eq!(idl, "root (u32, Ref<[u8]>);\n");
+    /// ```
+    #[must_use]
+    pub fn to_idl(&self) -> String {
+        let mut out = String::new();
+        self.write_idl(&mut out)
+            .expect("Writing to string can't fail");
+        out
+    }
+
+    fn write_json(&self, out: &mut String) -> fmt::Result {
+        out.push_str("{\"nodes\":[");
+        for (idx, node) in self.nodes.iter().enumerate() {
+            if idx > 0 {
+                out.push(',');
+            }
+            out.push_str("{\"name\":");
+            write_json_str(out, &node.name)?;
+            match node.max_stack_size {
+                None => out.push_str(",\"max_stack_size\":null"),
+                Some(size) => write!(out, ",\"max_stack_size\":{size}")?,
+            }
+            write!(
+                out,
+                ",\"exact_size\":{},\"heapless\":{},\"kind\":\"{}\"",
+                node.exact_size,
+                node.heapless,
+                node.kind.name()
+            )?;
+
+            match &node.kind {
+                SchemaKind::Opaque
+                | SchemaKind::Bool
+                | SchemaKind::Vlq
+                | SchemaKind::Bytes
+                | SchemaKind::Str => {}
+                SchemaKind::Unsigned(size) | SchemaKind::Signed(size) | SchemaKind::Float(size) => {
+                    write!(out, ",\"size\":{size}")?;
+                }
+                SchemaKind::Array(elem, len) => write!(out, ",\"elem\":{elem},\"len\":{len}")?,
+                SchemaKind::Slice(elem) => write!(out, ",\"elem\":{elem}")?,
+                SchemaKind::Ref(formula) | SchemaKind::Option(formula) => {
+                    write!(out, ",\"formula\":{formula}")?;
+                }
+                SchemaKind::Tuple(elems) => {
+                    out.push_str(",\"elems\":[");
+                    for (idx, elem) in elems.iter().enumerate() {
+                        if idx > 0 {
+                            out.push(',');
+                        }
+                        write!(out, "{elem}")?;
+                    }
+                    out.push(']');
+                }
+                SchemaKind::Struct(fields) => {
+                    out.push_str(",\"fields\":");
+                    write_json_fields(out, fields)?;
+                }
+                SchemaKind::Enum(variants) => {
+                    out.push_str(",\"variants\":[");
+                    for (idx, variant) in variants.iter().enumerate() {
+                        if idx > 0 {
+                            out.push(',');
+                        }
+                        out.push_str("{\"name\":");
+                        write_json_str(out, &variant.name)?;
+                        write!(out, ",\"tag\":{},\"fields\":", variant.tag)?;
+                        write_json_fields(out, &variant.fields)?;
+                        out.push('}');
+                    }
+                    out.push(']');
+                }
+            }
+            out.push('}');
+        }
+        out.push_str("]}");
+        Ok(())
+    }
+
+    fn write_idl(&self, out: &mut String) -> fmt::Result {
+        let names = self.idl_names();
+
+        out.push_str("root ");
+        self.write_idl_expr(out, &names, 0)?;
+        out.push_str(";\n");
+
+        for (idx, node) in self.nodes.iter().enumerate() {
+            match &node.kind {
+                SchemaKind::Opaque => {
+                    write!(out, "\nopaque {}", names[idx])?;
+                    match node.max_stack_size {
+                        Some(size) if node.exact_size => write!(out, " [{size}]")?,
+                        Some(size) => write!(out, " [..{size}]")?,
+                        None => out.push_str(" [..]"),
+                    }
+                    out.push_str(";\n");
+                }
+                SchemaKind::Struct(fields) => {
+                    write!(out, "\nstruct {}", names[idx])?;
+                    self.write_idl_fields(out, &names, fields)?;
+                    out.push_str(";\n");
+                }
+                SchemaKind::Enum(variants) => {
+                    writeln!(out, "\nenum {} {{", names[idx])?;
+                    for variant in variants {
+                        write!(out, "    {}", variant.name)?;
+                        self.write_idl_fields(out, &names, &variant.fields)?;
+                        writeln!(out, " = {},", variant.tag)?;
+                    }
+                    out.push_str("}\n");
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn write_idl_fields(
+        &self,
+        out: &mut String,
+        names: &[String],
+        fields: &[SchemaField],
+    ) -> fmt::Result {
+        if fields.is_empty() {
+            return Ok(());
+        }
+
+        out.push_str(" { ");
+        for (idx, field) in fields.iter().enumerate() {
+            if idx > 0 {
+                out.push_str(", ");
+            }
+            write!(out, "{}: ", field.name)?;
+            self.write_idl_expr(out, names, field.formula)?;
+        }
+        out.push_str(" }");
+        Ok(())
+    }
+
+    pub(crate) fn write_idl_expr(
+        &self,
+        out: &mut String,
+        names: &[String],
+        idx: usize,
+    ) -> fmt::Result {
+        match &self.nodes[idx].kind {
+            SchemaKind::Opaque | SchemaKind::Struct(_) | SchemaKind::Enum(_) => {
+                out.push_str(&names[idx]);
+            }
+            SchemaKind::Bool => out.push_str("bool"),
+            SchemaKind::Unsigned(size) => write!(out, "u{}", size * 8)?,
+            SchemaKind::Signed(size) => write!(out, "i{}", size * 8)?,
+            SchemaKind::Float(size) => write!(out, "f{}", size * 8)?,
+            SchemaKind::Vlq => out.push_str("vlq"),
+            SchemaKind::Bytes => out.push_str("bytes"),
+            SchemaKind::Str => out.push_str("str"),
+            SchemaKind::Array(elem, len) => {
+                out.push('[');
+                self.write_idl_expr(out, names, *elem)?;
+                write!(out, "; {len}]")?;
+            }
+            SchemaKind::Slice(elem) => {
+                out.push('[');
+                self.write_idl_expr(out, names, *elem)?;
+                out.push(']');
+            }
+            SchemaKind::Ref(formula) => {
+                out.push_str("Ref<");
+                self.write_idl_expr(out, names, *formula)?;
+                out.push('>');
+            }
+            SchemaKind::Option(formula) => {
+                out.push_str("Option<");
+                self.write_idl_expr(out, names, *formula)?;
+                out.push('>');
+            }
+            SchemaKind::Tuple(elems) => {
+                out.push('(');
+                for (idx, elem) in elems.iter().enumerate() {
+                    if idx > 0 {
+                        out.push_str(", ");
+                    }
+                    self.write_idl_expr(out, names, *elem)?;
+                }
+                if elems.len() == 1 {
+                    out.push(',');
+                }
+                out.push(')');
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns names for declared nodes.
+    /// Type names are shortened unless that makes them ambiguous.
+    pub(crate) fn idl_names(&self) -> Vec<String> {
+        let short = self
+            .nodes
+            .iter()
+            .map(|node| short_name(&node.name))
+            .collect::<Vec<_>>();
+
+        self.nodes
+            .iter()
+            .zip(&short)
+            .map(|(node, name)| {
+                let ambiguous = self
+                    .nodes
+                    .iter()
+                    .zip(&short)
+                    .any(|(other, other_name)| other_name == name && other.name != node.name);
+
+                if ambiguous {
+                    node.name.clone()
+                } else {
+                    name.clone()
+                }
+            })
+            .collect()
+    }
+}
+
+/// Strips module paths from the type name, including generic arguments.
+pub(crate) fn short_name(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    let mut rest = name;
+
+    while !rest.is_empty() {
+        if let Some(tail) = rest.strip_prefix("::") {
+            // Drop the path segment written so far.
+            let segment = out
+                .rfind(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .map_or(0, |pos| pos + 1);
+            out.truncate(segment);
+            rest = tail;
+        } else {
+            let c = rest.chars().next().unwrap();
+            out.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    out
+}
+
+fn write_json_fields(out: &mut String, fields: &[SchemaField]) -> fmt::Result {
+    out.push('[');
+    for (idx, field) in fields.iter().enumerate() {
+        if idx > 0 {
+            out.push(',');
+        }
+        out.push_str("{\"name\":");
+        write_json_str(out, &field.name)?;
+        write!(out, ",\"formula\":{}}}", field.formula)?;
+    }
+    out.push(']');
+    Ok(())
+}
+
+fn write_json_str(out: &mut String, value: &str) -> fmt::Result {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => write!(out, "\\u{:04x}", u32::from(c))?,
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    Ok(())
+}
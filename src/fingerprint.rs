@@ -0,0 +1,296 @@
+//!
+//! This module provides stable fingerprints of formulas.
+//!
+
+use crate::{
+    descriptor::{
+        Descriptor, DescriptorKind, FieldDescriptor, KIND_ARRAY, KIND_BOOL, KIND_BYTES, KIND_ENUM,
+        KIND_FLOAT, KIND_OPAQUE, KIND_OPTION, KIND_REF, KIND_SIGNED, KIND_SLICE, KIND_STR,
+        KIND_STRUCT, KIND_TUPLE, KIND_UNSIGNED, KIND_VLQ,
+    },
+    formula::Formula,
+};
+
+#[cfg(feature = "alloc")]
+use crate::schema::{Schema, SchemaField, SchemaKind};
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Returns 64-bit fingerprint of the formula `F`.
+///
+/// Fingerprint is computed from the formula [`Descriptor`]
+/// and covers layout of the formula, names of fields and variants
+/// and variant tags.
+/// Rust type names are not included, so formula may be moved or renamed
+/// without changing the fingerprint.
+/// Formulas that are described as opaque are fingerprinted
+/// by their size properties only.
+///
+/// Fingerprint is stable across builds and platforms with the same
+/// size of [`FixedUsizeType`](crate::advanced::FixedUsizeType)
+/// and can be exchanged by peers to check that they agree on the formula.
+///
+/// # Limitations
+///
+/// Fingerprint is not available in const context.
+/// [`Formula::describe`] is a trait method and cannot be evaluated at compile time,
+/// so the fingerprint is computed at runtime by walking the descriptors.
+/// Compute it once and store if it is required often.
+///
+/// Recursive formulas are detected by comparing [`Descriptor::name`](crate::Descriptor::name)
+/// of a nested formula with names of formulas that contain it.
+/// Names are only compared and never hashed,
+/// but [`type_name`](core::any::type_name) is not guaranteed
+/// to be unique or stable across compiler versions.
+/// Fingerprints of recursive formulas, and of formulas that contain
+/// distinct types with equal names, may change with the compiler.
+/// Fingerprints of other formulas depend on structural descriptor data only.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// assert_eq!(fingerprint::<(u32, [u8])>(), fingerprint::<(u32, [u8])>());
+/// assert_ne!(fingerprint::<(u32, [u8])>(), fingerprint::<(u64, [u8])>());
+/// ```
+#[must_use]
+pub fn fingerprint<F>() -> u64
+where
+    F: Formula + ?Sized,
+{
+    let mut hasher = Fnv(FNV_OFFSET);
+    hasher.descriptor(&F::describe(), None);
+    hasher.0
+}
+
+/// Chain of formulas being hashed, used to detect recursion.
+/// Formulas are identified by type names, see limitations of [`fingerprint`].
+struct Ancestor<'a> {
+    name: &'static str,
+    parent: Option<&'a Ancestor<'a>>,
+}
+
+struct Fnv(u64);
+
+impl Fnv {
+    fn bytes(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    fn usize(&mut self, value: usize) {
+        self.u64(value as u64);
+    }
+
+    fn str(&mut self, value: &str) {
+        self.usize(value.len());
+        self.bytes(value.as_bytes());
+    }
+
+    fn descriptor(&mut self, descriptor: &Descriptor, parent: Option<&Ancestor<'_>>) {
+        // Recursive formula refers back to one of its ancestors.
+        // Hash distance to that ancestor instead of descending again.
+        let mut distance = 0;
+        let mut ancestor = parent;
+        while let Some(a) = ancestor {
+            if a.name == descriptor.name {
+                self.bytes(&[0xff]);
+                self.usize(distance);
+                return;
+            }
+            distance += 1;
+            ancestor = a.parent;
+        }
+
+        let this = Ancestor {
+            name: descriptor.name,
+            parent,
+        };
+
+        self.header(
+            descriptor.max_stack_size,
+            descriptor.exact_size,
+            descriptor.heapless,
+        );
+
+        match descriptor.kind {
+            DescriptorKind::Opaque => self.bytes(&[KIND_OPAQUE]),
+            DescriptorKind::Bool => self.bytes(&[KIND_BOOL]),
+            DescriptorKind::Unsigned(size) => self.sized(KIND_UNSIGNED, size),
+            DescriptorKind::Signed(size) => self.sized(KIND_SIGNED, size),
+            DescriptorKind::Float(size) => self.sized(KIND_FLOAT, size),
+            DescriptorKind::Vlq => self.bytes(&[KIND_VLQ]),
+            DescriptorKind::Bytes => self.bytes(&[KIND_BYTES]),
+            DescriptorKind::Str => self.bytes(&[KIND_STR]),
+            DescriptorKind::Array(elem, len) => {
+                self.sized(KIND_ARRAY, len);
+                self.descriptor(&elem.get(), Some(&this));
+            }
+            DescriptorKind::Slice(elem) => {
+                self.bytes(&[KIND_SLICE]);
+                self.descriptor(&elem.get(), Some(&this));
+            }
+            DescriptorKind::Ref(formula) => {
+                self.bytes(&[KIND_REF]);
+                self.descriptor(&formula.get(), Some(&this));
+            }
+            DescriptorKind::Option(formula) => {
+                self.bytes(&[KIND_OPTION]);
+                self.descriptor(&formula.get(), Some(&this));
+            }
+            DescriptorKind::Tuple(elems) => {
+                self.sized(KIND_TUPLE, elems.len());
+                for elem in elems {
+                    self.descriptor(&elem.get(), Some(&this));
+                }
+            }
+            DescriptorKind::Struct(fields) => {
+                self.bytes(&[KIND_STRUCT]);
+                self.fields(fields, &this);
+            }
+            DescriptorKind::Enum(variants) => {
+                self.sized(KIND_ENUM, variants.len());
+                for variant in variants {
+                    self.str(variant.name);
+                    self.u64(u64::from(variant.tag));
+                    self.fields(variant.fields, &this);
+                }
+            }
+        }
+    }
+
+    fn header(&mut self, max_stack_size: Option<usize>, exact_size: bool, heapless: bool) {
+        match max_stack_size {
+            None => self.bytes(&[0]),
+            Some(size) => {
+                self.bytes(&[1]);
+                self.usize(size);
+            }
+        }
+        self.bytes(&[u8::from(exact_size), u8::from(heapless)]);
+    }
+
+    fn sized(&mut self, kind: u8, size: usize) {
+        self.bytes(&[kind]);
+        self.usize(size);
+    }
+
+    fn fields(&mut self, fields: &[FieldDescriptor], this: &Ancestor<'_>) {
+        self.usize(fields.len());
+        for field in fields {
+            self.str(field.name);
+            self.descriptor(&field.formula.get(), Some(this));
+        }
+    }
+}
+
+/// Chain of schema nodes being hashed, used to detect recursion.
+#[cfg(feature = "alloc")]
+struct SchemaAncestor<'a> {
+    node: usize,
+    parent: Option<&'a SchemaAncestor<'a>>,
+}
+
+#[cfg(feature = "alloc")]
+impl Schema {
+    /// Returns 64-bit fingerprint of the schema.
+    ///
+    /// Equals to [`fingerprint`] of the formula the schema was built from.
+    ///
+    /// # Panics
+    ///
+    /// Panics if schema refers to missing nodes.
+    #[must_use]
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = Fnv(FNV_OFFSET);
+        hasher.schema_node(self, 0, None);
+        hasher.0
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Fnv {
+    fn schema_node(&mut self, schema: &Schema, idx: usize, parent: Option<&SchemaAncestor<'_>>) {
+        let mut distance = 0;
+        let mut ancestor = parent;
+        while let Some(a) = ancestor {
+            if a.node == idx {
+                self.bytes(&[0xff]);
+                self.usize(distance);
+                return;
+            }
+            distance += 1;
+            ancestor = a.parent;
+        }
+
+        let this = SchemaAncestor { node: idx, parent };
+        let node = &schema.nodes[idx];
+
+        self.header(node.max_stack_size, node.exact_size, node.heapless);
+
+        match &node.kind {
+            SchemaKind::Opaque => self.bytes(&[KIND_OPAQUE]),
+            SchemaKind::Bool => self.bytes(&[KIND_BOOL]),
+            SchemaKind::Unsigned(size) => self.sized(KIND_UNSIGNED, *size),
+            SchemaKind::Signed(size) => self.sized(KIND_SIGNED, *size),
+            SchemaKind::Float(size) => self.sized(KIND_FLOAT, *size),
+            SchemaKind::Vlq => self.bytes(&[KIND_VLQ]),
+            SchemaKind::Bytes => self.bytes(&[KIND_BYTES]),
+            SchemaKind::Str => self.bytes(&[KIND_STR]),
+            SchemaKind::Array(elem, len) => {
+                self.sized(KIND_ARRAY, *len);
+                self.schema_node(schema, *elem, Some(&this));
+            }
+            SchemaKind::Slice(elem) => {
+                self.bytes(&[KIND_SLICE]);
+                self.schema_node(schema, *elem, Some(&this));
+            }
+            SchemaKind::Ref(formula) => {
+                self.bytes(&[KIND_REF]);
+                self.schema_node(schema, *formula, Some(&this));
+            }
+            SchemaKind::Option(formula) => {
+                self.bytes(&[KIND_OPTION]);
+                self.schema_node(schema, *formula, Some(&this));
+            }
+            SchemaKind::Tuple(elems) => {
+                self.sized(KIND_TUPLE, elems.len());
+                for elem in elems {
+                    self.schema_node(schema, *elem, Some(&this));
+                }
+            }
+            SchemaKind::Struct(fields) => {
+                self.bytes(&[KIND_STRUCT]);
+                self.schema_fields(schema, fields, &this);
+            }
+            SchemaKind::Enum(variants) => {
+                self.sized(KIND_ENUM, variants.len());
+                for variant in variants {
+                    self.str(&variant.name);
+                    self.u64(u64::from(variant.tag));
+                    self.schema_fields(schema, &variant.fields, &this);
+                }
+            }
+        }
+    }
+
+    fn schema_fields(
+        &mut self,
+        schema: &Schema,
+        fields: &[SchemaField],
+        this: &SchemaAncestor<'_>,
+    ) {
+        self.usize(fields.len());
+        for field in fields {
+            self.str(&field.name);
+            self.schema_node(schema, field.formula, Some(this));
+        }
+    }
+}
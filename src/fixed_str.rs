@@ -0,0 +1,177 @@
+//!
+//! This module provides formula for strings stored inline in fixed number of bytes.
+//!
+
+use crate::{
+    buffer::Buffer,
+    deserialize::{Deserialize, DeserializeError, Deserializer},
+    formula::{BareFormula, Formula},
+    serialize::{write_bytes, SerializeRef, Sizes},
+};
+
+#[cfg(feature = "alloc")]
+use crate::serialize::Serialize;
+
+/// Formula for strings stored inline in exactly `N` bytes.
+///
+/// String bytes are followed by NUL padding up to `N` bytes.
+/// String of exactly `N` bytes has no padding.
+/// Unlike `str` and [`Ref<str>`](crate::Ref) it has fixed size,
+/// so short identifiers need neither size prefix nor heap indirection.
+///
+/// Serializable from `str` and `String` and references to them.
+/// Deserializable into `&str` and `String`.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// type Message = (FixedStr<8>, u32);
+///
+/// let mut buffer = [0u8; max_packet_size::<Message>()];
+/// let size = write_packet::<Message, _>(("name", 42u32), &mut buffer).unwrap();
+///
+/// let (value, _) = read_packet::<Message, (&str, u32)>(&buffer[..size]).unwrap();
+/// assert_eq!(value, ("name", 42));
+/// ```
+pub struct FixedStr<const N: usize>;
+
+impl<const N: usize> Formula for FixedStr<N> {
+    const MAX_STACK_SIZE: Option<usize> = Some(N);
+    const EXACT_SIZE: bool = true;
+    const HEAPLESS: bool = true;
+}
+
+impl<const N: usize> BareFormula for FixedStr<N> {}
+
+impl<const N: usize> SerializeRef<FixedStr<N>> for str {
+    /// # Panics
+    ///
+    /// Panics if string is longer than `N` bytes or contains NUL byte.
+    #[inline]
+    fn serialize<B>(&self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_fixed_str::<N, B>(self, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        Some(Sizes::with_stack(N))
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<const N: usize> Serialize<FixedStr<N>> for alloc::string::String {
+    /// # Panics
+    ///
+    /// Panics if string is longer than `N` bytes or contains NUL byte.
+    #[inline]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_fixed_str::<N, B>(&self, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        Some(Sizes::with_stack(N))
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<const N: usize> Serialize<FixedStr<N>> for &alloc::string::String {
+    /// # Panics
+    ///
+    /// Panics if string is longer than `N` bytes or contains NUL byte.
+    #[inline]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_fixed_str::<N, B>(self, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        Some(Sizes::with_stack(N))
+    }
+}
+
+impl<'de, 'fe: 'de, const N: usize> Deserialize<'fe, FixedStr<N>> for &'de str {
+    #[inline]
+    fn deserialize(de: Deserializer<'fe>) -> Result<Self, DeserializeError> {
+        read_fixed_str::<N>(de)
+    }
+
+    #[inline]
+    fn deserialize_in_place(&mut self, de: Deserializer<'fe>) -> Result<(), DeserializeError> {
+        *self = read_fixed_str::<N>(de)?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'de, const N: usize> Deserialize<'de, FixedStr<N>> for alloc::string::String {
+    #[inline]
+    fn deserialize(de: Deserializer<'de>) -> Result<Self, DeserializeError> {
+        Ok(read_fixed_str::<N>(de)?.into())
+    }
+
+    #[inline]
+    fn deserialize_in_place(&mut self, de: Deserializer<'de>) -> Result<(), DeserializeError> {
+        let string = read_fixed_str::<N>(de)?;
+        self.clear();
+        self.push_str(string);
+        Ok(())
+    }
+}
+
+/// Writes string bytes followed by NUL padding up to `N` bytes.
+#[inline]
+fn write_fixed_str<const N: usize, B>(
+    string: &str,
+    sizes: &mut Sizes,
+    mut buffer: B,
+) -> Result<(), B::Error>
+where
+    B: Buffer,
+{
+    let bytes = string.as_bytes();
+    let len = bytes.len();
+    assert!(
+        len <= N,
+        "String length {len} exceeds {N} bytes of `FixedStr`"
+    );
+    assert!(
+        !bytes.contains(&0),
+        "String with NUL cannot be serialized with `FixedStr`"
+    );
+
+    // Stack grows towards the start of the buffer,
+    // so padding is written first to follow the string bytes.
+    let pad = N - len;
+    if pad > 0 {
+        buffer.pad_stack(sizes.heap, sizes.stack, pad)?;
+        sizes.stack += pad;
+    }
+    write_bytes(bytes, sizes, buffer)
+}
+
+/// Reads `N` bytes and strips NUL padding.
+/// Non-NUL bytes after padding are rejected to keep encoding canonical.
+#[inline]
+fn read_fixed_str<const N: usize>(mut de: Deserializer<'_>) -> Result<&str, DeserializeError> {
+    let bytes = de.read_bytes(N)?;
+    let len = bytes.iter().position(|&b| b == 0).unwrap_or(N);
+    if bytes[len..].iter().any(|&b| b != 0) {
+        return Err(DeserializeError::NonCanonical);
+    }
+
+    match core::str::from_utf8(&bytes[..len]) {
+        Ok(s) => Ok(s),
+        Err(error) => Err(DeserializeError::NonUtf8(error)),
+    }
+}
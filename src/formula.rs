@@ -1,4 +1,4 @@
-use crate::size::SIZE_STACK;
+use crate::{descriptor::Descriptor, size::SIZE_STACK};
 
 /// Trait for data formulas.
 /// Types that implement this trait are used as markers
@@ -107,6 +107,34 @@ pub trait Formula {
 
     /// Signals that heap is not used for serialzation.
     const HEAPLESS: bool;
+
+    /// Maximum number of bytes serialization of any value with this formula writes.
+    /// `None` if the size is not bounded.
+    ///
+    /// Derived from other constants and should not be overridden.
+    const MAX_SIZE: Option<usize> = if Self::HEAPLESS {
+        Self::MAX_STACK_SIZE
+    } else {
+        None
+    };
+
+    /// Maximum size of packet with any value of this formula.
+    /// `None` if the size is not bounded.
+    ///
+    /// Derived from other constants and should not be overridden.
+    const MAX_PACKET_SIZE: Option<usize> =
+        sum_size(Self::MAX_SIZE, Some(reference_size_with(Self::EXACT_SIZE)));
+
+    /// Returns runtime descriptor of the formula.
+    ///
+    /// Default implementation describes the formula as opaque.
+    /// Formulas provided by the crate and derived formulas
+    /// describe their structure.
+    #[must_use]
+    #[inline(always)]
+    fn describe() -> Descriptor {
+        Descriptor::opaque::<Self>()
+    }
 }
 
 /// Ad-hoc negative trait.
@@ -176,9 +204,108 @@ pub const fn reference_size<F>() -> usize
 where
     F: Formula + ?Sized,
 {
-    if F::EXACT_SIZE {
+    reference_size_with(F::EXACT_SIZE)
+}
+
+/// Returns size of reference to formula with specified `EXACT_SIZE`.
+#[inline(always)]
+pub(crate) const fn reference_size_with(exact_size: bool) -> usize {
+    if exact_size {
         SIZE_STACK
     } else {
         SIZE_STACK * 2
     }
 }
+
+/// Asserts that formula `F` never writes to the heap,
+/// so values are stored inline without references.
+///
+/// # Panics
+///
+/// Panics if [`Formula::HEAPLESS`] is `false`.
+/// In const context this is a compile-time error.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// const _: () = assert_heapless::<(u32, [u16; 2], Option<u8>)>();
+/// ```
+///
+/// ```compile_fail
+/// # use alkahest::*;
+/// const _: () = assert_heapless::<(u32, Ref<str>)>();
+/// ```
+#[inline(always)]
+pub const fn assert_heapless<F>()
+where
+    F: Formula + ?Sized,
+{
+    if !F::HEAPLESS {
+        panic!("Formula uses heap");
+    }
+}
+
+/// Asserts that stack size of formula `F` is bounded
+/// and returns [`Formula::MAX_STACK_SIZE`].
+///
+/// # Panics
+///
+/// Panics if the stack size is not bounded.
+/// In const context this is a compile-time error.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// const SIZE: usize = assert_bounded::<(u32, Option<u16>)>();
+/// assert_eq!(SIZE, 7);
+/// ```
+///
+/// ```compile_fail
+/// # use alkahest::*;
+/// const _: usize = assert_bounded::<(u32, [u8])>();
+/// ```
+#[must_use]
+#[inline(always)]
+pub const fn assert_bounded<F>() -> usize
+where
+    F: Formula + ?Sized,
+{
+    match F::MAX_STACK_SIZE {
+        Some(size) => size,
+        None => panic!("Formula stack size is not bounded"),
+    }
+}
+
+/// Asserts that every value of formula `F` occupies the same number of stack bytes
+/// and returns that size.
+///
+/// # Panics
+///
+/// Panics if [`Formula::EXACT_SIZE`] is `false` or the stack size is not bounded.
+/// In const context this is a compile-time error.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// const SIZE: usize = assert_exact_size::<(u32, [u16; 2])>();
+/// assert_eq!(SIZE, 8);
+/// ```
+///
+/// ```compile_fail
+/// # use alkahest::*;
+/// const _: usize = assert_exact_size::<(u32, Option<u16>)>();
+/// ```
+#[must_use]
+#[inline(always)]
+pub const fn assert_exact_size<F>() -> usize
+where
+    F: Formula + ?Sized,
+{
+    match (F::MAX_STACK_SIZE, F::EXACT_SIZE) {
+        (Some(size), true) => size,
+        _ => panic!("Formula size is not exact"),
+    }
+}
@@ -0,0 +1,459 @@
+//!
+//! This module provides fragmentation of packets into datagrams
+//! no larger than MTU and their reassembly.
+//!
+
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::fmt;
+
+use crate::{formula::Formula, packet::write_packet_to_vec, serialize::Serialize};
+
+/// Size of fragment header: message id, fragment index and fragments count.
+const HEADER_SIZE: usize = 8;
+
+/// Default maximum size of reassembled message.
+const DEFAULT_MAX_MESSAGE_SIZE: usize = 8 * 1024 * 1024;
+
+/// Default maximum number of messages with pending fragments.
+const DEFAULT_MAX_PENDING: usize = 64;
+
+/// Error of packet fragmentation and reassembly.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FragmentError {
+    /// MTU cannot fit fragment header and at least one byte of payload.
+    MtuTooSmall(usize),
+
+    /// Packet requires more than `u16::MAX` fragments.
+    TooManyFragments(usize),
+
+    /// Datagram is shorter than fragment header.
+    /// Contains the datagram length.
+    Truncated(usize),
+
+    /// Fragment index is out of range or fragments count is zero.
+    InvalidIndex {
+        /// Index of the fragment.
+        index: u16,
+        /// Number of fragments in the message.
+        count: u16,
+    },
+
+    /// Fragment of the message specifies different fragments count
+    /// than previously received ones.
+    CountMismatch {
+        /// Fragments count of previously received fragments.
+        expected: u16,
+        /// Fragments count of the fragment.
+        found: u16,
+    },
+
+    /// Fragment that is not the last one has different size
+    /// than previously received fragments of the message.
+    ChunkSizeMismatch {
+        /// Size of previously received fragments.
+        expected: usize,
+        /// Size of the fragment.
+        found: usize,
+    },
+
+    /// Message exceeds maximum message size of the reassembler.
+    MessageTooLarge(usize),
+}
+
+impl fmt::Display for FragmentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FragmentError::MtuTooSmall(mtu) => {
+                write!(
+                    f,
+                    "MTU of {mtu} bytes cannot fit fragment header and payload"
+                )
+            }
+            FragmentError::TooManyFragments(count) => {
+                write!(
+                    f,
+                    "packet requires {count} fragments which exceeds u16::MAX"
+                )
+            }
+            FragmentError::Truncated(len) => {
+                write!(f, "datagram of {len} bytes is shorter than fragment header")
+            }
+            FragmentError::InvalidIndex { index, count } => {
+                write!(f, "fragment index {index} is invalid for {count} fragments")
+            }
+            FragmentError::CountMismatch { expected, found } => {
+                write!(
+                    f,
+                    "fragments count {found} differs from expected {expected}"
+                )
+            }
+            FragmentError::ChunkSizeMismatch { expected, found } => {
+                write!(
+                    f,
+                    "fragment of {found} bytes differs from fragment size {expected}"
+                )
+            }
+            FragmentError::MessageTooLarge(size) => {
+                write!(f, "message of {size} bytes exceeds maximum message size")
+            }
+        }
+    }
+}
+
+/// Serializes the value into packet and splits it into datagrams
+/// no larger than `mtu` bytes, each prefixed with fragment header.
+///
+/// Header consists of message id, fragment index and fragments count
+/// encoded as little-endian `u32`, `u16` and `u16`.
+/// Message id should be unique among messages in flight
+/// so that [`Reassembler`] does not mix their fragments.
+///
+/// # Errors
+///
+/// Returns `FragmentError::MtuTooSmall` if `mtu` cannot fit header and payload.
+/// Returns `FragmentError::TooManyFragments` if packet does not fit in `u16::MAX` fragments.
+///
+/// # Panics
+///
+/// Panics if serialized data exceeds [`PACKET_SIZE_LIMIT`](crate::PACKET_SIZE_LIMIT).
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// let value: Vec<u32> = (0..100).collect();
+/// let fragments = fragment_packet::<[u32], _>(7, &value, 64).unwrap();
+/// assert!(fragments.len() > 1);
+///
+/// let mut reassembler = Reassembler::new();
+/// let mut packet = None;
+/// for datagram in fragments.rev() {
+///     assert!(datagram.len() <= 64);
+///     packet = reassembler.push(&datagram).unwrap();
+/// }
+///
+/// let (message, packet) = packet.unwrap();
+/// assert_eq!(message, 7);
+/// let (decoded, _) = read_packet::<[u32], Vec<u32>>(&packet).unwrap();
+/// assert_eq!(decoded, value);
+/// ```
+pub fn fragment_packet<F, T>(message: u32, value: T, mtu: usize) -> Result<Fragments, FragmentError>
+where
+    F: Formula + ?Sized,
+    T: Serialize<F>,
+{
+    if mtu <= HEADER_SIZE {
+        return Err(FragmentError::MtuTooSmall(mtu));
+    }
+    let chunk = mtu - HEADER_SIZE;
+
+    let mut payload = Vec::new();
+    let size = write_packet_to_vec::<F, T>(value, &mut payload);
+    payload.truncate(size);
+
+    let count = size.div_ceil(chunk).max(1);
+    let Ok(count) = u16::try_from(count) else {
+        return Err(FragmentError::TooManyFragments(count));
+    };
+
+    Ok(Fragments {
+        message,
+        payload,
+        chunk,
+        count,
+        front: 0,
+        back: count,
+    })
+}
+
+/// Iterator over datagrams of fragmented packet.
+///
+/// Created by [`fragment_packet`].
+#[derive(Clone, Debug)]
+pub struct Fragments {
+    message: u32,
+    payload: Vec<u8>,
+    chunk: usize,
+    count: u16,
+    front: u16,
+    back: u16,
+}
+
+impl Fragments {
+    /// Returns message id of the fragments.
+    #[must_use]
+    #[inline(always)]
+    pub fn message(&self) -> u32 {
+        self.message
+    }
+
+    /// Returns total number of fragments.
+    #[must_use]
+    #[inline(always)]
+    pub fn total(&self) -> u16 {
+        self.count
+    }
+
+    /// Returns datagram of the fragment with specified index.
+    /// Useful to resend lost fragments.
+    #[must_use]
+    pub fn fragment(&self, index: u16) -> Option<Vec<u8>> {
+        if index >= self.count {
+            return None;
+        }
+
+        let start = usize::from(index) * self.chunk;
+        let end = (start + self.chunk).min(self.payload.len());
+
+        let mut datagram = Vec::with_capacity(HEADER_SIZE + end - start);
+        datagram.extend_from_slice(&self.message.to_le_bytes());
+        datagram.extend_from_slice(&index.to_le_bytes());
+        datagram.extend_from_slice(&self.count.to_le_bytes());
+        datagram.extend_from_slice(&self.payload[start..end]);
+        Some(datagram)
+    }
+}
+
+impl Iterator for Fragments {
+    type Item = Vec<u8>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Vec<u8>> {
+        if self.front == self.back {
+            return None;
+        }
+        let datagram = self.fragment(self.front);
+        self.front += 1;
+        datagram
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = usize::from(self.back - self.front);
+        (len, Some(len))
+    }
+}
+
+impl DoubleEndedIterator for Fragments {
+    #[inline]
+    fn next_back(&mut self) -> Option<Vec<u8>> {
+        if self.front == self.back {
+            return None;
+        }
+        self.back -= 1;
+        self.fragment(self.back)
+    }
+}
+
+impl ExactSizeIterator for Fragments {}
+
+/// Fragments of a message received so far.
+struct Partial {
+    chunks: BTreeMap<u16, Vec<u8>>,
+    count: u16,
+    chunk_size: Option<usize>,
+    size: usize,
+    sequence: u64,
+}
+
+/// Reassembles packets from datagrams produced by [`fragment_packet`].
+///
+/// Fragments may arrive in any order and interleave with fragments of other messages.
+/// Duplicate fragments are ignored.
+///
+/// Only received fragments are stored.
+/// When number of pending messages reaches the limit,
+/// the oldest pending message is discarded to make room for a new one.
+pub struct Reassembler {
+    messages: BTreeMap<u32, Partial>,
+    max_message_size: usize,
+    max_pending: usize,
+    sequence: u64,
+}
+
+impl Default for Reassembler {
+    #[inline(always)]
+    fn default() -> Self {
+        Reassembler::new()
+    }
+}
+
+impl Reassembler {
+    /// Returns new reassembler with default maximum message size of 8 MiB
+    /// and with up to 64 pending messages.
+    #[must_use]
+    #[inline(always)]
+    pub fn new() -> Self {
+        Reassembler::with_max_message_size(DEFAULT_MAX_MESSAGE_SIZE)
+    }
+
+    /// Returns new reassembler that rejects messages larger than specified size
+    /// and keeps up to 64 pending messages.
+    #[must_use]
+    #[inline(always)]
+    pub fn with_max_message_size(max_message_size: usize) -> Self {
+        Reassembler::with_limits(max_message_size, DEFAULT_MAX_PENDING)
+    }
+
+    /// Returns new reassembler that rejects messages larger than `max_message_size`
+    /// and keeps up to `max_pending` pending messages.
+    ///
+    /// `max_pending` of zero is treated as one.
+    #[must_use]
+    #[inline(always)]
+    pub fn with_limits(max_message_size: usize, max_pending: usize) -> Self {
+        Reassembler {
+            messages: BTreeMap::new(),
+            max_message_size,
+            max_pending: max_pending.max(1),
+            sequence: 0,
+        }
+    }
+
+    /// Accepts a datagram.
+    /// Returns message id and reassembled packet
+    /// when the datagram completes a message.
+    ///
+    /// # Errors
+    ///
+    /// Returns `FragmentError` if datagram is malformed or inconsistent
+    /// with previously received fragments of the message,
+    /// or if the message exceeds maximum size.
+    /// Fragments count of a new message is checked against maximum size
+    /// before anything is stored.
+    /// Fragments other than the last one must have equal size.
+    /// Partially received message is discarded if it exceeds maximum size.
+    pub fn push(&mut self, datagram: &[u8]) -> Result<Option<(u32, Vec<u8>)>, FragmentError> {
+        let Some((header, chunk)) = datagram.split_first_chunk::<HEADER_SIZE>() else {
+            return Err(FragmentError::Truncated(datagram.len()));
+        };
+
+        let message = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+        let index = u16::from_le_bytes([header[4], header[5]]);
+        let count = u16::from_le_bytes([header[6], header[7]]);
+
+        if index >= count {
+            return Err(FragmentError::InvalidIndex { index, count });
+        }
+
+        let partial = match self.messages.get_mut(&message) {
+            Some(partial) => partial,
+            None => {
+                // All fragments but the last one have the same size,
+                // other fragments carry at least one byte each.
+                let others = usize::from(count - 1);
+                let least = if index + 1 < count {
+                    others.saturating_mul(chunk.len()).saturating_add(1)
+                } else {
+                    others.saturating_add(chunk.len())
+                };
+                if least > self.max_message_size {
+                    return Err(FragmentError::MessageTooLarge(least));
+                }
+
+                if self.messages.len() >= self.max_pending {
+                    self.evict_oldest();
+                }
+
+                self.sequence += 1;
+                self.messages.entry(message).or_insert(Partial {
+                    chunks: BTreeMap::new(),
+                    count,
+                    chunk_size: None,
+                    size: 0,
+                    sequence: self.sequence,
+                })
+            }
+        };
+
+        if partial.count != count {
+            return Err(FragmentError::CountMismatch {
+                expected: partial.count,
+                found: count,
+            });
+        }
+
+        if partial.chunks.contains_key(&index) {
+            return Ok(None);
+        }
+
+        // All fragments but the last one have the same size.
+        if index + 1 < count {
+            match partial.chunk_size {
+                Some(expected) if expected != chunk.len() => {
+                    return Err(FragmentError::ChunkSizeMismatch {
+                        expected,
+                        found: chunk.len(),
+                    });
+                }
+                _ => partial.chunk_size = Some(chunk.len()),
+            }
+        }
+
+        let size = partial.size.saturating_add(chunk.len());
+        if size > self.max_message_size {
+            self.messages.remove(&message);
+            return Err(FragmentError::MessageTooLarge(size));
+        }
+
+        partial.chunks.insert(index, chunk.to_vec());
+        partial.size = size;
+
+        if partial.chunks.len() < usize::from(partial.count) {
+            return Ok(None);
+        }
+
+        let partial = self.messages.remove(&message).unwrap();
+        let mut packet = Vec::with_capacity(partial.size);
+        for chunk in partial.chunks.into_values() {
+            packet.extend_from_slice(&chunk);
+        }
+        Ok(Some((message, packet)))
+    }
+
+    /// Discards pending message that was started first.
+    fn evict_oldest(&mut self) {
+        let oldest = self
+            .messages
+            .iter()
+            .min_by_key(|(_, partial)| partial.sequence)
+            .map(|(&message, _)| message);
+
+        if let Some(message) = oldest {
+            self.messages.remove(&message);
+        }
+    }
+
+    /// Returns `true` if some but not all fragments of the message were received.
+    #[must_use]
+    #[inline(always)]
+    pub fn is_pending(&self, message: u32) -> bool {
+        self.messages.contains_key(&message)
+    }
+
+    /// Returns indices of fragments of the message that were not received yet.
+    /// Returns `None` if no fragments of the message are pending.
+    #[must_use]
+    pub fn missing(&self, message: u32) -> Option<Vec<u16>> {
+        let partial = self.messages.get(&message)?;
+        let missing = (0..partial.count)
+            .filter(|index| !partial.chunks.contains_key(index))
+            .collect();
+        Some(missing)
+    }
+
+    /// Discards received fragments of the message.
+    /// Returns `true` if the message was pending.
+    #[inline(always)]
+    pub fn discard(&mut self, message: u32) -> bool {
+        self.messages.remove(&message).is_some()
+    }
+
+    /// Returns number of messages with pending fragments.
+    #[must_use]
+    #[inline(always)]
+    pub fn pending(&self) -> usize {
+        self.messages.len()
+    }
+}
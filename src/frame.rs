@@ -0,0 +1,319 @@
+//!
+//! This module provides length-delimited framing of packets over `std::io` streams.
+//!
+
+use alloc::vec::Vec;
+use core::fmt;
+use std::io::{self, Read, Write};
+
+use crate::{
+    deserialize::{Deserialize, DeserializeError, ErrorDetails},
+    formula::Formula,
+    packet::{read_packet, write_packet_to_vec},
+    serialize::Serialize,
+};
+
+/// Size of the frame length prefix.
+const PREFIX_SIZE: usize = 4;
+
+/// Default maximum frame size, 8 MiB.
+const DEFAULT_MAX_FRAME_SIZE: usize = 8 * 1024 * 1024;
+
+/// Number of bytes requested from the reader at once.
+const READ_CHUNK: usize = 4096;
+
+/// Error of [`FrameReader`] and [`FrameWriter`].
+#[derive(Debug)]
+pub enum FrameError {
+    /// Error returned by the reader or writer.
+    Io(io::Error),
+
+    /// Frame length exceeds maximum frame size.
+    FrameTooLarge(usize),
+
+    /// Reader reached end of stream in the middle of a frame.
+    UnexpectedEof,
+
+    /// Frame failed to deserialize.
+    Deserialize(DeserializeError),
+}
+
+impl From<io::Error> for FrameError {
+    #[inline(always)]
+    fn from(err: io::Error) -> Self {
+        FrameError::Io(err)
+    }
+}
+
+impl From<DeserializeError> for FrameError {
+    #[inline(always)]
+    fn from(err: DeserializeError) -> Self {
+        FrameError::Deserialize(err)
+    }
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FrameError::Io(err) => write!(f, "frame I/O error: {err}"),
+            FrameError::FrameTooLarge(len) => {
+                write!(f, "frame of {len} bytes exceeds maximum frame size")
+            }
+            FrameError::UnexpectedEof => f.write_str("unexpected end of stream inside frame"),
+            FrameError::Deserialize(err) => write!(f, "frame deserialization error: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for FrameError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FrameError::Io(err) => Some(err),
+            FrameError::Deserialize(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Writes packets into a stream, each prefixed with its length
+/// as big-endian `u32`.
+///
+/// The framing is compatible with [`FrameReader`]
+/// and with `AlkahestCodec` from "tokio-codec" feature.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// let mut writer = FrameWriter::new(Vec::new());
+/// writer.write::<[u32], _>([1u32, 2, 3]).unwrap();
+/// writer.write::<As<str>, _>("four").unwrap();
+///
+/// let stream = writer.into_inner();
+/// let mut reader = FrameReader::new(&stream[..]);
+/// assert_eq!(reader.read::<[u32], Vec<u32>>().unwrap(), Some(vec![1, 2, 3]));
+/// assert_eq!(reader.read::<As<str>, &str>().unwrap(), Some("four"));
+/// assert_eq!(reader.read::<As<str>, &str>().unwrap(), None);
+/// ```
+pub struct FrameWriter<W> {
+    writer: W,
+    scratch: Vec<u8>,
+    max_frame_size: usize,
+}
+
+impl<W> FrameWriter<W>
+where
+    W: Write,
+{
+    /// Returns new frame writer with default maximum frame size of 8 MiB.
+    #[must_use]
+    #[inline(always)]
+    pub fn new(writer: W) -> Self {
+        FrameWriter::with_max_frame_size(writer, DEFAULT_MAX_FRAME_SIZE)
+    }
+
+    /// Returns new frame writer with specified maximum frame size.
+    #[must_use]
+    #[inline(always)]
+    pub fn with_max_frame_size(writer: W, max_frame_size: usize) -> Self {
+        FrameWriter {
+            writer,
+            scratch: Vec::new(),
+            max_frame_size,
+        }
+    }
+
+    /// Writes packet with the value as a frame.
+    /// Returns the number of bytes in the frame, excluding length prefix.
+    ///
+    /// # Errors
+    ///
+    /// Returns `FrameError::FrameTooLarge` if packet exceeds maximum frame size.
+    /// Nothing is written in this case.
+    /// Returns `FrameError::Io` if writer fails.
+    ///
+    /// # Panics
+    ///
+    /// Panics if serialized data exceeds [`PACKET_SIZE_LIMIT`](crate::PACKET_SIZE_LIMIT).
+    pub fn write<F, T>(&mut self, value: T) -> Result<usize, FrameError>
+    where
+        F: Formula + ?Sized,
+        T: Serialize<F>,
+    {
+        let size = write_packet_to_vec::<F, T>(value, &mut self.scratch);
+        let prefix = match u32::try_from(size) {
+            Ok(prefix) if size <= self.max_frame_size => prefix,
+            _ => return Err(FrameError::FrameTooLarge(size)),
+        };
+
+        self.writer.write_all(&prefix.to_be_bytes())?;
+        self.writer.write_all(&self.scratch[..size])?;
+        Ok(size)
+    }
+
+    /// Flushes the underlying writer.
+    ///
+    /// # Errors
+    ///
+    /// Returns `FrameError::Io` if writer fails.
+    #[inline(always)]
+    pub fn flush(&mut self) -> Result<(), FrameError> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    /// Returns reference to the underlying writer.
+    #[inline(always)]
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Returns mutable reference to the underlying writer.
+    #[inline(always)]
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Unwraps the underlying writer.
+    #[inline(always)]
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Reads frames written by [`FrameWriter`] from a stream
+/// and deserializes packets from them.
+///
+/// Bytes are accumulated in internal buffer until whole frame is read,
+/// so partial reads are handled transparently.
+/// If reader returns an error, e.g. `WouldBlock` for non-blocking streams,
+/// bytes read so far are kept and reading may be retried.
+pub struct FrameReader<R> {
+    reader: R,
+    buffer: Vec<u8>,
+    consumed: usize,
+    max_frame_size: usize,
+}
+
+impl<R> FrameReader<R>
+where
+    R: Read,
+{
+    /// Returns new frame reader with default maximum frame size of 8 MiB.
+    #[must_use]
+    #[inline(always)]
+    pub fn new(reader: R) -> Self {
+        FrameReader::with_max_frame_size(reader, DEFAULT_MAX_FRAME_SIZE)
+    }
+
+    /// Returns new frame reader with specified maximum frame size.
+    #[must_use]
+    #[inline(always)]
+    pub fn with_max_frame_size(reader: R, max_frame_size: usize) -> Self {
+        FrameReader {
+            reader,
+            buffer: Vec::new(),
+            consumed: 0,
+            max_frame_size,
+        }
+    }
+
+    /// Reads next frame and returns its bytes.
+    /// Returns `None` if stream ends at frame boundary.
+    ///
+    /// # Errors
+    ///
+    /// Returns `FrameError::FrameTooLarge` if frame length exceeds maximum frame size.
+    /// Returns `FrameError::UnexpectedEof` if stream ends inside a frame.
+    /// Returns `FrameError::Io` if reader fails.
+    pub fn read_frame(&mut self) -> Result<Option<&[u8]>, FrameError> {
+        if self.consumed > 0 {
+            self.buffer.drain(..self.consumed);
+            self.consumed = 0;
+        }
+
+        loop {
+            if let Some(len) = self.complete_frame()? {
+                self.consumed = PREFIX_SIZE + len;
+                return Ok(Some(&self.buffer[PREFIX_SIZE..self.consumed]));
+            }
+
+            let filled = self.buffer.len();
+            self.buffer.resize(filled + READ_CHUNK, 0);
+            let result = self.reader.read(&mut self.buffer[filled..]);
+            let read = *result.as_ref().unwrap_or(&0);
+            self.buffer.truncate(filled + read);
+
+            match result {
+                Ok(0) if self.buffer.is_empty() => return Ok(None),
+                Ok(0) => return Err(FrameError::UnexpectedEof),
+                Ok(_) => {}
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(FrameError::Io(err)),
+            }
+        }
+    }
+
+    /// Reads next frame and deserializes the value from it.
+    /// Returns `None` if stream ends at frame boundary.
+    ///
+    /// # Errors
+    ///
+    /// Returns `FrameError::Deserialize` if deserialization fails
+    /// or the packet does not span the whole frame.
+    /// Returns other `FrameError` if reading the frame fails,
+    /// see [`FrameReader::read_frame`].
+    pub fn read<'de, F, T>(&'de mut self) -> Result<Option<T>, FrameError>
+    where
+        F: Formula + ?Sized,
+        T: Deserialize<'de, F>,
+    {
+        let Some(frame) = self.read_frame()? else {
+            return Ok(None);
+        };
+
+        let (value, size) = read_packet::<F, T>(frame)?;
+        if size != frame.len() {
+            let err = DeserializeError::WrongLength(ErrorDetails::sizes(size, frame.len()));
+            return Err(FrameError::Deserialize(err.with_formula::<F>()));
+        }
+        Ok(Some(value))
+    }
+
+    /// Returns reference to the underlying reader.
+    #[inline(always)]
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    /// Returns mutable reference to the underlying reader.
+    #[inline(always)]
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    /// Unwraps the underlying reader.
+    /// Bytes that were read from it but not returned in frames are lost.
+    #[inline(always)]
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Returns length of the first frame in the buffer if it is complete.
+    fn complete_frame(&self) -> Result<Option<usize>, FrameError> {
+        let Some(prefix) = self.buffer.first_chunk::<PREFIX_SIZE>() else {
+            return Ok(None);
+        };
+
+        let len = usize::try_from(u32::from_be_bytes(*prefix)).unwrap_or(usize::MAX);
+        if len > self.max_frame_size {
+            return Err(FrameError::FrameTooLarge(len));
+        }
+
+        if self.buffer.len() - PREFIX_SIZE < len {
+            Ok(None)
+        } else {
+            Ok(Some(len))
+        }
+    }
+}
@@ -0,0 +1,123 @@
+//!
+//! This module provides helpers for fuzzing with `arbitrary`.
+//!
+
+use arbitrary::Unstructured;
+
+use crate::{
+    deserialize::{deserialize, deserialize_with_size, Deserialize},
+    formula::Formula,
+    packet::read_packet,
+};
+
+#[cfg(feature = "alloc")]
+use core::fmt::Debug;
+
+#[cfg(feature = "alloc")]
+use arbitrary::Arbitrary;
+
+#[cfg(feature = "alloc")]
+use crate::{
+    packet::{packet_size, write_packet_to_vec},
+    serialize::{serialized_size, Serialize},
+};
+
+/// Feeds untrusted bytes into deserialization of `T` with formula `F`.
+///
+/// Bytes are read as a packet, as a value occupying the whole input
+/// and as a value with stack size taken from the first bytes of the input.
+/// Errors are ignored, so the function may only fail by panicking,
+/// which is what a fuzzer is looking for.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// fuzz_deserialize::<[Bytes], Vec<&[u8]>>(&[0xff, 0x13, 0, 0, 3, 0, 0]);
+/// ```
+#[inline]
+pub fn fuzz_deserialize<'de, F, T>(data: &'de [u8])
+where
+    F: Formula + ?Sized,
+    T: Deserialize<'de, F>,
+{
+    let _ = read_packet::<F, T>(data);
+
+    if F::HEAPLESS || F::MAX_STACK_SIZE.is_some() {
+        let _ = deserialize::<F, T>(data);
+    }
+
+    let mut u = Unstructured::new(data);
+    if let Ok(stack) = u.int_in_range(0..=u32::MAX) {
+        let input = u.take_rest();
+        let stack = (stack as usize).min(input.len());
+        let _ = deserialize_with_size::<F, T>(input, stack);
+    }
+}
+
+/// Serializes the value with formula `F` and checks that it reads back equal.
+///
+/// Also checks that packet size and size hint, when provided,
+/// agree with the number of bytes actually written.
+///
+/// # Panics
+///
+/// Panics if any of the checks fails.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// check_roundtrip::<Vec<String>, Vec<String>>(vec!["a".into(), "bc".into()]);
+/// ```
+#[cfg(feature = "alloc")]
+#[inline]
+pub fn check_roundtrip<F, T>(value: T)
+where
+    F: Formula + ?Sized,
+    T: Serialize<F> + for<'de> Deserialize<'de, F> + Clone + PartialEq + Debug,
+{
+    let (size, stack) = serialized_size::<F, T>(value.clone());
+    if let Some(sizes) = <T as Serialize<F>>::size_hint(&value) {
+        assert_eq!(
+            (sizes.total(), sizes.stack),
+            (size, stack),
+            "size hint does not match serialized size"
+        );
+    }
+
+    let mut buffer = alloc::vec::Vec::new();
+    let written = write_packet_to_vec::<F, T>(value.clone(), &mut buffer);
+    assert_eq!(written, packet_size::<F, T>(value.clone()));
+
+    let (read, consumed) = read_packet::<F, T>(&buffer[..written])
+        .unwrap_or_else(|err| panic!("failed to read packet back: {err:?}"));
+    assert_eq!(consumed, written);
+    assert_eq!(read, value);
+}
+
+/// Generates arbitrary value of `T` and checks it with [`check_roundtrip`].
+///
+/// # Errors
+///
+/// Returns error if `u` has not enough data to generate the value.
+///
+/// # Panics
+///
+/// Panics if roundtrip check fails.
+#[cfg(feature = "alloc")]
+#[inline]
+pub fn fuzz_roundtrip<F, T>(u: &mut Unstructured<'_>) -> arbitrary::Result<()>
+where
+    F: Formula + ?Sized,
+    T: for<'a> Arbitrary<'a>
+        + Serialize<F>
+        + for<'de> Deserialize<'de, F>
+        + Clone
+        + PartialEq
+        + Debug,
+{
+    let value = T::arbitrary(u)?;
+    check_roundtrip::<F, T>(value);
+    Ok(())
+}
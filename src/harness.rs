@@ -0,0 +1,233 @@
+//!
+//! This module provides helpers to test `Formula` implementations.
+//!
+
+use alloc::vec::Vec;
+use core::fmt::Debug;
+
+use proptest::{
+    collection::vec,
+    prelude::{any, Just, Strategy},
+    prop_oneof,
+};
+
+use crate::{
+    deserialize::Deserialize,
+    formula::Formula,
+    packet::{packet_size, read_packet, read_packet_in_place, write_packet_to_vec},
+    serialize::{serialize_to_vec, serialized_size, Serialize},
+    size::{FixedIsizeType, FixedUsizeType},
+    validate::validate_packet,
+};
+
+/// Checks that sizes reported for the value agree with formula constants
+/// and with the number of bytes actually written.
+///
+/// Checked are `Serialize::size_hint`, `Formula::MAX_STACK_SIZE`,
+/// `Formula::EXACT_SIZE`, `Formula::HEAPLESS` and `Formula::MAX_SIZE`.
+///
+/// # Panics
+///
+/// Panics if any of the checks fails.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::{*, testing::*};
+/// assert_size_hint_consistent::<[Ref<str>], _>(["a", "bc"]);
+/// ```
+#[track_caller]
+pub fn assert_size_hint_consistent<F, T>(value: T)
+where
+    F: Formula + ?Sized,
+    T: Serialize<F> + Clone,
+{
+    let (size, stack) = serialized_size::<F, T>(value.clone());
+
+    if let Some(sizes) = <T as Serialize<F>>::size_hint(&value) {
+        assert_eq!(
+            (sizes.total(), sizes.stack),
+            (size, stack),
+            "size hint does not match serialized size"
+        );
+    }
+
+    if let Some(max_stack) = F::MAX_STACK_SIZE {
+        assert!(stack <= max_stack, "stack size exceeds `MAX_STACK_SIZE`");
+        if F::EXACT_SIZE {
+            assert_eq!(stack, max_stack, "stack size differs from exact size");
+        }
+    }
+    if F::HEAPLESS {
+        assert_eq!(size, stack, "heapless formula wrote heap data");
+    }
+    if let Some(max_size) = F::MAX_SIZE {
+        assert!(size <= max_size, "size exceeds `MAX_SIZE`");
+    }
+
+    let mut buffer = Vec::new();
+    assert_eq!(
+        serialize_to_vec::<F, T>(value.clone(), &mut buffer),
+        (size, stack),
+        "serialized size differs from bytes written"
+    );
+
+    buffer.clear();
+    assert_eq!(
+        write_packet_to_vec::<F, T>(value.clone(), &mut buffer),
+        packet_size::<F, T>(value),
+        "packet size differs from bytes written"
+    );
+}
+
+/// Serializes the value with formula `F` and checks that it reads back equal,
+/// both into new value and in-place.
+/// Also checks sizes with [`assert_size_hint_consistent`]
+/// and that written packet passes validation.
+///
+/// # Panics
+///
+/// Panics if any of the checks fails.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::{*, testing::*};
+/// assert_roundtrip::<Vec<String>, _>(vec![String::from("a"), String::from("bc")]);
+/// ```
+#[track_caller]
+pub fn assert_roundtrip<F, T>(value: T)
+where
+    F: Formula + ?Sized,
+    T: Serialize<F> + for<'de> Deserialize<'de, F> + Clone + PartialEq + Debug,
+{
+    assert_size_hint_consistent::<F, T>(value.clone());
+
+    let mut buffer = Vec::new();
+    let size = write_packet_to_vec::<F, T>(value.clone(), &mut buffer);
+    let packet = &buffer[..size];
+
+    match validate_packet::<F>(packet) {
+        Ok(validated) => assert_eq!(validated, size, "validated size differs"),
+        Err(err) => panic!("written packet fails validation: {err:?}"),
+    }
+
+    let (read, consumed) = read_packet::<F, T>(packet)
+        .unwrap_or_else(|err| panic!("failed to read packet back: {err:?}"));
+    assert_eq!(consumed, size, "packet is not consumed entirely");
+    assert_eq!(read, value);
+
+    let mut place = read;
+    read_packet_in_place::<F, T>(&mut place, packet)
+        .unwrap_or_else(|err| panic!("failed to read packet back in-place: {err:?}"));
+    assert_eq!(place, value);
+}
+
+/// Feeds arbitrary bytes into deserialization of `T` with formula `F`
+/// and checks that it returns error instead of panicking.
+///
+/// Returns whether deserialization succeeded.
+///
+/// # Panics
+///
+/// Panics if deserialization panics.
+pub fn assert_no_panic<'de, F, T>(input: &'de [u8]) -> bool
+where
+    F: Formula + ?Sized,
+    T: Deserialize<'de, F>,
+{
+    read_packet::<F, T>(input).is_ok()
+}
+
+/// Strategy for `usize` values that fit into `FixedUsizeType`,
+/// so they can be serialized with current "fixed*" feature.
+pub fn fixed_usize() -> impl Strategy<Value = usize> {
+    any::<FixedUsizeType>().prop_filter_map("value does not fit `usize`", |value| {
+        usize::try_from(value).ok()
+    })
+}
+
+/// Strategy for `isize` values that fit into `FixedIsizeType`,
+/// so they can be serialized with current "fixed*" feature.
+pub fn fixed_isize() -> impl Strategy<Value = isize> {
+    any::<FixedIsizeType>().prop_filter_map("value does not fit `isize`", |value| {
+        isize::try_from(value).ok()
+    })
+}
+
+/// Strategy for values of `Vlq` formula.
+/// Unlike `any::<u64>()` covers encodings of every length evenly.
+pub fn vlq() -> impl Strategy<Value = u64> {
+    (any::<u64>(), 0..u64::BITS).prop_map(|(value, shift)| value >> shift)
+}
+
+/// Strategy for packets with values generated by `values`
+/// serialized with formula `F`.
+///
+/// # Panics
+///
+/// Panics if serialized data exceeds [`PACKET_SIZE_LIMIT`](crate::PACKET_SIZE_LIMIT).
+pub fn packet<F, T, S>(values: S) -> impl Strategy<Value = Vec<u8>>
+where
+    F: Formula + ?Sized,
+    S: Strategy<Value = T>,
+    T: Serialize<F>,
+{
+    values.prop_map(|value| {
+        let mut buffer = Vec::new();
+        let size = write_packet_to_vec::<F, T>(value, &mut buffer);
+        buffer.truncate(size);
+        buffer
+    })
+}
+
+/// Strategy for packets generated by [`packet`] with some bytes corrupted,
+/// truncated or prepended.
+///
+/// Use with [`assert_no_panic`] to check deserialization of malformed input.
+pub fn corrupted_packet<F, T, S>(values: S) -> impl Strategy<Value = Vec<u8>>
+where
+    F: Formula + ?Sized,
+    S: Strategy<Value = T>,
+    T: Serialize<F>,
+{
+    let mutation = prop_oneof![
+        (any::<usize>(), any::<u8>()).prop_map(|(idx, byte)| Mutation::Replace(idx, byte)),
+        any::<usize>().prop_map(Mutation::Truncate),
+        vec(any::<u8>(), 1..8).prop_map(Mutation::Prepend),
+        Just(Mutation::Keep),
+    ];
+
+    (packet::<F, T, S>(values), vec(mutation, 1..4)).prop_map(|(mut packet, mutations)| {
+        for mutation in mutations {
+            mutation.apply(&mut packet);
+        }
+        packet
+    })
+}
+
+#[derive(Clone, Debug)]
+enum Mutation {
+    Replace(usize, u8),
+    Truncate(usize),
+    Prepend(Vec<u8>),
+    Keep,
+}
+
+impl Mutation {
+    fn apply(self, packet: &mut Vec<u8>) {
+        match self {
+            Mutation::Replace(idx, byte) => {
+                if !packet.is_empty() {
+                    let len = packet.len();
+                    packet[idx % len] = byte;
+                }
+            }
+            Mutation::Truncate(len) => packet.truncate(len % (packet.len() + 1)),
+            Mutation::Prepend(bytes) => {
+                packet.splice(0..0, bytes);
+            }
+            Mutation::Keep => {}
+        }
+    }
+}
@@ -0,0 +1,279 @@
+//!
+//! This module provides slice formula with index for random access.
+//!
+
+use core::{iter::FusedIterator, marker::PhantomData};
+
+use alloc::vec::Vec;
+
+use crate::{
+    buffer::Buffer,
+    deserialize::{Deserialize, DeserializeError, Deserializer, ErrorDetails},
+    formula::Formula,
+    iter::deserialize_extend_iter,
+    serialize::{write_field, Serialize, Sizes},
+    size::{deserialize_usize, SIZE_STACK},
+};
+
+/// Slice formula with index of element offsets.
+///
+/// Elements are serialized the same way as with `[F]` formula,
+/// followed by offset of each element and number of elements.
+/// This costs extra `usize` per element and allows [`IndexedIter`]
+/// to access any element in O(1), while iterator over `[F]`
+/// has to walk size prefixes of all preceding elements
+/// when `F::MAX_STACK_SIZE` is `None`.
+///
+/// Any iterable with items serializable with `F` can be serialized.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// let words = ["zero", "one", "two", "three"];
+///
+/// let mut buffer = Vec::new();
+/// let size = write_packet_to_vec::<Indexed<Bytes>, _>(words.map(str::as_bytes), &mut buffer);
+/// let (mut iter, _) =
+///     read_packet::<Indexed<Bytes>, IndexedIter<Bytes, &[u8]>>(&buffer[..size]).unwrap();
+///
+/// assert_eq!(iter.len(), 4);
+/// assert_eq!(iter.get(2).unwrap().unwrap(), b"two");
+/// assert_eq!(iter.nth(3).unwrap().unwrap(), b"three");
+/// ```
+pub struct Indexed<F> {
+    marker: PhantomData<fn(&F) -> &F>,
+}
+
+impl<F> Formula for Indexed<F>
+where
+    F: Formula,
+{
+    const MAX_STACK_SIZE: Option<usize> = None;
+    const EXACT_SIZE: bool = false;
+    const HEAPLESS: bool = F::HEAPLESS;
+}
+
+impl<F, I> Serialize<Indexed<F>> for I
+where
+    F: Formula,
+    I: IntoIterator,
+    I::Item: Serialize<F>,
+{
+    #[inline]
+    fn serialize<B>(self, sizes: &mut Sizes, mut buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        let start = sizes.stack;
+        let mut offsets = Vec::new();
+        for elem in self {
+            offsets.push(sizes.stack - start);
+            write_field::<F, _, _>(elem, sizes, buffer.reborrow(), false)?;
+        }
+
+        for &offset in offsets.iter().rev() {
+            write_field::<usize, _, _>(offset, sizes, buffer.reborrow(), false)?;
+        }
+        write_field::<usize, _, _>(offsets.len(), sizes, buffer, false)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        None
+    }
+}
+
+/// Iterator over elements serialized with [`Indexed`] formula.
+///
+/// Unlike [`DeIter`](crate::DeIter) it provides random access to elements
+/// with [`IndexedIter::get`] and skips elements in O(1)
+/// in `nth` and `nth_back`.
+#[must_use]
+pub struct IndexedIter<'de, F, T> {
+    input: &'de [u8],
+    stack: usize,
+    index: &'de [u8],
+    front: usize,
+    back: usize,
+    marker: PhantomData<fn(&F) -> T>,
+}
+
+impl<'de, F, T> Clone for IndexedIter<'de, F, T> {
+    #[inline(always)]
+    fn clone(&self) -> Self {
+        IndexedIter {
+            input: self.input,
+            stack: self.stack,
+            index: self.index,
+            front: self.front,
+            back: self.back,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<'de, F, T> IndexedIter<'de, F, T>
+where
+    F: Formula,
+    T: Deserialize<'de, F>,
+{
+    /// Deserializes element at specified position
+    /// relative to the current front of the iterator.
+    /// Returns `None` if `index` is out of bounds.
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<Result<T, DeserializeError>> {
+        if index >= self.back - self.front {
+            return None;
+        }
+        Some(self.read(self.front + index))
+    }
+
+    #[inline]
+    fn read(&self, index: usize) -> Result<T, DeserializeError> {
+        let offset = &self.index[index * SIZE_STACK..][..SIZE_STACK];
+        let offset = deserialize_usize(Deserializer::new_unchecked(SIZE_STACK, offset))?;
+        if offset > self.stack {
+            return Err(DeserializeError::WrongAddress);
+        }
+
+        let input = &self.input[..self.input.len() - offset];
+        let mut de = Deserializer::new_unchecked(self.stack - offset, input);
+        de.read_value::<F, T>(false)
+    }
+}
+
+impl<'de, F, T> Iterator for IndexedIter<'de, F, T>
+where
+    F: Formula,
+    T: Deserialize<'de, F>,
+{
+    type Item = Result<T, DeserializeError>;
+
+    #[inline(always)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.back - self.front;
+        (len, Some(len))
+    }
+
+    #[inline]
+    fn next(&mut self) -> Option<Result<T, DeserializeError>> {
+        if self.front == self.back {
+            return None;
+        }
+        let item = self.read(self.front);
+        self.front += 1;
+        Some(item)
+    }
+
+    #[inline]
+    fn nth(&mut self, n: usize) -> Option<Result<T, DeserializeError>> {
+        if n >= self.back - self.front {
+            self.front = self.back;
+            return None;
+        }
+        self.front += n;
+        self.next()
+    }
+
+    #[inline(always)]
+    fn count(self) -> usize {
+        self.back - self.front
+    }
+}
+
+impl<'de, F, T> DoubleEndedIterator for IndexedIter<'de, F, T>
+where
+    F: Formula,
+    T: Deserialize<'de, F>,
+{
+    #[inline]
+    fn next_back(&mut self) -> Option<Result<T, DeserializeError>> {
+        if self.front == self.back {
+            return None;
+        }
+        self.back -= 1;
+        Some(self.read(self.back))
+    }
+
+    #[inline]
+    fn nth_back(&mut self, n: usize) -> Option<Result<T, DeserializeError>> {
+        if n >= self.back - self.front {
+            self.back = self.front;
+            return None;
+        }
+        self.back -= n;
+        self.next_back()
+    }
+}
+
+impl<'de, F, T> ExactSizeIterator for IndexedIter<'de, F, T>
+where
+    F: Formula,
+    T: Deserialize<'de, F>,
+{
+    #[inline(always)]
+    fn len(&self) -> usize {
+        self.back - self.front
+    }
+}
+
+impl<'de, F, T> FusedIterator for IndexedIter<'de, F, T>
+where
+    F: Formula,
+    T: Deserialize<'de, F>,
+{
+}
+
+impl<'de, F, T> Deserialize<'de, Indexed<F>> for IndexedIter<'de, F, T>
+where
+    F: Formula,
+    T: Deserialize<'de, F>,
+{
+    #[inline]
+    fn deserialize(mut de: Deserializer<'de>) -> Result<Self, DeserializeError> {
+        let len = de.read_front_bytes(SIZE_STACK)?;
+        let len = deserialize_usize(Deserializer::new_unchecked(SIZE_STACK, len))?;
+        let index_size = len
+            .checked_mul(SIZE_STACK)
+            .ok_or(DeserializeError::WrongLength(ErrorDetails::new()))?;
+        let index = de.read_front_bytes(index_size)?;
+
+        Ok(IndexedIter {
+            input: de.input(),
+            stack: de.stack(),
+            index,
+            front: 0,
+            back: len,
+            marker: PhantomData,
+        })
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(&mut self, de: Deserializer<'de>) -> Result<(), DeserializeError> {
+        *self = <Self as Deserialize<Indexed<F>>>::deserialize(de)?;
+        Ok(())
+    }
+}
+
+impl<'de, F, T> Deserialize<'de, Indexed<F>> for Vec<T>
+where
+    F: Formula,
+    T: Deserialize<'de, F>,
+{
+    #[inline]
+    fn deserialize(de: Deserializer<'de>) -> Result<Self, DeserializeError> {
+        let iter = <IndexedIter<F, T> as Deserialize<Indexed<F>>>::deserialize(de)?;
+        let mut vec = Vec::with_capacity(iter.len());
+        deserialize_extend_iter(&mut vec, iter)?;
+        Ok(vec)
+    }
+
+    #[inline]
+    fn deserialize_in_place(&mut self, de: Deserializer<'de>) -> Result<(), DeserializeError> {
+        let iter = <IndexedIter<F, T> as Deserialize<Indexed<F>>>::deserialize(de)?;
+        self.clear();
+        self.reserve(iter.len());
+        deserialize_extend_iter(self, iter)
+    }
+}
@@ -0,0 +1,353 @@
+//!
+//! This module provides formula-guided dump of serialized data.
+//!
+
+use alloc::{format, string::String};
+use core::{fmt::Write, ops::Range};
+
+use crate::{
+    deserialize::{read_reference_with, DeserializeError, Deserializer, ErrorDetails},
+    export::short_name,
+    formula::{reference_size_with, Formula},
+    schema::{Schema, SchemaField, SchemaKind},
+    size::SIZE_STACK,
+    value::{decode, node, Value},
+};
+
+/// Maximum nesting of values shown by inspector.
+const MAX_DEPTH: usize = 64;
+
+/// Maximum number of bytes shown in hex dump of a single value.
+const MAX_HEX: usize = 16;
+
+/// Renders serialized value of formula `F` as human-readable dump.
+/// `stack` is the size of the value stack, as returned by [`serialize`](crate::serialize).
+///
+/// See [`Schema::inspect`].
+#[must_use]
+pub fn inspect<F>(input: &[u8], stack: usize) -> String
+where
+    F: Formula + ?Sized,
+{
+    Schema::of::<F>().inspect(input, stack)
+}
+
+/// Renders packet with value of formula `F` as human-readable dump.
+///
+/// See [`Schema::inspect_packet`].
+#[must_use]
+pub fn inspect_packet<F>(input: &[u8]) -> String
+where
+    F: Formula + ?Sized,
+{
+    Schema::of::<F>().inspect_packet(input)
+}
+
+impl Schema {
+    /// Renders serialized value as human-readable dump.
+    /// `stack` is the size of the value stack, as returned by [`serialize`](crate::serialize).
+    ///
+    /// Each line shows range of input bytes, field name, formula
+    /// and, for primitive values, decoded value and bytes in hex.
+    /// Nested values are indented.
+    /// Values that fail to deserialize are marked with `!!`
+    /// and the error, followed by bytes of the corrupt region.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use alkahest::*;
+    /// let mut buffer = [0u8; 64];
+    /// let (size, root) = serialize::<(u8, Vec<u16>), _>((1u8, [2u16]), &mut buffer).unwrap();
+    ///
+    /// let dump = inspect::<(u8, Vec<u16>)>(&buffer[..size], root);
+    /// assert!(dump.contains("  .0: u8 = 1 [01]"));
+    /// ```
+    #[must_use]
+    pub fn inspect(&self, input: &[u8], stack: usize) -> String {
+        let mut inspector = Inspector {
+            schema: self,
+            input,
+            out: String::new(),
+        };
+
+        match Deserializer::new(stack, input) {
+            Ok(de) => inspector.value(0, de, "root", 0),
+            Err(err) => inspector.error(input.len()..input.len(), "root", 0, err),
+        }
+        inspector.out
+    }
+
+    /// Renders packet as human-readable dump.
+    /// See [`Schema::inspect`] for format.
+    #[must_use]
+    pub fn inspect_packet(&self, input: &[u8]) -> String {
+        let mut inspector = Inspector {
+            schema: self,
+            input,
+            out: String::new(),
+        };
+
+        let Some(root) = self.nodes.first() else {
+            inspector.error(0..0, "root", 0, DeserializeError::Incompatible);
+            return inspector.out;
+        };
+
+        let reference_size = reference_size_with(root.exact_size);
+        if input.len() < reference_size {
+            let err =
+                DeserializeError::OutOfBounds(ErrorDetails::sizes(reference_size, input.len()));
+            inspector.error(0..input.len(), "packet", 0, err);
+            return inspector.out;
+        }
+
+        let reference = read_reference_with(
+            input,
+            input.len() - reference_size,
+            root.max_stack_size,
+            root.exact_size,
+        );
+        let (address, size) = match reference {
+            Ok(reference) => reference,
+            Err(err) => {
+                inspector.error(0..reference_size, "packet", 0, err);
+                return inspector.out;
+            }
+        };
+        let _ = writeln!(
+            inspector.out,
+            "{} packet: address {address}, size {size}",
+            range(0..reference_size)
+        );
+
+        if size > address {
+            let err = DeserializeError::WrongAddress;
+            inspector.error(0..reference_size, "packet", 0, err);
+        } else if address > input.len() {
+            let err = DeserializeError::OutOfBounds(ErrorDetails::sizes(address, input.len()));
+            inspector.error(0..reference_size, "packet", 0, err);
+        } else {
+            let de = Deserializer::new_unchecked(size, &input[..address]);
+            inspector.value(0, de, "root", 1);
+        }
+        inspector.out
+    }
+}
+
+struct Inspector<'a> {
+    schema: &'a Schema,
+    input: &'a [u8],
+    out: String,
+}
+
+impl Inspector<'_> {
+    fn line(&mut self, bytes: Range<usize>, label: &str, depth: usize) {
+        let _ = write!(
+            self.out,
+            "{} {:indent$}{label}: ",
+            range(bytes),
+            "",
+            indent = depth * 2
+        );
+    }
+
+    fn hex(&mut self, bytes: Range<usize>) {
+        self.out.push_str(" [");
+        let len = bytes.len();
+        for (idx, byte) in self.input[bytes].iter().take(MAX_HEX).enumerate() {
+            if idx > 0 {
+                self.out.push(' ');
+            }
+            let _ = write!(self.out, "{byte:02x}");
+        }
+        if len > MAX_HEX {
+            let _ = write!(self.out, " .. {} more", len - MAX_HEX);
+        }
+        self.out.push(']');
+    }
+
+    fn error(&mut self, bytes: Range<usize>, label: &str, depth: usize, err: DeserializeError) {
+        let _ = write!(
+            self.out,
+            "{} {:indent$}!! {label}: {err:?}",
+            range(bytes.clone()),
+            "",
+            indent = depth * 2
+        );
+        if !bytes.is_empty() {
+            self.hex(bytes);
+        }
+        self.out.push('\n');
+    }
+
+    fn value(&mut self, idx: usize, mut de: Deserializer, label: &str, depth: usize) {
+        let bytes = de.stack_range();
+
+        let current = match node(self.schema, idx) {
+            Ok(current) => current,
+            Err(err) => return self.error(bytes, label, depth, err),
+        };
+        if depth > MAX_DEPTH {
+            return self.error(bytes, label, depth, DeserializeError::Incompatible);
+        }
+
+        let name = short_name(&current.name);
+
+        match &current.kind {
+            SchemaKind::Array(elem, len) => {
+                self.line(bytes, label, depth);
+                let _ = writeln!(self.out, "{name}");
+                for elem_idx in 0..*len {
+                    let label = format!("[{elem_idx}]");
+                    if !self.field(*elem, &mut de, &label, depth + 1, false) {
+                        break;
+                    }
+                }
+            }
+            SchemaKind::Slice(elem) => {
+                self.line(bytes.clone(), label, depth);
+                let _ = writeln!(self.out, "{name}");
+
+                let max_stack_size = match node(self.schema, *elem) {
+                    Ok(elem) => elem.max_stack_size,
+                    Err(err) => return self.error(bytes, label, depth + 1, err),
+                };
+                let count = match max_stack_size {
+                    Some(0) => match de.read_usize() {
+                        Ok(count) => Some(count),
+                        Err(err) => return self.error(bytes, "len", depth + 1, err),
+                    },
+                    _ => None,
+                };
+                let min_stack = max_stack_size.unwrap_or(SIZE_STACK);
+
+                let mut elem_idx = 0;
+                while count.map_or(de.stack() >= min_stack, |count| elem_idx < count) {
+                    let label = format!("[{elem_idx}]");
+                    if !self.field(*elem, &mut de, &label, depth + 1, false) {
+                        break;
+                    }
+                    elem_idx += 1;
+                }
+            }
+            SchemaKind::Ref(formula) => {
+                let pointee = match node(self.schema, *formula) {
+                    Ok(pointee) => pointee,
+                    Err(err) => return self.error(bytes, label, depth, err),
+                };
+                match de.deref_with(pointee.max_stack_size, pointee.exact_size) {
+                    Ok(target) => {
+                        self.line(bytes, label, depth);
+                        let _ = writeln!(self.out, "{name} -> {}", range(target.stack_range()));
+                        self.value(*formula, target, "*", depth + 1);
+                    }
+                    Err(err) => self.error(bytes, label, depth, err),
+                }
+            }
+            SchemaKind::Option(formula) => match de.read_flag() {
+                Ok(false) => {
+                    self.line(bytes, label, depth);
+                    let _ = writeln!(self.out, "{name} = None");
+                }
+                Ok(true) => {
+                    self.line(bytes, label, depth);
+                    let _ = writeln!(self.out, "{name} = Some");
+                    self.field(*formula, &mut de, "?", depth + 1, true);
+                }
+                Err(err) => self.error(bytes, label, depth, err),
+            },
+            SchemaKind::Tuple(elems) => {
+                self.line(bytes, label, depth);
+                let _ = writeln!(self.out, "{name}");
+                let len = elems.len();
+                for (elem_idx, elem) in elems.iter().enumerate() {
+                    let label = format!(".{elem_idx}");
+                    if !self.field(*elem, &mut de, &label, depth + 1, elem_idx + 1 == len) {
+                        break;
+                    }
+                }
+            }
+            SchemaKind::Struct(fields) => {
+                self.line(bytes, label, depth);
+                let _ = writeln!(self.out, "{name}");
+                self.fields(fields, &mut de, depth + 1);
+            }
+            SchemaKind::Enum(variants) => match de.read_value::<u32, u32>(false) {
+                Ok(tag) => match variants.iter().find(|variant| variant.tag == tag) {
+                    Some(variant) => {
+                        self.line(bytes, label, depth);
+                        let _ = writeln!(self.out, "{name}::{} (tag {tag})", variant.name);
+                        self.fields(&variant.fields, &mut de, depth + 1);
+                    }
+                    None => self.error(
+                        bytes,
+                        label,
+                        depth,
+                        DeserializeError::WrongVariant(tag, ErrorDetails::new()),
+                    ),
+                },
+                Err(err) => self.error(bytes, label, depth, err),
+            },
+            _ => match decode(self.schema, idx, de) {
+                Ok(value) => {
+                    self.line(bytes.clone(), label, depth);
+                    let _ = write!(self.out, "{name} = ");
+                    self.primitive(&value);
+                    self.hex(bytes);
+                    self.out.push('\n');
+                }
+                Err(err) => self.error(bytes, label, depth, err),
+            },
+        }
+    }
+
+    /// Reads field from the deserializer and renders it.
+    /// Returns `false` if field can't be read.
+    fn field(
+        &mut self,
+        idx: usize,
+        de: &mut Deserializer,
+        label: &str,
+        depth: usize,
+        last: bool,
+    ) -> bool {
+        let bytes = de.stack_range();
+        let field = match node(self.schema, idx)
+            .and_then(|field| de.read_field_with(field.max_stack_size, field.exact_size, last))
+        {
+            Ok(field) => field,
+            Err(err) => {
+                self.error(bytes, label, depth, err);
+                return false;
+            }
+        };
+        self.value(idx, field, label, depth);
+        true
+    }
+
+    fn fields(&mut self, fields: &[SchemaField], de: &mut Deserializer, depth: usize) {
+        let len = fields.len();
+        for (idx, field) in fields.iter().enumerate() {
+            let label = format!(".{}", field.name);
+            if !self.field(field.formula, de, &label, depth, idx + 1 == len) {
+                break;
+            }
+        }
+    }
+
+    fn primitive(&mut self, value: &Value) {
+        let _ = match value {
+            Value::Bool(value) => write!(self.out, "{value}"),
+            Value::Unsigned(value) => write!(self.out, "{value}"),
+            Value::Signed(value) => write!(self.out, "{value}"),
+            Value::Float(value) => write!(self.out, "{value}"),
+            Value::Str(value) => write!(self.out, "{value:?}"),
+            Value::Bytes(value) | Value::Opaque(value) => write!(self.out, "{} bytes", value.len()),
+            value => write!(self.out, "{value:?}"),
+        };
+    }
+}
+
+fn range(bytes: Range<usize>) -> String {
+    format!("{:04x}..{:04x}", bytes.start, bytes.end)
+}
@@ -0,0 +1,191 @@
+//!
+//! This module provides formula wrapper that shares identical values within a packet.
+//!
+
+use core::{cell::RefCell, marker::PhantomData};
+
+use alloc::collections::BTreeMap;
+
+use crate::{
+    buffer::Buffer,
+    deserialize::{Deserialize, DeserializeError, Deserializer},
+    formula::{reference_size, BareFormula, Formula},
+    serialize::{write_ref, write_reference, Serialize, Sizes},
+};
+
+/// Formula wrapper that deduplicates identical values within one packet.
+///
+/// Layout is the same as [`Ref<F>`](crate::Ref).
+/// Values are serialized through [`Interner::intern`].
+/// First occurrence of a value is written to the heap as with `Ref<F>`,
+/// later occurrences of equal value become references to the same bytes.
+/// Any type that can be deserialized from `F` can be deserialized from `Interned<F>`.
+///
+/// Since shared references are intentional,
+/// [`validate_packet`](crate::validate_packet) does not check data under this formula.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// let materials = ["steel", "wood", "steel", "steel", "wood"];
+///
+/// let interner = Interner::new();
+/// let mut buffer = Vec::new();
+/// let size = write_packet_to_vec::<[Interned<str>], _>(
+///     materials.iter().map(|m| interner.intern(*m)),
+///     &mut buffer,
+/// );
+/// assert_eq!(interner.len(), 2);
+///
+/// let (value, _) = read_packet::<[Interned<str>], Vec<&str>>(&buffer[..size]).unwrap();
+/// assert_eq!(value, materials);
+/// ```
+pub struct Interned<F: ?Sized> {
+    marker: PhantomData<fn(&F) -> &F>,
+}
+
+impl<F> Formula for Interned<F>
+where
+    F: BareFormula + ?Sized,
+{
+    const MAX_STACK_SIZE: Option<usize> = Some(reference_size::<F>());
+    const EXACT_SIZE: bool = true;
+    const HEAPLESS: bool = matches!(F::MAX_STACK_SIZE, Some(0));
+}
+
+/// Table of values already written with [`Interned`] formula.
+///
+/// Use one interner per packet.
+/// Values are compared with `Ord`, so equal values are written once.
+pub struct Interner<'a, T: ?Sized> {
+    /// Heap address and stack size of written values.
+    written: RefCell<BTreeMap<&'a T, (usize, usize)>>,
+}
+
+impl<'a, T> Default for Interner<'a, T>
+where
+    T: Ord + ?Sized,
+{
+    #[inline(always)]
+    fn default() -> Self {
+        Interner::new()
+    }
+}
+
+impl<'a, T> Interner<'a, T>
+where
+    T: Ord + ?Sized,
+{
+    /// Returns new empty interner.
+    #[must_use]
+    #[inline(always)]
+    pub const fn new() -> Self {
+        Interner {
+            written: RefCell::new(BTreeMap::new()),
+        }
+    }
+
+    /// Wraps value to be serialized with [`Interned`] formula.
+    #[inline(always)]
+    pub fn intern(&self, value: &'a T) -> Intern<'_, 'a, T> {
+        Intern {
+            interner: self,
+            value,
+        }
+    }
+
+    /// Returns number of distinct values written.
+    #[must_use]
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.written.borrow().len()
+    }
+
+    /// Returns `true` if no values were written.
+    #[must_use]
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.written.borrow().is_empty()
+    }
+
+    /// Forgets written values, so that interner can be used for another packet.
+    #[inline(always)]
+    pub fn clear(&mut self) {
+        self.written.get_mut().clear();
+    }
+}
+
+/// Value wrapped by [`Interner::intern`].
+/// Serializable with [`Interned`] formula.
+pub struct Intern<'i, 'a, T: ?Sized> {
+    interner: &'i Interner<'a, T>,
+    value: &'a T,
+}
+
+impl<'i, 'a, T> Clone for Intern<'i, 'a, T>
+where
+    T: ?Sized,
+{
+    #[inline(always)]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'i, 'a, T> Copy for Intern<'i, 'a, T> where T: ?Sized {}
+
+impl<'i, 'a, F, T> Serialize<Interned<F>> for Intern<'i, 'a, T>
+where
+    F: BareFormula + ?Sized,
+    T: Ord + ?Sized,
+    &'a T: Serialize<F>,
+{
+    #[inline]
+    fn serialize<B>(self, sizes: &mut Sizes, mut buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        let written = self.interner.written.borrow().get(self.value).copied();
+        match written {
+            // Value may be recorded by previous attempt to serialize the packet,
+            // only data below the current heap is already written.
+            Some((address, size)) if address <= sizes.heap => {
+                write_reference::<F, B>(size, address, sizes.heap, sizes.stack, buffer)?;
+            }
+            _ => {
+                let size = write_ref::<F, &'a T, _>(self.value, sizes, buffer.reborrow())?;
+                self.interner
+                    .written
+                    .borrow_mut()
+                    .insert(self.value, (sizes.heap, size));
+                write_reference::<F, B>(size, sizes.heap, sizes.heap, sizes.stack, buffer)?;
+            }
+        }
+        sizes.stack += reference_size::<F>();
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        None
+    }
+}
+
+impl<'de, F, T> Deserialize<'de, Interned<F>> for T
+where
+    F: BareFormula + ?Sized,
+    T: Deserialize<'de, F>,
+{
+    #[inline(always)]
+    fn deserialize(de: Deserializer<'de>) -> Result<T, DeserializeError> {
+        let de = de.deref::<F>()?;
+        <T as Deserialize<F>>::deserialize(de)
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(&mut self, de: Deserializer<'de>) -> Result<(), DeserializeError> {
+        let de = de.deref::<F>()?;
+        <T as Deserialize<F>>::deserialize_in_place(self, de)
+    }
+}
@@ -0,0 +1,201 @@
+//!
+//! This module provides packet streaming over `embedded-io` readers and writers.
+//!
+
+use core::fmt;
+
+use embedded_io::{Read, ReadExactError, Write};
+
+use crate::{
+    buffer::BufferExhausted,
+    deserialize::{Deserialize, DeserializeError},
+    formula::{reference_size, Formula},
+    packet::{packet_address, read_packet, write_packet},
+    serialize::Serialize,
+};
+
+/// Error of packet streaming over `embedded-io`.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum IoError<E> {
+    /// Error returned by the reader or writer.
+    Io(E),
+
+    /// Reader reached end of stream before the whole packet was read.
+    UnexpectedEof,
+
+    /// Scratch buffer is too small to hold the packet.
+    BufferExhausted,
+
+    /// Packet failed to deserialize.
+    Deserialize(DeserializeError),
+}
+
+impl<E> From<ReadExactError<E>> for IoError<E> {
+    #[inline(always)]
+    fn from(err: ReadExactError<E>) -> Self {
+        match err {
+            ReadExactError::UnexpectedEof => IoError::UnexpectedEof,
+            ReadExactError::Other(err) => IoError::Io(err),
+        }
+    }
+}
+
+impl<E> From<BufferExhausted> for IoError<E> {
+    #[inline(always)]
+    fn from(BufferExhausted: BufferExhausted) -> Self {
+        IoError::BufferExhausted
+    }
+}
+
+impl<E> From<DeserializeError> for IoError<E> {
+    #[inline(always)]
+    fn from(err: DeserializeError) -> Self {
+        IoError::Deserialize(err)
+    }
+}
+
+impl<E> fmt::Display for IoError<E>
+where
+    E: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IoError::Io(err) => write!(f, "I/O error: {err:?}"),
+            IoError::UnexpectedEof => f.write_str("unexpected end of stream"),
+            IoError::BufferExhausted => f.write_str("buffer exhausted"),
+            IoError::Deserialize(err) => write!(f, "deserialization error: {err}"),
+        }
+    }
+}
+
+/// Writes packet with the value into the writer.
+/// Packet is serialized into `scratch` first, so it must be large enough to hold it.
+/// Returns the number of bytes written.
+///
+/// # Errors
+///
+/// Returns `IoError::BufferExhausted` if `scratch` is too small.
+/// Returns `IoError::Io` if writer fails.
+#[inline]
+pub fn write_packet_to_io<F, T, W>(
+    value: T,
+    scratch: &mut [u8],
+    writer: &mut W,
+) -> Result<usize, IoError<W::Error>>
+where
+    F: Formula + ?Sized,
+    T: Serialize<F>,
+    W: Write + ?Sized,
+{
+    let size = write_packet::<F, T>(value, scratch)?;
+    writer.write_all(&scratch[..size]).map_err(IoError::Io)?;
+    Ok(size)
+}
+
+/// Reads packet from the reader into `scratch` and deserializes the value from it.
+/// Reads exactly the packet bytes and nothing more.
+///
+/// # Errors
+///
+/// Returns `IoError::BufferExhausted` if `scratch` is too small for the packet.
+/// Returns `IoError::UnexpectedEof` or `IoError::Io` if reader fails.
+/// Returns `IoError::Deserialize` if deserialization fails.
+#[inline]
+pub fn read_packet_from_io<'de, F, T, R>(
+    reader: &mut R,
+    scratch: &'de mut [u8],
+) -> Result<T, IoError<R::Error>>
+where
+    F: Formula + ?Sized,
+    T: Deserialize<'de, F>,
+    R: Read + ?Sized,
+{
+    let header = header_size::<F>(scratch)?;
+    reader.read_exact(&mut scratch[..header])?;
+    let size = packet_size::<F>(scratch)?;
+    reader.read_exact(&mut scratch[header..size])?;
+
+    let (value, _) = read_packet::<F, T>(&scratch[..size])?;
+    Ok(value)
+}
+
+/// Async version of [`write_packet_to_io`].
+///
+/// # Errors
+///
+/// Returns `IoError::BufferExhausted` if `scratch` is too small.
+/// Returns `IoError::Io` if writer fails.
+#[cfg(feature = "embedded-io-async")]
+#[inline]
+pub async fn write_packet_to_io_async<F, T, W>(
+    value: T,
+    scratch: &mut [u8],
+    writer: &mut W,
+) -> Result<usize, IoError<W::Error>>
+where
+    F: Formula + ?Sized,
+    T: Serialize<F>,
+    W: embedded_io_async::Write + ?Sized,
+{
+    let size = write_packet::<F, T>(value, scratch)?;
+    writer
+        .write_all(&scratch[..size])
+        .await
+        .map_err(IoError::Io)?;
+    Ok(size)
+}
+
+/// Async version of [`read_packet_from_io`].
+///
+/// # Errors
+///
+/// Returns `IoError::BufferExhausted` if `scratch` is too small for the packet.
+/// Returns `IoError::UnexpectedEof` or `IoError::Io` if reader fails.
+/// Returns `IoError::Deserialize` if deserialization fails.
+#[cfg(feature = "embedded-io-async")]
+#[inline]
+pub async fn read_packet_from_io_async<'de, F, T, R>(
+    reader: &mut R,
+    scratch: &'de mut [u8],
+) -> Result<T, IoError<R::Error>>
+where
+    F: Formula + ?Sized,
+    T: Deserialize<'de, F>,
+    R: embedded_io_async::Read + ?Sized,
+{
+    let header = header_size::<F>(scratch)?;
+    reader.read_exact(&mut scratch[..header]).await?;
+    let size = packet_size::<F>(scratch)?;
+    reader.read_exact(&mut scratch[header..size]).await?;
+
+    let (value, _) = read_packet::<F, T>(&scratch[..size])?;
+    Ok(value)
+}
+
+/// Returns size of the packet header that holds packet size.
+fn header_size<F>(scratch: &[u8]) -> Result<usize, BufferExhausted>
+where
+    F: Formula + ?Sized,
+{
+    let header = reference_size::<F>();
+    if scratch.len() < header {
+        return Err(BufferExhausted);
+    }
+    Ok(header)
+}
+
+/// Returns size of the packet which header is in `scratch`.
+fn packet_size<F>(scratch: &[u8]) -> Result<usize, BufferExhausted>
+where
+    F: Formula + ?Sized,
+{
+    let header = reference_size::<F>();
+    let size = packet_address::<F>(&scratch[..header])
+        .unwrap_or(0)
+        .max(header);
+    if scratch.len() < size {
+        return Err(BufferExhausted);
+    }
+    Ok(size)
+}
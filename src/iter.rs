@@ -6,8 +6,6 @@ use crate::{
     size::SIZE_STACK,
 };
 
-const ITER_UPPER: usize = 4;
-
 /// Returns the size of the serialized data if it can be determined fast.
 #[inline(always)]
 pub fn default_iter_fast_sizes<F, I>(iter: &I) -> Option<Sizes>
@@ -55,15 +53,11 @@ where
             }
         }
         _ => {
-            let (_lower, upper) = iter.size_hint();
-            if upper.map_or(false, |upper| upper <= ITER_UPPER) {
-                let mut sizes = Sizes::ZERO;
-                for elem in iter {
-                    sizes += field_size_hint::<F>(elem, false)?;
-                }
-                return Some(sizes);
+            let mut sizes = Sizes::ZERO;
+            for elem in iter {
+                sizes += elem_size_hint::<F>(elem)?;
             }
-            None
+            Some(sizes)
         }
     }
 }
@@ -90,19 +84,34 @@ where
             }
         }
         _ => {
-            let (_lower, upper) = iter.size_hint();
-            if upper.map_or(false, |upper| upper <= ITER_UPPER) {
-                let mut sizes = Sizes::ZERO;
-                for elem in iter {
-                    sizes += field_size_hint::<F>(&elem, false)?;
-                }
-                return Some(sizes);
+            let mut sizes = Sizes::ZERO;
+            for elem in iter {
+                sizes += elem_size_hint::<F>(&elem)?;
             }
-            None
+            Some(sizes)
         }
     }
 }
 
+/// Size hint for serializing a slice element.
+///
+/// Unlike [`field_size_hint`] it accounts for the size prefix
+/// of elements with unbounded stack size instead of giving up,
+/// so that slices of byte slices, strings and vectors of flat elements
+/// are sized cheaply from element lengths.
+/// Sizes of nested collections are summed recursively.
+#[inline(always)]
+fn elem_size_hint<F: Formula + ?Sized>(elem: &impl Serialize<F>) -> Option<Sizes> {
+    match F::MAX_STACK_SIZE {
+        None => {
+            let mut sizes = elem.size_hint()?;
+            sizes.add_stack(SIZE_STACK);
+            Some(sizes)
+        }
+        Some(_) => field_size_hint::<F>(elem, false),
+    }
+}
+
 macro_rules! serialize_iter_to_slice {
     ($F:ty : $self:expr => $sizes:ident, $buffer:ident) => {{
         write_slice::<$F, _, _>($self, $sizes, $buffer)
@@ -136,6 +145,52 @@ where
     }
 }
 
+/// Wrapper for iterators over key-value pairs
+/// serializable with slice of pairs formula `[(KF, VF)]`.
+///
+/// Accepts both owned `(K, V)` and borrowed `(&K, &V)` items,
+/// so map iterators can be serialized without collecting.
+/// Size hint walks a clone of the iterator,
+/// so pairs of variable size are written in a single pass.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// # use std::collections::BTreeMap;
+/// let map = BTreeMap::from([(1u32, String::from("one")), (2, String::from("two"))]);
+///
+/// let mut buffer = Vec::new();
+/// let size = write_packet_to_vec::<[(Vlq, String)], _>(SerIterMap(map.iter()), &mut buffer);
+/// let (pairs, _) = read_packet::<[(Vlq, String)], Vec<(u32, &str)>>(&buffer[..size]).unwrap();
+/// assert_eq!(pairs, [(1, "one"), (2, "two")]);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct SerIterMap<T>(pub T);
+
+impl<KF, VF, K, V, I> Serialize<[(KF, VF)]> for SerIterMap<I>
+where
+    KF: Formula,
+    VF: Formula,
+    I: Iterator<Item = (K, V)> + Clone,
+    K: Serialize<KF>,
+    V: Serialize<VF>,
+{
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        serialize_iter_to_slice!((KF, VF) : self.0 => sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        owned_iter_fast_sizes::<(KF, VF), _, _>(self.0.clone())
+    }
+}
+
 impl<F, T> Serialize<[F]> for core::ops::Range<T>
 where
     F: Formula,
@@ -723,14 +778,10 @@ where
     T: FromIterator<A>,
 {
     let mut err = None;
-    let value = core::iter::from_fn(|| match iter.next() {
-        None => None,
-        Some(Ok(elem)) => Some(elem),
-        Some(Err(e)) => {
-            err = Some(e);
-            None
-        }
-    })
+    let value = OkIter {
+        iter: &mut iter,
+        err: &mut err,
+    }
     .collect();
 
     match err {
@@ -752,14 +803,77 @@ pub fn deserialize_extend_iter<A, T>(
 where
     T: Extend<A>,
 {
-    let mut result = Ok(());
-    value.extend(core::iter::from_fn(|| match iter.next() {
-        None => None,
-        Some(Ok(elem)) => Some(elem),
-        Some(Err(err)) => {
-            result = Err(err);
-            None
+    let mut err = None;
+    value.extend(OkIter {
+        iter: &mut iter,
+        err: &mut err,
+    });
+
+    match err {
+        None => Ok(()),
+        Some(e) => Err(e),
+    }
+}
+
+/// Limit of bytes preallocated for deserialized collection.
+/// 1 MiB, or the whole address space on 16-bit platforms.
+const MAX_PREALLOC_BYTES: usize = usize::MAX >> usize::BITS.saturating_sub(20);
+
+/// Returns number of elements to preallocate for deserialized collection
+/// given lower bound of element count.
+///
+/// Count of zero-sized values is not bounded by the input,
+/// so malformed input could otherwise cause huge allocation.
+#[inline(always)]
+pub(crate) const fn cautious_capacity<T>(lower: usize) -> usize {
+    let size = core::mem::size_of::<T>();
+    if size == 0 {
+        lower
+    } else if lower > MAX_PREALLOC_BYTES / size {
+        MAX_PREALLOC_BYTES / size
+    } else {
+        lower
+    }
+}
+
+/// Iterator over successfully deserialized elements
+/// that stops at the first error and stores it.
+///
+/// Forwards size hint of the underlying iterator,
+/// which is exact for sized formulas,
+/// so collections can reserve capacity up front.
+/// Lower bound is limited with [`cautious_capacity`].
+struct OkIter<'a, I> {
+    iter: I,
+    err: &'a mut Option<DeserializeError>,
+}
+
+impl<A, I> Iterator for OkIter<'_, I>
+where
+    I: Iterator<Item = Result<A, DeserializeError>>,
+{
+    type Item = A;
+
+    #[inline(always)]
+    fn next(&mut self) -> Option<A> {
+        if self.err.is_some() {
+            return None;
         }
-    }));
-    result
+        match self.iter.next()? {
+            Ok(elem) => Some(elem),
+            Err(err) => {
+                *self.err = Some(err);
+                None
+            }
+        }
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.err.is_some() {
+            return (0, Some(0));
+        }
+        let (lower, upper) = self.iter.size_hint();
+        (cautious_capacity::<A>(lower), upper)
+    }
 }
@@ -0,0 +1,178 @@
+use std::marker::PhantomData;
+
+use crate::{
+    buffer::Buffer,
+    bytes::Bytes,
+    descriptor::{Descriptor, DescriptorKind, LazyDescriptor},
+    deserialize::{Deserialize, DeserializeError, Deserializer},
+    formula::{reference_size, Formula},
+    serialize::{write_reference, Serialize, Sizes},
+    size::FixedUsizeType,
+};
+
+/// A formula that can be used to serialize and deserialize data
+/// as JSON using [`serde_json`] crate.
+///
+/// Any type serializable with `serde` can be used with this formula.
+/// Serialized value is stored as UTF-8 JSON text in the heap,
+/// so it stays human-readable in hex dumps and captured payloads.
+///
+/// If type is not serializable with `serde_json` crate it will cause a panic.
+/// Deserializing non-compatible type will cause deserialization error.
+pub struct Json;
+
+impl Formula for Json {
+    const MAX_STACK_SIZE: Option<usize> = Some(reference_size::<Bytes>());
+    const EXACT_SIZE: bool = true;
+    const HEAPLESS: bool = false;
+
+    #[inline(always)]
+    fn describe() -> Descriptor {
+        Descriptor::new::<Json>(DescriptorKind::Ref(LazyDescriptor::of::<Bytes>()))
+    }
+}
+
+impl<T> Serialize<Json> for T
+where
+    T: serde::Serialize,
+{
+    #[inline]
+    fn serialize<B>(self, sizes: &mut Sizes, mut buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        let json = match serde_json::to_vec(&self) {
+            Ok(json) => json,
+            Err(err) => panic!("JSON serialization error: {}", err),
+        };
+
+        if FixedUsizeType::try_from(json.len()).is_err() {
+            panic!("JSON serialization uses more that `FixedUsizeType::MAX` bytes");
+        }
+
+        let size = json.len();
+        match buffer.reserve_heap(sizes.heap, sizes.stack, size) {
+            Err(err) => return Err(err),
+            Ok([]) => {} // Nothing to do.
+            Ok(bytes) => bytes[sizes.heap..][..size].copy_from_slice(&json),
+        }
+
+        sizes.heap += size;
+        write_reference::<Bytes, B>(size, sizes.heap, sizes.heap, sizes.stack, buffer)?;
+        sizes.stack += reference_size::<Bytes>();
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        None
+    }
+}
+
+impl<'de, T> Deserialize<'de, Json> for T
+where
+    T: serde::Deserialize<'de>,
+{
+    #[inline]
+    fn deserialize(de: Deserializer<'de>) -> Result<Self, DeserializeError>
+    where
+        Self: Sized,
+    {
+        let de = de.deref::<Bytes>()?;
+
+        match serde_json::from_slice(de.read_all_bytes()) {
+            Ok(value) => Ok(value),
+            Err(_err) => Err(DeserializeError::Incompatible),
+        }
+    }
+
+    #[inline]
+    fn deserialize_in_place(&mut self, de: Deserializer<'de>) -> Result<(), DeserializeError> {
+        let de = de.deref::<Bytes>()?;
+
+        let mut de = serde_json::Deserializer::from_slice(de.read_all_bytes());
+        match <T as serde::Deserialize<'de>>::deserialize_in_place(&mut de, self) {
+            Ok(()) => {}
+            Err(_err) => return Err(DeserializeError::Incompatible),
+        }
+        match de.end() {
+            Ok(()) => Ok(()),
+            Err(_err) => Err(DeserializeError::Incompatible),
+        }
+    }
+}
+
+/// A formula that can be used to serialize and deserialize data
+/// as JSON using [`serde_json`] crate.
+///
+/// Only one specified type can be used with this formula.
+/// This helps avoid accidental deserialization of wrong type.
+///
+/// If type is not serializable with `serde_json` crate it will cause a panic.
+/// Deserializing non-compatible type will cause deserialization error.
+pub struct Jsoned<T>(PhantomData<fn(&T) -> &T>);
+
+impl<T> Formula for Jsoned<T> {
+    const MAX_STACK_SIZE: Option<usize> = Some(reference_size::<Bytes>());
+    const EXACT_SIZE: bool = true;
+    const HEAPLESS: bool = false;
+
+    #[inline(always)]
+    fn describe() -> Descriptor {
+        Descriptor::new::<Self>(DescriptorKind::Ref(LazyDescriptor::of::<Bytes>()))
+    }
+}
+
+impl<T> Serialize<Jsoned<T>> for T
+where
+    T: serde::Serialize,
+{
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        <T as Serialize<Json>>::serialize(self, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        <T as Serialize<Json>>::size_hint(self)
+    }
+}
+
+impl<T> Serialize<Jsoned<T>> for &T
+where
+    T: serde::Serialize,
+{
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        <&T as Serialize<Json>>::serialize(self, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        <&T as Serialize<Json>>::size_hint(self)
+    }
+}
+
+impl<'de, T> Deserialize<'de, Jsoned<T>> for T
+where
+    T: serde::Deserialize<'de>,
+{
+    #[inline(always)]
+    fn deserialize(de: Deserializer<'de>) -> Result<Self, DeserializeError>
+    where
+        Self: Sized,
+    {
+        <T as Deserialize<'de, Json>>::deserialize(de)
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(&mut self, de: Deserializer<'de>) -> Result<(), DeserializeError> {
+        <T as Deserialize<'de, Json>>::deserialize_in_place(self, de)
+    }
+}
@@ -0,0 +1,230 @@
+//!
+//! This module provides layout tables of schemas for protocol documentation.
+//!
+
+use alloc::{format, string::String, vec::Vec};
+use core::fmt::{self, Write};
+
+use crate::{
+    private::VARIANT_SIZE,
+    schema::{Schema, SchemaKind},
+    size::SIZE_STACK,
+};
+
+impl Schema {
+    /// Renders stack layout of the schema as Markdown tables.
+    ///
+    /// The first table describes the root formula,
+    /// followed by a table for each structure and enum of the schema.
+    /// Each row is a field with its offset, size in bytes, formula
+    /// and notes about size prefixes, padding and heap references.
+    ///
+    /// Fields are stored in reverse order, the first field is at the end of the value.
+    /// Offsets count from the start of the value when its size is fixed,
+    /// and from its end, e.g. `end-8`, otherwise.
+    /// Variable-size fields are shown as `..end-N` where they end,
+    /// and fields stored before them have no fixed offset.
+    ///
+    /// # Panics
+    ///
+    /// Panics if schema refers to missing nodes.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use alkahest::*;
+    /// let layout = Schema::of::<(u32, [u8])>().to_layout();
+    /// assert!(layout.contains("| 0 | end-4 | 4 | `u32` | little-endian |"));
+    /// assert!(layout.contains("| 1 | ..end-4 | rest | `[u8]` |"));
+    /// ```
+    #[must_use]
+    pub fn to_layout(&self) -> String {
+        let mut out = String::new();
+        self.write_layout(&mut out)
+            .expect("Writing to string can't fail");
+        out
+    }
+
+    fn write_layout(&self, out: &mut String) -> fmt::Result {
+        let names = self.idl_names();
+
+        for (idx, node) in self.nodes.iter().enumerate() {
+            if idx > 0 && !matches!(node.kind, SchemaKind::Struct(_) | SchemaKind::Enum(_)) {
+                continue;
+            }
+            if idx > 0 {
+                out.push('\n');
+            }
+
+            write!(out, "### {}\n\n", names[idx])?;
+            match node.max_stack_size {
+                Some(size) if node.exact_size => write!(out, "Stack size: {size} bytes")?,
+                Some(size) => write!(out, "Stack size: up to {size} bytes")?,
+                None => out.push_str("Stack size: variable"),
+            }
+            if node.heapless {
+                out.push_str(", no heap.\n\n");
+            } else {
+                out.push_str(", with heap references.\n\n");
+            }
+
+            out.push_str("| Field | Offset | Size | Formula | Notes |\n");
+            out.push_str("|---|---|---|---|---|\n");
+
+            let total = match node.max_stack_size {
+                Some(size) if node.exact_size => Some(size),
+                _ => None,
+            };
+
+            match &node.kind {
+                SchemaKind::Struct(fields) => {
+                    let fields = fields
+                        .iter()
+                        .map(|field| (field.name.clone(), field.formula));
+                    self.layout_rows(out, &names, fields, total, 0)?;
+                }
+                SchemaKind::Tuple(elems) => {
+                    let elems = elems
+                        .iter()
+                        .enumerate()
+                        .map(|(idx, elem)| (format!("{idx}"), *elem));
+                    self.layout_rows(out, &names, elems, total, 0)?;
+                }
+                SchemaKind::Enum(variants) => {
+                    let tags = variants
+                        .iter()
+                        .map(|variant| format!("{} = {}", variant.name, variant.tag))
+                        .collect::<Vec<_>>();
+                    writeln!(
+                        out,
+                        "| tag | {} | {VARIANT_SIZE} | `u32` | variant tag: {} |",
+                        offset(total, Some(0), Some(VARIANT_SIZE)),
+                        tags.join(", ")
+                    )?;
+
+                    for variant in variants {
+                        let fields = variant.fields.iter().map(|field| {
+                            (format!("{}.{}", variant.name, field.name), field.formula)
+                        });
+                        self.layout_rows(out, &names, fields, total, VARIANT_SIZE)?;
+                    }
+                }
+                _ => {
+                    let value = core::iter::once((String::from("value"), idx));
+                    self.layout_rows(out, &names, value, total, 0)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes rows for fields of a value with `total` size,
+    /// when `tail` bytes at the end are occupied.
+    fn layout_rows(
+        &self,
+        out: &mut String,
+        names: &[String],
+        fields: impl ExactSizeIterator<Item = (String, usize)>,
+        total: Option<usize>,
+        tail: usize,
+    ) -> fmt::Result {
+        // Distance from the end of the value to the end of the next field.
+        let mut end = Some(tail);
+        let count = fields.len();
+
+        for (field_idx, (name, idx)) in fields.enumerate() {
+            let node = &self.nodes[idx];
+            let last = field_idx + 1 == count;
+
+            let mut formula = String::new();
+            self.write_idl_expr(&mut formula, names, idx)?;
+            let note = self.layout_note(names, idx);
+
+            match (node.max_stack_size, node.exact_size, last) {
+                (None, _, false) => {
+                    writeln!(
+                        out,
+                        "| {name} size | {} | {SIZE_STACK} | `u{}` | stack size of `{name}` |",
+                        offset(total, end, Some(SIZE_STACK)),
+                        SIZE_STACK * 8
+                    )?;
+                    end = end.map(|end| end + SIZE_STACK);
+                    writeln!(
+                        out,
+                        "| {name} | {} | variable | `{formula}` | {note} |",
+                        offset(total, end, None)
+                    )?;
+                    end = None;
+                }
+                (None, _, true) => {
+                    writeln!(
+                        out,
+                        "| {name} | {} | rest | `{formula}` | takes remaining stack; {note} |",
+                        offset(total, end, None)
+                    )?;
+                }
+                (Some(size), false, false) => {
+                    writeln!(
+                        out,
+                        "| {name} | {} | {size} | `{formula}` | padded to {size} bytes; {note} |",
+                        offset(total, end, Some(size))
+                    )?;
+                    end = end.map(|end| end + size);
+                }
+                (Some(size), false, true) => {
+                    writeln!(
+                        out,
+                        "| {name} | {} | up to {size} | `{formula}` | {note} |",
+                        offset(total, end, None)
+                    )?;
+                }
+                (Some(size), true, _) => {
+                    writeln!(
+                        out,
+                        "| {name} | {} | {size} | `{formula}` | {note} |",
+                        offset(total, end, Some(size))
+                    )?;
+                    end = end.map(|end| end + size);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns note about encoding of the formula.
+    fn layout_note(&self, names: &[String], idx: usize) -> String {
+        let node = &self.nodes[idx];
+        match &node.kind {
+            SchemaKind::Opaque => String::from("opaque"),
+            SchemaKind::Bool => String::from("`0` or `1`"),
+            SchemaKind::Unsigned(_) | SchemaKind::Signed(_) | SchemaKind::Float(_) => {
+                String::from("little-endian")
+            }
+            SchemaKind::Vlq => String::from("variable-length quantity"),
+            SchemaKind::Bytes => String::from("raw bytes"),
+            SchemaKind::Str => String::from("UTF-8 bytes"),
+            SchemaKind::Array(..) | SchemaKind::Slice(_) | SchemaKind::Tuple(_) => {
+                String::from("elements in reverse order")
+            }
+            SchemaKind::Ref(formula) if self.nodes[*formula].exact_size => {
+                format!("{SIZE_STACK}-byte heap address of the value")
+            }
+            SchemaKind::Ref(_) => {
+                format!("{SIZE_STACK}-byte heap address and {SIZE_STACK}-byte size of the value")
+            }
+            SchemaKind::Option(_) => String::from("presence byte at the end, value before it"),
+            SchemaKind::Struct(_) | SchemaKind::Enum(_) => format!("see `{}`", names[idx]),
+        }
+    }
+}
+
+/// Formats offset of a field that ends `end` bytes before the end of the value.
+fn offset(total: Option<usize>, end: Option<usize>, size: Option<usize>) -> String {
+    match (end, size, total) {
+        (None, _, _) => String::from("-"),
+        (Some(end), Some(size), Some(total)) => format!("{}", total - end - size),
+        (Some(end), Some(size), None) => format!("end-{}", end + size),
+        (Some(0), None, _) => String::from("..end"),
+        (Some(end), None, _) => format!("..end-{end}"),
+    }
+}
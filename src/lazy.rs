@@ -1,23 +1,39 @@
 use core::{
     any::type_name,
+    cell::OnceCell,
     fmt::{self, Debug},
     marker::PhantomData,
 };
 
 use crate::{
-    deserialize::{DeIter, Deserialize, DeserializeError, Deserializer, SizedDeIter},
+    bytes::Bytes,
+    deserialize::{DeIter, Deserialize, DeserializeError, Deserializer, ErrorDetails, SizedDeIter},
     formula::{unwrap_size, BareFormula, Formula},
+    reference::{inline_deref, InlineRef, Ref},
+    size::SIZE_STACK,
 };
 
 /// Wrapper for lazy deserialization.
 /// `Lazy<F>` may deserialize data from formula `F`.
 /// Then any it may produce any type `T` that can be deserialized from formula `F`.
-#[derive(Clone)]
 pub struct Lazy<'de, F: ?Sized> {
     de: Deserializer<'de>,
     marker: PhantomData<fn(&F) -> &F>,
 }
 
+impl<'de, F> Clone for Lazy<'de, F>
+where
+    F: ?Sized,
+{
+    #[inline(always)]
+    fn clone(&self) -> Self {
+        Lazy {
+            de: self.de.clone(),
+            marker: PhantomData,
+        }
+    }
+}
+
 impl<'de, F> Debug for Lazy<'de, F>
 where
     F: ?Sized,
@@ -57,6 +73,121 @@ where
     {
         <T as Deserialize<'de, F>>::deserialize_in_place(place, self.de.clone())
     }
+
+    /// Deserialize the lazy value and map it with the function.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError` if deserialization fails.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use alkahest::*;
+    /// let mut buffer = [0u8; 1024];
+    ///
+    /// let (size, root) = serialize::<[u32], _>([1u8, 2, 3], &mut buffer).unwrap();
+    /// let lazy = deserialize_with_size::<[u32], Lazy<[u32]>>(&buffer[..size], root).unwrap();
+    /// let sum = lazy.map(|vec: Vec<u32>| vec.iter().sum::<u32>()).unwrap();
+    /// assert_eq!(sum, 6);
+    /// ```
+    #[inline(always)]
+    pub fn map<T, U>(&self, f: impl FnOnce(T) -> U) -> Result<U, DeserializeError>
+    where
+        T: Deserialize<'de, F>,
+    {
+        Ok(f(self.get::<T>()?))
+    }
+
+    /// Deserialize the lazy value and map it with the fallible function.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if deserialization or the function fails.
+    #[inline(always)]
+    pub fn try_map<T, U, E>(&self, f: impl FnOnce(T) -> Result<U, E>) -> Result<U, E>
+    where
+        T: Deserialize<'de, F>,
+        E: From<DeserializeError>,
+    {
+        f(self.get::<T>()?)
+    }
+
+    /// Returns deserializer over the lazy value
+    /// to read it manually.
+    #[inline(always)]
+    pub fn deserializer(&self) -> Deserializer<'de> {
+        self.de.clone()
+    }
+
+    /// Converts lazy value into deserializer over it
+    /// to read it manually.
+    #[inline(always)]
+    pub fn into_deserializer(self) -> Deserializer<'de> {
+        self.de
+    }
+
+    /// Returns number of bytes the value occupies on the stack
+    /// without deserializing it.
+    /// Data behind references is not counted.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use alkahest::*;
+    /// let mut buffer = [0u8; 1024];
+    ///
+    /// let (size, root) = serialize::<[u32], _>([1u8, 2, 3], &mut buffer).unwrap();
+    /// let lazy = deserialize_with_size::<[u32], Lazy<[u32]>>(&buffer[..size], root).unwrap();
+    /// assert_eq!(lazy.byte_size(), 12);
+    /// ```
+    #[must_use]
+    #[inline(always)]
+    pub fn byte_size(&self) -> usize {
+        self.de.stack()
+    }
+}
+
+impl<'de> Lazy<'de, str> {
+    /// Returns length of the string in bytes without deserializing it.
+    #[must_use]
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.de.stack()
+    }
+
+    /// Returns `true` if the string is empty.
+    #[must_use]
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.de.stack() == 0
+    }
+}
+
+impl<'de> Lazy<'de, Bytes> {
+    /// Returns number of bytes without deserializing them.
+    #[must_use]
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.de.stack()
+    }
+
+    /// Returns `true` if there are no bytes.
+    #[must_use]
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.de.stack() == 0
+    }
+}
+
+impl<'de, F> From<Lazy<'de, F>> for Deserializer<'de>
+where
+    F: BareFormula + ?Sized,
+{
+    #[inline(always)]
+    fn from(lazy: Lazy<'de, F>) -> Self {
+        lazy.de
+    }
 }
 
 trait LazySizedIter<'de, F: ?Sized> {
@@ -90,6 +221,74 @@ impl<'de, F> Lazy<'de, [F]>
 where
     F: Formula,
 {
+    /// Returns number of elements in the slice without deserializing them.
+    ///
+    /// This is O(1) for element formulas with known `MAX_STACK_SIZE`.
+    /// Otherwise size prefixes of all elements are read.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use alkahest::*;
+    /// let mut buffer = [0u8; 1024];
+    ///
+    /// let (size, root) = serialize::<[u32], _>([1u8, 2, 3], &mut buffer).unwrap();
+    /// let lazy = deserialize_with_size::<[u32], Lazy<[u32]>>(&buffer[..size], root).unwrap();
+    /// assert_eq!(lazy.len(), 3);
+    /// ```
+    #[must_use]
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.de.count_values::<F>()
+    }
+
+    /// Returns `true` if the slice has no elements.
+    #[must_use]
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        match F::MAX_STACK_SIZE {
+            None => self.de.stack() < SIZE_STACK,
+            Some(_) => self.len() == 0,
+        }
+    }
+
+    /// Returns iterator over lazy sub-slices of at most `chunk_len` elements.
+    /// Chunks can be deserialized independently, e.g. in parallel.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use alkahest::*;
+    /// let mut buffer = [0u8; 1024];
+    ///
+    /// let (size, root) = serialize::<[u32], _>([1u8, 2, 3], &mut buffer).unwrap();
+    /// let lazy = deserialize_with_size::<[u32], Lazy<[u32]>>(&buffer[..size], root).unwrap();
+    /// let mut chunks = lazy.chunks(2);
+    /// let first = chunks.next().unwrap().unwrap();
+    /// let second = chunks.next().unwrap().unwrap();
+    /// assert!(chunks.next().is_none());
+    ///
+    /// assert_eq!(first.get::<Vec<u32>>().unwrap(), [1, 2]);
+    /// assert_eq!(second.get::<Vec<u32>>().unwrap(), [3]);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk_len` is zero or element formula is zero-sized.
+    #[inline(always)]
+    pub fn chunks(&self, chunk_len: usize) -> LazyChunks<'de, F> {
+        assert!(chunk_len > 0, "Chunk length must be non-zero");
+        assert!(
+            F::MAX_STACK_SIZE != Some(0),
+            "Slices of zero-sized formula cannot be split"
+        );
+        LazyChunks {
+            de: self.de.clone(),
+            chunk_len,
+            marker: PhantomData,
+        }
+    }
+
     /// Produce iterator over lazy deserialized values.
     /// # Example
     ///
@@ -165,6 +364,88 @@ where
     {
         self.de.clone().into_unsized_iter()
     }
+
+    /// Produce iterator over lazy elements of the slice.
+    /// Elements are not deserialized until requested.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use alkahest::*;
+    /// let mut buffer = [0u8; 1024];
+    ///
+    /// let (size, root) = serialize::<[[u32; 2]], _>([[1u32, 2], [3, 4]], &mut buffer).unwrap();
+    /// let lazy = deserialize_with_size::<[[u32; 2]], Lazy<[[u32; 2]]>>(&buffer[..size], root).unwrap();
+    /// let mut iter = lazy.lazy_iter();
+    /// let first = iter.next().unwrap().unwrap();
+    /// assert_eq!(first.get::<[u32; 2]>().unwrap(), [1, 2]);
+    ///
+    /// // Filter on a cheap field and decode only kept elements.
+    /// let (size, root) = serialize::<[(u8, Ref<str>)], _>([(1u8, "skip"), (2, "keep")], &mut buffer).unwrap();
+    /// let lazy = deserialize_with_size::<[(u8, Ref<str>)], Lazy<[(u8, Ref<str>)]>>(&buffer[..size], root).unwrap();
+    /// let kept = lazy
+    ///     .lazy_iter()
+    ///     .map(Result::unwrap)
+    ///     .filter(|elem| elem.get::<(u8, Skip)>().unwrap().0 == 2)
+    ///     .map(|elem| elem.get::<(u8, &str)>().unwrap().1)
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(kept, ["keep"]);
+    /// ```
+    #[inline(always)]
+    pub fn lazy_iter(&self) -> DeIter<'de, F, Lazy<'de, F>>
+    where
+        F: BareFormula,
+    {
+        self.de.clone().into_unsized_iter()
+    }
+
+    /// Deserializes elements into prefix of the provided slice
+    /// and returns number of elements written.
+    /// Never allocates, so it suits preallocated buffers.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use alkahest::*;
+    /// let mut buffer = [0u8; 1024];
+    ///
+    /// let (size, root) = serialize::<[u32], _>([1u8, 2, 3], &mut buffer).unwrap();
+    /// let lazy = deserialize_with_size::<[u32], Lazy<[u32]>>(&buffer[..size], root).unwrap();
+    ///
+    /// let mut out = [0u32; 4];
+    /// assert_eq!(lazy.read_into(&mut out).unwrap(), 3);
+    /// assert_eq!(out, [1, 2, 3, 0]);
+    ///
+    /// assert!(lazy.read_into(&mut [0u32; 2]).is_err());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError::WrongLength` if slice has more elements than `out`.
+    /// Returns other `DeserializeError` if element deserialization fails.
+    /// Elements before the failed one are already written.
+    #[inline]
+    pub fn read_into<T>(&self, out: &mut [T]) -> Result<usize, DeserializeError>
+    where
+        T: Deserialize<'de, F>,
+    {
+        let mut iter = self.iter::<T>();
+        for (count, slot) in out.iter_mut().enumerate() {
+            match iter.next() {
+                None => return Ok(count),
+                Some(value) => *slot = value?,
+            }
+        }
+
+        if iter.is_empty() {
+            Ok(out.len())
+        } else {
+            Err(DeserializeError::WrongLength(ErrorDetails::sizes(
+                out.len(),
+                self.len(),
+            )))
+        }
+    }
 }
 
 impl<'de, 'fe: 'de, F> Deserialize<'fe, F> for Lazy<'de, F>
@@ -185,3 +466,275 @@ where
         Ok(())
     }
 }
+
+/// Field formula that can be projected into [`Lazy`] value
+/// without deserializing sibling fields.
+///
+/// Bare formulas are projected as is,
+/// [`Ref<F>`](Ref) and [`InlineRef<F>`](InlineRef)
+/// are projected to `F` behind the reference.
+///
+/// Derived formulas for structs get projection methods
+/// like `project_name` for each field with projectable formula.
+/// For a struct `Foo` they are provided by generated `FooProjection` trait
+/// implemented for `Lazy<Foo>`.
+pub trait Project: Formula {
+    /// Formula of the projected lazy value.
+    type Target: BareFormula + ?Sized;
+
+    /// Converts deserializer of the field into deserializer of the target.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError` if reference is invalid.
+    fn project(de: Deserializer<'_>) -> Result<Deserializer<'_>, DeserializeError>;
+}
+
+impl<F> Project for F
+where
+    F: BareFormula + ?Sized,
+{
+    type Target = F;
+
+    #[inline(always)]
+    fn project(de: Deserializer<'_>) -> Result<Deserializer<'_>, DeserializeError> {
+        Ok(de)
+    }
+}
+
+impl<F> Project for Ref<F>
+where
+    F: BareFormula + ?Sized,
+{
+    type Target = F;
+
+    #[inline(always)]
+    fn project(de: Deserializer<'_>) -> Result<Deserializer<'_>, DeserializeError> {
+        de.deref::<F>()
+    }
+}
+
+impl<F> Project for InlineRef<F>
+where
+    F: BareFormula + ?Sized,
+{
+    type Target = F;
+
+    #[inline(always)]
+    fn project(de: Deserializer<'_>) -> Result<Deserializer<'_>, DeserializeError> {
+        inline_deref::<F>(de)
+    }
+}
+
+/// Reads field with formula `F` and projects it into lazy value.
+///
+/// # Errors
+///
+/// Returns `DeserializeError` if field is out of bounds or reference is invalid.
+#[inline(always)]
+pub fn project_field<'de, F>(
+    de: &mut Deserializer<'de>,
+    last: bool,
+) -> Result<Lazy<'de, F::Target>, DeserializeError>
+where
+    F: Project + ?Sized,
+{
+    let de = de
+        .read_field_with(F::MAX_STACK_SIZE, F::EXACT_SIZE, last)
+        .and_then(F::project)
+        .map_err(DeserializeError::with_formula::<F>)?;
+
+    Ok(Lazy {
+        de,
+        marker: PhantomData,
+    })
+}
+
+/// Skips non-last field with formula `F`.
+///
+/// # Errors
+///
+/// Returns `DeserializeError` if field is out of bounds.
+#[inline(always)]
+pub fn skip_field<F>(de: &mut Deserializer<'_>) -> Result<(), DeserializeError>
+where
+    F: Formula + ?Sized,
+{
+    de.read_field_with(F::MAX_STACK_SIZE, F::EXACT_SIZE, false)
+        .map(drop)
+        .map_err(DeserializeError::with_formula::<F>)
+}
+
+/// Wrapper for lazy deserialization that caches the value.
+/// `LazyCell<F, T>` deserializes `T` from formula `F` on first access
+/// and returns the cached value on subsequent accesses.
+///
+/// Errors are not cached, failed deserialization is repeated on next access.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// let mut buffer = [0u8; 1024];
+///
+/// let (size, root) = serialize::<[u32], _>([1u8, 2, 3], &mut buffer).unwrap();
+/// let cell = deserialize_with_size::<[u32], LazyCell<[u32], Vec<u32>>>(&buffer[..size], root).unwrap();
+/// assert!(cell.get_cached().is_none());
+/// assert_eq!(cell.get().unwrap(), &[1, 2, 3]);
+/// assert_eq!(cell.get_cached().unwrap(), &[1, 2, 3]);
+/// ```
+pub struct LazyCell<'de, F: ?Sized, T> {
+    lazy: Lazy<'de, F>,
+    value: OnceCell<T>,
+}
+
+impl<'de, F, T> Clone for LazyCell<'de, F, T>
+where
+    F: ?Sized,
+    T: Clone,
+{
+    #[inline(always)]
+    fn clone(&self) -> Self {
+        LazyCell {
+            lazy: self.lazy.clone(),
+            value: self.value.clone(),
+        }
+    }
+}
+
+impl<'de, F, T> Debug for LazyCell<'de, F, T>
+where
+    F: ?Sized,
+    T: Debug,
+{
+    #[inline(always)]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.value.get() {
+            None => write!(f, "LazyCell<{:?}>(<uninit>)", type_name::<F>()),
+            Some(value) => write!(f, "LazyCell<{:?}>({:?})", type_name::<F>(), value),
+        }
+    }
+}
+
+impl<'de, F, T> LazyCell<'de, F, T>
+where
+    F: BareFormula + ?Sized,
+    T: Deserialize<'de, F>,
+{
+    /// Returns the value, deserializing it on first access.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError` if deserialization fails.
+    #[inline]
+    pub fn get(&self) -> Result<&T, DeserializeError> {
+        if let Some(value) = self.value.get() {
+            return Ok(value);
+        }
+        let value = self.lazy.get::<T>()?;
+        Ok(self.value.get_or_init(|| value))
+    }
+
+    /// Returns mutable reference to the value, deserializing it on first access.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError` if deserialization fails.
+    #[inline]
+    pub fn get_mut(&mut self) -> Result<&mut T, DeserializeError> {
+        if self.value.get().is_none() {
+            let value = self.lazy.get::<T>()?;
+            let _ = self.value.set(value);
+        }
+        Ok(self.value.get_mut().expect("Value is initialized"))
+    }
+
+    /// Returns the value if it is already deserialized.
+    #[must_use]
+    #[inline(always)]
+    pub fn get_cached(&self) -> Option<&T> {
+        self.value.get()
+    }
+
+    /// Returns the value, deserializing it if it was not accessed yet.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError` if deserialization fails.
+    #[inline]
+    pub fn into_value(self) -> Result<T, DeserializeError> {
+        match self.value.into_inner() {
+            Some(value) => Ok(value),
+            None => self.lazy.get::<T>(),
+        }
+    }
+
+    /// Returns lazy value that is not cached.
+    #[must_use]
+    #[inline(always)]
+    pub fn lazy(&self) -> &Lazy<'de, F> {
+        &self.lazy
+    }
+}
+
+impl<'de, 'fe: 'de, F, T> Deserialize<'fe, F> for LazyCell<'de, F, T>
+where
+    F: BareFormula + ?Sized,
+{
+    #[inline(always)]
+    fn deserialize(de: Deserializer<'fe>) -> Result<Self, DeserializeError> {
+        Ok(LazyCell {
+            lazy: Lazy {
+                de,
+                marker: PhantomData,
+            },
+            value: OnceCell::new(),
+        })
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(&mut self, de: Deserializer<'fe>) -> Result<(), DeserializeError> {
+        self.lazy.de = de;
+        self.value = OnceCell::new();
+        Ok(())
+    }
+}
+
+/// Iterator over lazy sub-slices of `Lazy<[F]>`.
+/// See [`Lazy::chunks`].
+#[must_use]
+#[derive(Clone)]
+pub struct LazyChunks<'de, F> {
+    de: Deserializer<'de>,
+    chunk_len: usize,
+    marker: PhantomData<fn(&F) -> &F>,
+}
+
+impl<'de, F> Iterator for LazyChunks<'de, F>
+where
+    F: Formula,
+{
+    type Item = Result<Lazy<'de, [F]>, DeserializeError>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Result<Lazy<'de, [F]>, DeserializeError>> {
+        let empty = match F::MAX_STACK_SIZE {
+            None => self.de.stack() < SIZE_STACK,
+            Some(max_stack) => self.de.stack() < max_stack,
+        };
+        if empty {
+            return None;
+        }
+
+        match self.de.split_values::<F>(self.chunk_len) {
+            Ok(de) => Some(Ok(Lazy {
+                de,
+                marker: PhantomData,
+            })),
+            Err(err) => {
+                self.de = Deserializer::new_unchecked(0, &[]);
+                Some(Err(err))
+            }
+        }
+    }
+}
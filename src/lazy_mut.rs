@@ -0,0 +1,290 @@
+//!
+//! This module provides mutable views over serialized values of fixed size.
+//!
+
+use core::marker::PhantomData;
+
+use crate::{
+    deserialize::{Deserialize, DeserializeError, Deserializer, ErrorDetails},
+    formula::{sum_size, Formula},
+    odd_int::{U24, U40, U48},
+    packet::open_packet,
+    serialize::{write_field, Serialize, Sizes},
+};
+
+/// Formula of values that occupy fixed number of bytes without heap.
+///
+/// Values of such formulas can be overwritten in place
+/// through [`LazyMut`], since new value never changes the layout.
+///
+/// Implemented for primitives, arrays and tuples of fixed formulas.
+/// Derived formulas for structs implement it when all fields do.
+///
+/// Implement it only for formulas with known `MAX_STACK_SIZE`,
+/// `EXACT_SIZE` and `HEAPLESS`.
+pub trait Fixed: Formula {}
+
+/// Mutable view over serialized value of fixed formula `F`.
+///
+/// Allows reading and rewriting the value or its fields,
+/// e.g. bumping counters or rewriting ids in a cached buffer,
+/// without deserializing and serializing the whole packet.
+///
+/// Fields of tuples are accessed with `fields` method,
+/// elements of arrays with `at` method.
+/// Derived formulas for structs get `project_<field>` methods
+/// with generated `<Name>ProjectionMut` trait.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// let mut buffer = [0u8; 64];
+/// let size = write_packet::<(u64, u32), _>((42u64, 0u32), &mut buffer).unwrap();
+///
+/// let (mut view, _) = read_packet_mut::<(u64, u32)>(&mut buffer[..size]).unwrap();
+/// let (mut id, mut hits) = view.fields();
+/// id.set(7u64);
+/// hits.update(|n: u32| n + 1).unwrap();
+/// assert_eq!(view.get::<(u64, u32)>().unwrap(), (7, 1));
+/// ```
+pub struct LazyMut<'a, F: ?Sized> {
+    bytes: &'a mut [u8],
+    marker: PhantomData<fn(&F) -> &F>,
+}
+
+impl<'a, F> LazyMut<'a, F>
+where
+    F: Fixed + ?Sized,
+{
+    const SIZE: usize = match (F::MAX_STACK_SIZE, F::EXACT_SIZE, F::HEAPLESS) {
+        (Some(size), true, true) => size,
+        _ => panic!("Formula implements `Fixed` but has no fixed size"),
+    };
+
+    /// Creates view over stack bytes of the value.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError::WrongLength` if `bytes` length
+    /// does not match size of the formula.
+    #[inline(always)]
+    pub fn new(bytes: &'a mut [u8]) -> Result<Self, DeserializeError> {
+        if bytes.len() != Self::SIZE {
+            return Err(DeserializeError::WrongLength(ErrorDetails::sizes(
+                Self::SIZE,
+                bytes.len(),
+            ))
+            .with_formula::<F>());
+        }
+        Ok(LazyMut {
+            bytes,
+            marker: PhantomData,
+        })
+    }
+
+    /// Deserializes the value.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError` if deserialization fails.
+    #[inline(always)]
+    pub fn get<'de, T>(&'de self) -> Result<T, DeserializeError>
+    where
+        T: Deserialize<'de, F>,
+    {
+        <T as Deserialize<'de, F>>::deserialize(Deserializer::new_unchecked(Self::SIZE, self.bytes))
+    }
+
+    /// Overwrites the value in place.
+    #[inline(always)]
+    pub fn set<T>(&mut self, value: T)
+    where
+        T: Serialize<F>,
+    {
+        let mut sizes = Sizes::ZERO;
+        match write_field::<F, T, _>(value, &mut sizes, &mut *self.bytes, true) {
+            Ok(()) => {}
+            Err(never) => match never {},
+        }
+        debug_assert_eq!(sizes.stack, Self::SIZE);
+    }
+
+    /// Deserializes the value, maps it with the function and writes result back.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError` if deserialization fails.
+    /// The value is not changed in this case.
+    #[inline(always)]
+    pub fn update<T>(&mut self, f: impl FnOnce(T) -> T) -> Result<(), DeserializeError>
+    where
+        T: for<'de> Deserialize<'de, F> + Serialize<F>,
+    {
+        let value = self.get::<T>()?;
+        self.set(f(value));
+        Ok(())
+    }
+
+    /// Returns view with shorter lifetime.
+    #[inline(always)]
+    pub fn reborrow(&mut self) -> LazyMut<'_, F> {
+        LazyMut {
+            bytes: self.bytes,
+            marker: PhantomData,
+        }
+    }
+
+    /// Returns serialized bytes of the value.
+    #[must_use]
+    #[inline(always)]
+    pub fn as_bytes(&self) -> &[u8] {
+        self.bytes
+    }
+}
+
+impl<'a, F, const N: usize> LazyMut<'a, [F; N]>
+where
+    F: Fixed,
+{
+    /// Returns view over element of the array.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `idx` is out of bounds.
+    #[inline(always)]
+    pub fn at(&mut self, idx: usize) -> LazyMut<'_, F> {
+        assert!(idx < N, "Index out of bounds");
+        let size = LazyMut::<F>::SIZE;
+        fixed_field(self.bytes, idx * size, size)
+    }
+}
+
+/// Returns view over fixed field placed `offset` bytes after the first field.
+#[inline(always)]
+fn fixed_field<F>(bytes: &mut [u8], offset: usize, size: usize) -> LazyMut<'_, F>
+where
+    F: ?Sized,
+{
+    // First field is written first and occupies the end of the stack.
+    let end = bytes.len() - offset;
+    LazyMut {
+        bytes: &mut bytes[end - size..end],
+        marker: PhantomData,
+    }
+}
+
+/// Returns view over field of derived struct
+/// that follows fields with total size `offset`.
+#[inline(always)]
+pub fn project_fixed<'b, S, F>(
+    view: &'b mut LazyMut<'_, S>,
+    offset: Option<usize>,
+) -> LazyMut<'b, F>
+where
+    S: ?Sized,
+    F: Fixed + ?Sized,
+{
+    let Some(offset) = offset else {
+        unreachable!("Fields of fixed formula must be fixed");
+    };
+    fixed_field(view.bytes, offset, LazyMut::<F>::SIZE)
+}
+
+/// Reads packet with value of fixed formula from the input
+/// and returns mutable view over it
+/// and number of bytes the packet occupies.
+///
+/// # Errors
+///
+/// Returns `DeserializeError` if packet header is invalid.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// let mut buffer = [0u8; 64];
+/// let size = write_packet::<(u32, [u16; 2]), _>((1u32, [2u16, 3]), &mut buffer).unwrap();
+///
+/// let (mut view, _) = read_packet_mut::<(u32, [u16; 2])>(&mut buffer[..size]).unwrap();
+/// let (mut counter, mut pair) = view.fields();
+/// counter.update(|n: u32| n + 1).unwrap();
+/// pair.at(1).set(7u16);
+///
+/// let (value, _) = read_packet::<(u32, [u16; 2]), (u32, [u16; 2])>(&buffer[..size]).unwrap();
+/// assert_eq!(value, (2, [2, 7]));
+/// ```
+#[inline]
+pub fn read_packet_mut<F>(input: &mut [u8]) -> Result<(LazyMut<'_, F>, usize), DeserializeError>
+where
+    F: Fixed,
+{
+    let (de, address) = open_packet::<F>(input)?;
+    let stack = de.stack();
+    let view = LazyMut::new(&mut input[address - stack..address])?;
+    Ok((view, address))
+}
+
+macro_rules! impl_fixed {
+    ($($ty:ident)*) => {
+        $(impl Fixed for $ty {})*
+    };
+}
+
+impl_fixed!(u8 u16 u32 u64 u128 i8 i16 i32 i64 i128 f32 f64 bool char U24 U40 U48);
+
+impl<F, const N: usize> Fixed for [F; N] where F: Fixed {}
+
+macro_rules! fixed_tuple {
+    () => {
+        fixed_tuple!(@ AA AB AC AD AE AF AG AH AI AJ AK AL AM AN AO AP);
+    };
+    (@) => {};
+    (impl $($a:ident)+) => {
+        impl<$($a,)+> Fixed for ($($a,)+)
+        where
+            $($a: Fixed,)+
+        {
+        }
+
+        impl<'a, $($a,)+> LazyMut<'a, ($($a,)+)>
+        where
+            $($a: Fixed,)+
+        {
+            /// Returns views over fields of the tuple.
+            #[inline]
+            #[allow(non_snake_case, unused_assignments)]
+            pub fn fields(&mut self) -> ($(LazyMut<'_, $a>,)+) {
+                let mut rest = &mut *self.bytes;
+                $(
+                    let (head, $a) = rest.split_at_mut(rest.len() - LazyMut::<$a>::SIZE);
+                    rest = head;
+                    let $a = LazyMut {
+                        bytes: $a,
+                        marker: PhantomData,
+                    };
+                )+
+                ($($a,)+)
+            }
+        }
+    };
+    (@ $head:ident $($tail:ident)*) => {
+        fixed_tuple!(@ $($tail)*);
+        fixed_tuple!(impl $head $($tail)*);
+    };
+}
+
+fixed_tuple!();
+
+/// Returns size of fields that precede projected field.
+#[inline(always)]
+#[must_use]
+pub const fn fixed_offset(sizes: &[Option<usize>]) -> Option<usize> {
+    let mut offset = Some(0);
+    let mut idx = 0;
+    while idx < sizes.len() {
+        offset = sum_size(offset, sizes[idx]);
+        idx += 1;
+    }
+    offset
+}
@@ -7,7 +7,24 @@
 //! see [`advanced`] module.
 
 #![cfg_attr(not(feature = "std"), no_std)]
-#![forbid(unsafe_code)]
+#![cfg_attr(
+    not(any(
+        feature = "unchecked-utf8",
+        feature = "lock",
+        feature = "ffi",
+        feature = "memmap2"
+    )),
+    forbid(unsafe_code)
+)]
+#![cfg_attr(
+    any(
+        feature = "unchecked-utf8",
+        feature = "lock",
+        feature = "ffi",
+        feature = "memmap2"
+    ),
+    deny(unsafe_code)
+)]
 #![deny(missing_docs)]
 #![deny(
     clippy::correctness,
@@ -23,24 +40,47 @@ extern crate self as alkahest;
 #[cfg(feature = "alloc")]
 extern crate alloc;
 
+mod archive;
 mod array;
 mod r#as;
+mod ascii;
+mod bits;
+mod bounded;
 mod buffer;
 mod bytes;
+mod cached;
+mod convert;
+mod cstr;
+mod descriptor;
 mod deserialize;
+mod envelope;
+mod fingerprint;
+mod fixed_str;
 mod formula;
 mod iter;
 mod lazy;
+mod lazy_mut;
+mod macros;
+mod magic;
+mod odd_int;
 mod option;
 mod packet;
 mod primitive;
+mod raw;
+mod records;
 mod reference;
+mod self_formula;
 mod serialize;
 mod size;
 mod skip;
 mod slice;
+mod sorted;
+mod sparse;
 mod str;
+mod tail;
 mod tuple;
+mod versioned;
+mod visit;
 mod vlq;
 
 #[cfg(test)]
@@ -52,57 +92,265 @@ mod vec;
 #[cfg(feature = "alloc")]
 mod vec_deque;
 
+#[cfg(feature = "alloc")]
+mod indexed;
+
+#[cfg(feature = "alloc")]
+mod interned;
+
+#[cfg(feature = "alloc")]
+mod signed;
+
 #[cfg(feature = "alloc")]
 mod string;
 
+#[cfg(feature = "alloc")]
+mod schema;
+
+#[cfg(feature = "alloc")]
+mod bindings;
+
+#[cfg(feature = "alloc")]
+mod codegen;
+
+#[cfg(feature = "alloc")]
+mod compat;
+
+#[cfg(feature = "alloc")]
+mod diff;
+
+#[cfg(feature = "alloc")]
+mod export;
+
+#[cfg(feature = "alloc")]
+mod layout;
+
+#[cfg(feature = "alloc")]
+mod validate;
+
+#[cfg(feature = "alloc")]
+mod value;
+
+#[cfg(feature = "alloc")]
+mod owned;
+
+#[cfg(feature = "alloc")]
+mod decoder;
+
+#[cfg(feature = "alloc")]
+mod fragment;
+
+#[cfg(feature = "alloc")]
+mod rpc;
+
+#[cfg(feature = "std")]
+mod frame;
+
+#[cfg(feature = "std")]
+mod inspect;
+
 #[cfg(feature = "bincoded")]
 mod bincoded;
 
+#[cfg(feature = "serde")]
+mod serde_bridge;
+
+#[cfg(feature = "serde-json")]
+mod json;
+
+#[cfg(feature = "serde-postcard")]
+mod postcarded;
+
+#[cfg(feature = "protobuf")]
+mod protobuf;
+
+#[cfg(feature = "bytes")]
+mod buf;
+
+#[cfg(feature = "tokio-codec")]
+mod codec;
+
+#[cfg(feature = "embedded-io")]
+mod io;
+
+#[cfg(feature = "rayon")]
+mod par;
+
+#[cfg(feature = "arbitrary")]
+mod fuzz;
+
+#[cfg(feature = "testing")]
+mod harness;
+
+#[cfg(feature = "crypto")]
+mod encrypted;
+
+#[cfg(feature = "metrics")]
+mod metrics;
+
+#[cfg(feature = "lock")]
+mod lock;
+
+#[cfg(feature = "wasm")]
+mod wasm;
+
+#[cfg(feature = "ffi")]
+mod c_api;
+
 pub use crate::{
+    archive::Archive,
+    ascii::AsciiStr,
+    bounded::Bounded,
     buffer::BufferExhausted,
     bytes::Bytes,
+    cached::SizeCached,
+    convert::{DurationMillis, DurationNanos},
+    cstr::CStrFormula,
+    descriptor::{Descriptor, DescriptorKind, FieldDescriptor, LazyDescriptor, VariantDescriptor},
     deserialize::{
         deserialize, deserialize_in_place, deserialize_in_place_with_size, deserialize_with_size,
-        DeIter, Deserialize, DeserializeError,
+        DeIter, Deserialize, DeserializeError, ErrorDetails,
     },
-    formula::Formula,
-    iter::SerIter,
-    lazy::Lazy,
+    envelope::{Envelope, Enveloped, Opened, Tagged},
+    fingerprint::fingerprint,
+    fixed_str::FixedStr,
+    formula::{assert_bounded, assert_exact_size, assert_heapless, Formula},
+    iter::{SerIter, SerIterMap},
+    lazy::{Lazy, LazyCell, LazyChunks, Project},
+    lazy_mut::{read_packet_mut, Fixed, LazyMut},
+    magic::{read_packet_with_header, write_packet_with_header, FormatHeader, HeaderError},
+    odd_int::{U24, U40, U48},
     packet::{
-        packet_size, read_packet, read_packet_in_place, read_packet_size, write_packet,
-        write_packet_into, write_packet_unchecked,
+        max_packet_size, packet_size, peek_header, read_packet, read_packet_in_place, write_packet,
+        write_packet_into, write_packet_unchecked, PacketHeader, PacketSummary,
+        WIRE_FORMAT_VERSION,
     },
     r#as::As,
-    reference::Ref,
+    raw::{deserialize_raw, serialize_raw},
+    records::{RecordIter, Records, SkipCorrupt},
+    reference::{InlineRef, Ref},
+    self_formula::{DeserializeSelf, SerializeSelf},
     serialize::{
-        serialize, serialize_or_size, serialize_unchecked, serialized_size, BufferSizeRequired,
-        Serialize, SerializeRef,
+        max_size, serialize, serialize_or_size, serialize_unchecked, serialized_size,
+        serialized_size_exact, size_hint, BufferSizeRequired, Serialize, SerializeRef, Sizes,
     },
+    size::PACKET_SIZE_LIMIT,
     skip::Skip,
+    sorted::{SortedMap, SortedMapView, SortedSet, SortedSetView},
+    sparse::Sparse,
+    tail::Tail,
+    versioned::{Migrate, OldVersion, Versioned},
+    visit::{for_each, visit_packet, SeqFormula, Sink},
     vlq::Vlq,
 };
 
 #[cfg(feature = "alloc")]
-pub use crate::{packet::write_packet_to_vec, serialize::serialize_to_vec};
+pub use crate::{
+    codegen::{generate_rust, SchemaParseError},
+    compat::{
+        check_compatibility, Compatibility, CompatibilityReport, SchemaChange, SchemaChangeKind,
+    },
+    decoder::{Decoder, DecoderError, Progress, Resumable},
+    diff::{diff, eq, ValueDiff},
+    envelope::Registry,
+    fragment::{fragment_packet, FragmentError, Fragments, Reassembler},
+    indexed::{Indexed, IndexedIter},
+    interned::{Intern, Interned, Interner},
+    magic::write_packet_with_header_to_vec,
+    owned::Packet,
+    packet::{
+        read_described_packet, read_described_packet_value, read_packet_canonical,
+        write_described_packet_to_vec, write_packet_to_vec,
+    },
+    records::RecordWriter,
+    rpc::{read_response, write_request, Client, Method, Router, RpcError, Transport},
+    schema::{Schema, SchemaField, SchemaKind, SchemaNode, SchemaVariant},
+    serialize::serialize_to_vec,
+    signed::{InvalidSignature, Sign, Signed, Signer, Unverified, Verifier},
+    validate::{read_packet_validated, validate_packet, SkipValidated},
+    value::{deserialize_value, read_packet_value, Value},
+};
+
+#[cfg(feature = "std")]
+pub use crate::{
+    convert::{UnixMillis, UnixNanos, UnixSeconds},
+    frame::{FrameError, FrameReader, FrameWriter},
+    inspect::{inspect, inspect_packet},
+};
+
+#[allow(deprecated)]
+pub use crate::packet::read_packet_size;
+
+#[cfg(feature = "unchecked-utf8")]
+pub use crate::packet::read_packet_unchecked_utf8;
 
 #[cfg(feature = "derive")]
 pub use alkahest_proc::{alkahest, Deserialize, Formula, Serialize, SerializeRef};
 
+#[cfg(feature = "crypto")]
+pub use crate::encrypted::{AuthenticationFailed, Cipher, Encrypt, Encrypted, Sealed};
+
+#[cfg(feature = "metrics")]
+pub use crate::metrics::Metrics;
+
+#[cfg(feature = "lock")]
+pub use crate::lock::{check_formula_lock, formula_lock, LockError, UPDATE_LOCK_ENV};
+
+#[cfg(feature = "wasm")]
+pub use crate::wasm::{read_packet_from_array, write_packet_to_array};
+
+#[cfg(feature = "bytes")]
+pub use buf::{read_packet_from_buf, write_packet_to_buf};
+
+#[cfg(feature = "tokio-codec")]
+pub use codec::{AlkahestCodec, CodecError};
+
+#[cfg(feature = "embedded-io")]
+pub use io::{read_packet_from_io, write_packet_to_io, IoError};
+
+#[cfg(feature = "embedded-io-async")]
+pub use io::{read_packet_from_io_async, write_packet_to_io_async};
+
+#[cfg(feature = "rayon")]
+pub use par::ParSlice;
+
+#[cfg(feature = "arbitrary")]
+pub use fuzz::fuzz_deserialize;
+
+#[cfg(all(feature = "arbitrary", feature = "alloc"))]
+pub use fuzz::{check_roundtrip, fuzz_roundtrip};
+
 #[cfg(feature = "bincoded")]
-pub use bincoded::{Bincode, Bincoded};
+pub use bincoded::{
+    Bincode, BincodeAllowTrailing, BincodeBigEndian, BincodeDefault, BincodeFixint, BincodeLegacy,
+    BincodeLimit, BincodeOptions, Bincoded,
+};
+
+#[cfg(feature = "serde-json")]
+pub use json::{Json, Jsoned};
+
+#[cfg(feature = "serde-postcard")]
+pub use postcarded::{Postcard, Postcarded};
+
+#[cfg(feature = "protobuf")]
+pub use protobuf::{packet_to_protobuf, protobuf_to_packet, ProtobufError};
+
+#[cfg(feature = "serde")]
+pub use serde_bridge::{serde_to_value, Serde, SerdeDeserializer, SerdeError};
 
 /// This module contains types and functions for manual implementations of
 /// `Serialize` and `Deserialize` traits.
 pub mod advanced {
     pub use crate::{
-        buffer::{Buffer, CheckedFixedBuffer, MaybeFixedBuffer},
-        deserialize::Deserializer,
+        bits::BitField,
+        buffer::{Buffer, CheckedFixedBuffer, DynBuffer, MaybeFixedBuffer},
+        deserialize::{read_reference, try_read_reference, Deserializer},
         formula::{reference_size, BareFormula},
         iter::{default_iter_fast_sizes, deserialize_extend_iter, deserialize_from_iter},
         serialize::{
-            field_size_hint, formula_fast_sizes, slice_writer, write_array, write_bytes,
-            write_exact_size_field, write_field, write_ref, write_reference, write_slice, Sizes,
-            SliceWriter,
+            field_size_hint, field_size_hint_unknown, formula_fast_sizes, slice_writer,
+            struct_writer, variant_writer, write_array, write_bytes, write_exact_size_field,
+            write_field, write_ref, write_reference, write_slice, Sizes, SliceWriter, StructWriter,
         },
         size::{FixedIsizeType, FixedUsizeType},
     };
@@ -111,20 +359,59 @@ pub mod advanced {
     pub use crate::buffer::VecBuffer;
 }
 
+/// This module contains helpers and `proptest` strategies
+/// to test implementations of `Formula`, `Serialize` and `Deserialize`.
+#[cfg(feature = "testing")]
+pub mod testing {
+    pub use crate::harness::{
+        assert_no_panic, assert_roundtrip, assert_size_hint_consistent, corrupted_packet,
+        fixed_isize, fixed_usize, packet, vlq,
+    };
+}
+
+/// This module contains C functions to write and read packets
+/// with messages registered by tag, for C and C++ processes.
+#[cfg(feature = "ffi")]
+pub mod ffi {
+    pub use crate::c_api::{
+        alkahest_packet_tag, alkahest_read_packet, alkahest_value_size, alkahest_write_packet,
+        register_ffi, ALKAHEST_BUFFER_TOO_SMALL, ALKAHEST_DESERIALIZE_FAILED,
+        ALKAHEST_NULL_POINTER, ALKAHEST_OK, ALKAHEST_UNKNOWN_TAG, ALKAHEST_WRONG_TAG, FFI_HEADER,
+    };
+}
+
 /// Private module for macros to use.
 /// Changes here are not considered breaking.
 #[doc(hidden)]
 pub mod private {
     pub use {
         bool,
-        core::{convert::Into, debug_assert_eq, option::Option, result::Result},
+        core::{
+            concat, convert::Into, debug_assert_eq, module_path, option::Option, result::Result,
+            stringify,
+        },
         u32, u8, usize,
     };
 
+    #[cfg(feature = "lock")]
+    pub use {
+        crate::{
+            fingerprint::fingerprint,
+            lock::{FormulaEntry, FORMULAS},
+        },
+        linkme,
+    };
+
     pub use crate::{
+        bits::{pack, packed_size, read_packed, unpack, write_packed, MAX_BITS},
         buffer::Buffer,
-        deserialize::{Deserialize, DeserializeError, Deserializer},
+        descriptor::{
+            Descriptor, DescriptorKind, FieldDescriptor, LazyDescriptor, VariantDescriptor,
+        },
+        deserialize::{Deserialize, DeserializeError, Deserializer, ErrorDetails},
         formula::{max_size, sum_size, BareFormula, Formula},
+        lazy::{project_field, skip_field, Lazy, Project},
+        lazy_mut::{fixed_offset, project_fixed, Fixed, LazyMut},
         serialize::{
             formula_fast_sizes, write_exact_size_field, write_field, Serialize, SerializeRef, Sizes,
         },
@@ -185,6 +472,11 @@ pub mod private {
             de.read_in_place::<F, T>(place, last)
         }
 
+        #[inline(always)]
+        pub const fn size_hint_unknown(&self, last: bool) -> bool {
+            crate::serialize::field_size_hint_unknown::<F>(last)
+        }
+
         #[inline(always)]
         pub fn size_hint<T>(self, value: &T, last: bool) -> Option<Sizes>
         where
@@ -0,0 +1,146 @@
+//!
+//! This module provides lock file of formula fingerprints.
+//!
+
+use alloc::{collections::BTreeSet, format, string::String, vec::Vec};
+use std::{error::Error, fmt, io, path::Path};
+
+/// Environment variable that makes [`check_formula_lock`] rewrite the lock file.
+pub const UPDATE_LOCK_ENV: &str = "ALKAHEST_UPDATE_LOCK";
+
+const LOCK_HEADER: &str = "# Fingerprints of alkahest formulas. Generated, do not edit.\n";
+
+/// Formula registered with [`register_formula!`](crate::register_formula).
+#[doc(hidden)]
+pub struct FormulaEntry {
+    pub name: &'static str,
+    pub fingerprint: fn() -> u64,
+}
+
+// `link_section` placement of the registry counts as unsafe code.
+#[doc(hidden)]
+#[allow(unsafe_code)]
+#[linkme::distributed_slice]
+pub static FORMULAS: [FormulaEntry];
+
+/// Returns lock file content for all registered formulas.
+///
+/// Each line holds path of the formula and its [`fingerprint`](crate::fingerprint).
+/// Lines are sorted, so the file is stable between builds.
+#[must_use]
+pub fn formula_lock() -> String {
+    let entries: BTreeSet<String> = FORMULAS
+        .iter()
+        .map(|entry| format!("{} {:016x}", entry.name, (entry.fingerprint)()))
+        .collect();
+
+    let mut lock = String::from(LOCK_HEADER);
+    for entry in entries {
+        lock.push_str(&entry);
+        lock.push('\n');
+    }
+    lock
+}
+
+/// Checks that fingerprints of registered formulas match the lock file.
+///
+/// Formulas derived with `Formula` derive are registered automatically,
+/// except generic ones, which are registered with
+/// [`register_formula!`](crate::register_formula) for each instantiation.
+///
+/// Lock file is written if it does not exist
+/// or [`UPDATE_LOCK_ENV`] environment variable is set.
+/// Check the lock file in and call this function from a test,
+/// so that changes of the wire format show up in code review.
+///
+/// # Errors
+///
+/// Returns `LockError::Mismatch` if fingerprints differ from the lock file.
+/// Returns `LockError::Io` if lock file can't be read or written.
+///
+/// # Example
+///
+/// ```no_run
+/// #[test]
+/// fn wire_format_is_locked() {
+///     alkahest::check_formula_lock("alkahest.lock").unwrap();
+/// }
+/// ```
+pub fn check_formula_lock(path: impl AsRef<Path>) -> Result<(), LockError> {
+    let path = path.as_ref();
+    let current = formula_lock();
+
+    let update = std::env::var_os(UPDATE_LOCK_ENV).is_some();
+    let locked = match std::fs::read_to_string(path) {
+        Ok(locked) if !update => locked,
+        Err(err) if !update && err.kind() != io::ErrorKind::NotFound => {
+            return Err(LockError::Io(err));
+        }
+        _ => return std::fs::write(path, current).map_err(LockError::Io),
+    };
+
+    let locked: BTreeSet<&str> = lock_entries(&locked).collect();
+    let current: BTreeSet<&str> = lock_entries(&current).collect();
+
+    let removed: Vec<String> = locked.difference(&current).map(|&e| e.into()).collect();
+    let added: Vec<String> = current.difference(&locked).map(|&e| e.into()).collect();
+
+    if removed.is_empty() && added.is_empty() {
+        Ok(())
+    } else {
+        Err(LockError::Mismatch { removed, added })
+    }
+}
+
+fn lock_entries(lock: &str) -> impl Iterator<Item = &str> {
+    lock.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+}
+
+/// Error of [`check_formula_lock`].
+#[derive(Debug)]
+pub enum LockError {
+    /// Lock file can't be read or written.
+    Io(io::Error),
+
+    /// Fingerprints differ from the lock file.
+    Mismatch {
+        /// Lock file entries that no longer match any formula.
+        removed: Vec<String>,
+
+        /// Entries of formulas that are new or changed.
+        added: Vec<String>,
+    },
+}
+
+impl fmt::Display for LockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LockError::Io(err) => write!(f, "failed to access formula lock file: {err}"),
+            LockError::Mismatch { removed, added } => {
+                writeln!(
+                    f,
+                    "formula fingerprints differ from lock file, \
+                    set {UPDATE_LOCK_ENV} to update it if the change is intended"
+                )?;
+                for entry in removed {
+                    writeln!(f, "- {entry}")?;
+                }
+                for entry in added {
+                    writeln!(f, "+ {entry}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Error for LockError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LockError::Io(err) => Some(err),
+            LockError::Mismatch { .. } => None,
+        }
+    }
+}
@@ -0,0 +1,371 @@
+/// Declares formula for structural data inline, without named type.
+///
+/// Expands to tuple of field formulas in declaration order,
+/// which has the same layout as derived formula of a struct with these fields.
+/// Field names are for documentation only,
+/// so descriptor and fingerprint of the formula do not carry them.
+/// Only the last field may have formula that is not `Sized`.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// type Record = formula!{ { id: u64, name: Ref<str>, tags: [u32] } };
+///
+/// let mut buffer = Vec::new();
+/// let size = write_packet_to_vec::<Record, _>((1u64, "one", [2u32, 3]), &mut buffer);
+/// let (value, _) = read_packet::<Record, (u64, &str, Vec<u32>)>(&buffer[..size]).unwrap();
+/// assert_eq!(value, (1, "one", vec![2, 3]));
+/// ```
+#[macro_export]
+macro_rules! formula {
+    ({ $($field:ident : $formula:ty),* $(,)? }) => {
+        ($($formula,)*)
+    };
+    ($($field:ident : $formula:ty),* $(,)?) => {
+        ($($formula,)*)
+    };
+}
+
+/// Defines formula type that serializes values of a type
+/// by converting them into representation type first.
+///
+/// Representation type must be its own formula, e.g. primitive or array of primitives.
+/// `into` converts reference to the value into representation,
+/// `from` converts representation back or returns `DeserializeError`.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// #[derive(Clone, Copy, Debug, PartialEq)]
+/// enum Color {
+///     Red = 1,
+///     Green = 2,
+/// }
+///
+/// define_as! {
+///     /// Serializes `Color` as its discriminant.
+///     pub struct ColorRepr(Color as u8) {
+///         into = |color| *color as u8;
+///         from = |tag| match tag {
+///             1 => Ok(Color::Red),
+///             2 => Ok(Color::Green),
+///             _ => Err(DeserializeError::WrongVariant(tag.into(), ErrorDetails::new())),
+///         };
+///     }
+/// }
+///
+/// let mut buffer = [0u8; 2];
+/// serialize::<[ColorRepr; 2], _>([Color::Green, Color::Red], &mut buffer).unwrap();
+/// assert_eq!(deserialize::<[u8; 2], [u8; 2]>(&buffer).unwrap(), [2, 1]);
+///
+/// let colors = deserialize::<[ColorRepr; 2], [Color; 2]>(&buffer).unwrap();
+/// assert_eq!(colors, [Color::Green, Color::Red]);
+/// assert!(deserialize::<ColorRepr, Color>(&[3]).is_err());
+/// ```
+#[macro_export]
+macro_rules! define_as {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident($ty:ty as $repr:ty) {
+            into = $into:expr;
+            from = $from:expr;
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name;
+
+        impl $name {
+            #[inline(always)]
+            fn alkahest_into(value: &$ty) -> $repr {
+                let into: fn(&$ty) -> $repr = $into;
+                into(value)
+            }
+
+            #[inline(always)]
+            fn alkahest_from(repr: $repr) -> ::core::result::Result<$ty, $crate::DeserializeError> {
+                let from: fn($repr) -> ::core::result::Result<$ty, $crate::DeserializeError> = $from;
+                from(repr)
+            }
+        }
+
+        impl $crate::Formula for $name {
+            const MAX_STACK_SIZE: ::core::option::Option<usize> =
+                <$repr as $crate::Formula>::MAX_STACK_SIZE;
+            const EXACT_SIZE: bool = <$repr as $crate::Formula>::EXACT_SIZE;
+            const HEAPLESS: bool = <$repr as $crate::Formula>::HEAPLESS;
+
+            #[inline(always)]
+            fn describe() -> $crate::Descriptor {
+                <$repr as $crate::Formula>::describe()
+            }
+        }
+
+        impl $crate::advanced::BareFormula for $name {}
+
+        impl $crate::Serialize<$name> for $ty {
+            #[inline(always)]
+            fn serialize<B>(
+                self,
+                sizes: &mut $crate::advanced::Sizes,
+                buffer: B,
+            ) -> ::core::result::Result<(), B::Error>
+            where
+                B: $crate::advanced::Buffer,
+            {
+                <$repr as $crate::Serialize<$repr>>::serialize($name::alkahest_into(&self), sizes, buffer)
+            }
+
+            #[inline(always)]
+            fn size_hint(&self) -> ::core::option::Option<$crate::advanced::Sizes> {
+                <$repr as $crate::Serialize<$repr>>::size_hint(&$name::alkahest_into(self))
+            }
+        }
+
+        impl $crate::Serialize<$name> for &$ty {
+            #[inline(always)]
+            fn serialize<B>(
+                self,
+                sizes: &mut $crate::advanced::Sizes,
+                buffer: B,
+            ) -> ::core::result::Result<(), B::Error>
+            where
+                B: $crate::advanced::Buffer,
+            {
+                <$repr as $crate::Serialize<$repr>>::serialize($name::alkahest_into(self), sizes, buffer)
+            }
+
+            #[inline(always)]
+            fn size_hint(&self) -> ::core::option::Option<$crate::advanced::Sizes> {
+                <$repr as $crate::Serialize<$repr>>::size_hint(&$name::alkahest_into(self))
+            }
+        }
+
+        impl<'de> $crate::Deserialize<'de, $name> for $ty {
+            #[inline(always)]
+            fn deserialize(
+                de: $crate::advanced::Deserializer<'de>,
+            ) -> ::core::result::Result<Self, $crate::DeserializeError> {
+                let repr = <$repr as $crate::Deserialize<'de, $repr>>::deserialize(de)?;
+                $name::alkahest_from(repr)
+            }
+
+            #[inline(always)]
+            fn deserialize_in_place(
+                &mut self,
+                de: $crate::advanced::Deserializer<'de>,
+            ) -> ::core::result::Result<(), $crate::DeserializeError> {
+                *self = <$ty as $crate::Deserialize<'de, $name>>::deserialize(de)?;
+                Ok(())
+            }
+        }
+    };
+}
+
+/// Defines struct which fields are packed into shared bitfield
+/// and implements formula for it.
+///
+/// Each field is declared with `#[bits(N)]` attribute that sets its width.
+/// Fields may be `bool`, unsigned integers
+/// or any other type that implements [`BitField`](crate::advanced::BitField).
+/// Fields are packed starting from the least significant bit
+/// of little-endian integer in declaration order,
+/// which is serialized as the minimal number of bytes.
+/// Total width may not exceed 128 bits.
+///
+/// Serialization panics if field value does not fit into its width.
+/// Deserialization fails with `DeserializeError::NonCanonical`
+/// if unused bits of the last byte are set.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// define_bits! {
+///     #[derive(Clone, Copy, Debug, PartialEq)]
+///     pub struct Header {
+///         #[bits(3)]
+///         pub version: u8,
+///         #[bits(1)]
+///         pub urgent: bool,
+///         #[bits(1)]
+///         pub ack: bool,
+///         #[bits(11)]
+///         pub len: u16,
+///     }
+/// }
+///
+/// assert_eq!(<Header as Formula>::MAX_STACK_SIZE, Some(2));
+///
+/// let header = Header { version: 5, urgent: true, ack: false, len: 1000 };
+/// let mut buffer = [0u8; 2];
+/// serialize::<Header, _>(header, &mut buffer).unwrap();
+/// assert_eq!(deserialize::<Header, Header>(&buffer).unwrap(), header);
+/// ```
+#[macro_export]
+macro_rules! define_bits {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $(
+                #[bits($width:literal)]
+                $(#[$field_meta:meta])*
+                $field_vis:vis $field:ident : $ty:ty
+            ),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name {
+            $(
+                $(#[$field_meta])*
+                $field_vis $field: $ty,
+            )*
+        }
+
+        impl $name {
+            const ALKAHEST_BITS: u32 = 0 $(+ $width)*;
+        }
+
+        const _: () = {
+            ::core::assert!(
+                $name::ALKAHEST_BITS <= $crate::private::MAX_BITS,
+                ::core::concat!("Bit-packed struct `", ::core::stringify!($name), "` exceeds 128 bits"),
+            );
+            $(
+                ::core::assert!(
+                    $width > 0 && $width <= <$ty as $crate::advanced::BitField>::MAX_WIDTH,
+                    ::core::concat!("Width of field `", ::core::stringify!($field), "` is out of range of its type"),
+                );
+            )*
+        };
+
+        impl $crate::Formula for $name {
+            const MAX_STACK_SIZE: ::core::option::Option<usize> =
+                ::core::option::Option::Some($crate::private::packed_size($name::ALKAHEST_BITS));
+            const EXACT_SIZE: bool = true;
+            const HEAPLESS: bool = true;
+        }
+
+        impl $crate::advanced::BareFormula for $name {}
+
+        impl $crate::Serialize<$name> for &$name {
+            #[inline(always)]
+            fn serialize<B>(
+                self,
+                sizes: &mut $crate::advanced::Sizes,
+                buffer: B,
+            ) -> ::core::result::Result<(), B::Error>
+            where
+                B: $crate::advanced::Buffer,
+            {
+                let mut acc = 0u128;
+                let mut shift = 0u32;
+                $(
+                    $crate::private::pack(&mut acc, &mut shift, self.$field, $width, ::core::stringify!($field));
+                )*
+                $crate::private::write_packed(acc, $name::ALKAHEST_BITS, sizes, buffer)
+            }
+
+            #[inline(always)]
+            fn size_hint(&self) -> ::core::option::Option<$crate::advanced::Sizes> {
+                ::core::option::Option::Some($crate::advanced::Sizes::with_stack(
+                    $crate::private::packed_size($name::ALKAHEST_BITS),
+                ))
+            }
+        }
+
+        impl $crate::Serialize<$name> for $name {
+            #[inline(always)]
+            fn serialize<B>(
+                self,
+                sizes: &mut $crate::advanced::Sizes,
+                buffer: B,
+            ) -> ::core::result::Result<(), B::Error>
+            where
+                B: $crate::advanced::Buffer,
+            {
+                <&$name as $crate::Serialize<$name>>::serialize(&self, sizes, buffer)
+            }
+
+            #[inline(always)]
+            fn size_hint(&self) -> ::core::option::Option<$crate::advanced::Sizes> {
+                <&$name as $crate::Serialize<$name>>::size_hint(&self)
+            }
+        }
+
+        impl<'de> $crate::Deserialize<'de, $name> for $name {
+            #[inline(always)]
+            fn deserialize(
+                de: $crate::advanced::Deserializer<'de>,
+            ) -> ::core::result::Result<Self, $crate::DeserializeError> {
+                let acc = $crate::private::read_packed(de, $name::ALKAHEST_BITS)?;
+                let mut shift = 0u32;
+                ::core::result::Result::Ok($name {
+                    $(
+                        $field: $crate::private::unpack(acc, &mut shift, $width),
+                    )*
+                })
+            }
+
+            #[inline(always)]
+            fn deserialize_in_place(
+                &mut self,
+                de: $crate::advanced::Deserializer<'de>,
+            ) -> ::core::result::Result<(), $crate::DeserializeError> {
+                *self = <$name as $crate::Deserialize<'de, $name>>::deserialize(de)?;
+                ::core::result::Result::Ok(())
+            }
+        }
+    };
+}
+
+/// Registers formula for [`check_formula_lock`](crate::check_formula_lock).
+///
+/// `Formula` derive registers non-generic formulas automatically.
+/// Use this macro for generic formulas and manual implementations.
+/// Does nothing without "lock" feature.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// struct Id<T>(T);
+/// # impl<T> Formula for Id<T> {
+/// #     const MAX_STACK_SIZE: Option<usize> = Some(8);
+/// #     const EXACT_SIZE: bool = true;
+/// #     const HEAPLESS: bool = true;
+/// # }
+///
+/// register_formula!(Id<u64>);
+/// ```
+#[cfg(feature = "lock")]
+#[macro_export]
+macro_rules! register_formula {
+    ($formula:ty) => {
+        const _: () = {
+            #[$crate::private::linkme::distributed_slice($crate::private::FORMULAS)]
+            #[linkme(crate = $crate::private::linkme)]
+            #[allow(unsafe_code)]
+            static ENTRY: $crate::private::FormulaEntry = $crate::private::FormulaEntry {
+                name: $crate::private::concat!(
+                    $crate::private::module_path!(),
+                    "::",
+                    $crate::private::stringify!($formula)
+                ),
+                fingerprint: $crate::private::fingerprint::<$formula>,
+            };
+        };
+    };
+}
+
+/// Registers formula for `check_formula_lock`.
+///
+/// `Formula` derive registers non-generic formulas automatically.
+/// Use this macro for generic formulas and manual implementations.
+/// Does nothing without "lock" feature.
+#[cfg(not(feature = "lock"))]
+#[macro_export]
+macro_rules! register_formula {
+    ($formula:ty) => {};
+}
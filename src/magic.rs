@@ -0,0 +1,284 @@
+//!
+//! This module provides format header written ahead of packets
+//! to check that input is in expected format.
+//!
+
+use core::fmt;
+
+use crate::{
+    buffer::BufferExhausted,
+    deserialize::{Deserialize, DeserializeError},
+    fingerprint::fingerprint,
+    formula::Formula,
+    packet::{read_packet, write_packet, WIRE_FORMAT_VERSION},
+    serialize::Serialize,
+};
+
+/// Header that identifies format of the packet that follows it.
+///
+/// Consists of application-chosen magic number,
+/// [`WIRE_FORMAT_VERSION`] and [`fingerprint`] of the packet formula.
+/// Encoded in 16 bytes: magic number as is,
+/// then version and fingerprint as little-endian integers.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// const MAGIC: [u8; 4] = *b"DEMO";
+///
+/// let mut buffer = [0u8; 64];
+/// let size = write_packet_with_header::<[u16], _>(MAGIC, [1u16, 2], &mut buffer).unwrap();
+///
+/// let (value, _) = read_packet_with_header::<[u16], Vec<u16>>(MAGIC, &buffer[..size]).unwrap();
+/// assert_eq!(value, [1, 2]);
+///
+/// let err = read_packet_with_header::<[u16], Vec<u16>>(*b"OTHR", &buffer[..size]).unwrap_err();
+/// assert!(matches!(err, HeaderError::MagicMismatch { .. }));
+///
+/// let err = read_packet_with_header::<[u32], Vec<u32>>(MAGIC, &buffer[..size]).unwrap_err();
+/// assert!(matches!(err, HeaderError::FingerprintMismatch { .. }));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FormatHeader {
+    /// Application-chosen magic number.
+    pub magic: [u8; 4],
+
+    /// Wire format version.
+    pub version: u32,
+
+    /// Fingerprint of the packet formula.
+    pub fingerprint: u64,
+}
+
+impl FormatHeader {
+    /// Number of bytes the header occupies.
+    pub const SIZE: usize = 16;
+
+    /// Returns header for packets of formula `F` written by this version of the crate.
+    #[must_use]
+    #[inline]
+    pub fn of<F>(magic: [u8; 4]) -> Self
+    where
+        F: Formula + ?Sized,
+    {
+        FormatHeader {
+            magic,
+            version: WIRE_FORMAT_VERSION,
+            fingerprint: fingerprint::<F>(),
+        }
+    }
+
+    /// Encodes the header into bytes.
+    #[must_use]
+    #[inline]
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0; Self::SIZE];
+        bytes[..4].copy_from_slice(&self.magic);
+        bytes[4..8].copy_from_slice(&self.version.to_le_bytes());
+        bytes[8..].copy_from_slice(&self.fingerprint.to_le_bytes());
+        bytes
+    }
+
+    /// Decodes the header from the start of the input.
+    ///
+    /// # Errors
+    ///
+    /// Returns `HeaderError::Truncated` if input is shorter than the header.
+    #[inline]
+    pub fn from_bytes(input: &[u8]) -> Result<Self, HeaderError> {
+        let Some(bytes) = input.first_chunk::<{ Self::SIZE }>() else {
+            return Err(HeaderError::Truncated(input.len()));
+        };
+
+        let mut magic = [0; 4];
+        let mut version = [0; 4];
+        let mut fingerprint = [0; 8];
+        magic.copy_from_slice(&bytes[..4]);
+        version.copy_from_slice(&bytes[4..8]);
+        fingerprint.copy_from_slice(&bytes[8..]);
+
+        Ok(FormatHeader {
+            magic,
+            version: u32::from_le_bytes(version),
+            fingerprint: u64::from_le_bytes(fingerprint),
+        })
+    }
+
+    /// Checks that this header matches the expected one.
+    /// Fields are compared in order, first mismatch is reported.
+    ///
+    /// # Errors
+    ///
+    /// Returns `HeaderError` describing the first mismatching field.
+    #[inline]
+    pub fn check(&self, expected: &FormatHeader) -> Result<(), HeaderError> {
+        if self.magic != expected.magic {
+            return Err(HeaderError::MagicMismatch {
+                expected: expected.magic,
+                found: self.magic,
+            });
+        }
+        if self.version != expected.version {
+            return Err(HeaderError::VersionMismatch {
+                expected: expected.version,
+                found: self.version,
+            });
+        }
+        if self.fingerprint != expected.fingerprint {
+            return Err(HeaderError::FingerprintMismatch {
+                expected: expected.fingerprint,
+                found: self.fingerprint,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Error of reading packet with [`FormatHeader`].
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum HeaderError {
+    /// Input is shorter than the header.
+    /// Contains the input length.
+    Truncated(usize),
+
+    /// Magic number differs, input is not in the expected format.
+    MagicMismatch {
+        /// Expected magic number.
+        expected: [u8; 4],
+        /// Magic number found in the input.
+        found: [u8; 4],
+    },
+
+    /// Packet was written with different wire format version.
+    VersionMismatch {
+        /// Expected version.
+        expected: u32,
+        /// Version found in the input.
+        found: u32,
+    },
+
+    /// Packet was written with different formula.
+    FingerprintMismatch {
+        /// Fingerprint of expected formula.
+        expected: u64,
+        /// Fingerprint found in the input.
+        found: u64,
+    },
+
+    /// Packet failed to deserialize.
+    Deserialize(DeserializeError),
+}
+
+impl From<DeserializeError> for HeaderError {
+    #[inline(always)]
+    fn from(err: DeserializeError) -> Self {
+        HeaderError::Deserialize(err)
+    }
+}
+
+impl fmt::Display for HeaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HeaderError::Truncated(len) => {
+                write!(f, "input of {len} bytes is shorter than format header")
+            }
+            HeaderError::MagicMismatch { expected, found } => {
+                write!(
+                    f,
+                    "magic number {found:02x?} differs from expected {expected:02x?}"
+                )
+            }
+            HeaderError::VersionMismatch { expected, found } => {
+                write!(
+                    f,
+                    "wire format version {found} differs from expected {expected}"
+                )
+            }
+            HeaderError::FingerprintMismatch { expected, found } => {
+                write!(
+                    f,
+                    "formula fingerprint {found:#018x} differs from expected {expected:#018x}"
+                )
+            }
+            HeaderError::Deserialize(err) => write!(f, "deserialization error: {err}"),
+        }
+    }
+}
+
+/// Writes [`FormatHeader`] for formula `F` followed by packet with the value.
+/// Returns the number of bytes written.
+///
+/// # Errors
+///
+/// Returns `BufferExhausted` if the buffer is too small.
+#[inline]
+pub fn write_packet_with_header<F, T>(
+    magic: [u8; 4],
+    value: T,
+    output: &mut [u8],
+) -> Result<usize, BufferExhausted>
+where
+    F: Formula + ?Sized,
+    T: Serialize<F>,
+{
+    if output.len() < FormatHeader::SIZE {
+        return Err(BufferExhausted);
+    }
+    let (header, payload) = output.split_at_mut(FormatHeader::SIZE);
+    header.copy_from_slice(&FormatHeader::of::<F>(magic).to_bytes());
+
+    let size = write_packet::<F, T>(value, payload)?;
+    Ok(FormatHeader::SIZE + size)
+}
+
+/// Writes [`FormatHeader`] for formula `F` followed by packet with the value
+/// into byte vector.
+/// Returns the number of bytes written.
+///
+/// # Panics
+///
+/// Panics if serialized data exceeds [`PACKET_SIZE_LIMIT`](crate::PACKET_SIZE_LIMIT).
+#[cfg(feature = "alloc")]
+#[inline]
+pub fn write_packet_with_header_to_vec<F, T>(
+    magic: [u8; 4],
+    value: T,
+    output: &mut alloc::vec::Vec<u8>,
+) -> usize
+where
+    F: Formula + ?Sized,
+    T: Serialize<F>,
+{
+    let size = crate::packet::write_packet_to_vec::<F, T>(value, output);
+    output.truncate(size);
+    output.splice(0..0, FormatHeader::of::<F>(magic).to_bytes());
+    FormatHeader::SIZE + size
+}
+
+/// Reads packet written by [`write_packet_with_header`].
+/// Checks that the header has expected magic number,
+/// current [`WIRE_FORMAT_VERSION`] and fingerprint of formula `F`.
+/// Returns deserialized value and number of bytes consumed, including the header.
+///
+/// # Errors
+///
+/// Returns `HeaderError` describing mismatching header field
+/// or `HeaderError::Deserialize` if deserialization fails.
+#[inline]
+pub fn read_packet_with_header<'de, F, T>(
+    magic: [u8; 4],
+    input: &'de [u8],
+) -> Result<(T, usize), HeaderError>
+where
+    F: Formula + ?Sized,
+    T: Deserialize<'de, F>,
+{
+    let header = FormatHeader::from_bytes(input)?;
+    header.check(&FormatHeader::of::<F>(magic))?;
+
+    let (value, size) = read_packet::<F, T>(&input[FormatHeader::SIZE..])?;
+    Ok((value, FormatHeader::SIZE + size))
+}
@@ -0,0 +1,51 @@
+//!
+//! This module provides serialization statistics for buffers that collect them.
+//!
+
+/// Statistics of serialization calls.
+///
+/// Collected by buffers that return it from [`Buffer::metrics`](crate::advanced::Buffer::metrics),
+/// e.g. [`VecBuffer::with_metrics`](crate::advanced::VecBuffer::with_metrics).
+/// Counters accumulate over all calls that use the buffer,
+/// use fresh value to get stats of a single call.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::{*, advanced::*};
+/// let mut metrics = Metrics::default();
+/// let mut output = Vec::new();
+///
+/// let iter = ["a", "bc", "def"].into_iter().filter(|s| s.len() > 1);
+/// let buffer = VecBuffer::with_metrics(&mut output, &mut metrics);
+/// write_packet_into::<[Ref<str>], _, _>(SerIter(iter), buffer).unwrap();
+///
+/// assert_eq!(metrics.heap, 5);
+/// assert_eq!(metrics.size_hint_misses, 1);
+/// assert!(metrics.reallocations > 0);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Metrics {
+    /// Bytes of root values.
+    pub stack: usize,
+
+    /// Bytes of values placed outside of root values,
+    /// e.g. referenced by `Ref` formula.
+    pub heap: usize,
+
+    /// Number of values serialized without size hint,
+    /// which costs an additional copy of their bytes.
+    pub size_hint_misses: usize,
+
+    /// Number of times the buffer reallocated its storage.
+    pub reallocations: usize,
+}
+
+impl Metrics {
+    /// Records root value of serialization call.
+    #[inline(always)]
+    pub(crate) fn record_root(&mut self, stack: usize, heap: usize) {
+        self.stack += stack;
+        self.heap += heap;
+    }
+}
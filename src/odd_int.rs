@@ -0,0 +1,161 @@
+//!
+//! This module provides unsigned integer formulas of odd widths.
+//!
+
+use crate::{
+    buffer::Buffer,
+    descriptor::{Descriptor, DescriptorKind},
+    deserialize::{Deserialize, DeserializeError, Deserializer},
+    formula::{BareFormula, Formula},
+    serialize::{write_bytes, Serialize, SerializeRef, Sizes},
+};
+
+macro_rules! impl_odd_int {
+    ($(
+        $(#[$meta:meta])*
+        $name:ident($size:literal as $repr:ident) from [$($from:ident)*];
+    )*) => {$(
+        $(#[$meta])*
+        pub struct $name;
+
+        impl $name {
+            /// Maximum value representable by this formula.
+            pub const MAX: $repr = (1 << ($size * 8)) - 1;
+        }
+
+        impl Formula for $name {
+            const MAX_STACK_SIZE: Option<usize> = Some($size);
+            const EXACT_SIZE: bool = true;
+            const HEAPLESS: bool = true;
+
+            #[inline(always)]
+            fn describe() -> Descriptor {
+                Descriptor::new::<$name>(DescriptorKind::Unsigned($size))
+            }
+        }
+
+        impl BareFormula for $name {}
+
+        impl SerializeRef<$name> for $repr {
+            /// # Panics
+            ///
+            #[doc = concat!("Panics if value exceeds [`", stringify!($name), "::MAX`].")]
+            #[inline(always)]
+            fn serialize<B>(&self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+            where
+                B: Buffer,
+            {
+                assert!(
+                    *self <= $name::MAX,
+                    concat!("Value {} is out of range of `", stringify!($name), "`"),
+                    self
+                );
+                write_bytes(&self.to_le_bytes()[..$size], sizes, buffer)
+            }
+
+            #[inline(always)]
+            fn size_hint(&self) -> Option<Sizes> {
+                Some(Sizes::with_stack($size))
+            }
+        }
+
+        impl Serialize<$name> for $repr {
+            #[inline(always)]
+            fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+            where
+                B: Buffer,
+            {
+                <$repr as SerializeRef<$name>>::serialize(&self, sizes, buffer)
+            }
+
+            #[inline(always)]
+            fn size_hint(&self) -> Option<Sizes> {
+                Some(Sizes::with_stack($size))
+            }
+        }
+
+        $(
+            impl Serialize<$name> for $from {
+                #[inline(always)]
+                fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+                where
+                    B: Buffer,
+                {
+                    write_bytes(&<$repr>::from(self).to_le_bytes()[..$size], sizes, buffer)
+                }
+
+                #[inline(always)]
+                fn size_hint(&self) -> Option<Sizes> {
+                    Some(Sizes::with_stack($size))
+                }
+            }
+
+            impl SerializeRef<$name> for $from {
+                #[inline(always)]
+                fn serialize<B>(&self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+                where
+                    B: Buffer,
+                {
+                    <$from as Serialize<$name>>::serialize(*self, sizes, buffer)
+                }
+
+                #[inline(always)]
+                fn size_hint(&self) -> Option<Sizes> {
+                    Some(Sizes::with_stack($size))
+                }
+            }
+        )*
+
+        impl<T> Deserialize<'_, $name> for T
+        where
+            T: From<$repr>,
+        {
+            #[inline(always)]
+            fn deserialize(mut de: Deserializer) -> Result<Self, DeserializeError> {
+                let input = de.read_byte_array::<$size>()?;
+                let mut bytes = [0; core::mem::size_of::<$repr>()];
+                bytes[..$size].copy_from_slice(&input);
+                Ok(T::from(<$repr>::from_le_bytes(bytes)))
+            }
+
+            #[inline(always)]
+            fn deserialize_in_place(&mut self, de: Deserializer) -> Result<(), DeserializeError> {
+                *self = <T as Deserialize<$name>>::deserialize(de)?;
+                Ok(())
+            }
+        }
+    )*};
+}
+
+impl_odd_int! {
+    /// Formula for 24-bit unsigned integer encoded in exactly 3 little-endian bytes.
+    ///
+    /// Serializable from `u8`, `u16` and `u32`,
+    /// the latter panics if value exceeds [`U24::MAX`].
+    /// Deserializable into any type that implements `From<u32>`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use alkahest::*;
+    /// let mut buffer = [0u8; 3];
+    /// serialize::<U24, _>(0x123456u32, &mut buffer).unwrap();
+    /// assert_eq!(buffer, [0x56, 0x34, 0x12]);
+    /// assert_eq!(deserialize::<U24, u32>(&buffer).unwrap(), 0x123456);
+    /// ```
+    U24(3 as u32) from [u8 u16];
+
+    /// Formula for 40-bit unsigned integer encoded in exactly 5 little-endian bytes.
+    ///
+    /// Serializable from `u8`, `u16`, `u32` and `u64`,
+    /// the latter panics if value exceeds [`U40::MAX`].
+    /// Deserializable into any type that implements `From<u64>`.
+    U40(5 as u64) from [u8 u16 u32];
+
+    /// Formula for 48-bit unsigned integer encoded in exactly 6 little-endian bytes.
+    ///
+    /// Serializable from `u8`, `u16`, `u32` and `u64`,
+    /// the latter panics if value exceeds [`U48::MAX`].
+    /// Deserializable into any type that implements `From<u64>`.
+    U48(6 as u64) from [u8 u16 u32];
+}
@@ -1,5 +1,6 @@
 use crate::{
     buffer::Buffer,
+    descriptor::{Descriptor, DescriptorKind, LazyDescriptor},
     deserialize::{Deserialize, DeserializeError, Deserializer},
     formula::{sum_size, BareFormula, Formula},
     serialize::{field_size_hint, write_bytes, write_field, Serialize, SerializeRef, Sizes},
@@ -12,6 +13,11 @@ where
     const MAX_STACK_SIZE: Option<usize> = sum_size(Some(1), F::MAX_STACK_SIZE);
     const EXACT_SIZE: bool = matches!(F::MAX_STACK_SIZE, Some(0));
     const HEAPLESS: bool = F::HEAPLESS;
+
+    #[inline(always)]
+    fn describe() -> Descriptor {
+        Descriptor::new::<Self>(DescriptorKind::Option(LazyDescriptor::of::<F>()))
+    }
 }
 
 impl<F> BareFormula for Option<F> where F: Formula {}
@@ -38,10 +44,7 @@ where
     #[inline(always)]
     fn size_hint(&self) -> Option<Sizes> {
         match self {
-            None => {
-                let stack = <Option<F>>::MAX_STACK_SIZE?;
-                Some(Sizes::with_stack(stack))
-            }
+            None => Some(Sizes::with_stack(1)),
             Some(value) => {
                 let mut sizes = field_size_hint::<F>(value, true)?;
                 sizes.add_stack(1);
@@ -73,10 +76,7 @@ where
     #[inline(always)]
     fn size_hint(&self) -> Option<Sizes> {
         match self {
-            None => {
-                let stack = <Option<F>>::MAX_STACK_SIZE?;
-                Some(Sizes::with_stack(stack))
-            }
+            None => Some(Sizes::with_stack(1)),
             Some(value) => {
                 let mut sizes = field_size_hint::<F>(&value, true)?;
                 sizes.add_stack(1);
@@ -93,8 +93,7 @@ where
 {
     #[inline(always)]
     fn deserialize(mut de: Deserializer<'de>) -> Result<Self, DeserializeError> {
-        let is_some: u8 = de.read_byte()?;
-        if is_some == 0 {
+        if !de.read_flag()? {
             Ok(None)
         } else {
             Ok(Some(de.read_value::<F, T>(true)?))
@@ -103,8 +102,7 @@ where
 
     #[inline(always)]
     fn deserialize_in_place(&mut self, mut de: Deserializer<'de>) -> Result<(), DeserializeError> {
-        let is_some: u8 = de.read_byte()?;
-        if is_some == 0 {
+        if !de.read_flag()? {
             *self = None;
         } else {
             match self {
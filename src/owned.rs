@@ -0,0 +1,265 @@
+use alloc::vec::Vec;
+use core::{
+    any::type_name,
+    fmt::{self, Debug},
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+};
+
+use crate::{
+    deserialize::{Deserialize, DeserializeError},
+    formula::{BareFormula, Formula},
+    lazy::Lazy,
+    packet::{open_packet, read_packet, read_packet_in_place, write_packet_to_vec},
+    serialize::Serialize,
+    validate::validate_packet,
+};
+
+/// Packet with value of formula `F` that owns its bytes.
+///
+/// Value is deserialized on access, so packet can be passed around
+/// and stored without decoding it first.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// let packet = Packet::<[As<str>]>::new(["a", "bc"]);
+///
+/// let lazy = packet.lazy().unwrap();
+/// assert_eq!(lazy.len(), 2);
+/// assert_eq!(packet.get::<Vec<&str>>().unwrap(), ["a", "bc"]);
+///
+/// let copy = Packet::<[As<str>]>::from_bytes(packet.bytes().to_vec()).unwrap();
+/// assert_eq!(copy, packet);
+/// ```
+pub struct Packet<F: ?Sized> {
+    bytes: Vec<u8>,
+    marker: PhantomData<fn(&F) -> &F>,
+}
+
+impl<F> Packet<F>
+where
+    F: Formula + ?Sized,
+{
+    /// Writes packet with the value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if serialized data exceeds [`PACKET_SIZE_LIMIT`](crate::PACKET_SIZE_LIMIT).
+    #[must_use]
+    #[inline]
+    pub fn new<T>(value: T) -> Self
+    where
+        T: Serialize<F>,
+    {
+        let mut bytes = Vec::new();
+        let size = write_packet_to_vec::<F, T>(value, &mut bytes);
+        bytes.truncate(size);
+
+        Packet {
+            bytes,
+            marker: PhantomData,
+        }
+    }
+
+    /// Wraps bytes of a packet.
+    /// Only packet header is checked,
+    /// the value may still fail to deserialize.
+    /// Bytes past the end of the packet are dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError` if packet header is malformed.
+    #[inline]
+    pub fn from_bytes(mut bytes: Vec<u8>) -> Result<Self, DeserializeError> {
+        let (_, size) = open_packet::<F>(&bytes)?;
+        bytes.truncate(size);
+
+        Ok(Packet {
+            bytes,
+            marker: PhantomData,
+        })
+    }
+
+    /// Wraps bytes of a packet after checking it with [`validate_packet`].
+    /// Bytes past the end of the packet are dropped.
+    ///
+    /// Use for untrusted input.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError` if the packet fails validation.
+    #[inline]
+    pub fn from_bytes_validated(mut bytes: Vec<u8>) -> Result<Self, DeserializeError> {
+        let size = validate_packet::<F>(&bytes)?;
+        bytes.truncate(size);
+
+        Ok(Packet {
+            bytes,
+            marker: PhantomData,
+        })
+    }
+
+    /// Returns bytes of the packet.
+    #[must_use]
+    #[inline(always)]
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Unwraps bytes of the packet.
+    #[must_use]
+    #[inline(always)]
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    /// Returns number of bytes in the packet.
+    #[must_use]
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// Returns `true` if the packet has no bytes.
+    /// Only packets of zero-sized formulas are empty.
+    #[must_use]
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// Deserializes the value.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError` if deserialization fails.
+    #[inline]
+    pub fn get<'de, T>(&'de self) -> Result<T, DeserializeError>
+    where
+        T: Deserialize<'de, F>,
+    {
+        let (value, _) = read_packet::<F, T>(&self.bytes)?;
+        Ok(value)
+    }
+
+    /// Deserializes the value in-place.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError` if deserialization fails.
+    #[inline]
+    pub fn get_in_place<'de, T>(&'de self, place: &mut T) -> Result<(), DeserializeError>
+    where
+        T: Deserialize<'de, F> + ?Sized,
+    {
+        read_packet_in_place::<F, T>(place, &self.bytes)?;
+        Ok(())
+    }
+
+    /// Returns lazy value that borrows the packet.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError` if packet header is malformed.
+    #[inline]
+    pub fn lazy(&self) -> Result<Lazy<'_, F>, DeserializeError>
+    where
+        F: BareFormula,
+    {
+        self.get::<Lazy<F>>()
+    }
+
+    /// Replaces the value in the packet, reusing its buffer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if serialized data exceeds [`PACKET_SIZE_LIMIT`](crate::PACKET_SIZE_LIMIT).
+    #[inline]
+    pub fn set<T>(&mut self, value: T)
+    where
+        T: Serialize<F>,
+    {
+        self.bytes.clear();
+        let size = write_packet_to_vec::<F, T>(value, &mut self.bytes);
+        self.bytes.truncate(size);
+    }
+
+    /// Deserializes the value as `T` and writes it into new packet with formula `G`.
+    ///
+    /// Use to convert packets between formulas or to compact
+    /// packets that were not written by serialization.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError` if deserialization fails.
+    #[inline]
+    pub fn reserialize<'de, G, T>(&'de self) -> Result<Packet<G>, DeserializeError>
+    where
+        G: Formula + ?Sized,
+        T: Deserialize<'de, F> + Serialize<G>,
+    {
+        Ok(Packet::new(self.get::<T>()?))
+    }
+}
+
+impl<F> Clone for Packet<F>
+where
+    F: ?Sized,
+{
+    #[inline(always)]
+    fn clone(&self) -> Self {
+        Packet {
+            bytes: self.bytes.clone(),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<F> Debug for Packet<F>
+where
+    F: ?Sized,
+{
+    #[inline(always)]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Packet<{:?}>({} bytes)",
+            type_name::<F>(),
+            self.bytes.len()
+        )
+    }
+}
+
+impl<F> PartialEq for Packet<F>
+where
+    F: ?Sized,
+{
+    #[inline(always)]
+    fn eq(&self, other: &Self) -> bool {
+        self.bytes == other.bytes
+    }
+}
+
+impl<F> Eq for Packet<F> where F: ?Sized {}
+
+impl<F> Hash for Packet<F>
+where
+    F: ?Sized,
+{
+    #[inline(always)]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.bytes.hash(state);
+    }
+}
+
+impl<F> AsRef<[u8]> for Packet<F>
+where
+    F: ?Sized,
+{
+    #[inline(always)]
+    fn as_ref(&self) -> &[u8] {
+        &self.bytes
+    }
+}
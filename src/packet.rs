@@ -1,12 +1,63 @@
 use crate::{
     advanced::FixedUsizeType,
-    buffer::{Buffer, BufferExhausted, CheckedFixedBuffer, DryBuffer, VecBuffer},
-    deserialize::{read_reference, Deserialize, DeserializeError, Deserializer},
+    buffer::{Buffer, BufferExhausted, CheckedFixedBuffer, DryBuffer},
+    deserialize::{try_read_reference, Deserialize, DeserializeError, Deserializer, ErrorDetails},
+    fingerprint::fingerprint,
     formula::{reference_size, Formula},
     serialize::{write_ref, write_reference, Serialize, Sizes},
     size::SIZE_STACK,
 };
 
+#[cfg(feature = "alloc")]
+use crate::{
+    buffer::VecBuffer,
+    schema::Schema,
+    value::{read_packet_value, Value},
+};
+
+/// Version of the wire format.
+///
+/// Incremented whenever serialized layout of any built-in formula changes,
+/// so peers can check that they are able to read each other's packets.
+/// Layout also depends on the "fixed*" feature that sets size of `usize`.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// let peer_version = 1;
+/// assert_eq!(WIRE_FORMAT_VERSION, peer_version);
+/// ```
+pub const WIRE_FORMAT_VERSION: u32 = 1;
+
+/// Returns maximum size of packet with any value of formula `F`.
+/// Can be used to size buffers at compile time.
+/// Packet header size is returned by [`reference_size`](crate::advanced::reference_size).
+///
+/// # Panics
+///
+/// Panics if the size is not bounded, see [`Formula::MAX_PACKET_SIZE`].
+/// In const context this is a compile-time error.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// let mut buffer = [0u8; max_packet_size::<(u32, [u16; 2])>()];
+/// write_packet::<(u32, [u16; 2]), _>((1u32, [2u16, 3]), &mut buffer).unwrap();
+/// ```
+#[must_use]
+#[inline(always)]
+pub const fn max_packet_size<F>() -> usize
+where
+    F: Formula + ?Sized,
+{
+    match F::MAX_PACKET_SIZE {
+        Some(size) => size,
+        None => panic!("Formula size is not bounded"),
+    }
+}
+
 /// Returns the number of bytes required to write packet with the value.
 /// Note that value is consumed.
 ///
@@ -34,6 +85,10 @@ where
     T: Serialize<F>,
     B: Buffer,
 {
+    #[cfg(feature = "tracing")]
+    let _span =
+        tracing::debug_span!("write_packet", formula = core::any::type_name::<F>(),).entered();
+
     let reference_size = reference_size::<F>();
     buffer.reserve_heap(0, 0, reference_size)?;
 
@@ -51,6 +106,11 @@ where
         }
     }
 
+    #[cfg(feature = "metrics")]
+    if let Some(metrics) = buffer.metrics() {
+        metrics.record_root(size, sizes.heap - reference_size - size);
+    }
+
     Ok(sizes.heap)
 }
 
@@ -94,9 +154,13 @@ where
 /// Returns the number of bytes written.
 ///
 /// Grows the vector if needed.
-/// Infallible except for allocation errors.
+/// Infallible except for allocation errors and data exceeding the size limit.
 ///
 /// Use pre-allocated vector when possible to avoid reallocations.
+///
+/// # Panics
+///
+/// Panics if serialized data exceeds [`PACKET_SIZE_LIMIT`](crate::PACKET_SIZE_LIMIT).
 #[cfg(feature = "alloc")]
 #[inline(always)]
 pub fn write_packet_to_vec<F, T>(value: T, output: &mut alloc::vec::Vec<u8>) -> usize
@@ -110,15 +174,113 @@ where
     }
 }
 
-/// Reads size of the packet with value from the input.
-/// Returns `None` if the input is too short to determine the size.
+/// Header of a packet.
 ///
-/// # Panics
+/// Read with [`peek_header`] without deserializing the value,
+/// e.g. to find out how many bytes of a packet
+/// are still to be received from a stream.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PacketHeader {
+    /// Address right after the root value.
+    /// This is the number of bytes the packet occupies.
+    pub address: usize,
+
+    /// Stack size of the root value.
+    pub size: usize,
+}
+
+impl PacketHeader {
+    /// Returns `true` if input of length `len` contains the whole packet.
+    #[must_use]
+    #[inline(always)]
+    pub fn fits(&self, len: usize) -> bool {
+        self.address <= len
+    }
+
+    /// Checks that input of length `len` contains the whole packet.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError::OutOfBounds` with required and available sizes
+    /// if the input is too short.
+    #[inline(always)]
+    pub fn check_fits(&self, len: usize) -> Result<(), DeserializeError> {
+        if self.fits(len) {
+            Ok(())
+        } else {
+            Err(DeserializeError::OutOfBounds(ErrorDetails::sizes(
+                self.address,
+                len,
+            )))
+        }
+    }
+}
+
+/// Reads header of the packet with value of formula `F` from the input.
+///
+/// Input is not required to contain the whole packet,
+/// only the header.
+/// Use [`PacketHeader::fits`] to check the rest.
 ///
-/// This function may panic if the value size is too big to fit `usize`.
+/// # Errors
+///
+/// Returns `DeserializeError::OutOfBounds` if the input is shorter than the header.
+/// Returns `DeserializeError::WrongAddress` if the root value
+/// does not fit the declared packet.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// let mut buffer = [0u8; 64];
+/// let size = write_packet::<(u32, str), _>((1u32, "hello"), &mut buffer).unwrap();
+///
+/// // Truncated packet.
+/// let header = peek_header::<(u32, str)>(&buffer[..size - 1]).unwrap();
+/// assert_eq!(header.address, size);
+/// assert!(!header.fits(size - 1));
+/// assert!(header.fits(size));
+/// ```
+#[inline]
+pub fn peek_header<F>(input: &[u8]) -> Result<PacketHeader, DeserializeError>
+where
+    F: Formula + ?Sized,
+{
+    let reference_size = reference_size::<F>();
+
+    if input.len() < reference_size {
+        return Err(DeserializeError::OutOfBounds(ErrorDetails::sizes(
+            reference_size,
+            input.len(),
+        ))
+        .with_formula::<F>());
+    }
+
+    let (address, size) = try_read_reference::<F>(input, input.len() - reference_size)?;
+
+    if size > address {
+        return Err(DeserializeError::WrongAddress);
+    }
+
+    Ok(PacketHeader { address, size })
+}
+
+/// Reads size of the packet with value from the input.
+/// Returns `None` if the input is too short to determine the size
+/// or the size is too big to fit `usize`.
+#[deprecated(note = "Use `peek_header` that reports why header can't be read")]
 #[must_use]
 #[inline]
 pub fn read_packet_size<F>(input: &[u8]) -> Option<usize>
+where
+    F: Formula + ?Sized,
+{
+    packet_address::<F>(input)
+}
+
+/// Reads packet size from the header without validating it.
+#[inline]
+pub(crate) fn packet_address<F>(input: &[u8]) -> Option<usize>
 where
     F: Formula + ?Sized,
 {
@@ -130,15 +292,28 @@ where
             } else {
                 let mut bytes = [0u8; SIZE_STACK];
                 bytes.copy_from_slice(&input[..SIZE_STACK]);
-                let address = FixedUsizeType::from_le_bytes(bytes)
-                    .try_into()
-                    .expect("Value size can't fit `usize`");
-                Some(address)
+                FixedUsizeType::from_le_bytes(bytes).try_into().ok()
             }
         }
     }
 }
 
+/// Reads packet header and returns deserializer for the value
+/// and number of bytes the packet occupies.
+#[inline(always)]
+pub(crate) fn open_packet<F>(input: &[u8]) -> Result<(Deserializer<'_>, usize), DeserializeError>
+where
+    F: Formula + ?Sized,
+{
+    let header = peek_header::<F>(input)?;
+    header
+        .check_fits(input.len())
+        .map_err(DeserializeError::with_formula::<F>)?;
+
+    let de = Deserializer::new_unchecked(header.size, &input[..header.address]);
+    Ok((de, header.address))
+}
+
 /// Reads packet with value from the input.
 /// Returns deserialized value and number of bytes consumed.
 ///
@@ -151,28 +326,131 @@ where
     F: Formula + ?Sized,
     T: Deserialize<'de, F>,
 {
-    let reference_size = reference_size::<F>();
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!(
+        "read_packet",
+        formula = core::any::type_name::<F>(),
+        len = input.len(),
+    )
+    .entered();
 
-    if input.len() < reference_size {
-        return Err(DeserializeError::OutOfBounds);
-    }
+    let (de, address) = open_packet::<F>(input)?;
+    let value =
+        <T as Deserialize<'de, F>>::deserialize(de).map_err(DeserializeError::with_formula::<F>)?;
 
-    let (address, size) = read_reference::<F>(input, input.len() - reference_size);
+    Ok((value, address))
+}
 
-    if size > address {
-        return Err(DeserializeError::WrongAddress);
-    }
+/// Reads packet with value from the input
+/// without validating UTF-8 of strings.
+/// Returns deserialized value and number of bytes consumed.
+///
+/// Use for trusted input to skip the cost of UTF-8 validation.
+///
+/// # Safety
+///
+/// Every byte sequence deserialized with `str` formula
+/// from the packet must be valid UTF-8.
+/// This holds if the packet was produced by serialization
+/// or passed [`validate_packet`](crate::validate_packet).
+///
+/// # Errors
+///
+/// Returns `DeserializeError` if deserialization fails.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// let mut buffer = Vec::new();
+/// let size = write_packet_to_vec::<[As<str>], _>(["a", "bc"], &mut buffer);
+/// validate_packet::<[As<str>]>(&buffer[..size]).unwrap();
+///
+/// // SAFETY: Packet passed validation.
+/// let (value, _) =
+///     unsafe { read_packet_unchecked_utf8::<[As<str>], Vec<&str>>(&buffer[..size]) }.unwrap();
+/// assert_eq!(value, ["a", "bc"]);
+/// ```
+#[cfg(feature = "unchecked-utf8")]
+#[allow(unsafe_code)]
+#[inline]
+pub unsafe fn read_packet_unchecked_utf8<'de, F, T>(
+    input: &'de [u8],
+) -> Result<(T, usize), DeserializeError>
+where
+    F: Formula + ?Sized,
+    T: Deserialize<'de, F>,
+{
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!(
+        "read_packet_unchecked_utf8",
+        formula = core::any::type_name::<F>(),
+        len = input.len(),
+    )
+    .entered();
 
-    if address > input.len() {
-        return Err(DeserializeError::OutOfBounds);
-    }
+    let (de, address) = open_packet::<F>(input)?;
 
-    let de = Deserializer::new_unchecked(size, &input[..address]);
-    let value = <T as Deserialize<'de, F>>::deserialize(de)?;
+    // SAFETY: Caller guarantees that strings in the packet are valid UTF-8.
+    let de = unsafe { de.assume_utf8() };
+    let value =
+        <T as Deserialize<'de, F>>::deserialize(de).map_err(DeserializeError::with_formula::<F>)?;
 
     Ok((value, address))
 }
 
+/// Reads packet with value from the input
+/// and checks that the packet is the canonical encoding of the value.
+/// Returns deserialized value and number of bytes consumed.
+///
+/// Canonical encoding is the one produced by serializing the value,
+/// so any two distinct packets that read successfully hold distinct values.
+/// Oversized size prefixes, non-zero padding, references
+/// to shared or misplaced data and non-minimal `Vlq` values are rejected.
+/// Use when packets are hashed or signed.
+///
+/// The value is serialized again to check the packet,
+/// so this is about twice as slow as [`read_packet`].
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// let mut buffer = Vec::new();
+/// let size = write_packet_to_vec::<(Option<u32>, u8), _>((None::<u32>, 1u8), &mut buffer);
+///
+/// let (value, _) =
+///     read_packet_canonical::<(Option<u32>, u8), (Option<u32>, u8)>(&buffer[..size]).unwrap();
+/// assert_eq!(value, (None, 1));
+///
+/// buffer[size - 2] = 0xff; // Padding of the `None`.
+/// assert!(read_packet::<(Option<u32>, u8), (Option<u32>, u8)>(&buffer[..size]).is_ok());
+/// assert!(read_packet_canonical::<(Option<u32>, u8), (Option<u32>, u8)>(&buffer[..size]).is_err());
+/// ```
+///
+/// # Errors
+///
+/// Returns `DeserializeError::NonCanonical` if packet is not canonical.
+/// Returns other `DeserializeError` if deserialization fails.
+#[cfg(feature = "alloc")]
+#[inline]
+pub fn read_packet_canonical<'de, F, T>(input: &'de [u8]) -> Result<(T, usize), DeserializeError>
+where
+    F: Formula + ?Sized,
+    T: Deserialize<'de, F>,
+    for<'a> &'a T: Serialize<F>,
+{
+    let (value, size) = read_packet::<F, T>(input)?;
+
+    let mut canonical = alloc::vec::Vec::with_capacity(size);
+    let canonical_size = write_packet_to_vec::<F, &T>(&value, &mut canonical);
+    if canonical[..canonical_size] != input[..size] {
+        return Err(DeserializeError::NonCanonical);
+    }
+
+    Ok((value, size))
+}
+
 /// Reads packet with value from the input.
 /// Updates the value in-place.
 /// Returns number of bytes consumed.
@@ -189,24 +467,175 @@ where
     F: Formula + ?Sized,
     T: Deserialize<'de, F> + ?Sized,
 {
-    let reference_size = reference_size::<F>();
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!(
+        "read_packet_in_place",
+        formula = core::any::type_name::<F>(),
+        len = input.len(),
+    )
+    .entered();
 
-    if input.len() < reference_size {
-        return Err(DeserializeError::OutOfBounds);
-    }
+    let (de, address) = open_packet::<F>(input)?;
+    <T as Deserialize<'de, F>>::deserialize_in_place(place, de)
+        .map_err(DeserializeError::with_formula::<F>)?;
 
-    let (address, size) = read_reference::<F>(input, input.len() - reference_size);
+    Ok(address)
+}
 
-    if size > address {
-        return Err(DeserializeError::WrongAddress);
+/// Compact summary of a packet header.
+///
+/// Built without deserializing the value, so it can be logged
+/// along with the error when packet fails to deserialize.
+/// Implements `defmt::Format` with "defmt" feature.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PacketSummary {
+    /// Fingerprint of the formula the packet is read with.
+    pub fingerprint: u64,
+
+    /// Length of the input.
+    pub input_len: usize,
+
+    /// Size of the packet read from its header.
+    /// `None` if input is shorter than the header or the header is malformed.
+    pub packet_size: Option<usize>,
+
+    /// Stack size of the root value read from the header.
+    /// `None` if input is shorter than the header or the header is malformed.
+    pub stack_size: Option<usize>,
+}
+
+impl PacketSummary {
+    /// Summarizes packet with value of formula `F` in the input.
+    #[must_use]
+    #[inline]
+    pub fn new<F>(input: &[u8]) -> Self
+    where
+        F: Formula + ?Sized,
+    {
+        let reference_size = reference_size::<F>();
+
+        let reference = if input.len() < reference_size {
+            None
+        } else {
+            try_read_reference::<F>(input, input.len() - reference_size).ok()
+        };
+        let (packet_size, stack_size) = match reference {
+            None => (None, None),
+            Some((address, size)) => (Some(address), Some(size)),
+        };
+
+        PacketSummary {
+            fingerprint: fingerprint::<F>(),
+            input_len: input.len(),
+            packet_size,
+            stack_size,
+        }
     }
 
-    if address > input.len() {
-        return Err(DeserializeError::OutOfBounds);
+    /// Returns `true` if header is consistent with the input,
+    /// i.e. packet fits the input and root value fits the packet.
+    #[must_use]
+    #[inline]
+    pub fn is_consistent(&self) -> bool {
+        match (self.packet_size, self.stack_size) {
+            (Some(packet_size), Some(stack_size)) => {
+                packet_size <= self.input_len && stack_size <= packet_size
+            }
+            _ => false,
+        }
     }
+}
 
-    let de = Deserializer::new_unchecked(size, &input[..address]);
-    <T as Deserialize<'de, F>>::deserialize_in_place(place, de)?;
+/// Writes self-describing packet with the value into byte vector.
+/// Returns the number of bytes written.
+///
+/// Self-describing packet is a packet with [`Schema`] of the formula `F`
+/// followed by a packet with the value.
+/// It can be read with [`read_described_packet`] by code that
+/// has the formula, or with [`read_described_packet_value`] by code
+/// that does not.
+///
+/// Grows the vector if needed.
+/// Infallible except for allocation errors and data exceeding the size limit.
+///
+/// # Panics
+///
+/// Panics if serialized data exceeds [`PACKET_SIZE_LIMIT`](crate::PACKET_SIZE_LIMIT).
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// let mut buffer = Vec::new();
+/// let size = write_described_packet_to_vec::<(u32, Vec<u8>), _>((1u32, [2u8, 3]), &mut buffer);
+///
+/// let ((a, b), _) = read_described_packet::<(u32, Vec<u8>), (u32, Vec<u8>)>(&buffer[..size]).unwrap();
+/// assert_eq!((a, b), (1, vec![2, 3]));
+///
+/// let (_schema, value, _) = read_described_packet_value(&buffer[..size]).unwrap();
+/// assert_eq!(
+///     value,
+///     Value::Tuple(vec![
+///         Value::Unsigned(1),
+///         Value::Array(vec![Value::Unsigned(2), Value::Unsigned(3)]),
+///     ])
+/// );
+/// ```
+#[cfg(feature = "alloc")]
+pub fn write_described_packet_to_vec<F, T>(value: T, output: &mut alloc::vec::Vec<u8>) -> usize
+where
+    F: Formula + ?Sized,
+    T: Serialize<F>,
+{
+    let schema_size = write_packet_to_vec::<Schema, _>(&Schema::of::<F>(), output);
+    output.truncate(schema_size);
 
-    Ok(address)
+    let mut payload = alloc::vec::Vec::new();
+    let payload_size = write_packet_to_vec::<F, T>(value, &mut payload);
+    output.extend_from_slice(&payload[..payload_size]);
+
+    schema_size + payload_size
+}
+
+/// Reads self-describing packet with value from the input.
+/// Returns deserialized value and number of bytes consumed.
+///
+/// Embedded schema is validated against the formula `F`
+/// by comparing their [`fingerprint`](crate::fingerprint)s.
+///
+/// # Errors
+///
+/// Returns `DeserializeError::Incompatible` if embedded schema
+/// does not match the formula.
+/// Returns `DeserializeError` if deserialization fails.
+#[cfg(feature = "alloc")]
+pub fn read_described_packet<'de, F, T>(input: &'de [u8]) -> Result<(T, usize), DeserializeError>
+where
+    F: Formula + ?Sized,
+    T: Deserialize<'de, F>,
+{
+    let (schema, schema_size) = read_packet::<Schema, Schema>(input)?;
+    if schema.fingerprint() != fingerprint::<F>() {
+        return Err(DeserializeError::Incompatible);
+    }
+
+    let (value, size) = read_packet::<F, T>(&input[schema_size..])?;
+    Ok((value, schema_size + size))
+}
+
+/// Reads self-describing packet with value from the input
+/// without knowing the formula.
+/// Returns embedded schema, dynamic value and number of bytes consumed.
+///
+/// # Errors
+///
+/// Returns `DeserializeError` if deserialization fails.
+#[cfg(feature = "alloc")]
+pub fn read_described_packet_value(
+    input: &[u8],
+) -> Result<(Schema, Value, usize), DeserializeError> {
+    let (schema, schema_size) = read_packet::<Schema, Schema>(input)?;
+    let (value, size) = read_packet_value(&schema, &input[schema_size..])?;
+    Ok((schema, value, schema_size + size))
 }
@@ -0,0 +1,200 @@
+//!
+//! This module provides parallel serialization and deserialization
+//! of large slices with `rayon`.
+//!
+
+use alloc::vec::Vec;
+
+use rayon::prelude::*;
+
+use crate::{
+    buffer::{Buffer, DryBuffer},
+    deserialize::{Deserialize, DeserializeError},
+    formula::Formula,
+    iter::owned_iter_fast_sizes,
+    lazy::Lazy,
+    serialize::{write_bytes, write_field, write_slice, Serialize, Sizes},
+    size::SIZE_STACK,
+};
+
+/// Minimal number of elements serialized by one task.
+const MIN_CHUNK: usize = 1024;
+
+/// Slice wrapper serializable with slice formula
+/// that serializes elements in parallel.
+///
+/// Elements are split into chunks, stack size of each chunk is computed
+/// in parallel and then chunks are written concurrently into
+/// disjoint parts of a scratch buffer, which is copied into the output.
+///
+/// Only heap-less element formulas are serialized in parallel,
+/// since heap addresses of elements depend on all preceding elements.
+/// For other formulas and small slices `ParSlice` serializes
+/// sequentially, same as a plain slice.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// let data: Vec<u32> = (0..100_000).collect();
+///
+/// let mut buffer = Vec::new();
+/// let size = write_packet_to_vec::<Vec<u32>, _>(ParSlice(&data), &mut buffer);
+/// let (value, _) = read_packet::<Vec<u32>, Vec<u32>>(&buffer[..size]).unwrap();
+/// assert_eq!(value, data);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct ParSlice<'a, T>(pub &'a [T]);
+
+impl<'a, F, T> Serialize<[F]> for ParSlice<'a, T>
+where
+    F: Formula,
+    T: Sync,
+    &'a T: Serialize<F>,
+{
+    #[inline]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        if !parallel::<F>(self.0.len()) {
+            return write_slice::<F, _, _>(self.0.iter(), sizes, buffer);
+        }
+
+        let chunk_len = chunk_len(self.0.len());
+        let chunk_sizes: Vec<usize> = self
+            .0
+            .par_chunks(chunk_len)
+            .map(|chunk| chunk.iter().map(elem_stack_size::<F, _>).sum())
+            .collect();
+
+        // Chunks are written in order with stack growing down,
+        // so the first chunk is at the end of the scratch.
+        let mut scratch = alloc::vec![0; chunk_sizes.iter().sum()];
+        let mut parts = Vec::with_capacity(chunk_sizes.len());
+        let mut rest = &mut scratch[..];
+        for &size in &chunk_sizes {
+            let (head, part) = rest.split_at_mut(rest.len() - size);
+            parts.push(part);
+            rest = head;
+        }
+
+        self.0
+            .par_chunks(chunk_len)
+            .zip(parts)
+            .for_each(|(chunk, part)| {
+                let mut sizes = Sizes::ZERO;
+                for elem in chunk {
+                    match write_field::<F, _, _>(elem, &mut sizes, &mut *part, false) {
+                        Ok(()) => {}
+                        Err(never) => match never {},
+                    }
+                }
+                debug_assert_eq!(sizes.stack, part.len());
+            });
+
+        write_bytes(&scratch, sizes, buffer)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> Option<Sizes> {
+        if !parallel::<F>(self.0.len()) {
+            return owned_iter_fast_sizes::<F, _, _>(self.0.iter());
+        }
+
+        let stack = self
+            .0
+            .par_iter()
+            .with_min_len(MIN_CHUNK)
+            .map(elem_stack_size::<F, _>)
+            .sum();
+        Some(Sizes::with_stack(stack))
+    }
+}
+
+impl<'de, F> Lazy<'de, [F]>
+where
+    F: Formula,
+{
+    /// Deserializes elements of the slice in parallel and collects them.
+    ///
+    /// The slice is split into chunks with [`Lazy::chunks`]
+    /// and chunks are deserialized on `rayon` thread pool.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use alkahest::*;
+    /// let data: Vec<u32> = (0..100_000).collect();
+    ///
+    /// let mut buffer = Vec::new();
+    /// let size = write_packet_to_vec::<[u32], _>(&data, &mut buffer);
+    /// let (lazy, _) = read_packet::<[u32], Lazy<[u32]>>(&buffer[..size]).unwrap();
+    /// let value: Vec<u32> = lazy.par_collect().unwrap();
+    /// assert_eq!(value, data);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError` if deserialization of any element fails.
+    #[inline]
+    pub fn par_collect<T, C>(&self) -> Result<C, DeserializeError>
+    where
+        T: Deserialize<'de, F> + Send,
+        C: FromParallelIterator<T>,
+    {
+        if F::MAX_STACK_SIZE == Some(0) {
+            return self
+                .iter::<T>()
+                .collect::<Vec<_>>()
+                .into_par_iter()
+                .collect();
+        }
+
+        let chunks = self
+            .chunks(chunk_len(self.len()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        chunks
+            .into_par_iter()
+            .flat_map_iter(|chunk| chunk.iter::<T>())
+            .collect()
+    }
+}
+
+/// Returns `true` if slice of `len` elements of `F` is serialized in parallel.
+#[inline(always)]
+fn parallel<F>(len: usize) -> bool
+where
+    F: Formula,
+{
+    F::HEAPLESS && F::MAX_STACK_SIZE != Some(0) && len > MIN_CHUNK
+}
+
+#[inline(always)]
+fn chunk_len(len: usize) -> usize {
+    let chunks = rayon::current_num_threads() * 4;
+    len.div_ceil(chunks).max(MIN_CHUNK)
+}
+
+/// Returns stack size of the element written as a slice element.
+#[inline]
+fn elem_stack_size<F, T>(elem: T) -> usize
+where
+    F: Formula,
+    T: Serialize<F>,
+{
+    if let Some(max_stack) = F::MAX_STACK_SIZE {
+        return max_stack;
+    }
+    if let Some(sizes) = elem.size_hint() {
+        return sizes.stack + SIZE_STACK;
+    }
+
+    let mut sizes = Sizes::ZERO;
+    match write_field::<F, _, _>(elem, &mut sizes, DryBuffer, false) {
+        Ok(()) => sizes.stack,
+        Err(never) => match never {},
+    }
+}
@@ -0,0 +1,182 @@
+use core::marker::PhantomData;
+
+use crate::{
+    buffer::Buffer,
+    bytes::Bytes,
+    descriptor::{Descriptor, DescriptorKind, LazyDescriptor},
+    deserialize::{Deserialize, DeserializeError, Deserializer},
+    formula::{reference_size, Formula},
+    serialize::{write_reference, Serialize, Sizes},
+    size::FixedUsizeType,
+};
+
+/// A formula that can be used to serialize and deserialize data
+/// using [`postcard`] crate.
+///
+/// Any type serializable with `serde` can be used with this formula.
+/// Serialized value is stored in the heap exactly as `postcard::to_slice` produces it,
+/// so payloads already encoded by `no_std` devices can be embedded as-is.
+/// Does not require "alloc" feature.
+///
+/// If type is not serializable with `postcard` crate it will cause a panic.
+/// Deserializing non-compatible type will cause deserialization error.
+pub struct Postcard;
+
+impl Formula for Postcard {
+    const MAX_STACK_SIZE: Option<usize> = Some(reference_size::<Bytes>());
+    const EXACT_SIZE: bool = true;
+    const HEAPLESS: bool = false;
+
+    #[inline(always)]
+    fn describe() -> Descriptor {
+        Descriptor::new::<Postcard>(DescriptorKind::Ref(LazyDescriptor::of::<Bytes>()))
+    }
+}
+
+impl<T> Serialize<Postcard> for T
+where
+    T: serde::Serialize,
+{
+    #[inline]
+    fn serialize<B>(self, sizes: &mut Sizes, mut buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        let size =
+            match postcard::serialize_with_flavor(&self, postcard::ser_flavors::Size::default()) {
+                Ok(size) => size,
+                Err(err) => panic!("Postcard serialization error: {}", err),
+            };
+
+        if FixedUsizeType::try_from(size).is_err() {
+            panic!("Postcard serialization uses more that `FixedUsizeType::MAX` bytes");
+        }
+
+        match buffer.reserve_heap(sizes.heap, sizes.stack, size) {
+            Err(err) => return Err(err),
+            Ok([]) => {} // Nothing to do.
+            Ok(bytes) => {
+                if let Err(err) = postcard::to_slice(&self, &mut bytes[sizes.heap..][..size]) {
+                    panic!("Postcard serialization error: {}", err);
+                }
+            }
+        }
+
+        sizes.heap += size;
+        write_reference::<Bytes, B>(size, sizes.heap, sizes.heap, sizes.stack, buffer)?;
+        sizes.stack += reference_size::<Bytes>();
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        None
+    }
+}
+
+impl<'de, T> Deserialize<'de, Postcard> for T
+where
+    T: serde::Deserialize<'de>,
+{
+    #[inline]
+    fn deserialize(de: Deserializer<'de>) -> Result<Self, DeserializeError>
+    where
+        Self: Sized,
+    {
+        let de = de.deref::<Bytes>()?;
+
+        match postcard::take_from_bytes(de.read_all_bytes()) {
+            Ok((value, [])) => Ok(value),
+            Ok(_) | Err(_) => Err(DeserializeError::Incompatible),
+        }
+    }
+
+    #[inline]
+    fn deserialize_in_place(&mut self, de: Deserializer<'de>) -> Result<(), DeserializeError> {
+        let de = de.deref::<Bytes>()?;
+
+        let mut de = postcard::Deserializer::from_bytes(de.read_all_bytes());
+        if <T as serde::Deserialize<'de>>::deserialize_in_place(&mut de, self).is_err() {
+            return Err(DeserializeError::Incompatible);
+        }
+        match de.finalize() {
+            Ok([]) => Ok(()),
+            Ok(_) | Err(_) => Err(DeserializeError::Incompatible),
+        }
+    }
+}
+
+/// A formula that can be used to serialize and deserialize data
+/// using [`postcard`] crate.
+///
+/// Only one specified type can be used with this formula.
+/// This helps avoid accidental deserialization of wrong type.
+///
+/// If type is not serializable with `postcard` crate it will cause a panic.
+/// Deserializing non-compatible type will cause deserialization error.
+pub struct Postcarded<T>(PhantomData<fn(&T) -> &T>);
+
+impl<T> Formula for Postcarded<T> {
+    const MAX_STACK_SIZE: Option<usize> = Some(reference_size::<Bytes>());
+    const EXACT_SIZE: bool = true;
+    const HEAPLESS: bool = false;
+
+    #[inline(always)]
+    fn describe() -> Descriptor {
+        Descriptor::new::<Self>(DescriptorKind::Ref(LazyDescriptor::of::<Bytes>()))
+    }
+}
+
+impl<T> Serialize<Postcarded<T>> for T
+where
+    T: serde::Serialize,
+{
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        <T as Serialize<Postcard>>::serialize(self, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        <T as Serialize<Postcard>>::size_hint(self)
+    }
+}
+
+impl<T> Serialize<Postcarded<T>> for &T
+where
+    T: serde::Serialize,
+{
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        <&T as Serialize<Postcard>>::serialize(self, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        <&T as Serialize<Postcard>>::size_hint(self)
+    }
+}
+
+impl<'de, T> Deserialize<'de, Postcarded<T>> for T
+where
+    T: serde::Deserialize<'de>,
+{
+    #[inline(always)]
+    fn deserialize(de: Deserializer<'de>) -> Result<Self, DeserializeError>
+    where
+        Self: Sized,
+    {
+        <T as Deserialize<'de, Postcard>>::deserialize(de)
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(&mut self, de: Deserializer<'de>) -> Result<(), DeserializeError> {
+        <T as Deserialize<'de, Postcard>>::deserialize_in_place(self, de)
+    }
+}
@@ -2,6 +2,7 @@ use core::mem::size_of;
 
 use crate::{
     buffer::Buffer,
+    descriptor::{Descriptor, DescriptorKind},
     deserialize::{Deserialize, DeserializeError, Deserializer},
     formula::{BareFormula, Formula},
     serialize::{write_bytes, Serialize, SerializeRef, Sizes},
@@ -10,22 +11,27 @@ use crate::{
 macro_rules! impl_primitive {
     () => {};
 
-    ([$($head:ident)+] $([$($tail:ident)+])*) => {
-        impl_primitive!(@ < $($head)+);
-        impl_primitive!($([$($tail)+])*);
+    ([$kind:ident: $($head:ident)+] $([$tail_kind:ident: $($tail:ident)+])*) => {
+        impl_primitive!(@ $kind: < $($head)+);
+        impl_primitive!($([$tail_kind: $($tail)+])*);
     };
 
-    (@ $($head:ident)* <) => {};
-    (@ $($head:ident)* < $cursor:ident $($tail:ident)*) => {
-        impl_primitive!{! $($head)* < $cursor < $($tail)* }
-        impl_primitive!{@ $($head)* $cursor < $($tail)* }
+    (@ $kind:ident: $($head:ident)* <) => {};
+    (@ $kind:ident: $($head:ident)* < $cursor:ident $($tail:ident)*) => {
+        impl_primitive!{! $kind: $($head)* < $cursor < $($tail)* }
+        impl_primitive!{@ $kind: $($head)* $cursor < $($tail)* }
     };
 
-    (! $($from:ident)* < $ty:ident < $($to:ident)*) => {
+    (! $kind:ident: $($from:ident)* < $ty:ident < $($to:ident)*) => {
         impl Formula for $ty {
             const MAX_STACK_SIZE: Option<usize> = Some(size_of::<$ty>());
             const EXACT_SIZE: bool = true;
             const HEAPLESS: bool = true;
+
+            #[inline(always)]
+            fn describe() -> Descriptor {
+                Descriptor::new::<$ty>(DescriptorKind::$kind(size_of::<$ty>()))
+            }
         }
 
         impl BareFormula for $ty {}
@@ -119,15 +125,20 @@ macro_rules! impl_primitive {
 }
 
 impl_primitive! {
-    [u8 u16 u32 u64 u128]
-    [i8 i16 i32 i64 i128]
-    [f32 f64]
+    [Unsigned: u8 u16 u32 u64 u128]
+    [Signed: i8 i16 i32 i64 i128]
+    [Float: f32 f64]
 }
 
 impl Formula for bool {
     const MAX_STACK_SIZE: Option<usize> = Some(1);
     const EXACT_SIZE: bool = true;
     const HEAPLESS: bool = true;
+
+    #[inline(always)]
+    fn describe() -> Descriptor {
+        Descriptor::new::<bool>(DescriptorKind::Bool)
+    }
 }
 
 impl BareFormula for bool {}
@@ -175,14 +186,84 @@ where
 {
     #[inline(always)]
     fn deserialize(mut de: Deserializer) -> Result<Self, DeserializeError> {
-        let byte = de.read_byte()?;
-        Ok(T::from(byte != 0))
+        Ok(T::from(de.read_flag()?))
     }
 
     #[inline(always)]
     fn deserialize_in_place(&mut self, mut de: Deserializer) -> Result<(), DeserializeError> {
-        let byte = de.read_byte()?;
-        *self = From::from(byte != 0);
+        *self = From::from(de.read_flag()?);
+        Ok(())
+    }
+}
+
+/// Encoded as little-endian 32-bit unsigned integer.
+/// Deserialization rejects values that are not Unicode scalar values.
+impl Formula for char {
+    const MAX_STACK_SIZE: Option<usize> = Some(size_of::<u32>());
+    const EXACT_SIZE: bool = true;
+    const HEAPLESS: bool = true;
+
+    #[inline(always)]
+    fn describe() -> Descriptor {
+        Descriptor::new::<char>(DescriptorKind::Unsigned(size_of::<u32>()))
+    }
+}
+
+impl BareFormula for char {}
+
+impl Serialize<char> for char {
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        Self: Sized,
+        B: Buffer,
+    {
+        write_bytes(&u32::from(self).to_le_bytes(), sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        Some(Sizes {
+            heap: 0,
+            stack: size_of::<u32>(),
+        })
+    }
+}
+
+impl Serialize<char> for &char {
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        <char as Serialize<char>>::serialize(*self, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        Some(Sizes {
+            heap: 0,
+            stack: size_of::<u32>(),
+        })
+    }
+}
+
+impl<T> Deserialize<'_, char> for T
+where
+    T: From<char>,
+{
+    #[inline(always)]
+    fn deserialize(mut de: Deserializer) -> Result<Self, DeserializeError> {
+        let value = u32::from_le_bytes(de.read_byte_array()?);
+        match char::from_u32(value) {
+            Some(c) => Ok(T::from(c)),
+            None => Err(DeserializeError::InvalidChar(value)),
+        }
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(&mut self, de: Deserializer) -> Result<(), DeserializeError> {
+        *self = <T as Deserialize<char>>::deserialize(de)?;
         Ok(())
     }
 }
@@ -0,0 +1,760 @@
+//!
+//! This module provides conversion between serialized values and protobuf wire format.
+//!
+
+use alloc::{boxed::Box, string::String, vec::Vec};
+use core::{fmt, marker::PhantomData};
+
+use crate::{
+    buffer::Buffer,
+    deserialize::DeserializeError,
+    formula::Formula,
+    packet::write_packet_to_vec,
+    schema::{Schema, SchemaKind, SchemaNode, SchemaVariant},
+    serialize::{Serialize, Sizes},
+    value::{read_packet_value, write_value, Value},
+};
+
+const VARINT: u8 = 0;
+const FIXED64: u8 = 1;
+const LEN: u8 = 2;
+const FIXED32: u8 = 5;
+
+/// Error of conversion between alkahest values and protobuf wire format.
+#[derive(Clone, Debug)]
+pub enum ProtobufError {
+    /// Formula with this name has no protobuf representation,
+    /// e.g. 128-bit integer or nested repeated field.
+    Unsupported(String),
+
+    /// Protobuf input is truncated or has invalid key, varint or wire type.
+    Malformed,
+
+    /// Field with this number is encoded with wire type
+    /// that does not match the formula.
+    WrongWireType(u32),
+
+    /// Value of field with this number does not fit the formula,
+    /// e.g. integer overflow, invalid UTF-8 or unknown enum variant.
+    InvalidValue(u32),
+
+    /// Value does not match the schema.
+    Mismatch,
+
+    /// Packet failed to deserialize.
+    Deserialize(DeserializeError),
+}
+
+impl fmt::Display for ProtobufError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProtobufError::Unsupported(name) => {
+                write!(f, "formula `{name}` has no protobuf representation")
+            }
+            ProtobufError::Malformed => f.write_str("malformed protobuf input"),
+            ProtobufError::WrongWireType(number) => {
+                write!(f, "wrong wire type of field {number}")
+            }
+            ProtobufError::InvalidValue(number) => write!(f, "invalid value of field {number}"),
+            ProtobufError::Mismatch => f.write_str("value does not match the schema"),
+            ProtobufError::Deserialize(err) => write!(f, "deserialization error: {err}"),
+        }
+    }
+}
+
+/// Converts packet with value of formula `F` into protobuf message.
+///
+/// See [`Schema::to_protobuf`] for mapping rules.
+///
+/// # Errors
+///
+/// Returns `ProtobufError::Deserialize` if packet fails to deserialize.
+/// Returns `ProtobufError::Unsupported` if formula has no protobuf representation.
+pub fn packet_to_protobuf<F>(packet: &[u8]) -> Result<Vec<u8>, ProtobufError>
+where
+    F: Formula + ?Sized,
+{
+    let schema = Schema::of::<F>();
+    let (value, _) = read_packet_value(&schema, packet).map_err(ProtobufError::Deserialize)?;
+    schema.to_protobuf(&value)
+}
+
+/// Converts protobuf message into packet with value of formula `F`
+/// written into byte vector.
+/// Returns the number of bytes written, as [`write_packet_to_vec`](crate::write_packet_to_vec).
+///
+/// See [`Schema::to_protobuf`] for mapping rules.
+///
+/// # Errors
+///
+/// Returns `ProtobufError` if message is malformed or does not fit the formula.
+///
+/// # Panics
+///
+/// Panics if serialized data exceeds [`PACKET_SIZE_LIMIT`](crate::PACKET_SIZE_LIMIT).
+pub fn protobuf_to_packet<F>(message: &[u8], output: &mut Vec<u8>) -> Result<usize, ProtobufError>
+where
+    F: Formula + ?Sized,
+{
+    let schema = Schema::of::<F>();
+    let value = schema.from_protobuf(message)?;
+
+    let size = write_packet_to_vec::<Described<F>, _>(
+        SchemaValue {
+            schema: &schema,
+            value: &value,
+        },
+        output,
+    );
+    Ok(size)
+}
+
+/// Formula with the same layout as `F`.
+struct Described<F: ?Sized> {
+    marker: PhantomData<fn(&F) -> &F>,
+}
+
+impl<F> Formula for Described<F>
+where
+    F: Formula + ?Sized,
+{
+    const MAX_STACK_SIZE: Option<usize> = F::MAX_STACK_SIZE;
+    const EXACT_SIZE: bool = F::EXACT_SIZE;
+    const HEAPLESS: bool = F::HEAPLESS;
+}
+
+/// Value paired with the schema of formula `F`.
+struct SchemaValue<'a> {
+    schema: &'a Schema,
+    value: &'a Value,
+}
+
+impl<F> Serialize<Described<F>> for SchemaValue<'_>
+where
+    F: Formula + ?Sized,
+{
+    #[inline]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_value(self.schema, 0, self.value, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        None
+    }
+}
+
+impl Schema {
+    /// Encodes value with layout described by the schema as protobuf message.
+    ///
+    /// Root formula must be a structure, a tuple or an enum.
+    /// Fields are numbered from 1 in order of declaration.
+    ///
+    /// * `bool`, integers up to 64 bits and `Vlq` are varints,
+    ///   signed integers use `int32`/`int64` encoding.
+    /// * `f32` and `f64` are `float` and `double`.
+    /// * `str` and `Bytes` are `string` and `bytes`, so are sequences of `u8`.
+    /// * Other arrays and slices are repeated fields, packed for scalars.
+    /// * `Option` is a field that may be absent.
+    /// * Structures and tuples are embedded messages.
+    /// * Enums without fields are protobuf enums with variant tag as value.
+    ///   Other enums are messages with `oneof` of variants,
+    ///   each variant is embedded message with number `tag + 1`.
+    /// * References are transparent.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ProtobufError::Unsupported` if formula has no protobuf representation.
+    /// Returns `ProtobufError::Mismatch` if value does not match the schema.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use alkahest::*;
+    /// let schema = Schema::of::<(u32, Option<u8>)>();
+    /// let value = Value::Tuple(vec![Value::Unsigned(150), Value::Option(None)]);
+    ///
+    /// let message = schema.to_protobuf(&value).unwrap();
+    /// assert_eq!(message, [0x08, 0x96, 0x01]);
+    /// assert_eq!(schema.from_protobuf(&message).unwrap(), value);
+    /// ```
+    pub fn to_protobuf(&self, value: &Value) -> Result<Vec<u8>, ProtobufError> {
+        let mut out = Vec::new();
+        Encoder { schema: self }.message(0, value, &mut out)?;
+        Ok(out)
+    }
+
+    /// Decodes protobuf message into value with layout described by the schema.
+    ///
+    /// Missing fields get default values, unknown fields are skipped.
+    /// When non-repeated field occurs multiple times, the last one is used.
+    /// Repeated scalars are accepted both packed and unpacked.
+    /// See [`Schema::to_protobuf`] for mapping rules.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ProtobufError` if message is malformed or does not fit the schema.
+    pub fn from_protobuf(&self, message: &[u8]) -> Result<Value, ProtobufError> {
+        Decoder { schema: self }.message(0, message)
+    }
+}
+
+/// Encoded field value.
+#[derive(Clone, Copy)]
+enum Wire<'a> {
+    Varint(u64),
+    Fixed64([u8; 8]),
+    Len(&'a [u8]),
+    Fixed32([u8; 4]),
+}
+
+fn node(schema: &Schema, idx: usize) -> Result<&SchemaNode, ProtobufError> {
+    schema.nodes.get(idx).ok_or(ProtobufError::Mismatch)
+}
+
+/// Follows references to the formula.
+fn resolve(schema: &Schema, mut idx: usize) -> Result<(usize, &SchemaNode), ProtobufError> {
+    loop {
+        let current = node(schema, idx)?;
+        match current.kind {
+            SchemaKind::Ref(formula) => idx = formula,
+            _ => return Ok((idx, current)),
+        }
+    }
+}
+
+fn is_unit_enum(variants: &[SchemaVariant]) -> bool {
+    variants.iter().all(|variant| variant.fields.is_empty())
+}
+
+/// Returns wire type of scalar formula.
+fn scalar_wire(node: &SchemaNode) -> Option<u8> {
+    match &node.kind {
+        SchemaKind::Bool | SchemaKind::Vlq => Some(VARINT),
+        SchemaKind::Unsigned(size) | SchemaKind::Signed(size) if *size <= 8 => Some(VARINT),
+        SchemaKind::Float(4) => Some(FIXED32),
+        SchemaKind::Float(8) => Some(FIXED64),
+        SchemaKind::Enum(variants) if is_unit_enum(variants) => Some(VARINT),
+        _ => None,
+    }
+}
+
+fn write_varint(mut value: u64, out: &mut Vec<u8>) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn write_key(number: u32, wire: u8, out: &mut Vec<u8>) {
+    write_varint(u64::from(number) << 3 | u64::from(wire), out);
+}
+
+fn write_len(number: u32, payload: &[u8], out: &mut Vec<u8>) {
+    write_key(number, LEN, out);
+    write_varint(payload.len() as u64, out);
+    out.extend_from_slice(payload);
+}
+
+fn read_varint(input: &mut &[u8]) -> Result<u64, ProtobufError> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let Some((&byte, rest)) = input.split_first() else {
+            return Err(ProtobufError::Malformed);
+        };
+        *input = rest;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(ProtobufError::Malformed)
+}
+
+fn read_bytes<'a>(input: &mut &'a [u8], len: usize) -> Result<&'a [u8], ProtobufError> {
+    if input.len() < len {
+        return Err(ProtobufError::Malformed);
+    }
+    let (bytes, rest) = input.split_at(len);
+    *input = rest;
+    Ok(bytes)
+}
+
+fn read_wire<'a>(input: &mut &'a [u8], wire: u8) -> Result<Wire<'a>, ProtobufError> {
+    match wire {
+        VARINT => Ok(Wire::Varint(read_varint(input)?)),
+        FIXED64 => {
+            let mut bytes = [0; 8];
+            bytes.copy_from_slice(read_bytes(input, 8)?);
+            Ok(Wire::Fixed64(bytes))
+        }
+        LEN => {
+            let len = usize::try_from(read_varint(input)?).map_err(|_| ProtobufError::Malformed)?;
+            Ok(Wire::Len(read_bytes(input, len)?))
+        }
+        FIXED32 => {
+            let mut bytes = [0; 4];
+            bytes.copy_from_slice(read_bytes(input, 4)?);
+            Ok(Wire::Fixed32(bytes))
+        }
+        _ => Err(ProtobufError::Malformed),
+    }
+}
+
+/// Splits message into numbered fields.
+fn parse(mut input: &[u8]) -> Result<Vec<(u32, Wire<'_>)>, ProtobufError> {
+    let mut records = Vec::new();
+    while !input.is_empty() {
+        let key = read_varint(&mut input)?;
+        let number = u32::try_from(key >> 3).map_err(|_| ProtobufError::Malformed)?;
+        if number == 0 {
+            return Err(ProtobufError::Malformed);
+        }
+        records.push((number, read_wire(&mut input, (key & 7) as u8)?));
+    }
+    Ok(records)
+}
+
+/// Returns protobuf field number of the field at `idx`.
+fn field_number(idx: usize) -> Result<u32, ProtobufError> {
+    u32::try_from(idx + 1).map_err(|_| ProtobufError::Mismatch)
+}
+
+fn variant_number(variant: &SchemaVariant) -> Result<u32, ProtobufError> {
+    variant.tag.checked_add(1).ok_or(ProtobufError::Mismatch)
+}
+
+struct Encoder<'a> {
+    schema: &'a Schema,
+}
+
+impl Encoder<'_> {
+    fn message(&self, idx: usize, value: &Value, out: &mut Vec<u8>) -> Result<(), ProtobufError> {
+        let (_, current) = resolve(self.schema, idx)?;
+        match (&current.kind, value) {
+            (SchemaKind::Struct(fields), Value::Struct(values)) => self.fields(
+                fields.iter().map(|field| field.formula),
+                values.iter().map(|(_, value)| value),
+                values.len(),
+                out,
+            ),
+            (SchemaKind::Tuple(elems), Value::Tuple(values)) => {
+                self.fields(elems.iter().copied(), values.iter(), values.len(), out)
+            }
+            (SchemaKind::Enum(variants), Value::Enum { tag, fields, .. }) => {
+                let Some(variant) = variants.iter().find(|variant| variant.tag == *tag) else {
+                    return Err(ProtobufError::Mismatch);
+                };
+                let mut payload = Vec::new();
+                self.fields(
+                    variant.fields.iter().map(|field| field.formula),
+                    fields.iter().map(|(_, value)| value),
+                    fields.len(),
+                    &mut payload,
+                )?;
+                write_len(variant_number(variant)?, &payload, out);
+                Ok(())
+            }
+            (SchemaKind::Struct(_) | SchemaKind::Tuple(_) | SchemaKind::Enum(_), _) => {
+                Err(ProtobufError::Mismatch)
+            }
+            _ => Err(ProtobufError::Unsupported(current.name.clone())),
+        }
+    }
+
+    fn fields<'v>(
+        &self,
+        formulas: impl ExactSizeIterator<Item = usize>,
+        values: impl Iterator<Item = &'v Value>,
+        len: usize,
+        out: &mut Vec<u8>,
+    ) -> Result<(), ProtobufError> {
+        if formulas.len() != len {
+            return Err(ProtobufError::Mismatch);
+        }
+        for (idx, (formula, value)) in formulas.zip(values).enumerate() {
+            self.field(field_number(idx)?, formula, value, out)?;
+        }
+        Ok(())
+    }
+
+    fn field(
+        &self,
+        number: u32,
+        idx: usize,
+        value: &Value,
+        out: &mut Vec<u8>,
+    ) -> Result<(), ProtobufError> {
+        let (idx, current) = resolve(self.schema, idx)?;
+        match (&current.kind, value) {
+            (SchemaKind::Option(inner), Value::Option(value)) => match value {
+                None => Ok(()),
+                Some(value) => {
+                    let (_, inner_node) = resolve(self.schema, *inner)?;
+                    if let SchemaKind::Option(_) = inner_node.kind {
+                        return Err(ProtobufError::Unsupported(current.name.clone()));
+                    }
+                    self.field(number, *inner, value, out)
+                }
+            },
+            (SchemaKind::Array(elem, _) | SchemaKind::Slice(elem), Value::Array(values)) => {
+                let (elem, elem_node) = resolve(self.schema, *elem)?;
+                if let SchemaKind::Unsigned(1) = elem_node.kind {
+                    let bytes = values
+                        .iter()
+                        .map(|value| match value {
+                            Value::Unsigned(byte) => u8::try_from(*byte).ok(),
+                            _ => None,
+                        })
+                        .collect::<Option<Vec<u8>>>()
+                        .ok_or(ProtobufError::Mismatch)?;
+                    write_len(number, &bytes, out);
+                    return Ok(());
+                }
+
+                if scalar_wire(elem_node).is_some() {
+                    if values.is_empty() {
+                        return Ok(());
+                    }
+                    let mut packed = Vec::new();
+                    for value in values {
+                        self.scalar(number, elem_node, value, &mut packed)?;
+                    }
+                    write_len(number, &packed, out);
+                    return Ok(());
+                }
+
+                values
+                    .iter()
+                    .try_for_each(|value| self.single(number, elem, value, out))
+            }
+            (SchemaKind::Option(_) | SchemaKind::Array(..) | SchemaKind::Slice(_), _) => {
+                Err(ProtobufError::Mismatch)
+            }
+            _ => self.single(number, idx, value, out),
+        }
+    }
+
+    /// Writes non-repeated and non-optional field.
+    fn single(
+        &self,
+        number: u32,
+        idx: usize,
+        value: &Value,
+        out: &mut Vec<u8>,
+    ) -> Result<(), ProtobufError> {
+        let (idx, current) = resolve(self.schema, idx)?;
+        if let Some(wire) = scalar_wire(current) {
+            write_key(number, wire, out);
+            return self.scalar(number, current, value, out);
+        }
+
+        match (&current.kind, value) {
+            (SchemaKind::Str, Value::Str(string)) => write_len(number, string.as_bytes(), out),
+            (SchemaKind::Bytes, Value::Bytes(bytes)) => write_len(number, bytes, out),
+            (SchemaKind::Struct(_) | SchemaKind::Tuple(_) | SchemaKind::Enum(_), value) => {
+                let mut payload = Vec::new();
+                self.message(idx, value, &mut payload)?;
+                write_len(number, &payload, out);
+            }
+            (SchemaKind::Str | SchemaKind::Bytes, _) => return Err(ProtobufError::Mismatch),
+            _ => return Err(ProtobufError::Unsupported(current.name.clone())),
+        }
+        Ok(())
+    }
+
+    /// Writes scalar value without key.
+    fn scalar(
+        &self,
+        number: u32,
+        node: &SchemaNode,
+        value: &Value,
+        out: &mut Vec<u8>,
+    ) -> Result<(), ProtobufError> {
+        match (&node.kind, value) {
+            (SchemaKind::Bool, Value::Bool(value)) => write_varint(u64::from(*value), out),
+            (SchemaKind::Unsigned(_) | SchemaKind::Vlq, Value::Unsigned(value)) => {
+                let value =
+                    u64::try_from(*value).map_err(|_| ProtobufError::InvalidValue(number))?;
+                write_varint(value, out);
+            }
+            (SchemaKind::Signed(_), Value::Signed(value)) => {
+                let value =
+                    i64::try_from(*value).map_err(|_| ProtobufError::InvalidValue(number))?;
+                write_varint(value as u64, out);
+            }
+            (SchemaKind::Float(4), Value::Float(value)) => {
+                out.extend_from_slice(&(*value as f32).to_le_bytes());
+            }
+            (SchemaKind::Float(8), Value::Float(value)) => {
+                out.extend_from_slice(&value.to_le_bytes());
+            }
+            (SchemaKind::Enum(_), Value::Enum { tag, .. }) => write_varint(u64::from(*tag), out),
+            _ => return Err(ProtobufError::Mismatch),
+        }
+        Ok(())
+    }
+}
+
+struct Decoder<'a> {
+    schema: &'a Schema,
+}
+
+impl Decoder<'_> {
+    fn message(&self, idx: usize, message: &[u8]) -> Result<Value, ProtobufError> {
+        let (idx, current) = resolve(self.schema, idx)?;
+        let records = parse(message)?;
+
+        match &current.kind {
+            SchemaKind::Struct(fields) => {
+                let values = fields
+                    .iter()
+                    .enumerate()
+                    .map(|(n, field)| {
+                        let value = self.field(field_number(n)?, field.formula, &records)?;
+                        Ok((field.name.clone(), value))
+                    })
+                    .collect::<Result<_, _>>()?;
+                Ok(Value::Struct(values))
+            }
+            SchemaKind::Tuple(elems) => {
+                let values = elems
+                    .iter()
+                    .enumerate()
+                    .map(|(n, elem)| self.field(field_number(n)?, *elem, &records))
+                    .collect::<Result<_, _>>()?;
+                Ok(Value::Tuple(values))
+            }
+            SchemaKind::Enum(variants) => {
+                let found = records.iter().rev().find_map(|(number, wire)| {
+                    variants
+                        .iter()
+                        .find(|variant| variant.tag.checked_add(1) == Some(*number))
+                        .map(|variant| (variant, *number, wire))
+                });
+                let Some((variant, number, wire)) = found else {
+                    return default_value(self.schema, idx);
+                };
+                let Wire::Len(payload) = wire else {
+                    return Err(ProtobufError::WrongWireType(number));
+                };
+
+                let records = parse(payload)?;
+                let fields = variant
+                    .fields
+                    .iter()
+                    .enumerate()
+                    .map(|(n, field)| {
+                        let value = self.field(field_number(n)?, field.formula, &records)?;
+                        Ok((field.name.clone(), value))
+                    })
+                    .collect::<Result<_, _>>()?;
+
+                Ok(Value::Enum {
+                    name: variant.name.clone(),
+                    tag: variant.tag,
+                    fields,
+                })
+            }
+            _ => Err(ProtobufError::Unsupported(current.name.clone())),
+        }
+    }
+
+    fn field(
+        &self,
+        number: u32,
+        idx: usize,
+        records: &[(u32, Wire)],
+    ) -> Result<Value, ProtobufError> {
+        let (idx, current) = resolve(self.schema, idx)?;
+        let mut present = records
+            .iter()
+            .filter(|(field, _)| *field == number)
+            .map(|(_, wire)| wire);
+
+        match &current.kind {
+            SchemaKind::Option(inner) => match present.next() {
+                None => Ok(Value::Option(None)),
+                Some(_) => {
+                    let value = self.field(number, *inner, records)?;
+                    Ok(Value::Option(Some(Box::new(value))))
+                }
+            },
+            SchemaKind::Array(elem, _) | SchemaKind::Slice(elem) => {
+                let (elem, elem_node) = resolve(self.schema, *elem)?;
+                let mut elems = Vec::new();
+
+                if let SchemaKind::Unsigned(1) = elem_node.kind {
+                    match present.next_back() {
+                        None => {}
+                        Some(Wire::Len(bytes)) => {
+                            elems.extend(bytes.iter().map(|byte| Value::Unsigned((*byte).into())));
+                        }
+                        Some(_) => return Err(ProtobufError::WrongWireType(number)),
+                    }
+                } else if let Some(wire) = scalar_wire(elem_node) {
+                    for record in present {
+                        match record {
+                            Wire::Len(mut packed) => {
+                                while !packed.is_empty() {
+                                    let record = read_wire(&mut packed, wire)?;
+                                    elems.push(self.scalar(number, elem_node, &record)?);
+                                }
+                            }
+                            record => elems.push(self.scalar(number, elem_node, record)?),
+                        }
+                    }
+                } else {
+                    for record in present {
+                        elems.push(self.single(number, elem, record)?);
+                    }
+                }
+
+                if let SchemaKind::Array(_, len) = current.kind {
+                    if elems.is_empty() {
+                        return default_value(self.schema, idx);
+                    }
+                    if elems.len() != len {
+                        return Err(ProtobufError::InvalidValue(number));
+                    }
+                }
+                Ok(Value::Array(elems))
+            }
+            _ => match present.next_back() {
+                None => default_value(self.schema, idx),
+                Some(record) => self.single(number, idx, record),
+            },
+        }
+    }
+
+    /// Reads non-repeated and non-optional field.
+    fn single(&self, number: u32, idx: usize, record: &Wire) -> Result<Value, ProtobufError> {
+        let (idx, current) = resolve(self.schema, idx)?;
+        if scalar_wire(current).is_some() {
+            return self.scalar(number, current, record);
+        }
+
+        match (&current.kind, record) {
+            (SchemaKind::Str, Wire::Len(bytes)) => match core::str::from_utf8(bytes) {
+                Ok(string) => Ok(Value::Str(string.into())),
+                Err(_) => Err(ProtobufError::InvalidValue(number)),
+            },
+            (SchemaKind::Bytes, Wire::Len(bytes)) => Ok(Value::Bytes(bytes.to_vec())),
+            (
+                SchemaKind::Struct(_) | SchemaKind::Tuple(_) | SchemaKind::Enum(_),
+                Wire::Len(bytes),
+            ) => self.message(idx, bytes),
+            (
+                SchemaKind::Str
+                | SchemaKind::Bytes
+                | SchemaKind::Struct(_)
+                | SchemaKind::Tuple(_)
+                | SchemaKind::Enum(_),
+                _,
+            ) => Err(ProtobufError::WrongWireType(number)),
+            _ => Err(ProtobufError::Unsupported(current.name.clone())),
+        }
+    }
+
+    /// Reads scalar value.
+    fn scalar(
+        &self,
+        number: u32,
+        node: &SchemaNode,
+        record: &Wire,
+    ) -> Result<Value, ProtobufError> {
+        let value = match (&node.kind, *record) {
+            (SchemaKind::Bool, Wire::Varint(value)) => Value::Bool(value != 0),
+            (SchemaKind::Unsigned(size), Wire::Varint(value)) => {
+                if *size < 8 && value >> (size * 8) != 0 {
+                    return Err(ProtobufError::InvalidValue(number));
+                }
+                Value::Unsigned(value.into())
+            }
+            (SchemaKind::Vlq, Wire::Varint(value)) => Value::Unsigned(value.into()),
+            (SchemaKind::Signed(size), Wire::Varint(value)) => {
+                let value = value as i64;
+                if *size < 8 {
+                    let bound = 1i64 << (size * 8 - 1);
+                    if value < -bound || value >= bound {
+                        return Err(ProtobufError::InvalidValue(number));
+                    }
+                }
+                Value::Signed(value.into())
+            }
+            (SchemaKind::Float(4), Wire::Fixed32(bytes)) => {
+                Value::Float(f32::from_le_bytes(bytes).into())
+            }
+            (SchemaKind::Float(8), Wire::Fixed64(bytes)) => Value::Float(f64::from_le_bytes(bytes)),
+            (SchemaKind::Enum(variants), Wire::Varint(tag)) => {
+                let Some(variant) = variants
+                    .iter()
+                    .find(|variant| u64::from(variant.tag) == tag)
+                else {
+                    return Err(ProtobufError::InvalidValue(number));
+                };
+                Value::Enum {
+                    name: variant.name.clone(),
+                    tag: variant.tag,
+                    fields: Vec::new(),
+                }
+            }
+            _ => return Err(ProtobufError::WrongWireType(number)),
+        };
+        Ok(value)
+    }
+}
+
+/// Returns value of field absent in protobuf message.
+fn default_value(schema: &Schema, idx: usize) -> Result<Value, ProtobufError> {
+    let (_, current) = resolve(schema, idx)?;
+    let value = match &current.kind {
+        SchemaKind::Bool => Value::Bool(false),
+        SchemaKind::Unsigned(_) | SchemaKind::Vlq => Value::Unsigned(0),
+        SchemaKind::Signed(_) => Value::Signed(0),
+        SchemaKind::Float(_) => Value::Float(0.0),
+        SchemaKind::Bytes => Value::Bytes(Vec::new()),
+        SchemaKind::Str => Value::Str(String::new()),
+        SchemaKind::Array(elem, len) => Value::Array(
+            (0..*len)
+                .map(|_| default_value(schema, *elem))
+                .collect::<Result<_, _>>()?,
+        ),
+        SchemaKind::Slice(_) => Value::Array(Vec::new()),
+        SchemaKind::Option(_) => Value::Option(None),
+        SchemaKind::Tuple(elems) => Value::Tuple(
+            elems
+                .iter()
+                .map(|elem| default_value(schema, *elem))
+                .collect::<Result<_, _>>()?,
+        ),
+        SchemaKind::Struct(fields) => Value::Struct(
+            fields
+                .iter()
+                .map(|field| Ok((field.name.clone(), default_value(schema, field.formula)?)))
+                .collect::<Result<_, _>>()?,
+        ),
+        SchemaKind::Enum(variants) => {
+            let Some(variant) = variants.iter().min_by_key(|variant| variant.tag) else {
+                return Err(ProtobufError::Unsupported(current.name.clone()));
+            };
+            Value::Enum {
+                name: variant.name.clone(),
+                tag: variant.tag,
+                fields: variant
+                    .fields
+                    .iter()
+                    .map(|field| Ok((field.name.clone(), default_value(schema, field.formula)?)))
+                    .collect::<Result<_, _>>()?,
+            }
+        }
+        SchemaKind::Opaque | SchemaKind::Ref(_) => {
+            return Err(ProtobufError::Unsupported(current.name.clone()))
+        }
+    };
+    Ok(value)
+}
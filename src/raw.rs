@@ -0,0 +1,81 @@
+//!
+//! This module provides headerless serialization of fixed-size values.
+//!
+
+use crate::{
+    buffer::BufferExhausted,
+    deserialize::{deserialize, Deserialize, DeserializeError, ErrorDetails},
+    lazy_mut::Fixed,
+    serialize::{max_size, serialize, Serialize},
+};
+
+/// Serializes value of fixed formula into the beginning of the output
+/// without packet header.
+/// Returns the number of bytes written, which is always [`max_size::<F>()`](max_size).
+///
+/// Records serialized this way can be stored back to back
+/// and read with [`deserialize_raw`].
+///
+/// # Errors
+///
+/// Returns [`BufferExhausted`] if the output is shorter than the value.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// type Sample = (u32, f32, u16);
+///
+/// let mut buffer = [0u8; 3 * max_size::<Sample>()];
+/// let mut offset = 0;
+/// for sample in [(1u32, 0.5f32, 7u16), (2, 1.5, 8), (3, 2.5, 9)] {
+///     offset += serialize_raw::<Sample, _>(sample, &mut buffer[offset..]).unwrap();
+/// }
+/// assert_eq!(offset, 30);
+///
+/// let samples = buffer
+///     .chunks_exact(max_size::<Sample>())
+///     .map(|chunk| deserialize_raw::<Sample, Sample>(chunk).unwrap().0)
+///     .collect::<Vec<_>>();
+/// assert_eq!(samples[2], (3, 2.5, 9));
+/// ```
+#[inline]
+pub fn serialize_raw<F, T>(value: T, output: &mut [u8]) -> Result<usize, BufferExhausted>
+where
+    F: Fixed + ?Sized,
+    T: Serialize<F>,
+{
+    let size = max_size::<F>();
+    let Some(output) = output.get_mut(..size) else {
+        return Err(BufferExhausted);
+    };
+    let (written, _) = serialize::<F, T>(value, output)?;
+    debug_assert_eq!(written, size);
+    Ok(size)
+}
+
+/// Deserializes value of fixed formula from the beginning of the input
+/// written with [`serialize_raw`].
+/// Returns deserialized value and number of bytes consumed,
+/// which is always [`max_size::<F>()`](max_size).
+///
+/// # Errors
+///
+/// Returns `DeserializeError::OutOfBounds` if the input is shorter than the value.
+/// Returns other `DeserializeError` if deserialization fails.
+#[inline]
+pub fn deserialize_raw<'de, F, T>(input: &'de [u8]) -> Result<(T, usize), DeserializeError>
+where
+    F: Fixed + ?Sized,
+    T: Deserialize<'de, F>,
+{
+    let size = max_size::<F>();
+    let Some(input) = input.get(..size) else {
+        return Err(
+            DeserializeError::OutOfBounds(ErrorDetails::sizes(size, input.len()))
+                .with_formula::<F>(),
+        );
+    };
+    let value = deserialize::<F, T>(input)?;
+    Ok((value, size))
+}
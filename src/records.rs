@@ -0,0 +1,505 @@
+//!
+//! This module provides container of records with index of their offsets.
+//!
+
+use core::{
+    iter::FusedIterator,
+    marker::PhantomData,
+    ops::{Bound, RangeBounds},
+};
+
+use crate::{
+    deserialize::{Deserialize, DeserializeError, ErrorDetails},
+    formula::Formula,
+    packet::read_packet,
+};
+
+#[cfg(feature = "alloc")]
+use crate::{packet::write_packet_to_vec, serialize::Serialize};
+
+/// Size of offset in index entry and footer.
+const OFFSET_SIZE: usize = 8;
+
+/// Size of index entry: record offset and checksum.
+const ENTRY_SIZE: usize = OFFSET_SIZE + 4;
+
+/// Size of container footer: index offset and number of records.
+const FOOTER_SIZE: usize = 16;
+
+/// Writer of record container readable with [`Records`].
+///
+/// Each record is a packet of formula `F`.
+/// Records are followed by index of their offsets as little-endian `u64`
+/// and CRC-32 checksums as little-endian `u32`,
+/// and footer with index offset and number of records as little-endian `u64`.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// let mut writer = RecordWriter::<As<str>>::new();
+/// for name in ["grass.png", "stone.png", "water.png"] {
+///     writer.push(name);
+/// }
+/// let container = writer.finish();
+///
+/// let records = Records::<As<str>>::open(&container).unwrap();
+/// assert_eq!(records.len(), 3);
+/// assert_eq!(records.get::<&str>(1).unwrap().unwrap(), "stone.png");
+///
+/// let tail: Vec<&str> = records.range::<&str>(1..).map(Result::unwrap).collect();
+/// assert_eq!(tail, ["stone.png", "water.png"]);
+/// ```
+#[cfg(feature = "alloc")]
+pub struct RecordWriter<F: ?Sized> {
+    output: alloc::vec::Vec<u8>,
+    entries: alloc::vec::Vec<(u64, u32)>,
+    scratch: alloc::vec::Vec<u8>,
+    marker: PhantomData<fn(&F)>,
+}
+
+#[cfg(feature = "alloc")]
+impl<F> Default for RecordWriter<F>
+where
+    F: Formula + ?Sized,
+{
+    #[inline(always)]
+    fn default() -> Self {
+        RecordWriter::new()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<F> RecordWriter<F>
+where
+    F: Formula + ?Sized,
+{
+    /// Returns new writer without records.
+    #[must_use]
+    #[inline(always)]
+    pub fn new() -> Self {
+        RecordWriter {
+            output: alloc::vec::Vec::new(),
+            entries: alloc::vec::Vec::new(),
+            scratch: alloc::vec::Vec::new(),
+            marker: PhantomData,
+        }
+    }
+
+    /// Appends record with the value.
+    /// Returns index of the record.
+    ///
+    /// # Panics
+    ///
+    /// Panics if serialized data exceeds [`PACKET_SIZE_LIMIT`](crate::PACKET_SIZE_LIMIT).
+    pub fn push<T>(&mut self, value: T) -> usize
+    where
+        T: Serialize<F>,
+    {
+        let size = write_packet_to_vec::<F, T>(value, &mut self.scratch);
+        let record = &self.scratch[..size];
+        self.entries.push((self.output.len() as u64, crc32(record)));
+        self.output.extend_from_slice(record);
+        self.entries.len() - 1
+    }
+
+    /// Returns number of records written so far.
+    #[must_use]
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if no records were written.
+    #[must_use]
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Writes index and footer and returns the container bytes.
+    #[must_use]
+    pub fn finish(mut self) -> alloc::vec::Vec<u8> {
+        let index = self.output.len() as u64;
+        for (offset, checksum) in &self.entries {
+            self.output.extend_from_slice(&offset.to_le_bytes());
+            self.output.extend_from_slice(&checksum.to_le_bytes());
+        }
+        self.output.extend_from_slice(&index.to_le_bytes());
+        self.output
+            .extend_from_slice(&(self.entries.len() as u64).to_le_bytes());
+        self.output
+    }
+}
+
+/// View into record container written by [`RecordWriter`].
+///
+/// Any record can be read in O(1) using the index,
+/// without touching preceding records.
+/// Record checksum is verified before it is deserialized,
+/// corrupted records are reported with `DeserializeError::ChecksumMismatch`
+/// and can be skipped with [`RecordIter::skip_corrupt`].
+pub struct Records<'a, F: ?Sized> {
+    records: &'a [u8],
+    index: &'a [u8],
+    marker: PhantomData<fn(&F)>,
+}
+
+impl<F: ?Sized> Clone for Records<'_, F> {
+    #[inline(always)]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<F: ?Sized> Copy for Records<'_, F> {}
+
+impl<'a, F> Records<'a, F>
+where
+    F: Formula + ?Sized,
+{
+    /// Opens container in the input.
+    /// Checks that footer and index are consistent with the input,
+    /// records themselves are checked when read.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError::OutOfBounds` if input is too short for footer or index.
+    /// Returns `DeserializeError::WrongAddress` if index contains invalid offsets.
+    pub fn open(input: &'a [u8]) -> Result<Self, DeserializeError> {
+        let Some((head, footer)) = input.split_last_chunk::<FOOTER_SIZE>() else {
+            return Err(DeserializeError::OutOfBounds(ErrorDetails::sizes(
+                FOOTER_SIZE,
+                input.len(),
+            )));
+        };
+
+        let index_offset = read_offset(&footer[..OFFSET_SIZE])?;
+        let count = read_offset(&footer[OFFSET_SIZE..])?;
+
+        let index_size = count
+            .checked_mul(ENTRY_SIZE)
+            .ok_or(DeserializeError::WrongLength(ErrorDetails::new()))?;
+        let expected = index_offset
+            .checked_add(index_size)
+            .ok_or(DeserializeError::WrongLength(ErrorDetails::new()))?;
+        if expected != head.len() {
+            return Err(DeserializeError::WrongLength(ErrorDetails::sizes(
+                expected,
+                head.len(),
+            )));
+        }
+
+        let (records, index) = head.split_at(index_offset);
+
+        let mut previous = 0;
+        for entry in index.chunks_exact(ENTRY_SIZE) {
+            let offset = read_offset(&entry[..OFFSET_SIZE])?;
+            if offset < previous || offset > records.len() {
+                return Err(DeserializeError::WrongAddress);
+            }
+            previous = offset;
+        }
+
+        Ok(Records {
+            records,
+            index,
+            marker: PhantomData,
+        })
+    }
+
+    /// Returns offset of the index in the container.
+    #[inline(always)]
+    pub(crate) fn index_offset(&self) -> usize {
+        self.records.len()
+    }
+
+    /// Returns view of container previously opened with [`Records::open`]
+    /// which index starts at `index_offset`.
+    #[inline(always)]
+    pub(crate) fn reopen(input: &'a [u8], index_offset: usize) -> Self {
+        let (records, index) = input[..input.len() - FOOTER_SIZE].split_at(index_offset);
+        Records {
+            records,
+            index,
+            marker: PhantomData,
+        }
+    }
+
+    /// Returns number of records.
+    #[must_use]
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.index.len() / ENTRY_SIZE
+    }
+
+    /// Returns `true` if container has no records.
+    #[must_use]
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    /// Returns bytes of the record packet without verifying its checksum.
+    /// Returns `None` if `n` is out of bounds.
+    #[must_use]
+    #[inline]
+    pub fn record(&self, n: usize) -> Option<&'a [u8]> {
+        if n >= self.len() {
+            return None;
+        }
+        let start = self.offset(n);
+        let end = if n + 1 < self.len() {
+            self.offset(n + 1)
+        } else {
+            self.records.len()
+        };
+        Some(&self.records[start..end])
+    }
+
+    /// Returns bytes of the record packet after verifying its checksum.
+    /// Returns `None` if `n` is out of bounds.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError::ChecksumMismatch` if the record is corrupted.
+    #[inline]
+    pub fn checked_record(&self, n: usize) -> Option<Result<&'a [u8], DeserializeError>> {
+        let record = self.record(n)?;
+        let entry = &self.index[n * ENTRY_SIZE + OFFSET_SIZE..][..ENTRY_SIZE - OFFSET_SIZE];
+        let checksum = u32::from_le_bytes([entry[0], entry[1], entry[2], entry[3]]);
+        if crc32(record) != checksum {
+            return Some(Err(DeserializeError::ChecksumMismatch));
+        }
+        Some(Ok(record))
+    }
+
+    /// Verifies checksum and deserializes record `n`.
+    /// Returns `None` if `n` is out of bounds.
+    #[inline]
+    pub fn get<T>(&self, n: usize) -> Option<Result<T, DeserializeError>>
+    where
+        T: Deserialize<'a, F>,
+    {
+        let record = match self.checked_record(n)? {
+            Ok(record) => record,
+            Err(err) => return Some(Err(err)),
+        };
+        Some(read_packet::<F, T>(record).map(|(value, _)| value))
+    }
+
+    /// Returns iterator over all records.
+    #[must_use]
+    #[inline(always)]
+    pub fn iter<T>(&self) -> RecordIter<'a, F, T>
+    where
+        T: Deserialize<'a, F>,
+    {
+        self.range(..)
+    }
+
+    /// Returns iterator over records in the range.
+    ///
+    /// # Panics
+    ///
+    /// Panics if range is out of bounds.
+    #[must_use]
+    pub fn range<T>(&self, range: impl RangeBounds<usize>) -> RecordIter<'a, F, T>
+    where
+        T: Deserialize<'a, F>,
+    {
+        let front = match range.start_bound() {
+            Bound::Included(&start) => start,
+            Bound::Excluded(&start) => start + 1,
+            Bound::Unbounded => 0,
+        };
+        let back = match range.end_bound() {
+            Bound::Included(&end) => end + 1,
+            Bound::Excluded(&end) => end,
+            Bound::Unbounded => self.len(),
+        };
+        assert!(
+            front <= back && back <= self.len(),
+            "Range {front}..{back} is out of bounds of {} records",
+            self.len()
+        );
+
+        RecordIter {
+            records: *self,
+            front,
+            back,
+            marker: PhantomData,
+        }
+    }
+
+    /// Returns offset of record `n`, validated on open.
+    #[inline(always)]
+    fn offset(&self, n: usize) -> usize {
+        read_offset(&self.index[n * ENTRY_SIZE..][..OFFSET_SIZE]).unwrap_or(0)
+    }
+}
+
+/// Iterator over records of [`Records`].
+pub struct RecordIter<'a, F: ?Sized, T> {
+    records: Records<'a, F>,
+    front: usize,
+    back: usize,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<'a, F, T> Iterator for RecordIter<'a, F, T>
+where
+    F: Formula + ?Sized,
+    T: Deserialize<'a, F>,
+{
+    type Item = Result<T, DeserializeError>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Result<T, DeserializeError>> {
+        if self.front == self.back {
+            return None;
+        }
+        let item = self.records.get(self.front);
+        self.front += 1;
+        item
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.back - self.front;
+        (len, Some(len))
+    }
+
+    #[inline]
+    fn nth(&mut self, n: usize) -> Option<Result<T, DeserializeError>> {
+        if n >= self.back - self.front {
+            self.front = self.back;
+            return None;
+        }
+        self.front += n;
+        self.next()
+    }
+}
+
+impl<'a, F, T> RecordIter<'a, F, T>
+where
+    F: Formula + ?Sized,
+    T: Deserialize<'a, F>,
+{
+    /// Returns iterator that skips corrupted records
+    /// and records that fail to deserialize,
+    /// reporting their indices and errors to `report`.
+    /// Yields indices and values of intact records.
+    #[inline(always)]
+    pub fn skip_corrupt<R>(self, report: R) -> SkipCorrupt<'a, F, T, R>
+    where
+        R: FnMut(usize, DeserializeError),
+    {
+        SkipCorrupt { iter: self, report }
+    }
+}
+
+impl<'a, F, T> DoubleEndedIterator for RecordIter<'a, F, T>
+where
+    F: Formula + ?Sized,
+    T: Deserialize<'a, F>,
+{
+    #[inline]
+    fn next_back(&mut self) -> Option<Result<T, DeserializeError>> {
+        if self.front == self.back {
+            return None;
+        }
+        self.back -= 1;
+        self.records.get(self.back)
+    }
+}
+
+impl<'a, F, T> ExactSizeIterator for RecordIter<'a, F, T>
+where
+    F: Formula + ?Sized,
+    T: Deserialize<'a, F>,
+{
+}
+
+impl<'a, F, T> FusedIterator for RecordIter<'a, F, T>
+where
+    F: Formula + ?Sized,
+    T: Deserialize<'a, F>,
+{
+}
+
+/// Iterator over intact records.
+///
+/// Created by [`RecordIter::skip_corrupt`].
+pub struct SkipCorrupt<'a, F: ?Sized, T, R> {
+    iter: RecordIter<'a, F, T>,
+    report: R,
+}
+
+impl<'a, F, T, R> Iterator for SkipCorrupt<'a, F, T, R>
+where
+    F: Formula + ?Sized,
+    T: Deserialize<'a, F>,
+    R: FnMut(usize, DeserializeError),
+{
+    type Item = (usize, T);
+
+    fn next(&mut self) -> Option<(usize, T)> {
+        loop {
+            let n = self.iter.front;
+            match self.iter.next()? {
+                Ok(value) => return Some((n, value)),
+                Err(err) => (self.report)(n, err),
+            }
+        }
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.iter.len()))
+    }
+}
+
+impl<'a, F, T, R> FusedIterator for SkipCorrupt<'a, F, T, R>
+where
+    F: Formula + ?Sized,
+    T: Deserialize<'a, F>,
+    R: FnMut(usize, DeserializeError),
+{
+}
+
+/// Computes CRC-32 (IEEE) checksum of the bytes.
+fn crc32(bytes: &[u8]) -> u32 {
+    const TABLE: [u32; 256] = {
+        let mut table = [0; 256];
+        let mut i = 0;
+        while i < 256 {
+            let mut crc = i as u32;
+            let mut bit = 0;
+            while bit < 8 {
+                crc = if crc & 1 == 1 {
+                    (crc >> 1) ^ 0xEDB8_8320
+                } else {
+                    crc >> 1
+                };
+                bit += 1;
+            }
+            table[i] = crc;
+            i += 1;
+        }
+        table
+    };
+
+    !bytes.iter().fold(!0, |crc, &byte| {
+        TABLE[usize::from((crc as u8) ^ byte)] ^ (crc >> 8)
+    })
+}
+
+/// Reads little-endian `u64` offset that must fit `usize`.
+#[inline(always)]
+fn read_offset(bytes: &[u8]) -> Result<usize, DeserializeError> {
+    let mut offset = [0; OFFSET_SIZE];
+    offset.copy_from_slice(bytes);
+    let offset = u64::from_le_bytes(offset);
+    usize::try_from(offset).map_err(|_| DeserializeError::WrongAddress)
+}
@@ -6,9 +6,12 @@ use core::marker::PhantomData;
 
 use crate::{
     buffer::Buffer,
-    deserialize::{Deserialize, DeserializeError, Deserializer},
+    descriptor::{Descriptor, DescriptorKind, LazyDescriptor},
+    deserialize::{Deserialize, DeserializeError, Deserializer, ErrorDetails},
     formula::{reference_size, BareFormula, Formula},
-    serialize::{field_size_hint, write_ref, write_reference, Serialize, Sizes},
+    serialize::{
+        field_size_hint, write_bytes, write_field, write_ref, write_reference, Serialize, Sizes,
+    },
 };
 
 /// `Ref` is a formula wrapper.
@@ -29,6 +32,11 @@ where
     const MAX_STACK_SIZE: Option<usize> = Some(reference_size::<F>());
     const EXACT_SIZE: bool = true;
     const HEAPLESS: bool = matches!(F::MAX_STACK_SIZE, Some(0));
+
+    #[inline(always)]
+    fn describe() -> Descriptor {
+        Descriptor::new::<Self>(DescriptorKind::Ref(LazyDescriptor::of::<F>()))
+    }
 }
 
 impl<F, T> Serialize<Ref<F>> for T
@@ -76,3 +84,142 @@ where
         <T as Deserialize<F>>::deserialize_in_place(self, de)
     }
 }
+
+/// `InlineRef` is a formula wrapper similar to [`Ref`].
+/// Values that fit into the space of the reference
+/// and do not use heap are stored inline instead.
+///
+/// Stack of `InlineRef` holds one tag byte followed by the reference slot.
+/// Tag `0` means the slot contains a reference as in [`Ref`].
+/// Otherwise the value is stored inline in the slot
+/// and occupies `tag - 1` bytes.
+///
+/// This avoids heap indirection for short strings and slices
+/// at the cost of one byte per field.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// let mut buffer = [0u8; 64];
+/// let (size, root) = serialize::<(InlineRef<str>, InlineRef<str>), _>(
+///     ("abc", "longer than reference"),
+///     &mut buffer,
+/// )
+/// .unwrap();
+///
+/// let (a, b) = deserialize_with_size::<(InlineRef<str>, InlineRef<str>), (&str, &str)>(
+///     &buffer[..size],
+///     root,
+/// )
+/// .unwrap();
+/// assert_eq!(a, "abc");
+/// assert_eq!(b, "longer than reference");
+/// ```
+pub struct InlineRef<F: ?Sized> {
+    marker: PhantomData<fn(&F) -> &F>,
+}
+
+impl<F> Formula for InlineRef<F>
+where
+    F: BareFormula + ?Sized,
+{
+    const MAX_STACK_SIZE: Option<usize> = Some(1 + reference_size::<F>());
+    const EXACT_SIZE: bool = true;
+    const HEAPLESS: bool = matches!(F::MAX_STACK_SIZE, Some(0));
+}
+
+/// Returns `true` if value with specified sizes is stored inline by `InlineRef<F>`.
+#[inline(always)]
+fn fits_inline<F>(sizes: Sizes) -> bool
+where
+    F: BareFormula + ?Sized,
+{
+    sizes.heap == 0 && sizes.stack <= reference_size::<F>()
+}
+
+impl<F, T> Serialize<InlineRef<F>> for T
+where
+    F: BareFormula + ?Sized,
+    T: Serialize<F>,
+{
+    #[inline]
+    fn serialize<B>(self, sizes: &mut Sizes, mut buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        match <T as Serialize<F>>::size_hint(&self) {
+            Some(hint) if fits_inline::<F>(hint) => {
+                #[allow(clippy::cast_possible_truncation)]
+                write_bytes(&[1 + hint.stack as u8], sizes, buffer.reborrow())?;
+
+                let old_stack = sizes.stack;
+                write_field::<F, T, _>(self, sizes, buffer.reborrow(), true)?;
+                debug_assert_eq!(sizes.stack - old_stack, hint.stack);
+
+                let pad = reference_size::<F>() - hint.stack;
+                buffer.pad_stack(sizes.heap, sizes.stack, pad)?;
+                sizes.stack += pad;
+                Ok(())
+            }
+            _ => {
+                write_bytes(&[0], sizes, buffer.reborrow())?;
+                <T as Serialize<Ref<F>>>::serialize(self, sizes, buffer)
+            }
+        }
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        let sizes = field_size_hint::<F>(self, true)?;
+        if fits_inline::<F>(sizes) {
+            return Some(Sizes::with_stack(1 + reference_size::<F>()));
+        }
+        let mut sizes = <T as Serialize<Ref<F>>>::size_hint(self)?;
+        sizes.add_stack(1);
+        Some(sizes)
+    }
+}
+
+impl<'de, F, T> Deserialize<'de, InlineRef<F>> for T
+where
+    F: BareFormula + ?Sized,
+    T: Deserialize<'de, F> + ?Sized,
+{
+    #[inline(always)]
+    fn deserialize(de: Deserializer<'de>) -> Result<T, DeserializeError>
+    where
+        T: Sized,
+    {
+        let de = inline_deref::<F>(de)?;
+        <T as Deserialize<F>>::deserialize(de)
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(&mut self, de: Deserializer<'de>) -> Result<(), DeserializeError> {
+        let de = inline_deref::<F>(de)?;
+        <T as Deserialize<F>>::deserialize_in_place(self, de)
+    }
+}
+
+/// Returns deserializer for the value of `InlineRef<F>`,
+/// either inline or referenced.
+#[inline]
+pub(crate) fn inline_deref<F>(mut de: Deserializer) -> Result<Deserializer, DeserializeError>
+where
+    F: BareFormula + ?Sized,
+{
+    match de.read_byte()? {
+        0 => de.deref::<F>(),
+        tag => {
+            let len = usize::from(tag - 1);
+            if len > reference_size::<F>() {
+                return Err(DeserializeError::WrongLength(ErrorDetails::sizes(
+                    reference_size::<F>(),
+                    len,
+                )));
+            }
+            de.sub(len)
+        }
+    }
+}
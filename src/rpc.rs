@@ -0,0 +1,392 @@
+//!
+//! This module provides request/response plumbing over transports that move byte buffers.
+//!
+
+use alloc::{boxed::Box, collections::BTreeMap, vec::Vec};
+use core::fmt;
+
+use crate::{
+    deserialize::{Deserialize, DeserializeError, ErrorDetails},
+    formula::Formula,
+    packet::{read_packet, write_packet_to_vec},
+    serialize::Serialize,
+};
+
+/// Size of request header: method id and call id.
+const REQUEST_HEADER_SIZE: usize = 8;
+
+/// Size of response header: call id and status.
+const RESPONSE_HEADER_SIZE: usize = 5;
+
+const STATUS_OK: u8 = 0;
+const STATUS_UNKNOWN_METHOD: u8 = 1;
+const STATUS_BAD_REQUEST: u8 = 2;
+
+/// Remote method with formulas of request and response.
+///
+/// Usually defined with [`rpc_methods!`](crate::rpc_methods).
+pub trait Method {
+    /// Identifier of the method.
+    /// Must be unique among methods of a protocol.
+    const ID: u32;
+
+    /// Formula of the request.
+    type Request: Formula + ?Sized;
+
+    /// Formula of the response.
+    type Response: Formula + ?Sized;
+}
+
+/// Defines types that implement [`Method`].
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// rpc_methods! {
+///     /// Adds two numbers.
+///     pub Add = 1: (u32, u32) => u64;
+///
+///     /// Returns the string back.
+///     pub Echo = 2: As<str> => As<str>;
+/// }
+///
+/// let mut router = Router::<u32>::new();
+/// router.handle::<Add, _, _>(|calls, (a, b): (u32, u32)| {
+///     *calls += 1;
+///     u64::from(a) + u64::from(b)
+/// });
+///
+/// // Transport that dispatches requests locally.
+/// let mut calls = 0;
+/// let mut client = Client::new(|request: &[u8], response: &mut Vec<u8>| {
+///     router.dispatch(&mut calls, request, response)
+/// });
+///
+/// assert_eq!(client.call::<Add, _, u64>((2u32, 3u32)).unwrap(), 5);
+/// assert!(matches!(
+///     client.call::<Echo, _, &str>("hi"),
+///     Err(RpcError::UnknownMethod(2))
+/// ));
+/// ```
+#[macro_export]
+macro_rules! rpc_methods {
+    ($(
+        $(#[$meta:meta])*
+        $vis:vis $name:ident = $id:literal : $request:ty => $response:ty;
+    )*) => {$(
+        $(#[$meta])*
+        $vis struct $name;
+
+        impl $crate::Method for $name {
+            const ID: u32 = $id;
+            type Request = $request;
+            type Response = $response;
+        }
+    )*};
+}
+
+/// Error of remote call.
+#[derive(Clone, Copy, Debug)]
+pub enum RpcError<E> {
+    /// Transport failed to deliver request or response.
+    Transport(E),
+
+    /// Server has no handler for the method.
+    UnknownMethod(u32),
+
+    /// Server failed to deserialize the request.
+    BadRequest,
+
+    /// Response belongs to another call.
+    CallMismatch {
+        /// Id of the call.
+        expected: u32,
+        /// Id of the call in the response.
+        found: u32,
+    },
+
+    /// Request or response failed to deserialize.
+    Deserialize(DeserializeError),
+}
+
+impl<E> From<DeserializeError> for RpcError<E> {
+    #[inline(always)]
+    fn from(err: DeserializeError) -> Self {
+        RpcError::Deserialize(err)
+    }
+}
+
+impl<E> fmt::Display for RpcError<E>
+where
+    E: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RpcError::Transport(err) => write!(f, "transport error: {err:?}"),
+            RpcError::UnknownMethod(id) => write!(f, "unknown method {id}"),
+            RpcError::BadRequest => f.write_str("server failed to deserialize request"),
+            RpcError::CallMismatch { expected, found } => {
+                write!(f, "response to call {found} received for call {expected}")
+            }
+            RpcError::Deserialize(err) => write!(f, "deserialization error: {err}"),
+        }
+    }
+}
+
+/// Transport that sends request bytes and receives response bytes.
+///
+/// Implemented for closures with the same signature as [`Transport::round_trip`].
+pub trait Transport {
+    /// Transport error.
+    type Error;
+
+    /// Sends the request and writes received response into `response`,
+    /// replacing its content.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if request or response cannot be delivered.
+    fn round_trip(&mut self, request: &[u8], response: &mut Vec<u8>) -> Result<(), Self::Error>;
+}
+
+impl<F, E> Transport for F
+where
+    F: FnMut(&[u8], &mut Vec<u8>) -> Result<(), E>,
+{
+    type Error = E;
+
+    #[inline(always)]
+    fn round_trip(&mut self, request: &[u8], response: &mut Vec<u8>) -> Result<(), E> {
+        self(request, response)
+    }
+}
+
+/// Writes request for method `M` with the call id into the output,
+/// replacing its content.
+#[inline]
+pub fn write_request<M, T>(call: u32, value: T, output: &mut Vec<u8>)
+where
+    M: Method + ?Sized,
+    T: Serialize<M::Request>,
+{
+    output.clear();
+    output.extend_from_slice(&M::ID.to_le_bytes());
+    output.extend_from_slice(&call.to_le_bytes());
+    append_packet::<M::Request, T>(value, output);
+}
+
+/// Reads response to the call of method `M`.
+///
+/// # Errors
+///
+/// Returns `RpcError::CallMismatch` if response belongs to another call.
+/// Returns `RpcError::UnknownMethod` or `RpcError::BadRequest`
+/// if server failed to handle the request.
+/// Returns `RpcError::Deserialize` if response fails to deserialize.
+pub fn read_response<'de, M, R, E>(call: u32, input: &'de [u8]) -> Result<R, RpcError<E>>
+where
+    M: Method + ?Sized,
+    R: Deserialize<'de, M::Response>,
+{
+    let Some((header, packet)) = input.split_first_chunk::<RESPONSE_HEADER_SIZE>() else {
+        return Err(RpcError::Deserialize(DeserializeError::OutOfBounds(
+            ErrorDetails::sizes(RESPONSE_HEADER_SIZE, input.len()),
+        )));
+    };
+
+    let found = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+    if found != call {
+        return Err(RpcError::CallMismatch {
+            expected: call,
+            found,
+        });
+    }
+
+    match header[4] {
+        STATUS_OK => {
+            let (value, _) = read_packet::<M::Response, R>(packet)?;
+            Ok(value)
+        }
+        STATUS_UNKNOWN_METHOD => Err(RpcError::UnknownMethod(M::ID)),
+        STATUS_BAD_REQUEST => Err(RpcError::BadRequest),
+        _ => Err(RpcError::Deserialize(DeserializeError::Incompatible)),
+    }
+}
+
+/// Client that calls methods over [`Transport`].
+///
+/// Requests are tagged with sequential call ids
+/// and responses are checked to match them.
+pub struct Client<T> {
+    transport: T,
+    next_call: u32,
+    request: Vec<u8>,
+    response: Vec<u8>,
+}
+
+impl<T> Client<T>
+where
+    T: Transport,
+{
+    /// Returns new client over the transport.
+    #[must_use]
+    #[inline(always)]
+    pub fn new(transport: T) -> Self {
+        Client {
+            transport,
+            next_call: 0,
+            request: Vec::new(),
+            response: Vec::new(),
+        }
+    }
+
+    /// Calls method `M` with the request and deserializes the response.
+    /// Response may borrow from the client until next call.
+    ///
+    /// # Errors
+    ///
+    /// Returns `RpcError::Transport` if transport fails.
+    /// Returns other `RpcError` if server fails to handle the request
+    /// or response fails to deserialize.
+    pub fn call<'a, M, Q, R>(&'a mut self, request: Q) -> Result<R, RpcError<T::Error>>
+    where
+        M: Method + ?Sized,
+        Q: Serialize<M::Request>,
+        R: Deserialize<'a, M::Response>,
+    {
+        let call = self.next_call;
+        self.next_call = self.next_call.wrapping_add(1);
+
+        write_request::<M, Q>(call, request, &mut self.request);
+        self.transport
+            .round_trip(&self.request, &mut self.response)
+            .map_err(RpcError::Transport)?;
+
+        read_response::<M, R, T::Error>(call, &self.response)
+    }
+
+    /// Returns reference to the transport.
+    #[inline(always)]
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    /// Returns mutable reference to the transport.
+    #[inline(always)]
+    pub fn transport_mut(&mut self) -> &mut T {
+        &mut self.transport
+    }
+}
+
+type Handler<S> = Box<dyn Fn(&mut S, &[u8], &mut Vec<u8>) -> Result<(), DeserializeError>>;
+
+/// Registry of method handlers that dispatches requests
+/// and writes responses.
+///
+/// Handlers receive mutable reference to state `S`.
+pub struct Router<S> {
+    handlers: BTreeMap<u32, Handler<S>>,
+}
+
+impl<S> Default for Router<S> {
+    #[inline(always)]
+    fn default() -> Self {
+        Router::new()
+    }
+}
+
+impl<S> Router<S> {
+    /// Returns new router without handlers.
+    #[must_use]
+    #[inline(always)]
+    pub fn new() -> Self {
+        Router {
+            handlers: BTreeMap::new(),
+        }
+    }
+
+    /// Registers handler of method `M`
+    /// that takes request deserialized into `Q`
+    /// and returns response serialized from `R`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if handler of method with the same id is already registered.
+    pub fn handle<M, Q, R>(&mut self, handler: impl Fn(&mut S, Q) -> R + 'static) -> &mut Self
+    where
+        M: Method + ?Sized,
+        Q: for<'de> Deserialize<'de, M::Request>,
+        R: Serialize<M::Response>,
+    {
+        let handler: Handler<S> = Box::new(move |state, request, response| {
+            let (request, _) = read_packet::<M::Request, Q>(request)?;
+            append_packet::<M::Response, R>(handler(state, request), response);
+            Ok(())
+        });
+        let old = self.handlers.insert(M::ID, handler);
+        assert!(old.is_none(), "Method {} is already registered", M::ID);
+        self
+    }
+
+    /// Returns `true` if handler of the method is registered.
+    #[must_use]
+    #[inline(always)]
+    pub fn contains(&self, id: u32) -> bool {
+        self.handlers.contains_key(&id)
+    }
+
+    /// Handles the request and writes response into `response`,
+    /// replacing its content.
+    ///
+    /// Unknown methods and requests that fail to deserialize
+    /// are reported to the client in the response.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError::OutOfBounds` if request is too short
+    /// to contain method and call ids, so no response can be written.
+    pub fn dispatch(
+        &self,
+        state: &mut S,
+        request: &[u8],
+        response: &mut Vec<u8>,
+    ) -> Result<(), DeserializeError> {
+        let Some((header, packet)) = request.split_first_chunk::<REQUEST_HEADER_SIZE>() else {
+            return Err(DeserializeError::OutOfBounds(ErrorDetails::sizes(
+                REQUEST_HEADER_SIZE,
+                request.len(),
+            )));
+        };
+        let method = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+
+        response.clear();
+        response.extend_from_slice(&header[4..]);
+        response.push(STATUS_OK);
+
+        let status = match self.handlers.get(&method) {
+            None => STATUS_UNKNOWN_METHOD,
+            Some(handler) => match handler(state, packet, response) {
+                Ok(()) => STATUS_OK,
+                Err(_) => STATUS_BAD_REQUEST,
+            },
+        };
+
+        if status != STATUS_OK {
+            response.truncate(RESPONSE_HEADER_SIZE);
+            response[RESPONSE_HEADER_SIZE - 1] = status;
+        }
+        Ok(())
+    }
+}
+
+/// Writes packet with the value at the end of the output.
+fn append_packet<F, T>(value: T, output: &mut Vec<u8>)
+where
+    F: Formula + ?Sized,
+    T: Serialize<F>,
+{
+    let mut packet = Vec::new();
+    let size = write_packet_to_vec::<F, T>(value, &mut packet);
+    output.extend_from_slice(&packet[..size]);
+}
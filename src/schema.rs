@@ -0,0 +1,492 @@
+//!
+//! This module provides serializable schema of formulas.
+//!
+
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
+
+use crate::{
+    buffer::Buffer,
+    descriptor::{
+        Descriptor, DescriptorKind, FieldDescriptor, LazyDescriptor, KIND_ARRAY, KIND_BOOL,
+        KIND_BYTES, KIND_ENUM, KIND_FLOAT, KIND_OPAQUE, KIND_OPTION, KIND_REF, KIND_SIGNED,
+        KIND_SLICE, KIND_STR, KIND_STRUCT, KIND_TUPLE, KIND_UNSIGNED, KIND_VLQ,
+    },
+    deserialize::{Deserialize, DeserializeError, Deserializer},
+    formula::Formula,
+    iter::SerIter,
+    serialize::{Serialize, Sizes},
+};
+
+/// Field of a structure or an enum variant in [`Schema`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SchemaField {
+    /// Name of the field.
+    pub name: String,
+
+    /// Index of the field formula node.
+    pub formula: usize,
+}
+
+/// Enum variant in [`Schema`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SchemaVariant {
+    /// Name of the variant.
+    pub name: String,
+
+    /// Tag that is written before variant's fields.
+    pub tag: u32,
+
+    /// Fields of the variant.
+    pub fields: Vec<SchemaField>,
+}
+
+/// Structure of a formula node in [`Schema`].
+///
+/// Mirrors [`DescriptorKind`] with nested formulas
+/// referred by node index.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SchemaKind {
+    /// Formula that does not describe its structure.
+    Opaque,
+
+    /// Boolean value encoded in a single byte.
+    Bool,
+
+    /// Little-endian unsigned integer of specified size in bytes.
+    Unsigned(usize),
+
+    /// Little-endian signed integer of specified size in bytes.
+    Signed(usize),
+
+    /// Little-endian IEEE 754 floating point number of specified size in bytes.
+    Float(usize),
+
+    /// Variable-length quantity.
+    Vlq,
+
+    /// Raw bytes that occupy the whole value.
+    Bytes,
+
+    /// UTF-8 string that occupies the whole value.
+    Str,
+
+    /// Array of fixed number of elements.
+    Array(usize, usize),
+
+    /// Slice of elements.
+    Slice(usize),
+
+    /// Reference to a value placed in the heap.
+    Ref(usize),
+
+    /// Optional value prefixed with presence byte.
+    Option(usize),
+
+    /// Tuple of formulas.
+    Tuple(Vec<usize>),
+
+    /// Structure with fields.
+    Struct(Vec<SchemaField>),
+
+    /// Enum with variants.
+    Enum(Vec<SchemaVariant>),
+}
+
+impl SchemaKind {
+    /// Returns name of the kind.
+    #[must_use]
+    pub fn name(&self) -> &'static str {
+        match self {
+            SchemaKind::Opaque => "opaque",
+            SchemaKind::Bool => "bool",
+            SchemaKind::Unsigned(_) => "unsigned",
+            SchemaKind::Signed(_) => "signed",
+            SchemaKind::Float(_) => "float",
+            SchemaKind::Vlq => "vlq",
+            SchemaKind::Bytes => "bytes",
+            SchemaKind::Str => "str",
+            SchemaKind::Array(..) => "array",
+            SchemaKind::Slice(_) => "slice",
+            SchemaKind::Ref(_) => "ref",
+            SchemaKind::Option(_) => "option",
+            SchemaKind::Tuple(_) => "tuple",
+            SchemaKind::Struct(_) => "struct",
+            SchemaKind::Enum(_) => "enum",
+        }
+    }
+}
+
+/// Node of [`Schema`] that describes one formula.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SchemaNode {
+    /// Name of the formula type.
+    /// Informative only and should not be relied upon.
+    pub name: String,
+
+    /// Value of [`Formula::MAX_STACK_SIZE`].
+    pub max_stack_size: Option<usize>,
+
+    /// Value of [`Formula::EXACT_SIZE`].
+    pub exact_size: bool,
+
+    /// Value of [`Formula::HEAPLESS`].
+    pub heapless: bool,
+
+    /// Structure of the formula.
+    pub kind: SchemaKind,
+}
+
+/// Owned and serializable description of a formula.
+///
+/// Unlike [`Descriptor`] it does not refer to formula types
+/// and can be stored alongside the data or sent to a peer
+/// that does not have the original types.
+///
+/// Formulas are stored as a flat list of nodes.
+/// Nested formulas are referred by node index,
+/// so recursive formulas are described with cycles.
+/// Root formula is the first node.
+///
+/// `Schema` is a formula as well and can be serialized with itself.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// let schema = Schema::of::<(u32, Vec<u8>)>();
+///
+/// let mut buffer = [0u8; 1024];
+/// let (size, root) = serialize::<Schema, _>(&schema, &mut buffer).unwrap();
+/// let de = deserialize_with_size::<Schema, Schema>(&buffer[..size], root).unwrap();
+///
+/// assert_eq!(schema, de);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Schema {
+    /// Nodes of the schema. Root formula is the first one.
+    pub nodes: Vec<SchemaNode>,
+}
+
+impl Schema {
+    /// Returns schema of the formula `F`.
+    #[must_use]
+    pub fn of<F>() -> Self
+    where
+        F: Formula + ?Sized,
+    {
+        let mut builder = SchemaBuilder {
+            nodes: Vec::new(),
+            indices: BTreeMap::new(),
+        };
+        builder.add(LazyDescriptor::of::<F>());
+        Schema {
+            nodes: builder.nodes,
+        }
+    }
+
+    /// Returns root node of the schema.
+    ///
+    /// # Panics
+    ///
+    /// Panics if schema is empty.
+    #[must_use]
+    #[inline(always)]
+    pub fn root(&self) -> &SchemaNode {
+        &self.nodes[0]
+    }
+}
+
+struct SchemaBuilder {
+    nodes: Vec<SchemaNode>,
+    indices: BTreeMap<&'static str, usize>,
+}
+
+impl SchemaBuilder {
+    fn add(&mut self, formula: LazyDescriptor) -> usize {
+        let descriptor: Descriptor = formula.get();
+        if let Some(&idx) = self.indices.get(descriptor.name) {
+            return idx;
+        }
+
+        // Reserve the node before descending so that
+        // recursive formulas refer to it.
+        let idx = self.nodes.len();
+        self.indices.insert(descriptor.name, idx);
+        self.nodes.push(SchemaNode {
+            name: descriptor.name.into(),
+            max_stack_size: descriptor.max_stack_size,
+            exact_size: descriptor.exact_size,
+            heapless: descriptor.heapless,
+            kind: SchemaKind::Opaque,
+        });
+
+        let kind = match descriptor.kind {
+            DescriptorKind::Opaque => SchemaKind::Opaque,
+            DescriptorKind::Bool => SchemaKind::Bool,
+            DescriptorKind::Unsigned(size) => SchemaKind::Unsigned(size),
+            DescriptorKind::Signed(size) => SchemaKind::Signed(size),
+            DescriptorKind::Float(size) => SchemaKind::Float(size),
+            DescriptorKind::Vlq => SchemaKind::Vlq,
+            DescriptorKind::Bytes => SchemaKind::Bytes,
+            DescriptorKind::Str => SchemaKind::Str,
+            DescriptorKind::Array(elem, len) => SchemaKind::Array(self.add(elem), len),
+            DescriptorKind::Slice(elem) => SchemaKind::Slice(self.add(elem)),
+            DescriptorKind::Ref(formula) => SchemaKind::Ref(self.add(formula)),
+            DescriptorKind::Option(formula) => SchemaKind::Option(self.add(formula)),
+            DescriptorKind::Tuple(elems) => {
+                SchemaKind::Tuple(elems.iter().map(|elem| self.add(*elem)).collect())
+            }
+            DescriptorKind::Struct(fields) => SchemaKind::Struct(self.fields(fields)),
+            DescriptorKind::Enum(variants) => SchemaKind::Enum(
+                variants
+                    .iter()
+                    .map(|variant| SchemaVariant {
+                        name: variant.name.into(),
+                        tag: variant.tag,
+                        fields: self.fields(variant.fields),
+                    })
+                    .collect(),
+            ),
+        };
+
+        self.nodes[idx].kind = kind;
+        idx
+    }
+
+    fn fields(&mut self, fields: &[FieldDescriptor]) -> Vec<SchemaField> {
+        fields
+            .iter()
+            .map(|field| SchemaField {
+                name: field.name.into(),
+                formula: self.add(field.formula),
+            })
+            .collect()
+    }
+}
+
+type FieldFormula = (String, usize);
+type VariantFormula = (String, u32, Vec<FieldFormula>);
+
+/// Formula of a schema node.
+/// Name, stack size, exact size and heapless flags, kind tag,
+/// size or length, nested nodes, fields and variants.
+type NodeFormula = (
+    String,
+    Option<usize>,
+    bool,
+    bool,
+    u8,
+    usize,
+    Vec<usize>,
+    Vec<FieldFormula>,
+    Vec<VariantFormula>,
+);
+
+type NodeValue = (
+    String,
+    Option<usize>,
+    bool,
+    bool,
+    u8,
+    usize,
+    Vec<usize>,
+    Vec<(String, usize)>,
+    Vec<(String, u32, Vec<(String, usize)>)>,
+);
+
+impl Formula for Schema {
+    const MAX_STACK_SIZE: Option<usize> = <Vec<NodeFormula> as Formula>::MAX_STACK_SIZE;
+    const EXACT_SIZE: bool = <Vec<NodeFormula> as Formula>::EXACT_SIZE;
+    const HEAPLESS: bool = <Vec<NodeFormula> as Formula>::HEAPLESS;
+
+    #[inline(always)]
+    fn describe() -> Descriptor {
+        <Vec<NodeFormula> as Formula>::describe()
+    }
+}
+
+impl Serialize<Schema> for &Schema {
+    #[inline]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        <_ as Serialize<Vec<NodeFormula>>>::serialize(
+            self.nodes.iter().map(node_fields),
+            sizes,
+            buffer,
+        )
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        None
+    }
+}
+
+impl Serialize<Schema> for Schema {
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        <&Schema as Serialize<Schema>>::serialize(&self, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        None
+    }
+}
+
+impl<'de> Deserialize<'de, Schema> for Schema {
+    #[inline]
+    fn deserialize(de: Deserializer<'de>) -> Result<Self, DeserializeError> {
+        let nodes = <Vec<NodeValue> as Deserialize<'de, Vec<NodeFormula>>>::deserialize(de)?;
+        let nodes = nodes
+            .into_iter()
+            .map(node_from_fields)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let len = nodes.len();
+        if len == 0 || nodes.iter().any(|node| !node_children_valid(node, len)) {
+            return Err(DeserializeError::Incompatible);
+        }
+
+        Ok(Schema { nodes })
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(&mut self, de: Deserializer<'de>) -> Result<(), DeserializeError> {
+        *self = <Schema as Deserialize<'de, Schema>>::deserialize(de)?;
+        Ok(())
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn node_fields(
+    node: &SchemaNode,
+) -> (
+    &str,
+    Option<usize>,
+    bool,
+    bool,
+    u8,
+    usize,
+    &[usize],
+    SerIter<impl Iterator<Item = (&str, usize)>>,
+    SerIter<impl Iterator<Item = (&str, u32, SerIter<impl Iterator<Item = (&str, usize)>>)>>,
+) {
+    let (kind, size, children, fields, variants): (_, _, &[usize], &[SchemaField], &[_]) =
+        match &node.kind {
+            SchemaKind::Opaque => (KIND_OPAQUE, 0, &[], &[], &[]),
+            SchemaKind::Bool => (KIND_BOOL, 0, &[], &[], &[]),
+            SchemaKind::Unsigned(size) => (KIND_UNSIGNED, *size, &[], &[], &[]),
+            SchemaKind::Signed(size) => (KIND_SIGNED, *size, &[], &[], &[]),
+            SchemaKind::Float(size) => (KIND_FLOAT, *size, &[], &[], &[]),
+            SchemaKind::Vlq => (KIND_VLQ, 0, &[], &[], &[]),
+            SchemaKind::Bytes => (KIND_BYTES, 0, &[], &[], &[]),
+            SchemaKind::Str => (KIND_STR, 0, &[], &[], &[]),
+            SchemaKind::Array(elem, len) => {
+                (KIND_ARRAY, *len, core::slice::from_ref(elem), &[], &[])
+            }
+            SchemaKind::Slice(elem) => (KIND_SLICE, 0, core::slice::from_ref(elem), &[], &[]),
+            SchemaKind::Ref(formula) => (KIND_REF, 0, core::slice::from_ref(formula), &[], &[]),
+            SchemaKind::Option(formula) => {
+                (KIND_OPTION, 0, core::slice::from_ref(formula), &[], &[])
+            }
+            SchemaKind::Tuple(elems) => (KIND_TUPLE, 0, elems, &[], &[]),
+            SchemaKind::Struct(fields) => (KIND_STRUCT, 0, &[], fields, &[]),
+            SchemaKind::Enum(variants) => (KIND_ENUM, 0, &[], &[], variants),
+        };
+
+    (
+        &node.name,
+        node.max_stack_size,
+        node.exact_size,
+        node.heapless,
+        kind,
+        size,
+        children,
+        SerIter(fields.iter().map(field_fields)),
+        SerIter(variants.iter().map(|variant: &SchemaVariant| {
+            (
+                variant.name.as_str(),
+                variant.tag,
+                SerIter(variant.fields.iter().map(field_fields)),
+            )
+        })),
+    )
+}
+
+fn field_fields(field: &SchemaField) -> (&str, usize) {
+    (&field.name, field.formula)
+}
+
+fn node_from_fields(
+    (name, max_stack_size, exact_size, heapless, kind, size, children, fields, variants): NodeValue,
+) -> Result<SchemaNode, DeserializeError> {
+    let child = || match *children {
+        [child] => Ok(child),
+        _ => Err(DeserializeError::Incompatible),
+    };
+
+    let fields_from = |fields: Vec<(String, usize)>| {
+        fields
+            .into_iter()
+            .map(|(name, formula)| SchemaField { name, formula })
+            .collect()
+    };
+
+    let kind = match kind {
+        KIND_OPAQUE => SchemaKind::Opaque,
+        KIND_BOOL => SchemaKind::Bool,
+        KIND_UNSIGNED => SchemaKind::Unsigned(size),
+        KIND_SIGNED => SchemaKind::Signed(size),
+        KIND_FLOAT => SchemaKind::Float(size),
+        KIND_VLQ => SchemaKind::Vlq,
+        KIND_BYTES => SchemaKind::Bytes,
+        KIND_STR => SchemaKind::Str,
+        KIND_ARRAY => SchemaKind::Array(child()?, size),
+        KIND_SLICE => SchemaKind::Slice(child()?),
+        KIND_REF => SchemaKind::Ref(child()?),
+        KIND_OPTION => SchemaKind::Option(child()?),
+        KIND_TUPLE => SchemaKind::Tuple(children),
+        KIND_STRUCT => SchemaKind::Struct(fields_from(fields)),
+        KIND_ENUM => SchemaKind::Enum(
+            variants
+                .into_iter()
+                .map(|(name, tag, fields)| SchemaVariant {
+                    name,
+                    tag,
+                    fields: fields_from(fields),
+                })
+                .collect(),
+        ),
+        _ => return Err(DeserializeError::Incompatible),
+    };
+
+    Ok(SchemaNode {
+        name,
+        max_stack_size,
+        exact_size,
+        heapless,
+        kind,
+    })
+}
+
+/// Checks that all nested nodes are present in schema with `len` nodes.
+pub(crate) fn node_children_valid(node: &SchemaNode, len: usize) -> bool {
+    let fields_valid = |fields: &[SchemaField]| fields.iter().all(|field| field.formula < len);
+
+    match &node.kind {
+        SchemaKind::Array(elem, _)
+        | SchemaKind::Slice(elem)
+        | SchemaKind::Ref(elem)
+        | SchemaKind::Option(elem) => *elem < len,
+        SchemaKind::Tuple(elems) => elems.iter().all(|elem| *elem < len),
+        SchemaKind::Struct(fields) => fields_valid(fields),
+        SchemaKind::Enum(variants) => variants.iter().all(|variant| fields_valid(&variant.fields)),
+        _ => true,
+    }
+}
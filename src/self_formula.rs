@@ -0,0 +1,108 @@
+use crate::{
+    buffer::BufferExhausted,
+    deserialize::{Deserialize, DeserializeError},
+    formula::Formula,
+    packet::{packet_size, read_packet, read_packet_in_place, write_packet},
+    serialize::Serialize,
+};
+
+#[cfg(feature = "alloc")]
+use crate::{packet::write_packet_to_vec, validate::read_packet_validated};
+
+/// Convenience methods to write packets with types that are their own formula,
+/// without specifying the formula explicitly.
+///
+/// Implemented for all such types, including derived ones.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// type Point = (i32, i32);
+///
+/// let bytes = (1, 2).alkahest_to_vec();
+/// assert_eq!(Point::alkahest_from(&bytes).unwrap(), (1, 2));
+/// ```
+pub trait SerializeSelf: Formula + Serialize<Self> + Sized {
+    /// Returns the number of bytes required to write packet with the value.
+    ///
+    /// See [`packet_size`].
+    #[inline(always)]
+    fn alkahest_packet_size(self) -> usize {
+        packet_size::<Self, Self>(self)
+    }
+
+    /// Writes packet with the value into output buffer.
+    /// Returns the number of bytes written.
+    ///
+    /// See [`write_packet`].
+    ///
+    /// # Errors
+    ///
+    /// Returns `BufferExhausted` if the buffer is too small.
+    #[inline(always)]
+    fn alkahest_write(self, output: &mut [u8]) -> Result<usize, BufferExhausted> {
+        write_packet::<Self, Self>(self, output)
+    }
+
+    /// Writes packet with the value into new vector.
+    #[cfg(feature = "alloc")]
+    #[must_use]
+    #[inline]
+    fn alkahest_to_vec(self) -> alloc::vec::Vec<u8> {
+        let mut output = alloc::vec::Vec::new();
+        let size = write_packet_to_vec::<Self, Self>(self, &mut output);
+        output.truncate(size);
+        output
+    }
+}
+
+impl<T> SerializeSelf for T where T: Formula + Serialize<T> {}
+
+/// Convenience methods to read packets with types that are their own formula,
+/// without specifying the formula explicitly.
+///
+/// Implemented for all such types, including derived ones.
+pub trait DeserializeSelf<'de>: Formula + Deserialize<'de, Self> + Sized {
+    /// Reads packet with the value from the input.
+    ///
+    /// See [`read_packet`].
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError` if deserialization fails.
+    #[inline(always)]
+    fn alkahest_from(input: &'de [u8]) -> Result<Self, DeserializeError> {
+        let (value, _) = read_packet::<Self, Self>(input)?;
+        Ok(value)
+    }
+
+    /// Reads packet with the value from the input after validating it.
+    ///
+    /// See [`read_packet_validated`].
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError` if validation or deserialization fails.
+    #[cfg(feature = "alloc")]
+    #[inline(always)]
+    fn alkahest_from_validated(input: &'de [u8]) -> Result<Self, DeserializeError> {
+        let (value, _) = read_packet_validated::<Self, Self>(input)?;
+        Ok(value)
+    }
+
+    /// Reads packet with the value from the input in-place.
+    /// Returns number of bytes consumed.
+    ///
+    /// See [`read_packet_in_place`].
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError` if deserialization fails.
+    #[inline(always)]
+    fn alkahest_from_in_place(&mut self, input: &'de [u8]) -> Result<usize, DeserializeError> {
+        read_packet_in_place::<Self, Self>(self, input)
+    }
+}
+
+impl<'de, T> DeserializeSelf<'de> for T where T: Formula + Deserialize<'de, T> {}
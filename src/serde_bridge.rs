@@ -0,0 +1,1470 @@
+//!
+//! This module provides bridge between `serde` and alkahest formulas.
+//!
+
+use alloc::{
+    borrow::ToOwned,
+    boxed::Box,
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{fmt, marker::PhantomData};
+
+use serde::{
+    de::{self, IntoDeserializer},
+    ser,
+};
+
+use crate::{
+    buffer::Buffer,
+    bytes::Bytes,
+    descriptor::Descriptor,
+    deserialize::{Deserialize, DeserializeError, Deserializer, ErrorDetails},
+    formula::Formula,
+    schema::{Schema, SchemaField, SchemaKind, SchemaNode, SchemaVariant},
+    serialize::{Serialize, Sizes},
+    value::{slice_elements, write_value, Value},
+    vlq::Vlq,
+};
+
+/// Formula adapter that serializes any `serde` type with layout of formula `F`.
+///
+/// Value is converted with its `serde::Serialize` implementation
+/// and written as if it was serialized with `F`.
+/// Structs and tuples are matched by field names or positions,
+/// sequences become arrays or slices, maps become slices of key-value pairs,
+/// enum variants are matched by name.
+///
+/// Data written with the adapter can be deserialized with `F` directly.
+/// Any `serde::Deserialize` type can be read with the adapter
+/// from data written with `F`, see [`SerdeDeserializer`].
+///
+/// If value does not match the formula it will cause a panic.
+/// Use [`serde_to_value`] to check the value beforehand.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// let mut buffer = [0u8; 64];
+/// let (size, root) =
+///     serialize::<Serde<(u8, Vec<u16>)>, _>((1u8, vec![2u16, 3]), &mut buffer).unwrap();
+///
+/// let (a, b) = deserialize_with_size::<(u8, Vec<u16>), (u8, Vec<u16>)>(&buffer[..size], root)
+///     .unwrap();
+/// assert_eq!(a, 1);
+/// assert_eq!(b, [2, 3]);
+/// ```
+pub struct Serde<F: ?Sized> {
+    marker: PhantomData<fn(&F) -> &F>,
+}
+
+impl<F> Formula for Serde<F>
+where
+    F: Formula + ?Sized,
+{
+    const MAX_STACK_SIZE: Option<usize> = F::MAX_STACK_SIZE;
+    const EXACT_SIZE: bool = F::EXACT_SIZE;
+    const HEAPLESS: bool = F::HEAPLESS;
+
+    #[inline(always)]
+    fn describe() -> Descriptor {
+        F::describe()
+    }
+}
+
+impl<F, T> Serialize<Serde<F>> for T
+where
+    F: Formula + ?Sized,
+    T: serde::Serialize,
+{
+    #[inline]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        let schema = Schema::of::<F>();
+        let value = match serde_to_value(&schema, &self) {
+            Ok(value) => value,
+            Err(err) => panic!("Serde serialization error: {}", err),
+        };
+        write_value(&schema, 0, &value, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        None
+    }
+}
+
+/// Error of conversion between `serde` data model and alkahest formula.
+#[derive(Clone, Debug)]
+pub struct SerdeError {
+    message: String,
+    source: Option<DeserializeError>,
+}
+
+impl SerdeError {
+    #[inline(always)]
+    fn new(message: String) -> Self {
+        SerdeError {
+            message,
+            source: None,
+        }
+    }
+
+    /// Returns deserialization error of the underlying data if any.
+    #[must_use]
+    #[inline(always)]
+    pub fn deserialize_error(&self) -> Option<DeserializeError> {
+        self.source
+    }
+}
+
+impl From<DeserializeError> for SerdeError {
+    #[inline(always)]
+    fn from(err: DeserializeError) -> Self {
+        SerdeError {
+            message: format!("Deserialization error: {err}"),
+            source: Some(err),
+        }
+    }
+}
+
+impl fmt::Display for SerdeError {
+    #[inline(always)]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl ser::StdError for SerdeError {}
+
+impl de::Error for SerdeError {
+    #[inline(always)]
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        SerdeError::new(msg.to_string())
+    }
+}
+
+impl ser::Error for SerdeError {
+    #[inline(always)]
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        SerdeError::new(msg.to_string())
+    }
+}
+
+/// Converts `serde` value into [`Value`] with layout described by the schema.
+/// Resulting value can be written as formula described by the schema.
+///
+/// See [`Serde`] for conversion rules.
+///
+/// # Errors
+///
+/// Returns `SerdeError` if value does not match the schema.
+pub fn serde_to_value<T>(schema: &Schema, value: &T) -> Result<Value, SerdeError>
+where
+    T: serde::Serialize + ?Sized,
+{
+    let value = serde::Serialize::serialize(value, ValueSerializer)?;
+    conform(schema, 0, value)
+}
+
+fn node(schema: &Schema, idx: usize) -> Result<&SchemaNode, SerdeError> {
+    schema
+        .nodes
+        .get(idx)
+        .ok_or_else(|| SerdeError::new("Malformed schema".to_owned()))
+}
+
+fn mismatch(node: &SchemaNode, value: &Value) -> SerdeError {
+    let found = match value {
+        Value::Opaque(_) => "opaque bytes",
+        Value::Bool(_) => "bool",
+        Value::Unsigned(_) => "unsigned integer",
+        Value::Signed(_) => "signed integer",
+        Value::Float(_) => "float",
+        Value::Bytes(_) => "bytes",
+        Value::Str(_) => "string",
+        Value::Array(_) => "sequence",
+        Value::Option(_) => "option",
+        Value::Tuple(_) => "tuple",
+        Value::Struct(_) => "struct",
+        Value::Enum { .. } => "enum",
+    };
+    SerdeError::new(format!(
+        "Formula `{}` of kind {} can't hold {found}",
+        node.name,
+        node.kind.name()
+    ))
+}
+
+/// Converts loosely typed value produced from `serde` data model
+/// into value that exactly matches the schema node.
+fn conform(schema: &Schema, idx: usize, value: Value) -> Result<Value, SerdeError> {
+    let current = node(schema, idx)?;
+
+    // Present optional value written to non-optional formula.
+    let value = match (&current.kind, value) {
+        (SchemaKind::Option(_) | SchemaKind::Ref(_), value) => value,
+        (_, Value::Option(Some(value))) => *value,
+        (_, value) => value,
+    };
+
+    let value = match (&current.kind, value) {
+        (SchemaKind::Bool, Value::Bool(value)) => Value::Bool(value),
+        (SchemaKind::Unsigned(size), value) => {
+            let unsigned = unsigned(current, &value)?;
+            if *size < 16 && unsigned >> (size * 8) != 0 {
+                return Err(out_of_range(current, &value));
+            }
+            Value::Unsigned(unsigned)
+        }
+        (SchemaKind::Signed(size), value) => {
+            let signed = match value {
+                Value::Signed(signed) => signed,
+                Value::Unsigned(unsigned) => {
+                    i128::try_from(unsigned).map_err(|_| out_of_range(current, &value))?
+                }
+                value => return Err(mismatch(current, &value)),
+            };
+            if *size < 16 {
+                let bound = 1i128 << (size * 8 - 1);
+                if signed < -bound || signed >= bound {
+                    return Err(out_of_range(current, &value));
+                }
+            }
+            Value::Signed(signed)
+        }
+        (SchemaKind::Float(_), Value::Float(value)) => Value::Float(value),
+        (SchemaKind::Vlq, value) => Value::Unsigned(unsigned(current, &value)?),
+        (SchemaKind::Bytes, Value::Bytes(bytes)) => Value::Bytes(bytes),
+        (SchemaKind::Bytes, Value::Str(string)) => Value::Bytes(string.into_bytes()),
+        (SchemaKind::Bytes, Value::Array(elems) | Value::Tuple(elems)) => {
+            let bytes = elems
+                .iter()
+                .map(|elem| match elem {
+                    Value::Unsigned(byte) => u8::try_from(*byte).ok(),
+                    _ => None,
+                })
+                .collect::<Option<Vec<u8>>>();
+            match bytes {
+                Some(bytes) => Value::Bytes(bytes),
+                None => return Err(mismatch(current, &Value::Array(elems))),
+            }
+        }
+        (SchemaKind::Str, Value::Str(string)) => Value::Str(string),
+        (SchemaKind::Array(elem, len), value) => {
+            let elems = elements(current, value)?;
+            if elems.len() != *len {
+                return Err(SerdeError::new(format!(
+                    "Formula `{}` expects {len} elements, found {}",
+                    current.name,
+                    elems.len()
+                )));
+            }
+            Value::Array(conform_all(schema, *elem, elems)?)
+        }
+        (SchemaKind::Slice(elem), value) => {
+            Value::Array(conform_all(schema, *elem, elements(current, value)?)?)
+        }
+        (SchemaKind::Ref(formula), value) => conform(schema, *formula, value)?,
+        (SchemaKind::Option(_), Value::Option(None)) => Value::Option(None),
+        (SchemaKind::Option(formula), Value::Option(Some(value))) => {
+            Value::Option(Some(Box::new(conform(schema, *formula, *value)?)))
+        }
+        (SchemaKind::Tuple(elems), value) => {
+            let fields = elems
+                .iter()
+                .enumerate()
+                .map(|(idx, elem)| SchemaField {
+                    name: idx.to_string(),
+                    formula: *elem,
+                })
+                .collect::<Vec<_>>();
+
+            let values = conform_fields(schema, current, &fields, fields_of(value))?;
+            Value::Tuple(values.into_iter().map(|(_, value)| value).collect())
+        }
+        (SchemaKind::Struct(fields), value) => {
+            Value::Struct(conform_fields(schema, current, fields, fields_of(value))?)
+        }
+        (
+            SchemaKind::Enum(variants),
+            Value::Enum {
+                name,
+                tag: _,
+                fields,
+            },
+        ) => {
+            let Some(variant) = variants.iter().find(|variant| variant.name == name) else {
+                return Err(SerdeError::new(format!(
+                    "Formula `{}` has no variant `{name}`",
+                    current.name
+                )));
+            };
+            Value::Enum {
+                name,
+                tag: variant.tag,
+                fields: conform_fields(schema, current, &variant.fields, fields)?,
+            }
+        }
+        (_, value) => return Err(mismatch(current, &value)),
+    };
+    Ok(value)
+}
+
+fn out_of_range(node: &SchemaNode, value: &Value) -> SerdeError {
+    SerdeError::new(format!(
+        "Value {value:?} is out of range of formula `{}`",
+        node.name
+    ))
+}
+
+fn unsigned(node: &SchemaNode, value: &Value) -> Result<u128, SerdeError> {
+    match *value {
+        Value::Unsigned(unsigned) => Ok(unsigned),
+        Value::Signed(signed) => u128::try_from(signed).map_err(|_| out_of_range(node, value)),
+        ref value => Err(mismatch(node, value)),
+    }
+}
+
+fn elements(node: &SchemaNode, value: Value) -> Result<Vec<Value>, SerdeError> {
+    match value {
+        Value::Array(elems) | Value::Tuple(elems) => Ok(elems),
+        Value::Bytes(bytes) => Ok(bytes
+            .into_iter()
+            .map(|byte| Value::Unsigned(byte.into()))
+            .collect()),
+        value => Err(mismatch(node, &value)),
+    }
+}
+
+fn conform_all(schema: &Schema, elem: usize, elems: Vec<Value>) -> Result<Vec<Value>, SerdeError> {
+    elems
+        .into_iter()
+        .map(|value| conform(schema, elem, value))
+        .collect()
+}
+
+/// Returns fields of tuple-like or struct-like value.
+/// Any other value is treated as a single field of newtype.
+fn fields_of(value: Value) -> Vec<(String, Value)> {
+    match value {
+        Value::Tuple(elems) => positional(elems),
+        Value::Struct(fields) => fields,
+        value => positional(alloc::vec![value]),
+    }
+}
+
+fn positional(elems: Vec<Value>) -> Vec<(String, Value)> {
+    elems
+        .into_iter()
+        .enumerate()
+        .map(|(idx, value)| (idx.to_string(), value))
+        .collect()
+}
+
+fn is_positional(fields: &[(String, Value)]) -> bool {
+    fields
+        .iter()
+        .enumerate()
+        .all(|(idx, (name, _))| *name == idx.to_string())
+}
+
+/// Matches value fields with schema fields and conforms them.
+/// Fields are matched by name, or by position if either side is tuple-like.
+fn conform_fields(
+    schema: &Schema,
+    current: &SchemaNode,
+    fields: &[SchemaField],
+    mut values: Vec<(String, Value)>,
+) -> Result<Vec<(String, Value)>, SerdeError> {
+    // Newtype wrapping all fields, e.g. newtype enum variant.
+    if fields.len() != 1 && values.len() == 1 && values[0].0 == "0" {
+        let (_, value) = values.pop().unwrap();
+        values = fields_of(value);
+    }
+
+    let by_position = fields.len() == 1
+        || is_positional(&values)
+        || fields
+            .iter()
+            .enumerate()
+            .all(|(idx, field)| field.name == idx.to_string());
+
+    if by_position {
+        if values.len() != fields.len() {
+            return Err(SerdeError::new(format!(
+                "Formula `{}` expects {} fields, found {}",
+                current.name,
+                fields.len(),
+                values.len()
+            )));
+        }
+        return fields
+            .iter()
+            .zip(values)
+            .map(|(field, (_, value))| {
+                Ok((field.name.clone(), conform(schema, field.formula, value)?))
+            })
+            .collect();
+    }
+
+    if let Some((name, _)) = values
+        .iter()
+        .find(|(name, _)| fields.iter().all(|field| field.name != *name))
+    {
+        return Err(SerdeError::new(format!(
+            "Formula `{}` has no field `{name}`",
+            current.name
+        )));
+    }
+
+    fields
+        .iter()
+        .map(|field| {
+            let Some(pos) = values.iter().position(|(name, _)| *name == field.name) else {
+                return Err(SerdeError::new(format!(
+                    "Missing field `{}` of formula `{}`",
+                    field.name, current.name
+                )));
+            };
+            let (_, value) = values.swap_remove(pos);
+            Ok((field.name.clone(), conform(schema, field.formula, value)?))
+        })
+        .collect()
+}
+
+/// Serializer from `serde` data model into loosely typed [`Value`].
+struct ValueSerializer;
+
+struct SeqSerializer {
+    elems: Vec<Value>,
+    tuple: bool,
+}
+
+struct FieldsSerializer {
+    fields: Vec<(String, Value)>,
+    variant: Option<(&'static str, u32)>,
+}
+
+struct MapSerializer {
+    entries: Vec<Value>,
+    key: Option<Value>,
+}
+
+impl FieldsSerializer {
+    fn finish(self) -> Value {
+        match self.variant {
+            None => Value::Struct(self.fields),
+            Some((name, tag)) => Value::Enum {
+                name: name.to_owned(),
+                tag,
+                fields: self.fields,
+            },
+        }
+    }
+}
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = Value;
+    type Error = SerdeError;
+    type SerializeSeq = SeqSerializer;
+    type SerializeTuple = SeqSerializer;
+    type SerializeTupleStruct = SeqSerializer;
+    type SerializeTupleVariant = FieldsSerializer;
+    type SerializeMap = MapSerializer;
+    type SerializeStruct = FieldsSerializer;
+    type SerializeStructVariant = FieldsSerializer;
+
+    fn serialize_bool(self, v: bool) -> Result<Value, SerdeError> {
+        Ok(Value::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Value, SerdeError> {
+        Ok(Value::Signed(v.into()))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Value, SerdeError> {
+        Ok(Value::Signed(v.into()))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Value, SerdeError> {
+        Ok(Value::Signed(v.into()))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Value, SerdeError> {
+        Ok(Value::Signed(v.into()))
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Value, SerdeError> {
+        Ok(Value::Signed(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Value, SerdeError> {
+        Ok(Value::Unsigned(v.into()))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Value, SerdeError> {
+        Ok(Value::Unsigned(v.into()))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Value, SerdeError> {
+        Ok(Value::Unsigned(v.into()))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Value, SerdeError> {
+        Ok(Value::Unsigned(v.into()))
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Value, SerdeError> {
+        Ok(Value::Unsigned(v))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Value, SerdeError> {
+        Ok(Value::Float(v.into()))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Value, SerdeError> {
+        Ok(Value::Float(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Value, SerdeError> {
+        Ok(Value::Str(v.to_string()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Value, SerdeError> {
+        Ok(Value::Str(v.to_owned()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Value, SerdeError> {
+        Ok(Value::Bytes(v.to_owned()))
+    }
+
+    fn serialize_none(self) -> Result<Value, SerdeError> {
+        Ok(Value::Option(None))
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<Value, SerdeError>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        Ok(Value::Option(Some(Box::new(serde::Serialize::serialize(
+            value, self,
+        )?))))
+    }
+
+    fn serialize_unit(self) -> Result<Value, SerdeError> {
+        Ok(Value::Tuple(Vec::new()))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Value, SerdeError> {
+        Ok(Value::Tuple(Vec::new()))
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Value, SerdeError> {
+        Ok(Value::Enum {
+            name: variant.to_owned(),
+            tag: variant_index,
+            fields: Vec::new(),
+        })
+    }
+
+    fn serialize_newtype_struct<T>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Value, SerdeError>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        serde::Serialize::serialize(value, self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Value, SerdeError>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        Ok(Value::Enum {
+            name: variant.to_owned(),
+            tag: variant_index,
+            fields: positional(alloc::vec![serde::Serialize::serialize(value, self)?]),
+        })
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqSerializer, SerdeError> {
+        Ok(SeqSerializer {
+            elems: Vec::with_capacity(len.unwrap_or(0)),
+            tuple: false,
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SeqSerializer, SerdeError> {
+        Ok(SeqSerializer {
+            elems: Vec::with_capacity(len),
+            tuple: true,
+        })
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SeqSerializer, SerdeError> {
+        self.serialize_tuple(len)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<FieldsSerializer, SerdeError> {
+        Ok(FieldsSerializer {
+            fields: Vec::with_capacity(len),
+            variant: Some((variant, variant_index)),
+        })
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<MapSerializer, SerdeError> {
+        Ok(MapSerializer {
+            entries: Vec::with_capacity(len.unwrap_or(0)),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<FieldsSerializer, SerdeError> {
+        Ok(FieldsSerializer {
+            fields: Vec::with_capacity(len),
+            variant: None,
+        })
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<FieldsSerializer, SerdeError> {
+        Ok(FieldsSerializer {
+            fields: Vec::with_capacity(len),
+            variant: Some((variant, variant_index)),
+        })
+    }
+}
+
+impl SeqSerializer {
+    fn push<T>(&mut self, value: &T) -> Result<(), SerdeError>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        self.elems
+            .push(serde::Serialize::serialize(value, ValueSerializer)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Value {
+        if self.tuple {
+            Value::Tuple(self.elems)
+        } else {
+            Value::Array(self.elems)
+        }
+    }
+}
+
+impl ser::SerializeSeq for SeqSerializer {
+    type Ok = Value;
+    type Error = SerdeError;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), SerdeError>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, SerdeError> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeTuple for SeqSerializer {
+    type Ok = Value;
+    type Error = SerdeError;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), SerdeError>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, SerdeError> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeTupleStruct for SeqSerializer {
+    type Ok = Value;
+    type Error = SerdeError;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), SerdeError>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, SerdeError> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeTupleVariant for FieldsSerializer {
+    type Ok = Value;
+    type Error = SerdeError;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), SerdeError>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        let name = self.fields.len().to_string();
+        self.fields
+            .push((name, serde::Serialize::serialize(value, ValueSerializer)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, SerdeError> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeStruct for FieldsSerializer {
+    type Ok = Value;
+    type Error = SerdeError;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), SerdeError>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        self.fields.push((
+            key.to_owned(),
+            serde::Serialize::serialize(value, ValueSerializer)?,
+        ));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, SerdeError> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeStructVariant for FieldsSerializer {
+    type Ok = Value;
+    type Error = SerdeError;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), SerdeError>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        self.fields.push((
+            key.to_owned(),
+            serde::Serialize::serialize(value, ValueSerializer)?,
+        ));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, SerdeError> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeMap for MapSerializer {
+    type Ok = Value;
+    type Error = SerdeError;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), SerdeError>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        self.key = Some(serde::Serialize::serialize(key, ValueSerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), SerdeError>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        let Some(key) = self.key.take() else {
+            return Err(SerdeError::new("Map value without key".to_owned()));
+        };
+        let value = serde::Serialize::serialize(value, ValueSerializer)?;
+        self.entries.push(Value::Tuple(alloc::vec![key, value]));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, SerdeError> {
+        Ok(Value::Array(self.entries))
+    }
+}
+
+impl<'de, F, T> Deserialize<'de, Serde<F>> for T
+where
+    F: Formula + ?Sized,
+    T: serde::Deserialize<'de>,
+{
+    #[inline]
+    fn deserialize(de: Deserializer<'de>) -> Result<Self, DeserializeError> {
+        let schema = Schema::of::<F>();
+        match T::deserialize(SerdeDeserializer::new(&schema, de)) {
+            Ok(value) => Ok(value),
+            Err(err) => Err(err.source.unwrap_or(DeserializeError::Incompatible)),
+        }
+    }
+
+    #[inline]
+    fn deserialize_in_place(&mut self, de: Deserializer<'de>) -> Result<(), DeserializeError> {
+        *self = <T as Deserialize<'de, Serde<F>>>::deserialize(de)?;
+        Ok(())
+    }
+}
+
+/// `serde::Deserializer` that reads value with layout described by the schema.
+///
+/// Structs are visited as maps with field names as keys,
+/// tuples, arrays and slices are visited as sequences,
+/// enum variants are identified by name and references are followed transparently.
+/// Strings and bytes are borrowed from the input.
+pub struct SerdeDeserializer<'a, 'de> {
+    schema: &'a Schema,
+    idx: usize,
+    de: Deserializer<'de>,
+}
+
+impl<'a, 'de> SerdeDeserializer<'a, 'de> {
+    /// Returns deserializer of the root value of the schema.
+    #[must_use]
+    #[inline(always)]
+    pub fn new(schema: &'a Schema, de: Deserializer<'de>) -> Self {
+        SerdeDeserializer { schema, idx: 0, de }
+    }
+
+    /// Follows references to the value.
+    fn resolve(mut self) -> Result<(&'a SchemaNode, Self), SerdeError> {
+        loop {
+            let current = node(self.schema, self.idx)?;
+            let SchemaKind::Ref(formula) = current.kind else {
+                return Ok((current, self));
+            };
+            let pointee = node(self.schema, formula)?;
+            self.de = self
+                .de
+                .deref_with(pointee.max_stack_size, pointee.exact_size)?;
+            self.idx = formula;
+        }
+    }
+
+    fn fields(self, items: Items<'a>) -> FieldsAccess<'a, 'de> {
+        FieldsAccess {
+            schema: self.schema,
+            de: self.de,
+            items,
+            value: None,
+        }
+    }
+}
+
+fn field<'a, 'de>(
+    schema: &'a Schema,
+    idx: usize,
+    de: &mut Deserializer<'de>,
+    last: bool,
+) -> Result<SerdeDeserializer<'a, 'de>, SerdeError> {
+    let field = node(schema, idx)?;
+    let de = de.read_field_with(field.max_stack_size, field.exact_size, last)?;
+    Ok(SerdeDeserializer { schema, idx, de })
+}
+
+fn malformed() -> SerdeError {
+    SerdeError::new("Malformed schema".to_owned())
+}
+
+impl<'de> de::Deserializer<'de> for SerdeDeserializer<'_, 'de> {
+    type Error = SerdeError;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, SerdeError>
+    where
+        V: de::Visitor<'de>,
+    {
+        let (current, mut this) = self.resolve()?;
+        let de = this.de.clone();
+
+        match &current.kind {
+            SchemaKind::Opaque => visitor.visit_borrowed_bytes(de.read_all_bytes()),
+            SchemaKind::Bool => visitor.visit_bool(<bool as Deserialize<bool>>::deserialize(de)?),
+            SchemaKind::Unsigned(1) => visitor.visit_u8(<u8 as Deserialize<u8>>::deserialize(de)?),
+            SchemaKind::Unsigned(2) => {
+                visitor.visit_u16(<u16 as Deserialize<u16>>::deserialize(de)?)
+            }
+            SchemaKind::Unsigned(4) => {
+                visitor.visit_u32(<u32 as Deserialize<u32>>::deserialize(de)?)
+            }
+            SchemaKind::Unsigned(8) => {
+                visitor.visit_u64(<u64 as Deserialize<u64>>::deserialize(de)?)
+            }
+            SchemaKind::Unsigned(16) => {
+                visitor.visit_u128(<u128 as Deserialize<u128>>::deserialize(de)?)
+            }
+            SchemaKind::Signed(1) => visitor.visit_i8(<i8 as Deserialize<i8>>::deserialize(de)?),
+            SchemaKind::Signed(2) => visitor.visit_i16(<i16 as Deserialize<i16>>::deserialize(de)?),
+            SchemaKind::Signed(4) => visitor.visit_i32(<i32 as Deserialize<i32>>::deserialize(de)?),
+            SchemaKind::Signed(8) => visitor.visit_i64(<i64 as Deserialize<i64>>::deserialize(de)?),
+            SchemaKind::Signed(16) => {
+                visitor.visit_i128(<i128 as Deserialize<i128>>::deserialize(de)?)
+            }
+            SchemaKind::Float(4) => visitor.visit_f32(<f32 as Deserialize<f32>>::deserialize(de)?),
+            SchemaKind::Float(8) => visitor.visit_f64(<f64 as Deserialize<f64>>::deserialize(de)?),
+            SchemaKind::Unsigned(_) | SchemaKind::Signed(_) | SchemaKind::Float(_) => {
+                Err(malformed())
+            }
+            SchemaKind::Vlq => {
+                let value = <u128 as Deserialize<Vlq>>::deserialize(de)?;
+                match u64::try_from(value) {
+                    Ok(value) => visitor.visit_u64(value),
+                    Err(_) => visitor.visit_u128(value),
+                }
+            }
+            SchemaKind::Bytes => {
+                visitor.visit_borrowed_bytes(<&[u8] as Deserialize<Bytes>>::deserialize(de)?)
+            }
+            SchemaKind::Str => {
+                visitor.visit_borrowed_str(<&str as Deserialize<str>>::deserialize(de)?)
+            }
+            SchemaKind::Array(elem, len) => visitor.visit_seq(this.fields(Items::Array {
+                elem: *elem,
+                remaining: *len,
+            })),
+            SchemaKind::Slice(elem) => visitor.visit_seq(Elements {
+                schema: this.schema,
+                elem: *elem,
+                iter: slice_elements(this.schema, *elem, de)?.into_iter(),
+            }),
+            SchemaKind::Ref(_) => Err(malformed()),
+            SchemaKind::Option(formula) => {
+                if !this.de.read_flag()? {
+                    visitor.visit_none()
+                } else {
+                    visitor.visit_some(field(this.schema, *formula, &mut this.de, true)?)
+                }
+            }
+            SchemaKind::Tuple(elems) => visitor.visit_seq(this.fields(Items::Tuple(elems.iter()))),
+            SchemaKind::Struct(fields) => {
+                FieldsDeserializer(this.fields(Items::Fields(fields.iter())))
+                    .deserialize_any(visitor)
+            }
+            SchemaKind::Enum(variants) => {
+                let tag = this.de.read_value::<u32, u32>(false)?;
+                let Some(variant) = variants.iter().find(|variant| variant.tag == tag) else {
+                    return Err(DeserializeError::WrongVariant(tag, ErrorDetails::new()).into());
+                };
+                visitor.visit_enum(VariantAccess {
+                    schema: this.schema,
+                    variant,
+                    de: this.de,
+                })
+            }
+        }
+    }
+
+    fn deserialize_char<V>(self, visitor: V) -> Result<V::Value, SerdeError>
+    where
+        V: de::Visitor<'de>,
+    {
+        let (current, this) = self.resolve()?;
+        match current.kind {
+            SchemaKind::Str => {
+                let string = <&str as Deserialize<str>>::deserialize(this.de)?;
+                let mut chars = string.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => visitor.visit_char(c),
+                    _ => visitor.visit_borrowed_str(string),
+                }
+            }
+            _ => this.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value, SerdeError>
+    where
+        V: de::Visitor<'de>,
+    {
+        let (current, this) = self.resolve()?;
+        match current.kind {
+            SchemaKind::Bytes | SchemaKind::Str => {
+                visitor.visit_borrowed_bytes(<&[u8] as Deserialize<Bytes>>::deserialize(this.de)?)
+            }
+            _ => this.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value, SerdeError>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, SerdeError>
+    where
+        V: de::Visitor<'de>,
+    {
+        let (current, this) = self.resolve()?;
+        match current.kind {
+            SchemaKind::Option(_) => this.deserialize_any(visitor),
+            _ => visitor.visit_some(this),
+        }
+    }
+
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value, SerdeError>
+    where
+        V: de::Visitor<'de>,
+    {
+        let (current, this) = self.resolve()?;
+        match &current.kind {
+            SchemaKind::Tuple(elems) if elems.is_empty() => visitor.visit_unit(),
+            SchemaKind::Struct(fields) if fields.is_empty() => visitor.visit_unit(),
+            _ => this.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_unit_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, SerdeError>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, SerdeError>
+    where
+        V: de::Visitor<'de>,
+    {
+        let (current, mut this) = self.resolve()?;
+        let inner = match &current.kind {
+            SchemaKind::Tuple(elems) if elems.len() == 1 => elems[0],
+            SchemaKind::Struct(fields) if fields.len() == 1 => fields[0].formula,
+            _ => return visitor.visit_newtype_struct(this),
+        };
+        visitor.visit_newtype_struct(field(this.schema, inner, &mut this.de, true)?)
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, SerdeError>
+    where
+        V: de::Visitor<'de>,
+    {
+        let (current, this) = self.resolve()?;
+        match &current.kind {
+            SchemaKind::Struct(fields) => {
+                visitor.visit_seq(this.fields(Items::Fields(fields.iter())))
+            }
+            _ => this.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value, SerdeError>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, SerdeError>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value, SerdeError>
+    where
+        V: de::Visitor<'de>,
+    {
+        let (current, this) = self.resolve()?;
+        let SchemaKind::Slice(elem) = current.kind else {
+            return this.deserialize_any(visitor);
+        };
+        let SchemaKind::Tuple(pair) = &node(this.schema, elem)?.kind else {
+            return this.deserialize_any(visitor);
+        };
+        let &[key, value] = pair.as_slice() else {
+            return this.deserialize_any(visitor);
+        };
+
+        visitor.visit_map(Pairs {
+            schema: this.schema,
+            key,
+            value,
+            iter: slice_elements(this.schema, elem, this.de)?.into_iter(),
+            pending: None,
+        })
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, SerdeError>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    #[inline(always)]
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 str string
+        struct enum identifier
+    }
+}
+
+/// Fields of a tuple, structure, enum variant or elements of an array.
+enum Items<'a> {
+    Array { elem: usize, remaining: usize },
+    Tuple(core::slice::Iter<'a, usize>),
+    Fields(core::slice::Iter<'a, SchemaField>),
+}
+
+impl<'a> Items<'a> {
+    /// Returns name, formula and whether it is the last field.
+    fn next(&mut self) -> Option<(&'a str, usize, bool)> {
+        match self {
+            Items::Array { elem, remaining } => {
+                *remaining = remaining.checked_sub(1)?;
+                Some(("", *elem, false))
+            }
+            Items::Tuple(elems) => {
+                let elem = elems.next()?;
+                Some(("", *elem, elems.len() == 0))
+            }
+            Items::Fields(fields) => {
+                let field = fields.next()?;
+                Some((&field.name, field.formula, fields.len() == 0))
+            }
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Items::Array { remaining, .. } => *remaining,
+            Items::Tuple(elems) => elems.len(),
+            Items::Fields(fields) => fields.len(),
+        }
+    }
+
+    fn is_positional(&self) -> bool {
+        match self {
+            Items::Fields(fields) => fields
+                .as_slice()
+                .iter()
+                .enumerate()
+                .all(|(idx, field)| field.name == idx.to_string()),
+            _ => true,
+        }
+    }
+}
+
+struct FieldsAccess<'a, 'de> {
+    schema: &'a Schema,
+    de: Deserializer<'de>,
+    items: Items<'a>,
+    value: Option<SerdeDeserializer<'a, 'de>>,
+}
+
+impl<'a, 'de> FieldsAccess<'a, 'de> {
+    fn next_field(&mut self) -> Result<Option<(&'a str, SerdeDeserializer<'a, 'de>)>, SerdeError> {
+        let Some((name, idx, last)) = self.items.next() else {
+            return Ok(None);
+        };
+        let field = field(self.schema, idx, &mut self.de, last)?;
+        Ok(Some((name, field)))
+    }
+}
+
+impl<'de> de::SeqAccess<'de> for FieldsAccess<'_, 'de> {
+    type Error = SerdeError;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, SerdeError>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        match self.next_field()? {
+            None => Ok(None),
+            Some((_, field)) => seed.deserialize(field).map(Some),
+        }
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.items.len())
+    }
+}
+
+impl<'de> de::MapAccess<'de> for FieldsAccess<'_, 'de> {
+    type Error = SerdeError;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, SerdeError>
+    where
+        K: de::DeserializeSeed<'de>,
+    {
+        match self.next_field()? {
+            None => Ok(None),
+            Some((name, field)) => {
+                self.value = Some(field);
+                seed.deserialize(IntoDeserializer::<SerdeError>::into_deserializer(name))
+                    .map(Some)
+            }
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, SerdeError>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        match self.value.take() {
+            None => Err(SerdeError::new("Field value without key".to_owned())),
+            Some(field) => seed.deserialize(field),
+        }
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.items.len())
+    }
+}
+
+/// Deserializer of fields that do not have own schema node,
+/// e.g. fields of enum variant visited as newtype.
+struct FieldsDeserializer<'a, 'de>(FieldsAccess<'a, 'de>);
+
+impl<'de> de::Deserializer<'de> for FieldsDeserializer<'_, 'de> {
+    type Error = SerdeError;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, SerdeError>
+    where
+        V: de::Visitor<'de>,
+    {
+        if self.0.items.is_positional() {
+            visitor.visit_seq(self.0)
+        } else {
+            visitor.visit_map(self.0)
+        }
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, SerdeError>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_seq(self.0)
+    }
+
+    fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value, SerdeError>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_seq(self.0)
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, SerdeError>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_seq(self.0)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct map
+        struct enum identifier ignored_any
+    }
+}
+
+/// Elements of a slice.
+struct Elements<'a, 'de> {
+    schema: &'a Schema,
+    elem: usize,
+    iter: alloc::vec::IntoIter<Deserializer<'de>>,
+}
+
+impl<'de> de::SeqAccess<'de> for Elements<'_, 'de> {
+    type Error = SerdeError;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, SerdeError>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        match self.iter.next() {
+            None => Ok(None),
+            Some(de) => seed
+                .deserialize(SerdeDeserializer {
+                    schema: self.schema,
+                    idx: self.elem,
+                    de,
+                })
+                .map(Some),
+        }
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+/// Slice of key-value pairs visited as map.
+struct Pairs<'a, 'de> {
+    schema: &'a Schema,
+    key: usize,
+    value: usize,
+    iter: alloc::vec::IntoIter<Deserializer<'de>>,
+    pending: Option<SerdeDeserializer<'a, 'de>>,
+}
+
+impl<'de> de::MapAccess<'de> for Pairs<'_, 'de> {
+    type Error = SerdeError;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, SerdeError>
+    where
+        K: de::DeserializeSeed<'de>,
+    {
+        let Some(mut de) = self.iter.next() else {
+            return Ok(None);
+        };
+        let key = field(self.schema, self.key, &mut de, false)?;
+        self.pending = Some(field(self.schema, self.value, &mut de, true)?);
+        seed.deserialize(key).map(Some)
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, SerdeError>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        match self.pending.take() {
+            None => Err(SerdeError::new("Map value without key".to_owned())),
+            Some(value) => seed.deserialize(value),
+        }
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+/// Enum with variant tag already read.
+struct VariantAccess<'a, 'de> {
+    schema: &'a Schema,
+    variant: &'a SchemaVariant,
+    de: Deserializer<'de>,
+}
+
+impl<'a, 'de> VariantAccess<'a, 'de> {
+    fn fields(self) -> FieldsAccess<'a, 'de> {
+        FieldsAccess {
+            schema: self.schema,
+            de: self.de,
+            items: Items::Fields(self.variant.fields.iter()),
+            value: None,
+        }
+    }
+}
+
+impl<'a, 'de> de::EnumAccess<'de> for VariantAccess<'a, 'de> {
+    type Error = SerdeError;
+    type Variant = Self;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self), SerdeError>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        let name: &'a str = &self.variant.name;
+        let value = seed.deserialize(IntoDeserializer::<SerdeError>::into_deserializer(name))?;
+        Ok((value, self))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for VariantAccess<'_, 'de> {
+    type Error = SerdeError;
+
+    fn unit_variant(self) -> Result<(), SerdeError> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value, SerdeError>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        let mut fields = self.fields();
+        if fields.items.len() == 1 {
+            match fields.next_field()? {
+                Some((_, field)) => seed.deserialize(field),
+                None => Err(malformed()),
+            }
+        } else {
+            seed.deserialize(FieldsDeserializer(fields))
+        }
+    }
+
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value, SerdeError>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_seq(self.fields())
+    }
+
+    fn struct_variant<V>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, SerdeError>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_map(self.fields())
+    }
+}
@@ -9,7 +9,33 @@ use crate::{
 #[cfg(feature = "alloc")]
 use crate::buffer::VecBuffer;
 
-/// Heap and stack sizes.
+/// Heap and stack sizes of serialized data.
+///
+/// Serialized value occupies `heap + stack` bytes.
+/// Heap comes first and holds values placed behind references,
+/// e.g. elements of `Vec` or strings.
+/// Stack follows it and holds the value itself
+/// with references into the heap.
+///
+/// Returned by [`serialized_size_exact`] and [`size_hint`],
+/// so buffers for both parts can be planned before serialization.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// let value = (7u32, "hello");
+/// let sizes = serialized_size_exact::<(u32, Ref<str>), _>(value);
+/// assert_eq!(sizes.heap, 5);
+///
+/// let mut buffer = [0u8; 64];
+/// let (size, _) = serialize::<(u32, Ref<str>), _>(value, &mut buffer).unwrap();
+/// assert_eq!(size, sizes.total());
+///
+/// let (heap, stack) = sizes.split(&buffer[..size]).unwrap();
+/// assert_eq!(heap, b"hello");
+/// assert_eq!(stack.len(), sizes.stack);
+/// ```
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Sizes {
     /// Heap size.
@@ -59,10 +85,23 @@ impl Sizes {
     }
 
     /// Returns total size.
+    #[must_use]
     #[inline(always)]
-    pub fn total(&self) -> usize {
+    pub const fn total(&self) -> usize {
         self.heap + self.stack
     }
+
+    /// Splits serialized value into heap and stack bytes.
+    ///
+    /// Returns `None` if `bytes` length is not equal to total size.
+    #[must_use]
+    #[inline(always)]
+    pub fn split<'a>(&self, bytes: &'a [u8]) -> Option<(&'a [u8], &'a [u8])> {
+        if bytes.len() != self.total() {
+            return None;
+        }
+        Some(bytes.split_at(self.heap))
+    }
 }
 
 impl ops::Add for Sizes {
@@ -306,18 +345,104 @@ where
     }
 }
 
+impl<F, T> Serialize<F> for &mut T
+where
+    F: BareFormula + ?Sized,
+    T: ?Sized,
+    for<'a> &'a T: Serialize<F>,
+{
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        Self: Sized,
+        B: Buffer,
+    {
+        <&T as Serialize<F>>::serialize(self, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        <&T as Serialize<F>>::size_hint(&&**self)
+    }
+}
+
+impl<F, T> SerializeRef<F> for &mut T
+where
+    F: Formula + ?Sized,
+    T: ?Sized,
+    for<'a> &'a T: Serialize<F>,
+{
+    #[inline(always)]
+    fn serialize<B>(&self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        <&T as Serialize<F>>::serialize(self, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        <&T as Serialize<F>>::size_hint(&&**self)
+    }
+}
+
+/// Implements `SerializeRef` for smart pointers,
+/// so references to them serialize like references to the pointee.
+#[cfg(feature = "alloc")]
+macro_rules! impl_serialize_ref_pointer {
+    ($($pointer:ident)::+) => {
+        impl<F, T> SerializeRef<F> for $($pointer)::+<T>
+        where
+            F: Formula + ?Sized,
+            T: ?Sized,
+            for<'a> &'a T: Serialize<F>,
+        {
+            #[inline(always)]
+            fn serialize<B>(&self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+            where
+                B: Buffer,
+            {
+                <&T as Serialize<F>>::serialize(self, sizes, buffer)
+            }
+
+            #[inline(always)]
+            fn size_hint(&self) -> Option<Sizes> {
+                <&T as Serialize<F>>::size_hint(&&**self)
+            }
+        }
+    };
+}
+
+#[cfg(feature = "alloc")]
+impl_serialize_ref_pointer!(alloc::boxed::Box);
+
+#[cfg(feature = "alloc")]
+impl_serialize_ref_pointer!(alloc::rc::Rc);
+
+#[cfg(all(feature = "alloc", target_has_atomic = "ptr"))]
+impl_serialize_ref_pointer!(alloc::sync::Arc);
+
 /// Serialize value into buffer.
 /// Returns total number of bytes written and size of the root value.
 /// The buffer type controls bytes writing and failing strategy.
 #[inline(always)]
-pub fn serialize_into<F, T, B>(value: T, buffer: B) -> Result<(usize, usize), B::Error>
+pub fn serialize_into<F, T, B>(value: T, mut buffer: B) -> Result<(usize, usize), B::Error>
 where
     F: Formula + ?Sized,
     T: Serialize<F>,
     B: Buffer,
 {
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!("serialize", formula = core::any::type_name::<F>(),).entered();
+
     let mut sizes = Sizes { heap: 0, stack: 0 };
-    let size = write_ref(value, &mut sizes, buffer)?;
+    let size = write_ref(value, &mut sizes, buffer.reborrow())?;
+
+    #[cfg(feature = "metrics")]
+    if let Some(metrics) = buffer.metrics() {
+        metrics.record_root(size, sizes.heap - size);
+    }
+
     Ok((sizes.heap, size))
 }
 
@@ -361,12 +486,25 @@ where
 ///
 /// Contains the size of the buffer required to fit serialized data.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[repr(transparent)]
 pub struct BufferSizeRequired {
     /// Size of the buffer required to fit serialized data.
     pub required: usize,
 }
 
+impl BufferSizeRequired {
+    /// Returns stable numeric code of the error.
+    ///
+    /// Same as code of [`BufferExhausted`],
+    /// see [`DeserializeError::code`](crate::DeserializeError::code) for other codes.
+    #[must_use]
+    #[inline(always)]
+    pub const fn code(&self) -> u16 {
+        256
+    }
+}
+
 impl fmt::Display for BufferSizeRequired {
     #[inline(always)]
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -374,6 +512,16 @@ impl fmt::Display for BufferSizeRequired {
     }
 }
 
+#[cfg(feature = "std")]
+impl std::error::Error for BufferSizeRequired {}
+
+impl From<BufferSizeRequired> for BufferExhausted {
+    #[inline(always)]
+    fn from(_: BufferSizeRequired) -> Self {
+        BufferExhausted
+    }
+}
+
 /// Serialize value into bytes slice.
 /// Returns the number of bytes written.
 ///
@@ -412,9 +560,13 @@ where
 /// Returns the number of bytes written.
 ///
 /// Grows the vector if needed.
-/// Infallible except for allocation errors.
+/// Infallible except for allocation errors and data exceeding the size limit.
 ///
 /// Use pre-allocated vector when possible to avoid reallocations.
+///
+/// # Panics
+///
+/// Panics if serialized data exceeds [`PACKET_SIZE_LIMIT`](crate::PACKET_SIZE_LIMIT).
 #[cfg(feature = "alloc")]
 #[inline(always)]
 pub fn serialize_to_vec<F, T>(value: T, output: &mut alloc::vec::Vec<u8>) -> (usize, usize)
@@ -428,25 +580,125 @@ where
     }
 }
 
+/// Returns maximum number of bytes required to serialize any value with formula `F`.
+/// Can be used to size buffers at compile time.
+///
+/// # Panics
+///
+/// Panics if the size is not bounded, see [`Formula::MAX_SIZE`].
+/// In const context this is a compile-time error.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// let mut buffer = [0u8; max_size::<(u32, [u16; 2])>()];
+/// serialize::<(u32, [u16; 2]), _>((1u32, [2u16, 3]), &mut buffer).unwrap();
+/// ```
+#[must_use]
+#[inline(always)]
+pub const fn max_size<F>() -> usize
+where
+    F: Formula + ?Sized,
+{
+    match F::MAX_SIZE {
+        Some(size) => size,
+        None => panic!("Formula size is not bounded"),
+    }
+}
+
 /// Returns the number of bytes required to serialize the value.
 /// Note that value is consumed.
 ///
 /// Use when value is `Copy` or can be cheaply replicated to allocate
 /// the buffer for serialization in advance.
 /// Or to find out required size after [`serialize`] fails.
+///
+/// Returns total size and stack size.
+/// Use [`serialized_size_exact`] to get [`Sizes`] with heap and stack split.
 #[inline(always)]
 pub fn serialized_size<F, T>(value: T) -> (usize, usize)
+where
+    F: Formula + ?Sized,
+    T: Serialize<F>,
+{
+    let sizes = serialized_size_exact::<F, T>(value);
+    (sizes.total(), sizes.stack)
+}
+
+/// Returns heap and stack sizes of the value if they can be determined
+/// without serialization.
+///
+/// Cheap alternative to [`serialized_size_exact`] that does not consume the value,
+/// but may return `None` for values of unknown shape,
+/// e.g. iterators without known length.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// let sizes = size_hint::<(u32, Ref<str>), _>(&(7u32, "hello")).unwrap();
+/// assert_eq!(sizes.heap, 5);
+///
+/// let iter = SerIter((0..10u32).filter(|n| n % 3 == 0));
+/// assert_eq!(size_hint::<[u32], _>(&iter), None);
+/// ```
+#[must_use]
+#[inline(always)]
+pub fn size_hint<F, T>(value: &T) -> Option<Sizes>
+where
+    F: Formula + ?Sized,
+    T: Serialize<F> + ?Sized,
+{
+    <T as Serialize<F>>::size_hint(value)
+}
+
+/// Returns exact stack and heap sizes of the serialized value.
+/// Note that value is consumed.
+///
+/// Unlike [`Serialize::size_hint`] this never gives up or estimates.
+/// Value is serialized into a buffer that only counts bytes,
+/// so the result is precise for any value shape,
+/// including iterators without known length.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// let iter = (0..10u32).filter(|n| n % 3 == 0);
+/// let sizes = serialized_size_exact::<[u32], _>(SerIter(iter));
+/// assert_eq!((sizes.heap, sizes.stack), (0, 16));
+///
+/// let sizes = serialized_size_exact::<(Ref<str>, u8), _>(("hello", 1u8));
+/// assert_eq!(sizes.heap, 5);
+/// ```
+#[inline(always)]
+pub fn serialized_size_exact<F, T>(value: T) -> Sizes
 where
     F: Formula + ?Sized,
     T: Serialize<F>,
 {
     let mut sizes = Sizes::ZERO;
     match Serialize::<F>::serialize(value, &mut sizes, DryBuffer) {
-        Ok(()) => (sizes.total(), sizes.stack),
+        Ok(()) => sizes,
         Err(never) => match never {},
     }
 }
 
+/// Returns `true` if [`field_size_hint`] never returns sizes for the field,
+/// i.e. field is not last and its formula has unbounded stack size.
+///
+/// Check all fields with it in [`Serialize::size_hint`](Serialize::size_hint)
+/// implementation before calculating sizes of any,
+/// so that values with such fields are not traversed twice.
+/// Those are serialized in a single pass
+/// with field size written after the field is serialized.
+#[must_use]
+#[inline(always)]
+pub const fn field_size_hint_unknown<F: Formula + ?Sized>(last: bool) -> bool {
+    !last && F::MAX_STACK_SIZE.is_none()
+}
+
 /// Size hint for serializing a field.
 ///
 /// Use in [`Serialize::size_hint`](Serialize::size_hint) implementation.
@@ -486,23 +738,49 @@ pub fn write_reference<F, B>(
     address: usize,
     heap: usize,
     stack: usize,
-    mut buffer: B,
+    buffer: B,
 ) -> Result<(), B::Error>
 where
     F: Formula + ?Sized,
     B: Buffer,
 {
-    let address = usize_truncate_unchecked(address);
-    let size = usize_truncate_unchecked(size);
+    debug_assert!(!F::EXACT_SIZE || size == unwrap_size(F::MAX_STACK_SIZE));
+    write_reference_with(size, address, heap, stack, F::EXACT_SIZE, buffer)
+}
 
-    if F::EXACT_SIZE {
-        debug_assert_eq!(size, usize_truncate_unchecked(F::MAX_STACK_SIZE.unwrap()));
-        buffer.write_stack(heap, stack, &address.to_le_bytes())?;
+/// Writes reference to a value of formula with specified properties.
+#[inline(always)]
+pub(crate) fn write_reference_with<B>(
+    size: usize,
+    address: usize,
+    heap: usize,
+    stack: usize,
+    exact_size: bool,
+    mut buffer: B,
+) -> Result<(), B::Error>
+where
+    B: Buffer,
+{
+    let (bytes, len) = reference_bytes(size, address, exact_size);
+    buffer.write_stack(heap, stack, &bytes[..len])
+}
+
+/// Encodes reference into bytes in the order they appear on the stack.
+/// Returns buffer and number of bytes used.
+#[cfg_attr(feature = "inline-more", inline(always))]
+#[cfg_attr(not(feature = "inline-more"), inline(never))]
+fn reference_bytes(size: usize, address: usize, exact_size: bool) -> ([u8; 2 * SIZE_STACK], usize) {
+    let address = usize_truncate_unchecked(address).to_le_bytes();
+    let size = usize_truncate_unchecked(size).to_le_bytes();
+
+    let mut bytes = [0; 2 * SIZE_STACK];
+    bytes[..SIZE_STACK].copy_from_slice(&address);
+    if exact_size {
+        (bytes, SIZE_STACK)
     } else {
-        buffer.write_stack(heap, stack, &size.to_le_bytes())?;
-        buffer.write_stack(heap, stack + SIZE_STACK, &address.to_le_bytes())?;
+        bytes[SIZE_STACK..].copy_from_slice(&size);
+        (bytes, 2 * SIZE_STACK)
     }
-    Ok(())
 }
 
 /// Writes field value into the buffer.
@@ -516,7 +794,7 @@ where
 pub fn write_field<F, T, B>(
     value: T,
     sizes: &mut Sizes,
-    mut buffer: B,
+    buffer: B,
     last: bool,
 ) -> Result<(), B::Error>
 where
@@ -524,37 +802,94 @@ where
     T: Serialize<F>,
     B: Buffer,
 {
-    if !last && F::MAX_STACK_SIZE.is_none() {
+    write_field_with(
+        F::MAX_STACK_SIZE,
+        F::EXACT_SIZE,
+        last,
+        sizes,
+        buffer,
+        |sizes, buffer| <T as Serialize<F>>::serialize(value, sizes, buffer),
+    )
+}
+
+/// Writes field of formula with specified properties into the buffer.
+/// Value is written with `write` function.
+#[inline(always)]
+pub(crate) fn write_field_with<B>(
+    max_stack_size: Option<usize>,
+    exact_size: bool,
+    last: bool,
+    sizes: &mut Sizes,
+    mut buffer: B,
+    write: impl FnOnce(&mut Sizes, B::Reborrow<'_>) -> Result<(), B::Error>,
+) -> Result<(), B::Error>
+where
+    B: Buffer,
+{
+    if !last && max_stack_size.is_none() {
         buffer.write_stack(sizes.heap, sizes.stack, &[0; SIZE_STACK])?;
         sizes.stack += SIZE_STACK;
     }
 
     let old_stack = sizes.stack;
-    <T as Serialize<F>>::serialize(value, sizes, buffer.reborrow())?;
+    write(sizes, buffer.reborrow())?;
 
-    match (F::MAX_STACK_SIZE, F::EXACT_SIZE, last) {
-        (None, _, false) => {
-            let size = usize_truncate_unchecked(sizes.stack - old_stack);
-            let res = buffer.write_stack(sizes.heap, old_stack - SIZE_STACK, &size.to_le_bytes());
+    match field_end(max_stack_size, exact_size, last, old_stack, sizes.stack) {
+        FieldEnd::Done => {}
+        FieldEnd::SizePrefix(size) => {
+            let res = buffer.write_stack(sizes.heap, old_stack - SIZE_STACK, &size);
             if res.is_err() {
                 unreachable!("Successfully written before");
             };
         }
-        (None, _, true) => {}
+        FieldEnd::Pad(len) => {
+            buffer.pad_stack(sizes.heap, sizes.stack, len)?;
+            sizes.stack += len;
+        }
+    }
+
+    Ok(())
+}
+
+/// Action required to finish field after its value is written.
+enum FieldEnd {
+    Done,
+    SizePrefix([u8; SIZE_STACK]),
+    Pad(usize),
+}
+
+/// Returns action required to finish field
+/// which value occupies stack from `old_stack` to `stack`.
+///
+/// Kept out of generic code, so with "inline-more" feature disabled
+/// it is compiled once instead of for each field type.
+#[cfg_attr(feature = "inline-more", inline(always))]
+#[cfg_attr(not(feature = "inline-more"), inline(never))]
+fn field_end(
+    max_stack_size: Option<usize>,
+    exact_size: bool,
+    last: bool,
+    old_stack: usize,
+    stack: usize,
+) -> FieldEnd {
+    match (max_stack_size, exact_size, last) {
+        (None, _, false) => {
+            FieldEnd::SizePrefix(usize_truncate_unchecked(stack - old_stack).to_le_bytes())
+        }
+        (None, _, true) => FieldEnd::Done,
         (Some(max_stack), false, false) => {
-            debug_assert!(sizes.stack - old_stack <= max_stack);
-            buffer.pad_stack(sizes.heap, sizes.stack, old_stack + max_stack - sizes.stack)?;
-            sizes.stack = old_stack + max_stack;
+            debug_assert!(stack - old_stack <= max_stack);
+            FieldEnd::Pad(old_stack + max_stack - stack)
         }
         (Some(max_stack), false, true) => {
-            debug_assert!(sizes.stack - old_stack <= max_stack);
+            debug_assert!(stack - old_stack <= max_stack);
+            FieldEnd::Done
         }
         (Some(max_stack), true, _) => {
-            debug_assert_eq!(sizes.stack - old_stack, max_stack);
+            debug_assert_eq!(stack - old_stack, max_stack);
+            FieldEnd::Done
         }
     }
-
-    Ok(())
 }
 
 /// Write a field with exact size into buffer.
@@ -636,7 +971,13 @@ where
     let promised = <T as Serialize<F>>::size_hint(&value);
 
     let stack = match promised {
-        None => write_ref_slow(value, sizes, buffer.reborrow())?,
+        None => {
+            #[cfg(feature = "metrics")]
+            if let Some(metrics) = buffer.metrics() {
+                metrics.size_hint_misses += 1;
+            }
+            write_ref_slow(value, sizes, buffer.reborrow())?
+        }
         Some(promised) => match buffer.reserve_heap(sizes.heap, sizes.stack, promised.total())? {
             [] => match write_ref_slow(value, sizes, DryBuffer) {
                 Ok(stack) => stack,
@@ -799,6 +1140,122 @@ where
     })
 }
 
+/// Writes fields of a structure, tuple or enum variant
+/// one by one into associated buffer.
+///
+/// Fields must be written in the order of the formula,
+/// the last one with [`StructWriter::last_field`].
+///
+/// Use in [`Serialize::serialize`](Serialize::serialize) implementation
+/// for formulas with the layout of derived ones.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::{*, advanced::*};
+/// struct Person {
+///     id: u32,
+///     name: String,
+/// }
+///
+/// impl Serialize<(u32, str)> for &Person {
+///     fn serialize<B>(self, sizes: &mut Sizes, mut buffer: B) -> Result<(), B::Error>
+///     where
+///         B: Buffer,
+///     {
+///         struct_writer(sizes, &mut buffer)
+///             .field::<u32, _>(self.id)?
+///             .last_field::<str, _>(self.name.as_str())
+///     }
+///
+///     fn size_hint(&self) -> Option<Sizes> {
+///         Some(field_size_hint::<u32>(&self.id, false)? + field_size_hint::<str>(&self.name.as_str(), true)?)
+///     }
+/// }
+///
+/// let person = Person { id: 7, name: String::from("Alice") };
+///
+/// let mut buffer = Vec::new();
+/// let size = write_packet_to_vec::<(u32, str), _>(&person, &mut buffer);
+/// let (value, _) = read_packet::<(u32, str), (u32, &str)>(&buffer[..size]).unwrap();
+/// assert_eq!(value, (7, "Alice"));
+/// ```
+#[must_use]
+pub struct StructWriter<'a, B: Buffer + ?Sized> {
+    buffer: &'a mut B,
+    sizes: &'a mut Sizes,
+}
+
+impl<'a, B> StructWriter<'a, B>
+where
+    B: Buffer + ?Sized,
+{
+    /// Serialize next field that is not the last one.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if buffer write fails.
+    #[inline(always)]
+    pub fn field<F, T>(self, value: T) -> Result<Self, B::Error>
+    where
+        F: Formula,
+        T: Serialize<F>,
+    {
+        write_field::<F, _, _>(value, self.sizes, self.buffer.reborrow(), false)?;
+        Ok(self)
+    }
+
+    /// Serialize the last field and finish the structure.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if buffer write fails.
+    #[inline(always)]
+    pub fn last_field<F, T>(self, value: T) -> Result<(), B::Error>
+    where
+        F: Formula + ?Sized,
+        T: Serialize<F>,
+    {
+        write_field::<F, _, _>(value, self.sizes, self.buffer.reborrow(), true)
+    }
+}
+
+/// Returns a writer to write fields of a structure or tuple
+/// one by one into associated buffer.
+///
+/// See [`StructWriter`].
+#[inline(always)]
+pub fn struct_writer<'a, B>(sizes: &'a mut Sizes, buffer: &'a mut B) -> StructWriter<'a, B>
+where
+    B: Buffer,
+{
+    StructWriter { buffer, sizes }
+}
+
+/// Writes tag of enum variant and returns a writer
+/// to write fields of the variant one by one into associated buffer.
+///
+/// Variants are tagged with their index in declaration order.
+/// Variants without fields are complete after this call.
+///
+/// See [`StructWriter`].
+///
+/// # Errors
+///
+/// Returns error if buffer write fails.
+#[inline(always)]
+pub fn variant_writer<'a, B>(
+    tag: u32,
+    sizes: &'a mut Sizes,
+    buffer: &'a mut B,
+) -> Result<StructWriter<'a, B>, B::Error>
+where
+    B: Buffer,
+{
+    write_exact_size_field::<u32, u32, _>(tag, sizes, buffer.reborrow())?;
+    Ok(StructWriter { buffer, sizes })
+}
+
 /// Returns size hint for the formula if it is known at compile time.
 ///
 /// Use in [`Serialize::size_hint`](Serialize::size_hint) implementation
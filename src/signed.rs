@@ -0,0 +1,279 @@
+//!
+//! This module provides formula wrapper that appends detached signature to payload.
+//!
+
+use core::{fmt, marker::PhantomData};
+
+use alloc::vec::Vec;
+
+use crate::{
+    buffer::Buffer,
+    bytes::Bytes,
+    deserialize::{Deserialize, DeserializeError, Deserializer, ErrorDetails},
+    formula::{BareFormula, Formula},
+    packet::{read_packet, read_packet_in_place, write_packet_to_vec},
+    reference::Ref,
+    serialize::{Serialize, Sizes},
+};
+
+/// Signature scheme with trusted verifying key.
+///
+/// Implement this trait for a type that holds the public key
+/// to use it with [`Signed`] formula.
+/// Key may be loaded at runtime and replaced between reads.
+pub trait Verifier {
+    /// Size of the signature in bytes.
+    const SIGNATURE_SIZE: usize;
+
+    /// Verifies signature of the payload.
+    ///
+    /// `signature` is `SIGNATURE_SIZE` bytes long.
+    ///
+    /// # Errors
+    ///
+    /// Returns `InvalidSignature` if signature does not match the payload.
+    fn verify(&self, payload: &[u8], signature: &[u8]) -> Result<(), InvalidSignature>;
+}
+
+/// Signing key for signature scheme `V`.
+pub trait Signer<V: Verifier> {
+    /// Writes signature of the payload.
+    ///
+    /// `signature` is `V::SIGNATURE_SIZE` bytes long.
+    fn sign(&self, payload: &[u8], signature: &mut [u8]);
+}
+
+/// Error returned by [`Verifier::verify`]
+/// when signature does not match the payload.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct InvalidSignature;
+
+impl fmt::Display for InvalidSignature {
+    #[inline(always)]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid signature")
+    }
+}
+
+/// Formula wrapper that stores payload of formula `F`
+/// with detached signature verified by `V`.
+///
+/// Layout is the same as `Ref<Bytes>`.
+/// Referenced bytes are packet of formula `F` followed by signature over the packet bytes.
+///
+/// Values are serialized through [`Sign`]
+/// and deserialized into [`Unverified`] that is verified with the verifying key
+/// before the payload is read.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// // Toy scheme for demonstration. Never use it for real data.
+/// struct Trusted(u8);
+/// struct Key(u8);
+///
+/// fn checksum(key: u8, payload: &[u8]) -> u8 {
+///     payload.iter().fold(key, |acc, b| acc.wrapping_add(*b))
+/// }
+///
+/// impl Verifier for Trusted {
+///     const SIGNATURE_SIZE: usize = 1;
+///
+///     fn verify(&self, payload: &[u8], signature: &[u8]) -> Result<(), InvalidSignature> {
+///         match checksum(self.0, payload) == signature[0] {
+///             true => Ok(()),
+///             false => Err(InvalidSignature),
+///         }
+///     }
+/// }
+///
+/// impl Signer<Trusted> for Key {
+///     fn sign(&self, payload: &[u8], signature: &mut [u8]) {
+///         signature[0] = checksum(self.0, payload);
+///     }
+/// }
+///
+/// let mut buffer = Vec::new();
+/// let size = write_packet_to_vec::<Signed<str, Trusted>, _>(Sign::new(&Key(0x5a), "genuine"), &mut buffer);
+///
+/// let (unverified, _) = read_packet::<Signed<str, Trusted>, Unverified<str, Trusted>>(&buffer[..size]).unwrap();
+/// let value: &str = unverified.verify(&Trusted(0x5a)).unwrap();
+/// assert_eq!(value, "genuine");
+///
+/// assert!(matches!(
+///     unverified.verify::<&str>(&Trusted(0)),
+///     Err(DeserializeError::InvalidSignature),
+/// ));
+/// ```
+pub struct Signed<F: ?Sized, V> {
+    marker: PhantomData<fn(&F, V) -> &F>,
+}
+
+impl<F, V> Formula for Signed<F, V>
+where
+    F: Formula + ?Sized,
+    V: Verifier,
+{
+    const MAX_STACK_SIZE: Option<usize> = <Ref<Bytes> as Formula>::MAX_STACK_SIZE;
+    const EXACT_SIZE: bool = <Ref<Bytes> as Formula>::EXACT_SIZE;
+    const HEAPLESS: bool = <Ref<Bytes> as Formula>::HEAPLESS;
+}
+
+impl<F, V> BareFormula for Signed<F, V>
+where
+    F: Formula + ?Sized,
+    V: Verifier,
+{
+}
+
+/// Value to be signed with signer `S` and serialized with [`Signed`] formula.
+pub struct Sign<'a, S, T> {
+    signer: &'a S,
+    value: T,
+}
+
+impl<'a, S, T> Sign<'a, S, T> {
+    /// Wraps the value to be signed with the signer.
+    #[inline(always)]
+    pub fn new(signer: &'a S, value: T) -> Self {
+        Sign { signer, value }
+    }
+}
+
+impl<F, V, S, T> Serialize<Signed<F, V>> for Sign<'_, S, T>
+where
+    F: Formula + ?Sized,
+    V: Verifier,
+    S: Signer<V>,
+    T: Serialize<F>,
+{
+    #[inline]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        let mut signed = Vec::new();
+        let size = write_packet_to_vec::<F, T>(self.value, &mut signed);
+        signed.resize(size + V::SIGNATURE_SIZE, 0);
+
+        let (payload, signature) = signed.split_at_mut(size);
+        self.signer.sign(payload, signature);
+
+        <&[u8] as Serialize<Ref<Bytes>>>::serialize(&signed, sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        None
+    }
+}
+
+/// Signed payload deserialized from [`Signed`] formula.
+pub struct Unverified<'de, F: ?Sized, V> {
+    payload: &'de [u8],
+    signature: &'de [u8],
+    marker: PhantomData<fn(&F, V) -> &F>,
+}
+
+impl<F, V> Clone for Unverified<'_, F, V>
+where
+    F: ?Sized,
+{
+    #[inline(always)]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<F, V> Copy for Unverified<'_, F, V> where F: ?Sized {}
+
+impl<'de, F, V> Unverified<'de, F, V>
+where
+    F: Formula + ?Sized,
+    V: Verifier,
+{
+    /// Returns signed packet bytes without verifying them.
+    #[must_use]
+    #[inline(always)]
+    pub fn payload(&self) -> &'de [u8] {
+        self.payload
+    }
+
+    /// Returns detached signature.
+    #[must_use]
+    #[inline(always)]
+    pub fn signature(&self) -> &'de [u8] {
+        self.signature
+    }
+
+    /// Verifies signature with the verifier and deserializes value from the payload.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError::InvalidSignature`
+    /// if payload was not signed with matching key or was tampered with.
+    /// Returns other `DeserializeError` if signed packet is invalid.
+    #[inline]
+    pub fn verify<T>(&self, verifier: &V) -> Result<T, DeserializeError>
+    where
+        T: Deserialize<'de, F>,
+    {
+        let payload = self.verified(verifier)?;
+        let (value, _) = read_packet::<F, T>(payload)?;
+        Ok(value)
+    }
+
+    /// Verifies signature with the verifier and deserializes value from the payload in-place.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`Unverified::verify`].
+    #[inline]
+    pub fn verify_in_place<T>(&self, verifier: &V, place: &mut T) -> Result<(), DeserializeError>
+    where
+        T: Deserialize<'de, F> + ?Sized,
+    {
+        let payload = self.verified(verifier)?;
+        read_packet_in_place::<F, T>(place, payload)?;
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn verified(&self, verifier: &V) -> Result<&'de [u8], DeserializeError> {
+        if verifier.verify(self.payload, self.signature).is_err() {
+            return Err(DeserializeError::InvalidSignature);
+        }
+        Ok(self.payload)
+    }
+}
+
+impl<'de, 'fe: 'de, F, V> Deserialize<'fe, Signed<F, V>> for Unverified<'de, F, V>
+where
+    F: Formula + ?Sized,
+    V: Verifier,
+{
+    #[inline(always)]
+    fn deserialize(de: Deserializer<'fe>) -> Result<Self, DeserializeError> {
+        let bytes = <&[u8] as Deserialize<Ref<Bytes>>>::deserialize(de)?;
+        if bytes.len() < V::SIGNATURE_SIZE {
+            return Err(DeserializeError::WrongLength(ErrorDetails::sizes(
+                V::SIGNATURE_SIZE,
+                bytes.len(),
+            )));
+        }
+        let (payload, signature) = bytes.split_at(bytes.len() - V::SIGNATURE_SIZE);
+        Ok(Unverified {
+            payload,
+            signature,
+            marker: PhantomData,
+        })
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(&mut self, de: Deserializer<'fe>) -> Result<(), DeserializeError> {
+        *self = <Self as Deserialize<'fe, Signed<F, V>>>::deserialize(de)?;
+        Ok(())
+    }
+}
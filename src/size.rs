@@ -2,6 +2,7 @@ use core::mem::size_of;
 
 use crate::{
     buffer::Buffer,
+    descriptor::{Descriptor, DescriptorKind},
     deserialize::{Deserialize, DeserializeError, Deserializer},
     formula::{BareFormula, Formula},
     serialize::{write_bytes, Serialize, Sizes},
@@ -45,6 +46,22 @@ cfg_if::cfg_if! {
 
 pub const SIZE_STACK: usize = size_of::<FixedUsizeType>();
 
+/// Maximum size of a packet in bytes.
+///
+/// Sizes and addresses are serialized as [`FixedUsizeType`]
+/// and must fit `usize` of the platform,
+/// so this is the smaller of the two maximums.
+/// E.g. 64 KiB - 1 with "fixed16" feature or on 16-bit platforms
+/// and 4 GiB - 1 with default "fixed32" feature on wider platforms.
+///
+/// Fixed buffers use at most this number of bytes
+/// and growing buffers panic when exceeding it.
+pub const PACKET_SIZE_LIMIT: usize = if (FixedUsizeType::MAX as u128) < (usize::MAX as u128) {
+    FixedUsizeType::MAX as usize
+} else {
+    usize::MAX
+};
+
 pub fn usize_truncate_unchecked(value: usize) -> FixedUsizeType {
     debug_assert!(FixedUsizeType::try_from(value).is_ok());
     value as FixedUsizeType
@@ -59,6 +76,11 @@ impl Formula for usize {
     const MAX_STACK_SIZE: Option<usize> = Some(size_of::<FixedUsizeType>());
     const EXACT_SIZE: bool = true;
     const HEAPLESS: bool = true;
+
+    #[inline(always)]
+    fn describe() -> Descriptor {
+        Descriptor::new::<usize>(DescriptorKind::Unsigned(size_of::<FixedUsizeType>()))
+    }
 }
 
 impl BareFormula for usize {}
@@ -110,6 +132,11 @@ impl Formula for isize {
     const MAX_STACK_SIZE: Option<usize> = Some(size_of::<FixedIsizeType>());
     const EXACT_SIZE: bool = true;
     const HEAPLESS: bool = true;
+
+    #[inline(always)]
+    fn describe() -> Descriptor {
+        Descriptor::new::<isize>(DescriptorKind::Signed(size_of::<FixedIsizeType>()))
+    }
 }
 
 impl BareFormula for isize {}
@@ -187,12 +214,10 @@ pub fn deserialize_usize(mut de: Deserializer) -> Result<usize, DeserializeError
     // de.finish()?;
     let value = <FixedUsizeType>::from_le_bytes(input);
 
-    #[cfg(debug_assertions)]
-    if usize::try_from(value).is_err() {
-        return Err(DeserializeError::InvalidUsize(value));
+    match usize::try_from(value) {
+        Ok(value) => Ok(value),
+        Err(_) => Err(DeserializeError::InvalidUsize(value)),
     }
-
-    Ok(value as usize)
 }
 
 #[inline(always)]
@@ -201,10 +226,8 @@ pub fn deserialize_isize(mut de: Deserializer) -> Result<isize, DeserializeError
     // de.finish()?;
     let value = <FixedIsizeType>::from_le_bytes(input);
 
-    #[cfg(debug_assertions)]
-    if usize::try_from(value).is_err() {
-        return Err(DeserializeError::InvalidIsize(value));
+    match isize::try_from(value) {
+        Ok(value) => Ok(value),
+        Err(_) => Err(DeserializeError::InvalidIsize(value)),
     }
-
-    Ok(value as isize)
 }
@@ -1,5 +1,6 @@
 use crate::{
     buffer::Buffer,
+    descriptor::{Descriptor, DescriptorKind, LazyDescriptor},
     formula::{BareFormula, Formula},
     iter::owned_iter_fast_sizes,
     serialize::{write_slice, Serialize, Sizes},
@@ -16,6 +17,11 @@ where
     };
     const EXACT_SIZE: bool = false;
     const HEAPLESS: bool = F::HEAPLESS;
+
+    #[inline(always)]
+    fn describe() -> Descriptor {
+        Descriptor::new::<Self>(DescriptorKind::Slice(LazyDescriptor::of::<F>()))
+    }
 }
 
 impl<F> BareFormula for [F] where F: Formula {}
@@ -0,0 +1,678 @@
+//!
+//! This module provides sorted collection formulas with lookups over serialized data.
+//!
+
+use core::{borrow::Borrow, cmp::Ordering, marker::PhantomData};
+
+use crate::{
+    buffer::Buffer,
+    descriptor::{Descriptor, DescriptorKind, LazyDescriptor},
+    deserialize::{DeIter, Deserialize, DeserializeError, Deserializer},
+    formula::Formula,
+    serialize::{write_field, Serialize, Sizes},
+};
+
+/// Map formula with entries sorted by key.
+///
+/// Layout is the same as `[(K, V)]` with entries in ascending order of keys.
+/// Both `K` and `V` must be sized, use [`Ref`](crate::Ref) for unsized keys and values.
+/// Since entries have fixed size, [`SortedMapView::get`] finds entry
+/// with binary search over serialized data, deserializing only
+/// O(log n) keys and the found value.
+///
+/// Serializable from any iterable of pairs with keys in strictly ascending order,
+/// e.g. `BTreeMap` or sorted slice of pairs.
+/// Deserializable into [`SortedMapView`], `Vec<(K, V)>` and `BTreeMap<K, V>`.
+/// The latter two fail with `DeserializeError::Unsorted`
+/// if keys are not in strictly ascending order.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// # use std::collections::BTreeMap;
+/// type Table = SortedMap<u32, Ref<str>>;
+///
+/// let map = BTreeMap::from([(3u32, "three"), (1, "one"), (2, "two")]);
+///
+/// let mut buffer = Vec::new();
+/// let size = write_packet_to_vec::<Table, _>(&map, &mut buffer);
+///
+/// let (view, _) = read_packet::<Table, SortedMapView<u32, Ref<str>, u32, &str>>(&buffer[..size]).unwrap();
+/// assert_eq!(view.get(&2).unwrap(), Some("two"));
+/// assert_eq!(view.get(&4).unwrap(), None);
+/// ```
+pub struct SortedMap<K, V> {
+    marker: PhantomData<fn(K, V)>,
+}
+
+impl<K, V> SortedMap<K, V>
+where
+    K: Formula,
+    V: Formula,
+{
+    const KEY_SIZE: usize = match K::MAX_STACK_SIZE {
+        Some(0) => panic!("Key formula of `SortedMap` must not be zero-sized"),
+        Some(size) => size,
+        None => panic!("Key formula of `SortedMap` must be sized"),
+    };
+
+    const ENTRY_SIZE: usize = match V::MAX_STACK_SIZE {
+        Some(size) => Self::KEY_SIZE + size,
+        None => panic!("Value formula of `SortedMap` must be sized"),
+    };
+}
+
+impl<K, V> Formula for SortedMap<K, V>
+where
+    K: Formula,
+    V: Formula,
+{
+    const MAX_STACK_SIZE: Option<usize> = None;
+    const EXACT_SIZE: bool = false;
+    const HEAPLESS: bool = K::HEAPLESS && V::HEAPLESS;
+
+    #[inline(always)]
+    fn describe() -> Descriptor {
+        Descriptor::new::<Self>(DescriptorKind::Slice(LazyDescriptor::of::<(K, V)>()))
+    }
+}
+
+impl<K, V, I, KT, VT> Serialize<SortedMap<K, V>> for I
+where
+    K: Formula,
+    V: Formula,
+    I: IntoIterator<Item = (KT, VT)>,
+    KT: Serialize<K> + PartialOrd,
+    VT: Serialize<V>,
+{
+    /// # Panics
+    ///
+    /// Panics if keys are not in strictly ascending order.
+    #[inline]
+    fn serialize<B>(self, sizes: &mut Sizes, mut buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        let _ = SortedMap::<K, V>::ENTRY_SIZE;
+
+        let mut iter = self.into_iter().peekable();
+        while let Some((key, value)) = iter.next() {
+            if let Some((next, _)) = iter.peek() {
+                assert!(
+                    key < *next,
+                    "Keys of `SortedMap` must be in strictly ascending order"
+                );
+            }
+            write_field::<(K, V), _, _>((key, value), sizes, buffer.reborrow(), false)?;
+        }
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        None
+    }
+}
+
+/// View over entries serialized with [`SortedMap`] formula.
+///
+/// Keys are deserialized as `KT` and values as `VT` on access.
+/// View does not check order of keys when created,
+/// lookups in unsorted map may miss entries but never panic.
+/// Use [`SortedMapView::validate`] to check the order.
+#[must_use]
+pub struct SortedMapView<'de, K, V, KT, VT> {
+    de: Deserializer<'de>,
+    len: usize,
+    formula: PhantomData<fn(K, V)>,
+    marker: PhantomData<fn() -> (KT, VT)>,
+}
+
+impl<'de, K, V, KT, VT> Clone for SortedMapView<'de, K, V, KT, VT> {
+    #[inline(always)]
+    fn clone(&self) -> Self {
+        SortedMapView {
+            de: self.de.clone(),
+            len: self.len,
+            formula: PhantomData,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<'de, K, V, KT, VT> SortedMapView<'de, K, V, KT, VT>
+where
+    K: Formula,
+    V: Formula,
+    KT: Deserialize<'de, K>,
+    VT: Deserialize<'de, V>,
+{
+    /// Returns number of entries.
+    #[must_use]
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if map has no entries.
+    #[must_use]
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Finds entry with the key using binary search
+    /// and deserializes its value.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError` if key or value deserialization fails.
+    #[inline]
+    pub fn get<Q>(&self, key: &Q) -> Result<Option<VT>, DeserializeError>
+    where
+        KT: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        match self.search(key)? {
+            Ok(n) => {
+                let mut entry = self.entry_de(n)?;
+                entry.read_bytes(SortedMap::<K, V>::KEY_SIZE)?;
+                Ok(Some(entry.read_value::<V, VT>(true)?))
+            }
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// Returns `true` if map contains entry with the key.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError` if key deserialization fails.
+    #[inline]
+    pub fn contains_key<Q>(&self, key: &Q) -> Result<bool, DeserializeError>
+    where
+        KT: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        Ok(self.search(key)?.is_ok())
+    }
+
+    /// Binary searches the key.
+    /// Returns `Ok(n)` with index of the entry if key is found,
+    /// otherwise `Err(n)` with index where entry with the key would be inserted.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError` if key deserialization fails.
+    #[inline]
+    pub fn search<Q>(&self, key: &Q) -> Result<Result<usize, usize>, DeserializeError>
+    where
+        KT: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        binary_search(self.len, key, |n| self.key(n))
+    }
+
+    /// Deserializes entry `n`.
+    /// Returns `None` if `n` is out of bounds.
+    #[inline]
+    pub fn entry(&self, n: usize) -> Option<Result<(KT, VT), DeserializeError>> {
+        if n >= self.len {
+            return None;
+        }
+        Some(
+            self.entry_de(n)
+                .and_then(|mut entry| entry.read_value::<(K, V), (KT, VT)>(true)),
+        )
+    }
+
+    /// Returns iterator over entries in order of keys.
+    #[inline]
+    pub fn iter(&self) -> DeIter<'de, (K, V), (KT, VT)> {
+        self.de.clone().into_unsized_array_iter(self.len)
+    }
+
+    /// Checks that keys are in strictly ascending order.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError::Unsorted` if keys are out of order or duplicated,
+    /// or other `DeserializeError` if key deserialization fails.
+    #[inline]
+    pub fn validate(&self) -> Result<(), DeserializeError>
+    where
+        KT: Ord,
+    {
+        let mut prev = None;
+        for n in 0..self.len {
+            let key = self.key(n)?;
+            check_ascending(&mut prev, key)?;
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn key(&self, n: usize) -> Result<KT, DeserializeError> {
+        self.entry_de(n)?.read_value::<K, KT>(false)
+    }
+
+    #[inline]
+    fn entry_de(&self, n: usize) -> Result<Deserializer<'de>, DeserializeError> {
+        let entry_size = SortedMap::<K, V>::ENTRY_SIZE;
+        let mut de = self.de.clone();
+        de.read_bytes(n * entry_size)?;
+        de.sub(entry_size)
+    }
+}
+
+impl<'de, K, V, KT, VT> Deserialize<'de, SortedMap<K, V>> for SortedMapView<'de, K, V, KT, VT>
+where
+    K: Formula,
+    V: Formula,
+    KT: Deserialize<'de, K>,
+    VT: Deserialize<'de, V>,
+{
+    #[inline]
+    fn deserialize(de: Deserializer<'de>) -> Result<Self, DeserializeError> {
+        let len = de.stack() / SortedMap::<K, V>::ENTRY_SIZE;
+        Ok(SortedMapView {
+            de,
+            len,
+            formula: PhantomData,
+            marker: PhantomData,
+        })
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(&mut self, de: Deserializer<'de>) -> Result<(), DeserializeError> {
+        *self = <Self as Deserialize<'de, SortedMap<K, V>>>::deserialize(de)?;
+        Ok(())
+    }
+}
+
+/// Set formula with elements sorted in ascending order without duplicates.
+///
+/// Layout is the same as `[F]` with unique elements in ascending order,
+/// which makes encoding of a set canonical.
+/// `F` must be sized, use [`Ref`](crate::Ref) for unsized elements.
+/// Since elements have fixed size, [`SortedSetView::contains`] tests membership
+/// with binary search over serialized data, deserializing only O(log n) elements.
+///
+/// Serializable from any iterable with elements in strictly ascending order,
+/// e.g. `BTreeSet` or sorted and deduplicated slice.
+/// Deserializable into [`SortedSetView`], `Vec<T>` and `BTreeSet<T>`.
+/// The latter two fail with `DeserializeError::Unsorted`
+/// if elements are not in strictly ascending order.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// # use std::collections::BTreeSet;
+/// let set = BTreeSet::from([7u32, 3, 5]);
+///
+/// let mut buffer = Vec::new();
+/// let size = write_packet_to_vec::<SortedSet<u32>, _>(&set, &mut buffer);
+///
+/// let (view, _) = read_packet::<SortedSet<u32>, SortedSetView<u32, u32>>(&buffer[..size]).unwrap();
+/// assert!(view.contains(&5).unwrap());
+/// assert!(!view.contains(&4).unwrap());
+/// ```
+pub struct SortedSet<F> {
+    marker: PhantomData<fn(F)>,
+}
+
+impl<F> SortedSet<F>
+where
+    F: Formula,
+{
+    const ELEMENT_SIZE: usize = match F::MAX_STACK_SIZE {
+        Some(0) => panic!("Element formula of `SortedSet` must not be zero-sized"),
+        Some(size) => size,
+        None => panic!("Element formula of `SortedSet` must be sized"),
+    };
+}
+
+impl<F> Formula for SortedSet<F>
+where
+    F: Formula,
+{
+    const MAX_STACK_SIZE: Option<usize> = None;
+    const EXACT_SIZE: bool = false;
+    const HEAPLESS: bool = F::HEAPLESS;
+
+    #[inline(always)]
+    fn describe() -> Descriptor {
+        Descriptor::new::<Self>(DescriptorKind::Slice(LazyDescriptor::of::<F>()))
+    }
+}
+
+impl<F, I> Serialize<SortedSet<F>> for I
+where
+    F: Formula,
+    I: IntoIterator,
+    I::Item: Serialize<F> + PartialOrd,
+{
+    /// # Panics
+    ///
+    /// Panics if elements are not in strictly ascending order.
+    #[inline]
+    fn serialize<B>(self, sizes: &mut Sizes, mut buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        let _ = SortedSet::<F>::ELEMENT_SIZE;
+
+        let mut iter = self.into_iter().peekable();
+        while let Some(elem) = iter.next() {
+            if let Some(next) = iter.peek() {
+                assert!(
+                    elem < *next,
+                    "Elements of `SortedSet` must be in strictly ascending order"
+                );
+            }
+            write_field::<F, _, _>(elem, sizes, buffer.reborrow(), false)?;
+        }
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        None
+    }
+}
+
+/// View over elements serialized with [`SortedSet`] formula.
+///
+/// Elements are deserialized as `T` on access.
+/// View does not check order of elements when created,
+/// lookups in unsorted set may miss elements but never panic.
+/// Use [`SortedSetView::validate`] to check the order.
+#[must_use]
+pub struct SortedSetView<'de, F, T> {
+    de: Deserializer<'de>,
+    len: usize,
+    marker: PhantomData<fn(F) -> T>,
+}
+
+impl<'de, F, T> Clone for SortedSetView<'de, F, T> {
+    #[inline(always)]
+    fn clone(&self) -> Self {
+        SortedSetView {
+            de: self.de.clone(),
+            len: self.len,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<'de, F, T> SortedSetView<'de, F, T>
+where
+    F: Formula,
+    T: Deserialize<'de, F>,
+{
+    /// Returns number of elements.
+    #[must_use]
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if set has no elements.
+    #[must_use]
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns `true` if set contains the value.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError` if element deserialization fails.
+    #[inline]
+    pub fn contains<Q>(&self, value: &Q) -> Result<bool, DeserializeError>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        Ok(self.search(value)?.is_ok())
+    }
+
+    /// Binary searches the value.
+    /// Returns `Ok(n)` with index of the element if value is found,
+    /// otherwise `Err(n)` with index where the value would be inserted.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError` if element deserialization fails.
+    #[inline]
+    pub fn search<Q>(&self, value: &Q) -> Result<Result<usize, usize>, DeserializeError>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        binary_search(self.len, value, |n| self.read(n))
+    }
+
+    /// Deserializes element `n`.
+    /// Returns `None` if `n` is out of bounds.
+    #[inline]
+    pub fn get(&self, n: usize) -> Option<Result<T, DeserializeError>> {
+        if n >= self.len {
+            return None;
+        }
+        Some(self.read(n))
+    }
+
+    /// Returns iterator over elements in ascending order.
+    #[inline]
+    pub fn iter(&self) -> DeIter<'de, F, T> {
+        self.de.clone().into_unsized_array_iter(self.len)
+    }
+
+    /// Checks that elements are in strictly ascending order.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError::Unsorted` if elements are out of order or duplicated,
+    /// or other `DeserializeError` if element deserialization fails.
+    #[inline]
+    pub fn validate(&self) -> Result<(), DeserializeError>
+    where
+        T: Ord,
+    {
+        let mut prev = None;
+        for n in 0..self.len {
+            check_ascending(&mut prev, self.read(n)?)?;
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn read(&self, n: usize) -> Result<T, DeserializeError> {
+        let element_size = SortedSet::<F>::ELEMENT_SIZE;
+        let mut de = self.de.clone();
+        de.read_bytes(n * element_size)?;
+        de.read_value::<F, T>(false)
+    }
+}
+
+impl<'de, F, T> Deserialize<'de, SortedSet<F>> for SortedSetView<'de, F, T>
+where
+    F: Formula,
+    T: Deserialize<'de, F>,
+{
+    #[inline]
+    fn deserialize(de: Deserializer<'de>) -> Result<Self, DeserializeError> {
+        let len = de.stack() / SortedSet::<F>::ELEMENT_SIZE;
+        Ok(SortedSetView {
+            de,
+            len,
+            marker: PhantomData,
+        })
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(&mut self, de: Deserializer<'de>) -> Result<(), DeserializeError> {
+        *self = <Self as Deserialize<'de, SortedSet<F>>>::deserialize(de)?;
+        Ok(())
+    }
+}
+
+/// Binary searches `key` among `len` ascending values returned by `read`.
+#[inline]
+fn binary_search<T, Q>(
+    len: usize,
+    key: &Q,
+    read: impl Fn(usize) -> Result<T, DeserializeError>,
+) -> Result<Result<usize, usize>, DeserializeError>
+where
+    T: Borrow<Q>,
+    Q: Ord + ?Sized,
+{
+    let mut low = 0;
+    let mut high = len;
+    while low < high {
+        let mid = low + (high - low) / 2;
+        match read(mid)?.borrow().cmp(key) {
+            Ordering::Less => low = mid + 1,
+            Ordering::Greater => high = mid,
+            Ordering::Equal => return Ok(Ok(mid)),
+        }
+    }
+    Ok(Err(low))
+}
+
+/// Stores `key` as previous one if it is greater than the `prev`.
+#[inline(always)]
+fn check_ascending<T>(prev: &mut Option<T>, key: T) -> Result<(), DeserializeError>
+where
+    T: Ord,
+{
+    if let Some(prev) = prev {
+        if *prev >= key {
+            return Err(DeserializeError::Unsorted);
+        }
+    }
+    *prev = Some(key);
+    Ok(())
+}
+
+#[cfg(feature = "alloc")]
+mod alloc_impls {
+    use alloc::{
+        collections::{BTreeMap, BTreeSet},
+        vec::Vec,
+    };
+
+    use crate::{
+        deserialize::{Deserialize, DeserializeError, Deserializer},
+        formula::Formula,
+        iter::cautious_capacity,
+    };
+
+    use super::{SortedMap, SortedMapView, SortedSet, SortedSetView};
+
+    impl<'de, K, V, KT, VT> Deserialize<'de, SortedMap<K, V>> for Vec<(KT, VT)>
+    where
+        K: Formula,
+        V: Formula,
+        KT: Deserialize<'de, K> + Ord,
+        VT: Deserialize<'de, V>,
+    {
+        #[inline]
+        fn deserialize(de: Deserializer<'de>) -> Result<Self, DeserializeError> {
+            let mut vec = Vec::new();
+            <Self as Deserialize<'de, SortedMap<K, V>>>::deserialize_in_place(&mut vec, de)?;
+            Ok(vec)
+        }
+
+        #[inline]
+        fn deserialize_in_place(&mut self, de: Deserializer<'de>) -> Result<(), DeserializeError> {
+            let view = <SortedMapView<'de, K, V, KT, VT> as Deserialize<'de, SortedMap<K, V>>>::deserialize(de)?;
+            self.clear();
+            self.reserve(cautious_capacity::<(KT, VT)>(view.len()));
+            for entry in view.iter() {
+                let (key, value) = entry?;
+                if let Some((prev, _)) = self.last() {
+                    if *prev >= key {
+                        return Err(DeserializeError::Unsorted);
+                    }
+                }
+                self.push((key, value));
+            }
+            Ok(())
+        }
+    }
+
+    impl<'de, K, V, KT, VT> Deserialize<'de, SortedMap<K, V>> for BTreeMap<KT, VT>
+    where
+        K: Formula,
+        V: Formula,
+        KT: Deserialize<'de, K> + Ord,
+        VT: Deserialize<'de, V>,
+    {
+        #[inline]
+        fn deserialize(de: Deserializer<'de>) -> Result<Self, DeserializeError> {
+            let entries = <Vec<(KT, VT)> as Deserialize<'de, SortedMap<K, V>>>::deserialize(de)?;
+            Ok(entries.into_iter().collect())
+        }
+
+        #[inline]
+        fn deserialize_in_place(&mut self, de: Deserializer<'de>) -> Result<(), DeserializeError> {
+            *self = <Self as Deserialize<'de, SortedMap<K, V>>>::deserialize(de)?;
+            Ok(())
+        }
+    }
+
+    impl<'de, F, T> Deserialize<'de, SortedSet<F>> for Vec<T>
+    where
+        F: Formula,
+        T: Deserialize<'de, F> + Ord,
+    {
+        #[inline]
+        fn deserialize(de: Deserializer<'de>) -> Result<Self, DeserializeError> {
+            let mut vec = Vec::new();
+            <Self as Deserialize<'de, SortedSet<F>>>::deserialize_in_place(&mut vec, de)?;
+            Ok(vec)
+        }
+
+        #[inline]
+        fn deserialize_in_place(&mut self, de: Deserializer<'de>) -> Result<(), DeserializeError> {
+            let view =
+                <SortedSetView<'de, F, T> as Deserialize<'de, SortedSet<F>>>::deserialize(de)?;
+            self.clear();
+            self.reserve(cautious_capacity::<T>(view.len()));
+            for elem in view.iter() {
+                let elem = elem?;
+                if let Some(prev) = self.last() {
+                    if *prev >= elem {
+                        return Err(DeserializeError::Unsorted);
+                    }
+                }
+                self.push(elem);
+            }
+            Ok(())
+        }
+    }
+
+    impl<'de, F, T> Deserialize<'de, SortedSet<F>> for BTreeSet<T>
+    where
+        F: Formula,
+        T: Deserialize<'de, F> + Ord,
+    {
+        #[inline]
+        fn deserialize(de: Deserializer<'de>) -> Result<Self, DeserializeError> {
+            let elems = <Vec<T> as Deserialize<'de, SortedSet<F>>>::deserialize(de)?;
+            Ok(elems.into_iter().collect())
+        }
+
+        #[inline]
+        fn deserialize_in_place(&mut self, de: Deserializer<'de>) -> Result<(), DeserializeError> {
+            *self = <Self as Deserialize<'de, SortedSet<F>>>::deserialize(de)?;
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,207 @@
+//!
+//! This module provides formula for tuples of optional fields that stores only present values.
+//!
+
+use core::marker::PhantomData;
+
+use crate::{
+    bits::packed_size,
+    buffer::Buffer,
+    deserialize::{Deserialize, DeserializeError, Deserializer},
+    formula::{sum_size, BareFormula, Formula},
+    serialize::{field_size_hint, write_bytes, write_field, Serialize, Sizes},
+};
+
+/// Formula for a tuple of optional fields.
+///
+/// `Sparse<(A, B, ...)>` is serialized from and deserialized into
+/// `(Option<TA>, Option<TB>, ...)` where `TA` is serializable with formula `A` and so on.
+///
+/// Unlike tuple of `Option` formulas, that stores tag byte for each field
+/// and reserves space for absent values,
+/// this formula stores presence bitmap with one bit per field
+/// followed by present values only.
+/// Sparse messages like patches and deltas become several times smaller.
+///
+/// Supports tuples of up to 16 fields.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// type Patch = Sparse<(u64, u64, Ref<str>, u32)>;
+///
+/// let mut buffer = [0u8; 64];
+/// let size = write_packet::<Patch, _>(
+///     (None::<u64>, Some(5u64), None::<&str>, None::<u32>),
+///     &mut buffer,
+/// )
+/// .unwrap();
+/// assert!(size < write_packet::<(Option<u64>, Option<u64>, Option<Ref<str>>, Option<u32>), _>(
+///     (None::<u64>, Some(5u64), None::<&str>, None::<u32>),
+///     &mut [0u8; 64],
+/// ).unwrap());
+///
+/// let (patch, _) =
+///     read_packet::<Patch, (Option<u64>, Option<u64>, Option<&str>, Option<u32>)>(&buffer[..size]).unwrap();
+/// assert_eq!(patch, (None, Some(5), None, None));
+/// ```
+pub struct Sparse<T> {
+    marker: PhantomData<fn(&T) -> &T>,
+}
+
+/// Returns bit of the last present field, or zero if no fields are present.
+#[inline(always)]
+const fn last_bit(mask: u32) -> u32 {
+    match mask {
+        0 => 0,
+        _ => 1 << (u32::BITS - 1 - mask.leading_zeros()),
+    }
+}
+
+macro_rules! sparse_formula {
+    () => {
+        sparse_formula!(
+            AA AB AC AD AE AF AG AH AI AJ AK AL AM AN AO AP,
+            BA BB BC BD BE BF BG BH BI BJ BK BL BM BN BO BP
+        );
+    };
+    (,) => {};
+    (impl $($a:ident)*, $($b:ident)*) => {
+        impl<$($a,)*> Sparse<($($a,)*)> {
+            const FIELDS: usize = [$(stringify!($a),)*].len();
+            const BITMAP_SIZE: usize = packed_size(Self::FIELDS as u32);
+        }
+
+        impl<$($a,)*> Formula for Sparse<($($a,)*)>
+        where
+            $($a: Formula,)*
+        {
+            const MAX_STACK_SIZE: Option<usize> = {
+                let mut size = Some(Self::BITMAP_SIZE);
+                $(size = sum_size(size, <$a as Formula>::MAX_STACK_SIZE);)*
+                size
+            };
+
+            const EXACT_SIZE: bool = false;
+            const HEAPLESS: bool = $(<$a as Formula>::HEAPLESS &&)* true;
+        }
+
+        impl<$($a,)*> BareFormula for Sparse<($($a,)*)>
+        where
+            $($a: Formula,)*
+        {
+        }
+
+        impl<$($a,)* $($b,)*> Serialize<Sparse<($($a,)*)>> for ($(Option<$b>,)*)
+        where
+            $(
+                $a: Formula,
+                $b: Serialize<$a>,
+            )*
+        {
+            #[inline]
+            fn serialize<B>(self, sizes: &mut Sizes, mut buffer: B) -> Result<(), B::Error>
+            where
+                B: Buffer,
+            {
+                #![allow(non_snake_case, unused_assignments)]
+
+                let ($($b,)*) = self;
+
+                let mut mask = 0u32;
+                let mut bit = 1u32;
+                $(
+                    if $b.is_some() {
+                        mask |= bit;
+                    }
+                    bit <<= 1;
+                )*
+
+                let size = Sparse::<($($a,)*)>::BITMAP_SIZE;
+                write_bytes(&mask.to_le_bytes()[..size], sizes, buffer.reborrow())?;
+
+                let last = last_bit(mask);
+                let mut bit = 1u32;
+                $(
+                    if let Some($b) = $b {
+                        write_field::<$a, $b, _>($b, sizes, buffer.reborrow(), bit == last)?;
+                    }
+                    bit <<= 1;
+                )*
+                Ok(())
+            }
+
+            #[inline]
+            fn size_hint(&self) -> Option<Sizes> {
+                #![allow(non_snake_case, unused_assignments)]
+
+                let ($($b,)*) = self;
+
+                let mut mask = 0u32;
+                let mut bit = 1u32;
+                $(
+                    if $b.is_some() {
+                        mask |= bit;
+                    }
+                    bit <<= 1;
+                )*
+
+                let mut sizes = Sizes::with_stack(Sparse::<($($a,)*)>::BITMAP_SIZE);
+                let last = last_bit(mask);
+                let mut bit = 1u32;
+                $(
+                    if let Some($b) = $b {
+                        sizes += field_size_hint::<$a>($b, bit == last)?;
+                    }
+                    bit <<= 1;
+                )*
+                Some(sizes)
+            }
+        }
+
+        impl<'de, $($a,)* $($b,)*> Deserialize<'de, Sparse<($($a,)*)>> for ($(Option<$b>,)*)
+        where
+            $(
+                $a: Formula,
+                $b: Deserialize<'de, $a>,
+            )*
+        {
+            #[inline]
+            fn deserialize(mut de: Deserializer<'de>) -> Result<Self, DeserializeError> {
+                #![allow(non_snake_case, unused_assignments)]
+
+                let size = Sparse::<($($a,)*)>::BITMAP_SIZE;
+                let mut bytes = [0; 4];
+                bytes[..size].copy_from_slice(de.read_bytes(size)?);
+                let mask = u32::from_le_bytes(bytes);
+                if mask >> Sparse::<($($a,)*)>::FIELDS != 0 {
+                    return Err(DeserializeError::NonCanonical);
+                }
+
+                let last = last_bit(mask);
+                let mut bit = 1u32;
+                $(
+                    let $b = match mask & bit {
+                        0 => None,
+                        _ => Some(de.read_value::<$a, $b>(bit == last)?),
+                    };
+                    bit <<= 1;
+                )*
+                Ok(($($b,)*))
+            }
+
+            #[inline]
+            fn deserialize_in_place(&mut self, de: Deserializer<'de>) -> Result<(), DeserializeError> {
+                *self = <Self as Deserialize<'de, Sparse<($($a,)*)>>>::deserialize(de)?;
+                Ok(())
+            }
+        }
+    };
+    ($a_head:ident $($a_tail:ident)*, $b_head:ident $($b_tail:ident)*) => {
+        sparse_formula!($($a_tail)*, $($b_tail)*);
+        sparse_formula!(impl $a_head $($a_tail)*, $b_head $($b_tail)*);
+    };
+}
+
+sparse_formula!();
@@ -1,5 +1,6 @@
 use crate::{
     buffer::Buffer,
+    descriptor::{Descriptor, DescriptorKind},
     deserialize::{Deserialize, DeserializeError, Deserializer},
     formula::{BareFormula, Formula},
     serialize::{write_bytes, SerializeRef, Sizes},
@@ -9,6 +10,11 @@ impl Formula for str {
     const MAX_STACK_SIZE: Option<usize> = None;
     const EXACT_SIZE: bool = false;
     const HEAPLESS: bool = true;
+
+    #[inline(always)]
+    fn describe() -> Descriptor {
+        Descriptor::new::<str>(DescriptorKind::Str)
+    }
 }
 
 impl BareFormula for str {}
@@ -34,11 +40,7 @@ impl<'de, 'fe: 'de> Deserialize<'fe, str> for &'de str {
     where
         Self: Sized,
     {
-        let bytes = deserializer.read_all_bytes();
-        match core::str::from_utf8(bytes) {
-            Ok(s) => Ok(s),
-            Err(error) => Err(DeserializeError::NonUtf8(error)),
-        }
+        read_str(deserializer)
     }
 
     #[inline(always)]
@@ -46,13 +48,24 @@ impl<'de, 'fe: 'de> Deserialize<'fe, str> for &'de str {
         &mut self,
         deserializer: Deserializer<'fe>,
     ) -> Result<(), DeserializeError> {
+        *self = read_str(deserializer)?;
+        Ok(())
+    }
+}
+
+#[inline(always)]
+fn read_str<'de>(deserializer: Deserializer<'de>) -> Result<&'de str, DeserializeError> {
+    #[cfg(feature = "unchecked-utf8")]
+    if deserializer.trusted_utf8() {
         let bytes = deserializer.read_all_bytes();
-        match core::str::from_utf8(bytes) {
-            Ok(s) => {
-                *self = s;
-                Ok(())
-            }
-            Err(error) => Err(DeserializeError::NonUtf8(error)),
-        }
+        // SAFETY: Caller of `Deserializer::assume_utf8` guarantees
+        // that strings in the input are valid UTF-8.
+        #[allow(unsafe_code)]
+        return Ok(unsafe { core::str::from_utf8_unchecked(bytes) });
+    }
+
+    match core::str::from_utf8(deserializer.read_all_bytes()) {
+        Ok(s) => Ok(s),
+        Err(error) => Err(DeserializeError::NonUtf8(error)),
     }
 }
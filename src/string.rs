@@ -2,6 +2,7 @@ use alloc::{borrow::ToOwned, string::String};
 
 use crate::{
     buffer::Buffer,
+    descriptor::{Descriptor, DescriptorKind, LazyDescriptor},
     deserialize::{Deserialize, DeserializeError, Deserializer},
     formula::{reference_size, Formula},
     reference::Ref,
@@ -12,6 +13,11 @@ impl Formula for String {
     const MAX_STACK_SIZE: Option<usize> = <Ref<str> as Formula>::MAX_STACK_SIZE;
     const EXACT_SIZE: bool = <Ref<str> as Formula>::EXACT_SIZE;
     const HEAPLESS: bool = <Ref<str> as Formula>::HEAPLESS;
+
+    #[inline(always)]
+    fn describe() -> Descriptor {
+        Descriptor::new::<String>(DescriptorKind::Ref(LazyDescriptor::of::<str>()))
+    }
 }
 
 impl<T> Serialize<String> for T
@@ -0,0 +1,107 @@
+//!
+//! This module provides formula for raw bytes that occupy the rest of the value.
+//!
+
+use crate::{
+    buffer::Buffer,
+    descriptor::{Descriptor, DescriptorKind},
+    deserialize::{Deserialize, DeserializeError, Deserializer},
+    formula::Formula,
+    serialize::{write_bytes, Serialize, Sizes},
+};
+
+/// A formula for raw bytes that take all remaining stack bytes.
+///
+/// Intended for the last field of a struct or tuple,
+/// where it is stored without length prefix,
+/// so opaque payloads are tunneled with zero overhead.
+/// Same as [`Bytes`](crate::Bytes) in that position,
+/// but serializable from anything that implements `AsRef<[u8]>`.
+/// Anywhere else length prefix is written as for `Bytes`.
+///
+/// Deserializable into `&[u8]`, `Vec<u8>` and `bytes::Bytes`
+/// with "bytes" feature.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// let mut buffer = [0u8; 64];
+/// let size = write_packet::<(u16, Tail), _>((7u16, [1u8, 2, 3]), &mut buffer).unwrap();
+///
+/// let ((kind, payload), _) = read_packet::<(u16, Tail), (u16, &[u8])>(&buffer[..size]).unwrap();
+/// assert_eq!(kind, 7);
+/// assert_eq!(payload, [1, 2, 3]);
+/// ```
+pub struct Tail;
+
+impl Formula for Tail {
+    const MAX_STACK_SIZE: Option<usize> = None;
+    const EXACT_SIZE: bool = false;
+    const HEAPLESS: bool = true;
+
+    #[inline(always)]
+    fn describe() -> Descriptor {
+        Descriptor::new::<Tail>(DescriptorKind::Bytes)
+    }
+}
+
+impl<T> Serialize<Tail> for T
+where
+    T: AsRef<[u8]>,
+{
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_bytes(self.as_ref(), sizes, buffer)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        Some(Sizes::with_stack(self.as_ref().len()))
+    }
+}
+
+impl<'de, 'fe: 'de> Deserialize<'fe, Tail> for &'de [u8] {
+    #[inline(always)]
+    fn deserialize(de: Deserializer<'fe>) -> Result<Self, DeserializeError> {
+        Ok(de.read_all_bytes())
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(&mut self, de: Deserializer<'fe>) -> Result<(), DeserializeError> {
+        *self = de.read_all_bytes();
+        Ok(())
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'de> Deserialize<'de, Tail> for alloc::vec::Vec<u8> {
+    #[inline(always)]
+    fn deserialize(de: Deserializer) -> Result<Self, DeserializeError> {
+        Ok(de.read_all_bytes().to_vec())
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(&mut self, de: Deserializer) -> Result<(), DeserializeError> {
+        self.clear();
+        self.extend_from_slice(de.read_all_bytes());
+        Ok(())
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl<'de> Deserialize<'de, Tail> for ::bytes::Bytes {
+    #[inline(always)]
+    fn deserialize(de: Deserializer) -> Result<Self, DeserializeError> {
+        Ok(::bytes::Bytes::copy_from_slice(de.read_all_bytes()))
+    }
+
+    #[inline(always)]
+    fn deserialize_in_place(&mut self, de: Deserializer) -> Result<(), DeserializeError> {
+        *self = ::bytes::Bytes::copy_from_slice(de.read_all_bytes());
+        Ok(())
+    }
+}
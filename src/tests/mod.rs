@@ -188,6 +188,75 @@ fn test_complex_tuple() {
     );
 }
 
+#[test]
+fn test_tuple_16() {
+    type Formula = (
+        u8,
+        u16,
+        u32,
+        u64,
+        i8,
+        i16,
+        i32,
+        i64,
+        bool,
+        (),
+        u8,
+        [u8; 2],
+        Option<u8>,
+        As<str>,
+        Ref<str>,
+        str,
+    );
+    type Value<'a> = (
+        u8,
+        u16,
+        u32,
+        u64,
+        i8,
+        i16,
+        i32,
+        i64,
+        bool,
+        (),
+        u8,
+        [u8; 2],
+        Option<u8>,
+        &'a str,
+        &'a str,
+        &'a str,
+    );
+
+    let value: Value = (
+        1,
+        2,
+        3,
+        4,
+        -5,
+        -6,
+        -7,
+        -8,
+        true,
+        (),
+        11,
+        [12, 13],
+        Some(14),
+        "a",
+        "bc",
+        "def",
+    );
+
+    let mut buffer = [0u8; 256];
+    let (size, root) = serialize::<Formula, Value>(value, &mut buffer).unwrap();
+    let read = deserialize_with_size::<Formula, Value>(&buffer[..size], root).unwrap();
+
+    // std implements `PartialEq` and `Debug` only for tuples up to 12 elements.
+    let (a, b, c, d, e, f, g, h, i, j, k, l, m, n, o, p) = read;
+    assert_eq!((a, b, c, d, e, f, g, h), (1, 2, 3, 4, -5, -6, -7, -8));
+    assert_eq!((i, j, k, l, m), (true, (), 11, [12, 13], Some(14)));
+    assert_eq!((n, o, p), ("a", "bc", "def"));
+}
+
 #[cfg(feature = "alloc")]
 #[test]
 fn test_vec() {
@@ -260,6 +329,236 @@ fn test_slice_of_slice() {
     });
 }
 
+#[cfg(feature = "alloc")]
+#[test]
+fn test_nested_size_hint() {
+    use alloc::{string::String, vec::Vec};
+
+    fn check<F, T>(value: &T)
+    where
+        F: Formula + ?Sized,
+        for<'a> &'a T: Serialize<F>,
+    {
+        let sizes = Serialize::<F>::size_hint(&value).unwrap();
+        assert_eq!((sizes.total(), sizes.stack), serialized_size::<F, _>(value));
+    }
+
+    let bytes: Vec<Vec<u8>> = (0..100u8).map(|n| vec![n; n as usize]).collect();
+    check::<Vec<Vec<u8>>, _>(&bytes);
+    check::<[Vec<u8>], _>(&bytes);
+    check::<[Bytes], _>(&bytes);
+    check::<[As<[u8]>], _>(&bytes);
+
+    let strings: Vec<String> = (0..100).map(|n| "x".repeat(n)).collect();
+    check::<Vec<String>, _>(&strings);
+    check::<[As<str>], _>(&strings);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_indexed() {
+    use alloc::{vec, vec::Vec};
+
+    use crate::indexed::{Indexed, IndexedIter};
+
+    type Message = (Indexed<Vec<u32>>, u32);
+
+    let lists: Vec<Vec<u32>> = (0..10).map(|i| (0..i).collect()).collect();
+
+    let mut buffer = [0u8; 1024];
+    let (size, root) = serialize::<Message, _>((&lists, 42u32), &mut buffer).unwrap();
+
+    let (mut iter, tail) =
+        deserialize_with_size::<Message, (IndexedIter<Vec<u32>, Vec<u32>>, u32)>(
+            &buffer[..size],
+            root,
+        )
+        .unwrap();
+    assert_eq!(tail, 42);
+    assert_eq!(iter.len(), 10);
+    assert_eq!(iter.get(7).unwrap().unwrap(), lists[7]);
+    assert!(iter.get(10).is_none());
+
+    assert_eq!(iter.nth(3).unwrap().unwrap(), lists[3]);
+    assert_eq!(iter.nth_back(2).unwrap().unwrap(), lists[7]);
+    assert_eq!(iter.get(0).unwrap().unwrap(), lists[4]);
+    assert_eq!(iter.len(), 3);
+
+    let rest = iter.map(Result::unwrap).collect::<Vec<_>>();
+    assert_eq!(rest, &lists[4..7]);
+
+    let (all, _) =
+        deserialize_with_size::<Message, (Vec<Vec<u32>>, u32)>(&buffer[..size], root).unwrap();
+    assert_eq!(all, lists);
+
+    let (size, root) = serialize::<Indexed<u8>, _>(vec![1u8, 2, 3], &mut buffer).unwrap();
+    let all = deserialize_with_size::<Indexed<u8>, Vec<u8>>(&buffer[..size], root).unwrap();
+    assert_eq!(all, [1, 2, 3]);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_inline_ref() {
+    use alloc::{vec, vec::Vec};
+
+    use crate::{formula::reference_size, iter::SerIter, reference::InlineRef, size::SIZE_STACK};
+
+    let mut buffer = [0u8; 256];
+
+    let (size, root) = serialize::<InlineRef<str>, _>("abc", &mut buffer).unwrap();
+    assert_eq!(size, 1 + reference_size::<str>());
+    assert_eq!(root, size);
+    let value = deserialize::<InlineRef<str>, &str>(&buffer[..size]).unwrap();
+    assert_eq!(value, "abc");
+
+    let long = "does not fit into reference";
+    let (size, root) = serialize::<InlineRef<str>, _>(long, &mut buffer).unwrap();
+    assert_eq!(size, 1 + 2 * SIZE_STACK + long.len());
+    assert_eq!(root, 1 + 2 * SIZE_STACK);
+    let mut value = "";
+    deserialize_in_place_with_size::<InlineRef<str>, &str>(&mut value, &buffer[..size], root)
+        .unwrap();
+    assert_eq!(value, long);
+
+    type Message = [(InlineRef<[u8]>, u8)];
+    let (size, root) = serialize::<Message, _>(
+        [([1u8, 2].as_slice(), 3u8), (&[4; 16], 5)].iter().copied(),
+        &mut buffer,
+    )
+    .unwrap();
+    let value =
+        deserialize_with_size::<Message, Vec<(Vec<u8>, u8)>>(&buffer[..size], root).unwrap();
+    assert_eq!(value, [(vec![1, 2], 3), (vec![4; 16], 5)]);
+
+    // Values without size hint are always referenced.
+    let (size, _) =
+        serialize::<InlineRef<[u8]>, _>(SerIter([1u8].into_iter().filter(|_| true)), &mut buffer)
+            .unwrap();
+    assert_eq!(size, 1 + 2 * SIZE_STACK + 1);
+}
+
+#[test]
+fn test_lazy_slice_len() {
+    let mut buffer = [0u8; 256];
+
+    let (size, root) = serialize::<[u32], _>([1u32, 2, 3], &mut buffer).unwrap();
+    let lazy = deserialize_with_size::<[u32], Lazy<[u32]>>(&buffer[..size], root).unwrap();
+    assert_eq!(lazy.len(), 3);
+    assert!(!lazy.is_empty());
+    assert_eq!(Iterator::size_hint(&lazy.iter::<u32>()), (3, Some(3)));
+
+    let (size, root) = serialize::<[()], _>([(), (), (), ()], &mut buffer).unwrap();
+    let lazy = deserialize_with_size::<[()], Lazy<[()]>>(&buffer[..size], root).unwrap();
+    assert_eq!(lazy.len(), 4);
+
+    let (size, root) = serialize::<[Bytes], _>([&b"a"[..], b"bc", b""], &mut buffer).unwrap();
+    let lazy = deserialize_with_size::<[Bytes], Lazy<[Bytes]>>(&buffer[..size], root).unwrap();
+    assert_eq!(lazy.len(), 3);
+
+    let (size, root) = serialize::<[Bytes], _>([&b""[..]; 0], &mut buffer).unwrap();
+    let lazy = deserialize_with_size::<[Bytes], Lazy<[Bytes]>>(&buffer[..size], root).unwrap();
+    assert_eq!(lazy.len(), 0);
+    assert!(lazy.is_empty());
+}
+
+#[test]
+fn test_lazy_chunks() {
+    let mut buffer = [0u8; 256];
+
+    let (size, root) = serialize::<[u16], _>(0..10u16, &mut buffer).unwrap();
+    let lazy = deserialize_with_size::<[u16], Lazy<[u16]>>(&buffer[..size], root).unwrap();
+    let lens = lazy.chunks(4).map(|chunk| chunk.unwrap().len());
+    assert!(lens.eq([4, 4, 2]));
+
+    let (size, root) =
+        serialize::<[Bytes], _>([&b"a"[..], b"bc", b"", b"def"], &mut buffer).unwrap();
+    let lazy = deserialize_with_size::<[Bytes], Lazy<[Bytes]>>(&buffer[..size], root).unwrap();
+    let mut chunks = lazy.chunks(3);
+    let mut first = chunks.next().unwrap().unwrap().iter::<&[u8]>();
+    assert_eq!(first.next().unwrap().unwrap(), b"a");
+    assert_eq!(first.next().unwrap().unwrap(), b"bc");
+    assert_eq!(first.next().unwrap().unwrap(), b"");
+    assert!(first.next().is_none());
+    let mut second = chunks.next().unwrap().unwrap().iter::<&[u8]>();
+    assert_eq!(second.next().unwrap().unwrap(), b"def");
+    assert!(second.next().is_none());
+    assert!(chunks.next().is_none());
+}
+
+#[test]
+fn test_lazy_combinators() {
+    use crate::deserialize::{DeserializeError, Deserializer};
+
+    let mut buffer = [0u8; 256];
+
+    let (size, root) =
+        serialize::<[(u8, [u16; 2])], _>([(1u8, [2u16, 3]), (4, [5, 6]), (7, [8, 9])], &mut buffer)
+            .unwrap();
+    let lazy =
+        deserialize_with_size::<[(u8, [u16; 2])], Lazy<[(u8, [u16; 2])]>>(&buffer[..size], root)
+            .unwrap();
+
+    let mut elems = lazy.lazy_iter();
+    let second = elems.nth(1).unwrap().unwrap();
+    assert_eq!(second.get::<(u8, [u16; 2])>().unwrap(), (4, [5, 6]));
+    assert_eq!(second.map(|(tag, _): (u8, [u16; 2])| tag).unwrap(), 4);
+    assert_eq!(elems.count(), 1);
+
+    let sum = lazy
+        .map(|elems: Vec<(u8, [u16; 2])>| elems.iter().map(|(tag, _)| u32::from(*tag)).sum::<u32>())
+        .unwrap();
+    assert_eq!(sum, 12);
+
+    let total: Result<u32, DeserializeError> = lazy.try_map(|elems: Vec<Lazy<(u8, [u16; 2])>>| {
+        elems
+            .iter()
+            .map(|elem| elem.map(|(_, [a, b]): (u8, [u16; 2])| u32::from(a + b)))
+            .sum()
+    });
+    assert_eq!(total.unwrap(), 33);
+
+    let mut de = lazy
+        .lazy_iter()
+        .next()
+        .unwrap()
+        .unwrap()
+        .into_deserializer();
+    assert_eq!(de.read_value::<u8, u8>(false).unwrap(), 1);
+    assert_eq!(de.read_value::<[u16; 2], [u16; 2]>(true).unwrap(), [2, 3]);
+
+    let mut de = Deserializer::from(lazy.clone());
+    assert_eq!(
+        de.read_back_value::<(u8, [u16; 2]), (u8, [u16; 2])>()
+            .unwrap(),
+        (7, [8, 9])
+    );
+    assert_eq!(lazy.deserializer().read_all_bytes().len(), root);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_deserialize_from_iter_capacity() {
+    use alloc::vec::Vec;
+
+    use crate::iter::{deserialize_extend_iter, deserialize_from_iter};
+
+    let mut buffer = vec![0u8; 8192];
+    let (size, root) = serialize::<[u32], _>(0..1500u32, &mut buffer).unwrap();
+    let lazy = deserialize_with_size::<[u32], Lazy<[u32]>>(&buffer[..size], root).unwrap();
+
+    let vec: Vec<u32> = deserialize_from_iter(lazy.iter::<u32>()).unwrap();
+    assert_eq!(vec.len(), 1500);
+    assert_eq!(vec.capacity(), 1500);
+
+    let mut vec = Vec::new();
+    deserialize_extend_iter(&mut vec, lazy.iter::<u32>()).unwrap();
+    assert_eq!(vec.capacity(), 1500);
+
+    let (size, root) = serialize::<[Vlq], _>([1u32, 300, 2], &mut buffer).unwrap();
+    let lazy = deserialize_with_size::<[Vlq], Lazy<[Vlq]>>(&buffer[..size], root).unwrap();
+    assert!(deserialize_from_iter::<_, Vec<u8>>(lazy.iter::<u8>()).is_err());
+}
+
 #[test]
 fn test_size() {
     const REFS: usize = 4;
@@ -470,92 +769,5250 @@ fn test_bincoded() {
     assert_eq!(de.0, 102414);
 }
 
+#[cfg(feature = "bincoded")]
 #[test]
-fn test_zero_sized_arrays() {
-    serialize::<[u8; 0], [u8; 0]>([], &mut []).unwrap();
-    serialize::<[(); 1], [(); 1]>([()], &mut []).unwrap();
+fn test_bincode_options() {
+    use crate::{bincoded::*, deserialize::DeserializeError};
 
-    let [] = deserialize::<[u8; 0], [u8; 0]>(&[]).unwrap();
-    let [()] = deserialize::<[(); 1], [(); 1]>(&[]).unwrap();
+    let mut buffer = [0u8; 1024];
 
-    #[cfg(feature = "alloc")]
-    {
-        deserialize::<[u8; 0], Vec<u8>>(&[]).unwrap();
-        deserialize::<[u8; 0], VecDeque<u8>>(&[]).unwrap();
+    // Varint encoding by default.
+    let (size, root) = serialize::<Bincode, _>(1u32, &mut buffer).unwrap();
+    assert_eq!(buffer[0], 1);
+    assert_eq!(size, 1 + root);
+
+    let (size, _) = serialize::<Bincode<BincodeLegacy>, _>(0x0102_0304u32, &mut buffer).unwrap();
+    let legacy = bincode::serialize(&0x0102_0304u32).unwrap();
+    assert_eq!(buffer[..legacy.len()], *legacy);
+    assert_eq!(
+        deserialize::<Bincode<BincodeLegacy>, u32>(&buffer[..size]).unwrap(),
+        0x0102_0304
+    );
+
+    type BigFixint = BincodeBigEndian<BincodeFixint>;
+    let (size, _) = serialize::<Bincoded<u32, BigFixint>, _>(0x0102_0304u32, &mut buffer).unwrap();
+    assert_eq!(buffer[..4], [1, 2, 3, 4]);
+    assert_eq!(
+        deserialize::<Bincoded<u32, BigFixint>, u32>(&buffer[..size]).unwrap(),
+        0x0102_0304
+    );
+
+    let (size, _) = serialize::<Bincode, _>([7u8; 8], &mut buffer).unwrap();
+    assert!(matches!(
+        deserialize::<Bincode<BincodeLimit<4>>, [u8; 8]>(&buffer[..size]),
+        Err(DeserializeError::Incompatible)
+    ));
+    assert!(deserialize::<Bincode<BincodeLimit<8>>, [u8; 8]>(&buffer[..size]).is_ok());
+}
+
+#[cfg(feature = "serde-postcard")]
+#[test]
+fn test_postcard() {
+    use crate::{
+        deserialize::DeserializeError,
+        postcarded::{Postcard, Postcarded},
+    };
+
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct Reading<'a> {
+        id: u32,
+        name: &'a str,
     }
+
+    let reading = Reading { id: 300, name: "t" };
+
+    let mut encoded = [0u8; 16];
+    let encoded = postcard::to_slice(&reading, &mut encoded).unwrap();
+
+    let mut buffer = [0u8; 1024];
+    let (size, root) = serialize::<Postcard, _>(&reading, &mut buffer).unwrap();
+    assert_eq!(buffer[..encoded.len()], *encoded);
+
+    let de = deserialize_with_size::<Postcard, Reading>(&buffer[..size], root).unwrap();
+    assert_eq!(de, reading);
+
+    let size = serialize::<(u8, Postcarded<u64>), _>((3u8, 5u64), &mut buffer).unwrap();
+    let (a, b) = deserialize::<(u8, Postcarded<u64>), (u8, u64)>(&buffer[..size.0]).unwrap();
+    assert_eq!((a, b), (3, 5));
+
+    // Trailing bytes are not ignored.
+    let (size, root) = serialize::<Postcard, _>((1u8, 2u8), &mut buffer).unwrap();
+    assert!(matches!(
+        deserialize_with_size::<Postcard, u8>(&buffer[..size], root),
+        Err(DeserializeError::Incompatible)
+    ));
 }
 
-#[cfg(feature = "derive")]
+#[cfg(feature = "bytes")]
 #[test]
-fn test_recursive_types() {
-    use alkahest_proc::alkahest;
+fn test_bytes_buf() {
+    use ::bytes::{Buf, BytesMut};
+    use alloc::{string::String, vec::Vec};
 
-    let mut buffer = [0; 1024];
+    use crate::{
+        buf::{read_packet_from_buf, write_packet_to_buf},
+        deserialize::DeserializeError,
+    };
 
-    #[derive(Clone, Debug, PartialEq, Eq)]
-    #[alkahest(Formula, SerializeRef, Deserialize)]
-    struct Node {
-        value: u32,
-        children: Vec<Node>,
+    type Message = (u32, String, Vec<u16>);
+
+    let mut buf = BytesMut::new();
+    let size = write_packet_to_buf::<Message, _, _>((1u32, "one", [1u16, 2]), &mut buf);
+    write_packet_to_buf::<Message, _, _>((2u32, "two", [3u16]), &mut buf);
+    assert!(buf.len() > size);
+
+    // Split the stream in the middle of the second packet.
+    let mut packets = buf.freeze();
+    let tail = packets.split_off(size + 3);
+    let mut chain = packets.chain(tail);
+
+    let value = read_packet_from_buf::<Message, Message, _>(&mut chain).unwrap();
+    assert_eq!(value, (1, "one".into(), alloc::vec![1, 2]));
+
+    let value = read_packet_from_buf::<Message, Message, _>(&mut chain).unwrap();
+    assert_eq!(value, (2, "two".into(), alloc::vec![3]));
+    assert_eq!(chain.remaining(), 0);
+
+    assert!(matches!(
+        read_packet_from_buf::<Message, Message, _>(&mut chain),
+        Err(DeserializeError::OutOfBounds(_))
+    ));
+}
+
+#[cfg(feature = "tokio-codec")]
+#[test]
+fn test_tokio_codec() {
+    use ::bytes::BytesMut;
+    use alloc::{string::String, vec::Vec};
+    use tokio_util::codec::{Decoder, Encoder};
+
+    use crate::codec::{AlkahestCodec, CodecError};
+
+    type Message = (u32, String, Vec<u16>);
+
+    let mut codec = AlkahestCodec::<Message, Message>::new();
+    let mut stream = BytesMut::new();
+    codec.encode((1u32, "one", [1u16, 2]), &mut stream).unwrap();
+    codec.encode((2u32, "two", [3u16]), &mut stream).unwrap();
+
+    // Frames arrive in pieces.
+    let mut src = stream.split_to(3);
+    assert!(codec.decode(&mut src).unwrap().is_none());
+    src.unsplit(stream);
+
+    let value = codec.decode(&mut src).unwrap().unwrap();
+    assert_eq!(value, (1, "one".into(), alloc::vec![1, 2]));
+    let value = codec.decode(&mut src).unwrap().unwrap();
+    assert_eq!(value, (2, "two".into(), alloc::vec![3]));
+    assert!(codec.decode(&mut src).unwrap().is_none());
+
+    let mut codec = AlkahestCodec::<Message, Message>::with_max_frame_size(16);
+    assert_eq!(codec.max_frame_size(), 16);
+    assert!(matches!(
+        codec.encode((3u32, "three", [0u16; 8]), &mut src),
+        Err(CodecError::Io(_))
+    ));
+}
+
+#[cfg(feature = "embedded-io")]
+#[test]
+fn test_embedded_io() {
+    use crate::io::{read_packet_from_io, write_packet_to_io, IoError};
+
+    type Message = (u32, str);
+
+    let mut scratch = [0u8; 64];
+    let mut stream = [0u8; 64];
+
+    let mut writer = &mut stream[..];
+    let size =
+        write_packet_to_io::<Message, _, _>((1u32, "one"), &mut scratch, &mut writer).unwrap();
+    write_packet_to_io::<Message, _, _>((4u32, "four"), &mut scratch, &mut writer).unwrap();
+
+    let mut reader = &stream[..];
+    let (a, b) = read_packet_from_io::<Message, (u32, &str), _>(&mut reader, &mut scratch).unwrap();
+    assert_eq!((a, b), (1, "one"));
+    assert_eq!(reader.len(), stream.len() - size);
+
+    let (a, b) = read_packet_from_io::<Message, (u32, &str), _>(&mut reader, &mut scratch).unwrap();
+    assert_eq!((a, b), (4, "four"));
+
+    let mut reader = &stream[..size];
+    assert!(matches!(
+        read_packet_from_io::<Message, (u32, &str), _>(&mut reader, &mut scratch[..size - 1]),
+        Err(IoError::BufferExhausted)
+    ));
+
+    let mut reader = &stream[..size - 1];
+    assert!(matches!(
+        read_packet_from_io::<Message, (u32, &str), _>(&mut reader, &mut scratch),
+        Err(IoError::UnexpectedEof)
+    ));
+
+    let mut writer = &mut stream[..];
+    assert!(matches!(
+        write_packet_to_io::<Message, _, _>((1u32, "one"), &mut scratch[..4], &mut writer),
+        Err(IoError::BufferExhausted)
+    ));
+}
+
+#[cfg(feature = "embedded-io-async")]
+#[test]
+fn test_embedded_io_async() {
+    use core::{
+        future::Future,
+        pin::pin,
+        task::{Context, Poll, Waker},
+    };
+
+    use crate::io::{read_packet_from_io_async, write_packet_to_io_async};
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = pin!(future);
+        let mut cx = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
     }
 
-    let node = Node {
-        value: 1,
-        children: vec![
-            Node {
-                value: 2,
-                children: vec![Node {
-                    value: 3,
-                    children: vec![],
-                }],
-            },
-            Node {
-                value: 4,
-                children: vec![],
-            },
-        ],
+    let mut scratch = [0u8; 64];
+    let mut stream = [0u8; 64];
+
+    let mut writer = &mut stream[..];
+    block_on(write_packet_to_io_async::<(u32, str), _, _>(
+        (1u32, "one"),
+        &mut scratch,
+        &mut writer,
+    ))
+    .unwrap();
+
+    let mut reader = &stream[..];
+    let (a, b) = block_on(read_packet_from_io_async::<(u32, str), (u32, &str), _>(
+        &mut reader,
+        &mut scratch,
+    ))
+    .unwrap();
+    assert_eq!((a, b), (1, "one"));
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn test_par_slice() {
+    use crate::{
+        packet::{read_packet, write_packet_to_vec},
+        par::ParSlice,
     };
 
-    let (size, root) = crate::serialize_unchecked::<Node, &Node>(&node, &mut buffer);
-    let de = crate::deserialize_with_size::<Node, Node>(&buffer[..size], root).unwrap();
+    let numbers: Vec<(u32, u16)> = (0..10_000).map(|i| (i, i as u16)).collect();
+    let mut expected = Vec::new();
+    let size = write_packet_to_vec::<Vec<(u32, u16)>, _>(&numbers, &mut expected);
+    let mut buffer = Vec::new();
+    let par_size = write_packet_to_vec::<Vec<(u32, u16)>, _>(ParSlice(&numbers), &mut buffer);
+    assert_eq!(buffer[..par_size], expected[..size]);
 
-    assert_eq!(de, node);
+    let blobs: Vec<Vec<u8>> = (0..10_000).map(|i| vec![0xAB; i % 7]).collect();
+    let mut expected = Vec::new();
+    let size = write_packet_to_vec::<Vec<Bytes>, _>(&blobs, &mut expected);
+    let mut buffer = Vec::new();
+    let par_size = write_packet_to_vec::<Vec<Bytes>, _>(ParSlice(&blobs), &mut buffer);
+    assert_eq!(buffer[..par_size], expected[..size]);
 
-    #[alkahest(Formula where T: Formula)]
-    struct A<T> {
-        a: T,
-        b: Vec<A<T>>,
-    }
+    let (value, _) = read_packet::<Vec<Bytes>, Vec<Vec<u8>>>(&buffer[..par_size]).unwrap();
+    assert_eq!(value, blobs);
+}
 
-    #[derive(Debug)]
-    #[alkahest(for<U: Formula> SerializeRef<A<U>> where for<'a> &'a T: Serialize<U>)]
-    struct B<T> {
-        a: T,
-        b: Vec<B<T>>,
-    }
+#[cfg(feature = "rayon")]
+#[test]
+fn test_par_collect() {
+    use crate::packet::{read_packet, write_packet_to_vec};
 
-    #[derive(Debug)]
-    #[alkahest(for<'de, U: Formula> Deserialize<'de, A<U>> where T: Deserialize<'de, U>)]
-    struct C<T> {
-        a: T,
-        b: Vec<C<T>>,
-    }
+    let blobs: Vec<Vec<u8>> = (0..10_000).map(|i| vec![0xAB; i % 7]).collect();
+    let mut buffer = Vec::new();
+    let size = write_packet_to_vec::<[Bytes], _>(&blobs, &mut buffer);
 
-    impl<T> PartialEq<C<T>> for B<T>
-    where
-        T: PartialEq,
-    {
-        fn eq(&self, other: &C<T>) -> bool {
-            self.a == other.a && self.b == other.b
+    let (lazy, _) = read_packet::<[Bytes], Lazy<[Bytes]>>(&buffer[..size]).unwrap();
+    let value: Vec<Vec<u8>> = lazy.par_collect().unwrap();
+    assert_eq!(value, blobs);
+
+    let mut buffer = Vec::new();
+    let size = write_packet_to_vec::<[()], _>([(); 3], &mut buffer);
+    let (lazy, _) = read_packet::<[()], Lazy<[()]>>(&buffer[..size]).unwrap();
+    let value: Vec<()> = lazy.par_collect().unwrap();
+    assert_eq!(value.len(), 3);
+}
+
+#[test]
+fn test_const_sizes() {
+    use crate::{
+        packet::{max_packet_size, write_packet},
+        serialize::max_size,
+    };
+
+    type Message = (u32, Option<u16>, [u8; 3]);
+
+    const SIZE: usize = max_size::<Message>();
+    const PACKET_SIZE: usize = max_packet_size::<Message>();
+
+    let mut buffer = [0u8; SIZE];
+    let (size, _) = serialize::<Message, _>((1u32, Some(2u16), [3u8; 3]), &mut buffer).unwrap();
+    assert_eq!(size, SIZE);
+
+    let mut buffer = [0u8; PACKET_SIZE];
+    let size = write_packet::<Message, _>((1u32, Some(2u16), [3u8; 3]), &mut buffer).unwrap();
+    assert_eq!(size, PACKET_SIZE);
+
+    assert_eq!(<u32 as Formula>::MAX_SIZE, Some(4));
+    assert_eq!(<str as Formula>::MAX_SIZE, None);
+    assert_eq!(<Ref<u32> as Formula>::MAX_SIZE, None);
+    assert_eq!(<Ref<u32> as Formula>::MAX_PACKET_SIZE, None);
+}
+
+#[test]
+fn test_size_hint_unknown_field() {
+    use core::cell::Cell;
+
+    use crate::{
+        buffer::Buffer,
+        serialize::{Serialize, Sizes},
+    };
+
+    struct Counted<'a>(u32, &'a Cell<usize>);
+
+    impl Serialize<u32> for Counted<'_> {
+        fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+        where
+            B: Buffer,
+        {
+            <u32 as Serialize<u32>>::serialize(self.0, sizes, buffer)
+        }
+
+        fn size_hint(&self) -> Option<Sizes> {
+            self.1.set(self.1.get() + 1);
+            Some(Sizes::with_stack(4))
         }
     }
 
-    let b = B {
-        a: 1,
-        b: vec![B { a: 2, b: vec![] }],
+    let calls = Cell::new(0);
+    let value = (Counted(1, &calls), 2u32, Counted(3, &calls));
+    assert_eq!(Serialize::<(u32, Vlq, u32)>::size_hint(&value), None);
+    assert_eq!(calls.get(), 0);
+
+    let mut buffer = [0u8; 64];
+    let (size, root) = serialize::<(u32, Vlq, u32), _>(value, &mut buffer).unwrap();
+    let (a, b, c) =
+        deserialize_with_size::<(u32, Vlq, u32), (u32, u32, u32)>(&buffer[..size], root).unwrap();
+    assert_eq!((a, b, c), (1, 2, 3));
+    assert_eq!(calls.get(), 0);
+}
+
+#[test]
+fn test_size_cached() {
+    use core::cell::Cell;
+
+    use crate::{
+        buffer::Buffer,
+        cached::SizeCached,
+        packet::{read_packet, write_packet},
+        serialize::Sizes,
     };
 
-    let (size, root) = crate::serialize_unchecked::<A<i32>, &B<i32>>(&b, &mut buffer);
-    let c = crate::deserialize_with_size::<A<i32>, C<i32>>(&buffer[..size], root).unwrap();
-    assert_eq!(b, c);
+    struct Counted<'a>(&'a [u8], &'a Cell<usize>);
+
+    impl Serialize<Bytes> for Counted<'_> {
+        fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+        where
+            B: Buffer,
+        {
+            <&[u8] as Serialize<Bytes>>::serialize(self.0, sizes, buffer)
+        }
+
+        fn size_hint(&self) -> Option<Sizes> {
+            self.1.set(self.1.get() + 1);
+            <&[u8] as Serialize<Bytes>>::size_hint(&self.0)
+        }
+    }
+
+    let calls = Cell::new(0);
+    let mut buffer = [0u8; 64];
+    let size =
+        write_packet::<Ref<(Ref<Bytes>,)>, _>((Counted(b"abc", &calls),), &mut buffer).unwrap();
+    let (value, _) = read_packet::<Ref<(Ref<Bytes>,)>, (&[u8],)>(&buffer[..size]).unwrap();
+    assert_eq!(value, (&b"abc"[..],));
+    assert_eq!(calls.get(), 3);
+
+    let calls = Cell::new(0);
+    let value = (SizeCached::new(Counted(b"abc", &calls)),);
+    let cached_size = write_packet::<Ref<(Ref<Bytes>,)>, _>(value, &mut buffer).unwrap();
+    assert_eq!(cached_size, size);
+    let (value, _) = read_packet::<Ref<(Ref<Bytes>,)>, (&[u8],)>(&buffer[..size]).unwrap();
+    assert_eq!(value, (&b"abc"[..],));
+    assert_eq!(calls.get(), 1);
+}
+
+#[test]
+fn test_packet_summary() {
+    use crate::{
+        fingerprint::fingerprint,
+        packet::{write_packet, PacketSummary},
+    };
+
+    let mut buffer = [0u8; 64];
+    let size = write_packet::<(u32, str), _>((1u32, "one"), &mut buffer).unwrap();
+
+    let summary = PacketSummary::new::<(u32, str)>(&buffer[..size]);
+    assert_eq!(summary.fingerprint, fingerprint::<(u32, str)>());
+    assert_eq!(summary.input_len, size);
+    assert_eq!(summary.packet_size, Some(size));
+    assert!(summary.is_consistent());
+
+    let summary = PacketSummary::new::<(u32, str)>(&buffer[..size - 1]);
+    assert!(!summary.is_consistent());
+
+    let summary = PacketSummary::new::<(u32, str)>(&buffer[..1]);
+    assert_eq!(summary.packet_size, None);
+    assert!(!summary.is_consistent());
+}
+
+#[cfg(feature = "protobuf")]
+#[test]
+fn test_protobuf() {
+    use alloc::{string::String, vec::Vec};
+
+    use crate::{
+        packet::{read_packet, write_packet_to_vec},
+        protobuf::{packet_to_protobuf, protobuf_to_packet, ProtobufError},
+        schema::Schema,
+    };
+
+    type Message = (u32, String, Vec<u32>, i32);
+
+    let message = [
+        0x08, 0x96, 0x01, // 1: 150
+        0x12, 0x07, b't', b'e', b's', b't', b'i', b'n', b'g', // 2: "testing"
+        0x1a, 0x06, 0x03, 0x8e, 0x02, 0x9e, 0xa7, 0x05, // 3: [3, 270, 86942] packed
+        0x20, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01, // 4: -1
+    ];
+
+    let mut packet = Vec::new();
+    let size = write_packet_to_vec::<Message, _>(
+        (150u32, "testing", [3u32, 270, 86942], -1i32),
+        &mut packet,
+    );
+    assert_eq!(
+        packet_to_protobuf::<Message>(&packet[..size]).unwrap(),
+        message
+    );
+
+    let mut packet = Vec::new();
+    let size = protobuf_to_packet::<Message>(&message, &mut packet).unwrap();
+    let (value, _) = read_packet::<Message, (u32, String, Vec<u32>, i32)>(&packet[..size]).unwrap();
+    assert_eq!(
+        value,
+        (150, "testing".into(), alloc::vec![3, 270, 86942], -1)
+    );
+
+    // Unpacked repeated field, unknown field and missing fields.
+    let message = [0x1a, 0x01, 0x03, 0x18, 0x04, 0x48, 0x01];
+    let size = protobuf_to_packet::<Message>(&message, &mut packet).unwrap();
+    let (value, _) = read_packet::<Message, (u32, String, Vec<u32>, i32)>(&packet[..size]).unwrap();
+    assert_eq!(value, (0, String::new(), alloc::vec![3, 4], 0));
+
+    let schema = Schema::of::<(u8, Option<bool>)>();
+    assert!(matches!(
+        schema.from_protobuf(&[0x08, 0x80, 0x02]),
+        Err(ProtobufError::InvalidValue(1))
+    ));
+    assert!(matches!(
+        schema.from_protobuf(&[0x0a, 0x00]),
+        Err(ProtobufError::WrongWireType(1))
+    ));
+    assert!(matches!(
+        schema.from_protobuf(&[0x10, 0x01, 0x08]),
+        Err(ProtobufError::Malformed)
+    ));
+
+    let schema = Schema::of::<(u128,)>();
+    assert!(matches!(
+        schema.to_protobuf(&crate::value::Value::Tuple(alloc::vec![
+            crate::value::Value::Unsigned(1)
+        ])),
+        Err(ProtobufError::Unsupported(_))
+    ));
+}
+
+#[cfg(all(feature = "protobuf", feature = "derive"))]
+#[test]
+fn test_protobuf_derive() {
+    use alkahest_proc::alkahest;
+    use alloc::{string::String, vec::Vec};
+
+    use crate::{
+        packet::{read_packet, write_packet_to_vec},
+        protobuf::{packet_to_protobuf, protobuf_to_packet},
+    };
+
+    #[derive(Debug, PartialEq)]
+    #[alkahest(Formula, Serialize, Deserialize)]
+    enum Level {
+        Low,
+        High,
+    }
+
+    #[derive(Debug, PartialEq)]
+    #[alkahest(Formula, Serialize, Deserialize)]
+    enum Shape {
+        Circle(f32),
+        Rect { w: u16, h: u16 },
+    }
+
+    #[derive(Debug, PartialEq)]
+    #[alkahest(Formula, Serialize, Deserialize)]
+    struct Item {
+        name: String,
+        level: Level,
+        shapes: Vec<Shape>,
+    }
+
+    let item = Item {
+        name: "a".into(),
+        level: Level::High,
+        shapes: alloc::vec![Shape::Rect { w: 1, h: 2 }],
+    };
+
+    let mut packet = Vec::new();
+    let size = write_packet_to_vec::<Item, _>(
+        Item {
+            name: "a".into(),
+            level: Level::High,
+            shapes: alloc::vec![Shape::Rect { w: 1, h: 2 }],
+        },
+        &mut packet,
+    );
+    let message = packet_to_protobuf::<Item>(&packet[..size]).unwrap();
+    assert_eq!(
+        message,
+        [
+            0x0a, 0x01, b'a', // name
+            0x10, 0x01, // level
+            0x1a, 0x06, 0x12, 0x04, 0x08, 0x01, 0x10, 0x02, // shapes
+        ]
+    );
+
+    let size = protobuf_to_packet::<Item>(&message, &mut packet).unwrap();
+    let (de, _) = read_packet::<Item, Item>(&packet[..size]).unwrap();
+    assert_eq!(de, item);
+}
+
+#[cfg(feature = "serde-json")]
+#[test]
+fn test_json() {
+    use crate::{
+        deserialize::DeserializeError,
+        json::{Json, Jsoned},
+    };
+
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct Point {
+        x: i32,
+        y: i32,
+    }
+
+    let mut buffer = [0u8; 1024];
+
+    let (size, root) = serialize::<Json, _>(Point { x: 1, y: -2 }, &mut buffer).unwrap();
+    assert!(buffer[..size]
+        .windows(14)
+        .any(|window| window == br#"{"x":1,"y":-2}"#));
+
+    let de = deserialize_with_size::<Json, Point>(&buffer[..size], root).unwrap();
+    assert_eq!(de, Point { x: 1, y: -2 });
+
+    let size =
+        serialize::<(u8, Jsoned<Point>), _>((3u8, &Point { x: 4, y: 5 }), &mut buffer).unwrap();
+    let (a, b) = deserialize::<(u8, Jsoned<Point>), (u8, Point)>(&buffer[..size.0]).unwrap();
+    assert_eq!(a, 3);
+    assert_eq!(b, Point { x: 4, y: 5 });
+
+    let (size, root) = serialize::<Json, _>("text", &mut buffer).unwrap();
+    assert!(matches!(
+        deserialize_with_size::<Json, u32>(&buffer[..size], root),
+        Err(DeserializeError::Incompatible)
+    ));
+}
+
+#[test]
+fn test_zero_sized_arrays() {
+    serialize::<[u8; 0], [u8; 0]>([], &mut []).unwrap();
+    serialize::<[(); 1], [(); 1]>([()], &mut []).unwrap();
+
+    let [] = deserialize::<[u8; 0], [u8; 0]>(&[]).unwrap();
+    let [()] = deserialize::<[(); 1], [(); 1]>(&[]).unwrap();
+
+    #[cfg(feature = "alloc")]
+    {
+        deserialize::<[u8; 0], Vec<u8>>(&[]).unwrap();
+        deserialize::<[u8; 0], VecDeque<u8>>(&[]).unwrap();
+    }
+}
+
+#[cfg(feature = "derive")]
+#[test]
+fn test_recursive_types() {
+    use alkahest_proc::alkahest;
+
+    let mut buffer = [0; 1024];
+
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    #[alkahest(Formula, SerializeRef, Deserialize)]
+    struct Node {
+        value: u32,
+        children: Vec<Node>,
+    }
+
+    let node = Node {
+        value: 1,
+        children: vec![
+            Node {
+                value: 2,
+                children: vec![Node {
+                    value: 3,
+                    children: vec![],
+                }],
+            },
+            Node {
+                value: 4,
+                children: vec![],
+            },
+        ],
+    };
+
+    let (size, root) = crate::serialize_unchecked::<Node, &Node>(&node, &mut buffer);
+    let de = crate::deserialize_with_size::<Node, Node>(&buffer[..size], root).unwrap();
+
+    assert_eq!(de, node);
+
+    #[alkahest(Formula where T: Formula)]
+    struct A<T> {
+        a: T,
+        b: Vec<A<T>>,
+    }
+
+    #[derive(Debug)]
+    #[alkahest(for<U: Formula> SerializeRef<A<U>> where for<'a> &'a T: Serialize<U>)]
+    struct B<T> {
+        a: T,
+        b: Vec<B<T>>,
+    }
+
+    #[derive(Debug)]
+    #[alkahest(for<'de, U: Formula> Deserialize<'de, A<U>> where T: Deserialize<'de, U>)]
+    struct C<T> {
+        a: T,
+        b: Vec<C<T>>,
+    }
+
+    impl<T> PartialEq<C<T>> for B<T>
+    where
+        T: PartialEq,
+    {
+        fn eq(&self, other: &C<T>) -> bool {
+            self.a == other.a && self.b == other.b
+        }
+    }
+
+    let b = B {
+        a: 1,
+        b: vec![B { a: 2, b: vec![] }],
+    };
+
+    let (size, root) = crate::serialize_unchecked::<A<i32>, &B<i32>>(&b, &mut buffer);
+    let c = crate::deserialize_with_size::<A<i32>, C<i32>>(&buffer[..size], root).unwrap();
+    assert_eq!(b, c);
+}
+
+#[cfg(all(feature = "alloc", feature = "derive"))]
+#[test]
+fn test_describe() {
+    use alkahest_proc::alkahest;
+
+    use crate::descriptor::DescriptorKind;
+
+    #[alkahest(Formula)]
+    struct Node {
+        value: u32,
+        children: Vec<Node>,
+    }
+
+    #[alkahest(Formula)]
+    enum Message {
+        Ping,
+        Data(Node, As<str>),
+    }
+
+    let descriptor = Message::describe();
+    assert_eq!(descriptor.max_stack_size, None);
+
+    let DescriptorKind::Enum(variants) = descriptor.kind else {
+        panic!("enum is expected");
+    };
+    assert_eq!(variants.len(), 2);
+    assert_eq!((variants[0].name, variants[0].tag), ("Ping", 0));
+    assert_eq!((variants[1].name, variants[1].tag), ("Data", 1));
+    assert_eq!(variants[1].fields[0].name, "0");
+    assert!(matches!(
+        variants[1].fields[1].formula.get().kind,
+        DescriptorKind::Str
+    ));
+
+    let DescriptorKind::Struct(fields) = variants[1].fields[0].formula.get().kind else {
+        panic!("struct is expected");
+    };
+    assert_eq!(fields[0].name, "value");
+    assert!(matches!(
+        fields[0].formula.get().kind,
+        DescriptorKind::Unsigned(4)
+    ));
+    assert_eq!(fields[1].name, "children");
+
+    // Recursive formulas are described lazily.
+    let DescriptorKind::Ref(slice) = fields[1].formula.get().kind else {
+        panic!("reference is expected");
+    };
+    let DescriptorKind::Slice(node) = slice.get().kind else {
+        panic!("slice is expected");
+    };
+    assert!(matches!(node.get().kind, DescriptorKind::Struct(_)));
+}
+
+#[cfg(all(feature = "alloc", feature = "derive"))]
+#[test]
+fn test_fingerprint() {
+    use alkahest_proc::alkahest;
+
+    use crate::fingerprint::fingerprint;
+
+    #[alkahest(Formula)]
+    struct Node {
+        value: u32,
+        children: Vec<Node>,
+    }
+
+    #[alkahest(Formula)]
+    struct Renamed {
+        value: u32,
+        children: Vec<Renamed>,
+    }
+
+    #[alkahest(Formula)]
+    struct OtherField {
+        val: u32,
+        children: Vec<OtherField>,
+    }
+
+    #[alkahest(Formula)]
+    struct OtherType {
+        value: u64,
+        children: Vec<OtherType>,
+    }
+
+    assert_eq!(fingerprint::<Node>(), fingerprint::<Renamed>());
+    assert_ne!(fingerprint::<Node>(), fingerprint::<OtherField>());
+    assert_ne!(fingerprint::<Node>(), fingerprint::<OtherType>());
+
+    // Formula with the same layout but different structure.
+    assert_ne!(fingerprint::<(u32, Vec<u32>)>(), fingerprint::<Node>());
+    assert_eq!(fingerprint::<Vec<u8>>(), fingerprint::<Ref<[u8]>>());
+}
+
+#[cfg(all(feature = "alloc", feature = "derive"))]
+#[test]
+fn test_described_packet() {
+    use alloc::{borrow::ToOwned, boxed::Box, string::String};
+
+    use alkahest_proc::alkahest;
+
+    use crate::{
+        deserialize::DeserializeError,
+        packet::{
+            read_described_packet, read_described_packet_value, write_described_packet_to_vec,
+        },
+        schema::Schema,
+        value::Value,
+    };
+
+    #[alkahest(Formula, SerializeRef, Deserialize)]
+    #[derive(Debug, PartialEq)]
+    struct Node {
+        name: String,
+        children: Vec<Node>,
+    }
+
+    #[alkahest(Formula, SerializeRef, Deserialize)]
+    #[derive(Debug, PartialEq)]
+    enum Message {
+        Ping,
+        Tree { root: Node, depth: Option<u8> },
+    }
+
+    let message = Message::Tree {
+        root: Node {
+            name: "root".to_owned(),
+            children: vec![Node {
+                name: "leaf".to_owned(),
+                children: vec![],
+            }],
+        },
+        depth: Some(2),
+    };
+
+    let mut buffer = Vec::new();
+    let size = write_described_packet_to_vec::<Message, _>(&message, &mut buffer);
+
+    let (de, read) = read_described_packet::<Message, Message>(&buffer[..size]).unwrap();
+    assert_eq!(de, message);
+    assert_eq!(read, size);
+
+    let err = read_described_packet::<Node, Node>(&buffer[..size]).unwrap_err();
+    assert!(matches!(err, DeserializeError::Incompatible));
+
+    let (schema, value, read) = read_described_packet_value(&buffer[..size]).unwrap();
+    assert_eq!(schema, Schema::of::<Message>());
+    assert_eq!(read, size);
+
+    let node = |name: &str, children| {
+        Value::Struct(vec![
+            ("name".to_owned(), Value::Str(name.to_owned())),
+            ("children".to_owned(), Value::Array(children)),
+        ])
+    };
+
+    assert_eq!(
+        value,
+        Value::Enum {
+            name: "Tree".to_owned(),
+            tag: 1,
+            fields: vec![
+                ("root".to_owned(), node("root", vec![node("leaf", vec![])])),
+                (
+                    "depth".to_owned(),
+                    Value::Option(Some(Box::new(Value::Unsigned(2))))
+                ),
+            ],
+        }
+    );
+}
+
+#[cfg(all(feature = "alloc", feature = "derive"))]
+#[test]
+fn test_compatibility() {
+    use alloc::{borrow::ToOwned, string::String};
+
+    use alkahest_proc::alkahest;
+
+    use crate::{
+        compat::{check_compatibility, Compatibility, SchemaChangeKind},
+        packet::{read_packet, write_packet_to_vec},
+        schema::Schema,
+    };
+
+    #[alkahest(Formula, Serialize, Deserialize)]
+    #[derive(Debug, PartialEq)]
+    struct V1 {
+        id: u32,
+        name: String,
+    }
+
+    #[alkahest(Formula, Serialize, Deserialize)]
+    #[derive(Debug, PartialEq)]
+    struct V2 {
+        id: u32,
+    }
+
+    #[alkahest(Formula, Serialize, Deserialize)]
+    #[derive(Debug, PartialEq)]
+    struct V3 {
+        key: u32,
+        name: String,
+    }
+
+    #[alkahest(Formula)]
+    struct Wide {
+        id: u64,
+        name: String,
+    }
+
+    #[alkahest(Formula)]
+    struct Outer1 {
+        inner: V1,
+        tail: u8,
+    }
+
+    #[alkahest(Formula)]
+    struct Outer2 {
+        inner: V2,
+        tail: u8,
+    }
+
+    #[alkahest(Formula, Serialize, Deserialize)]
+    #[derive(Debug, PartialEq)]
+    enum E1 {
+        A,
+        C(u16),
+        B(u8),
+    }
+
+    #[alkahest(Formula, Serialize, Deserialize)]
+    #[derive(Debug, PartialEq)]
+    enum E2 {
+        A,
+        C(u16),
+    }
+
+    let v1 = Schema::of::<V1>();
+    let v2 = Schema::of::<V2>();
+
+    let report = check_compatibility(&v1, &v2);
+    assert_eq!(report.compatibility, Compatibility::Lossy);
+    assert_eq!(
+        report.changes[0].kind,
+        SchemaChangeKind::FieldRemoved {
+            name: "name".to_owned()
+        }
+    );
+
+    let mut buffer = Vec::new();
+    let size = write_packet_to_vec::<V1, _>(
+        V1 {
+            id: 42,
+            name: "x".to_owned(),
+        },
+        &mut buffer,
+    );
+    let (v, _) = read_packet::<V2, V2>(&buffer[..size]).unwrap();
+    assert_eq!(v, V2 { id: 42 });
+
+    let report = check_compatibility(&v2, &v1);
+    assert_eq!(report.compatibility, Compatibility::Incompatible);
+
+    let report = check_compatibility(&v1, &Schema::of::<V3>());
+    assert!(report.is_compatible());
+    assert_eq!(report.changes[0].path, ".key");
+
+    let report = check_compatibility(&v1, &Schema::of::<Wide>());
+    assert_eq!(report.compatibility, Compatibility::Incompatible);
+    assert_eq!(
+        report.changes[0].kind,
+        SchemaChangeKind::SizeChanged {
+            writer: 4,
+            reader: 8
+        }
+    );
+
+    // Removing a field from a structure in the middle shifts following fields.
+    let report = check_compatibility(&Schema::of::<Outer1>(), &Schema::of::<Outer2>());
+    assert_eq!(report.compatibility, Compatibility::Incompatible);
+
+    let e1 = Schema::of::<E1>();
+    let e2 = Schema::of::<E2>();
+    assert_eq!(
+        check_compatibility(&e1, &e2).compatibility,
+        Compatibility::Lossy
+    );
+    assert!(check_compatibility(&e2, &e1).is_compatible());
+
+    let size = write_packet_to_vec::<E2, _>(E2::A, &mut buffer);
+    let (e, _) = read_packet::<E1, E1>(&buffer[..size]).unwrap();
+    assert_eq!(e, E1::A);
+}
+
+#[cfg(all(feature = "alloc", feature = "derive"))]
+#[test]
+fn test_schema_export() {
+    use alkahest_proc::alkahest;
+
+    use crate::schema::Schema;
+
+    #[alkahest(Formula)]
+    struct Node {
+        value: u32,
+        children: Vec<Node>,
+    }
+
+    #[alkahest(Formula)]
+    enum Message {
+        Ping,
+        Tree { root: Node, depth: Option<u8> },
+    }
+
+    assert_eq!(
+        Schema::of::<Message>().to_idl(),
+        "root Message;
+
+enum Message {
+    Ping = 0,
+    Tree { root: Node, depth: Option<u8> } = 1,
+}
+
+struct Node { value: u32, children: Ref<[Node]> };
+"
+    );
+
+    let json = Schema::of::<Node>().to_json();
+    assert!(json.starts_with(r#"{"nodes":[{"name":"alkahest::tests::test_schema_export::Node","#));
+    assert!(json.contains(
+        r#""kind":"struct","fields":[{"name":"value","formula":1},{"name":"children","formula":2}]"#
+    ));
+}
+
+#[cfg(all(feature = "alloc", feature = "derive"))]
+#[test]
+fn test_codegen() {
+    use alkahest_proc::alkahest;
+
+    use alloc::string::String;
+
+    use crate::{codegen::generate_rust, fingerprint::fingerprint, schema::Schema};
+
+    #[alkahest(Formula)]
+    struct Point(i32, i32);
+
+    #[alkahest(Formula)]
+    enum Shape {
+        Empty,
+        Line {
+            from: Point,
+            to: Point,
+        },
+        Polygon {
+            name: String,
+            points: Vec<Point>,
+            tag: As<str>,
+        },
+    }
+
+    let schema = Schema::of::<Shape>();
+    let json = schema.to_json();
+    assert_eq!(Schema::from_json(&json).unwrap(), schema);
+
+    let code = generate_rust(&json).unwrap();
+    assert_eq!(
+        code,
+        "// Generated from alkahest schema. Do not edit.
+
+#[::alkahest::alkahest(Formula)]
+pub enum Shape {
+    Empty,
+    Line {
+        from: Point,
+        to: Point,
+    },
+    Polygon {
+        name: ::alkahest::Ref<str>,
+        points: ::alkahest::Ref<[Point]>,
+        tag: ::alkahest::As<str>,
+    },
+}
+
+#[::alkahest::alkahest(Formula)]
+pub struct Point(pub i32, pub i32);
+"
+    );
+
+    // Same code as generated above.
+    mod generated {
+        #[::alkahest::alkahest(Formula)]
+        pub enum Shape {
+            Empty,
+            Line {
+                from: Point,
+                to: Point,
+            },
+            Polygon {
+                name: ::alkahest::Ref<str>,
+                points: ::alkahest::Ref<[Point]>,
+                tag: ::alkahest::As<str>,
+            },
+        }
+
+        #[::alkahest::alkahest(Formula)]
+        pub struct Point(pub i32, pub i32);
+    }
+
+    assert_eq!(fingerprint::<generated::Shape>(), fingerprint::<Shape>());
+
+    let error = Schema::from_json(r#"{"nodes":[{"name":"X"}]}"#).unwrap_err();
+    assert_eq!(error.offset, 10);
+    assert_eq!(error.message, "missing property");
+}
+
+#[cfg(all(feature = "alloc", feature = "derive"))]
+#[test]
+fn test_bindings() {
+    use alkahest_proc::alkahest;
+    use alloc::string::String;
+
+    use crate::schema::Schema;
+
+    #[alkahest(Formula, Serialize)]
+    struct Point(i32, i32);
+
+    #[alkahest(Formula, Serialize)]
+    enum Command {
+        Move { to: Point },
+        Paint([u8; 4], u32),
+    }
+
+    #[alkahest(Formula, Serialize)]
+    struct Frame {
+        id: u64,
+        command: Command,
+        flags: (bool, u16),
+    }
+
+    #[alkahest(Formula)]
+    struct Named {
+        name: String,
+    }
+
+    let schema = Schema::of::<Frame>();
+
+    let header = schema.to_c_header();
+    assert!(header.contains(
+        "typedef struct {
+    struct {
+        uint16_t _1;
+        uint8_t _0;
+    } flags;
+    Command command;
+    uint64_t id;
+} Frame;
+#define FRAME_SIZE 23
+#define FRAME_ID_OFFSET 15
+#define FRAME_COMMAND_OFFSET 3
+#define FRAME_FLAGS_OFFSET 0
+"
+    ));
+    assert!(header.contains("#define COMMAND_TAG_OFFSET 8\n"));
+    assert!(header.contains("#define COMMAND_PAINT_1_OFFSET 0\n"));
+
+    let ts = schema.to_typescript();
+    assert!(ts.contains("  | { kind: \"Paint\"; 0: number[]; 1: number };"));
+    assert!(ts.contains("  return [view.getInt32(offset + 4, true), view.getInt32(offset, true)];"));
+    assert!(ts.contains("      view.setUint32(offset + 8, 1, true);"));
+
+    // Offsets match serialized layout.
+    let mut buffer = [0u8; 23];
+    let frame = Frame {
+        id: 0x0102_0304_0506_0708,
+        command: Command::Paint([1, 2, 3, 4], 5),
+        flags: (true, 6),
+    };
+    assert_eq!(serialize::<Frame, _>(frame, &mut buffer).unwrap(), (23, 23));
+    assert_eq!(buffer[15..], 0x0102_0304_0506_0708u64.to_le_bytes());
+    assert_eq!(buffer[3 + 8..15], 1u32.to_le_bytes());
+    assert_eq!(buffer[3 + 4..3 + 8], [4, 3, 2, 1]);
+    assert_eq!(buffer[3..3 + 4], 5u32.to_le_bytes());
+    assert_eq!(buffer[..3], [6, 0, 1]);
+
+    let ts = Schema::of::<Named>().to_typescript();
+    assert!(ts.contains("// Named is not fixed-size and is skipped."));
+}
+
+#[cfg(all(feature = "std", feature = "derive"))]
+#[test]
+fn test_inspect() {
+    use alkahest_proc::alkahest;
+
+    use crate::{inspect::inspect_packet, packet::write_packet_to_vec, size::SIZE_STACK};
+
+    #[alkahest(Formula, Serialize)]
+    enum Shape {
+        Dot,
+        Polygon { name: String, points: Vec<(u8, u8)> },
+    }
+
+    let shape = Shape::Polygon {
+        name: "tri".into(),
+        points: vec![(0, 0), (1, 0)],
+    };
+    let mut buffer = Vec::new();
+    let size = write_packet_to_vec::<Shape, _>(shape, &mut buffer);
+
+    // Heap starts after packet header, root follows both heap values.
+    let heap = 2 * SIZE_STACK;
+    let root = heap + 7;
+    let tag = root + 4 * SIZE_STACK;
+    let address = tag + 4;
+    let r = |start: usize, end: usize| format!("{start:04x}..{end:04x}");
+
+    assert_eq!(
+        inspect_packet::<Shape>(&buffer[..size]),
+        format!(
+            "{} packet: address {address}, size {}
+{}   root: Shape::Polygon (tag 1)
+{}     .name: String -> {}
+{}       *: str = \"tri\" [74 72 69]
+{}     .points: Vec<(u8, u8)> -> {}
+{}       *: [(u8, u8)]
+{}         [0]: (u8, u8)
+{}           .0: u8 = 0 [00]
+{}           .1: u8 = 0 [00]
+{}         [1]: (u8, u8)
+{}           .0: u8 = 1 [01]
+{}           .1: u8 = 0 [00]
+",
+            r(0, heap),
+            address - root,
+            r(root, address),
+            r(root + 2 * SIZE_STACK, tag),
+            r(heap, heap + 3),
+            r(heap, heap + 3),
+            r(root, root + 2 * SIZE_STACK),
+            r(heap + 3, root),
+            r(heap + 3, root),
+            r(heap + 5, root),
+            r(heap + 6, root),
+            r(heap + 5, heap + 6),
+            r(heap + 3, heap + 5),
+            r(heap + 4, heap + 5),
+            r(heap + 3, heap + 4),
+        )
+    );
+
+    // Corrupt string content.
+    buffer[heap] = 0xff;
+    let dump = inspect_packet::<Shape>(&buffer[..size]);
+    assert!(dump.contains(&format!("{}       !! *: NonUtf8(", r(heap, heap + 3))));
+    assert!(dump.ends_with(&format!(
+        "{}           .1: u8 = 0 [00]\n",
+        r(heap + 3, heap + 4)
+    )));
+
+    // Corrupt variant tag.
+    buffer[tag] = 7;
+    let dump = inspect_packet::<Shape>(&buffer[..size]);
+    assert!(dump.contains(&format!("{}   !! root: WrongVariant(7", r(root, address))));
+    assert!(dump.ends_with(" more]\n"));
+}
+
+#[cfg(all(feature = "alloc", feature = "derive"))]
+#[test]
+fn test_diff() {
+    use alkahest_proc::alkahest;
+    use alloc::{string::String, vec::Vec};
+
+    use crate::{
+        diff::{diff, ValueDiff},
+        packet::write_packet_to_vec,
+        value::Value,
+    };
+
+    #[alkahest(Formula, Serialize)]
+    enum Status {
+        Active,
+        Banned { reason: String },
+    }
+
+    #[alkahest(Formula, Serialize)]
+    struct User {
+        id: u32,
+        name: String,
+        tags: Vec<String>,
+        status: Status,
+        score: Option<u16>,
+    }
+
+    let packet = |user: User| {
+        let mut buffer = Vec::new();
+        write_packet_to_vec::<User, _>(user, &mut buffer);
+        buffer
+    };
+
+    let old = packet(User {
+        id: 1,
+        name: "alice".into(),
+        tags: vec!["a".into(), "b".into()],
+        status: Status::Active,
+        score: Some(10),
+    });
+
+    assert_eq!(diff::<User>(&old, &old).unwrap(), []);
+
+    let new = packet(User {
+        id: 1,
+        name: "alicia".into(),
+        tags: vec!["a".into(), "c".into(), "d".into()],
+        status: Status::Banned {
+            reason: "spam".into(),
+        },
+        score: Some(12),
+    });
+
+    let str = |s: &str| Some(Value::Str(s.into()));
+    assert_eq!(
+        diff::<User>(&old, &new).unwrap(),
+        [
+            ValueDiff {
+                path: ".name".into(),
+                old: str("alice"),
+                new: str("alicia"),
+            },
+            ValueDiff {
+                path: ".tags[1]".into(),
+                old: str("b"),
+                new: str("c"),
+            },
+            ValueDiff {
+                path: ".tags[2]".into(),
+                old: None,
+                new: str("d"),
+            },
+            ValueDiff {
+                path: ".status".into(),
+                old: Some(Value::Enum {
+                    name: "Active".into(),
+                    tag: 0,
+                    fields: vec![],
+                }),
+                new: Some(Value::Enum {
+                    name: "Banned".into(),
+                    tag: 1,
+                    fields: vec![("reason".into(), Value::Str("spam".into()))],
+                }),
+            },
+            ValueDiff {
+                path: ".score?".into(),
+                old: Some(Value::Unsigned(10)),
+                new: Some(Value::Unsigned(12)),
+            },
+        ]
+    );
+}
+
+#[cfg(all(feature = "alloc", feature = "derive"))]
+#[test]
+fn test_envelope() {
+    use alkahest_proc::alkahest;
+    use alloc::{boxed::Box, string::String, vec::Vec};
+
+    use crate::{
+        deserialize::DeserializeError,
+        envelope::{Envelope, Enveloped, Opened, Registry, Tagged},
+        packet::{read_packet, write_packet_to_vec},
+    };
+
+    #[alkahest(Formula, Serialize, Deserialize)]
+    #[derive(Debug, PartialEq)]
+    struct Ping {
+        seq: u32,
+    }
+
+    impl Tagged for Ping {
+        const TAG: u32 = 1;
+    }
+
+    #[alkahest(Formula, Serialize, Deserialize)]
+    #[derive(Debug, PartialEq)]
+    struct Chat {
+        text: String,
+    }
+
+    impl Tagged for Chat {
+        const TAG: u32 = 2;
+    }
+
+    trait Handler {
+        fn handle(&self) -> String;
+    }
+
+    impl Handler for Ping {
+        fn handle(&self) -> String {
+            alloc::format!("ping {}", self.seq)
+        }
+    }
+
+    impl Handler for Chat {
+        fn handle(&self) -> String {
+            self.text.clone()
+        }
+    }
+
+    let mut buffer = Vec::new();
+    let size =
+        write_packet_to_vec::<Envelope, _>(Enveloped::<Ping, _>::new(Ping { seq: 7 }), &mut buffer);
+    let ping = buffer[..size].to_vec();
+
+    buffer.clear();
+    let chat = Chat {
+        text: "hello".into(),
+    };
+    let size = write_packet_to_vec::<Envelope, _>(Enveloped::<Chat, _>::new(chat), &mut buffer);
+    let chat = buffer[..size].to_vec();
+
+    let (opened, _) = read_packet::<Envelope, Opened>(&chat).unwrap();
+    assert!(opened.is::<Chat>());
+    assert!(matches!(
+        opened.clone().decode::<Ping, Ping>(),
+        Err(DeserializeError::WrongVariant(2, _))
+    ));
+    assert_eq!(
+        opened.decode::<Chat, Chat>().unwrap(),
+        Chat {
+            text: "hello".into()
+        }
+    );
+
+    let mut registry = Registry::<Box<dyn Handler>>::new();
+    registry
+        .register::<Ping, Ping>(|ping| Box::new(ping))
+        .register::<Chat, Chat>(|chat| Box::new(chat));
+
+    assert_eq!(registry.read_packet(&ping).unwrap().0.handle(), "ping 7");
+    assert_eq!(registry.read_packet(&chat).unwrap().0.handle(), "hello");
+
+    let mut registry = Registry::<String>::new();
+    registry.register::<Ping, Ping>(|ping| ping.handle());
+    assert!(matches!(
+        registry.read_packet(&chat),
+        Err(DeserializeError::WrongVariant(2, _))
+    ));
+}
+
+#[cfg(all(feature = "serde", feature = "derive"))]
+#[test]
+fn test_serde_bridge() {
+    use alkahest_proc::alkahest;
+    use alloc::{collections::BTreeMap, string::String, vec::Vec};
+
+    use crate::{
+        schema::Schema,
+        serde_bridge::{serde_to_value, Serde},
+    };
+
+    #[derive(serde::Serialize)]
+    struct Point(i32, i32);
+
+    #[derive(serde::Serialize)]
+    enum Shape {
+        Dot,
+        Circle { radius: u16, center: Point },
+        Path(Vec<Point>),
+    }
+
+    #[derive(serde::Serialize)]
+    struct Drawing {
+        tags: BTreeMap<String, u8>,
+        shapes: Vec<Shape>,
+        name: String,
+        title: Option<char>,
+    }
+
+    #[alkahest(Formula, Deserialize)]
+    #[derive(Debug, PartialEq)]
+    struct PointFormula {
+        x: i32,
+        y: i32,
+    }
+
+    #[alkahest(Formula, Deserialize)]
+    #[derive(Debug, PartialEq)]
+    enum ShapeFormula {
+        Dot,
+        Circle { center: PointFormula, radius: u16 },
+        Path(Vec<PointFormula>),
+    }
+
+    #[alkahest(Formula, Deserialize)]
+    #[derive(Debug, PartialEq)]
+    struct DrawingFormula {
+        name: String,
+        title: Option<String>,
+        shapes: Vec<ShapeFormula>,
+        tags: Vec<(String, u32)>,
+    }
+
+    let mut tags = BTreeMap::new();
+    tags.insert(String::from("a"), 1);
+    tags.insert(String::from("b"), 2);
+
+    let drawing = Drawing {
+        tags,
+        shapes: vec![
+            Shape::Dot,
+            Shape::Circle {
+                radius: 5,
+                center: Point(1, -1),
+            },
+            Shape::Path(vec![Point(0, 0), Point(2, 3)]),
+        ],
+        name: String::from("sketch"),
+        title: Some('x'),
+    };
+
+    let mut buffer = [0u8; 1024];
+    let (size, root) = serialize::<Serde<DrawingFormula>, _>(&drawing, &mut buffer).unwrap();
+    let decoded =
+        deserialize_with_size::<DrawingFormula, DrawingFormula>(&buffer[..size], root).unwrap();
+
+    assert_eq!(
+        decoded,
+        DrawingFormula {
+            name: String::from("sketch"),
+            title: Some(String::from("x")),
+            shapes: vec![
+                ShapeFormula::Dot,
+                ShapeFormula::Circle {
+                    center: PointFormula { x: 1, y: -1 },
+                    radius: 5,
+                },
+                ShapeFormula::Path(vec![
+                    PointFormula { x: 0, y: 0 },
+                    PointFormula { x: 2, y: 3 },
+                ]),
+            ],
+            tags: vec![(String::from("a"), 1), (String::from("b"), 2)],
+        }
+    );
+
+    // Same bytes as serializing with the formula directly.
+    let mut direct = [0u8; 1024];
+    let (direct_size, _) = serialize::<(u8, Vec<u16>), _>((1u8, [2u16, 3]), &mut direct).unwrap();
+    let (size, _) = serialize::<Serde<(u8, Vec<u16>)>, _>((1u8, [2u16, 3]), &mut buffer).unwrap();
+    assert_eq!(buffer[..size], direct[..direct_size]);
+
+    let schema = Schema::of::<(u8, Vec<u16>)>();
+    assert!(serde_to_value(&schema, &(256u16, [1u8])).is_err());
+    assert!(serde_to_value(&schema, &(1u8, [-1i8])).is_err());
+    assert!(serde_to_value(&schema, &(1u8, "text")).is_err());
+}
+
+#[cfg(all(feature = "serde", feature = "derive"))]
+#[test]
+fn test_serde_deserializer() {
+    use alkahest_proc::alkahest;
+    use alloc::{collections::BTreeMap, string::String, vec::Vec};
+
+    use crate::{deserialize::DeserializeError, serde_bridge::Serde};
+
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct Point(i32, i32);
+
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    enum Shape {
+        Dot,
+        Circle { radius: u16, center: Point },
+        Path(Vec<Point>),
+    }
+
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct Drawing<'a> {
+        tags: BTreeMap<String, u8>,
+        shapes: Vec<Shape>,
+        name: &'a str,
+        title: Option<char>,
+        extra: Option<u8>,
+    }
+
+    #[alkahest(Formula)]
+    struct PointFormula {
+        x: i32,
+        y: i32,
+    }
+
+    #[alkahest(Formula)]
+    enum ShapeFormula {
+        Dot,
+        Circle { center: PointFormula, radius: u16 },
+        Path(Vec<PointFormula>),
+    }
+
+    #[alkahest(Formula)]
+    struct DrawingFormula {
+        name: String,
+        title: Option<String>,
+        shapes: Vec<ShapeFormula>,
+        tags: Vec<(String, u32)>,
+        extra: u8,
+    }
+
+    let mut tags = BTreeMap::new();
+    tags.insert(String::from("a"), 1);
+    tags.insert(String::from("b"), 2);
+
+    let drawing = Drawing {
+        tags,
+        shapes: vec![
+            Shape::Dot,
+            Shape::Circle {
+                radius: 5,
+                center: Point(1, -1),
+            },
+            Shape::Path(vec![Point(0, 0), Point(2, 3)]),
+        ],
+        name: "sketch",
+        title: Some('x'),
+        extra: Some(7),
+    };
+
+    let mut buffer = [0u8; 1024];
+    let (size, root) = serialize::<Serde<DrawingFormula>, _>(&drawing, &mut buffer).unwrap();
+    let decoded =
+        deserialize_with_size::<Serde<DrawingFormula>, Drawing>(&buffer[..size], root).unwrap();
+    assert_eq!(decoded, drawing);
+
+    // Plain alkahest data is readable with serde types.
+    let (size, root) = serialize::<(u8, Vec<u16>), _>((1u8, [2u16, 3]), &mut buffer).unwrap();
+    let decoded =
+        deserialize_with_size::<Serde<(u8, Vec<u16>)>, (u8, Vec<u16>)>(&buffer[..size], root)
+            .unwrap();
+    assert_eq!(decoded, (1, vec![2, 3]));
+
+    assert!(matches!(
+        deserialize_with_size::<Serde<(u8, Vec<u16>)>, (u8, Vec<bool>)>(&buffer[..size], root),
+        Err(DeserializeError::Incompatible)
+    ));
+    assert!(matches!(
+        deserialize_with_size::<Serde<(u8, Vec<u16>)>, (u8, Vec<u16>)>(&buffer[..size - 1], root),
+        Err(DeserializeError::OutOfBounds(_) | DeserializeError::WrongAddress)
+    ));
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_read_packet_canonical() {
+    use alloc::{string::String, vec::Vec};
+
+    use crate::packet::{read_packet, read_packet_canonical, write_packet, write_packet_to_vec};
+
+    type F = (bool, Vec<Option<u32>>, Vec<String>);
+    let value = (
+        true,
+        vec![None, Some(1), None],
+        vec![String::from("a"), String::from("bc")],
+    );
+
+    let mut buffer = Vec::new();
+    let size = write_packet_to_vec::<F, _>(&value, &mut buffer);
+
+    // Padding is zeroed regardless of previous buffer content.
+    let mut dirty = [0xaau8; 128];
+    let dirty_size = write_packet::<F, _>(&value, &mut dirty).unwrap();
+    assert_eq!(buffer[..size], dirty[..dirty_size]);
+
+    let (read, consumed) = read_packet_canonical::<F, F>(&buffer[..size]).unwrap();
+    assert_eq!(read, value);
+    assert_eq!(consumed, size);
+
+    let mut rejected = 0;
+    for at in 0..size {
+        let mut tampered = buffer.clone();
+        tampered[at] ^= 0x02;
+        let relaxed = read_packet::<F, F>(&tampered[..size]);
+        match read_packet_canonical::<F, F>(&tampered[..size]) {
+            Ok((read, _)) => assert_ne!(read, value),
+            Err(crate::DeserializeError::NonCanonical) => {
+                assert!(relaxed.is_ok());
+                rejected += 1;
+            }
+            Err(_) => assert!(relaxed.is_err()),
+        }
+    }
+
+    // At least `bool` flag, `Option` tags and padding of `None`s.
+    assert!(rejected >= 1 + 3 + 2 * 4);
+}
+
+#[cfg(all(feature = "alloc", feature = "derive"))]
+#[test]
+fn test_validate_packet() {
+    use alkahest_proc::alkahest;
+    use alloc::{string::String, vec::Vec};
+
+    use crate::{
+        packet::{read_packet, write_packet_to_vec},
+        validate::{read_packet_validated, validate_packet},
+    };
+
+    #[derive(Clone, Debug, PartialEq)]
+    #[alkahest(Formula, Serialize, Deserialize)]
+    enum Node {
+        Leaf(String),
+        Branch {
+            children: Vec<Node>,
+            tag: Option<Vec<u8>>,
+        },
+    }
+
+    let value = Node::Branch {
+        children: vec![
+            Node::Leaf("a".into()),
+            Node::Branch {
+                children: vec![Node::Leaf("bc".into()), Node::Leaf(String::new())],
+                tag: None,
+            },
+        ],
+        tag: Some(vec![1, 2, 3]),
+    };
+
+    let mut buffer = Vec::new();
+    let size = write_packet_to_vec::<Node, _>(value.clone(), &mut buffer);
+    assert_eq!(validate_packet::<Node>(&buffer[..size]).unwrap(), size);
+    let (read, _) = read_packet_validated::<Node, Node>(&buffer[..size]).unwrap();
+    assert_eq!(read, value);
+
+    let mut buffer = Vec::new();
+    let size = write_packet_to_vec::<[Ref<str>], _>(["abc", "def"], &mut buffer);
+    assert!(validate_packet::<[Ref<str>]>(&buffer[..size]).is_ok());
+
+    // Second reference aliases the first.
+    const REF: usize = crate::formula::reference_size::<str>();
+    let mut aliased = buffer.clone();
+    aliased.copy_within(size - REF..size, size - 2 * REF);
+    let (read, _) = read_packet::<[Ref<str>], Vec<&str>>(&aliased[..size]).unwrap();
+    assert_eq!(read, ["abc", "abc"]);
+    assert!(matches!(
+        validate_packet::<[Ref<str>]>(&aliased[..size]),
+        Err(crate::DeserializeError::OverlappingReference)
+    ));
+}
+
+#[cfg(all(feature = "alloc", feature = "derive"))]
+#[test]
+fn test_malformed_input() {
+    use alkahest_proc::alkahest;
+    use alloc::string::String;
+    use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+    use crate::{
+        indexed::{Indexed, IndexedIter},
+        packet::{read_packet, write_packet_to_vec},
+        reference::InlineRef,
+        schema::Schema,
+        validate::validate_packet,
+        value::read_packet_value,
+    };
+
+    #[derive(Clone, Debug, PartialEq)]
+    #[alkahest(Formula, Serialize, Deserialize)]
+    enum Node {
+        Leaf(String),
+        Branch {
+            children: Vec<Node>,
+            tag: Option<Vec<u8>>,
+        },
+        Num(u64, [u16; 3]),
+    }
+
+    fn decode(data: &[u8], stack: usize) {
+        macro_rules! decode {
+            ($($f:ty => $t:ty),* $(,)?) => {$(
+                let _ = read_packet::<$f, $t>(data);
+                let _ = deserialize_with_size::<$f, $t>(data, stack);
+                if <$f as Formula>::HEAPLESS || <$f as Formula>::MAX_STACK_SIZE.is_some() {
+                    let _ = deserialize::<$f, $t>(data);
+                }
+                let _ = validate_packet::<$f>(data);
+                let _ = read_packet_value(&Schema::of::<$f>(), data);
+            )*};
+        }
+
+        decode! {
+            Node => Node,
+            Vec<Node> => Vec<Node>,
+            [Bytes] => Vec<&[u8]>,
+            [Vlq] => Vec<u64>,
+            Vlq => u128,
+            [u32] => VecDeque<u32>,
+            [u16; 3] => [u16; 3],
+            [String; 2] => [String; 2],
+            Vec<String> => Vec<String>,
+            Ref<str> => &str,
+            InlineRef<str> => &str,
+            Option<Ref<str>> => Option<&str>,
+            (u8, Bytes, Vec<u32>) => (u8, &[u8], Vec<u32>),
+            Indexed<Bytes> => Vec<&[u8]>,
+            [As<[u32]>] => Vec<Vec<u32>>,
+            [[u32; 2]] => Vec<[u32; 2]>,
+        }
+
+        if let Ok((lazy, _)) = read_packet::<[u32], Lazy<[u32]>>(data) {
+            let _ = lazy.iter::<u32>().fold(0, |acc, _| acc + 1);
+            let _ = lazy.sized_iter::<u32>().rfold(0, |acc, _| acc + 1);
+            let _ = lazy.sized_iter::<u32>().nth_back(2);
+            for chunk in lazy.chunks(3).flatten() {
+                chunk.iter::<u32>().for_each(drop);
+            }
+        }
+        if let Ok((lazy, _)) = read_packet::<[String], Lazy<[String]>>(data) {
+            let _ = lazy.iter::<&str>().fold(0, |acc, _| acc + 1);
+            let _ = lazy.iter::<&str>().nth(2);
+        }
+        if let Ok((iter, _)) = read_packet::<Indexed<Bytes>, IndexedIter<Bytes, &[u8]>>(data) {
+            iter.rev().for_each(drop);
+        }
+    }
+
+    let node = Node::Branch {
+        children: vec![
+            Node::Leaf("ab".into()),
+            Node::Num(7, [1, 2, 3]),
+            Node::Branch {
+                children: vec![],
+                tag: Some(vec![1, 2]),
+            },
+        ],
+        tag: None,
+    };
+
+    let mut samples = Vec::new();
+    let mut push = |buffer: Vec<u8>, size: usize| samples.push(buffer[..size].to_vec());
+    let mut buffer = Vec::new();
+    let size = write_packet_to_vec::<Node, _>(node, &mut buffer);
+    push(buffer, size);
+    let mut buffer = Vec::new();
+    let size = write_packet_to_vec::<[Bytes], _>([&b"abc"[..], b"", b"zz"], &mut buffer);
+    push(buffer, size);
+    let mut buffer = Vec::new();
+    let size = write_packet_to_vec::<[u32], _>([1u32, 2, 3, 4], &mut buffer);
+    push(buffer, size);
+    let mut buffer = Vec::new();
+    let size = write_packet_to_vec::<[Vlq], _>([1u64, 300, 1 << 40], &mut buffer);
+    push(buffer, size);
+    let mut buffer = Vec::new();
+    let size = write_packet_to_vec::<Indexed<Bytes>, _>([&b"abc"[..], b"", b"zz"], &mut buffer);
+    push(buffer, size);
+    let mut buffer = Vec::new();
+    let size = write_packet_to_vec::<[As<[u32]>], _>([&[1u32, 2][..], &[3]], &mut buffer);
+    push(buffer, size);
+
+    // Headers of values that do not fit any integer type.
+    decode(&[0xc0, 0xff, 0xff], 3);
+
+    let mut rng = SmallRng::seed_from_u64(42);
+    for i in 0..20000 {
+        let mut data = if i % 5 == 0 {
+            let len = rng.gen_range(0..48);
+            (0..len).map(|_| rng.gen_range(0..8)).collect()
+        } else {
+            samples[i % samples.len()].clone()
+        };
+
+        for _ in 0..rng.gen_range(1..4) {
+            if data.is_empty() {
+                break;
+            }
+            let at = rng.gen_range(0..data.len());
+            match rng.gen_range(0..4) {
+                0 => data[at] ^= 1 << rng.gen_range(0..8),
+                1 => data[at] = rng.gen(),
+                2 => data[at] = [0, 1, 0x7f, 0x80, 0xff][rng.gen_range(0..5)],
+                _ => data.truncate(at.max(1)),
+            }
+        }
+
+        let stack = rng.gen_range(0..=data.len());
+        decode(&data, stack);
+    }
+}
+
+#[test]
+fn test_bit_patterns() {
+    let mut buffer = [0u8; 16];
+
+    let size = serialize::<char, _>('ы', &mut buffer).unwrap().0;
+    assert_eq!(size, 4);
+    assert_eq!(deserialize::<char, char>(&buffer[..size]).unwrap(), 'ы');
+    assert!(matches!(
+        deserialize::<char, char>(&0xD800u32.to_le_bytes()),
+        Err(crate::DeserializeError::InvalidChar(0xD800))
+    ));
+
+    let flag = deserialize::<bool, bool>(&[2]);
+    let tag = deserialize::<Option<u8>, Option<u8>>(&[7, 2]);
+    if cfg!(feature = "strict") {
+        assert!(matches!(flag, Err(crate::DeserializeError::InvalidBool(2))));
+        assert!(matches!(tag, Err(crate::DeserializeError::InvalidBool(2))));
+    } else {
+        assert!(flag.unwrap());
+        assert_eq!(tag.unwrap(), Some(7));
+    }
+    assert!(!deserialize::<bool, bool>(&[0]).unwrap());
+    assert!(deserialize::<bool, bool>(&[1]).unwrap());
+}
+
+#[test]
+fn test_size_limits() {
+    use crate::size::{FixedIsizeType, FixedUsizeType, PACKET_SIZE_LIMIT};
+
+    let mut buffer = [0u8; 16];
+    for value in [
+        -5isize,
+        0,
+        FixedIsizeType::MIN as isize,
+        FixedIsizeType::MAX as isize,
+    ] {
+        let size = serialize::<isize, _>(value, &mut buffer).unwrap().0;
+        assert_eq!(deserialize::<isize, isize>(&buffer[..size]).unwrap(), value);
+    }
+
+    let max = FixedUsizeType::MAX;
+    assert_eq!(
+        PACKET_SIZE_LIMIT as u128,
+        (max as u128).min(usize::MAX as u128)
+    );
+    let size = serialize::<usize, _>(PACKET_SIZE_LIMIT, &mut buffer)
+        .unwrap()
+        .0;
+    assert_eq!(
+        deserialize::<usize, usize>(&buffer[..size]).unwrap(),
+        PACKET_SIZE_LIMIT
+    );
+}
+
+/// Checks serialized packets against fixtures in `golden` directory,
+/// so that changes of the wire format are not accidental.
+/// Run with "std" feature and `ALKAHEST_BLESS` environment variable set
+/// to overwrite fixtures after intentional change,
+/// and bump `WIRE_FORMAT_VERSION`.
+#[cfg(all(
+    feature = "alloc",
+    feature = "derive",
+    feature = "fixed32",
+    not(feature = "fixed64")
+))]
+#[test]
+fn test_golden() {
+    use alkahest_proc::alkahest;
+    use alloc::string::String;
+
+    use crate::{
+        indexed::Indexed,
+        packet::{read_packet, write_packet_to_vec, WIRE_FORMAT_VERSION},
+        reference::InlineRef,
+    };
+
+    #[derive(Debug, PartialEq)]
+    #[alkahest(Formula, Serialize, Deserialize)]
+    struct GoldenStruct {
+        a: u8,
+        b: String,
+        c: Vec<u16>,
+    }
+
+    #[derive(Debug, PartialEq)]
+    #[alkahest(Formula, Serialize, Deserialize)]
+    enum GoldenEnum {
+        A,
+        B(u32),
+        C { s: String },
+    }
+
+    assert_eq!(WIRE_FORMAT_VERSION, 1);
+
+    let mut mismatches = Vec::new();
+
+    macro_rules! golden {
+        ($($name:literal: $f:ty => $t:ty = $value:expr;)*) => {$(
+            let mut buffer = Vec::new();
+            let size = write_packet_to_vec::<$f, _>($value, &mut buffer);
+            buffer.truncate(size);
+
+            let golden: &[u8] = include_bytes!(concat!("golden/", $name, ".bin"));
+            if buffer != golden {
+                #[cfg(feature = "std")]
+                if std::env::var_os("ALKAHEST_BLESS").is_some() {
+                    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/golden/", $name, ".bin");
+                    std::fs::write(path, &buffer).unwrap();
+                }
+                mismatches.push($name);
+            } else {
+                let (value, _) = read_packet::<$f, $t>(golden).unwrap();
+                assert_eq!(value, $value, "{}", $name);
+            }
+        )*};
+    }
+
+    golden! {
+        "unit": () => () = ();
+        "bool": bool => bool = true;
+        "char": char => char = 'ы';
+        "u8": u8 => u8 = 0xab_u8;
+        "u16": u16 => u16 = 0xabcd_u16;
+        "u32": u32 => u32 = 0x0123_4567_u32;
+        "u64": u64 => u64 = 0x0123_4567_89ab_cdef_u64;
+        "u128": u128 => u128 = u128::MAX - 1;
+        "i8": i8 => i8 = -5i8;
+        "i16": i16 => i16 = -1234i16;
+        "i32": i32 => i32 = -123_456i32;
+        "i64": i64 => i64 = i64::MIN + 1;
+        "i128": i128 => i128 = -1i128;
+        "f32": f32 => f32 = 1.5f32;
+        "f64": f64 => f64 = -2.25f64;
+        "usize": usize => usize = 1234usize;
+        "isize": isize => isize = -1234isize;
+        "vlq": Vlq => u64 = 300u64;
+        "bytes": Bytes => &[u8] = &b"bytes"[..];
+        "str": str => &str = "str";
+        "array": [u16; 3] => [u16; 3] = [1u16, 2, 3];
+        "slice": [u32] => Vec<u32> = [1u32, 2, 3];
+        "slice_bytes": [Bytes] => Vec<&[u8]> = [&b"a"[..], b"bc"];
+        "vec": Vec<u16> => Vec<u16> = vec![4u16, 5];
+        "string": String => String = String::from("string");
+        "vec_string": Vec<String> => Vec<String> = vec![String::from("a"), String::from("bc")];
+        "ref": Ref<str> => &str = "ref";
+        "inline_ref_short": InlineRef<str> => &str = "in";
+        "inline_ref_long": InlineRef<str> => &str = "long enough to be referenced";
+        "option_some": Option<u32> => Option<u32> = Some(7u32);
+        "option_none": Option<u32> => Option<u32> = None::<u32>;
+        "option_ref": Option<Ref<str>> => Option<&str> = Some("opt");
+        "tuple": (u8, Bytes, Vec<u32>) => (u8, &[u8], Vec<u32>) = (1u8, &b"t"[..], vec![2u32]);
+        "as_str": [As<str>] => Vec<&str> = ["a", "bc"];
+        "indexed": Indexed<Bytes> => Vec<&[u8]> = [&b"x"[..], b"yz"];
+        "struct": GoldenStruct => GoldenStruct = GoldenStruct { a: 1, b: String::from("b"), c: vec![2, 3] };
+        "enum_unit": GoldenEnum => GoldenEnum = GoldenEnum::A;
+        "enum_tuple": GoldenEnum => GoldenEnum = GoldenEnum::B(7);
+        "enum_struct": GoldenEnum => GoldenEnum = GoldenEnum::C { s: String::from("c") };
+    }
+
+    assert!(
+        mismatches.is_empty(),
+        "Wire format changed for {mismatches:?}"
+    );
+}
+
+#[cfg(all(feature = "testing", feature = "derive"))]
+#[test]
+fn test_testing_helpers() {
+    use alkahest_proc::alkahest;
+    use alloc::string::String;
+    use proptest::{collection::vec, prelude::any, proptest};
+
+    use crate::testing::{
+        assert_no_panic, assert_roundtrip, assert_size_hint_consistent, corrupted_packet,
+        fixed_isize, fixed_usize, packet, vlq,
+    };
+
+    #[derive(Clone, Debug, PartialEq)]
+    #[alkahest(Formula, Serialize, Deserialize)]
+    struct Record {
+        id: usize,
+        name: String,
+        values: Vec<u64>,
+    }
+
+    proptest!(|(id in fixed_usize(), offset in fixed_isize(), name in any::<String>(), values in vec(vlq(), 0..8))| {
+        assert_roundtrip::<usize, usize>(id);
+        assert_roundtrip::<isize, isize>(offset);
+        assert_roundtrip::<Vec<Vlq>, Vec<u64>>(values.clone());
+        assert_size_hint_consistent::<[Ref<str>], _>([name.as_str(), "tail"]);
+        assert_roundtrip::<Record, Record>(Record { id, name, values });
+    });
+
+    proptest!(|(bytes in packet::<[Vlq], _, _>(vec(vlq(), 0..8)))| {
+        assert!(assert_no_panic::<[Vlq], Vec<u64>>(&bytes));
+    });
+
+    proptest!(|(bytes in corrupted_packet::<Vec<String>, _, _>(vec(any::<String>(), 0..4)))| {
+        assert_no_panic::<Vec<String>, Vec<String>>(&bytes);
+    });
+}
+
+#[test]
+fn test_error_details() {
+    use crate::{
+        deserialize::{DeserializeError, ErrorDetails},
+        formula::reference_size,
+        packet::read_packet,
+        reference::InlineRef,
+    };
+
+    let err = deserialize::<(u8, [u32; 2]), (u8, [u32; 2])>(&[1, 2, 3]).unwrap_err();
+    let DeserializeError::WrongLength(details) = err else {
+        panic!("unexpected error {err:?}");
+    };
+
+    if cfg!(feature = "debug-errors") {
+        assert_eq!(details.formula(), Some(core::any::type_name::<[u32; 2]>()));
+        assert_eq!(details.expected(), Some(8));
+        assert_eq!(details.actual(), Some(2));
+    } else {
+        assert_eq!(details.formula(), None);
+        assert_eq!(details.expected(), None);
+        assert_eq!(details.actual(), None);
+        assert_eq!(core::mem::size_of::<ErrorDetails>(), 0);
+    }
+
+    let err = read_packet::<u32, u32>(&[0, 0]).unwrap_err();
+    assert!(err.details().is_some());
+    assert!(DeserializeError::WrongAddress.details().is_none());
+
+    // Inline length exceeds the reference size.
+    let mut buffer = [0u8; 64];
+    let (size, _) = serialize::<InlineRef<str>, _>("abc", &mut buffer).unwrap();
+    let tag = buffer[..size].iter().position(|&b| b == 4).unwrap();
+    buffer[tag] = 0xff;
+    let err = deserialize::<InlineRef<str>, &str>(&buffer[..size]).unwrap_err();
+    let DeserializeError::WrongLength(details) = err else {
+        panic!("unexpected error {err:?}");
+    };
+    if cfg!(feature = "debug-errors") {
+        assert_eq!(details.expected(), Some(reference_size::<str>()));
+        assert_eq!(details.actual(), Some(0xfe));
+    }
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_validate_utf8() {
+    use alloc::string::String;
+
+    use crate::{
+        deserialize::DeserializeError,
+        packet::write_packet_to_vec,
+        validate::{read_packet_validated, validate_packet},
+    };
+
+    let mut buffer = Vec::new();
+    let size = write_packet_to_vec::<(u8, Vec<String>), _>((1u8, ["ab", "cd"]), &mut buffer);
+    let packet = &mut buffer[..size];
+    assert_eq!(validate_packet::<(u8, Vec<String>)>(packet).unwrap(), size);
+
+    let at = packet.windows(2).position(|w| w == b"cd").unwrap();
+    packet[at] = 0xff;
+    assert!(matches!(
+        validate_packet::<(u8, Vec<String>)>(packet),
+        Err(DeserializeError::NonUtf8(_))
+    ));
+    assert!(read_packet_validated::<(u8, Vec<String>), (u8, Vec<String>)>(packet).is_err());
+}
+
+#[cfg(all(feature = "alloc", feature = "unchecked-utf8"))]
+#[allow(unsafe_code)]
+#[test]
+fn test_unchecked_utf8() {
+    use alloc::string::String;
+
+    use crate::packet::{read_packet_unchecked_utf8, write_packet_to_vec};
+
+    type Message = (Vec<String>, Option<Ref<str>>, [As<str>; 2]);
+
+    let mut buffer = Vec::new();
+    let size =
+        write_packet_to_vec::<Message, _>((["a", "bc"], Some("def"), ["gh", "ijk"]), &mut buffer);
+
+    // SAFETY: Packet is produced by serialization.
+    let (value, read) = unsafe {
+        read_packet_unchecked_utf8::<Message, (Vec<String>, Option<&str>, [&str; 2])>(
+            &buffer[..size],
+        )
+    }
+    .unwrap();
+
+    assert_eq!(read, size);
+    assert_eq!(value.0, ["a", "bc"]);
+    assert_eq!(value.1, Some("def"));
+    assert_eq!(value.2, ["gh", "ijk"]);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_owned_packet() {
+    use alloc::string::String;
+
+    use crate::{owned::Packet, reference::InlineRef};
+
+    let mut packet = Packet::<(u32, Vec<String>)>::new((7u32, ["a", "bc"]));
+    assert_eq!(
+        packet.get::<(u32, Vec<String>)>().unwrap(),
+        (7, vec![String::from("a"), String::from("bc")])
+    );
+
+    let mut place = (0u32, Vec::<String>::new());
+    packet.get_in_place(&mut place).unwrap();
+    assert_eq!(place.1, ["a", "bc"]);
+
+    packet.set((8u32, ["d"]));
+    assert_eq!(packet.get::<(u32, Vec<&str>)>().unwrap(), (8, vec!["d"]));
+
+    let mut bytes = packet.clone().into_bytes();
+    let size = bytes.len();
+    bytes.extend_from_slice(&[1, 2, 3]);
+    let copy = Packet::<(u32, Vec<String>)>::from_bytes(bytes.clone()).unwrap();
+    assert_eq!(copy.len(), size);
+    assert_eq!(copy, packet);
+    assert_eq!(
+        Packet::<(u32, Vec<String>)>::from_bytes_validated(bytes).unwrap(),
+        packet
+    );
+    assert!(Packet::<(u32, Vec<String>)>::from_bytes(vec![0xff; 3]).is_err());
+
+    let words = Packet::<[InlineRef<str>]>::new(["short", "longer than a reference"]);
+    let lazy = words.lazy().unwrap();
+    assert_eq!(lazy.len(), 2);
+    assert_eq!(
+        lazy.iter::<&str>().nth(1).unwrap().unwrap(),
+        "longer than a reference"
+    );
+
+    let strings = words.reserialize::<Vec<String>, Vec<String>>().unwrap();
+    assert_eq!(
+        strings.get::<Vec<&str>>().unwrap(),
+        ["short", "longer than a reference"]
+    );
+}
+
+#[cfg(all(feature = "alloc", feature = "derive"))]
+#[test]
+fn test_self_formula() {
+    use alkahest_proc::alkahest;
+    use alloc::string::String;
+
+    use crate::self_formula::{DeserializeSelf, SerializeSelf};
+
+    #[derive(Clone, Debug, PartialEq)]
+    #[alkahest(Formula, Serialize, Deserialize)]
+    struct Message {
+        id: u32,
+        text: String,
+        tags: Vec<String>,
+    }
+
+    let message = Message {
+        id: 1,
+        text: String::from("hello"),
+        tags: vec![String::from("a")],
+    };
+
+    let bytes = message.clone().alkahest_to_vec();
+    assert_eq!(bytes.len(), message.clone().alkahest_packet_size());
+    assert_eq!(Message::alkahest_from(&bytes).unwrap(), message);
+    assert_eq!(Message::alkahest_from_validated(&bytes).unwrap(), message);
+
+    let mut buffer = vec![0u8; bytes.len()];
+    let size = message.clone().alkahest_write(&mut buffer).unwrap();
+    assert_eq!(&buffer[..size], &bytes[..]);
+    assert!(message.clone().alkahest_write(&mut buffer[..4]).is_err());
+
+    let mut place = Message {
+        id: 0,
+        text: String::new(),
+        tags: Vec::new(),
+    };
+    assert_eq!(place.alkahest_from_in_place(&bytes).unwrap(), bytes.len());
+    assert_eq!(place, message);
+}
+
+#[cfg(all(feature = "alloc", feature = "derive"))]
+#[test]
+fn test_formula_macro() {
+    use alkahest_proc::alkahest;
+    use alloc::string::String;
+
+    use crate::packet::{read_packet, write_packet_to_vec};
+
+    #[alkahest(Formula)]
+    struct Named {
+        id: u64,
+        name: Ref<str>,
+        tags: [u32],
+    }
+
+    #[alkahest(Serialize<Named>)]
+    struct NamedValue {
+        id: u64,
+        name: &'static str,
+        tags: Vec<u32>,
+    }
+
+    type Inline = crate::formula! { { id: u64, name: Ref<str>, tags: [u32] } };
+    type Flat = crate::formula! { id: u64, inner: crate::formula!{ { a: u8, b: Bytes } } };
+
+    let mut inline = Vec::new();
+    let size = write_packet_to_vec::<Inline, _>((1u64, "one", [2u32, 3]), &mut inline);
+    inline.truncate(size);
+
+    let mut named = Vec::new();
+    let size = write_packet_to_vec::<Named, _>(
+        NamedValue {
+            id: 1,
+            name: "one",
+            tags: vec![2, 3],
+        },
+        &mut named,
+    );
+    named.truncate(size);
+    assert_eq!(inline, named);
+
+    let (value, _) = read_packet::<Inline, (u64, String, Vec<u32>)>(&inline).unwrap();
+    assert_eq!(value, (1, String::from("one"), vec![2, 3]));
+
+    let mut flat = Vec::new();
+    let size = write_packet_to_vec::<Flat, _>((5u64, (6u8, &b"seven"[..])), &mut flat);
+    let (value, _) = read_packet::<Flat, (u64, (u8, &[u8]))>(&flat[..size]).unwrap();
+    assert_eq!(value, (5, (6, &b"seven"[..])));
+
+    let _: crate::formula! { {} } = ();
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_ser_iter_map() {
+    use alloc::{collections::BTreeMap, string::String};
+
+    use crate::{
+        iter::SerIterMap,
+        packet::{read_packet, write_packet_to_vec},
+        serialize::Serialize,
+    };
+
+    let map = BTreeMap::from([
+        (String::from("one"), 1u32),
+        (String::from("two"), 2),
+        (String::from("three"), 3),
+    ]);
+
+    let hint = <SerIterMap<_> as Serialize<[(String, Vlq)]>>::size_hint(&SerIterMap(map.iter()));
+    assert!(hint.is_some());
+
+    let mut buffer = Vec::new();
+    let size = write_packet_to_vec::<[(String, Vlq)], _>(SerIterMap(map.iter()), &mut buffer);
+    let (pairs, _) = read_packet::<[(String, Vlq)], Vec<(String, u32)>>(&buffer[..size]).unwrap();
+    assert_eq!(pairs, map.clone().into_iter().collect::<Vec<_>>());
+
+    let size = write_packet_to_vec::<Vec<(Ref<str>, u32)>, _>(
+        SerIterMap(map.iter().map(|(k, v)| (k.as_str(), *v))),
+        &mut buffer,
+    );
+    let (pairs, _) =
+        read_packet::<Vec<(Ref<str>, u32)>, Vec<(&str, u32)>>(&buffer[..size]).unwrap();
+    assert_eq!(pairs, [("one", 1), ("three", 3), ("two", 2)]);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_serialize_mut_and_pointers() {
+    use alloc::{boxed::Box, rc::Rc, string::String, sync::Arc};
+
+    use crate::packet::{read_packet, write_packet_to_vec};
+
+    fn write_mut<F, T: ?Sized>(value: &mut T) -> Vec<u8>
+    where
+        F: Formula + ?Sized,
+        for<'a> &'a mut T: Serialize<F>,
+    {
+        let mut buffer = Vec::new();
+        let size = write_packet_to_vec::<F, _>(value, &mut buffer);
+        buffer.truncate(size);
+        buffer
+    }
+
+    let mut vec = vec![1u32, 2, 3];
+    let bytes = write_mut::<[u32], _>(&mut vec);
+    assert_eq!(read_packet::<[u32], Vec<u32>>(&bytes).unwrap().0, [1, 2, 3]);
+
+    let bytes = write_mut::<[u32], _>(&mut vec[..2]);
+    assert_eq!(read_packet::<[u32], Vec<u32>>(&bytes).unwrap().0, [1, 2]);
+
+    let mut pair = (1u8, String::from("two"));
+    let bytes = write_mut::<(u8, String), _>(&mut pair);
+    assert_eq!(
+        read_packet::<(u8, String), (u8, String)>(&bytes).unwrap().0,
+        pair
+    );
+
+    let mut buffer = Vec::new();
+    let size = write_packet_to_vec::<Vec<u32>, _>(&mut &mut vec, &mut buffer);
+    assert_eq!(
+        read_packet::<Vec<u32>, Vec<u32>>(&buffer[..size])
+            .unwrap()
+            .0,
+        [1, 2, 3]
+    );
+
+    let boxed: Box<str> = Box::from("boxed");
+    let size = write_packet_to_vec::<str, _>(&boxed, &mut buffer);
+    assert_eq!(
+        read_packet::<str, &str>(&buffer[..size]).unwrap().0,
+        "boxed"
+    );
+
+    let rc: Rc<[u8]> = Rc::from(&b"rc"[..]);
+    let size = write_packet_to_vec::<Bytes, _>(&rc, &mut buffer);
+    assert_eq!(
+        read_packet::<Bytes, &[u8]>(&buffer[..size]).unwrap().0,
+        b"rc"
+    );
+
+    let arc = Arc::new((1u16, 2u32));
+    let size = write_packet_to_vec::<(u16, u32), _>(&arc, &mut buffer);
+    assert_eq!(
+        read_packet::<(u16, u32), (u16, u32)>(&buffer[..size])
+            .unwrap()
+            .0,
+        (1, 2)
+    );
+
+    let size = write_packet_to_vec::<[Ref<str>], _>([&boxed, &boxed], &mut buffer);
+    assert_eq!(
+        read_packet::<[Ref<str>], Vec<&str>>(&buffer[..size])
+            .unwrap()
+            .0,
+        ["boxed", "boxed"]
+    );
+}
+
+#[test]
+fn test_define_as() {
+    use core::{
+        net::{Ipv4Addr, Ipv6Addr},
+        time::Duration,
+    };
+
+    use crate::{
+        convert::{DurationMillis, DurationNanos},
+        deserialize::{DeserializeError, ErrorDetails},
+    };
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    enum Level {
+        Low,
+        High,
+    }
+
+    crate::define_as! {
+        struct LevelRepr(Level as u16) {
+            into = |level| match level {
+                Level::Low => 10,
+                Level::High => 20,
+            };
+            from = |repr| match repr {
+                10 => Ok(Level::Low),
+                20 => Ok(Level::High),
+                _ => Err(DeserializeError::WrongVariant(repr.into(), ErrorDetails::new())),
+            };
+        }
+    }
+
+    assert_eq!(LevelRepr::MAX_STACK_SIZE, Some(2));
+    assert_eq!(
+        crate::fingerprint::<LevelRepr>(),
+        crate::fingerprint::<u16>()
+    );
+
+    let mut buffer = [0u8; 64];
+    let (size, root) =
+        serialize::<(LevelRepr, As<LevelRepr>), _>((Level::High, &Level::Low), &mut buffer)
+            .unwrap();
+    assert_eq!(
+        deserialize_with_size::<(u16, u16), (u16, u16)>(&buffer[..size], root).unwrap(),
+        (20, 10)
+    );
+    let value =
+        deserialize_with_size::<(LevelRepr, LevelRepr), (Level, Level)>(&buffer[..size], root)
+            .unwrap();
+    assert_eq!(value, (Level::High, Level::Low));
+    assert!(matches!(
+        deserialize::<LevelRepr, Level>(&[11, 0]),
+        Err(DeserializeError::WrongVariant(11, _))
+    ));
+
+    let duration = Duration::new(3, 456_789_012);
+    let (size, _) = serialize::<DurationNanos, _>(duration, &mut buffer).unwrap();
+    assert_eq!(size, 16);
+    assert_eq!(
+        deserialize::<u128, u128>(&buffer[..size]).unwrap(),
+        3_456_789_012
+    );
+    assert_eq!(
+        deserialize::<DurationNanos, Duration>(&buffer[..size]).unwrap(),
+        duration
+    );
+    serialize::<u128, _>(u128::MAX, &mut buffer).unwrap();
+    assert!(matches!(
+        deserialize::<DurationNanos, Duration>(&buffer[..16]),
+        Err(DeserializeError::IntegerOverflow)
+    ));
+
+    let (size, _) = serialize::<DurationMillis, _>(&duration, &mut buffer).unwrap();
+    assert_eq!(
+        deserialize::<DurationMillis, Duration>(&buffer[..size]).unwrap(),
+        Duration::from_millis(3456)
+    );
+
+    let ipv4 = Ipv4Addr::new(192, 168, 0, 1);
+    let (size, _) = serialize::<u32, _>(ipv4, &mut buffer).unwrap();
+    assert_eq!(&buffer[..size], [1, 0, 168, 192]);
+    assert_eq!(deserialize::<u32, Ipv4Addr>(&buffer[..size]).unwrap(), ipv4);
+
+    let ipv6 = Ipv6Addr::LOCALHOST;
+    let (size, _) = serialize::<u128, _>(&ipv6, &mut buffer).unwrap();
+    assert_eq!(
+        deserialize::<u128, Ipv6Addr>(&buffer[..size]).unwrap(),
+        ipv6
+    );
+}
+
+#[cfg(all(feature = "alloc", feature = "derive"))]
+#[test]
+fn test_struct_writer() {
+    use alkahest_proc::alkahest;
+    use alloc::string::String;
+
+    use crate::{
+        buffer::Buffer,
+        packet::{read_packet, write_packet_to_vec},
+        serialize::{field_size_hint, struct_writer, variant_writer, Sizes},
+    };
+
+    #[alkahest(Formula, Serialize, Deserialize)]
+    #[derive(Clone, Debug, PartialEq)]
+    enum Shape {
+        Empty,
+        Circle { radius: u32 },
+        Named { id: u16, label: String },
+    }
+
+    struct Manual<'a>(&'a Shape);
+
+    impl Serialize<Shape> for Manual<'_> {
+        fn serialize<B>(self, sizes: &mut Sizes, mut buffer: B) -> Result<(), B::Error>
+        where
+            B: Buffer,
+        {
+            match self.0 {
+                Shape::Empty => variant_writer(0, sizes, &mut buffer).map(drop),
+                Shape::Circle { radius } => {
+                    variant_writer(1, sizes, &mut buffer)?.last_field::<u32, _>(*radius)
+                }
+                Shape::Named { id, label } => variant_writer(2, sizes, &mut buffer)?
+                    .field::<u16, _>(*id)?
+                    .last_field::<String, _>(label.as_str()),
+            }
+        }
+
+        fn size_hint(&self) -> Option<Sizes> {
+            None
+        }
+    }
+
+    struct Pair(u8, &'static str);
+
+    impl Serialize<(u8, str)> for Pair {
+        fn serialize<B>(self, sizes: &mut Sizes, mut buffer: B) -> Result<(), B::Error>
+        where
+            B: Buffer,
+        {
+            struct_writer(sizes, &mut buffer)
+                .field::<u8, _>(self.0)?
+                .last_field::<str, _>(self.1)
+        }
+
+        fn size_hint(&self) -> Option<Sizes> {
+            Some(field_size_hint::<u8>(&self.0, false)? + field_size_hint::<str>(&self.1, true)?)
+        }
+    }
+
+    for shape in [
+        Shape::Empty,
+        Shape::Circle { radius: 5 },
+        Shape::Named {
+            id: 3,
+            label: String::from("three"),
+        },
+    ] {
+        let mut derived = Vec::new();
+        let size = write_packet_to_vec::<Shape, _>(shape.clone(), &mut derived);
+        derived.truncate(size);
+
+        let mut manual = Vec::new();
+        let size = write_packet_to_vec::<Shape, _>(Manual(&shape), &mut manual);
+        manual.truncate(size);
+
+        assert_eq!(manual, derived);
+        assert_eq!(read_packet::<Shape, Shape>(&manual).unwrap().0, shape);
+    }
+
+    let mut buffer = Vec::new();
+    let size = write_packet_to_vec::<(u8, str), _>(Pair(1, "one"), &mut buffer);
+    let (value, _) = read_packet::<(u8, str), (u8, &str)>(&buffer[..size]).unwrap();
+    assert_eq!(value, (1, "one"));
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_dyn_buffer() {
+    use core::convert::Infallible;
+
+    use crate::{
+        buffer::{BufferExhausted, CheckedFixedBuffer, DynBuffer, VecBuffer},
+        packet::{read_packet, write_packet_into},
+    };
+
+    fn write_dyn<E>(buffer: &mut dyn DynBuffer<Error = E>) -> Result<usize, E> {
+        write_packet_into::<(u32, [As<str>]), _, _>((7u32, ["a", "bc"]), buffer)
+    }
+
+    let mut output = Vec::new();
+    let size = write_dyn::<Infallible>(&mut VecBuffer::new(&mut output)).unwrap();
+    let (value, _) = read_packet::<(u32, [As<str>]), (u32, Vec<&str>)>(&output[..size]).unwrap();
+    assert_eq!(value, (7, vec!["a", "bc"]));
+
+    let mut fixed = vec![0u8; size];
+    let fixed_size = write_dyn(&mut CheckedFixedBuffer::new(&mut fixed)).unwrap();
+    assert_eq!(fixed[..fixed_size], output[..size]);
+
+    let mut small = vec![0u8; size - 1];
+    assert_eq!(
+        write_dyn(&mut CheckedFixedBuffer::new(&mut small)),
+        Err(BufferExhausted)
+    );
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_frames() {
+    use std::io::{self, Read};
+
+    use crate::{
+        deserialize::DeserializeError,
+        frame::{FrameError, FrameReader, FrameWriter},
+    };
+
+    /// Returns at most one byte per read and `WouldBlock` every other call.
+    struct Trickle<'a> {
+        bytes: &'a [u8],
+        block: bool,
+    }
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.block = !self.block;
+            if self.block {
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+            let Some((first, rest)) = self.bytes.split_first() else {
+                return Ok(0);
+            };
+            buf[0] = *first;
+            self.bytes = rest;
+            Ok(1)
+        }
+    }
+
+    let mut writer = FrameWriter::new(Vec::new());
+    writer.write::<[u32], _>([1u32, 2, 3]).unwrap();
+    writer.write::<(), _>(()).unwrap();
+    writer.write::<As<str>, _>("four").unwrap();
+    writer.flush().unwrap();
+    let stream = writer.into_inner();
+
+    let mut reader = FrameReader::new(Trickle {
+        bytes: &stream,
+        block: false,
+    });
+    let mut values = Vec::new();
+    loop {
+        match reader.read::<[u32], Vec<u32>>() {
+            Ok(Some(value)) => {
+                values.push(value);
+                break;
+            }
+            Ok(None) => panic!("unexpected end of frames"),
+            Err(FrameError::Io(err)) if err.kind() == io::ErrorKind::WouldBlock => {}
+            Err(err) => panic!("unexpected error: {err}"),
+        }
+    }
+    assert_eq!(values, [vec![1, 2, 3]]);
+
+    let mut reader = FrameReader::new(&stream[..]);
+    assert_eq!(
+        reader.read::<[u32], Vec<u32>>().unwrap(),
+        Some(vec![1, 2, 3])
+    );
+    assert_eq!(reader.read::<(), ()>().unwrap(), Some(()));
+    assert_eq!(reader.read::<As<str>, &str>().unwrap(), Some("four"));
+    assert!(reader.read_frame().unwrap().is_none());
+
+    let mut packet = Vec::new();
+    let size = crate::packet::write_packet_to_vec::<u32, _>(5u32, &mut packet);
+    packet.truncate(size);
+    packet.push(0xFF);
+    let mut trailing = (packet.len() as u32).to_be_bytes().to_vec();
+    trailing.extend_from_slice(&packet);
+    let mut reader = FrameReader::new(&trailing[..]);
+    assert!(matches!(
+        reader.read::<u32, u32>(),
+        Err(FrameError::Deserialize(DeserializeError::WrongLength(_)))
+    ));
+
+    let mut reader = FrameReader::new(&stream[..stream.len() - 1]);
+    reader.read_frame().unwrap();
+    reader.read_frame().unwrap();
+    assert!(matches!(
+        reader.read_frame(),
+        Err(FrameError::UnexpectedEof)
+    ));
+
+    let mut reader = FrameReader::with_max_frame_size(&stream[..], 4);
+    assert!(matches!(
+        reader.read_frame(),
+        Err(FrameError::FrameTooLarge(_))
+    ));
+
+    let mut writer = FrameWriter::with_max_frame_size(Vec::new(), 4);
+    assert!(matches!(
+        writer.write::<[u32], _>([1u32, 2]),
+        Err(FrameError::FrameTooLarge(_))
+    ));
+    assert!(writer.get_ref().is_empty());
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_format_header() {
+    use crate::{
+        fingerprint::fingerprint,
+        magic::{
+            read_packet_with_header, write_packet_with_header, write_packet_with_header_to_vec,
+            FormatHeader, HeaderError,
+        },
+        packet::WIRE_FORMAT_VERSION,
+    };
+
+    const MAGIC: [u8; 4] = *b"TEST";
+
+    let mut buffer = Vec::new();
+    let size =
+        write_packet_with_header_to_vec::<(u8, As<str>), _>(MAGIC, (1u8, "one"), &mut buffer);
+    assert_eq!(size, buffer.len());
+
+    let mut fixed = vec![0u8; size];
+    assert_eq!(
+        write_packet_with_header::<(u8, As<str>), _>(MAGIC, (1u8, "one"), &mut fixed).unwrap(),
+        size
+    );
+    assert_eq!(fixed, buffer);
+    assert!(
+        write_packet_with_header::<(u8, As<str>), _>(MAGIC, (1u8, "one"), &mut fixed[..8]).is_err()
+    );
+
+    let header = FormatHeader::from_bytes(&buffer).unwrap();
+    assert_eq!(header.magic, MAGIC);
+    assert_eq!(header.version, WIRE_FORMAT_VERSION);
+    assert_eq!(header.fingerprint, fingerprint::<(u8, As<str>)>());
+    assert_eq!(
+        FormatHeader::from_bytes(&header.to_bytes()).unwrap(),
+        header
+    );
+
+    let (value, consumed) =
+        read_packet_with_header::<(u8, As<str>), (u8, &str)>(MAGIC, &buffer).unwrap();
+    assert_eq!((value, consumed), ((1, "one"), size));
+
+    assert!(matches!(
+        read_packet_with_header::<(u8, As<str>), (u8, &str)>(MAGIC, &buffer[..10]),
+        Err(HeaderError::Truncated(10))
+    ));
+    assert!(matches!(
+        read_packet_with_header::<(u8, As<str>), (u8, &str)>(*b"ELSE", &buffer),
+        Err(HeaderError::MagicMismatch { expected, found }) if expected == *b"ELSE" && found == MAGIC
+    ));
+    assert!(matches!(
+        read_packet_with_header::<(u16, As<str>), (u16, &str)>(MAGIC, &buffer),
+        Err(HeaderError::FingerprintMismatch { .. })
+    ));
+    assert!(matches!(
+        read_packet_with_header::<(u8, As<str>), (u8, &str)>(MAGIC, &buffer[..size - 1]),
+        Err(HeaderError::Deserialize(_))
+    ));
+
+    let mut future = buffer.clone();
+    future[4..8].copy_from_slice(&(WIRE_FORMAT_VERSION + 1).to_le_bytes());
+    let err = read_packet_with_header::<(u8, As<str>), (u8, &str)>(MAGIC, &future).unwrap_err();
+    assert!(matches!(
+        err,
+        HeaderError::VersionMismatch { expected, found }
+            if expected == WIRE_FORMAT_VERSION && found == WIRE_FORMAT_VERSION + 1
+    ));
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_rpc() {
+    use alloc::{string::String, vec::Vec};
+
+    use crate::rpc::{write_request, Client, Router, RpcError};
+
+    crate::rpc_methods! {
+        Sum = 1: [u32] => u64;
+        Greet = 2: As<str> => As<str>;
+        Missing = 3: () => ();
+    }
+
+    let mut router = Router::<Vec<u32>>::new();
+    router
+        .handle::<Sum, _, _>(|log, values: Vec<u32>| {
+            log.extend_from_slice(&values);
+            values.iter().map(|&v| u64::from(v)).sum::<u64>()
+        })
+        .handle::<Greet, _, _>(|_, name: String| alloc::format!("Hello, {name}!"));
+    assert!(router.contains(1) && router.contains(2) && !router.contains(3));
+
+    let mut log = Vec::new();
+    let mut client = Client::new(|request: &[u8], response: &mut Vec<u8>| {
+        router.dispatch(&mut log, request, response)
+    });
+
+    assert_eq!(client.call::<Sum, _, u64>([1u32, 2, 3]).unwrap(), 6);
+    assert_eq!(
+        client.call::<Greet, _, &str>("alkahest").unwrap(),
+        "Hello, alkahest!"
+    );
+    assert!(matches!(
+        client.call::<Missing, _, ()>(()),
+        Err(RpcError::UnknownMethod(3))
+    ));
+
+    // Request with truncated packet.
+    let mut request = Vec::new();
+    let mut response = Vec::new();
+    write_request::<Sum, _>(7, [1u32, 2, 3], &mut request);
+    request.truncate(request.len() - 1);
+    router
+        .dispatch(&mut Vec::new(), &request, &mut response)
+        .unwrap();
+    assert!(matches!(
+        crate::rpc::read_response::<Sum, u64, ()>(7, &response),
+        Err(RpcError::BadRequest)
+    ));
+    assert!(matches!(
+        crate::rpc::read_response::<Sum, u64, ()>(8, &response),
+        Err(RpcError::CallMismatch {
+            expected: 8,
+            found: 7
+        })
+    ));
+    assert!(router
+        .dispatch(&mut Vec::new(), &[1, 2], &mut response)
+        .is_err());
+
+    drop(client);
+    assert_eq!(log, [1, 2, 3]);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_fragments() {
+    use alloc::{string::String, vec::Vec};
+
+    use crate::fragment::{fragment_packet, FragmentError, Reassembler};
+
+    let value: Vec<String> = (0..50).map(|i| alloc::format!("item-{i}")).collect();
+    let fragments = fragment_packet::<[As<str>], _>(1, &value, 32).unwrap();
+    let total = fragments.total();
+    assert!(total > 2);
+    assert_eq!(fragments.len(), usize::from(total));
+
+    let datagrams: Vec<Vec<u8>> = fragments.clone().collect();
+    assert!(datagrams.iter().all(|d| d.len() <= 32));
+    assert_eq!(fragments.fragment(1).as_ref(), Some(&datagrams[1]));
+    assert_eq!(fragments.fragment(total), None);
+
+    // Interleave with another message, shuffle and duplicate.
+    let other = fragment_packet::<u64, _>(2, 42u64, 12).unwrap();
+    let mut reassembler = Reassembler::new();
+    for datagram in other.clone().skip(1) {
+        assert_eq!(reassembler.push(&datagram).unwrap(), None);
+    }
+    assert_eq!(reassembler.missing(2), Some(alloc::vec![0]));
+
+    let mut result = None;
+    for (i, datagram) in datagrams.iter().enumerate().rev() {
+        let complete = reassembler.push(datagram).unwrap();
+        if i > 0 {
+            assert_eq!(complete, None);
+            assert_eq!(reassembler.push(datagram).unwrap(), None);
+        } else {
+            result = complete;
+        }
+    }
+    let (message, packet) = result.unwrap();
+    assert_eq!(message, 1);
+    assert!(!reassembler.is_pending(1));
+    let (decoded, _) = crate::read_packet::<[As<str>], Vec<&str>>(&packet).unwrap();
+    assert_eq!(decoded, value);
+
+    let (message, packet) = reassembler
+        .push(&other.fragment(0).unwrap())
+        .unwrap()
+        .unwrap();
+    assert_eq!(message, 2);
+    assert_eq!(crate::read_packet::<u64, u64>(&packet).unwrap().0, 42);
+    assert_eq!(reassembler.pending(), 0);
+
+    assert_eq!(
+        fragment_packet::<u8, _>(0, 1u8, 8).unwrap_err(),
+        FragmentError::MtuTooSmall(8)
+    );
+    assert_eq!(
+        reassembler.push(&[0; 4]).unwrap_err(),
+        FragmentError::Truncated(4)
+    );
+
+    let mut bad = datagrams[0].clone();
+    bad[4..6].copy_from_slice(&total.to_le_bytes());
+    assert!(matches!(
+        reassembler.push(&bad),
+        Err(FragmentError::InvalidIndex { .. })
+    ));
+
+    reassembler.push(&datagrams[0]).unwrap();
+    let mut bad = datagrams[1].clone();
+    bad[6..8].copy_from_slice(&(total + 1).to_le_bytes());
+    assert!(matches!(
+        reassembler.push(&bad),
+        Err(FragmentError::CountMismatch { .. })
+    ));
+    assert!(reassembler.discard(1));
+
+    let mut limited = Reassembler::with_max_message_size(40);
+    limited.push(&other.fragment(1).unwrap()).unwrap();
+    assert!(matches!(
+        limited.push(&datagrams[0]),
+        Err(FragmentError::MessageTooLarge(_))
+    ));
+    assert!(!limited.is_pending(1));
+
+    // Huge fragments count from a single datagram is rejected upfront.
+    let mut huge = alloc::vec![0; 16];
+    huge[6..8].copy_from_slice(&u16::MAX.to_le_bytes());
+    assert!(matches!(
+        limited.push(&huge),
+        Err(FragmentError::MessageTooLarge(_))
+    ));
+    assert_eq!(limited.pending(), 1);
+
+    // Oldest pending message is evicted.
+    let mut small = Reassembler::with_limits(usize::MAX, 2);
+    for message in 0..3u32 {
+        let mut datagram = alloc::vec![0; 9];
+        datagram[..4].copy_from_slice(&message.to_le_bytes());
+        datagram[6..8].copy_from_slice(&2u16.to_le_bytes());
+        assert_eq!(small.push(&datagram).unwrap(), None);
+    }
+    assert_eq!(small.pending(), 2);
+    assert!(!small.is_pending(0));
+    assert_eq!(small.missing(2), Some(alloc::vec![1]));
+
+    // Default limit rejects oversized message.
+    let mut datagram = alloc::vec![0; 8 + 256];
+    datagram[6..8].copy_from_slice(&u16::MAX.to_le_bytes());
+    assert!(matches!(
+        Reassembler::new().push(&datagram),
+        Err(FragmentError::MessageTooLarge(_))
+    ));
+
+    // Fragments other than the last one must have equal size.
+    let fragment = |index: u16, len: usize| {
+        let mut datagram = alloc::vec![0; 8 + len];
+        datagram[4..6].copy_from_slice(&index.to_le_bytes());
+        datagram[6..8].copy_from_slice(&3u16.to_le_bytes());
+        datagram
+    };
+    let mut reassembler = Reassembler::new();
+    assert_eq!(reassembler.push(&fragment(2, 1)).unwrap(), None);
+    assert_eq!(reassembler.push(&fragment(0, 4)).unwrap(), None);
+    assert_eq!(
+        reassembler.push(&fragment(1, 5)).unwrap_err(),
+        FragmentError::ChunkSizeMismatch {
+            expected: 4,
+            found: 5
+        }
+    );
+    assert_eq!(
+        reassembler.push(&fragment(1, 4)).unwrap().unwrap().1.len(),
+        9
+    );
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_decoder() {
+    use alloc::{string::String, vec::Vec};
+
+    use crate::{
+        decoder::{Decoder, DecoderError},
+        packet::write_packet_to_vec,
+    };
+
+    let values = ["one", "two", "three", "a bit longer string"];
+    let mut stream = Vec::new();
+    for value in values {
+        let mut packet = Vec::new();
+        let size = write_packet_to_vec::<As<str>, _>(value, &mut packet);
+        stream.extend_from_slice(&packet[..size]);
+    }
+
+    for chunk_size in [1, 2, 5, 7, stream.len()] {
+        let mut decoder = Decoder::<As<str>, String>::new();
+        let mut decoded = Vec::new();
+        for chunk in stream.chunks(chunk_size) {
+            decoded.extend(decoder.push(chunk).unwrap());
+            while let Some(value) = decoder.poll().unwrap() {
+                decoded.push(value);
+            }
+        }
+        assert_eq!(decoded, values);
+        assert_eq!(decoder.buffered(), 0);
+    }
+
+    let mut decoder = Decoder::<As<str>, String>::new();
+    assert!(decoder.push(&stream[..3]).unwrap().is_none());
+    assert_eq!(decoder.buffered(), 3);
+    decoder.clear();
+    assert_eq!(decoder.buffered(), 0);
+
+    let mut limited = Decoder::<As<str>, String>::with_max_packet_size(8);
+    assert!(matches!(
+        limited.push(&stream),
+        Err(DecoderError::PacketTooLarge(_))
+    ));
+
+    // Garbage header fails instead of buffering input indefinitely.
+    let mut decoder = Decoder::<As<str>, String>::new();
+    assert!(matches!(
+        decoder.push(&[0xff; 16]),
+        Err(DecoderError::PacketTooLarge(_))
+    ));
+
+    // Packet with invalid UTF-8 is skipped.
+    let mut packet = Vec::new();
+    let size = write_packet_to_vec::<Bytes, _>(&[0xFFu8][..], &mut packet);
+    let mut decoder = Decoder::<As<str>, String>::new();
+    assert!(matches!(
+        decoder.push(&packet[..size]),
+        Err(DecoderError::Deserialize(_))
+    ));
+    assert_eq!(decoder.push(&stream).unwrap().as_deref(), Some("one"));
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_resumable() {
+    use alloc::vec::Vec;
+
+    use crate::{
+        decoder::{Progress, Resumable},
+        packet::write_packet_to_vec,
+    };
+
+    let parts: Vec<Vec<u32>> = (0..4).map(|i| (i * 10..i * 10 + 5).collect()).collect();
+    let mut input = Vec::new();
+    for part in &parts {
+        let mut packet = Vec::new();
+        let size = write_packet_to_vec::<[u32], _>(part, &mut packet);
+        input.extend_from_slice(&packet[..size]);
+    }
+
+    // Grow input byte by byte, resume after each suspension.
+    let mut reader = Resumable::<[u32]>::new();
+    let mut available = 0;
+    let mut decoded = Vec::new();
+    while reader.position() < input.len() {
+        match reader.read::<Vec<u32>>(&input[..available]).unwrap() {
+            Progress::Done(part) => decoded.push(part),
+            Progress::Suspended { required } => {
+                assert!(required > available && required <= input.len());
+                available += 1;
+            }
+        }
+    }
+    assert_eq!(decoded, parts);
+    assert!(matches!(
+        reader.read::<Vec<u32>>(&input),
+        Ok(Progress::Suspended { .. })
+    ));
+
+    // Discard consumed prefix.
+    let mut reader = Resumable::<[u32]>::new();
+    let first = reader.read::<Vec<u32>>(&input).unwrap();
+    assert_eq!(first, Progress::Done(parts[0].clone()));
+    let consumed = reader.position();
+    let rest = input[consumed..].to_vec();
+    reader.rebase(consumed);
+    assert_eq!(reader.position(), 0);
+    assert_eq!(
+        reader.read::<Vec<u32>>(&rest).unwrap(),
+        Progress::Done(parts[1].clone())
+    );
+
+    let mut reader = Resumable::<[u32]>::at(reader.position());
+    assert_eq!(
+        reader.read::<Vec<u32>>(&rest).unwrap(),
+        Progress::Done(parts[2].clone())
+    );
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_records() {
+    use alloc::vec::Vec;
+
+    use crate::records::{RecordWriter, Records};
+
+    let mut writer = RecordWriter::<(u32, As<str>)>::new();
+    assert!(writer.is_empty());
+    for i in 0..10u32 {
+        let name = alloc::format!("record-{i}");
+        assert_eq!(writer.push((i, name.as_str())), i as usize);
+    }
+    assert_eq!(writer.len(), 10);
+    let container = writer.finish();
+
+    let records = Records::<(u32, As<str>)>::open(&container).unwrap();
+    assert_eq!(records.len(), 10);
+    assert_eq!(
+        records.get::<(u32, &str)>(7).unwrap().unwrap(),
+        (7, "record-7")
+    );
+    assert!(records.get::<(u32, &str)>(10).is_none());
+
+    let scan: Vec<u32> = records
+        .range::<(u32, &str)>(3..=5)
+        .map(|r| r.unwrap().0)
+        .collect();
+    assert_eq!(scan, [3, 4, 5]);
+    let back: Vec<u32> = records
+        .iter::<(u32, &str)>()
+        .rev()
+        .take(2)
+        .map(|r| r.unwrap().0)
+        .collect();
+    assert_eq!(back, [9, 8]);
+    assert_eq!(records.iter::<(u32, &str)>().nth(2).unwrap().unwrap().0, 2);
+
+    // Record bytes are standalone packets.
+    let (value, _) =
+        crate::read_packet::<(u32, As<str>), (u32, &str)>(records.record(0).unwrap()).unwrap();
+    assert_eq!(value, (0, "record-0"));
+
+    let empty = RecordWriter::<u8>::new().finish();
+    assert!(Records::<u8>::open(&empty).unwrap().is_empty());
+
+    assert!(Records::<u8>::open(&container[..8]).is_err());
+    assert!(Records::<u8>::open(&container[1..]).is_err());
+
+    let mut corrupted = container.clone();
+    let index = container.len() - 16 - 10 * 12;
+    corrupted[index + 12..index + 20].copy_from_slice(&u64::MAX.to_le_bytes());
+    assert!(matches!(
+        Records::<u8>::open(&corrupted),
+        Err(crate::DeserializeError::WrongAddress)
+    ));
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_archive() {
+    use alloc::{boxed::Box, vec::Vec};
+
+    use crate::{archive::Archive, records::RecordWriter};
+
+    let mut writer = RecordWriter::<[Ref<str>]>::new();
+    writer.push(["grass", "dirt"]);
+    writer.push(["stone"]);
+    writer.push([""; 0]);
+    let container: Box<[u8]> = writer.finish().into_boxed_slice();
+
+    let archive = Archive::<[Ref<str>], _>::open(container.clone()).unwrap();
+    assert_eq!(archive.len(), 3);
+    assert!(!archive.is_empty());
+
+    let lazy = archive.get(0).unwrap().unwrap();
+    assert_eq!(lazy.len(), 2);
+    assert_eq!(lazy.get::<Vec<&str>>().unwrap(), ["grass", "dirt"]);
+    assert!(archive.get(2).unwrap().unwrap().is_empty());
+    assert!(archive.get(3).is_none());
+    assert_eq!(archive.records().len(), 3);
+
+    let archive = Archive::<[Ref<str>], _>::open_validated(&container[..]).unwrap();
+    assert_eq!(
+        archive.get(1).unwrap().unwrap().get::<Vec<&str>>().unwrap(),
+        ["stone"]
+    );
+    assert_eq!(archive.into_storage().len(), container.len());
+
+    assert!(Archive::<[Ref<str>], _>::open(&container[1..]).is_err());
+
+    // Corrupt string of the second record.
+    let mut corrupted = container.to_vec();
+    let start = corrupted.windows(5).position(|w| w == b"stone").unwrap();
+    corrupted[start] = 0xFF;
+    assert!(Archive::<[Ref<str>], _>::open(&corrupted[..]).is_ok());
+    assert!(Archive::<[Ref<str>], _>::open_validated(&corrupted[..]).is_err());
+}
+
+#[cfg(feature = "memmap2")]
+#[allow(unsafe_code)]
+#[test]
+fn test_archive_file() {
+    use crate::{archive::Archive, records::RecordWriter};
+
+    let mut writer = RecordWriter::<[u32]>::new();
+    writer.push([1u32, 2, 3]);
+    writer.push([4u32]);
+
+    let dir = std::env::temp_dir();
+    let path = dir.join(alloc::format!("alkahest-archive-{}", std::process::id()));
+    std::fs::write(&path, writer.finish()).unwrap();
+
+    // Safety: the file is not modified while mapped.
+    let archive = unsafe { Archive::<[u32], _>::open_file(&path) }.unwrap();
+    assert_eq!(archive.len(), 2);
+    assert_eq!(
+        archive.get(0).unwrap().unwrap().get::<Vec<u32>>().unwrap(),
+        [1, 2, 3]
+    );
+    drop(archive);
+
+    std::fs::write(&path, b"garbage").unwrap();
+    let err = unsafe { Archive::<[u32], _>::open_file(&path) }
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_record_checksums() {
+    use alloc::vec::Vec;
+
+    use crate::records::{RecordWriter, Records};
+
+    let mut writer = RecordWriter::<As<str>>::new();
+    for word in ["alpha", "bravo", "charlie", "delta"] {
+        writer.push(word);
+    }
+    let mut container = writer.finish();
+
+    // Flip a byte inside of the second and the fourth records.
+    for word in [&b"bravo"[..], b"delta"] {
+        let start = container.windows(5).position(|w| w == word).unwrap();
+        container[start + 1] ^= 0x20;
+    }
+
+    let records = Records::<As<str>>::open(&container).unwrap();
+    assert_eq!(records.get::<&str>(0).unwrap().unwrap(), "alpha");
+    assert!(matches!(
+        records.get::<&str>(1).unwrap(),
+        Err(crate::DeserializeError::ChecksumMismatch)
+    ));
+    assert!(matches!(
+        records.checked_record(3).unwrap(),
+        Err(crate::DeserializeError::ChecksumMismatch)
+    ));
+    // Unchecked bytes are still accessible for recovery.
+    assert!(records.record(1).is_some());
+
+    let mut corrupt = Vec::new();
+    let intact: Vec<(usize, &str)> = records
+        .iter::<&str>()
+        .skip_corrupt(|n, err| corrupt.push((n, err)))
+        .collect();
+    assert_eq!(intact, [(0, "alpha"), (2, "charlie")]);
+    assert_eq!(corrupt.len(), 2);
+    assert_eq!(corrupt[0].0, 1);
+    assert_eq!(corrupt[1].0, 3);
+    assert!(matches!(
+        corrupt[1].1,
+        crate::DeserializeError::ChecksumMismatch
+    ));
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_bounded() {
+    use alloc::{string::String, vec, vec::Vec};
+
+    use crate::{
+        bounded::Bounded,
+        packet::{read_packet, write_packet_to_vec},
+        size::SIZE_STACK,
+        DeserializeError,
+    };
+
+    assert_eq!(
+        <Bounded<[u32], 4> as Formula>::MAX_STACK_SIZE,
+        Some(SIZE_STACK + 16)
+    );
+    assert_eq!(
+        <Bounded<str, 8> as Formula>::MAX_STACK_SIZE,
+        Some(SIZE_STACK + 8)
+    );
+    assert_eq!(
+        <Bounded<[()], 8> as Formula>::MAX_STACK_SIZE,
+        Some(SIZE_STACK)
+    );
+    assert_eq!(<Bounded<[As<str>], 8> as Formula>::MAX_STACK_SIZE, None);
+
+    type Message = (
+        Bounded<[u32], 4>,
+        Bounded<str, 8>,
+        Bounded<[As<str>], 2>,
+        Bounded<[()], 3>,
+        u8,
+    );
+
+    let mut buffer = Vec::new();
+    let size = write_packet_to_vec::<Message, _>(
+        (vec![1u32, 2], "abc", ["x", "yz"], [(), ()], 7u8),
+        &mut buffer,
+    );
+    let (value, _) =
+        read_packet::<Message, (Vec<u32>, String, Vec<&str>, Vec<()>, u8)>(&buffer[..size])
+            .unwrap();
+    assert_eq!(
+        value,
+        (
+            vec![1, 2],
+            String::from("abc"),
+            vec!["x", "yz"],
+            vec![(), ()],
+            7
+        )
+    );
+
+    // Values written with looser bound are rejected.
+    let size = write_packet_to_vec::<Bounded<[u32], 8>, _>([1u32; 5], &mut buffer);
+    assert!(matches!(
+        read_packet::<Bounded<[u32], 4>, Vec<u32>>(&buffer[..size]),
+        Err(DeserializeError::WrongLength(_))
+    ));
+    assert_eq!(
+        read_packet::<Bounded<[u32], 5>, Vec<u32>>(&buffer[..size])
+            .unwrap()
+            .0,
+        [1; 5]
+    );
+
+    let size = write_packet_to_vec::<Bounded<[As<str>], 8>, _>(["a", "b", "c"], &mut buffer);
+    assert!(matches!(
+        read_packet::<Bounded<[As<str>], 2>, Vec<&str>>(&buffer[..size]),
+        Err(DeserializeError::WrongLength(_))
+    ));
+
+    let size = write_packet_to_vec::<Bounded<[()], 8>, _>([(); 4], &mut buffer);
+    assert!(matches!(
+        read_packet::<Bounded<[()], 3>, Vec<()>>(&buffer[..size]),
+        Err(DeserializeError::WrongLength(_))
+    ));
+
+    let size = write_packet_to_vec::<Bounded<str, 16>, _>("123456789", &mut buffer);
+    assert!(matches!(
+        read_packet::<Bounded<str, 8>, &str>(&buffer[..size]),
+        Err(DeserializeError::WrongLength(_))
+    ));
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+#[should_panic(expected = "exceeds bound")]
+fn test_bounded_serialize_too_long() {
+    let mut buffer = alloc::vec::Vec::new();
+    crate::packet::write_packet_to_vec::<crate::bounded::Bounded<str, 2>, _>("abc", &mut buffer);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_c_str() {
+    use alloc::{ffi::CString, string::String, vec::Vec};
+    use core::ffi::CStr;
+
+    use crate::{
+        cstr::CStrFormula,
+        packet::{read_packet, write_packet_to_vec},
+    };
+
+    let mut buffer = [0u8; 16];
+    let (size, root) = serialize::<CStrFormula, _>("abc", &mut buffer).unwrap();
+    assert_eq!(size, 4);
+    assert_eq!(root, 4);
+    assert!(buffer.windows(4).any(|w| w == b"abc\0"));
+
+    let c_string = CString::new("firmware").unwrap();
+    let mut packet = Vec::new();
+    let size = write_packet_to_vec::<(CStrFormula, u8, CStrFormula), _>(
+        (c_string.as_c_str(), 5u8, String::from("tail")),
+        &mut packet,
+    );
+    let ((name, version, tail), _) =
+        read_packet::<(CStrFormula, u8, CStrFormula), (CString, u8, &str)>(&packet[..size])
+            .unwrap();
+    assert_eq!((name, version, tail), (c_string.clone(), 5, "tail"));
+
+    let (name, _) =
+        read_packet::<(CStrFormula, u8, CStrFormula), (&CStr, u8, String)>(&packet[..size])
+            .unwrap();
+    assert_eq!(name.0, c_string.as_c_str());
+
+    // Missing terminator or interior NUL.
+    for bytes in [&b"abc"[..], b"a\0bc\0"] {
+        let size = write_packet_to_vec::<Bytes, _>(bytes, &mut packet);
+        assert!(matches!(
+            read_packet::<CStrFormula, &str>(&packet[..size]),
+            Err(crate::DeserializeError::Incompatible)
+        ));
+    }
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_fixed_str() {
+    use alloc::{string::String, vec::Vec};
+
+    use crate::{
+        fixed_str::FixedStr,
+        packet::{read_packet, write_packet_to_vec},
+        DeserializeError,
+    };
+
+    assert_eq!(<FixedStr<8> as Formula>::MAX_STACK_SIZE, Some(8));
+    assert_eq!(
+        <(FixedStr<4>, u8) as Formula>::MAX_STACK_SIZE,
+        Some(5),
+        "No size prefix for fixed-size strings"
+    );
+
+    type Message = (FixedStr<4>, FixedStr<2>, FixedStr<0>, u8);
+
+    let mut buffer = Vec::new();
+    let size = write_packet_to_vec::<Message, _>(("ab", "xy", "", 7u8), &mut buffer);
+    let (value, _) = read_packet::<Message, (&str, String, &str, u8)>(&buffer[..size]).unwrap();
+    assert_eq!(value, ("ab", String::from("xy"), "", 7));
+
+    let size = write_packet_to_vec::<FixedStr<4>, _>(String::from("abcd"), &mut buffer);
+    let (value, _) = read_packet::<FixedStr<4>, &str>(&buffer[..size]).unwrap();
+    assert_eq!(value, "abcd");
+
+    // Non-canonical padding.
+    let size = write_packet_to_vec::<[u8; 4], _>([b'a', 0, b'b', 0], &mut buffer);
+    assert!(matches!(
+        read_packet::<FixedStr<4>, &str>(&buffer[..size]),
+        Err(DeserializeError::NonCanonical)
+    ));
+
+    let size = write_packet_to_vec::<Ref<FixedStr<4>>, _>("ab", &mut buffer);
+    let (value, _) = read_packet::<Ref<FixedStr<4>>, &str>(&buffer[..size]).unwrap();
+    assert_eq!(value, "ab");
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+#[should_panic]
+fn test_fixed_str_serialize_too_long() {
+    let mut buffer = alloc::vec::Vec::new();
+    crate::packet::write_packet_to_vec::<crate::fixed_str::FixedStr<2>, _>("abc", &mut buffer);
+}
+
+#[test]
+fn test_odd_ints() {
+    use crate::odd_int::{U24, U40, U48};
+
+    assert_eq!(<(U24, U40, U48) as Formula>::MAX_STACK_SIZE, Some(14));
+    assert_eq!(U24::MAX, 0xFF_FFFF);
+    assert_eq!(U48::MAX, 0xFFFF_FFFF_FFFF);
+
+    let mut buffer = [0u8; 14];
+    let (size, _) =
+        serialize::<(U24, U40, U48), _>((U24::MAX, 0x12_3456_789Au64, 7u8), &mut buffer).unwrap();
+    assert_eq!(size, 14);
+
+    let value = deserialize::<(U24, U40, U48), (u32, u64, u128)>(&buffer).unwrap();
+    assert_eq!(value, (0xFF_FFFF, 0x12_3456_789A, 7));
+
+    let mut buffer = [0u8; 3];
+    serialize::<U24, _>(&0x0102u16, &mut buffer).unwrap();
+    assert_eq!(buffer, [0x02, 0x01, 0]);
+}
+
+#[test]
+#[should_panic]
+fn test_odd_int_out_of_range() {
+    let mut buffer = [0u8; 3];
+    let _ = serialize::<crate::odd_int::U24, _>(1u32 << 24, &mut buffer);
+}
+
+crate::define_bits! {
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    struct TestBits {
+        #[bits(3)]
+        kind: u8,
+        #[bits(1)]
+        flag: bool,
+        #[bits(17)]
+        value: u32,
+        #[bits(64)]
+        wide: u64,
+    }
+}
+
+#[test]
+fn test_bits() {
+    use crate::DeserializeError;
+
+    assert_eq!(<TestBits as Formula>::MAX_STACK_SIZE, Some(11));
+    assert_eq!(
+        <(TestBits, u8) as Formula>::MAX_STACK_SIZE,
+        Some(12),
+        "No size prefix for bit-packed struct"
+    );
+
+    let value = TestBits {
+        kind: 5,
+        flag: true,
+        value: 0x1_2345,
+        wide: u64::MAX,
+    };
+
+    let mut buffer = [0u8; 11];
+    let (size, _) = serialize::<TestBits, _>(value, &mut buffer).unwrap();
+    assert_eq!(size, 11);
+    assert_eq!(buffer[0] & 0b1111, 0b1101);
+    assert_eq!(deserialize::<TestBits, TestBits>(&buffer).unwrap(), value);
+
+    // Unused bits of the last byte must be zero.
+    buffer[10] |= 0x80;
+    assert!(matches!(
+        deserialize::<TestBits, TestBits>(&buffer),
+        Err(DeserializeError::NonCanonical)
+    ));
+}
+
+#[test]
+#[should_panic]
+fn test_bits_value_too_wide() {
+    let value = TestBits {
+        kind: 8,
+        flag: false,
+        value: 0,
+        wide: 0,
+    };
+    let mut buffer = [0u8; 11];
+    let _ = serialize::<TestBits, _>(value, &mut buffer);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_sorted_map() {
+    use alloc::{collections::BTreeMap, vec, vec::Vec};
+
+    use crate::{
+        packet::{read_packet, write_packet_to_vec},
+        reference::Ref,
+        sorted::{SortedMap, SortedMapView},
+        DeserializeError,
+    };
+
+    type Table = SortedMap<u16, Ref<str>>;
+
+    let map: BTreeMap<u16, &str> = (0..100u16)
+        .map(|i| (i * 3, ["zero", "one", "two"][usize::from(i % 3)]))
+        .collect();
+
+    let mut buffer = Vec::new();
+    let size = write_packet_to_vec::<Table, _>(&map, &mut buffer);
+
+    let (view, _) =
+        read_packet::<Table, SortedMapView<u16, Ref<str>, u16, &str>>(&buffer[..size]).unwrap();
+    assert_eq!(view.len(), 100);
+    view.validate().unwrap();
+
+    for (key, value) in &map {
+        assert_eq!(view.get(key).unwrap(), Some(*value));
+    }
+    assert_eq!(view.get(&4).unwrap(), None);
+    assert_eq!(view.get(&1000).unwrap(), None);
+    assert_eq!(view.search(&4).unwrap(), Err(2));
+    assert!(view.contains_key(&297).unwrap());
+    assert_eq!(view.entry(1).unwrap().unwrap(), (3, "one"));
+    assert!(view.entry(100).is_none());
+    assert!(view
+        .iter()
+        .map(Result::unwrap)
+        .eq(map.iter().map(|(k, v)| (*k, *v))));
+
+    let (copy, _) = read_packet::<Table, BTreeMap<u16, &str>>(&buffer[..size]).unwrap();
+    assert_eq!(copy, map);
+
+    // Layout is the same as slice of pairs.
+    let (pairs, _) = read_packet::<[(u16, Ref<str>)], Vec<(u16, &str)>>(&buffer[..size]).unwrap();
+    assert_eq!(pairs.len(), 100);
+
+    let size = write_packet_to_vec::<[(u16, u8)], _>(vec![(1u16, 1u8), (1, 2)], &mut buffer);
+    assert!(matches!(
+        read_packet::<SortedMap<u16, u8>, Vec<(u16, u8)>>(&buffer[..size]),
+        Err(DeserializeError::Unsorted)
+    ));
+    let (view, _) =
+        read_packet::<SortedMap<u16, u8>, SortedMapView<u16, u8, u16, u8>>(&buffer[..size])
+            .unwrap();
+    assert!(matches!(view.validate(), Err(DeserializeError::Unsorted)));
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+#[should_panic]
+fn test_sorted_map_serialize_unsorted() {
+    let mut buffer = alloc::vec::Vec::new();
+    crate::packet::write_packet_to_vec::<crate::sorted::SortedMap<u8, u8>, _>(
+        [(2u8, 0u8), (1, 0)],
+        &mut buffer,
+    );
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_sorted_set() {
+    use alloc::{collections::BTreeSet, vec, vec::Vec};
+
+    use crate::{
+        packet::{read_packet, write_packet_to_vec},
+        reference::Ref,
+        sorted::{SortedSet, SortedSetView},
+        DeserializeError,
+    };
+
+    let set: BTreeSet<u32> = (0..50).map(|i| i * i).collect();
+
+    let mut buffer = Vec::new();
+    let size = write_packet_to_vec::<SortedSet<u32>, _>(&set, &mut buffer);
+
+    let (view, _) =
+        read_packet::<SortedSet<u32>, SortedSetView<u32, u32>>(&buffer[..size]).unwrap();
+    assert_eq!(view.len(), 50);
+    view.validate().unwrap();
+    assert!(set.iter().all(|value| view.contains(value).unwrap()));
+    assert!(!view.contains(&2).unwrap());
+    assert_eq!(view.search(&10).unwrap(), Err(4));
+    assert_eq!(view.get(3).unwrap().unwrap(), 9);
+    assert!(view.get(50).is_none());
+    assert!(view.iter().map(Result::unwrap).eq(set.iter().copied()));
+
+    let (copy, _) = read_packet::<SortedSet<u32>, BTreeSet<u32>>(&buffer[..size]).unwrap();
+    assert_eq!(copy, set);
+
+    // Layout is the same as slice.
+    let (elems, _) = read_packet::<[u32], Vec<u32>>(&buffer[..size]).unwrap();
+    assert!(elems.iter().eq(set.iter()));
+
+    let size = write_packet_to_vec::<SortedSet<Ref<str>>, _>(["a", "b", "c"], &mut buffer);
+    let (view, _) =
+        read_packet::<SortedSet<Ref<str>>, SortedSetView<Ref<str>, &str>>(&buffer[..size]).unwrap();
+    assert!(view.contains("b").unwrap());
+    assert!(!view.contains("d").unwrap());
+
+    let size = write_packet_to_vec::<[u8], _>(vec![1u8, 3, 3], &mut buffer);
+    assert!(matches!(
+        read_packet::<SortedSet<u8>, Vec<u8>>(&buffer[..size]),
+        Err(DeserializeError::Unsorted)
+    ));
+    let (view, _) = read_packet::<SortedSet<u8>, SortedSetView<u8, u8>>(&buffer[..size]).unwrap();
+    assert!(matches!(view.validate(), Err(DeserializeError::Unsorted)));
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+#[should_panic]
+fn test_sorted_set_serialize_duplicate() {
+    let mut buffer = alloc::vec::Vec::new();
+    crate::packet::write_packet_to_vec::<crate::sorted::SortedSet<u8>, _>([1u8, 1], &mut buffer);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_ascii_str() {
+    use alloc::{string::String, vec::Vec};
+
+    use crate::{
+        ascii::AsciiStr,
+        packet::{read_packet, write_packet_to_vec},
+        reference::Ref,
+        DeserializeError,
+    };
+
+    type Message = (Ref<AsciiStr>, AsciiStr);
+
+    let mut buffer = Vec::new();
+    let size = write_packet_to_vec::<Message, _>(("token", String::from("id-42")), &mut buffer);
+    let (value, _) = read_packet::<Message, (String, &str)>(&buffer[..size]).unwrap();
+    assert_eq!(value, (String::from("token"), "id-42"));
+
+    // Same layout as `str`.
+    let (value, _) = read_packet::<(Ref<str>, str), (&str, &str)>(&buffer[..size]).unwrap();
+    assert_eq!(value, ("token", "id-42"));
+
+    let size = write_packet_to_vec::<str, _>("caf\u{e9}", &mut buffer);
+    assert!(matches!(
+        read_packet::<AsciiStr, &str>(&buffer[..size]),
+        Err(DeserializeError::NonAscii)
+    ));
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+#[should_panic]
+fn test_ascii_str_serialize_non_ascii() {
+    let mut buffer = alloc::vec::Vec::new();
+    crate::packet::write_packet_to_vec::<crate::ascii::AsciiStr, _>("caf\u{e9}", &mut buffer);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_unix_time() {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use crate::{
+        convert::{UnixMillis, UnixNanos, UnixSeconds},
+        DeserializeError,
+    };
+
+    type Times = (UnixSeconds, UnixMillis, UnixNanos);
+
+    let time = UNIX_EPOCH + Duration::new(1_700_000_000, 123_456_789);
+    let mut buffer = [0u8; 32];
+    let (size, _) = serialize::<Times, _>((time, time, time), &mut buffer).unwrap();
+    assert_eq!(size, 32);
+
+    let (secs, millis, nanos) = deserialize::<(i64, i64, i128), (i64, i64, i128)>(&buffer).unwrap();
+    assert_eq!(secs, 1_700_000_000);
+    assert_eq!(millis, 1_700_000_000_123);
+    assert_eq!(nanos, 1_700_000_000_123_456_789);
+
+    let value = deserialize::<Times, (SystemTime, SystemTime, SystemTime)>(&buffer).unwrap();
+    assert_eq!(value.0, UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+    assert_eq!(
+        value.1,
+        UNIX_EPOCH + Duration::from_millis(1_700_000_000_123)
+    );
+    assert_eq!(value.2, time);
+
+    // Times before epoch are rounded towards negative infinity.
+    let before = UNIX_EPOCH - Duration::from_millis(1500);
+    serialize::<(UnixSeconds, UnixMillis), _>((before, before), &mut buffer).unwrap();
+    assert_eq!(
+        deserialize::<(i64, i64), (i64, i64)>(&buffer[..16]).unwrap(),
+        (-2, -1500)
+    );
+    serialize::<UnixMillis, _>(before, &mut buffer).unwrap();
+    assert_eq!(
+        deserialize::<UnixMillis, SystemTime>(&buffer[..8]).unwrap(),
+        before
+    );
+
+    serialize::<i128, _>(i128::MAX, &mut buffer).unwrap();
+    assert!(matches!(
+        deserialize::<UnixNanos, SystemTime>(&buffer[..16]),
+        Err(DeserializeError::IntegerOverflow)
+    ));
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_interned() {
+    use alloc::{vec, vec::Vec};
+
+    use crate::{
+        interned::{Interned, Interner},
+        packet::{read_packet, write_packet, write_packet_to_vec},
+        reference::Ref,
+    };
+
+    type Node = (u32, Interned<[u64]>);
+
+    let material = vec![1u64; 32];
+    let other = vec![2u64; 32];
+    let materials = [&material, &other, &material, &material, &other, &material];
+
+    let interner = Interner::new();
+    let mut buffer = Vec::new();
+    let size = write_packet_to_vec::<[Node], _>(
+        materials
+            .iter()
+            .enumerate()
+            .map(|(i, m)| (i as u32, interner.intern(m.as_slice()))),
+        &mut buffer,
+    );
+    assert_eq!(interner.len(), 2);
+
+    let (nodes, _) = read_packet::<[Node], Vec<(u32, Vec<u64>)>>(&buffer[..size]).unwrap();
+    assert_eq!(nodes.len(), 6);
+    for (i, (index, value)) in nodes.iter().enumerate() {
+        assert_eq!(*index as usize, i);
+        assert_eq!(value, materials[i]);
+    }
+
+    // Same layout as `Ref`, but values are written once.
+    let plain_size = write_packet_to_vec::<[(u32, Ref<[u64]>)], _>(
+        materials.iter().map(|m| (0u32, m.as_slice())),
+        &mut Vec::new(),
+    );
+    assert!(size + 4 * 32 * 8 <= plain_size);
+
+    // Failed attempt does not poison the interner.
+    let interner = Interner::new();
+    let mut small = [0u8; 64];
+    assert!(write_packet::<[Interned<[u64]>], _>(
+        materials.iter().map(|m| interner.intern(m.as_slice())),
+        &mut small
+    )
+    .is_err());
+    let size = write_packet_to_vec::<[Interned<[u64]>], _>(
+        materials.iter().map(|m| interner.intern(m.as_slice())),
+        &mut buffer,
+    );
+    let (values, _) = read_packet::<[Interned<[u64]>], Vec<Vec<u64>>>(&buffer[..size]).unwrap();
+    assert!(values.iter().zip(materials).all(|(v, m)| v == m));
+}
+
+#[test]
+fn test_versioned() {
+    use crate::{
+        deserialize::Deserializer,
+        packet::{read_packet, write_packet},
+        versioned::{Migrate, OldVersion, Versioned},
+        DeserializeError,
+    };
+
+    #[derive(Debug, PartialEq)]
+    struct Config(u32, u32);
+
+    impl<'de> Deserialize<'de, (u32, u32)> for Config {
+        fn deserialize(de: Deserializer<'de>) -> Result<Self, DeserializeError> {
+            let (a, b) = <(u32, u32) as Deserialize<(u32, u32)>>::deserialize(de)?;
+            Ok(Config(a, b))
+        }
+
+        fn deserialize_in_place(&mut self, de: Deserializer<'de>) -> Result<(), DeserializeError> {
+            *self = <Self as Deserialize<(u32, u32)>>::deserialize(de)?;
+            Ok(())
+        }
+    }
+
+    impl Migrate<'_, (u32, u32)> for Config {
+        fn migrate(old: OldVersion<'_>) -> Result<Self, DeserializeError> {
+            match old.version() {
+                2 => Ok(Config(old.decode::<u32, u32>()?, 7)),
+                _ => Err(old.unsupported()),
+            }
+        }
+    }
+
+    let mut buffer = [0u8; 32];
+
+    let size = write_packet::<Versioned<(u32, u32), 3>, _>((1u32, 2u32), &mut buffer).unwrap();
+    let (config, _) = read_packet::<Versioned<(u32, u32), 3>, Config>(&buffer[..size]).unwrap();
+    assert_eq!(config, Config(1, 2));
+
+    let size = write_packet::<Versioned<u32, 2>, _>(5u32, &mut buffer).unwrap();
+    let (config, _) = read_packet::<Versioned<(u32, u32), 3>, Config>(&buffer[..size]).unwrap();
+    assert_eq!(config, Config(5, 7));
+
+    let size = write_packet::<Versioned<u32, 1>, _>(5u32, &mut buffer).unwrap();
+    assert!(matches!(
+        read_packet::<Versioned<(u32, u32), 3>, Config>(&buffer[..size]),
+        Err(DeserializeError::WrongVariant(1, _))
+    ));
+
+    let size = write_packet::<Versioned<(u32, u32), 4>, _>((1u32, 2u32), &mut buffer).unwrap();
+    assert!(matches!(
+        read_packet::<Versioned<(u32, u32), 3>, Config>(&buffer[..size]),
+        Err(DeserializeError::WrongVariant(4, _))
+    ));
+}
+
+#[cfg(feature = "crypto")]
+#[test]
+fn test_encrypted() {
+    use alloc::{string::String, vec::Vec};
+
+    use crate::{
+        encrypted::{AuthenticationFailed, Cipher, Encrypt, Encrypted, Sealed},
+        packet::{read_packet, write_packet_to_vec},
+        reference::Ref,
+        DeserializeError,
+    };
+
+    struct Xor(u8);
+
+    impl Cipher for Xor {
+        const NONCE_SIZE: usize = 2;
+        const TAG_SIZE: usize = 1;
+
+        fn encrypt_in_place(&self, nonce: &[u8], data: &mut [u8], tag: &mut [u8]) {
+            tag[0] = data
+                .iter()
+                .fold(self.0 ^ nonce[0], |acc, b| acc.wrapping_add(*b));
+            data.iter_mut().for_each(|b| *b ^= self.0 ^ nonce[1]);
+        }
+
+        fn decrypt_in_place(
+            &self,
+            nonce: &[u8],
+            data: &mut [u8],
+            tag: &[u8],
+        ) -> Result<(), AuthenticationFailed> {
+            data.iter_mut().for_each(|b| *b ^= self.0 ^ nonce[1]);
+            if data
+                .iter()
+                .fold(self.0 ^ nonce[0], |acc, b| acc.wrapping_add(*b))
+                == tag[0]
+            {
+                Ok(())
+            } else {
+                Err(AuthenticationFailed)
+            }
+        }
+    }
+
+    type Secret = (u32, Ref<str>);
+    type Message = (Ref<str>, Encrypted<Secret, Xor>);
+
+    let cipher = Xor(0x3c);
+    let mut buffer = Vec::new();
+    let size = write_packet_to_vec::<Message, _>(
+        ("alice", Encrypt::new(&cipher, &[1, 2], (42u32, "password"))),
+        &mut buffer,
+    );
+    let buffer = &buffer[..size];
+
+    // Payload is not stored in plain text.
+    assert!(!buffer.windows(8).any(|w| w == b"password"));
+
+    let ((name, sealed), _) = read_packet::<Message, (&str, Sealed<Secret, Xor>)>(buffer).unwrap();
+    assert_eq!(name, "alice");
+    assert_eq!(sealed.nonce(), [1, 2]);
+
+    let mut plain = Vec::new();
+    let (id, secret): (u32, String) = sealed.open(&cipher, &mut plain).unwrap();
+    assert_eq!(id, 42);
+    assert_eq!(secret, "password");
+    assert_eq!(sealed.as_bytes().len(), 2 + plain.len() + 1);
+
+    // Decrypted bytes form regular packet.
+    let (id, secret) = read_packet::<Secret, (u32, &str)>(&plain).unwrap().0;
+    assert_eq!((id, secret), (42, "password"));
+
+    assert!(matches!(
+        sealed.open::<(u32, &str)>(&Xor(0), &mut plain),
+        Err(DeserializeError::AuthenticationFailed)
+    ));
+
+    let mut tampered = buffer.to_vec();
+    let offset = sealed.as_bytes().as_ptr() as usize - buffer.as_ptr() as usize;
+    tampered[offset + 3] ^= 1;
+    let ((_, sealed), _) = read_packet::<Message, (&str, Sealed<Secret, Xor>)>(&tampered).unwrap();
+    assert!(matches!(
+        sealed.open::<(u32, &str)>(&cipher, &mut plain),
+        Err(DeserializeError::AuthenticationFailed)
+    ));
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_signed() {
+    use alloc::{string::String, vec::Vec};
+
+    use crate::{
+        packet::{read_packet, write_packet_to_vec},
+        reference::Ref,
+        signed::{InvalidSignature, Sign, Signed, Signer, Unverified, Verifier},
+        DeserializeError,
+    };
+
+    struct Trusted(u16);
+    struct Key(u16);
+
+    fn digest(key: u16, payload: &[u8]) -> [u8; 2] {
+        payload
+            .iter()
+            .fold(key, |acc, b| acc.rotate_left(3) ^ u16::from(*b))
+            .to_le_bytes()
+    }
+
+    impl Verifier for Trusted {
+        const SIGNATURE_SIZE: usize = 2;
+
+        fn verify(&self, payload: &[u8], signature: &[u8]) -> Result<(), InvalidSignature> {
+            if digest(self.0, payload) == signature {
+                Ok(())
+            } else {
+                Err(InvalidSignature)
+            }
+        }
+    }
+
+    impl Signer<Trusted> for Key {
+        fn sign(&self, payload: &[u8], signature: &mut [u8]) {
+            signature.copy_from_slice(&digest(self.0, payload));
+        }
+    }
+
+    type Update = (u32, Signed<(u64, Ref<str>), Trusted>);
+    type Received<'de> = (u32, Unverified<'de, (u64, Ref<str>), Trusted>);
+
+    // Verifying key is loaded at runtime.
+    let trusted = Trusted(0x1234);
+
+    let mut buffer = Vec::new();
+    let size = write_packet_to_vec::<Update, _>(
+        (3u32, Sign::new(&Key(0x1234), (7u64, "firmware"))),
+        &mut buffer,
+    );
+
+    let ((id, unverified), _) = read_packet::<Update, Received>(&buffer[..size]).unwrap();
+    assert_eq!(id, 3);
+    assert_eq!(unverified.signature().len(), 2);
+    let (version, name): (u64, &str) = unverified.verify(&trusted).unwrap();
+    assert_eq!((version, name), (7, "firmware"));
+
+    let mut place = (0u64, String::new());
+    unverified.verify_in_place(&trusted, &mut place).unwrap();
+    assert_eq!(place, (7, String::from("firmware")));
+
+    // Rotated key rejects old signatures.
+    assert!(matches!(
+        unverified.verify::<(u64, &str)>(&Trusted(0x4321)),
+        Err(DeserializeError::InvalidSignature)
+    ));
+
+    let pos = buffer.windows(8).position(|w| w == b"firmware").unwrap();
+    let mut tampered = buffer[..size].to_vec();
+    tampered[pos] = b'F';
+    let ((_, unverified), _) = read_packet::<Update, Received>(&tampered).unwrap();
+    assert!(matches!(
+        unverified.verify::<(u64, &str)>(&trusted),
+        Err(DeserializeError::InvalidSignature)
+    ));
+
+    let size = write_packet_to_vec::<Update, _>(
+        (3u32, Sign::new(&Key(0x4321), (7u64, "firmware"))),
+        &mut buffer,
+    );
+    let ((_, unverified), _) = read_packet::<Update, Received>(&buffer[..size]).unwrap();
+    assert!(matches!(
+        unverified.verify::<(u64, &str)>(&trusted),
+        Err(DeserializeError::InvalidSignature)
+    ));
+
+    // Signed payload behind reference.
+    type Boxed = Ref<Signed<u32, Trusted>>;
+    let size = write_packet_to_vec::<Boxed, _>(Sign::new(&Key(0x1234), 5u32), &mut buffer);
+    let (unverified, _) = read_packet::<Boxed, Unverified<u32, Trusted>>(&buffer[..size]).unwrap();
+    assert_eq!(unverified.verify::<u32>(&trusted).unwrap(), 5);
+}
+
+#[test]
+fn test_sparse() {
+    use crate::{
+        packet::{read_packet, write_packet},
+        sparse::Sparse,
+        DeserializeError,
+    };
+
+    type Patch = Sparse<(u32, Vlq, Ref<str>, (u8, u16), Ref<[u32]>)>;
+    type PatchValue<'a> = (
+        Option<u32>,
+        Option<u64>,
+        Option<&'a str>,
+        Option<(u8, u16)>,
+        Option<Vec<u32>>,
+    );
+
+    let values: [PatchValue; 5] = [
+        (None, None, None, None, None),
+        (Some(1), None, None, None, None),
+        (None, Some(300), Some("hi"), None, None),
+        (None, None, None, Some((1, 2)), Some(vec![1, 2, 3])),
+        (Some(4), Some(5), Some("all"), Some((6, 7)), Some(vec![8])),
+    ];
+
+    let mut buffer = [0u8; 256];
+    for value in values {
+        let size = write_packet::<Patch, _>(value.clone(), &mut buffer).unwrap();
+        let (read, _) = read_packet::<Patch, PatchValue>(&buffer[..size]).unwrap();
+        assert_eq!(read, value);
+
+        // Also works when not the last field.
+        let size = write_packet::<(Patch, u8), _>((value.clone(), 9u8), &mut buffer).unwrap();
+        let ((read, tail), _) =
+            read_packet::<(Patch, u8), (PatchValue, u8)>(&buffer[..size]).unwrap();
+        assert_eq!(read, value);
+        assert_eq!(tail, 9);
+    }
+
+    let size = write_packet::<Sparse<(u64, u64, u64, u64)>, _>(
+        (None::<u64>, None::<u64>, Some(1u64), None::<u64>),
+        &mut buffer,
+    )
+    .unwrap();
+    let dense = write_packet::<(Option<u64>, Option<u64>, Option<u64>, Option<u64>), _>(
+        (None::<u64>, None::<u64>, Some(1u64), None::<u64>),
+        &mut [0u8; 256],
+    )
+    .unwrap();
+    // Tags and space reserved for absent values are replaced with single bitmap byte.
+    assert_eq!(dense - size, 4 + 2 * 8 - 1);
+
+    // Bits of nonexistent fields are rejected.
+    type Small = Sparse<(u8, u8, u8, u8)>;
+    type SmallValue = (Option<u8>, Option<u8>, Option<u8>, Option<u8>);
+    let size =
+        write_packet::<Small, _>((Some(1u8), None::<u8>, None::<u8>, None::<u8>), &mut buffer)
+            .unwrap();
+    assert_eq!(buffer[size - 1], 0b0001);
+    buffer[size - 1] |= 0b1_0000;
+    assert!(matches!(
+        read_packet::<Small, SmallValue>(&buffer[..size]),
+        Err(DeserializeError::NonCanonical)
+    ));
+}
+
+#[test]
+fn test_tail() {
+    use crate::{
+        packet::{read_packet, write_packet},
+        tail::Tail,
+    };
+
+    let mut buffer = [0u8; 64];
+
+    // No length prefix in the last field.
+    let size = write_packet::<(u32, Tail), _>((1u32, [5u8; 10]), &mut buffer).unwrap();
+    let bytes_size =
+        write_packet::<(u32, Bytes), _>((1u32, &[5u8; 10][..]), &mut [0u8; 64]).unwrap();
+    assert_eq!(size, bytes_size);
+
+    let ((id, payload), _) = read_packet::<(u32, Tail), (u32, &[u8])>(&buffer[..size]).unwrap();
+    assert_eq!(id, 1);
+    assert_eq!(payload, [5; 10]);
+
+    let ((_, payload), _) = read_packet::<(u32, Tail), (u32, Vec<u8>)>(&buffer[..size]).unwrap();
+    assert_eq!(payload, vec![5; 10]);
+
+    let size = write_packet::<(Tail, Tail), _>((vec![1u8, 2], "abc"), &mut buffer).unwrap();
+    let ((a, b), _) = read_packet::<(Tail, Tail), (&[u8], &[u8])>(&buffer[..size]).unwrap();
+    assert_eq!(a, [1, 2]);
+    assert_eq!(b, b"abc");
+}
+
+#[test]
+fn test_skip_validated() {
+    use crate::{
+        packet::{read_packet, write_packet_to_vec},
+        skip::Skip,
+        validate::SkipValidated,
+        DeserializeError,
+    };
+
+    type Message = (Ref<[Ref<str>]>, u8);
+
+    let mut buffer = Vec::new();
+    let size = write_packet_to_vec::<Message, _>((["abc", "def"], 3u8), &mut buffer);
+
+    let ((SkipValidated, tail), _) =
+        read_packet::<Message, (SkipValidated, u8)>(&buffer[..size]).unwrap();
+    assert_eq!(tail, 3);
+
+    let mut corrupted = buffer[..size].to_vec();
+    let pos = corrupted.windows(3).position(|w| w == b"def").unwrap();
+    corrupted[pos + 1] = 0xc0;
+    assert!(read_packet::<Message, (Skip, u8)>(&corrupted).is_ok());
+    assert!(matches!(
+        read_packet::<Message, (SkipValidated, u8)>(&corrupted),
+        Err(DeserializeError::NonUtf8(_))
+    ));
+}
+
+#[test]
+fn test_bytes_into_array() {
+    use crate::{
+        packet::{read_packet, write_packet},
+        DeserializeError,
+    };
+
+    let mut buffer = [0u8; 64];
+    let key = [7u8; 16];
+
+    let size = write_packet::<(Bytes, u8), _>((key, 1u8), &mut buffer).unwrap();
+    let ((array, tail), _) = read_packet::<(Bytes, u8), ([u8; 16], u8)>(&buffer[..size]).unwrap();
+    assert_eq!(array, key);
+    assert_eq!(tail, 1);
+
+    let ((array, _), _) = read_packet::<(Bytes, u8), (&[u8; 16], u8)>(&buffer[..size]).unwrap();
+    assert_eq!(array, &key);
+
+    assert!(matches!(
+        read_packet::<(Bytes, u8), ([u8; 15], u8)>(&buffer[..size]),
+        Err(DeserializeError::WrongLength(_))
+    ));
+    assert!(matches!(
+        read_packet::<(Bytes, u8), (&[u8; 17], u8)>(&buffer[..size]),
+        Err(DeserializeError::WrongLength(_))
+    ));
+}
+
+#[test]
+fn test_lazy_sizes() {
+    use crate::packet::{read_packet, write_packet};
+
+    type Message = (Ref<str>, Bytes);
+
+    let mut buffer = [0u8; 64];
+    let size = write_packet::<Message, _>(("hello", &[1u8, 2, 3][..]), &mut buffer).unwrap();
+    let ((name, data), _) =
+        read_packet::<Message, (Lazy<str>, Lazy<Bytes>)>(&buffer[..size]).unwrap();
+    assert_eq!(name.len(), 5);
+    assert!(!name.is_empty());
+    assert_eq!(data.len(), 3);
+    assert_eq!(data.byte_size(), 3);
+
+    let size = write_packet::<Message, _>(("", &[][..]), &mut buffer).unwrap();
+    let ((name, data), _) =
+        read_packet::<Message, (Lazy<str>, Lazy<Bytes>)>(&buffer[..size]).unwrap();
+    assert!(name.is_empty());
+    assert!(data.is_empty());
+
+    let size = write_packet::<[u64], _>([1u64, 2, 3, 4], &mut buffer).unwrap();
+    let (lazy, _) = read_packet::<[u64], Lazy<[u64]>>(&buffer[..size]).unwrap();
+    assert_eq!(lazy.len(), 4);
+    assert_eq!(lazy.byte_size(), 32);
+}
+
+#[test]
+fn test_lazy_cell() {
+    use crate::{
+        lazy::LazyCell,
+        packet::{read_packet, write_packet},
+    };
+
+    type Message = (u32, Ref<[Ref<str>]>);
+
+    let mut buffer = [0u8; 128];
+    let size = write_packet::<Message, _>((5u32, ["a", "bc"]), &mut buffer).unwrap();
+
+    let ((id, mut names), _) =
+        read_packet::<Message, (u32, LazyCell<[Ref<str>], Vec<&str>>)>(&buffer[..size]).unwrap();
+    assert_eq!(id, 5);
+    assert!(names.get_cached().is_none());
+
+    let first = names.get().unwrap().as_ptr();
+    assert_eq!(names.get().unwrap(), &["a", "bc"]);
+    assert_eq!(names.get().unwrap().as_ptr(), first);
+
+    names.get_mut().unwrap().push("d");
+    assert_eq!(names.get_cached().unwrap(), &["a", "bc", "d"]);
+    assert_eq!(names.lazy().get::<Vec<&str>>().unwrap(), ["a", "bc"]);
+    assert_eq!(names.into_value().unwrap(), ["a", "bc", "d"]);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_structural_eq() {
+    use alloc::string::String;
+
+    use crate::{
+        diff::eq,
+        packet::{read_packet, write_packet_to_vec},
+    };
+
+    type Message = (Option<u32>, Ref<str>, Ref<[u16]>, u8);
+    type MessageValue = (Option<u32>, String, Vec<u16>, u8);
+
+    let value: MessageValue = (None, "abc".into(), vec![1, 2], 7);
+
+    let mut a = Vec::new();
+    let size = write_packet_to_vec::<Message, _>(value.clone(), &mut a);
+    a.truncate(size);
+
+    let mut b = Vec::new();
+    write_packet_to_vec::<Message, _>((Some(1u32), "abc", [1u16, 2], 7u8), &mut b);
+    assert!(!eq::<Message>(&a, &b).unwrap());
+    assert!(eq::<Message>(&a, &a).unwrap());
+
+    // Equality under the formula matches equality of deserialized values,
+    // even when bytes differ.
+    let mut tolerated = 0;
+    for idx in 0..a.len() {
+        let mut b = a.clone();
+        b[idx] ^= 0x10;
+        let Ok((read, _)) = read_packet::<Message, MessageValue>(&b) else {
+            continue;
+        };
+        assert_eq!(eq::<Message>(&a, &b).unwrap(), read == value);
+        if read == value {
+            tolerated += 1;
+        }
+    }
+    // Padding of absent option value is ignored.
+    assert!(tolerated >= 4);
+}
+
+#[test]
+fn test_lazy_read_into() {
+    use crate::{deserialize::DeserializeError, Ref};
+
+    let mut buffer = [0u8; 256];
+
+    let (size, root) = serialize::<[u16], _>([1u16, 2, 3], &mut buffer).unwrap();
+    let lazy = deserialize_with_size::<[u16], Lazy<[u16]>>(&buffer[..size], root).unwrap();
+
+    let mut out = [9u16; 3];
+    assert_eq!(lazy.read_into(&mut out).unwrap(), 3);
+    assert_eq!(out, [1, 2, 3]);
+
+    let mut out = [9u16; 5];
+    assert_eq!(lazy.read_into(&mut out).unwrap(), 3);
+    assert_eq!(out, [1, 2, 3, 9, 9]);
+
+    assert!(matches!(
+        lazy.read_into(&mut [0u16; 2]),
+        Err(DeserializeError::WrongLength(_))
+    ));
+    assert!(lazy.read_into::<u16>(&mut []).is_err());
+
+    let (size, root) = serialize::<[Ref<str>], _>(["a", "bc"], &mut buffer).unwrap();
+    let lazy =
+        deserialize_with_size::<[Ref<str>], Lazy<[Ref<str>]>>(&buffer[..size], root).unwrap();
+
+    let mut out = [""; 3];
+    assert_eq!(lazy.read_into(&mut out).unwrap(), 2);
+    assert_eq!(out, ["a", "bc", ""]);
+    assert!(lazy.read_into(&mut [""; 1]).is_err());
+
+    let (size, root) = serialize::<[u16], _>([0u16; 0], &mut buffer).unwrap();
+    let lazy = deserialize_with_size::<[u16], Lazy<[u16]>>(&buffer[..size], root).unwrap();
+    assert_eq!(lazy.read_into::<u16>(&mut []).unwrap(), 0);
+}
+
+#[test]
+fn test_for_each() {
+    use core::ops::ControlFlow;
+
+    use crate::{for_each, visit_packet, write_packet, Ref, Sink, SortedMap};
+
+    struct FirstOver(u32, Option<u32>);
+
+    impl Sink<u32> for FirstOver {
+        fn accept(&mut self, item: u32) -> ControlFlow<()> {
+            if item > self.0 {
+                self.1 = Some(item);
+                return ControlFlow::Break(());
+            }
+            ControlFlow::Continue(())
+        }
+    }
+
+    let mut buffer = [0u8; 1024];
+
+    let size = write_packet::<[u32], _>([3u32, 1, 4, 1, 5, 9, 2, 6], &mut buffer).unwrap();
+    let mut sum = 0;
+    assert_eq!(
+        for_each::<[u32], u32>(&buffer[..size], |item| sum += item).unwrap(),
+        size
+    );
+    assert_eq!(sum, 31);
+
+    let mut sink = FirstOver(4, None);
+    visit_packet::<[u32], u32, _>(&buffer[..size], &mut sink).unwrap();
+    assert_eq!(sink.1, Some(5));
+
+    let size = write_packet::<[Ref<str>], _>(["a", "bc", "def"], &mut buffer).unwrap();
+    let mut total = 0;
+    for_each::<[Ref<str>], &str>(&buffer[..size], |item| total += item.len()).unwrap();
+    assert_eq!(total, 6);
+
+    let size = write_packet::<SortedMap<u8, u16>, _>([(1u8, 10u16), (2, 20)], &mut buffer).unwrap();
+    let mut entries = [(0u8, 0u16); 2];
+    let mut n = 0;
+    for_each::<SortedMap<u8, u16>, (u8, u16)>(&buffer[..size], |entry| {
+        entries[n] = entry;
+        n += 1;
+    })
+    .unwrap();
+    assert_eq!(entries, [(1, 10), (2, 20)]);
+
+    let size = write_packet::<[u32], _>([1u32, 2], &mut buffer).unwrap();
+    assert!(for_each::<[u32], u32>(&buffer[..size - 1], |_| {}).is_err());
+}
+
+#[cfg(all(feature = "alloc", feature = "derive"))]
+#[test]
+fn test_lazy_projections() {
+    use alkahest_proc::alkahest;
+    use alloc::{string::String, vec, vec::Vec};
+
+    use crate::{read_packet, write_packet_to_vec, Ref};
+
+    #[alkahest(Formula)]
+    struct Inner {
+        id: u32,
+        tags: Ref<[u16]>,
+    }
+
+    #[alkahest(Serialize<Inner>)]
+    struct InnerValue {
+        id: u32,
+        tags: Vec<u16>,
+    }
+
+    #[alkahest(Formula)]
+    struct Outer {
+        label: String,
+        name: Ref<str>,
+        inner: Inner,
+        ids: [u64],
+    }
+
+    #[alkahest(Serialize<Outer>)]
+    struct OuterValue {
+        label: String,
+        name: &'static str,
+        inner: InnerValue,
+        ids: Vec<u64>,
+    }
+
+    #[alkahest(Formula)]
+    struct Pair<T>(T, u8);
+
+    #[alkahest(Serialize<Pair<u32>>)]
+    struct PairValue(u32, u8);
+
+    let mut buffer = Vec::new();
+    let size = write_packet_to_vec::<Outer, _>(
+        OuterValue {
+            label: "label".into(),
+            name: "outer",
+            inner: InnerValue {
+                id: 7,
+                tags: vec![1, 2, 3],
+            },
+            ids: vec![10, 20],
+        },
+        &mut buffer,
+    );
+
+    let (lazy, _) = read_packet::<Outer, Lazy<Outer>>(&buffer[..size]).unwrap();
+
+    let name: Lazy<str> = lazy.project_name().unwrap();
+    assert_eq!(name.get::<&str>().unwrap(), "outer");
+
+    let inner = lazy.project_inner().unwrap();
+    assert_eq!(inner.project_id().unwrap().get::<u32>().unwrap(), 7);
+    let tags = inner.project_tags().unwrap();
+    assert_eq!(tags.get::<Vec<u16>>().unwrap(), [1, 2, 3]);
+
+    let ids = lazy.project_ids().unwrap();
+    assert_eq!(ids.len(), 2);
+    assert_eq!(ids.get::<Vec<u64>>().unwrap(), [10, 20]);
+
+    let size = write_packet_to_vec::<Pair<u32>, _>(PairValue(5, 6), &mut buffer);
+    let (lazy, _) = read_packet::<Pair<u32>, Lazy<Pair<u32>>>(&buffer[..size]).unwrap();
+    assert_eq!(lazy.project_0().unwrap().get::<u32>().unwrap(), 5);
+    assert_eq!(lazy.project_1().unwrap().get::<u8>().unwrap(), 6);
+}
+
+#[cfg(all(feature = "alloc", feature = "derive"))]
+#[test]
+fn test_lazy_mut() {
+    use alkahest_proc::alkahest;
+    use alloc::{string::String, vec::Vec};
+
+    use crate::{read_packet, read_packet_mut, write_packet_to_vec, Fixed, LazyMut, Ref};
+
+    #[alkahest(Formula, Serialize, Deserialize)]
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    struct Counters {
+        id: u64,
+        hits: u32,
+        flags: [bool; 2],
+    }
+
+    // Derives for structs with non-fixed fields still compile.
+    #[alkahest(Formula)]
+    struct Entry {
+        counters: Counters,
+        name: Ref<str>,
+    }
+
+    #[alkahest(Formula)]
+    struct Named {
+        label: String,
+        id: u32,
+    }
+
+    fn assert_fixed<F: Fixed>() {}
+    assert_fixed::<Counters>();
+    assert_fixed::<(u8, [Counters; 2])>();
+
+    let value = Counters {
+        id: 1,
+        hits: 10,
+        flags: [false, true],
+    };
+
+    let mut buffer = Vec::new();
+    let size = write_packet_to_vec::<Counters, _>(value, &mut buffer);
+
+    let (mut view, read) = read_packet_mut::<Counters>(&mut buffer[..size]).unwrap();
+    assert_eq!(read, size);
+    assert_eq!(view.get::<Counters>().unwrap(), value);
+
+    view.project_id().set(42u64);
+    view.project_hits().update(|hits: u32| hits + 1).unwrap();
+    view.project_flags().at(0).set(true);
+
+    let (read, _) = read_packet::<Counters, Counters>(&buffer[..size]).unwrap();
+    assert_eq!(
+        read,
+        Counters {
+            id: 42,
+            hits: 11,
+            flags: [true, true],
+        }
+    );
+
+    let mut bytes = [0u8; 12];
+    let mut view = LazyMut::<(u32, u64)>::new(&mut bytes).unwrap();
+    let (mut a, mut b) = view.fields();
+    a.set(1u32);
+    b.set(2u64);
+    assert_eq!(view.get::<(u32, u64)>().unwrap(), (1, 2));
+    assert!(LazyMut::<(u32, u64)>::new(&mut [0u8; 11]).is_err());
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_serialized_size_exact() {
+    use alloc::{string::String, vec, vec::Vec};
+
+    use crate::{serialized_size_exact, SerIter};
+
+    fn check<F, T>(value: T)
+    where
+        F: Formula + ?Sized,
+        T: Serialize<F> + Clone,
+    {
+        let sizes = serialized_size_exact::<F, T>(value.clone());
+        let mut buffer = vec![0u8; sizes.total() + 16];
+        let (heap, _) = serialize::<F, T>(value, &mut buffer).unwrap();
+        assert_eq!(sizes.total(), heap);
+    }
+
+    check::<[u32], _>(SerIter((0..100u32).filter(|n| n % 7 == 0)));
+    check::<[Ref<str>], _>(SerIter(
+        ["a", "bc", "def"].into_iter().filter(|s| s.len() > 1),
+    ));
+    check::<Vec<String>, _>(vec![String::from("hello"), String::new()]);
+    check::<(u8, [Bytes]), _>((1u8, SerIter([&[1u8, 2][..], &[3][..]].into_iter())));
+}
+
+#[test]
+fn test_peek_header() {
+    use crate::{
+        deserialize::DeserializeError,
+        packet::{peek_header, write_packet, PacketHeader},
+    };
+
+    let mut buffer = [0u8; 64];
+    let size = write_packet::<(u32, [u16]), _>((1u32, [2u16, 3, 4]), &mut buffer).unwrap();
+
+    let header = peek_header::<(u32, [u16])>(&buffer[..size]).unwrap();
+    assert_eq!(
+        header,
+        PacketHeader {
+            address: size,
+            size: 10
+        }
+    );
+    assert!(header.fits(size));
+    assert!(!header.fits(size - 1));
+    assert!(matches!(
+        header.check_fits(size - 1),
+        Err(DeserializeError::OutOfBounds(_))
+    ));
+
+    // Header alone is enough to find out packet size.
+    let header = peek_header::<(u32, [u16])>(&buffer[..1]);
+    assert!(matches!(header, Err(DeserializeError::OutOfBounds(_))));
+
+    // Root value can't exceed the packet.
+    let mut bad = [0u8; 64];
+    let header_size = write_packet::<[u8], _>([0u8; 0], &mut bad).unwrap();
+    bad[header_size - 1] = 0xff;
+    let header = peek_header::<[u8]>(&bad[..header_size]);
+    assert!(matches!(header, Err(DeserializeError::WrongAddress)));
+}
+
+#[test]
+fn test_raw() {
+    use crate::{
+        deserialize::DeserializeError,
+        raw::{deserialize_raw, serialize_raw},
+        serialize::max_size,
+    };
+
+    type Record = (u64, [u16; 3], bool);
+
+    let mut buffer = [0u8; 64];
+    let size = serialize_raw::<Record, _>((7u64, [1u16, 2, 3], true), &mut buffer).unwrap();
+    assert_eq!(size, max_size::<Record>());
+    assert_eq!(size, 15);
+
+    let (value, consumed) = deserialize_raw::<Record, Record>(&buffer).unwrap();
+    assert_eq!(value, (7, [1, 2, 3], true));
+    assert_eq!(consumed, size);
+
+    assert_eq!(
+        serialize_raw::<Record, _>((7u64, [1u16, 2, 3], true), &mut buffer[..size - 1]),
+        Err(BufferExhausted)
+    );
+    assert!(matches!(
+        deserialize_raw::<Record, Record>(&buffer[..size - 1]),
+        Err(DeserializeError::OutOfBounds(_))
+    ));
+}
+
+#[cfg(all(feature = "alloc", feature = "metrics"))]
+#[test]
+fn test_metrics() {
+    use alloc::vec::Vec;
+
+    use crate::{
+        buffer::VecBuffer, metrics::Metrics, packet::write_packet_into, serialize::serialize_into,
+        size::SIZE_STACK, SerIter,
+    };
+
+    let mut output = Vec::new();
+    let mut metrics = Metrics::default();
+    let buffer = VecBuffer::with_metrics(&mut output, &mut metrics);
+    write_packet_into::<(u32, Ref<[u8]>), _, _>((1u32, [1u8, 2, 3]), buffer).unwrap();
+    assert_eq!(metrics.stack, 4 + 2 * SIZE_STACK);
+    assert_eq!(metrics.heap, 3);
+    assert_eq!(metrics.size_hint_misses, 0);
+    assert!(metrics.reallocations > 0);
+
+    // Capacity is reused.
+    let mut metrics = Metrics::default();
+    let buffer = VecBuffer::with_metrics(&mut output, &mut metrics);
+    write_packet_into::<(u32, Ref<[u8]>), _, _>((1u32, [1u8, 2, 3]), buffer).unwrap();
+    assert_eq!(metrics.reallocations, 0);
+
+    let mut output = Vec::new();
+    let mut metrics = Metrics::default();
+    let buffer = VecBuffer::with_metrics(&mut output, &mut metrics);
+    serialize_into::<[u32], _, _>(SerIter((0..10u32).filter(|n| n % 2 == 0)), buffer).unwrap();
+    assert_eq!(metrics.stack, 20);
+    assert_eq!(metrics.heap, 0);
+    assert_eq!(metrics.size_hint_misses, 1);
+}
+
+#[cfg(all(feature = "tracing", feature = "std"))]
+#[test]
+fn test_tracing() {
+    use alloc::sync::Arc;
+    use core::sync::atomic::{AtomicU64, Ordering};
+
+    use tracing::{span, subscriber::with_default, Event, Metadata, Subscriber};
+
+    use crate::{
+        deserialize::deserialize,
+        packet::{read_packet, write_packet},
+    };
+
+    #[derive(Default)]
+    struct Counts {
+        spans: AtomicU64,
+        errors: AtomicU64,
+    }
+
+    struct Counter(Arc<Counts>);
+
+    impl Subscriber for Counter {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, _: &span::Attributes<'_>) -> span::Id {
+            span::Id::from_u64(self.0.spans.fetch_add(1, Ordering::Relaxed) + 1)
+        }
+
+        fn record(&self, _: &span::Id, _: &span::Record<'_>) {}
+
+        fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            if event.fields().any(|field| field.name() == "offset") {
+                self.0.errors.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        fn enter(&self, _: &span::Id) {}
+
+        fn exit(&self, _: &span::Id) {}
+    }
+
+    let counts = Arc::new(Counts::default());
+    with_default(Counter(counts.clone()), || {
+        let mut buffer = [0u8; 64];
+        let size = write_packet::<(u32, [u8]), _>((1u32, [2u8, 3]), &mut buffer).unwrap();
+        read_packet::<(u32, [u8]), (u32, Vec<u8>)>(&buffer[..size]).unwrap();
+        assert_eq!(counts.spans.load(Ordering::Relaxed), 2);
+        assert_eq!(counts.errors.load(Ordering::Relaxed), 0);
+
+        deserialize::<(u32, u32), (u32, u32)>(&[0u8; 5]).unwrap_err();
+        assert_eq!(counts.spans.load(Ordering::Relaxed), 3);
+        assert_eq!(counts.errors.load(Ordering::Relaxed), 1);
+    });
+}
+
+#[cfg(all(feature = "alloc", feature = "derive"))]
+#[test]
+fn test_derive_borrowed() {
+    use alkahest_proc::alkahest;
+
+    use crate::{read_packet, write_packet, Bytes};
+
+    #[alkahest(Formula)]
+    struct MsgFormula {
+        name: Ref<str>,
+        data: Bytes,
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    #[alkahest(Serialize<MsgFormula>, Deserialize<MsgFormula>)]
+    struct Msg<'a> {
+        name: &'a str,
+        data: &'a [u8],
+    }
+
+    #[alkahest(Formula)]
+    struct PairFormula(Ref<str>, Ref<str>);
+
+    #[derive(Debug, PartialEq, Eq)]
+    #[alkahest(Serialize<PairFormula>, Deserialize<PairFormula>)]
+    struct Pair<'a, 'b>(&'a str, &'b str);
+
+    #[alkahest(Formula)]
+    enum EventFormula {
+        Ping,
+        Text { text: Ref<str> },
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    #[alkahest(Serialize<EventFormula>, Deserialize<EventFormula>)]
+    enum Event<'a> {
+        Ping,
+        Text { text: &'a str },
+    }
+
+    let mut buffer = [0u8; 64];
+
+    let size = write_packet::<MsgFormula, _>(
+        Msg {
+            name: "sensor",
+            data: &[1, 2, 3],
+        },
+        &mut buffer,
+    )
+    .unwrap();
+    let (msg, _) = read_packet::<MsgFormula, Msg>(&buffer[..size]).unwrap();
+    assert_eq!(
+        msg,
+        Msg {
+            name: "sensor",
+            data: &[1, 2, 3]
+        }
+    );
+
+    let size = write_packet::<PairFormula, _>(Pair("a", "b"), &mut buffer).unwrap();
+    let (pair, _) = read_packet::<PairFormula, Pair>(&buffer[..size]).unwrap();
+    assert_eq!(pair, Pair("a", "b"));
+
+    let size = write_packet::<EventFormula, _>(Event::Text { text: "hi" }, &mut buffer).unwrap();
+    let (event, _) = read_packet::<EventFormula, Event>(&buffer[..size]).unwrap();
+    assert_eq!(event, Event::Text { text: "hi" });
+
+    let size = write_packet::<EventFormula, _>(Event::Ping, &mut buffer).unwrap();
+    let (event, _) = read_packet::<EventFormula, Event>(&buffer[..size]).unwrap();
+    assert_eq!(event, Event::Ping);
+}
+
+#[cfg(all(feature = "lock", feature = "derive"))]
+#[test]
+fn test_formula_lock() {
+    use alkahest_proc::alkahest;
+
+    use crate::{check_formula_lock, fingerprint, formula_lock, register_formula, LockError};
+
+    #[alkahest(Formula)]
+    struct LockedRecord {
+        id: u64,
+        name: Ref<str>,
+    }
+
+    #[alkahest(Formula)]
+    struct LockedPair<T> {
+        a: T,
+        b: T,
+    }
+
+    register_formula!(LockedPair<u16>);
+
+    let lock = formula_lock();
+    let record = format!(
+        "alkahest::tests::LockedRecord {:016x}",
+        fingerprint::<LockedRecord>()
+    );
+    assert!(lock.lines().any(|line| line == record));
+    let pair = format!(
+        "alkahest::tests::LockedPair<u16> {:016x}",
+        fingerprint::<LockedPair<u16>>()
+    );
+    assert!(lock.lines().any(|line| line == pair));
+
+    let path = std::env::temp_dir().join(format!("alkahest-{}.lock", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    // Created on first check.
+    check_formula_lock(&path).unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), lock);
+    check_formula_lock(&path).unwrap();
+
+    let changed = lock.replace(&record, "alkahest::tests::LockedRecord 0000000000000000");
+    std::fs::write(&path, changed).unwrap();
+    match check_formula_lock(&path) {
+        Err(LockError::Mismatch { removed, added }) => {
+            assert_eq!(removed, ["alkahest::tests::LockedRecord 0000000000000000"]);
+            assert_eq!(added, [record]);
+        }
+        result => panic!("unexpected result {result:?}"),
+    }
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[cfg(all(feature = "alloc", feature = "derive"))]
+#[test]
+fn test_layout() {
+    use alkahest_proc::alkahest;
+    use alloc::format;
+
+    use crate::{advanced::FixedUsizeType, schema::Schema};
+
+    #[alkahest(Formula, Serialize)]
+    enum Command {
+        Stop,
+        Move { x: i32, y: i32 },
+    }
+
+    #[alkahest(Formula)]
+    struct Frame {
+        id: u64,
+        command: Command,
+        name: Ref<str>,
+    }
+
+    #[alkahest(Formula)]
+    struct Message {
+        kind: u8,
+        body: Vlq,
+        tags: [u8],
+    }
+
+    let layout = Schema::of::<Frame>().to_layout();
+    let fixed = size_of::<FixedUsizeType>();
+    let frame = 8 + 12 + 2 * fixed;
+    assert!(layout.starts_with(&format!(
+        "### Frame\n\nStack size: {frame} bytes, with heap references.\n\n\
+         | Field | Offset | Size | Formula | Notes |\n\
+         |---|---|---|---|---|\n\
+         | id | {} | 8 | `u64` | little-endian |\n\
+         | command | {} | 12 | `Command` | padded to 12 bytes; see `Command` |\n\
+         | name | 0 | {} | `Ref<str>` | {fixed}-byte heap address and {fixed}-byte size of the value |\n",
+        frame - 8,
+        2 * fixed,
+        2 * fixed,
+    )));
+    assert!(layout.contains(
+        "### Command\n\nStack size: up to 12 bytes, no heap.\n\n\
+         | Field | Offset | Size | Formula | Notes |\n\
+         |---|---|---|---|---|\n\
+         | tag | end-4 | 4 | `u32` | variant tag: Stop = 0, Move = 1 |\n\
+         | Move.x | end-8 | 4 | `i32` | little-endian |\n\
+         | Move.y | end-12 | 4 | `i32` | little-endian |\n"
+    ));
+
+    let layout = Schema::of::<Message>().to_layout();
+    assert!(layout.contains(&format!(
+        "| kind | end-1 | 1 | `u8` | little-endian |\n\
+         | body size | end-{} | {fixed} | `u{}` | stack size of `body` |\n\
+         | body | ..end-{} | variable | `vlq` | variable-length quantity |\n\
+         | tags | - | rest | `[u8]` | takes remaining stack; elements in reverse order |\n",
+        1 + fixed,
+        fixed * 8,
+        1 + fixed,
+    )));
+
+    // Offsets match serialized layout.
+    let mut buffer = [0u8; 12];
+    serialize::<Command, _>(Command::Move { x: 2, y: 3 }, &mut buffer).unwrap();
+    assert_eq!(buffer[8..], 1u32.to_le_bytes());
+    assert_eq!(buffer[4..8], 2i32.to_le_bytes());
+    assert_eq!(buffer[..4], 3i32.to_le_bytes());
+}
+
+#[cfg(all(feature = "std", feature = "derive"))]
+#[test]
+fn test_assert_formula() {
+    use alkahest_proc::alkahest;
+
+    use crate::{assert_bounded, assert_exact_size, assert_heapless};
+
+    #[alkahest(Formula)]
+    struct Telemetry {
+        id: u32,
+        readings: [i16; 4],
+        flag: Option<bool>,
+    }
+
+    #[alkahest(Formula)]
+    struct Named {
+        id: u32,
+        name: Ref<str>,
+    }
+
+    const _: () = assert_heapless::<Telemetry>();
+    const TELEMETRY: usize = assert_bounded::<Telemetry>();
+    assert_eq!(TELEMETRY, 4 + 8 + 2);
+    assert_eq!(assert_exact_size::<([i16; 4], u32)>(), 12);
+
+    let named = std::panic::catch_unwind(assert_heapless::<Named>);
+    assert!(named.is_err());
+    let unbounded = std::panic::catch_unwind(assert_bounded::<(u32, [u8])>);
+    assert!(unbounded.is_err());
+    let inexact = std::panic::catch_unwind(assert_exact_size::<Telemetry>);
+    assert!(inexact.is_err());
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_bounded_unknown_length() {
+    use alloc::{string::String, vec::Vec};
+
+    use crate::{
+        bounded::Bounded,
+        packet::{read_packet, write_packet_to_vec},
+    };
+
+    type Message = (u8, Bounded<[As<str>], 4>, Bounded<[u32], 8>, Vec<u16>);
+
+    // Iterators that don't know their length until exhausted.
+    let mut words = ["alpha", "beta", "gamma"].into_iter();
+    let names = core::iter::from_fn(move || words.next());
+    let mut n = 0u32;
+    let squares = core::iter::from_fn(move || {
+        n += 1;
+        (n <= 5).then_some(n * n)
+    });
+    let evens = (0u16..10).filter(|n| n % 2 == 0);
+    assert_eq!(Iterator::size_hint(&names), (0, None));
+
+    let mut buffer = Vec::new();
+    let size = write_packet_to_vec::<Message, _>((1u8, names, squares, evens), &mut buffer);
+    let (value, _) =
+        read_packet::<Message, (u8, Vec<String>, Vec<u32>, Vec<u16>)>(&buffer[..size]).unwrap();
+    assert_eq!(value.1, ["alpha", "beta", "gamma"]);
+    assert_eq!(value.2, [1, 4, 9, 16, 25]);
+    assert_eq!(value.3, [0, 2, 4, 6, 8]);
+
+    // Same bytes as with slices of known length.
+    let mut expected = Vec::new();
+    write_packet_to_vec::<Message, _>(
+        (
+            1u8,
+            ["alpha", "beta", "gamma"],
+            [1u32, 4, 9, 16, 25],
+            [0u16, 2, 4, 6, 8],
+        ),
+        &mut expected,
+    );
+    assert_eq!(buffer, expected);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_sizes_split() {
+    use alloc::{string::String, vec, vec::Vec};
+
+    use crate::{serialize::serialized_size_exact, size_hint, Sizes};
+
+    type Record = (u16, Vec<u32>, String);
+    let value = (3u16, vec![1u32, 2, 3], String::from("abc"));
+
+    let hint = size_hint::<Record, _>(&value).unwrap();
+    let exact = serialized_size_exact::<Record, _>(&value);
+    assert_eq!(hint, exact);
+    assert_eq!(exact.heap, 12 + 3);
+
+    let mut buffer = [0u8; 128];
+    let (size, stack) = serialize::<Record, _>(&value, &mut buffer).unwrap();
+    assert_eq!((size, stack), (exact.total(), exact.stack));
+
+    let (heap, stack) = exact.split(&buffer[..size]).unwrap();
+    assert_eq!(heap.len(), exact.heap);
+    assert_eq!(stack.len(), exact.stack);
+    assert_eq!(stack[stack.len() - 2..], 3u16.to_le_bytes());
+    assert!(exact.split(&buffer[..size - 1]).is_none());
+
+    assert_eq!(
+        Sizes::with_heap(4) + Sizes::with_stack(2),
+        Sizes { heap: 4, stack: 2 }
+    );
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_vec_buffer_reuse() {
+    use alloc::{string::String, vec, vec::Vec};
+
+    use crate::{
+        advanced::{Buffer, VecBuffer},
+        packet::{read_packet, write_packet_into, write_packet_to_vec},
+    };
+
+    type Message = (u32, Vec<u16>, String);
+
+    let mut buffer = VecBuffer::with_capacity(16);
+    assert!(buffer.is_empty());
+
+    for n in 0..20u16 {
+        let value = (
+            u32::from(n),
+            (0..n).collect::<Vec<_>>(),
+            "x".repeat(n.into()),
+        );
+
+        buffer.clear();
+        let size = write_packet_into::<Message, _, _>(&value, buffer.reborrow()).unwrap();
+        assert_eq!(buffer.len(), size);
+
+        let mut expected = Vec::new();
+        write_packet_to_vec::<Message, _>(&value, &mut expected);
+        assert_eq!(buffer.as_slice(), &expected[..size]);
+
+        let (de, _) = read_packet::<Message, (u32, Vec<u16>, String)>(buffer.as_slice()).unwrap();
+        assert_eq!(de, value);
+    }
+
+    let last = buffer.as_slice().to_vec();
+    assert_eq!(buffer.into_inner(), last);
+
+    // Borrowed vector keeps the data and can be truncated.
+    let mut output = vec![0xff; 64];
+    let mut buffer = VecBuffer::from(&mut output);
+    let size = write_packet_into::<u32, _, _>(7u32, buffer.reborrow()).unwrap();
+    buffer.truncate(size);
+    assert_eq!(buffer.len(), size);
+    assert_eq!(read_packet::<u32, u32>(&output).unwrap().0, 7);
+}
+
+#[cfg(all(feature = "ffi", feature = "derive"))]
+#[test]
+#[allow(unsafe_code)]
+fn test_ffi() {
+    use alkahest_proc::alkahest;
+    use core::ffi::c_void;
+
+    use crate::{
+        envelope::{Envelope, Enveloped, Tagged},
+        ffi::{
+            alkahest_packet_tag, alkahest_read_packet, alkahest_value_size, alkahest_write_packet,
+            register_ffi, ALKAHEST_BUFFER_TOO_SMALL, ALKAHEST_DESERIALIZE_FAILED,
+            ALKAHEST_NULL_POINTER, ALKAHEST_OK, ALKAHEST_UNKNOWN_TAG, ALKAHEST_WRONG_TAG,
+        },
+        packet::write_packet_to_vec,
+    };
+
+    #[alkahest(Formula, Serialize, Deserialize)]
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    #[repr(C)]
+    struct Position {
+        id: u32,
+        x: f32,
+        y: f32,
+    }
+
+    impl Tagged for Position {
+        const TAG: u32 = 0xFF1;
+    }
+
+    #[alkahest(Formula, Serialize, Deserialize)]
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    #[repr(C)]
+    struct Ack {
+        id: u32,
+    }
+
+    impl Tagged for Ack {
+        const TAG: u32 = 0xFF2;
+    }
+
+    register_ffi::<Position, Position>();
+    register_ffi::<Ack, Ack>();
+
+    let position = Position {
+        id: 3,
+        x: 1.5,
+        y: -2.0,
+    };
+    let value = core::ptr::addr_of!(position).cast::<c_void>();
+
+    unsafe {
+        let mut size = 0;
+        assert_eq!(alkahest_value_size(0xFF1, &mut size), ALKAHEST_OK);
+        assert_eq!(size, core::mem::size_of::<Position>());
+        assert_eq!(alkahest_value_size(0xFF3, &mut size), ALKAHEST_UNKNOWN_TAG);
+
+        // Required size is reported for too small buffer.
+        let mut written = 0;
+        let code = alkahest_write_packet(0xFF1, value, core::ptr::null_mut(), 0, &mut written);
+        assert_eq!(code, ALKAHEST_BUFFER_TOO_SMALL);
+
+        let mut output = vec![0u8; written];
+        let code = alkahest_write_packet(
+            0xFF1,
+            value,
+            output.as_mut_ptr(),
+            output.len(),
+            &mut written,
+        );
+        assert_eq!(code, ALKAHEST_OK);
+        assert_eq!(written, output.len());
+
+        // Packet is the same as written by Rust side.
+        let mut expected = Vec::new();
+        let size = write_packet_to_vec::<Envelope, _>(
+            Enveloped::<Position, _>::new(position),
+            &mut expected,
+        );
+        assert_eq!(output, expected[..size]);
+
+        let mut tag = 0;
+        assert_eq!(
+            alkahest_packet_tag(output.as_ptr(), output.len(), &mut tag),
+            ALKAHEST_OK
+        );
+        assert_eq!(tag, Position::TAG);
+
+        let mut decoded = Position {
+            id: 0,
+            x: 0.0,
+            y: 0.0,
+        };
+        let out = core::ptr::addr_of_mut!(decoded).cast::<c_void>();
+        let mut consumed = 0;
+        let code = alkahest_read_packet(0xFF1, output.as_ptr(), output.len(), out, &mut consumed);
+        assert_eq!(code, ALKAHEST_OK);
+        assert_eq!(consumed, output.len());
+        assert_eq!(decoded, position);
+
+        let mut ack = Ack { id: 0 };
+        let out = core::ptr::addr_of_mut!(ack).cast::<c_void>();
+        let code = alkahest_read_packet(0xFF2, output.as_ptr(), output.len(), out, &mut consumed);
+        assert_eq!(code, ALKAHEST_WRONG_TAG);
+
+        let out = core::ptr::addr_of_mut!(decoded).cast::<c_void>();
+        let code = alkahest_read_packet(0xFF1, output.as_ptr(), 3, out, &mut consumed);
+        assert_eq!(code, ALKAHEST_DESERIALIZE_FAILED);
+
+        let code = alkahest_read_packet(
+            0xFF1,
+            output.as_ptr(),
+            output.len(),
+            out,
+            core::ptr::null_mut(),
+        );
+        assert_eq!(code, ALKAHEST_NULL_POINTER);
+    }
+}
+
+#[test]
+fn test_error_codes() {
+    use alloc::{format, vec::Vec};
+
+    use crate::{
+        buffer::BufferExhausted,
+        deserialize::{DeserializeError, ErrorDetails},
+        packet::{read_packet, write_packet},
+        serialize::BufferSizeRequired,
+    };
+
+    let invalid_utf8 = alloc::vec![0xff];
+    let errors = [
+        DeserializeError::OutOfBounds(ErrorDetails::new()),
+        DeserializeError::WrongAddress,
+        DeserializeError::WrongLength(ErrorDetails::new()),
+        DeserializeError::InvalidUsize(0),
+        DeserializeError::InvalidIsize(0),
+        DeserializeError::WrongVariant(0, ErrorDetails::new()),
+        DeserializeError::NonUtf8(core::str::from_utf8(&invalid_utf8).unwrap_err()),
+        DeserializeError::IntegerOverflow,
+        DeserializeError::Incompatible,
+        DeserializeError::OverlappingReference,
+        DeserializeError::NonCanonical,
+        DeserializeError::InvalidBool(2),
+        DeserializeError::InvalidChar(0xD800),
+        DeserializeError::ChecksumMismatch,
+        DeserializeError::Unsorted,
+        DeserializeError::NonAscii,
+        DeserializeError::AuthenticationFailed,
+        DeserializeError::InvalidSignature,
+    ];
+
+    // Codes are stable and follow declaration order.
+    let codes = errors
+        .iter()
+        .map(DeserializeError::code)
+        .collect::<Vec<_>>();
+    assert_eq!(codes, (1..=18).collect::<Vec<u16>>());
+
+    assert_eq!(
+        format!("{}", DeserializeError::WrongVariant(7, ErrorDetails::new())),
+        "invalid enum variant 7"
+    );
+    assert_eq!(
+        format!("{}", DeserializeError::InvalidChar(0xD800)),
+        "invalid `char` value 0xd800"
+    );
+
+    let mut buffer = [0u8; 3];
+    let exhausted = write_packet::<u64, _>(1u64, &mut buffer).unwrap_err();
+    assert_eq!(exhausted.code(), 256);
+    assert_eq!(
+        BufferExhausted::from(BufferSizeRequired { required: 8 }),
+        exhausted
+    );
+
+    let err = DeserializeError::from(exhausted);
+    assert_eq!(err.code(), 1);
+    let err = DeserializeError::from(BufferSizeRequired { required: 8 });
+    assert!(matches!(err, DeserializeError::OutOfBounds(_)));
+    #[cfg(feature = "debug-errors")]
+    assert_eq!(err.details().unwrap().expected(), Some(8));
+
+    let err = read_packet::<u64, u64>(&buffer).unwrap_err();
+    assert!(format!("{err}").starts_with("input is shorter than expected"));
 }
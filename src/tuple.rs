@@ -1,15 +1,22 @@
 use crate::{
     buffer::Buffer,
+    descriptor::{Descriptor, DescriptorKind, LazyDescriptor},
     deserialize::{Deserialize, DeserializeError, Deserializer},
     formula::{sum_size, BareFormula, Formula},
-    serialize::{field_size_hint, write_field, Serialize, SerializeRef, Sizes},
-    size::SIZE_STACK,
+    serialize::{
+        field_size_hint, field_size_hint_unknown, write_field, Serialize, SerializeRef, Sizes,
+    },
 };
 
 impl Formula for () {
     const MAX_STACK_SIZE: Option<usize> = Some(0);
     const EXACT_SIZE: bool = true;
     const HEAPLESS: bool = true;
+
+    #[inline(always)]
+    fn describe() -> Descriptor {
+        Descriptor::new::<()>(DescriptorKind::Tuple(&[]))
+    }
 }
 
 impl BareFormula for () {}
@@ -90,6 +97,13 @@ macro_rules! formula_serialize {
 
             const EXACT_SIZE: bool = $(<$a as Formula>::EXACT_SIZE &&)* <$at as Formula>::EXACT_SIZE;
             const HEAPLESS: bool = $(<$a as Formula>::HEAPLESS &&)* <$at as Formula>::HEAPLESS;
+
+            #[inline(always)]
+            fn describe() -> Descriptor {
+                Descriptor::new::<Self>(DescriptorKind::Tuple(const {
+                    &[$(LazyDescriptor::of::<$a>(),)* LazyDescriptor::of::<$at>()]
+                }))
+            }
         }
 
         impl<$($a,)* $at> BareFormula for ($($a,)* $at,)
@@ -125,12 +139,14 @@ macro_rules! formula_serialize {
             #[inline]
             fn size_hint(&self) -> Option<Sizes> {
                 #![allow(non_snake_case, unused_mut)]
+                $(
+                    if field_size_hint_unknown::<$a>(false) {
+                        return None;
+                    }
+                )*
                 let mut sizes = Sizes::ZERO;
                 let ($($b,)* $bt,) = self;
                 $(
-                    if $a::MAX_STACK_SIZE.is_none() {
-                        sizes.add_stack(SIZE_STACK);
-                    }
                     sizes += field_size_hint::<$a>($b, false)?;
                 )*
                 sizes += field_size_hint::<$at>($bt, true)?;
@@ -164,12 +180,14 @@ macro_rules! formula_serialize {
             #[inline]
             fn size_hint(&self) -> Option<Sizes> {
                 #![allow(non_snake_case, unused_mut)]
+                $(
+                    if field_size_hint_unknown::<$a>(false) {
+                        return None;
+                    }
+                )*
                 let mut sizes = Sizes::ZERO;
                 let ($($b,)* $bt,) = self;
                 $(
-                    if $a::MAX_STACK_SIZE.is_none() {
-                        sizes.add_stack(SIZE_STACK);
-                    }
                     sizes += field_size_hint::<$a>(&$b, false)?;
                 )*
 
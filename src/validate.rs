@@ -0,0 +1,237 @@
+//!
+//! This module provides validation of references in packets.
+//!
+
+use crate::{
+    deserialize::{Deserialize, DeserializeError, Deserializer, ErrorDetails},
+    formula::{reference_size, BareFormula, Formula},
+    packet::read_packet,
+    schema::{Schema, SchemaField, SchemaKind},
+    value::{node, packet_deserializer, slice_elements},
+};
+
+/// Part of the input where referenced data may be placed.
+struct Region {
+    /// Lowest address referenced data may start at.
+    /// Moves up after each reference, so that data of sibling
+    /// references does not overlap.
+    floor: usize,
+
+    /// Highest address referenced data may end at.
+    /// Start of the stack of the value that holds references.
+    ceil: usize,
+}
+
+/// Checks that references in the packet do not alias
+/// and that strings are valid UTF-8.
+/// Returns number of bytes the packet occupies.
+///
+/// Serializer places referenced data of each value below its stack,
+/// data of consecutive references in increasing order without overlap.
+/// Malicious packet may instead make several references point to the same bytes,
+/// so that they are decoded many times, or point into the stack of a value.
+/// This function walks the packet following the layout of formula `F`
+/// and rejects such packets, so that decoding work is bounded by the packet size.
+///
+/// Data under formulas that do not describe their structure is not checked.
+///
+/// # Errors
+///
+/// Returns `DeserializeError::OverlappingReference` if referenced data
+/// overlaps with other referenced data or the stack that holds the reference.
+/// Returns `DeserializeError::NonUtf8` if a string is not valid UTF-8.
+/// Returns other `DeserializeError` if the packet is malformed.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// let mut buffer = Vec::new();
+/// let size = write_packet_to_vec::<[Ref<str>], _>(["a", "b"], &mut buffer);
+/// assert_eq!(validate_packet::<[Ref<str>]>(&buffer[..size]).unwrap(), size);
+/// ```
+pub fn validate_packet<F>(input: &[u8]) -> Result<usize, DeserializeError>
+where
+    F: Formula + ?Sized,
+{
+    let schema = Schema::of::<F>();
+    let (de, address) = packet_deserializer(&schema, input)?;
+
+    let mut region = Region {
+        floor: reference_size::<F>(),
+        ceil: address - de.stack(),
+    };
+    walk(&schema, 0, de, &mut region)?;
+    Ok(address)
+}
+
+/// Reads packet with value from the input after checking it with [`validate_packet`].
+/// Returns deserialized value and number of bytes consumed.
+///
+/// Use for untrusted input.
+///
+/// # Errors
+///
+/// Returns `DeserializeError::OverlappingReference` if references in the packet alias.
+/// Returns other `DeserializeError` if deserialization fails.
+#[inline]
+pub fn read_packet_validated<'de, F, T>(input: &'de [u8]) -> Result<(T, usize), DeserializeError>
+where
+    F: Formula + ?Sized,
+    T: Deserialize<'de, F>,
+{
+    validate_packet::<F>(input)?;
+    read_packet::<F, T>(input)
+}
+
+/// Deserializer for any formula that discards the value
+/// after checking that it is well-formed.
+///
+/// Unlike [`Skip`](crate::Skip), that ignores the value entirely,
+/// this walks the value following the layout of the formula
+/// and checks sizes, enum tags, UTF-8 of strings and that references
+/// point below the stack of the value,
+/// so that selective decoders still reject corrupt messages.
+///
+/// Data under formulas that do not describe their structure is not checked.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// let mut buffer = Vec::new();
+/// let size = write_packet_to_vec::<(u32, Ref<str>), _>((1u32, "abc"), &mut buffer);
+///
+/// let ((id, SkipValidated), _) =
+///     read_packet::<(u32, Ref<str>), (u32, SkipValidated)>(&buffer[..size]).unwrap();
+/// assert_eq!(id, 1);
+///
+/// let pos = buffer.windows(3).position(|w| w == b"abc").unwrap();
+/// buffer[pos] = 0xff;
+/// assert!(read_packet::<(u32, Ref<str>), (u32, Skip)>(&buffer[..size]).is_ok());
+/// assert!(matches!(
+///     read_packet::<(u32, Ref<str>), (u32, SkipValidated)>(&buffer[..size]),
+///     Err(DeserializeError::NonUtf8(_)),
+/// ));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SkipValidated;
+
+impl<'de, F> Deserialize<'de, F> for SkipValidated
+where
+    F: BareFormula + ?Sized,
+{
+    #[inline]
+    fn deserialize(de: Deserializer<'de>) -> Result<Self, DeserializeError> {
+        let schema = Schema::of::<F>();
+        let mut region = Region {
+            floor: 0,
+            ceil: de.input().len() - de.stack(),
+        };
+        walk(&schema, 0, de, &mut region)?;
+        Ok(SkipValidated)
+    }
+
+    #[inline]
+    fn deserialize_in_place(&mut self, de: Deserializer<'de>) -> Result<(), DeserializeError> {
+        *self = <Self as Deserialize<'de, F>>::deserialize(de)?;
+        Ok(())
+    }
+}
+
+fn walk_field(
+    schema: &Schema,
+    idx: usize,
+    de: &mut Deserializer,
+    last: bool,
+    region: &mut Region,
+) -> Result<(), DeserializeError> {
+    let field = node(schema, idx)?;
+    let sub = de.read_field_with(field.max_stack_size, field.exact_size, last)?;
+    walk(schema, idx, sub, region)
+}
+
+fn walk_fields(
+    schema: &Schema,
+    fields: &[SchemaField],
+    de: &mut Deserializer,
+    region: &mut Region,
+) -> Result<(), DeserializeError> {
+    let len = fields.len();
+    for (idx, field) in fields.iter().enumerate() {
+        walk_field(schema, field.formula, de, idx + 1 == len, region)?;
+    }
+    Ok(())
+}
+
+fn walk(
+    schema: &Schema,
+    idx: usize,
+    mut de: Deserializer,
+    region: &mut Region,
+) -> Result<(), DeserializeError> {
+    match &node(schema, idx)?.kind {
+        SchemaKind::Opaque
+        | SchemaKind::Bool
+        | SchemaKind::Unsigned(_)
+        | SchemaKind::Signed(_)
+        | SchemaKind::Float(_)
+        | SchemaKind::Vlq
+        | SchemaKind::Bytes => Ok(()),
+        SchemaKind::Str => match core::str::from_utf8(de.read_all_bytes()) {
+            Ok(_) => Ok(()),
+            Err(error) => Err(DeserializeError::NonUtf8(error)),
+        },
+        SchemaKind::Array(elem, len) => {
+            for _ in 0..*len {
+                walk_field(schema, *elem, &mut de, false, region)?;
+            }
+            Ok(())
+        }
+        SchemaKind::Slice(elem) => {
+            for de in slice_elements(schema, *elem, de)? {
+                walk(schema, *elem, de, region)?;
+            }
+            Ok(())
+        }
+        SchemaKind::Ref(formula) => {
+            let pointee = node(schema, *formula)?;
+            let de = de.deref_with(pointee.max_stack_size, pointee.exact_size)?;
+
+            let end = de.input().len();
+            let start = end - de.stack();
+            if start < region.floor || end > region.ceil {
+                return Err(DeserializeError::OverlappingReference);
+            }
+
+            let mut inner = Region {
+                floor: region.floor,
+                ceil: start,
+            };
+            walk(schema, *formula, de, &mut inner)?;
+            region.floor = end;
+            Ok(())
+        }
+        SchemaKind::Option(formula) => {
+            if de.read_flag()? {
+                walk_field(schema, *formula, &mut de, true, region)?;
+            }
+            Ok(())
+        }
+        SchemaKind::Tuple(elems) => {
+            let len = elems.len();
+            for (idx, elem) in elems.iter().enumerate() {
+                walk_field(schema, *elem, &mut de, idx + 1 == len, region)?;
+            }
+            Ok(())
+        }
+        SchemaKind::Struct(fields) => walk_fields(schema, fields, &mut de, region),
+        SchemaKind::Enum(variants) => {
+            let tag = de.read_value::<u32, u32>(false)?;
+            let Some(variant) = variants.iter().find(|variant| variant.tag == tag) else {
+                return Err(DeserializeError::WrongVariant(tag, ErrorDetails::new()));
+            };
+            walk_fields(schema, &variant.fields, &mut de, region)
+        }
+    }
+}
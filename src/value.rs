@@ -0,0 +1,481 @@
+//!
+//! This module provides dynamic values decoded with [`Schema`].
+//!
+
+use alloc::{boxed::Box, string::String, vec::Vec};
+
+use crate::{
+    bytes::Bytes,
+    deserialize::{read_reference_with, Deserialize, DeserializeError, Deserializer, ErrorDetails},
+    formula::reference_size_with,
+    schema::{Schema, SchemaField, SchemaKind, SchemaNode},
+    size::SIZE_STACK,
+    vlq::Vlq,
+};
+
+#[cfg(any(feature = "serde", feature = "protobuf"))]
+use crate::{
+    buffer::Buffer,
+    serialize::{
+        write_bytes, write_field, write_field_with, write_reference_with, Serialize, Sizes,
+    },
+};
+
+/// Value decoded without knowing its formula type.
+///
+/// Produced by [`deserialize_value`] and [`read_packet_value`]
+/// that follow the layout described by a [`Schema`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    /// Stack bytes of a formula that does not describe its structure.
+    Opaque(Vec<u8>),
+
+    /// Boolean value.
+    Bool(bool),
+
+    /// Unsigned integer or variable-length quantity.
+    Unsigned(u128),
+
+    /// Signed integer.
+    Signed(i128),
+
+    /// Floating point number.
+    Float(f64),
+
+    /// Raw bytes.
+    Bytes(Vec<u8>),
+
+    /// UTF-8 string.
+    Str(String),
+
+    /// Elements of an array or a slice.
+    Array(Vec<Value>),
+
+    /// Optional value.
+    Option(Option<Box<Value>>),
+
+    /// Elements of a tuple.
+    Tuple(Vec<Value>),
+
+    /// Named fields of a structure.
+    Struct(Vec<(String, Value)>),
+
+    /// Enum variant with its fields.
+    Enum {
+        /// Name of the variant.
+        name: String,
+
+        /// Tag of the variant.
+        tag: u32,
+
+        /// Named fields of the variant.
+        fields: Vec<(String, Value)>,
+    },
+}
+
+/// Deserializes value with layout described by the schema.
+/// The value must occupy the whole input slice.
+/// `stack` is the size of the value stack, as returned by [`serialize`](crate::serialize).
+///
+/// References are followed transparently and
+/// do not appear in the resulting [`Value`].
+///
+/// # Errors
+///
+/// Returns `DeserializeError` if deserialization fails.
+/// Returns `DeserializeError::Incompatible` if schema is malformed.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// let mut buffer = [0u8; 1024];
+/// let (size, root) = serialize::<(u8, Vec<u16>), _>((1u8, [2u16, 3]), &mut buffer).unwrap();
+///
+/// let schema = Schema::of::<(u8, Vec<u16>)>();
+/// let value = deserialize_value(&schema, &buffer[..size], root).unwrap();
+///
+/// assert_eq!(
+///     value,
+///     Value::Tuple(vec![
+///         Value::Unsigned(1),
+///         Value::Array(vec![Value::Unsigned(2), Value::Unsigned(3)]),
+///     ])
+/// );
+/// ```
+pub fn deserialize_value(
+    schema: &Schema,
+    input: &[u8],
+    stack: usize,
+) -> Result<Value, DeserializeError> {
+    let de = Deserializer::new(stack, input)?;
+    decode(schema, 0, de)
+}
+
+/// Reads packet with value which layout is described by the schema.
+/// Returns deserialized value and number of bytes consumed.
+///
+/// # Errors
+///
+/// Returns `DeserializeError` if deserialization fails.
+/// Returns `DeserializeError::Incompatible` if schema is malformed.
+pub fn read_packet_value(
+    schema: &Schema,
+    input: &[u8],
+) -> Result<(Value, usize), DeserializeError> {
+    let (de, address) = packet_deserializer(schema, input)?;
+    let value = decode(schema, 0, de)?;
+    Ok((value, address))
+}
+
+/// Returns deserializer for the root value of the packet
+/// and number of bytes the packet occupies.
+pub(crate) fn packet_deserializer<'de>(
+    schema: &Schema,
+    input: &'de [u8],
+) -> Result<(Deserializer<'de>, usize), DeserializeError> {
+    let root = node(schema, 0)?;
+    let reference_size = reference_size_with(root.exact_size);
+
+    if input.len() < reference_size {
+        return Err(DeserializeError::OutOfBounds(ErrorDetails::sizes(
+            reference_size,
+            input.len(),
+        )));
+    }
+
+    let (address, size) = read_reference_with(
+        input,
+        input.len() - reference_size,
+        root.max_stack_size,
+        root.exact_size,
+    )?;
+
+    if size > address {
+        return Err(DeserializeError::WrongAddress);
+    }
+
+    if address > input.len() {
+        return Err(DeserializeError::OutOfBounds(ErrorDetails::sizes(
+            address,
+            input.len(),
+        )));
+    }
+
+    Ok((
+        Deserializer::new_unchecked(size, &input[..address]),
+        address,
+    ))
+}
+
+pub(crate) fn node(schema: &Schema, idx: usize) -> Result<&SchemaNode, DeserializeError> {
+    schema.nodes.get(idx).ok_or(DeserializeError::Incompatible)
+}
+
+/// Reads field with formula `idx` from the deserializer and decodes it.
+fn decode_field(
+    schema: &Schema,
+    idx: usize,
+    de: &mut Deserializer,
+    last: bool,
+) -> Result<Value, DeserializeError> {
+    let field = node(schema, idx)?;
+    let sub = de.read_field_with(field.max_stack_size, field.exact_size, last)?;
+    decode(schema, idx, sub)
+}
+
+fn decode_fields(
+    schema: &Schema,
+    fields: &[SchemaField],
+    de: &mut Deserializer,
+) -> Result<Vec<(String, Value)>, DeserializeError> {
+    let len = fields.len();
+    fields
+        .iter()
+        .enumerate()
+        .map(|(idx, field)| {
+            let value = decode_field(schema, field.formula, de, idx + 1 == len)?;
+            Ok((field.name.clone(), value))
+        })
+        .collect()
+}
+
+/// Reads all elements of a slice with element formula `elem`.
+/// Returns deserializers of the elements.
+pub(crate) fn slice_elements<'de>(
+    schema: &Schema,
+    elem: usize,
+    mut de: Deserializer<'de>,
+) -> Result<Vec<Deserializer<'de>>, DeserializeError> {
+    let elem_node = node(schema, elem)?;
+    let read = |de: &mut Deserializer<'de>| {
+        de.read_field_with(elem_node.max_stack_size, elem_node.exact_size, false)
+    };
+
+    let mut elems = Vec::new();
+    match elem_node.max_stack_size {
+        Some(0) => {
+            // Count of zero-sized elements is not bounded by the input,
+            // limit it to keep memory use proportional to the input size.
+            let len = de.read_usize()?;
+            if len > de.input().len() {
+                return Err(DeserializeError::WrongLength(ErrorDetails::sizes(
+                    len,
+                    de.input().len(),
+                )));
+            }
+            for _ in 0..len {
+                elems.push(read(&mut de)?);
+            }
+        }
+        Some(max_stack) => {
+            while de.stack() >= max_stack {
+                elems.push(read(&mut de)?);
+            }
+        }
+        None => {
+            while de.stack() >= SIZE_STACK {
+                elems.push(read(&mut de)?);
+            }
+        }
+    }
+    Ok(elems)
+}
+
+pub(crate) fn decode(
+    schema: &Schema,
+    idx: usize,
+    mut de: Deserializer,
+) -> Result<Value, DeserializeError> {
+    let value = match &node(schema, idx)?.kind {
+        SchemaKind::Opaque => Value::Opaque(de.read_all_bytes().into()),
+        SchemaKind::Bool => Value::Bool(<bool as Deserialize<bool>>::deserialize(de)?),
+        SchemaKind::Unsigned(size) => Value::Unsigned(match size {
+            1 => <u128 as Deserialize<u8>>::deserialize(de)?,
+            2 => <u128 as Deserialize<u16>>::deserialize(de)?,
+            4 => <u128 as Deserialize<u32>>::deserialize(de)?,
+            8 => <u128 as Deserialize<u64>>::deserialize(de)?,
+            16 => <u128 as Deserialize<u128>>::deserialize(de)?,
+            _ => return Err(DeserializeError::Incompatible),
+        }),
+        SchemaKind::Signed(size) => Value::Signed(match size {
+            1 => <i128 as Deserialize<i8>>::deserialize(de)?,
+            2 => <i128 as Deserialize<i16>>::deserialize(de)?,
+            4 => <i128 as Deserialize<i32>>::deserialize(de)?,
+            8 => <i128 as Deserialize<i64>>::deserialize(de)?,
+            16 => <i128 as Deserialize<i128>>::deserialize(de)?,
+            _ => return Err(DeserializeError::Incompatible),
+        }),
+        SchemaKind::Float(size) => Value::Float(match size {
+            4 => <f64 as Deserialize<f32>>::deserialize(de)?,
+            8 => <f64 as Deserialize<f64>>::deserialize(de)?,
+            _ => return Err(DeserializeError::Incompatible),
+        }),
+        SchemaKind::Vlq => Value::Unsigned(<u128 as Deserialize<Vlq>>::deserialize(de)?),
+        SchemaKind::Bytes => Value::Bytes(<&[u8] as Deserialize<Bytes>>::deserialize(de)?.into()),
+        SchemaKind::Str => Value::Str(<&str as Deserialize<str>>::deserialize(de)?.into()),
+        SchemaKind::Array(elem, len) => {
+            let elems = (0..*len)
+                .map(|_| decode_field(schema, *elem, &mut de, false))
+                .collect::<Result<_, _>>()?;
+            Value::Array(elems)
+        }
+        SchemaKind::Slice(elem) => {
+            let elems = slice_elements(schema, *elem, de)?
+                .into_iter()
+                .map(|de| decode(schema, *elem, de))
+                .collect::<Result<_, _>>()?;
+            Value::Array(elems)
+        }
+        SchemaKind::Ref(formula) => {
+            let pointee = node(schema, *formula)?;
+            let de = de.deref_with(pointee.max_stack_size, pointee.exact_size)?;
+            return decode(schema, *formula, de);
+        }
+        SchemaKind::Option(formula) => {
+            if !de.read_flag()? {
+                Value::Option(None)
+            } else {
+                let value = decode_field(schema, *formula, &mut de, true)?;
+                Value::Option(Some(Box::new(value)))
+            }
+        }
+        SchemaKind::Tuple(elems) => {
+            let len = elems.len();
+            let elems = elems
+                .iter()
+                .enumerate()
+                .map(|(idx, elem)| decode_field(schema, *elem, &mut de, idx + 1 == len))
+                .collect::<Result<_, _>>()?;
+            Value::Tuple(elems)
+        }
+        SchemaKind::Struct(fields) => Value::Struct(decode_fields(schema, fields, &mut de)?),
+        SchemaKind::Enum(variants) => {
+            let tag = de.read_value::<u32, u32>(false)?;
+            let Some(variant) = variants.iter().find(|variant| variant.tag == tag) else {
+                return Err(DeserializeError::WrongVariant(tag, ErrorDetails::new()));
+            };
+            Value::Enum {
+                name: variant.name.clone(),
+                tag,
+                fields: decode_fields(schema, &variant.fields, &mut de)?,
+            }
+        }
+    };
+    Ok(value)
+}
+
+/// Writes value with layout described by the schema node `idx`.
+///
+/// # Panics
+///
+/// Panics if value does not match the schema node.
+#[cfg(any(feature = "serde", feature = "protobuf"))]
+pub(crate) fn write_value<B>(
+    schema: &Schema,
+    idx: usize,
+    value: &Value,
+    sizes: &mut Sizes,
+    mut buffer: B,
+) -> Result<(), B::Error>
+where
+    B: Buffer,
+{
+    match (&schema.nodes[idx].kind, value) {
+        (SchemaKind::Opaque, Value::Opaque(bytes)) => write_bytes(bytes, sizes, buffer),
+        (SchemaKind::Bool, Value::Bool(value)) => {
+            <bool as Serialize<bool>>::serialize(*value, sizes, buffer)
+        }
+        (SchemaKind::Unsigned(size), Value::Unsigned(value)) => {
+            write_bytes(&value.to_le_bytes()[..*size], sizes, buffer)
+        }
+        (SchemaKind::Signed(size), Value::Signed(value)) => {
+            write_bytes(&value.to_le_bytes()[..*size], sizes, buffer)
+        }
+        #[allow(clippy::cast_possible_truncation)]
+        (SchemaKind::Float(4), Value::Float(value)) => {
+            <f32 as Serialize<f32>>::serialize(*value as f32, sizes, buffer)
+        }
+        (SchemaKind::Float(8), Value::Float(value)) => {
+            <f64 as Serialize<f64>>::serialize(*value, sizes, buffer)
+        }
+        (SchemaKind::Vlq, Value::Unsigned(value)) => {
+            <u128 as Serialize<Vlq>>::serialize(*value, sizes, buffer)
+        }
+        (SchemaKind::Bytes, Value::Bytes(bytes)) => write_bytes(bytes, sizes, buffer),
+        (SchemaKind::Str, Value::Str(string)) => write_bytes(string.as_bytes(), sizes, buffer),
+        (SchemaKind::Array(elem, len), Value::Array(elems)) => {
+            assert_eq!(*len, elems.len(), "Value does not match schema");
+            elems.iter().try_for_each(|value| {
+                write_value_field(schema, *elem, value, sizes, buffer.reborrow(), false)
+            })
+        }
+        (SchemaKind::Slice(elem), Value::Array(elems)) => {
+            if schema.nodes[*elem].max_stack_size == Some(0) {
+                write_field::<usize, _, _>(elems.len(), sizes, buffer, true)
+            } else {
+                elems.iter().try_for_each(|value| {
+                    write_value_field(schema, *elem, value, sizes, buffer.reborrow(), false)
+                })
+            }
+        }
+        (SchemaKind::Ref(formula), value) => {
+            let exact_size = schema.nodes[*formula].exact_size;
+            let old_stack = sizes.stack;
+            write_value_field(schema, *formula, value, sizes, buffer.reborrow(), true)?;
+            let len = sizes.to_heap(old_stack);
+            buffer.move_to_heap(sizes.heap - len, sizes.stack + len, len);
+
+            write_reference_with(len, sizes.heap, sizes.heap, sizes.stack, exact_size, buffer)?;
+            sizes.stack += reference_size_with(exact_size);
+            Ok(())
+        }
+        (SchemaKind::Option(_), Value::Option(None)) => write_bytes(&[0], sizes, buffer),
+        (SchemaKind::Option(formula), Value::Option(Some(value))) => {
+            write_bytes(&[1], sizes, buffer.reborrow())?;
+            write_value_field(schema, *formula, value, sizes, buffer, true)
+        }
+        (SchemaKind::Tuple(elems), Value::Tuple(values)) => {
+            assert_eq!(elems.len(), values.len(), "Value does not match schema");
+            let len = elems.len();
+            elems
+                .iter()
+                .zip(values)
+                .enumerate()
+                .try_for_each(|(idx, (elem, value))| {
+                    write_value_field(
+                        schema,
+                        *elem,
+                        value,
+                        sizes,
+                        buffer.reborrow(),
+                        idx + 1 == len,
+                    )
+                })
+        }
+        (SchemaKind::Struct(fields), Value::Struct(values)) => {
+            write_value_fields(schema, fields, values, sizes, buffer)
+        }
+        (SchemaKind::Enum(variants), Value::Enum { tag, fields, .. }) => {
+            let Some(variant) = variants.iter().find(|variant| variant.tag == *tag) else {
+                panic!("Value does not match schema");
+            };
+            write_field::<u32, u32, _>(*tag, sizes, buffer.reborrow(), false)?;
+            write_value_fields(schema, &variant.fields, fields, sizes, buffer)
+        }
+        _ => panic!("Value does not match schema"),
+    }
+}
+
+/// Writes value as a field with layout described by the schema node `idx`.
+#[cfg(any(feature = "serde", feature = "protobuf"))]
+fn write_value_field<B>(
+    schema: &Schema,
+    idx: usize,
+    value: &Value,
+    sizes: &mut Sizes,
+    buffer: B,
+    last: bool,
+) -> Result<(), B::Error>
+where
+    B: Buffer,
+{
+    let field = &schema.nodes[idx];
+    write_field_with(
+        field.max_stack_size,
+        field.exact_size,
+        last,
+        sizes,
+        buffer,
+        |sizes, buffer| write_value(schema, idx, value, sizes, buffer),
+    )
+}
+
+#[cfg(any(feature = "serde", feature = "protobuf"))]
+fn write_value_fields<B>(
+    schema: &Schema,
+    fields: &[SchemaField],
+    values: &[(String, Value)],
+    sizes: &mut Sizes,
+    mut buffer: B,
+) -> Result<(), B::Error>
+where
+    B: Buffer,
+{
+    assert_eq!(fields.len(), values.len(), "Value does not match schema");
+    let len = fields.len();
+    fields
+        .iter()
+        .zip(values)
+        .enumerate()
+        .try_for_each(|(idx, (field, (_, value)))| {
+            write_value_field(
+                schema,
+                field.formula,
+                value,
+                sizes,
+                buffer.reborrow(),
+                idx + 1 == len,
+            )
+        })
+}
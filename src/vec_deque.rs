@@ -3,9 +3,12 @@ use alloc::collections::VecDeque;
 use crate::{
     buffer::Buffer,
     bytes::Bytes,
+    descriptor::{Descriptor, DescriptorKind, LazyDescriptor},
     deserialize::{Deserialize, DeserializeError, Deserializer},
     formula::{reference_size, Formula},
-    iter::{deserialize_extend_iter, owned_iter_fast_sizes, ref_iter_fast_sizes},
+    iter::{
+        cautious_capacity, deserialize_extend_iter, owned_iter_fast_sizes, ref_iter_fast_sizes,
+    },
     reference::Ref,
     serialize::{
         write_bytes, write_ref, write_reference, write_slice, Serialize, SerializeRef, Sizes,
@@ -19,6 +22,11 @@ where
     const MAX_STACK_SIZE: Option<usize> = <Ref<[F]> as Formula>::MAX_STACK_SIZE;
     const EXACT_SIZE: bool = <Ref<[F]> as Formula>::EXACT_SIZE;
     const HEAPLESS: bool = <Ref<[F]> as Formula>::HEAPLESS;
+
+    #[inline(always)]
+    fn describe() -> Descriptor {
+        Descriptor::new::<Self>(DescriptorKind::Ref(LazyDescriptor::of::<[F]>()))
+    }
 }
 
 impl<F, T> Serialize<VecDeque<F>> for T
@@ -111,7 +119,7 @@ where
     fn deserialize(de: Deserializer<'de>) -> Result<Self, DeserializeError> {
         let iter = de.into_unsized_iter();
         let (lower, _) = Iterator::size_hint(&iter);
-        let mut vec = VecDeque::with_capacity(lower);
+        let mut vec = VecDeque::with_capacity(cautious_capacity::<T>(lower));
         deserialize_extend_iter(&mut vec, iter)?;
         Ok(vec)
     }
@@ -121,7 +129,7 @@ where
         self.clear();
         let iter = de.into_unsized_iter();
         let (lower, _) = Iterator::size_hint(&iter);
-        self.reserve(lower);
+        self.reserve(cautious_capacity::<T>(lower));
         deserialize_extend_iter(self, iter)
     }
 }
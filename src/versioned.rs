@@ -0,0 +1,187 @@
+//!
+//! This module provides formula wrapper with version tag and migration of older payloads.
+//!
+
+use core::marker::PhantomData;
+
+use crate::{
+    buffer::Buffer,
+    deserialize::{Deserialize, DeserializeError, Deserializer, ErrorDetails},
+    formula::Formula,
+    private::VARIANT_SIZE,
+    serialize::{field_size_hint, write_field, Serialize, Sizes},
+};
+
+/// Formula wrapper that writes version `V` before payload of formula `F`.
+///
+/// Layout is the same as `(u32, F)`.
+/// Payload of version `V` is deserialized with `F`.
+/// Payload of an older version is passed to [`Migrate::migrate`],
+/// so persistent data written by previous releases can be upgraded.
+/// Payload of a newer version fails with `DeserializeError::WrongVariant`.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// // Version 1 stored only name, version 2 adds score.
+/// type SaveV1 = Versioned<Ref<str>, 1>;
+/// type SaveV2 = Versioned<(Ref<str>, u32), 2>;
+///
+/// #[derive(Debug, PartialEq)]
+/// struct Save<'a> {
+///     name: &'a str,
+///     score: u32,
+/// }
+///
+/// impl<'de> Deserialize<'de, (Ref<str>, u32)> for Save<'de> {
+///     fn deserialize(de: advanced::Deserializer<'de>) -> Result<Self, DeserializeError> {
+///         let (name, score) = <(&str, u32) as Deserialize<(Ref<str>, u32)>>::deserialize(de)?;
+///         Ok(Save { name, score })
+///     }
+///
+///     fn deserialize_in_place(&mut self, de: advanced::Deserializer<'de>) -> Result<(), DeserializeError> {
+///         *self = <Self as Deserialize<(Ref<str>, u32)>>::deserialize(de)?;
+///         Ok(())
+///     }
+/// }
+///
+/// impl<'de> Migrate<'de, (Ref<str>, u32)> for Save<'de> {
+///     fn migrate(old: OldVersion<'de>) -> Result<Self, DeserializeError> {
+///         match old.version() {
+///             1 => Ok(Save { name: old.decode::<Ref<str>, &str>()?, score: 0 }),
+///             _ => Err(old.unsupported()),
+///         }
+///     }
+/// }
+///
+/// let mut buffer = [0u8; 64];
+/// let size = write_packet::<SaveV1, _>("hero", &mut buffer).unwrap();
+/// let (save, _) = read_packet::<SaveV2, Save>(&buffer[..size]).unwrap();
+/// assert_eq!(save, Save { name: "hero", score: 0 });
+///
+/// let size = write_packet::<SaveV2, _>(("hero", 42u32), &mut buffer).unwrap();
+/// let (save, _) = read_packet::<SaveV2, Save>(&buffer[..size]).unwrap();
+/// assert_eq!(save, Save { name: "hero", score: 42 });
+/// ```
+pub struct Versioned<F: ?Sized, const V: u32> {
+    marker: PhantomData<fn(&F) -> &F>,
+}
+
+impl<F, const V: u32> Formula for Versioned<F, V>
+where
+    F: Formula + ?Sized,
+{
+    const MAX_STACK_SIZE: Option<usize> = <(u32, F) as Formula>::MAX_STACK_SIZE;
+    const EXACT_SIZE: bool = <(u32, F) as Formula>::EXACT_SIZE;
+    const HEAPLESS: bool = <(u32, F) as Formula>::HEAPLESS;
+}
+
+impl<F, T, const V: u32> Serialize<Versioned<F, V>> for T
+where
+    F: Formula + ?Sized,
+    T: Serialize<F>,
+{
+    #[inline(always)]
+    fn serialize<B>(self, sizes: &mut Sizes, mut buffer: B) -> Result<(), B::Error>
+    where
+        B: Buffer,
+    {
+        write_field::<u32, u32, _>(V, sizes, buffer.reborrow(), false)?;
+        write_field::<F, T, _>(self, sizes, buffer, true)
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> Option<Sizes> {
+        let mut sizes = field_size_hint::<F>(self, true)?;
+        sizes.add_stack(VARIANT_SIZE);
+        Some(sizes)
+    }
+}
+
+/// Trait for types that can be deserialized from payload
+/// written with older version of [`Versioned`] formula.
+///
+/// Implement with empty body for types that have no older versions.
+pub trait Migrate<'de, F: ?Sized>: Sized {
+    /// Deserializes value from payload of older version.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError` if payload cannot be migrated.
+    /// Default implementation rejects all older versions.
+    #[inline(always)]
+    fn migrate(old: OldVersion<'de>) -> Result<Self, DeserializeError> {
+        Err(old.unsupported())
+    }
+}
+
+/// Payload written with older version of [`Versioned`] formula.
+#[derive(Clone)]
+pub struct OldVersion<'de> {
+    version: u32,
+    de: Deserializer<'de>,
+}
+
+impl<'de> OldVersion<'de> {
+    /// Returns version of the payload.
+    #[must_use]
+    #[inline(always)]
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Decodes payload with formula `F` that was used by this version.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DeserializeError` if deserialization fails.
+    #[inline(always)]
+    pub fn decode<F, T>(mut self) -> Result<T, DeserializeError>
+    where
+        F: Formula + ?Sized,
+        T: Deserialize<'de, F>,
+    {
+        self.de.read_value::<F, T>(true)
+    }
+
+    /// Returns error for version that cannot be migrated.
+    #[must_use]
+    #[inline(always)]
+    pub fn unsupported(&self) -> DeserializeError {
+        DeserializeError::WrongVariant(self.version, ErrorDetails::new())
+    }
+}
+
+impl<'de, F, T, const V: u32> Deserialize<'de, Versioned<F, V>> for T
+where
+    F: Formula + ?Sized,
+    T: Deserialize<'de, F> + Migrate<'de, F>,
+{
+    #[inline]
+    fn deserialize(mut de: Deserializer<'de>) -> Result<Self, DeserializeError> {
+        let version = de.read_value::<u32, u32>(false)?;
+        if version == V {
+            de.read_value::<F, T>(true)
+        } else if version < V {
+            T::migrate(OldVersion { version, de })
+        } else {
+            Err(DeserializeError::WrongVariant(version, ErrorDetails::new())
+                .with_formula::<Versioned<F, V>>())
+        }
+    }
+
+    #[inline]
+    fn deserialize_in_place(&mut self, mut de: Deserializer<'de>) -> Result<(), DeserializeError> {
+        let version = de.read_value::<u32, u32>(false)?;
+        if version == V {
+            de.read_in_place::<F, T>(self, true)
+        } else if version < V {
+            *self = T::migrate(OldVersion { version, de })?;
+            Ok(())
+        } else {
+            Err(DeserializeError::WrongVariant(version, ErrorDetails::new())
+                .with_formula::<Versioned<F, V>>())
+        }
+    }
+}
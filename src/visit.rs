@@ -0,0 +1,127 @@
+//!
+//! This module provides callback-driven deserialization of sequences.
+//!
+
+use core::ops::ControlFlow;
+
+use crate::{
+    deserialize::{Deserialize, DeserializeError},
+    formula::Formula,
+    packet::open_packet,
+    sorted::{SortedMap, SortedSet},
+};
+
+/// Formula of a sequence whose elements can be visited one by one.
+///
+/// Implemented for slices, [`SortedMap`] with `(K, V)` entries
+/// and [`SortedSet`].
+pub trait SeqFormula: Formula {
+    /// Formula of the elements.
+    type Item: Formula;
+}
+
+impl<F> SeqFormula for [F]
+where
+    F: Formula,
+{
+    type Item = F;
+}
+
+impl<K, V> SeqFormula for SortedMap<K, V>
+where
+    K: Formula,
+    V: Formula,
+{
+    type Item = (K, V);
+}
+
+impl<F> SeqFormula for SortedSet<F>
+where
+    F: Formula,
+{
+    type Item = F;
+}
+
+/// Receiver of elements deserialized by [`visit_packet`].
+///
+/// Implemented for closures that accept all elements.
+/// Implement it manually to stop early.
+pub trait Sink<T> {
+    /// Accepts next element.
+    /// Returns `ControlFlow::Break` to skip remaining elements.
+    fn accept(&mut self, item: T) -> ControlFlow<()>;
+}
+
+impl<T, Fun> Sink<T> for Fun
+where
+    Fun: FnMut(T),
+{
+    #[inline(always)]
+    fn accept(&mut self, item: T) -> ControlFlow<()> {
+        self(item);
+        ControlFlow::Continue(())
+    }
+}
+
+/// Reads packet with sequence formula `F` from the input
+/// and passes elements to the closure one by one,
+/// without collecting them.
+/// Returns number of bytes consumed.
+///
+/// Maps are visited as `(key, value)` pairs.
+/// Order of [`SortedMap`] keys is not checked.
+/// Use [`visit_packet`] with a [`Sink`] to stop early.
+///
+/// # Errors
+///
+/// Returns `DeserializeError` if deserialization of any element fails.
+/// Elements before the failed one are already passed to the closure.
+///
+/// # Example
+///
+/// ```
+/// # use alkahest::*;
+/// let mut buffer = [0u8; 1024];
+/// let size = write_packet::<[u32], _>(1..=100u32, &mut buffer).unwrap();
+///
+/// let mut sum = 0;
+/// for_each::<[u32], u32>(&buffer[..size], |item| sum += item).unwrap();
+/// assert_eq!(sum, 5050);
+/// ```
+#[inline]
+pub fn for_each<'de, F, T>(
+    input: &'de [u8],
+    mut f: impl FnMut(T),
+) -> Result<usize, DeserializeError>
+where
+    F: SeqFormula + ?Sized,
+    T: Deserialize<'de, F::Item>,
+{
+    visit_packet::<F, T, _>(input, &mut f)
+}
+
+/// Reads packet with sequence formula `F` from the input
+/// and passes elements to the sink until it breaks.
+/// Returns number of bytes consumed.
+///
+/// # Errors
+///
+/// Returns `DeserializeError` if deserialization of any visited element fails.
+/// Elements before the failed one are already passed to the sink.
+#[inline]
+pub fn visit_packet<'de, F, T, S>(input: &'de [u8], sink: &mut S) -> Result<usize, DeserializeError>
+where
+    F: SeqFormula + ?Sized,
+    T: Deserialize<'de, F::Item>,
+    S: Sink<T> + ?Sized,
+{
+    let (de, address) = open_packet::<F>(input)?;
+
+    for item in de.into_unsized_iter::<F::Item, T>() {
+        let item = item.map_err(DeserializeError::with_formula::<F>)?;
+        if sink.accept(item).is_break() {
+            break;
+        }
+    }
+    Ok(address)
+}
@@ -1,5 +1,6 @@
 use crate::{
     buffer::Buffer,
+    descriptor::{Descriptor, DescriptorKind},
     deserialize::{Deserialize, DeserializeError, Deserializer},
     formula::Formula,
     serialize::{write_bytes, Serialize, Sizes},
@@ -60,6 +61,11 @@ impl Formula for Vlq {
     const MAX_STACK_SIZE: Option<usize> = None;
     const EXACT_SIZE: bool = false;
     const HEAPLESS: bool = true;
+
+    #[inline(always)]
+    fn describe() -> Descriptor {
+        Descriptor::new::<Vlq>(DescriptorKind::Vlq)
+    }
 }
 
 trait VlqType: Copy {
@@ -157,6 +163,29 @@ where
     }
 }
 
+macro_rules! impl_vlq_ref {
+    ($($a:ident)*) => {
+        $(
+            impl Serialize<Vlq> for &$a {
+                #[inline(always)]
+                fn size_hint(&self) -> Option<Sizes> {
+                    Some(size_hint(**self))
+                }
+
+                #[inline(always)]
+                fn serialize<B>(self, sizes: &mut Sizes, buffer: B) -> Result<(), B::Error>
+                where
+                    B: Buffer,
+                {
+                    serialize(*self, sizes, buffer)
+                }
+            }
+        )*
+    };
+}
+
+impl_vlq_ref!(u8 u16 u32 u64 u128 usize);
+
 impl<'de, T> Deserialize<'de, Vlq> for T
 where
     T: VlqType,
@@ -245,11 +274,8 @@ where
     let (tail, msb) = match header {
         0x00..=0x7F => (header >> 4, header & 0x0F),
         0x80..=0xBF => (header & 0x3F, 0),
-        0xC0..=0xFF => {
-            unimplemented!(
-                "Decoding for values that require more than 63 bytes is not implemented yet."
-            )
-        }
+        // Values that require more than 63 bytes do not fit any supported type.
+        0xC0..=0xFF => return Err(DeserializeError::IntegerOverflow),
     };
 
     let mut value = T::from_lsb(msb);
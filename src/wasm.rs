@@ -0,0 +1,76 @@
+//!
+//! This module provides packet reading and writing with `js_sys::Uint8Array`.
+//!
+
+use alloc::{format, vec::Vec};
+
+use js_sys::{wasm_bindgen::JsValue, Uint8Array};
+
+use crate::{
+    deserialize::{Deserialize, DeserializeError},
+    formula::Formula,
+    packet::{read_packet, write_packet_to_vec},
+    serialize::Serialize,
+};
+
+/// Writes packet with the value into new `Uint8Array`.
+///
+/// The packet is serialized into `scratch` first
+/// and then copied into JS memory at once.
+/// Reuse `scratch` between calls to avoid allocations.
+///
+/// # Panics
+///
+/// Panics if serialized data exceeds [`PACKET_SIZE_LIMIT`](crate::PACKET_SIZE_LIMIT).
+///
+/// # Example
+///
+/// ```no_run
+/// # use alkahest::*;
+/// let mut scratch = Vec::new();
+/// let array = write_packet_to_array::<(u32, [u8]), _>((1u32, [2u8, 3]), &mut scratch);
+///
+/// let (value, _) = read_packet_from_array::<(u32, [u8]), (u32, Vec<u8>)>(&array, &mut scratch).unwrap();
+/// assert_eq!(value, (1, vec![2, 3]));
+/// ```
+#[inline]
+pub fn write_packet_to_array<F, T>(value: T, scratch: &mut Vec<u8>) -> Uint8Array
+where
+    F: Formula + ?Sized,
+    T: Serialize<F>,
+{
+    scratch.clear();
+    let size = write_packet_to_vec::<F, T>(value, scratch);
+    Uint8Array::from(&scratch[..size])
+}
+
+/// Reads packet with value from the `Uint8Array`.
+/// Returns deserialized value and number of bytes consumed.
+///
+/// Content of the array is copied into `scratch`,
+/// so deserialized value may borrow from it.
+///
+/// # Errors
+///
+/// Returns `DeserializeError` if deserialization fails.
+#[inline]
+pub fn read_packet_from_array<'de, F, T>(
+    array: &Uint8Array,
+    scratch: &'de mut Vec<u8>,
+) -> Result<(T, usize), DeserializeError>
+where
+    F: Formula + ?Sized,
+    T: Deserialize<'de, F>,
+{
+    scratch.clear();
+    scratch.resize(array.length() as usize, 0);
+    array.copy_to(scratch);
+    read_packet::<F, T>(scratch)
+}
+
+impl From<DeserializeError> for JsValue {
+    #[inline]
+    fn from(err: DeserializeError) -> Self {
+        js_sys::Error::new(&format!("{err}")).into()
+    }
+}